@@ -0,0 +1 @@
+ALTER TABLE message ADD COLUMN forward_from_id INTEGER;
@@ -0,0 +1,16 @@
+CREATE TABLE chat_folder (
+  id        INTEGER PRIMARY KEY AUTOINCREMENT,
+  name      TEXT NOT NULL,
+  parent_id INTEGER REFERENCES chat_folder (id),
+  "order"   INTEGER NOT NULL
+) STRICT;
+
+CREATE TABLE chat_folder_chat (
+  folder_id INTEGER NOT NULL REFERENCES chat_folder (id),
+  ds_uuid   BLOB NOT NULL,
+  chat_id   INTEGER NOT NULL,
+  "order"   INTEGER NOT NULL,
+
+  PRIMARY KEY (folder_id, ds_uuid, chat_id),
+  FOREIGN KEY (ds_uuid, chat_id) REFERENCES chat (ds_uuid, id)
+) STRICT;
@@ -0,0 +1,2 @@
+ALTER TABLE chat ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE chat ADD COLUMN hidden INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+CREATE TABLE redaction_log (
+  id                      INTEGER PRIMARY KEY AUTOINCREMENT,
+  ds_uuid                 BLOB NOT NULL REFERENCES dataset (uuid),
+  time                    INTEGER NOT NULL,
+  -- Not a foreign key, chat might be deleted or renamed later
+  chat_id                 INTEGER,
+  deleted_messages_count  INTEGER NOT NULL,
+  scrubbed_messages_count INTEGER NOT NULL,
+  scrubbed_strings_count  INTEGER NOT NULL
+) STRICT;
@@ -0,0 +1,9 @@
+CREATE TABLE retention_rule (
+  id           INTEGER PRIMARY KEY AUTOINCREMENT,
+  ds_uuid      BLOB NOT NULL REFERENCES dataset (uuid),
+  chat_id      INTEGER,
+  action       TEXT NOT NULL,
+  max_age_days INTEGER NOT NULL,
+
+  FOREIGN KEY (ds_uuid, chat_id) REFERENCES chat (ds_uuid, id)
+) STRICT;
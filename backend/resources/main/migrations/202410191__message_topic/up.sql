@@ -0,0 +1,2 @@
+ALTER TABLE message ADD COLUMN topic_id INTEGER;
+ALTER TABLE message ADD COLUMN topic_title TEXT;
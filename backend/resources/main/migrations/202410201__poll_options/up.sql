@@ -0,0 +1,3 @@
+ALTER TABLE message_content ADD COLUMN poll_options TEXT;
+ALTER TABLE message_content ADD COLUMN poll_is_quiz INTEGER;
+ALTER TABLE message_content ADD COLUMN poll_total_voters INTEGER;
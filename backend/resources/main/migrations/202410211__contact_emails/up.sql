@@ -0,0 +1 @@
+ALTER TABLE message_content ADD COLUMN emails TEXT;
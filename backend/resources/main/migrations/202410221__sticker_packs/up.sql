@@ -0,0 +1,2 @@
+ALTER TABLE message_content ADD COLUMN sticker_pack_id TEXT;
+ALTER TABLE message_content ADD COLUMN sticker_pack_name TEXT;
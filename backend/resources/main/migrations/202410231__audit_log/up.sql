@@ -0,0 +1,12 @@
+-- Not referencing datasets or chats, entries must outlive them
+CREATE TABLE audit_log (
+  id                      INTEGER PRIMARY KEY AUTOINCREMENT,
+  time                    INTEGER NOT NULL,
+  client                  TEXT,
+  operation               TEXT NOT NULL,
+  ds_uuid                 BLOB,
+  chat_id                 INTEGER,
+  affected_chats_count    INTEGER NOT NULL,
+  affected_messages_count INTEGER NOT NULL,
+  parameters              TEXT NOT NULL
+) STRICT;
@@ -0,0 +1,11 @@
+-- Deleted datasets and chats, contents of each are kept in a separate database under the trash directory
+CREATE TABLE trash_item (
+  id             INTEGER PRIMARY KEY AUTOINCREMENT,
+  time           INTEGER NOT NULL,
+  -- Not a foreign key, dataset might be deleted
+  ds_uuid        BLOB NOT NULL,
+  chat_id        INTEGER,
+  name           TEXT NOT NULL,
+  chats_count    INTEGER NOT NULL,
+  messages_count INTEGER NOT NULL
+) STRICT;
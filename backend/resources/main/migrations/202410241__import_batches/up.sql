@@ -0,0 +1,15 @@
+-- Imported source file, batch UUID is preserved when its messages are copied or merged into another database
+CREATE TABLE import_batch (
+  ds_uuid        BLOB NOT NULL REFERENCES dataset (uuid),
+  uuid           BLOB NOT NULL,
+  source_path    TEXT NOT NULL,
+  source_hash    TEXT NOT NULL,
+  loader_name    TEXT NOT NULL,
+  loader_version TEXT NOT NULL,
+  time           INTEGER NOT NULL,
+
+  PRIMARY KEY (ds_uuid, uuid)
+) STRICT;
+
+-- Not a foreign key, messages of unknown provenance have it empty
+ALTER TABLE message ADD COLUMN import_batch_uuid BLOB;
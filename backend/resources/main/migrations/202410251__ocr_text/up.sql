@@ -0,0 +1 @@
+ALTER TABLE message_content ADD COLUMN ocr_text TEXT;
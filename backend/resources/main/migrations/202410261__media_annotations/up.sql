@@ -0,0 +1,9 @@
+-- Tags attached to media files by external tools, path is relative to dataset root
+CREATE TABLE media_annotation (
+  ds_uuid BLOB NOT NULL REFERENCES dataset (uuid),
+  path    TEXT NOT NULL,
+  tag     TEXT NOT NULL,
+  source  TEXT NOT NULL,
+
+  PRIMARY KEY (ds_uuid, path, source, tag)
+) STRICT;
@@ -0,0 +1,9 @@
+-- Source-specific user keys mapped to users, user ID follows user updates
+CREATE TABLE user_identity (
+  ds_uuid    BLOB NOT NULL REFERENCES dataset (uuid),
+  source     TEXT NOT NULL,
+  source_key TEXT NOT NULL,
+  user_id    INTEGER NOT NULL,
+
+  PRIMARY KEY (ds_uuid, source, source_key)
+) STRICT;
@@ -0,0 +1,11 @@
+-- User asserted to be the same person as a user of another dataset, possibly stored in another database
+CREATE TABLE user_alias (
+  ds_uuid       BLOB NOT NULL REFERENCES dataset (uuid),
+  user_id       INTEGER NOT NULL,
+  other_ds_uuid BLOB NOT NULL,
+  other_user_id INTEGER NOT NULL,
+
+  PRIMARY KEY (ds_uuid, user_id, other_ds_uuid, other_user_id)
+) STRICT;
+
+CREATE INDEX user_alias_other_idx ON user_alias (other_ds_uuid, other_user_id);
@@ -0,0 +1,10 @@
+-- Names chat had before it was renamed or merged with a differently named chat.
+-- Names from group title service messages aren't stored here.
+CREATE TABLE chat_former_name (
+  ds_uuid BLOB NOT NULL REFERENCES dataset (uuid),
+  chat_id INTEGER NOT NULL,
+  name    TEXT NOT NULL,
+
+  PRIMARY KEY (ds_uuid, chat_id, name),
+  FOREIGN KEY (ds_uuid, chat_id) REFERENCES chat (ds_uuid, id)
+) STRICT;
@@ -0,0 +1,15 @@
+-- User's own notes on chats and messages, kept apart from imported content.
+-- Message is referenced by its source ID if it has one, by internal ID otherwise.
+CREATE TABLE note (
+  id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+  ds_uuid             BLOB NOT NULL REFERENCES dataset (uuid),
+  chat_id             INTEGER NOT NULL,
+  message_source_id   INTEGER,
+  message_internal_id INTEGER,
+  text                TEXT NOT NULL,
+  time                INTEGER NOT NULL,
+
+  FOREIGN KEY (ds_uuid, chat_id) REFERENCES chat (ds_uuid, id)
+) STRICT;
+
+CREATE INDEX note_chat_idx ON note (ds_uuid, chat_id);
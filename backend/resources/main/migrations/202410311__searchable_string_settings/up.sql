@@ -0,0 +1,7 @@
+-- What message searchable strings are made of. Datasets without a row use defaults.
+CREATE TABLE searchable_string_settings (
+  ds_uuid             BLOB NOT NULL PRIMARY KEY REFERENCES dataset (uuid),
+  include_file_names  INTEGER NOT NULL,
+  include_coordinates INTEGER NOT NULL,
+  phone_number_format TEXT NOT NULL
+) STRICT;
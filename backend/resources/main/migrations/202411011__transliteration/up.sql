@@ -0,0 +1 @@
+ALTER TABLE searchable_string_settings ADD COLUMN transliterate INTEGER NOT NULL DEFAULT 0;
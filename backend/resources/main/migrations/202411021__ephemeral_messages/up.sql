@@ -0,0 +1,2 @@
+-- Set for messages sent in disappearing mode
+ALTER TABLE message ADD COLUMN ephemeral_duration_sec INTEGER;
@@ -0,0 +1,13 @@
+-- How messages of blocked users are treated. Datasets without a row import everything.
+CREATE TABLE blocked_users_policy (
+  ds_uuid BLOB NOT NULL PRIMARY KEY REFERENCES dataset (uuid),
+  action  TEXT NOT NULL
+) STRICT;
+
+-- Not a foreign key, user might be absent from dataset (yet)
+CREATE TABLE blocked_user (
+  ds_uuid BLOB NOT NULL REFERENCES dataset (uuid),
+  user_id INTEGER NOT NULL,
+
+  PRIMARY KEY (ds_uuid, user_id)
+) STRICT;
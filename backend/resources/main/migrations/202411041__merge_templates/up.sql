@@ -0,0 +1,20 @@
+-- Decisions of the merge that produced a dataset, one per type of the merged source
+CREATE TABLE merge_template (
+  ds_uuid     BLOB NOT NULL REFERENCES dataset (uuid),
+  source_type TEXT NOT NULL,
+  time        INTEGER NOT NULL,
+
+  PRIMARY KEY (ds_uuid, source_type)
+) STRICT;
+
+-- Not foreign keys, users and chats might be absent from dataset (e.g. ones that weren't added)
+CREATE TABLE merge_template_decision (
+  ds_uuid     BLOB NOT NULL REFERENCES dataset (uuid),
+  source_type TEXT NOT NULL,
+  -- 'user' or 'chat'
+  entity_type TEXT NOT NULL,
+  entity_id   INTEGER NOT NULL,
+  decision    TEXT NOT NULL,
+
+  PRIMARY KEY (ds_uuid, source_type, entity_type, entity_id)
+) STRICT;
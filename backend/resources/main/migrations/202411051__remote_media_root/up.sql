@@ -0,0 +1,5 @@
+-- HTTP(S) base URL dataset media is fetched from instead of a local dataset root, if set.
+CREATE TABLE remote_media_root (
+  ds_uuid  BLOB NOT NULL PRIMARY KEY REFERENCES dataset (uuid),
+  base_url TEXT NOT NULL
+) STRICT;
@@ -0,0 +1,5 @@
+-- Mentioned user (if known) and their username (if known), for mention elements
+ALTER TABLE message_text_element ADD COLUMN user_id INTEGER;
+ALTER TABLE message_text_element ADD COLUMN handle TEXT;
+
+CREATE INDEX message_text_element_user_idx ON message_text_element(user_id) WHERE user_id IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Source-specific emoji ID (if known) and emoji image path (if downloaded), for custom emoji elements
+ALTER TABLE message_text_element ADD COLUMN emoji_id TEXT;
+ALTER TABLE message_text_element ADD COLUMN path TEXT;
@@ -0,0 +1,2 @@
+-- Source ID of the quoted message (if known), for blockquote elements
+ALTER TABLE message_text_element ADD COLUMN quoted_source_id INTEGER;
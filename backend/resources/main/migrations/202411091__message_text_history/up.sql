@@ -0,0 +1,4 @@
+-- Elements of former text versions (replaced by edits) are stored alongside the current ones.
+-- Version is numbered from 0 (oldest) and is NULL for the current text, time is when the version was written.
+ALTER TABLE message_text_element ADD COLUMN text_version INTEGER;
+ALTER TABLE message_text_element ADD COLUMN text_version_time INTEGER;
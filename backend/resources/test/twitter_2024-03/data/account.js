@@ -0,0 +1,12 @@
+window.YTD.account.part0 = [
+  {
+    "account" : {
+      "email" : "me@example.com",
+      "createdVia" : "web",
+      "username" : "my_username",
+      "accountId" : "1000",
+      "createdAt" : "2010-01-01T00:00:00.000Z",
+      "accountDisplayName" : "Me Myself"
+    }
+  }
+]
@@ -0,0 +1,41 @@
+window.YTD.direct_messages_group.part0 = [
+  {
+    "dmConversation" : {
+      "conversationId" : "9000",
+      "messages" : [
+        {
+          "participantsLeave" : {
+            "userIds" : [ "3000" ],
+            "createdAt" : "2024-03-03T10:03:00.000Z"
+          }
+        },
+        {
+          "conversationNameUpdate" : {
+            "initiatingUserId" : "1000",
+            "name" : "Best group",
+            "createdAt" : "2024-03-03T10:02:00.000Z"
+          }
+        },
+        {
+          "messageCreate" : {
+            "reactions" : [ ],
+            "urls" : [ ],
+            "text" : "Hey all",
+            "mediaUrls" : [ ],
+            "senderId" : "3000",
+            "id" : "2001",
+            "createdAt" : "2024-03-03T10:01:00.000Z",
+            "editHistory" : [ ]
+          }
+        },
+        {
+          "joinConversation" : {
+            "initiatingUserId" : "2000",
+            "participantsSnapshot" : [ "1000", "2000", "3000" ],
+            "createdAt" : "2024-03-03T10:00:00.000Z"
+          }
+        }
+      ]
+    }
+  }
+]
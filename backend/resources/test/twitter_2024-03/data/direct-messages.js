@@ -0,0 +1,84 @@
+window.YTD.direct_messages.part0 = [
+  {
+    "dmConversation" : {
+      "conversationId" : "1000-2000",
+      "messages" : [
+        {
+          "messageCreate" : {
+            "recipientId" : "1000",
+            "reactions" : [ ],
+            "urls" : [
+              {
+                "url" : "https://t.co/link1",
+                "expanded" : "https://example.com/page",
+                "display" : "example.com/page"
+              }
+            ],
+            "text" : "Check this out https://t.co/link1",
+            "mediaUrls" : [ ],
+            "senderId" : "2000",
+            "id" : "1003",
+            "createdAt" : "2024-03-01T12:02:00.000Z",
+            "editHistory" : [ ]
+          }
+        },
+        {
+          "messageCreate" : {
+            "recipientId" : "2000",
+            "reactions" : [ ],
+            "urls" : [
+              {
+                "url" : "https://t.co/media1",
+                "expanded" : "https://twitter.com/messages/media/1002",
+                "display" : "pic.twitter.com/media1"
+              }
+            ],
+            "text" : "Look at this photo https://t.co/media1",
+            "mediaUrls" : [
+              "https://ton.twitter.com/dm/1002/1002/abcd.jpg"
+            ],
+            "senderId" : "1000",
+            "id" : "1002",
+            "createdAt" : "2024-03-01T12:01:00.000Z",
+            "editHistory" : [ ]
+          }
+        },
+        {
+          "messageCreate" : {
+            "recipientId" : "2000",
+            "reactions" : [ ],
+            "urls" : [ ],
+            "text" : "Hi there!\nHow are you?",
+            "mediaUrls" : [ ],
+            "senderId" : "1000",
+            "id" : "1001",
+            "createdAt" : "2024-03-01T12:00:00.000Z",
+            "editHistory" : [ ]
+          }
+        }
+      ]
+    }
+  },
+  {
+    "dmConversation" : {
+      "conversationId" : "1000-4000",
+      "messages" : [
+        {
+          "messageCreate" : {
+            "recipientId" : "1000",
+            "reactions" : [ ],
+            "urls" : [ ],
+            "text" : "Hello from a stranger",
+            "mediaUrls" : [
+              "https://video.twimg.com/dm_video/1101/vid/720x1280/efgh.mp4?tag=1"
+            ],
+            "senderId" : "4000",
+            "id" : "1101",
+            "createdAt" : "2024-03-02T08:00:00.000Z",
+            "editHistory" : [ ]
+          }
+        }
+      ]
+    }
+  }
+]
@@ -0,0 +1,30 @@
+window.YTD.tweets.part0 = [
+  {
+    "tweet" : {
+      "id_str" : "5000",
+      "full_text" : "@friend_username @other_username Hello!",
+      "created_at" : "Fri Mar 01 12:00:00 +0000 2024",
+      "entities" : {
+        "hashtags" : [ ],
+        "symbols" : [ ],
+        "user_mentions" : [
+          {
+            "name" : "Friend Name",
+            "screen_name" : "friend_username",
+            "indices" : [ "0", "16" ],
+            "id_str" : "2000",
+            "id" : "2000"
+          },
+          {
+            "name" : "Other Name",
+            "screen_name" : "other_username",
+            "indices" : [ "17", "32" ],
+            "id_str" : "3000",
+            "id" : "3000"
+          }
+        ],
+        "urls" : [ ]
+      }
+    }
+  }
+]
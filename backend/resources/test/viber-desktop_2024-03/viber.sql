@@ -0,0 +1,110 @@
+--
+-- Schema
+--
+
+CREATE TABLE Contact
+(
+    ContactID    INTEGER PRIMARY KEY AUTOINCREMENT,
+    Name         TEXT,
+    ClientName   TEXT,
+    Number       TEXT,
+    MID          TEXT,
+    VID          TEXT,
+    ViberContact INTEGER DEFAULT 0
+);
+
+CREATE TABLE ChatInfo
+(
+    ChatID    INTEGER PRIMARY KEY AUTOINCREMENT,
+    Token     INTEGER,
+    Name      TEXT,
+    Flags     INTEGER DEFAULT 0,
+    TimeStamp INTEGER
+);
+
+CREATE TABLE ChatRelation
+(
+    ChatID    INTEGER NOT NULL,
+    ContactID INTEGER NOT NULL,
+    PGRole    INTEGER DEFAULT 0
+);
+
+CREATE TABLE Events
+(
+    EventID   INTEGER PRIMARY KEY AUTOINCREMENT,
+    TimeStamp INTEGER NOT NULL,
+    Direction INTEGER NOT NULL,
+    Type      INTEGER NOT NULL,
+    ContactID INTEGER,
+    ChatID    INTEGER,
+    Token     INTEGER,
+    Seq       INTEGER,
+    Flags     INTEGER DEFAULT 0
+);
+
+CREATE TABLE Messages
+(
+    EventID       INTEGER PRIMARY KEY,
+    Type          INTEGER NOT NULL,
+    Status        INTEGER,
+    Subject       TEXT,
+    Body          TEXT,
+    Flag          INTEGER DEFAULT 0,
+    PayloadPath   TEXT,
+    ThumbnailPath TEXT,
+    StickerID     INTEGER,
+    Duration      INTEGER,
+    Info          TEXT
+);
+
+CREATE TABLE Calls
+(
+    EventID  INTEGER PRIMARY KEY,
+    Type     INTEGER,
+    Duration INTEGER,
+    CallID   INTEGER
+);
+
+--
+-- Data
+--
+
+INSERT INTO Contact (ContactID, Name, ClientName, Number, MID, VID, ViberContact)
+VALUES (1, 'Alice Smith', 'Alice', '+79990001111', 'mid-alice', 'vid-alice', 1),
+       (2, NULL, 'Bob', '+79990002222', 'mid-bob', 'vid-bob', 1),
+       (3, 'Me Myself', NULL, '+79991112233', 'mid-me', 'vid-me', 1);
+
+-- Chat #12 has no events and is skipped
+INSERT INTO ChatInfo (ChatID, Token, Name, Flags, TimeStamp)
+VALUES (10, 5001, NULL, 0, 1709280240000),
+       (11, 5002, 'Weekend plans', 1, 1709370120000),
+       (12, 5003, NULL, 0, 1709200000000);
+
+INSERT INTO ChatRelation (ChatID, ContactID, PGRole)
+VALUES (10, 1, 0),
+       (11, 1, 0),
+       (11, 2, 0),
+       (11, 3, 2),
+       (12, 2, 0);
+
+INSERT INTO Events (EventID, TimeStamp, Direction, Type, ContactID, ChatID, Token, Seq)
+VALUES (100, 1709280000000, 0, 1, 1, 10, 7001, 1),
+       (101, 1709280060000, 1, 1, 1, 10, 7002, 2),
+       (102, 1709280120000, 0, 1, 1, 10, 7003, 3),
+       (103, 1709280180000, 0, 4, 1, 10, 7004, 4),
+       (104, 1709280240000, 1, 4, 1, 10, 7005, 5),
+       (200, 1709370000000, 1, 1, 1, 11, 7006, 1),
+       (201, 1709370060000, 0, 1, 2, 11, 7007, 2),
+       (202, 1709370120000, 0, 1, 1, 11, 7008, 3);
+
+INSERT INTO Messages (EventID, Type, Status, Subject, Body, Flag, PayloadPath, ThumbnailPath, StickerID, Duration, Info)
+VALUES (100, 1, 2, NULL, 'Hi there!', 0, NULL, NULL, NULL, NULL, NULL),
+       (101, 1, 2, NULL, 'Hello!', 0, NULL, NULL, NULL, NULL, NULL),
+       (102, 4, 2, NULL, NULL, 0, NULL, NULL, 123, NULL, NULL),
+       (200, 2, 2, NULL, 'Look at this', 0, 'C:\Users\me\Documents\ViberDownloads\0-1-photo.jpg', NULL, NULL, NULL, NULL),
+       (201, 10, 2, NULL, NULL, 0, '/Users/bob/Documents/ViberDownloads/notes.pdf', NULL, NULL, NULL, NULL),
+       (202, 3, 2, NULL, NULL, 0, NULL, NULL, NULL, 12, NULL);
+
+INSERT INTO Calls (EventID, Type, Duration, CallID)
+VALUES (103, 1, 65, 9001),
+       (104, 1, 0, 9002);
@@ -5,16 +5,21 @@ CREATE TABLE call_log (    _id                              INTEGER PRIMARY KEY
 CREATE TABLE chat (_id INTEGER PRIMARY KEY AUTOINCREMENT,jid_row_id INTEGER UNIQUE,hidden INTEGER,subject TEXT,created_timestamp INTEGER,display_message_row_id INTEGER,last_message_row_id INTEGER,last_read_message_row_id INTEGER,last_read_receipt_sent_message_row_id INTEGER,last_important_message_row_id INTEGER,archived INTEGER,sort_timestamp INTEGER,mod_tag INTEGER,gen REAL,spam_detection INTEGER,unseen_earliest_message_received_time INTEGER,unseen_message_count INTEGER,unseen_missed_calls_count INTEGER,unseen_row_count INTEGER,plaintext_disabled INTEGER,vcard_ui_dismissed INTEGER,change_number_notified_message_row_id INTEGER,show_group_description INTEGER,ephemeral_expiration INTEGER,last_read_ephemeral_message_row_id INTEGER,ephemeral_setting_timestamp INTEGER, unseen_important_message_count INTEGER NOT NULL DEFAULT 0, ephemeral_disappearing_messages_initiator INTEGER, group_type INTEGER NOT NULL DEFAULT 0, last_message_reaction_row_id INTEGER, last_seen_message_reaction_row_id INTEGER, unseen_message_reaction_count INTEGER, growth_lock_level INTEGER, growth_lock_expiration_ts INTEGER, last_read_message_sort_id INTEGER, display_message_sort_id INTEGER, last_message_sort_id INTEGER, last_read_receipt_sent_message_sort_id INTEGER, has_new_community_admin_dialog_been_acknowledged INTEGER NOT NULL DEFAULT 0, history_sync_progress INTEGER, ephemeral_displayed_exemptions INTEGER, chat_lock INTEGER);
 CREATE TABLE jid (_id INTEGER PRIMARY KEY AUTOINCREMENT, user TEXT NOT NULL, server TEXT NOT NULL, agent INTEGER, device INTEGER, type INTEGER, raw_string TEXT);
 CREATE TABLE message (_id INTEGER PRIMARY KEY AUTOINCREMENT, chat_row_id INTEGER NOT NULL, from_me INTEGER NOT NULL, key_id TEXT NOT NULL, sender_jid_row_id INTEGER, status INTEGER, broadcast INTEGER, recipient_count INTEGER, participant_hash TEXT, origination_flags INTEGER, origin INTEGER, timestamp INTEGER, received_timestamp INTEGER, receipt_server_timestamp INTEGER, message_type INTEGER, text_data TEXT, starred INTEGER, lookup_tables INTEGER, sort_id INTEGER NOT NULL DEFAULT 0 , message_add_on_flags INTEGER, view_mode INTEGER);
+CREATE TABLE message_add_on (_id INTEGER PRIMARY KEY AUTOINCREMENT, chat_row_id INTEGER NOT NULL, from_me INTEGER NOT NULL, key_id TEXT NOT NULL, sender_jid_row_id INTEGER, parent_message_row_id INTEGER NOT NULL, timestamp INTEGER, status INTEGER, message_add_on_type INTEGER);
+CREATE TABLE message_add_on_poll_vote_selected_option (message_add_on_row_id INTEGER NOT NULL, message_poll_option_id INTEGER NOT NULL);
 CREATE TABLE message_edit_info (message_row_id INTEGER PRIMARY KEY, original_key_id TEXT NOT NULL, edited_timestamp INTEGER NOT NULL, sender_timestamp INTEGER NOT NULL);
 CREATE TABLE message_forwarded(message_row_id INTEGER PRIMARY KEY, forward_score INTEGER);
 CREATE TABLE message_location (message_row_id INTEGER PRIMARY KEY, chat_row_id INTEGER, latitude REAL, longitude REAL, place_name TEXT, place_address TEXT, url TEXT, live_location_share_duration INTEGER, live_location_sequence_number INTEGER, live_location_final_latitude REAL, live_location_final_longitude REAL, live_location_final_timestamp INTEGER, map_download_status INTEGER);
 CREATE TABLE message_media (  message_row_id INTEGER PRIMARY KEY, chat_row_id INTEGER, autotransfer_retry_enabled INTEGER, multicast_id TEXT, media_job_uuid TEXT, transferred INTEGER, transcoded INTEGER, file_path TEXT, file_size INTEGER, suspicious_content INTEGER, trim_from INTEGER, trim_to INTEGER, face_x INTEGER, face_y INTEGER, media_key BLOB, media_key_timestamp INTEGER, width INTEGER, height INTEGER, has_streaming_sidecar INTEGER, gif_attribution INTEGER, thumbnail_height_width_ratio REAL, direct_path TEXT, first_scan_sidecar BLOB, first_scan_length INTEGER, message_url TEXT, mime_type TEXT, file_length INTEGER, media_name TEXT, file_hash TEXT, media_duration INTEGER, page_count INTEGER, enc_file_hash TEXT, partial_media_hash TEXT, partial_media_enc_hash TEXT, is_animated_sticker INTEGER, original_file_hash TEXT, mute_video INTEGER DEFAULT 0, media_caption TEXT, media_upload_handle TEXT);
+CREATE TABLE message_poll_option (_id INTEGER PRIMARY KEY AUTOINCREMENT, message_row_id INTEGER NOT NULL, option_name TEXT NOT NULL, option_hash TEXT, vote_total INTEGER NOT NULL DEFAULT 0);
 CREATE TABLE message_quoted (    message_row_id             INTEGER PRIMARY KEY AUTOINCREMENT,    chat_row_id                INTEGER NOT NULL,    parent_message_chat_row_id INTEGER NOT NULL,    from_me                    INTEGER NOT NULL,    sender_jid_row_id          INTEGER,    key_id                     TEXT    NOT NULL,    timestamp                  INTEGER,    message_type               INTEGER,    origin                     INTEGER,    text_data                  TEXT,    payment_transaction_id     TEXT,    lookup_tables              INTEGER);
 CREATE TABLE message_revoked (message_row_id INTEGER PRIMARY KEY, revoked_key_id TEXT NOT NULL, admin_jid_row_id INTEGER, revoke_timestamp INTEGER);
 CREATE TABLE message_system (message_row_id INTEGER PRIMARY KEY, action_type INTEGER NOT NULL);
 CREATE TABLE message_system_block_contact (message_row_id INTEGER PRIMARY KEY, is_blocked INTEGER);
 CREATE TABLE message_system_chat_participant (message_row_id INTEGER, user_jid_row_id INTEGER);
 CREATE TABLE message_system_group (message_row_id INTEGER PRIMARY KEY, is_me_joined INTEGER);
+CREATE TABLE message_system_photo_change (message_row_id INTEGER PRIMARY KEY, new_photo_id TEXT, old_photo BLOB, new_photo BLOB);
+CREATE TABLE message_system_value_change (message_row_id INTEGER PRIMARY KEY, old_data TEXT);
 CREATE TABLE message_system_number_change (message_row_id INTEGER PRIMARY KEY, old_jid_row_id INTEGER, new_jid_row_id INTEGER);
 CREATE TABLE message_vcard (_id  INTEGER PRIMARY KEY AUTOINCREMENT, message_row_id INTEGER, vcard TEXT);
 CREATE TABLE props (_id INTEGER PRIMARY KEY AUTOINCREMENT, key TEXT UNIQUE, value TEXT);
@@ -42,6 +47,23 @@ INSERT INTO message_system VALUES(169,12);
 INSERT INTO message_system_chat_participant VALUES(169,264);
 INSERT INTO message_system_group VALUES(169,1);
 
+-- Group subject change by user 1 (#msg = 400)
+INSERT INTO message VALUES(400,19,0,'GROUPMSG00400',252,6,0,5,NULL,0,0,1650000000000,1650000000500,-1,7,'My Group',0,0,400,0,NULL);
+INSERT INTO message_system VALUES(400,1);
+INSERT INTO message_system_value_change VALUES(400,'My Old Group');
+
+-- Group photo change by user 1 (#msg = 500)
+INSERT INTO message VALUES(500,19,0,'GROUPMSG00500',252,6,0,5,NULL,0,0,1655000000000,1655000000500,-1,7,'1655000000',0,0,500,0,NULL);
+INSERT INTO message_system VALUES(500,6);
+INSERT INTO message_system_photo_change VALUES(500,'1655000000',NULL,X'FFD8FFE000104A46494600010100000100010000FFD9');
+
+-- Poll by user 1 (#msg = 600), myself voted for the second option
+INSERT INTO message VALUES(600,19,0,'GROUPMSG00600',252,6,0,5,NULL,0,0,1658000000000,1658000000500,-1,46,'Lunch?',0,0,600,0,NULL);
+INSERT INTO message_poll_option VALUES(1,600,'Pizza','HASH1',1);
+INSERT INTO message_poll_option VALUES(2,600,'Sushi','HASH2',2);
+INSERT INTO message_add_on VALUES(1,19,1,'GROUPADDON00601',0,600,1658000001000,0,67);
+INSERT INTO message_add_on_poll_vote_selected_option VALUES(1,2);
+
 -- Last group message (#msg = 750), reply to first (system) message, edited and forwarded (probably not possible in real data)
 INSERT INTO message VALUES(750,19,1,'GROUPMSG99999',0,0,0,4,NULL,0,0,1661417508000,1661417509709,-1,0,'Last group message',0,0,750,0,NULL);
 INSERT INTO message_edit_info VALUES(750,'GROUPMSG99999OLD',1661417955000,1661417999999);
@@ -54,7 +76,7 @@ INSERT INTO chat VALUES(148,252,0,NULL,1687705763841,7747,7747,7756,7756,1,1,169
 
 -- Sharing location (#msg = 4863)
 INSERT INTO message VALUES(4863,148,0,'PERSONALMSG100100',0,0,0,0,NULL,0,0,1687757170000,1687757170352,-1,16,NULL,0,0,4863,0,NULL);
-INSERT INTO message_location VALUES(4863,148,-8.7038565050269092182,115.21673666751774955,'New Bahari','Jl. Gurita No.21x, Denpasar, Bali','https://foursquare.com/v/51e14cff498e834f4f815e43',123,NULL,NULL,NULL,NULL,2);
+INSERT INTO message_location VALUES(4863,148,-8.7038565050269092182,115.21673666751774955,'New Bahari','Jl. Gurita No.21x, Denpasar, Bali','https://foursquare.com/v/51e14cff498e834f4f815e43',123,3,-8.7041234500000001,115.21701234000000001,1687757290000,2);
 
 -- Deleted message
 INSERT INTO message VALUES(7454,148,1,'PERSONALMSG999900',0,5,0,0,NULL,0,0,1693993938000,1693995957435,-1,15,NULL,0,0,7454,0,NULL);
@@ -8,6 +8,23 @@ use crate::prelude::*;
 
 pub mod in_memory_dao;
 pub mod sqlite_dao;
+pub mod duplicates;
+pub mod fingerprint;
+pub mod membership;
+pub mod paging;
+pub mod search;
+pub mod permalink;
+pub mod gallery;
+pub mod language;
+pub mod message_kinds;
+pub mod storage_usage;
+pub mod remote_media;
+pub mod interactions;
+pub mod aliases;
+pub mod notes;
+
+/// Text that replaces redacted strings.
+pub const REDACTED_PLACEHOLDER: &str = "[REDACTED]";
 
 pub trait WithCache {
     /// For internal use
@@ -51,10 +68,11 @@ pub trait WithCache {
 }
 
 /**
+ * Read-only half of a DAO, modifications are done through [ChatHistoryWriter].
  * Everything except for messages should be pre-cached and readily available.
  * Should support equality and be thread-safe.
  */
-pub trait ChatHistoryDao: WithCache + Send + Sync {
+pub trait ChatHistoryReader: WithCache + Send + Sync {
     /** User-friendly name of a loaded data */
     fn name(&self) -> &str;
 
@@ -99,8 +117,7 @@ pub trait ChatHistoryDao: WithCache + Send + Sync {
      */
     fn chats(&self, ds_uuid: &PbUuid) -> Result<Vec<ChatWithDetails>> {
         let mut chats = self.chats_inner(ds_uuid)?;
-        chats.sort_by_key(|cwd| // Minus used to reverse order
-            cwd.last_msg_option.as_ref().map(|m| -m.timestamp).unwrap_or(cwd.chat.id));
+        chats.sort_by_key(chat_order_key);
         Ok(chats)
     }
 
@@ -140,6 +157,20 @@ pub trait ChatHistoryDao: WithCache + Send + Sync {
 
     fn messages_after_impl(&self, chat: &Chat, msg_id: MessageInternalId, limit: usize) -> Result<Vec<Message>>;
 
+    /// Return up to `before` messages preceding the given one, the message itself, and up to `after` messages
+    /// following it. Message must be present.
+    fn messages_around(&self, chat: &Chat, msg_id: MessageInternalId, before: usize, after: usize) -> Result<Vec<Message>> {
+        let Some(msg) = self.message_option_by_internal_id(chat, msg_id)? else {
+            bail!("Message {} not found in chat {}!", *msg_id, chat.qualified_name());
+        };
+        let mut result = if before > 0 { self.messages_before(chat, msg_id, before)? } else { vec![] };
+        result.push(msg);
+        if after > 0 {
+            result.extend(self.messages_after(chat, msg_id, after)?);
+        }
+        Ok(result)
+    }
+
     /// Return N messages between the given ones (inclusive). Messages must be present.
     /// Note: this might need rework in future, as the returned slice is unbounded.
     fn messages_slice(&self, chat: &Chat, msg1_id: MessageInternalId, msg2_id: MessageInternalId) -> Result<Vec<Message>>;
@@ -173,19 +204,115 @@ pub trait ChatHistoryDao: WithCache + Send + Sync {
 
     fn message_option(&self, chat: &Chat, source_id: MessageSourceId) -> Result<Option<Message>>;
 
+    fn message_option_by_internal_id(&self, chat: &Chat, internal_id: MessageInternalId) -> Result<Option<Message>>;
+
+    /// Return up to N messages matching the filter, in chronological order.
+    /// Messages are taken after the anchor message (exclusive), or before it if `backward` is set.
+    /// Without an anchor, messages are taken from the start of the chat, or from the end if `backward` is set.
+    fn messages_filtered(&self,
+                         chat: &Chat,
+                         filter: &MessageFilter,
+                         anchor_option: Option<MessageInternalId>,
+                         backward: bool,
+                         limit: usize) -> Result<Vec<Message>>;
+
+    /// First message (in chat order) sent at or after the given timestamp, if any.
+    fn first_message_on_or_after(&self, chat: &Chat, ts: Timestamp) -> Result<Option<Message>>;
+
+    /// Number of messages sent on each day (in local timezone), days without messages are omitted, oldest first.
+    fn messages_calendar(&self, chat: &Chat) -> Result<Vec<CalendarDay>>;
+
+    /// Links from message texts, deduplicated by href, in order of first appearance.
+    fn chat_links(&self, chat: &Chat) -> Result<Vec<ChatLink>>;
+
+    /// Messages of the dataset with searchable string containing the given text, newest first
+    /// (messages sent at the same time are ordered by internal ID, descending).
+    /// Search is case-insensitive for latin letters only.
+    /// If sender IDs are given, only messages from these users are considered.
+    /// If a (timestamp, internal ID) pair is given, only messages ordered after it are returned, for keyset pagination.
+    fn search_messages(&self,
+                       ds_uuid: &PbUuid,
+                       text: &str,
+                       from_ids_option: Option<&[i64]>,
+                       after_option: Option<(Timestamp, MessageInternalId)>,
+                       limit: usize) -> Result<Vec<SearchHit>>;
+
     /** Whether given data path is the one loaded in this DAO */
     fn is_loaded(&self, storage_path: &Path) -> bool {
         self.storage_path() == storage_path
     }
 
-    /// Return self as mutable if applicable, otherwise error out
-    fn as_mutable(&mut self) -> Result<&mut dyn MutableChatHistoryDao>;
+    /// All chat folders of this DAO, parents ordered before their children, siblings ordered by `order`.
+    fn chat_folders(&self) -> Result<Vec<ChatFolder>>;
 
-    /// Return self as shiftable if applicable, otherwise error out
-    fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryDao>;
+    /// Redactions performed on the dataset, oldest first.
+    fn redaction_log(&self, ds_uuid: &PbUuid) -> Result<Vec<RedactionLogEntry>>;
+
+    /// Mutating operations performed on this DAO, oldest first.
+    fn audit_log(&self) -> Result<Vec<AuditLogEntry>>;
+
+    /// Deleted datasets and chats that can still be restored, oldest first.
+    fn trash(&self) -> Result<Vec<TrashItem>>;
+
+    /// Retention rules of the dataset, in order of insertion.
+    fn retention_rules(&self, ds_uuid: &PbUuid) -> Result<Vec<RetentionRule>>;
+
+    /// Imports messages of the dataset came from, oldest first.
+    fn import_batches(&self, ds_uuid: &PbUuid) -> Result<Vec<ImportBatch>>;
+
+    /// UUIDs of import batches given messages came from, in the same order, `None` if provenance is unknown.
+    fn message_import_batch_uuids(&self, chat: &Chat, internal_ids: &[MessageInternalId]) -> Result<Vec<Option<PbUuid>>>;
+
+    fn message_provenance(&self, chat: &Chat, internal_id: MessageInternalId) -> Result<Option<ImportBatch>> {
+        let Some(batch_uuid) = self.message_import_batch_uuids(chat, &[internal_id])?.remove(0) else { return Ok(None) };
+        Ok(self.import_batches(&chat.ds_uuid)?.into_iter().find(|b| b.uuid == batch_uuid))
+    }
+
+    /// Tags attached to media files of the dataset, ordered by path, source and tag.
+    fn media_annotations(&self, ds_uuid: &PbUuid) -> Result<Vec<MediaAnnotation>>;
+
+    /// Identity registry of the dataset - which user each source-specific user key maps to,
+    /// ordered by source and key.
+    fn user_identities(&self, ds_uuid: &PbUuid) -> Result<Vec<UserIdentity>>;
+
+    /// Cross-dataset user aliases stored here, i.e. ones whose first dataset belongs to this DAO,
+    /// ordered by dataset UUID and user ID.
+    fn user_aliases(&self) -> Result<Vec<UserAlias>>;
+
+    /// Known names of a chat, oldest first. Names without a timestamp (ones chat had before being renamed
+    /// or in a merged source) go before names taken from group creation and title edit service messages.
+    fn chat_name_history(&self, chat: &Chat) -> Result<Vec<ChatNameEntry>>;
+
+    /// Distinct names chat was known by other than the current one, oldest first.
+    fn former_chat_names(&self, chat: &Chat) -> Result<Vec<String>> {
+        Ok(self.chat_name_history(chat)?.into_iter()
+            .map(|entry| entry.name)
+            .filter(|name| chat.name_option.as_ref() != Some(name))
+            .unique()
+            .collect_vec())
+    }
+
+    /// User notes on chats of the dataset and their messages (or on a single chat if given),
+    /// ordered by chat ID and note ID.
+    fn notes(&self, ds_uuid: &PbUuid, chat_id_option: Option<i64>) -> Result<Vec<Note>>;
+
+    /// What searchable strings of dataset messages are made of, defaults if never configured.
+    fn searchable_string_settings(&self, ds_uuid: &PbUuid) -> Result<SearchableStringSettings>;
+
+    /// How messages of blocked users are treated, importing everything if never configured.
+    fn blocked_users_policy(&self, ds_uuid: &PbUuid) -> Result<BlockedUsersPolicy>;
+
+    /// HTTP(S) base URL dataset media is fetched from instead of [Self::dataset_root], if configured.
+    fn remote_media_root(&self, ds_uuid: &PbUuid) -> Result<Option<String>>;
+
+    /// Decisions of merges that produced the dataset (or its predecessors), one per slave source type,
+    /// ordered by source type.
+    fn merge_templates(&self, ds_uuid: &PbUuid) -> Result<Vec<MergeTemplate>>;
 }
 
-pub trait MutableChatHistoryDao: ChatHistoryDao {
+/// Modifying half of a DAO. Code that only needs to read should take a [ChatHistoryReader] instead.
+/// Histories parsed by loaders are read-only, and have to be saved into a database to be modified.
+pub trait ChatHistoryWriter: ChatHistoryReader {
     fn backup(&mut self) -> Result<JoinHandle<()>>;
 
     /// Inserts dataset as-is, with the UUID already set.
@@ -194,6 +321,7 @@ pub trait MutableChatHistoryDao: ChatHistoryDao {
     fn update_dataset(&mut self, old_uuid: PbUuid, ds: Dataset) -> Result<Dataset>;
 
     /// Delete a dataset with all the related entities. Deleted dataset root will be moved to backup folder.
+    /// If DAO supports trash, a restorable copy of the dataset is put there first.
     fn delete_dataset(&mut self, uuid: PbUuid) -> EmptyRes;
 
     /// Note that profile pictures are NOT inserted and are discarded instead!
@@ -213,23 +341,179 @@ pub trait MutableChatHistoryDao: ChatHistoryDao {
     fn update_chat(&mut self, old_id: ChatId, chat: Chat) -> Result<Chat>;
 
     /// Delete a chat, as well as orphan users. Deleted files will be moved to backup folder.
+    /// If DAO supports trash, a restorable copy of the chat (with its members) is put there first.
     fn delete_chat(&mut self, chat: Chat) -> EmptyRes;
 
     /// Set master chat as a main chat for slave, and reassigns slave's slaves to the new master.
     /// Both chats have to be main.
     fn combine_chats(&mut self, master_chat: Chat, slave_chat: Chat) -> EmptyRes;
 
+    /// Fix misidentified archive owner.
+    ///
+    /// For a single chat, authorship of messages is swapped between the current myself and the given chat member,
+    /// myself stays the same. For a whole dataset, the given user becomes myself instead, and is put first
+    /// (added if needed) among members of every chat. Chat names are not changed.
+    fn override_myself(&mut self, ds_uuid: &PbUuid, new_myself_id: UserId, chat_id_option: Option<ChatId>) -> EmptyRes;
+
+    /// Insert a new folder, its ID will be ignored and assigned automatically.
+    /// Parent folder (if any) must be a top-level folder, and all referenced chats must exist.
+    fn insert_chat_folder(&mut self, folder: ChatFolder) -> Result<ChatFolder>;
+
+    /// Update folder name, parent, order and chats. Same constraints as for insertion apply.
+    fn update_chat_folder(&mut self, folder: ChatFolder) -> Result<ChatFolder>;
+
+    /// Delete a folder along with its subfolders. Chats themselves are not affected.
+    fn delete_chat_folder(&mut self, id: i64) -> EmptyRes;
+
+    /// Permanently delete given messages of a chat along with their files, bypassing backups.
+    /// No traces of redacted data should remain in storage, existing backups are replaced with a fresh one.
+    /// Records an entry in the redaction log.
+    fn redact_messages(&mut self, chat: &Chat, internal_ids: &[MessageInternalId]) -> Result<RedactionLogEntry>;
+
+    /// Replace all occurrences of the given strings (case-sensitive) in message texts and contents with
+    /// [REDACTED_PLACEHOLDER], either across the whole dataset or within a single chat.
+    /// Searchable strings are updated accordingly. Data is purged the same way as in [Self::redact_messages].
+    /// Records an entry in the redaction log.
+    fn redact_strings(&mut self,
+                      ds_uuid: &PbUuid,
+                      chat_id_option: Option<ChatId>,
+                      strings: &[String]) -> Result<RedactionLogEntry>;
+
+    /// Insert a new retention rule, its ID will be ignored and assigned automatically.
+    fn insert_retention_rule(&mut self, rule: RetentionRule) -> Result<RetentionRule>;
+
+    fn delete_retention_rule(&mut self, id: i64) -> EmptyRes;
+
+    /// Apply all retention rules of the dataset, treating messages older than `max_age_days` before `now` as expired.
+    /// Files are deleted permanently, bypassing backups.
+    /// If `dry_run` is set, nothing is changed, and reports show what would've been affected.
+    fn apply_retention_rules(&mut self,
+                             ds_uuid: &PbUuid,
+                             now: Timestamp,
+                             dry_run: bool) -> Result<Vec<RetentionRuleReport>>;
+
+    /// Append an entry to the audit log. Entries can never be changed or removed.
+    fn record_audit_entry(&mut self, entry: AuditLogEntry) -> EmptyRes;
+
+    /// Restore a deleted dataset or chat, removing it from trash.
+    /// Chat can only be restored into an existing dataset, and nothing can replace an existing entity with the same ID.
+    /// Chat folders that referenced a restored chat are not restored.
+    fn restore_from_trash(&mut self, id: i64) -> EmptyRes;
+
+    /// Permanently remove trash items deleted before the given time, returning the number of removed items.
+    fn purge_trash(&mut self, deleted_before: Timestamp) -> Result<usize>;
+
+    /// Rebuild the storage to reclaim space left by deleted entities and refresh query planner statistics.
+    /// Caller must make sure nothing else uses the DAO meanwhile.
+    fn compact(&mut self) -> Result<CompactionReport>;
+
     /// Insert a new message for the given chat.
     /// Internal ID will be ignored.
     /// Content will be resolved based on the given dataset root and copied accordingly.
     fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> EmptyRes;
+
+    /// Same as [Self::insert_messages], but keeps provenance of messages taken from the source chat.
+    /// Import batches should be copied beforehand, see [Self::copy_import_batches_from].
+    fn insert_messages_from(&mut self,
+                            src: &dyn ChatHistoryReader,
+                            src_chat: &Chat,
+                            msgs: Vec<Message>,
+                            chat: &Chat,
+                            src_ds_root: &DatasetRoot) -> EmptyRes;
+
+    /// Copy import batches of a source dataset into a dataset here, batches already present are skipped.
+    fn copy_import_batches_from(&mut self, src: &dyn ChatHistoryReader, src_ds_uuid: &PbUuid, dst_ds_uuid: &PbUuid) -> EmptyRes;
+
+    /// Register source-specific user keys in the identity registry of the dataset.
+    /// Keys that are already registered keep mapping to their users.
+    fn insert_user_identities(&mut self, ds_uuid: &PbUuid, identities: &[UserIdentity]) -> EmptyRes;
+
+    /// Assert that two users are the same person. First user must belong to this DAO, the other one isn't checked
+    /// as it may belong to a different database. Inserting an existing alias (in either direction) does nothing.
+    fn insert_user_alias(&mut self, alias: &UserAlias) -> EmptyRes;
+
+    /// Remove an alias regardless of its direction, does nothing if there's no such alias.
+    fn delete_user_alias(&mut self, alias: &UserAlias) -> EmptyRes;
+
+    /// Remember names chat had before, e.g. prior to being renamed. Names already remembered are skipped.
+    fn insert_chat_former_names(&mut self, chat: &Chat, names: &[String]) -> EmptyRes;
+
+    /// Attach a note to a chat or its message. Note ID is ignored, a new one is assigned.
+    fn insert_note(&mut self, note: Note) -> Result<Note>;
+
+    /// Replace note text, setting its timestamp to the given one.
+    fn update_note(&mut self, id: i64, text: &str, timestamp: Timestamp) -> Result<Note>;
+
+    fn delete_note(&mut self, id: i64) -> EmptyRes;
+
+    /// Only stores the settings, messages inserted afterwards will follow them.
+    /// Use [Self::recompute_searchable_strings] to bring existing messages in line.
+    fn update_searchable_string_settings(&mut self, ds_uuid: &PbUuid, settings: &SearchableStringSettings) -> EmptyRes;
+
+    /// Rebuild searchable strings of all dataset messages according to its current settings.
+    /// Returns the number of messages whose searchable strings changed.
+    fn recompute_searchable_strings(&mut self, ds_uuid: &PbUuid) -> Result<usize>;
+
+    /// Only stores the policy, use [Self::apply_blocked_users_policy] to apply it to existing messages.
+    fn update_blocked_users_policy(&mut self, ds_uuid: &PbUuid, policy: &BlockedUsersPolicy) -> EmptyRes;
+
+    /// Turn messages of blocked users into tombstones or remove them, according to the dataset policy.
+    /// Files no longer referenced by remaining messages are deleted.
+    /// Returns the number of affected messages.
+    fn apply_blocked_users_policy(&mut self, ds_uuid: &PbUuid) -> Result<usize>;
+
+    /// Set or clear (if `None`) the HTTP(S) base URL dataset media is fetched from.
+    fn update_remote_media_root(&mut self, ds_uuid: &PbUuid, base_url_option: Option<&str>) -> EmptyRes;
+
+    /// Replaces a template stored for the same dataset and source type, if any.
+    fn store_merge_template(&mut self, template: &MergeTemplate) -> EmptyRes;
+
+    /// Replace tags given source attached to a media file (path relative to dataset root).
+    /// Tags of other sources are not affected, empty tags remove the source's annotations of the file.
+    fn set_media_annotations(&mut self, ds_uuid: &PbUuid, path: &str, source: &str, tags: &[String]) -> EmptyRes;
+
+    /// Return self as shiftable if applicable, otherwise error out
+    fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryWriter> {
+        err!("{} does not support shifting time", self.name())
+    }
 }
 
-pub trait ShiftableChatHistoryDao: ChatHistoryDao {
+pub trait ShiftableChatHistoryWriter: ChatHistoryWriter {
     /// Shift time of all timestamps in the dataset to accommodate timezone differences.
     fn shift_dataset_time(&mut self, uuid: &PbUuid, hours_shift: i32) -> EmptyRes;
 }
 
+/// Order of [ChatHistoryReader::chats]: by last message timestamp, descending (minus used to reverse order),
+/// chats without messages use their ID instead. Chat ID breaks ties.
+pub fn chat_order_key(cwd: &ChatWithDetails) -> (i64, i64) {
+    (cwd.last_msg_option.as_ref().map(|m| -m.timestamp).unwrap_or(cwd.chat.id), cwd.chat.id)
+}
+
+/// DAO opened from disk, whether it can be modified is known from its variant.
+pub enum LoadedDao {
+    /// History parsed by a loader
+    ReadOnly(Box<dyn ChatHistoryReader>),
+    /// Database
+    Writable(Box<dyn ChatHistoryWriter>),
+}
+
+impl LoadedDao {
+    pub fn reader(&self) -> &dyn ChatHistoryReader {
+        match self {
+            LoadedDao::ReadOnly(dao) => dao.as_ref(),
+            LoadedDao::Writable(dao) => dao.as_ref(),
+        }
+    }
+
+    /// Error if DAO is read-only
+    pub fn writer(&mut self) -> Result<&mut dyn ChatHistoryWriter> {
+        match self {
+            LoadedDao::ReadOnly(dao) => err!("{} is read-only, save it as a database to modify it", dao.name()),
+            LoadedDao::Writable(dao) => Ok(dao.as_mut()),
+        }
+    }
+}
+
 type UserCache = HashMap<PbUuid, UserCacheForDataset>;
 
 #[derive(DeepSizeOf)]
@@ -258,13 +542,70 @@ impl DaoCache {
     }
 }
 
-const BATCH_SIZE: usize = 5_000;
+pub(crate) const BATCH_SIZE: usize = 5_000;
+
+/// Group link occurrences (given in chat order) by href.
+fn collect_chat_links(occurrences: impl IntoIterator<Item=(String, MessageInternalId, Timestamp)>) -> Vec<ChatLink> {
+    let mut links: Vec<ChatLink> = vec![];
+    let mut link_indices: HashMap<String, usize> = HashMap::new();
+    for (href, msg_id, timestamp) in occurrences {
+        match link_indices.get(&href) {
+            Some(&idx) => {
+                let link = &mut links[idx];
+                if link.message_internal_ids.last() != Some(&*msg_id) {
+                    link.message_internal_ids.push(*msg_id);
+                }
+            }
+            None => {
+                link_indices.insert(href.clone(), links.len());
+                links.push(ChatLink {
+                    href,
+                    message_internal_ids: vec![*msg_id],
+                    first_shared_timestamp: *timestamp,
+                });
+            }
+        }
+    }
+    links
+}
+
+/// Options adjusting what [get_datasets_diff] considers a difference, on top of practical equality.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// If set, message timestamps (including edit timestamps) are truncated to this precision before comparison,
+    /// in seconds.
+    pub timestamp_precision_sec: Option<i64>,
+    /// Compare message texts as plain strings, ignoring formatting and link targets.
+    pub ignore_text_formatting: bool,
+    /// Also require message searchable strings to match (practical equality ignores them).
+    pub check_searchable_strings: bool,
+    /// If non-empty, only chats with these IDs (present in both datasets) are compared,
+    /// dataset and user checks are skipped.
+    pub chat_ids: Vec<i64>,
+}
+
+impl DiffOptions {
+    fn normalize(&self, msg: &Message) -> Message {
+        let mut msg = msg.clone();
+        if let Some(precision) = self.timestamp_precision_sec.filter(|p| *p > 1) {
+            msg.timestamp -= msg.timestamp.rem_euclid(precision);
+            if let Some(message::Typed::Regular(ref mut mr)) = msg.typed {
+                mr.edit_timestamp_option = mr.edit_timestamp_option.map(|ts| ts - ts.rem_euclid(precision));
+            }
+        }
+        if self.ignore_text_formatting && !msg.text.is_empty() {
+            msg.text = vec![RichText::make_plain(RichText::to_plain(&msg.text, PlainTextStyle::Stripped))];
+        }
+        msg
+    }
+}
 
-pub fn get_datasets_diff(master_dao: &dyn ChatHistoryDao,
+pub fn get_datasets_diff(master_dao: &dyn ChatHistoryReader,
                          master_ds_uuid: &PbUuid,
-                         slave_dao: &dyn ChatHistoryDao,
+                         slave_dao: &dyn ChatHistoryReader,
                          slave_ds_uuid: &PbUuid,
-                         max_diffs: usize) -> Result<Vec<Difference>> {
+                         max_diffs: usize,
+                         options: &DiffOptions) -> Result<Vec<Difference>> {
     let mut differences = Vec::with_capacity(max_diffs);
 
     macro_rules! check_diff {
@@ -281,42 +622,56 @@ pub fn get_datasets_diff(master_dao: &dyn ChatHistoryDao,
     }
 
     measure(|| {
-        let master_ds = master_dao.datasets()?.into_iter().find(|ds| &ds.uuid == master_ds_uuid)
-            .with_context(|| format!("Dataset {} not found in master DAO!", master_ds_uuid.value))?;
-        let mut slave_ds = slave_dao.datasets()?.into_iter().find(|ds| &ds.uuid == slave_ds_uuid)
-            .with_context(|| format!("Dataset {} not found in slave DAO!", slave_ds_uuid.value))?;
-        slave_ds.uuid = master_ds_uuid.clone();
-        check_diff!(master_ds == slave_ds, false,
-                    "Dataset differs", Some((format!("{master_ds:?}"), format!("{slave_ds:?}"))));
         let master_ds_root = master_dao.dataset_root(master_ds_uuid)?;
         let slave_ds_root = slave_dao.dataset_root(slave_ds_uuid)?;
-        check_diff!(*master_ds_root != *slave_ds_root, false,
-                    "Master and slave dataset root paths are the same!", None::<(String, String)>);
-
-        let maybe_result = measure(|| {
-            let master_users = master_dao.users(master_ds_uuid)?;
-            let slave_users = slave_dao.users(slave_ds_uuid)?;
-            check_diff!(master_users.len() == slave_users.len(), true,
-                        "User count differs", Some((
-                            format!("{} ({:?})", master_users.len(), master_users),
-                            format!("{} ({:?})", slave_users.len(), slave_users)
-                        )));
-            for (i, (master_user, mut slave_user)) in master_users.iter().zip(slave_users.into_iter()).enumerate() {
-                slave_user.ds_uuid = master_ds_uuid.clone();
-                check_diff!(PracticalEqTuple::new_without_cwd(master_user, &master_ds_root).practically_equals(
-                                &PracticalEqTuple::new_without_cwd(&slave_user, &slave_ds_root))?, false,
-                            format!("User #{i} differs"), Some((format!("{master_user:?}"), format!("{slave_user:?}"))));
-            }
-            Ok(vec![])
-        }, |_: &Result<_>, t| log::info!("Users checked in {t} ms"))?;
-        if !maybe_result.is_empty() { return Ok(maybe_result); }
 
-        let master_chats = master_dao.chats(master_ds_uuid)?;
-        let slave_chats = slave_dao.chats(slave_ds_uuid)?;
-        check_diff!(master_chats.len() == slave_chats.len(), true,
-                    "Chat count differs", Some((format!("{}", master_chats.len()), format!("{}", slave_chats.len()))));
+        let chat_pairs = if options.chat_ids.is_empty() {
+            let master_ds = master_dao.datasets()?.into_iter().find(|ds| &ds.uuid == master_ds_uuid)
+                .with_context(|| format!("Dataset {} not found in master DAO!", master_ds_uuid.value))?;
+            let mut slave_ds = slave_dao.datasets()?.into_iter().find(|ds| &ds.uuid == slave_ds_uuid)
+                .with_context(|| format!("Dataset {} not found in slave DAO!", slave_ds_uuid.value))?;
+            slave_ds.uuid = master_ds_uuid.clone();
+            check_diff!(master_ds == slave_ds, false,
+                        "Dataset differs", Some((format!("{master_ds:?}"), format!("{slave_ds:?}"))));
+            check_diff!(*master_ds_root != *slave_ds_root, false,
+                        "Master and slave dataset root paths are the same!", None::<(String, String)>);
+
+            let maybe_result = measure(|| {
+                let master_users = master_dao.users(master_ds_uuid)?;
+                let slave_users = slave_dao.users(slave_ds_uuid)?;
+                check_diff!(master_users.len() == slave_users.len(), true,
+                            "User count differs", Some((
+                                format!("{} ({:?})", master_users.len(), master_users),
+                                format!("{} ({:?})", slave_users.len(), slave_users)
+                            )));
+                for (i, (master_user, mut slave_user)) in master_users.iter().zip(slave_users).enumerate() {
+                    slave_user.ds_uuid = master_ds_uuid.clone();
+                    check_diff!(PracticalEqTuple::new_without_cwd(master_user, &master_ds_root).practically_equals(
+                                    &PracticalEqTuple::new_without_cwd(&slave_user, &slave_ds_root))?, false,
+                                format!("User #{i} differs"), Some((format!("{master_user:?}"), format!("{slave_user:?}"))));
+                }
+                Ok(vec![])
+            }, |_: &Result<_>, t| log::info!("Users checked in {t} ms"))?;
+            if !maybe_result.is_empty() { return Ok(maybe_result); }
+
+            let master_chats = master_dao.chats(master_ds_uuid)?;
+            let slave_chats = slave_dao.chats(slave_ds_uuid)?;
+            check_diff!(master_chats.len() == slave_chats.len(), true,
+                        "Chat count differs", Some((format!("{}", master_chats.len()), format!("{}", slave_chats.len()))));
+            master_chats.into_iter().zip(slave_chats).collect_vec()
+        } else {
+            let mut chat_pairs = Vec::with_capacity(options.chat_ids.len());
+            for &chat_id in options.chat_ids.iter() {
+                let master_cwd = master_dao.chat_option(master_ds_uuid, chat_id)?
+                    .with_context(|| format!("Chat {chat_id} not found in master DAO!"))?;
+                let slave_cwd = slave_dao.chat_option(slave_ds_uuid, chat_id)?
+                    .with_context(|| format!("Chat {chat_id} not found in slave DAO!"))?;
+                chat_pairs.push((master_cwd, slave_cwd));
+            }
+            chat_pairs
+        };
 
-        for (i, (master_cwd, slave_cwd)) in master_chats.iter().zip(slave_chats.iter()).enumerate() {
+        for (i, (master_cwd, slave_cwd)) in chat_pairs.iter().enumerate() {
             let maybe_result = measure(|| {
                 {
                     let mut slave_cwd = slave_cwd.clone();
@@ -338,14 +693,20 @@ pub fn get_datasets_diff(master_dao: &dyn ChatHistoryDao,
                                 "Empty messages batch encountered", None::<(String, String)>);
                     check_diff!(master_messages.len() == slave_messages.len(), false,
                                 format!("Messages size for chat {} differs", master_cwd.chat.qualified_name()),
-                                Some((master_chats.len(), slave_chats.len())));
+                                Some((master_messages.len(), slave_messages.len())));
 
                     for (j, (master_msg, slave_msg)) in master_messages.iter().zip(slave_messages.iter()).enumerate() {
-                        let master_pet = PracticalEqTuple::new(master_msg, &master_ds_root, master_cwd);
-                        let slave_pet = PracticalEqTuple::new(slave_msg, &slave_ds_root, slave_cwd);
+                        let (master_msg, slave_msg) = (options.normalize(master_msg), options.normalize(slave_msg));
+                        let master_pet = PracticalEqTuple::new(&master_msg, &master_ds_root, master_cwd);
+                        let slave_pet = PracticalEqTuple::new(&slave_msg, &slave_ds_root, slave_cwd);
                         check_diff!(master_pet.practically_equals(&slave_pet)?, false,
                                     format!("Message #{j} for chat {} differs", master_cwd.chat.qualified_name()),
                                     Some((format!("{:?}", master_msg), format!("{:?}", slave_msg))));
+                        check_diff!(!options.check_searchable_strings ||
+                                        master_msg.searchable_string == slave_msg.searchable_string, false,
+                                    format!("Message #{j} searchable string for chat {} differs",
+                                            master_cwd.chat.qualified_name()),
+                                    Some((&master_msg.searchable_string, &slave_msg.searchable_string)));
                     }
                     offset += master_messages.len();
                 }
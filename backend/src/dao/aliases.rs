@@ -0,0 +1,52 @@
+use itertools::Itertools;
+
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "aliases_tests.rs"]
+mod tests;
+
+/// Users of different datasets known to be the same person, user IDs sorted within each dataset.
+pub type AliasGroup = HashMap<PbUuid, Vec<i64>>;
+
+/// Whether two aliases link the same users, regardless of their direction.
+pub fn same_alias(a: &UserAlias, b: &UserAlias) -> bool {
+    let (a1, a2) = ((&a.ds_uuid, a.user_id), (&a.other_ds_uuid, a.other_user_id));
+    let (b1, b2) = ((&b.ds_uuid, b.user_id), (&b.other_ds_uuid, b.other_user_id));
+    (a1 == b1 && a2 == b2) || (a1 == b2 && a2 == b1)
+}
+
+/// Aliases stored in all the given DAOs. Since a dataset might have aliases pointing to a dataset
+/// of another database, all open databases should be considered.
+pub fn all_aliases<'a>(daos: impl IntoIterator<Item=&'a dyn ChatHistoryReader>) -> Result<Vec<UserAlias>> {
+    let mut res = vec![];
+    for dao in daos {
+        res.extend(dao.user_aliases()?);
+    }
+    Ok(res)
+}
+
+/// All users linked to a given one by aliases, directly or transitively, including the user itself.
+pub fn alias_group(aliases: &[UserAlias], ds_uuid: &PbUuid, user_id: i64) -> AliasGroup {
+    let mut visited: HashSet<(&PbUuid, i64)> = HashSet::from([(ds_uuid, user_id)]);
+    let mut queue = vec![(ds_uuid, user_id)];
+    while let Some(current) = queue.pop() {
+        for alias in aliases {
+            let (this, other) = ((&alias.ds_uuid, alias.user_id), (&alias.other_ds_uuid, alias.other_user_id));
+            let next_option =
+                if this == current { Some(other) } else if other == current { Some(this) } else { None };
+            if let Some(next) = next_option {
+                if visited.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+    }
+
+    let mut res = AliasGroup::new();
+    for (ds_uuid, user_id) in visited.into_iter().sorted_by_key(|(ds_uuid, user_id)| (&ds_uuid.value, *user_id)) {
+        res.entry(ds_uuid.clone()).or_default().push(user_id);
+    }
+    res
+}
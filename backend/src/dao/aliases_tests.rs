@@ -0,0 +1,38 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+#[test]
+fn resolving_alias_groups() {
+    let (ds1, ds2, ds3) = (PbUuid::random(), PbUuid::random(), PbUuid::random());
+    let alias = |ds_uuid: &PbUuid, user_id: i64, other_ds_uuid: &PbUuid, other_user_id: i64| UserAlias {
+        ds_uuid: ds_uuid.clone(),
+        user_id,
+        other_ds_uuid: other_ds_uuid.clone(),
+        other_user_id,
+    };
+    let aliases = vec![
+        alias(&ds1, 1, &ds2, 10),
+        // Chained in reverse direction
+        alias(&ds3, 100, &ds2, 10),
+        alias(&ds3, 101, &ds1, 1),
+        // Unrelated
+        alias(&ds1, 2, &ds2, 20),
+    ];
+
+    let expected = AliasGroup::from([
+        (ds1.clone(), vec![1]),
+        (ds2.clone(), vec![10]),
+        (ds3.clone(), vec![100, 101]),
+    ]);
+    assert_eq!(alias_group(&aliases, &ds1, 1), expected);
+    assert_eq!(alias_group(&aliases, &ds3, 100), expected);
+
+    assert_eq!(alias_group(&aliases, &ds2, 20), AliasGroup::from([(ds1.clone(), vec![2]), (ds2.clone(), vec![20])]));
+    assert_eq!(alias_group(&aliases, &ds1, 3), AliasGroup::from([(ds1.clone(), vec![3])]));
+
+    assert!(same_alias(&aliases[0], &alias(&ds2, 10, &ds1, 1)));
+    assert!(!same_alias(&aliases[0], &aliases[1]));
+}
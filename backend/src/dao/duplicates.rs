@@ -0,0 +1,135 @@
+use std::hash::BuildHasher;
+
+use itertools::Itertools;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+pub const DEFAULT_MIN_TEXT_LENGTH: usize = 100;
+pub const DEFAULT_MIN_SIMILARITY: f32 = 0.8;
+
+/// Number of consecutive words forming a single shingle.
+const SHINGLE_SIZE: usize = 3;
+/// MinHash signature is split into bands, texts sharing at least one band become candidates.
+const BANDS: usize = 16;
+const ROWS_PER_BAND: usize = 4;
+
+struct LongText {
+    chat_id: i64,
+    internal_id: i64,
+    shingles: HashSet<u64>,
+    signature: [u64; BANDS * ROWS_PER_BAND],
+}
+
+/// Find groups of messages with identical or near-identical texts, spanning at least two different chats.
+///
+/// Only texts at least `min_text_length` characters long (after normalization) are considered.
+/// Texts are compared by Jaccard similarity of their word shingles, candidate pairs are found by MinHash
+/// locality-sensitive hashing, so the analysis doesn't compare every text with every other one.
+/// Groups and messages within them follow the order of chats and messages in the DAO.
+pub fn find_near_duplicates(dao: &dyn ChatHistoryReader,
+                            ds_uuid: &PbUuid,
+                            min_text_length: usize,
+                            min_similarity: f32) -> Result<Vec<NearDuplicateGroup>> {
+    ensure!(min_text_length > 0, "Minimum text length must be positive!");
+    ensure!(min_similarity > 0.0 && min_similarity <= 1.0, "Minimum similarity must be within (0, 1]!");
+
+    let mut texts: Vec<LongText> = vec![];
+    for cwd in dao.chats(ds_uuid)? {
+        let mut offset = 0;
+        loop {
+            let msgs = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+            for msg in msgs.iter() {
+                let text = normalize_text(msg);
+                if text.chars().count() < min_text_length { continue; }
+                let shingles = shingles(&text);
+                let signature = min_hash_signature(&shingles);
+                texts.push(LongText { chat_id: cwd.chat.id, internal_id: msg.internal_id, shingles, signature });
+            }
+            if msgs.len() < BATCH_SIZE { break; }
+            offset += BATCH_SIZE;
+        }
+    }
+
+    let mut candidates: HashSet<(usize, usize)> = HashSet::new();
+    for band in 0..BANDS {
+        let rows = band * ROWS_PER_BAND..(band + 1) * ROWS_PER_BAND;
+        let buckets = texts.iter().enumerate().into_group_map_by(|(_, t)| &t.signature[rows.clone()]);
+        for bucket in buckets.values().filter(|b| b.len() > 1) {
+            for ((i1, t1), (i2, t2)) in bucket.iter().tuple_combinations() {
+                if t1.chat_id != t2.chat_id {
+                    candidates.insert((*i1, *i2));
+                }
+            }
+        }
+    }
+
+    let mut parents = (0..texts.len()).collect_vec();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    for (i1, i2) in candidates {
+        if jaccard_similarity(&texts[i1].shingles, &texts[i2].shingles) >= min_similarity {
+            let (r1, r2) = (root(&mut parents, i1), root(&mut parents, i2));
+            parents[r1.max(r2)] = r1.min(r2);
+        }
+    }
+
+    let groups = (0..texts.len())
+        .into_group_map_by(|&i| root(&mut parents, i))
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .sorted_by_key(|(root, _)| *root)
+        .map(|(_, members)| NearDuplicateGroup {
+            messages: members.into_iter()
+                .map(|i| MessageRef { chat_id: texts[i].chat_id, message_internal_id: texts[i].internal_id })
+                .collect_vec()
+        })
+        .collect_vec();
+    Ok(groups)
+}
+
+/// Lowercase words of a message text, separated by a single space.
+fn normalize_text(msg: &Message) -> String {
+    msg.text.iter()
+        .filter_map(|rte| rte.get_text())
+        .flat_map(|s| s.split(|c: char| !c.is_alphanumeric()))
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .join(" ")
+}
+
+fn shingles(normalized_text: &str) -> HashSet<u64> {
+    let words = normalized_text.split(' ').collect_vec();
+    let hasher = hasher();
+    words.windows(SHINGLE_SIZE.min(words.len()))
+        .map(|window| hasher.hash_one(window))
+        .collect()
+}
+
+fn min_hash_signature(shingles: &HashSet<u64>) -> [u64; BANDS * ROWS_PER_BAND] {
+    let mut signature = [u64::MAX; BANDS * ROWS_PER_BAND];
+    for &shingle in shingles {
+        for (seed, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(mix(shingle ^ (seed as u64).wrapping_mul(0x9E3779B97F4A7C15)));
+        }
+    }
+    signature
+}
+
+/// SplitMix64 finalizer, turns a single hash into a family of independent-ish hashes.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+fn jaccard_similarity(s1: &HashSet<u64>, s2: &HashSet<u64>) -> f32 {
+    let intersection = s1.intersection(s2).count();
+    let union = s1.len() + s2.len() - intersection;
+    if union == 0 { 1.0 } else { intersection as f32 / union as f32 }
+}
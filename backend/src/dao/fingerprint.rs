@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use itertools::Itertools;
+use prost::Message as ProstMessage;
+use sha2::{Digest, Sha256};
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+/// Stable content hash of a dataset, as a lowercase hex string.
+///
+/// Covers users, chats and messages along with the contents of all referenced files.
+/// Dataset UUID and alias, message internal IDs and file paths don't affect the result,
+/// so the same data stored in a different DAO (or in a different layout) yields the same fingerprint.
+pub fn dataset_fingerprint(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid) -> Result<String> {
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let mut hasher = Sha256::new();
+
+    hasher.update(dao.myself(ds_uuid)?.id.to_le_bytes());
+    for user in dao.users(ds_uuid)?.into_iter().sorted_by_key(|u| u.id) {
+        let pic_paths = user.profile_pictures.iter().map(|pp| pp.path.clone()).collect_vec();
+        let user = User {
+            ds_uuid: PbUuid::default(),
+            profile_pictures: user.profile_pictures.into_iter()
+                .map(|pp| ProfilePicture { path: String::new(), ..pp })
+                .collect_vec(),
+            ..user
+        };
+        hasher.update(user.encode_length_delimited_to_vec());
+        update_with_files(&mut hasher, &ds_root, &pic_paths)?;
+    }
+
+    for cwd in dao.chats(ds_uuid)?.into_iter().sorted_by_key(|cwd| cwd.chat.id) {
+        let chat = Chat {
+            ds_uuid: PbUuid::default(),
+            img_path_option: None,
+            ..cwd.chat.clone()
+        };
+        hasher.update(chat.encode_length_delimited_to_vec());
+        update_with_files(&mut hasher, &ds_root, cwd.chat.img_path_option.as_slice())?;
+
+        let mut offset = 0;
+        loop {
+            let msgs = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+            for mut msg in msgs.iter().cloned() {
+                msg.internal_id = 0;
+                let paths = take_file_paths(&mut msg);
+                hasher.update(msg.encode_length_delimited_to_vec());
+                update_with_files(&mut hasher, &ds_root, &paths)?;
+            }
+            if msgs.len() < BATCH_SIZE { break; }
+            offset += BATCH_SIZE;
+        }
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Feeds content hashes of the given files (relative to dataset root), missing files are marked as such.
+fn update_with_files(hasher: &mut Sha256, ds_root: &DatasetRoot, paths: &[String]) -> EmptyRes {
+    for path in paths {
+        let path = ds_root.to_absolute(path);
+        if path.is_file() {
+            hasher.update(b"+");
+            hasher.update(file_sha256(&path)?);
+        } else {
+            hasher.update(b"-");
+        }
+    }
+    Ok(())
+}
+
+pub fn file_sha256(path: &Path) -> Result<Vec<u8>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 { break; }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Clears all file paths of a message (including custom emojis in text), returning them in order.
+fn take_file_paths(msg: &mut Message) -> Vec<String> {
+    let text_paths = msg.text.iter_mut()
+        .filter_map(|rte| match rte.val.as_mut().unwrap() {
+            rich_text_element::Val::CustomEmoji(v) => v.path_option.take(),
+            _ => None,
+        })
+        .collect_vec();
+    let slots: Vec<&mut Option<String>> = match msg.typed_mut() {
+        message::Typed::Regular(mr) => {
+            mr.contents.iter_mut()
+                .flat_map(|content| {
+                    use content::SealedValueOptional::*;
+                    match content.sealed_value_optional.as_mut().unwrap() {
+                        Sticker(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                        Photo(v) => vec![&mut v.path_option],
+                        VoiceMsg(v) => vec![&mut v.path_option],
+                        Audio(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                        VideoMsg(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                        Video(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                        File(v) => vec![&mut v.path_option, &mut v.thumbnail_path_option],
+                        Location(_) => vec![],
+                        Poll(_) => vec![],
+                        SharedContact(v) => vec![&mut v.vcard_path_option],
+                    }
+                })
+                .collect_vec()
+        }
+        message_service_pat!(ms) => {
+            use message_service::SealedValueOptional::*;
+            match ms {
+                SuggestProfilePhoto(v) => vec![&mut v.photo.path_option],
+                GroupEditPhoto(v) => vec![&mut v.photo.path_option],
+                _ => vec![],
+            }
+        }
+        message_service_pat_unreachable!() => { unreachable!() }
+    };
+    slots.into_iter().filter_map(|slot| slot.take()).chain(text_paths).collect_vec()
+}
@@ -0,0 +1,170 @@
+use std::path::Path;
+use std::process::Command;
+
+use itertools::Itertools;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader, ChatHistoryWriter};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "gallery_tests.rs"]
+mod tests;
+
+/// External tool attaching tags to media files, e.g. a face recognizer tagging persons on photos.
+/// The crate itself doesn't do any recognition.
+pub trait MediaAnnotator: Send + Sync {
+    /// Annotations are recorded under this source name.
+    fn name(&self) -> String;
+
+    /// Whether the annotator can be used at all, e.g. whether external tool is installed.
+    fn check_available(&self) -> EmptyRes;
+
+    /// Tags for the given file, possibly none.
+    fn annotate(&self, path: &Path) -> Result<Vec<String>>;
+}
+
+/// Runs a command with an absolute file path appended as the last argument,
+/// every non-blank line of its output is a tag.
+pub struct CommandAnnotator {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl MediaAnnotator for CommandAnnotator {
+    fn name(&self) -> String {
+        path_file_name(Path::new(&self.program)).unwrap_or(&self.program).to_owned()
+    }
+
+    fn check_available(&self) -> EmptyRes {
+        let program = Path::new(&self.program);
+        let found = if program.components().count() > 1 {
+            program.is_file()
+        } else {
+            std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        };
+        ensure!(found, "{} is not found", self.program);
+        Ok(())
+    }
+
+    fn annotate(&self, path: &Path) -> Result<Vec<String>> {
+        let output = Command::new(&self.program).args(&self.args).arg(path).output()?;
+        ensure!(output.status.success(), "{} failed: {}", self.program, String::from_utf8_lossy(&output.stderr).trim());
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_owned())
+            .collect_vec())
+    }
+}
+
+/// Parses media annotator specification: a command line (program followed by arguments, separated by whitespace),
+/// to which a file path will be appended.
+pub fn parse_media_annotator(spec: &str) -> Result<Box<dyn MediaAnnotator>> {
+    let mut parts = spec.split_whitespace().map(|s| s.to_owned());
+    let program = parts.next().context("Media annotator command is empty")?;
+    Ok(Box::new(CommandAnnotator { program, args: parts.collect_vec() }))
+}
+
+/// Photos and videos of a dataset, or of a single chat, newest first.
+/// Only items having all of `tags` are listed, `offset` and `limit` are applied after filtering.
+pub fn gallery(dao: &dyn ChatHistoryReader,
+               ds_uuid: &PbUuid,
+               chat_id_option: Option<i64>,
+               tags: &[String],
+               offset: usize,
+               limit: usize) -> Result<Vec<GalleryItem>> {
+    let mut tags_by_path: HashMap<String, Vec<String>> = HashMap::new();
+    for annotation in dao.media_annotations(ds_uuid)? {
+        tags_by_path.entry(annotation.path).or_default().push(annotation.tag);
+    }
+
+    let mut items = media_items(dao, ds_uuid, chat_id_option)?;
+    for item in items.iter_mut() {
+        if let Some(item_tags) = tags_by_path.get(&item.path) {
+            item.tags = item_tags.iter().sorted().dedup().cloned().collect_vec();
+        }
+    }
+    items.sort_by_key(|item| std::cmp::Reverse((item.timestamp, item.message_internal_id)));
+    Ok(items.into_iter()
+        .filter(|item| tags.iter().all(|tag| item.tags.contains(tag)))
+        .skip(offset)
+        .take(limit)
+        .collect_vec())
+}
+
+/// Run annotator over all present dataset media files it didn't tag yet, returning the number of processed files.
+/// Files it found no tags for are processed again on the next run, files it fails on are skipped.
+pub fn annotate_media(dao: &mut dyn ChatHistoryWriter,
+                      ds_uuid: &PbUuid,
+                      annotator: &dyn MediaAnnotator) -> Result<usize> {
+    annotator.check_available()?;
+    let source = annotator.name();
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let already_annotated: HashSet<String> = dao.media_annotations(ds_uuid)?.into_iter()
+        .filter(|a| a.source == source)
+        .map(|a| a.path)
+        .collect();
+
+    let paths = media_items(dao, ds_uuid, None)?.into_iter()
+        .map(|item| item.path)
+        .filter(|path| !already_annotated.contains(path))
+        .unique()
+        .collect_vec();
+    let mut num_annotated = 0;
+    for path in paths {
+        let abs_path = ds_root.to_absolute(&path);
+        if !abs_path.exists() { continue; }
+        match annotator.annotate(&abs_path) {
+            Ok(tags) => {
+                dao.set_media_annotations(ds_uuid, &path, &source, &tags)?;
+                num_annotated += 1;
+            }
+            Err(e) => log::warn!("{source} couldn't annotate {}: {e}", abs_path.display()),
+        }
+    }
+    log::info!("{source} annotated {num_annotated} media files");
+    Ok(num_annotated)
+}
+
+/// Media items without tags, in chat order.
+fn media_items(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid, chat_id_option: Option<i64>) -> Result<Vec<GalleryItem>> {
+    let cwds = dao.chats(ds_uuid)?.into_iter()
+        .filter(|cwd| chat_id_option.is_none_or(|id| cwd.chat.id == id))
+        .collect_vec();
+    if let Some(chat_id) = chat_id_option {
+        ensure!(!cwds.is_empty(), "Chat {chat_id} not found");
+    }
+
+    let mut items = vec![];
+    for cwd in cwds.iter() {
+        let mut offset = 0;
+        loop {
+            let batch = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+            for msg in batch.iter() {
+                let message_regular_pat! { contents, .. } = msg.typed() else { continue };
+                for content in contents.iter() {
+                    use content::SealedValueOptional::*;
+                    let (path_option, kind) = match content.sealed_value_optional.as_ref() {
+                        Some(Photo(c)) => (c.path_option.as_ref(), GalleryItemKind::Photo),
+                        Some(Video(c)) => (c.path_option.as_ref(), GalleryItemKind::Video),
+                        Some(VideoMsg(c)) => (c.path_option.as_ref(), GalleryItemKind::VideoMsg),
+                        _ => continue,
+                    };
+                    let Some(path) = path_option else { continue };
+                    items.push(GalleryItem {
+                        chat_id: cwd.chat.id,
+                        message_internal_id: msg.internal_id,
+                        timestamp: msg.timestamp,
+                        path: path.clone(),
+                        kind: kind as i32,
+                        tags: vec![],
+                    });
+                }
+            }
+            if batch.len() < BATCH_SIZE { break; }
+            offset += BATCH_SIZE;
+        }
+    }
+    Ok(items)
+}
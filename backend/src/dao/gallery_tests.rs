@@ -0,0 +1,125 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::sqlite_dao::SqliteDao;
+use crate::dao::ChatHistoryWriter;
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn annotating_and_filtering() -> EmptyRes {
+    let msgs = (1..=4).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let dao_holder = create_simple_dao(false, "Media", msgs, 2, &|_, ds_root, msg| {
+        let idx = msg.source_id_option.unwrap();
+        let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+        mr.contents = match idx {
+            1 | 2 => {
+                let rel_path = format!("photo_{idx}.jpg");
+                create_named_file(&ds_root.to_absolute(&rel_path), &[]);
+                vec![content!(Photo {
+                    path_option: Some(rel_path),
+                    width: 0,
+                    height: 0,
+                    mime_type_option: None,
+                    is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
+                })]
+            }
+            3 => {
+                let rel_path = "video_3.mp4".to_owned();
+                create_named_file(&ds_root.to_absolute(&rel_path), &[]);
+                vec![content!(Video {
+                    path_option: Some(rel_path),
+                    file_name_option: None,
+                    title_option: None,
+                    performer_option: None,
+                    width: 0,
+                    height: 0,
+                    mime_type: "video/mp4".to_owned(),
+                    duration_sec_option: None,
+                    thumbnail_path_option: None,
+                    is_one_time: false,
+                })]
+            }
+            _ => vec![],
+        };
+    });
+    let ds_uuid = dao_holder.dao.ds_uuid();
+
+    let tmp_dir = TmpDir::new();
+    let mut dao = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME))?;
+    dao.copy_datasets_from(dao_holder.dao.as_ref(), &[ds_uuid.clone()])?;
+    let chat_id = dao.chats(&ds_uuid)?[0].chat.id;
+
+    let items = gallery(&dao, &ds_uuid, None, &[], 0, usize::MAX)?;
+    assert_eq!(items.iter().map(|i| (i.path.as_str(), i.kind)).collect_vec(), vec![
+        ("video_3.mp4", GalleryItemKind::Video as i32),
+        ("photo_2.jpg", GalleryItemKind::Photo as i32),
+        ("photo_1.jpg", GalleryItemKind::Photo as i32),
+    ]);
+    assert!(items.iter().all(|i| i.tags.is_empty() && i.chat_id == chat_id));
+
+    assert_eq!(annotate_media(&mut dao, &ds_uuid, &MockAnnotator)?, 3);
+    // Already tagged files are skipped, untagged video is processed again
+    assert_eq!(annotate_media(&mut dao, &ds_uuid, &MockAnnotator)?, 1);
+    dao.set_media_annotations(&ds_uuid, "photo_2.jpg", "manual", &["person:Bob".to_owned(), "cat".to_owned()])?;
+
+    let paths_tagged = |dao: &SqliteDao, tags: &[&str]| -> Result<Vec<String>> {
+        let tags = tags.iter().map(|t| t.to_string()).collect_vec();
+        Ok(gallery(dao, &ds_uuid, Some(chat_id), &tags, 0, usize::MAX)?.into_iter().map(|i| i.path).collect_vec())
+    };
+    assert_eq!(paths_tagged(&dao, &["person:Alice"])?, vec!["photo_2.jpg", "photo_1.jpg"]);
+    assert_eq!(paths_tagged(&dao, &["person:Alice", "person:Bob"])?, vec!["photo_2.jpg"]);
+    assert_eq!(paths_tagged(&dao, &["dog"])?, Vec::<String>::new());
+    assert_eq!(gallery(&dao, &ds_uuid, None, &[], 1, 1)?[0].tags, vec!["cat", "person:Alice", "person:Bob"]);
+
+    // Replacing tags of one source keeps tags of other sources
+    dao.set_media_annotations(&ds_uuid, "photo_2.jpg", "manual", &[])?;
+    assert_eq!(paths_tagged(&dao, &["person:Bob"])?, Vec::<String>::new());
+    assert_eq!(paths_tagged(&dao, &["person:Alice"])?, vec!["photo_2.jpg", "photo_1.jpg"]);
+
+    // Annotations survive copying
+    let tmp_dir2 = TmpDir::new();
+    let mut dao2 = SqliteDao::create(&tmp_dir2.path.join(SqliteDao::FILENAME))?;
+    dao2.copy_datasets_from(&dao, &[ds_uuid.clone()])?;
+    assert_eq!(dao2.media_annotations(&ds_uuid)?, dao.media_annotations(&ds_uuid)?);
+
+    dao2.delete_dataset(ds_uuid.clone())?;
+    assert_eq!(dao2.media_annotations(&ds_uuid)?, vec![]);
+    Ok(())
+}
+
+#[test]
+fn parsing_annotator_spec() -> EmptyRes {
+    assert_eq!(parse_media_annotator("/opt/faces/tag --model small")?.name(), "tag");
+    assert!(parse_media_annotator("  ").is_err());
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+/// Finds Alice on photos, nothing on videos.
+struct MockAnnotator;
+
+impl MediaAnnotator for MockAnnotator {
+    fn name(&self) -> String {
+        "mock".to_owned()
+    }
+
+    fn check_available(&self) -> EmptyRes {
+        Ok(())
+    }
+
+    fn annotate(&self, path: &Path) -> Result<Vec<String>> {
+        Ok(if path_file_name(path)?.starts_with("photo_") { vec!["person:Alice".to_owned()] } else { vec![] })
+    }
+}
@@ -1,8 +1,9 @@
-use std::{cmp, thread};
+use std::cmp;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 use deepsize::DeepSizeOf;
-use itertools::Itertools;
+use itertools::{Either, Itertools};
 
 use super::*;
 
@@ -20,6 +21,10 @@ pub struct InMemoryDao {
     pub storage_path: PathBuf,
     pub ds_roots: HashMap<PbUuid, DatasetRoot>,
     pub cwms: HashMap<PbUuid, Vec<ChatWithMessages>>,
+    /// Import a dataset was loaded from, all of its messages share it
+    pub import_batches: HashMap<PbUuid, ImportBatch>,
+    /// Source-specific keys of users, recorded by loaders
+    pub user_identities: HashMap<PbUuid, Vec<UserIdentity>>,
     cache: DaoCache,
 }
 
@@ -58,7 +63,15 @@ impl InMemoryDao {
 
         drop(cache);
 
-        InMemoryDao { name, storage_path, ds_roots, cwms: cwms_map, cache: cache_wrapper }
+        InMemoryDao {
+            name,
+            storage_path,
+            ds_roots,
+            cwms: cwms_map,
+            import_batches: HashMap::new(),
+            user_identities: HashMap::new(),
+            cache: cache_wrapper,
+        }
     }
 
     fn chat_members(&self, chat: &Chat) -> Result<Vec<User>> {
@@ -114,6 +127,23 @@ impl InMemoryDao {
             log::debug!("Removed {num_removed} orphan users");
         }
     }
+
+    /// Adds a user to its dataset, replacing an existing one with the same ID.
+    pub fn put_user(&mut self, user: User) -> EmptyRes {
+        let mut cache = self.cache.inner.write().expect("cache write lock");
+        let users_for_ds = cache.users.get_mut(&user.ds_uuid).context("Dataset not found")?;
+        users_for_ds.user_by_id.insert(user.id(), user);
+        Ok(())
+    }
+
+    /// Removes a user from the dataset, references to them in chats and messages are left as-is.
+    pub fn remove_user(&mut self, ds_uuid: &PbUuid, id: UserId) -> EmptyRes {
+        let mut cache = self.cache.inner.write().expect("cache write lock");
+        let users_for_ds = cache.users.get_mut(ds_uuid).context("Dataset not found")?;
+        ensure!(users_for_ds.myself_id != id, "Cannot remove myself");
+        users_for_ds.user_by_id.remove(&id).context("User not found")?;
+        Ok(())
+    }
 }
 
 impl WithCache for InMemoryDao {
@@ -126,7 +156,7 @@ impl WithCache for InMemoryDao {
     fn invalidate_cache(&self) -> EmptyRes { err!("Cannot invalidate cache of in-memory DAO!") }
 }
 
-impl ChatHistoryDao for InMemoryDao {
+impl ChatHistoryReader for InMemoryDao {
     fn name(&self) -> &str {
         self.name.as_str()
     }
@@ -244,108 +274,153 @@ impl ChatHistoryDao for InMemoryDao {
             .iter().find(|m| m.source_id_option.iter().contains(&*source_id)).cloned())
     }
 
-    fn as_mutable(&mut self) -> Result<&mut dyn MutableChatHistoryDao> {
-        Ok(self)
+    fn message_option_by_internal_id(&self, chat: &Chat, internal_id: MessageInternalId) -> Result<Option<Message>> {
+        Ok(self.messages_option(&chat.ds_uuid, chat.id).unwrap()
+            .iter().find(|m| m.internal_id == *internal_id).cloned())
     }
 
-    fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryDao> {
-        Ok(self)
+    fn messages_filtered(&self,
+                         chat: &Chat,
+                         filter: &MessageFilter,
+                         anchor_option: Option<MessageInternalId>,
+                         backward: bool,
+                         limit: usize) -> Result<Vec<Message>> {
+        let msgs = self.messages_option(&chat.ds_uuid, chat.id).unwrap();
+        let msgs = match anchor_option {
+            None => msgs.as_slice(),
+            Some(anchor) => {
+                let Some(idx) = msgs.iter().position(|m| m.internal_id == *anchor) else {
+                    bail!("Message not found!");
+                };
+                if backward { &msgs[..idx] } else { &msgs[(idx + 1)..] }
+            }
+        };
+        let ordered = if backward { Either::Left(msgs.iter().rev()) } else { Either::Right(msgs.iter()) };
+        let mut result = vec![];
+        for m in ordered {
+            if result.len() >= limit { break; }
+            if filter.matches(m)? { result.push(m.clone()); }
+        }
+        if backward { result.reverse(); }
+        Ok(result)
     }
-}
 
-impl MutableChatHistoryDao for InMemoryDao {
-    fn backup(&mut self) -> Result<JoinHandle<()>> {
-        Ok(thread::spawn(|| {})) // NOOP
+    fn first_message_on_or_after(&self, chat: &Chat, ts: Timestamp) -> Result<Option<Message>> {
+        Ok(self.messages_option(&chat.ds_uuid, chat.id).unwrap()
+            .iter().find(|m| m.timestamp >= *ts).cloned())
     }
 
-    fn insert_dataset(&mut self, _ds: Dataset) -> Result<Dataset> {
-        err!("InMemoryDao does not implement inserting dataset")
+    fn chat_links(&self, chat: &Chat) -> Result<Vec<ChatLink>> {
+        let msgs = self.messages_option(&chat.ds_uuid, chat.id).unwrap();
+        Ok(collect_chat_links(msgs.iter().flat_map(|m| {
+            m.text.iter().filter_map(move |rte| match rte.val.as_ref().unwrap() {
+                rich_text_element::Val::Link(link) => Some((link.href.clone(), m.internal_id(), m.timestamp())),
+                _ => None,
+            })
+        })))
+    }
+
+    fn search_messages(&self,
+                       ds_uuid: &PbUuid,
+                       text: &str,
+                       from_ids_option: Option<&[i64]>,
+                       after_option: Option<(Timestamp, MessageInternalId)>,
+                       limit: usize) -> Result<Vec<SearchHit>> {
+        let text = text.to_ascii_lowercase();
+        Ok(self.cwms[ds_uuid].iter()
+            .flat_map(|cwm| cwm.messages.iter().map(|m| (cwm.chat.id, m)))
+            .filter(|(_, m)| from_ids_option.map_or(true, |ids| ids.contains(&m.from_id)))
+            .filter(|(_, m)| after_option.is_none_or(|(ts, id)| (m.timestamp, m.internal_id) < (ts.0, id.0)))
+            .filter(|(_, m)| m.searchable_string.to_ascii_lowercase().contains(&text))
+            .sorted_by_key(|(_, m)| cmp::Reverse((m.timestamp, m.internal_id)))
+            .take(limit)
+            .map(|(chat_id, m)| SearchHit { chat_id, message: m.clone() })
+            .collect_vec())
+    }
+
+    fn messages_calendar(&self, chat: &Chat) -> Result<Vec<CalendarDay>> {
+        let mut counts: BTreeMap<String, i32> = BTreeMap::new();
+        for m in self.messages_option(&chat.ds_uuid, chat.id).unwrap() {
+            *counts.entry(local_date_string(m.timestamp)?).or_default() += 1;
+        }
+        Ok(counts.into_iter().map(|(date, messages_count)| CalendarDay { date, messages_count }).collect_vec())
     }
 
-    fn update_dataset(&mut self, old_uuid: PbUuid, ds: Dataset) -> Result<Dataset> {
-        ensure!(old_uuid == ds.uuid, "Changing dataset UUID is not supported");
+    fn chat_folders(&self) -> Result<Vec<ChatFolder>> {
+        Ok(vec![])
+    }
 
-        let mut cache = self.cache.inner.write().expect("cache write lock");
-        if let Some(old_ds) = cache.datasets.iter_mut().find(|ds| ds.uuid == old_uuid) {
-            *old_ds = ds;
-            Ok(old_ds.clone())
-        } else {
-            err!("Dataset with UUID {} not found", old_uuid.value)
-        }
+    fn redaction_log(&self, _ds_uuid: &PbUuid) -> Result<Vec<RedactionLogEntry>> {
+        Ok(vec![])
     }
 
-    fn delete_dataset(&mut self, uuid: PbUuid) -> EmptyRes {
-        let mut cache = self.cache.inner.write().expect("cache write lock");
-        if let Some(ds_idx) = cache.datasets.iter().position(|ds| ds.uuid == uuid) {
-            cache.datasets.remove(ds_idx);
-            cache.users.remove(&uuid);
-            self.ds_roots.remove(&uuid);
-            self.cwms.remove(&uuid);
-            Ok(())
-        } else {
-            err!("Dataset with UUID {} not found", uuid.value)
-        }
+    fn audit_log(&self) -> Result<Vec<AuditLogEntry>> {
+        Ok(vec![])
     }
 
-    fn insert_user(&mut self, _user: User, _is_myself: bool) -> Result<User> {
-        err!("InMemoryDao does not implement inserting users")
+    fn trash(&self) -> Result<Vec<TrashItem>> {
+        Ok(vec![])
     }
 
-    fn update_user(&mut self, _old_id: UserId, _user: User) -> Result<User> {
-        err!("InMemoryDao does not implement updating users")
+    fn retention_rules(&self, _ds_uuid: &PbUuid) -> Result<Vec<RetentionRule>> {
+        Ok(vec![])
     }
 
-    fn update_user_profile_pics(&mut self, _user: User, _new_profile_pics: Vec<AbsoluteProfilePicture>) -> Result<User> {
-        err!("InMemoryDao does not implement updating user profile pictures")
+    fn import_batches(&self, ds_uuid: &PbUuid) -> Result<Vec<ImportBatch>> {
+        Ok(self.import_batches.get(ds_uuid).cloned().into_iter().collect_vec())
     }
 
-    fn insert_chat(&mut self, _chat: Chat, _src_ds_root: &DatasetRoot) -> Result<Chat> {
-        err!("InMemoryDao does not implement inserting chats")
+    fn message_import_batch_uuids(&self, chat: &Chat, internal_ids: &[MessageInternalId]) -> Result<Vec<Option<PbUuid>>> {
+        let batch_uuid_option = self.import_batches.get(&chat.ds_uuid).map(|b| b.uuid.clone());
+        Ok(internal_ids.iter().map(|_| batch_uuid_option.clone()).collect_vec())
     }
 
-    fn update_chat(&mut self, _old_id: ChatId, _chat: Chat) -> Result<Chat> {
-        err!("InMemoryDao does not implement updating chats")
+    fn media_annotations(&self, _ds_uuid: &PbUuid) -> Result<Vec<MediaAnnotation>> {
+        Ok(vec![])
     }
 
-    fn delete_chat(&mut self, chat: Chat) -> EmptyRes {
-        let chat_id = chat.id;
-        if let Some(cwms) = self.cwms.get_mut(&chat.ds_uuid) {
-            if let Some(idx) = cwms.iter().position(|cwm| cwm.chat.id == chat_id) {
-                cwms.remove(idx);
-                self.remove_orphan_users();
-                Ok(())
-            } else {
-                err!("Chat with ID {} not found", chat_id)
-            }
-        } else {
-            err!("Dataset with UUID {} not found", chat.ds_uuid.value)
-        }
+    fn user_identities(&self, ds_uuid: &PbUuid) -> Result<Vec<UserIdentity>> {
+        Ok(self.user_identities.get(ds_uuid).into_iter().flatten()
+            .sorted_by(|a, b| (&a.source, &a.source_key).cmp(&(&b.source, &b.source_key)))
+            .cloned()
+            .collect_vec())
     }
 
-    fn combine_chats(&mut self, _master_chat: Chat, _slave_chat: Chat) -> EmptyRes {
-        err!("InMemoryDao does not implement combining chats")
+    fn user_aliases(&self) -> Result<Vec<UserAlias>> {
+        Ok(vec![])
     }
 
-    fn insert_messages(&mut self, _msgs: Vec<Message>, _chat: &Chat, _src_ds_root: &DatasetRoot) -> EmptyRes {
-        err!("InMemoryDao does not implement inserting messages")
+    fn chat_name_history(&self, chat: &Chat) -> Result<Vec<ChatNameEntry>> {
+        let msgs = self.messages_option(&chat.ds_uuid, chat.id).context("Chat not found")?;
+        use message_service::SealedValueOptional::*;
+        Ok(msgs.iter()
+            .filter_map(|m| match m.typed() {
+                message_service_pat!(GroupCreate(MessageServiceGroupCreate { title, .. })) |
+                message_service_pat!(GroupEditTitle(MessageServiceGroupEditTitle { title })) =>
+                    Some(ChatNameEntry { name: title.clone(), timestamp_option: Some(m.timestamp) }),
+                _ => None
+            })
+            .collect_vec())
     }
-}
 
-impl ShiftableChatHistoryDao for InMemoryDao {
-    fn shift_dataset_time(&mut self, uuid: &PbUuid, hours_shift: i32) -> EmptyRes {
-        let timestamp_shift: i64 = (hours_shift * 60 * 60).into();
-        let cwms = self.cwms.get_mut(uuid).unwrap();
-        for cwm in cwms.iter_mut() {
-            for m in cwm.messages.iter_mut() {
-                m.timestamp += timestamp_shift;
-                match m.typed_mut() {
-                    message::Typed::Regular(mr) =>
-                        mr.edit_timestamp_option.iter_mut().for_each(|ts| *ts += timestamp_shift),
-                    message::Typed::Service(_) => { /* NOOP */ }
-                }
-            }
-        }
-        Ok(())
+    fn notes(&self, _ds_uuid: &PbUuid, _chat_id_option: Option<i64>) -> Result<Vec<Note>> {
+        Ok(vec![])
+    }
+
+    fn searchable_string_settings(&self, _ds_uuid: &PbUuid) -> Result<SearchableStringSettings> {
+        Ok(SearchableStringSettings::default())
+    }
+
+    fn blocked_users_policy(&self, _ds_uuid: &PbUuid) -> Result<BlockedUsersPolicy> {
+        Ok(BlockedUsersPolicy::default())
+    }
+
+    fn remote_media_root(&self, _ds_uuid: &PbUuid) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn merge_templates(&self, _ds_uuid: &PbUuid) -> Result<Vec<MergeTemplate>> {
+        Ok(vec![])
     }
 }
 
@@ -124,6 +124,29 @@ fn messages_befoer_after_slice() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn messages_around_message() -> EmptyRes {
+    let dao_holder = create_specific_dao();
+    let dao = dao_holder.dao;
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let chat = dao.chats(&ds_uuid)?.remove(0).chat;
+    let msgs = &dao.cwms[&ds_uuid][0].messages;
+    let len = msgs.len();
+
+    assert_eq!(dao.messages_around(&chat, msgs[0].internal_id(), 0, 0)?, msgs.smart_slice(0..=0));
+    assert_eq!(dao.messages_around(&chat, msgs[0].internal_id(), 1000, 0)?, msgs.smart_slice(0..=0));
+    assert_eq!(dao.messages_around(&chat, msgs[0].internal_id(), 1000, 2)?, msgs.smart_slice(0..=2));
+    assert_eq!(dao.messages_around(&chat, msgs[2].internal_id(), 1, 1)?, msgs.smart_slice(1..=3));
+    assert_eq!(dao.messages_around(&chat, msgs[2].internal_id(), 2, 0)?, msgs.smart_slice(0..=2));
+    assert_eq!(dao.messages_around(&chat, msgs[2].internal_id(), 0, 2)?, msgs.smart_slice(2..=4));
+    assert_eq!(dao.messages_around(&chat, msgs[len - 1].internal_id(), 1, 1000)?, msgs.smart_slice(-2..));
+    assert_eq!(dao.messages_around(&chat, msgs[len - 2].internal_id(), 1000, 1000)?, *msgs);
+
+    assert!(dao.messages_around(&chat, MessageInternalId(i64::MAX), 1, 1).is_err());
+
+    Ok(())
+}
+
 #[test]
 fn messages_around() -> EmptyRes {
     let dao_holder = create_specific_dao();
@@ -172,6 +195,77 @@ fn messages_around() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn date_navigation() -> EmptyRes {
+    let dao_holder = create_specific_dao();
+    let dao = dao_holder.dao;
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let chat = dao.chats(&ds_uuid)?.remove(0).chat;
+    let msgs = &dao.cwms[&ds_uuid][0].messages;
+    let len = msgs.len();
+
+    assert_eq!(dao.first_message_on_or_after(&chat, Timestamp::MIN)?.as_ref(), Some(&msgs[0]));
+    assert_eq!(dao.first_message_on_or_after(&chat, msgs[0].timestamp())?.as_ref(), Some(&msgs[0]));
+    assert_eq!(dao.first_message_on_or_after(&chat, Timestamp(msgs[0].timestamp + 1))?.as_ref(), Some(&msgs[1]));
+    assert_eq!(dao.first_message_on_or_after(&chat, msgs[len - 1].timestamp())?.as_ref(), Some(&msgs[len - 1]));
+    assert_eq!(dao.first_message_on_or_after(&chat, Timestamp(msgs[len - 1].timestamp + 1))?, None);
+
+    let calendar = dao.messages_calendar(&chat)?;
+    let expected = msgs.iter()
+        .map(|m| local_date_string(m.timestamp).unwrap())
+        .counts()
+        .into_iter()
+        .sorted()
+        .map(|(date, count)| CalendarDay { date, messages_count: count as i32 })
+        .collect_vec();
+    assert_eq!(calendar, expected);
+    assert_eq!(calendar.iter().map(|d| d.messages_count as usize).sum::<usize>(), len);
+
+    Ok(())
+}
+
+#[test]
+fn datasets_diff_options() -> EmptyRes {
+    let msgs_a = (0..=2).map(|i| create_regular_message(i, 1)).collect_vec();
+    let mut msgs_b = msgs_a.clone();
+    msgs_b[0].searchable_string = "Something else".to_owned();
+    msgs_b[1].timestamp += 3;
+    msgs_b[2].text = vec![RichText::make_bold("Hello there, 2!".to_owned())];
+    let helper = MergerHelper::new_from_daos(create_simple_dao(true, "One", msgs_a, 2, &|_, _, _| {}),
+                                             create_simple_dao(false, "One", msgs_b, 2, &|_, _, _| {}));
+    let (m_dao, s_dao) = (helper.m.dao_holder.dao.as_ref(), helper.s.dao_holder.dao.as_ref());
+    let (m_uuid, s_uuid) = (&helper.m.ds.uuid, &helper.s.ds.uuid);
+    // Chat images are random, make them the same
+    let chat_img = |dao: &dyn ChatHistoryReader, cwd: &ChatWithDetails|
+        dao.dataset_root(&cwd.chat.ds_uuid).unwrap().to_absolute(cwd.chat.img_path_option.as_ref().unwrap());
+    std::fs::copy(chat_img(m_dao, helper.m.cwd()), chat_img(s_dao, helper.s.cwd()))?;
+
+    let diff = |options: DiffOptions| -> Result<Vec<String>> {
+        let options = DiffOptions { chat_ids: vec![helper.m.cwd().chat.id], ..options };
+        ok(get_datasets_diff(m_dao, m_uuid, s_dao, s_uuid, 10, &options)?.into_iter().map(|d| d.message).collect_vec())
+    };
+
+    // Datasets themselves differ
+    assert!(!get_datasets_diff(m_dao, m_uuid, s_dao, s_uuid, 10, &DiffOptions::default())?.is_empty());
+
+    assert_eq!(diff(DiffOptions::default())?, vec![
+        "Message #1 for chat 'Chat One' (#1) differs".to_owned(),
+        "Message #2 for chat 'Chat One' (#1) differs".to_owned(),
+    ]);
+    assert_eq!(diff(DiffOptions {
+        timestamp_precision_sec: Some(60),
+        ignore_text_formatting: true,
+        ..Default::default()
+    })?, Vec::<String>::new());
+    assert_eq!(diff(DiffOptions {
+        timestamp_precision_sec: Some(60),
+        ignore_text_formatting: true,
+        check_searchable_strings: true,
+        ..Default::default()
+    })?, vec!["Message #0 searchable string for chat 'Chat One' (#1) differs".to_owned()]);
+    Ok(())
+}
+
 //
 // Helpers
 //
@@ -226,6 +320,8 @@ pub fn create_specific_dao() -> InMemoryDaoHolder {
                 member_ids: users.iter().map(|u| u.id).collect_vec(),
                 msg_count: messages.len() as i32,
                 main_chat_id: None,
+                archived: None,
+                hidden: None,
             },
             messages,
         }
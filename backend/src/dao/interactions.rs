@@ -0,0 +1,69 @@
+use itertools::Itertools;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "interactions_tests.rs"]
+mod tests;
+
+/// Message sent within this many seconds after a message of someone else is considered a response to it,
+/// unless it's an explicit reply.
+pub const DEFAULT_MAX_GAP_SEC: i32 = 5 * 60;
+
+/// Who responds to whom in a chat, as square matrices suitable for a chord diagram.
+///
+/// An explicit reply counts as a response to the author of the replied message. Otherwise, a message sent
+/// within `max_gap_sec` after a message of another user counts as a response to that user.
+/// Self-responses and service messages are ignored. Users are ordered by ID, and include chat members
+/// as well as every sender.
+pub fn interaction_matrix(dao: &dyn ChatHistoryReader, cwd: &ChatWithDetails, max_gap_sec: i32) -> Result<InteractionMatrixResponse> {
+    let mut user_ids = cwd.chat.member_ids.clone();
+    let mut authors_by_source_id: HashMap<i64, i64> = HashMap::new();
+    // (from, to) -> count
+    let mut replies: HashMap<(i64, i64), i32> = HashMap::new();
+    let mut adjacent: HashMap<(i64, i64), i32> = HashMap::new();
+
+    let mut prev_option: Option<(i64, i64)> = None;
+    let mut offset = 0;
+    loop {
+        let msgs = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+        for msg in msgs.iter() {
+            let message_regular_pat! { reply_to_message_id_option, .. } = msg.typed() else { continue };
+            user_ids.push(msg.from_id);
+            if let Some(source_id) = msg.source_id_option {
+                authors_by_source_id.insert(source_id, msg.from_id);
+            }
+
+            let reply_author_option = reply_to_message_id_option.and_then(|id| authors_by_source_id.get(&id).cloned());
+            match (reply_author_option, prev_option) {
+                (Some(to_id), _) => {
+                    if to_id != msg.from_id {
+                        *replies.entry((msg.from_id, to_id)).or_default() += 1;
+                    }
+                }
+                (None, Some((prev_from_id, prev_timestamp)))
+                if prev_from_id != msg.from_id && msg.timestamp - prev_timestamp <= max_gap_sec as i64 => {
+                    *adjacent.entry((msg.from_id, prev_from_id)).or_default() += 1;
+                }
+                _ => { /* Not a response */ }
+            }
+            prev_option = Some((msg.from_id, msg.timestamp));
+        }
+        if msgs.len() < BATCH_SIZE { break; }
+        offset += BATCH_SIZE;
+    }
+
+    let user_ids = user_ids.into_iter().sorted().dedup().collect_vec();
+    let to_matrix = |counts: &HashMap<(i64, i64), i32>| -> Vec<i32> {
+        user_ids.iter()
+            .cartesian_product(user_ids.iter())
+            .map(|(from_id, to_id)| counts.get(&(*from_id, *to_id)).cloned().unwrap_or(0))
+            .collect_vec()
+    };
+    Ok(InteractionMatrixResponse {
+        reply_counts: to_matrix(&replies),
+        adjacent_counts: to_matrix(&adjacent),
+        user_ids,
+    })
+}
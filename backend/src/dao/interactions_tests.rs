@@ -0,0 +1,69 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn building_matrix() -> EmptyRes {
+    let msgs = vec![
+        msg(0, 1, 0, None),
+        // Quick response
+        msg(1, 2, 60, None),
+        // Late reply
+        msg(2, 3, 3600, Some(0)),
+        // Self-response
+        msg(3, 3, 3610, None),
+        msg(4, 1, 3620, Some(3)),
+        // Too late to be a response
+        msg(5, 2, 90000, None),
+        // Replied message is unknown, so it's just a quick response
+        msg(6, 1, 90010, Some(100)),
+        // Self-reply
+        msg(7, 1, 90020, Some(6)),
+    ];
+    let dao_holder = create_simple_dao(false, "test", msgs, 4, &|_, _, _| {});
+    let dao = dao_holder.dao.as_ref();
+    let cwd = dao.chats(&dao.ds_uuid())?.remove(0);
+
+    let matrix = interaction_matrix(dao, &cwd, DEFAULT_MAX_GAP_SEC)?;
+    assert_eq!(matrix.user_ids, vec![1, 2, 3, 4]);
+    assert_eq!(matrix.reply_counts, vec![
+        0, 0, 1, 0,
+        0, 0, 0, 0,
+        1, 0, 0, 0,
+        0, 0, 0, 0,
+    ]);
+    assert_eq!(matrix.adjacent_counts, vec![
+        0, 1, 0, 0,
+        1, 0, 0, 0,
+        0, 0, 0, 0,
+        0, 0, 0, 0,
+    ]);
+
+    // With a larger gap, late messages become responses too
+    let matrix = interaction_matrix(dao, &cwd, 100_000)?;
+    assert_eq!(matrix.adjacent_counts, vec![
+        0, 1, 0, 0,
+        2, 0, 0, 0,
+        0, 0, 0, 0,
+        0, 0, 0, 0,
+    ]);
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn msg(idx: usize, user_id: usize, time_offset: i64, reply_to_option: Option<i64>) -> Message {
+    let mut msg = create_regular_message(idx, user_id);
+    msg.timestamp = create_regular_message(0, user_id).timestamp + time_offset;
+    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+    mr.reply_to_message_id_option = reply_to_option;
+    msg
+}
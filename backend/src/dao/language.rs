@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "language_tests.rs"]
+mod tests;
+
+/// Texts with fewer letters than this are too short to tell their language.
+const MIN_LETTERS: usize = 10;
+
+/// Frequent short words of languages written in Latin script, used to tell them apart.
+const LATIN_STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "is", "are", "you", "to", "of", "it", "that", "this", "with", "for", "have", "not", "was",
+             "what", "but", "be", "my", "on"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "ich", "du", "es", "mit", "ein", "eine", "auf", "zu", "sie",
+             "wir", "auch", "den", "sind", "noch"]),
+    ("fr", &["le", "la", "les", "et", "est", "je", "tu", "pas", "une", "des", "que", "qui", "dans", "pour", "avec",
+             "sur", "vous", "nous", "ce", "mais"]),
+    ("es", &["el", "los", "las", "y", "es", "que", "de", "no", "un", "una", "por", "con", "para", "pero", "está",
+             "como", "muy", "yo", "qué", "también"]),
+    ("it", &["il", "lo", "gli", "e", "è", "che", "non", "di", "un", "una", "per", "con", "sono", "ma", "io", "ho",
+             "anche", "questo", "molto", "come"]),
+    ("pt", &["o", "os", "as", "e", "é", "que", "não", "de", "um", "uma", "por", "com", "para", "mas", "eu", "você",
+             "está", "muito", "isso", "também"]),
+    ("nl", &["de", "het", "een", "en", "is", "niet", "ik", "je", "dat", "van", "met", "op", "voor", "maar", "zijn",
+             "ook", "wat", "er", "heb", "naar"]),
+    ("pl", &["i", "w", "nie", "się", "to", "na", "jest", "że", "z", "do", "co", "jak", "ale", "tak", "mnie", "już",
+             "czy", "jestem", "ty", "ja"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Han,
+    Kana,
+    Hangul,
+    Thai,
+    Devanagari,
+    Georgian,
+    Armenian,
+}
+
+impl Script {
+    fn of(c: char) -> Option<Script> {
+        use Script::*;
+        Some(match c {
+            'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' if c.is_alphabetic() => Latin,
+            '\u{0370}'..='\u{03FF}' => Greek,
+            '\u{0400}'..='\u{04FF}' => Cyrillic,
+            '\u{0530}'..='\u{058F}' => Armenian,
+            '\u{0590}'..='\u{05FF}' => Hebrew,
+            '\u{0600}'..='\u{06FF}' => Arabic,
+            '\u{0900}'..='\u{097F}' => Devanagari,
+            '\u{0E00}'..='\u{0E7F}' => Thai,
+            '\u{10A0}'..='\u{10FF}' => Georgian,
+            '\u{3040}'..='\u{30FF}' => Kana,
+            '\u{4E00}'..='\u{9FFF}' => Han,
+            '\u{AC00}'..='\u{D7AF}' => Hangul,
+            _ => return None,
+        })
+    }
+}
+
+/// Best guess of a text language as an ISO 639-1 code, `None` if text is too short or language is not recognized.
+///
+/// Language is mostly determined by the script, languages sharing a script are told apart by their specific letters
+/// or frequent words. Only a handful of widespread languages are recognized.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut script_counts: HashMap<Script, usize> = HashMap::new();
+    for c in text.chars() {
+        if let Some(script) = Script::of(c) {
+            *script_counts.entry(script).or_default() += 1;
+        }
+    }
+    if script_counts.values().sum::<usize>() < MIN_LETTERS {
+        return None;
+    }
+    let lowercase = text.to_lowercase();
+    let has = |letters: &[char]| lowercase.chars().any(|c| letters.contains(&c));
+    // Japanese mixes kana with Han characters, so any noticeable amount of kana points to it
+    if script_counts.get(&Script::Kana).is_some_and(|&kana| kana * 10 >= script_counts.values().sum::<usize>()) {
+        return Some("ja");
+    }
+    let (script, _) = script_counts.into_iter().sorted().max_by_key(|(_, count)| *count)?;
+    match script {
+        Script::Latin => detect_latin_language(text),
+        Script::Cyrillic if has(&['і', 'ї', 'є', 'ґ']) => Some("uk"),
+        Script::Cyrillic if has(&['ў']) => Some("be"),
+        Script::Cyrillic => Some("ru"),
+        Script::Arabic if has(&['پ', 'چ', 'ژ', 'گ']) => Some("fa"),
+        Script::Arabic => Some("ar"),
+        Script::Greek => Some("el"),
+        Script::Hebrew => Some("he"),
+        Script::Han => Some("zh"),
+        Script::Kana => Some("ja"),
+        Script::Hangul => Some("ko"),
+        Script::Thai => Some("th"),
+        Script::Devanagari => Some("hi"),
+        Script::Georgian => Some("ka"),
+        Script::Armenian => Some("hy"),
+    }
+}
+
+fn detect_latin_language(text: &str) -> Option<&'static str> {
+    let words = text.split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect_vec();
+    let mut best: Option<(&'static str, usize)> = None;
+    for &(lang, stopwords) in LATIN_STOPWORDS.iter() {
+        let score = words.iter().filter(|w| stopwords.contains(&w.as_str())).count();
+        // Ties are resolved in favor of the language listed first
+        if score > 0 && best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((lang, score));
+        }
+    }
+    best.map(|(lang, _)| lang)
+}
+
+/// Number of text messages per chat, sender, month and detected language, for the whole dataset or a single chat.
+/// Messages without text are not counted, messages whose language couldn't be detected have no language.
+///
+/// Stats are ordered by chat ID, sender ID, month and language.
+pub fn language_stats(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid, chat_id_option: Option<i64>) -> Result<Vec<LanguageStat>> {
+    let cwds = dao.chats(ds_uuid)?.into_iter()
+        .filter(|cwd| chat_id_option.is_none_or(|id| cwd.chat.id == id))
+        .collect_vec();
+    if let Some(chat_id) = chat_id_option {
+        ensure!(!cwds.is_empty(), "Chat {chat_id} not found");
+    }
+
+    let mut counts: BTreeMap<(i64, i64, String, Option<&'static str>), i32> = BTreeMap::new();
+    for cwd in cwds.iter() {
+        let mut offset = 0;
+        loop {
+            let msgs = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+            for msg in msgs.iter() {
+                let text = message_prose(msg);
+                if text.trim().is_empty() { continue; }
+                let month = local_timestamp_string(msg.timestamp, "%Y-%m")?;
+                *counts.entry((cwd.chat.id, msg.from_id, month, detect_language(&text))).or_default() += 1;
+            }
+            if msgs.len() < BATCH_SIZE { break; }
+            offset += BATCH_SIZE;
+        }
+    }
+    Ok(counts.into_iter()
+        .map(|((chat_id, from_id, month, language), messages_count)| LanguageStat {
+            chat_id,
+            from_id,
+            month,
+            language: language.map(|l| l.to_owned()),
+            messages_count,
+        })
+        .collect_vec())
+}
+
+/// Text written by a message author, excluding links and code.
+fn message_prose(msg: &Message) -> String {
+    use rich_text_element::Val;
+    msg.text.iter()
+        .filter(|rte| !matches!(rte.val, Some(Val::Link(_)) | Some(Val::PrefmtInline(_)) | Some(Val::PrefmtBlock(_))))
+        .filter_map(|rte| rte.get_text())
+        .join(" ")
+}
@@ -0,0 +1,82 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn detecting_language() {
+    assert_eq!(detect_language("Hi!"), None);
+    assert_eq!(detect_language("1234567890 :) https://example.org"), None);
+    assert_eq!(detect_language("Zyxwvut qrstp lkjhg"), None);
+
+    assert_eq!(detect_language("What is that thing on the table?"), Some("en"));
+    assert_eq!(detect_language("Ich weiß nicht, ob das stimmt"), Some("de"));
+    assert_eq!(detect_language("Je ne sais pas, mais c'est possible"), Some("fr"));
+    assert_eq!(detect_language("No sé, pero el perro está muy cansado"), Some("es"));
+    assert_eq!(detect_language("Nie wiem, czy to jest prawda"), Some("pl"));
+
+    assert_eq!(detect_language("Привет, как дела у тебя?"), Some("ru"));
+    assert_eq!(detect_language("Привіт, як у тебе справи?"), Some("uk"));
+    assert_eq!(detect_language("Γεια σου, τι κάνεις σήμερα;"), Some("el"));
+    assert_eq!(detect_language("今日はとても暑いですね、本当に"), Some("ja"));
+    assert_eq!(detect_language("我们明天一起去北京看看朋友吧"), Some("zh"));
+    assert_eq!(detect_language("안녕하세요 오늘 날씨가 정말 좋네요"), Some("ko"));
+
+    // Dominant script wins
+    assert_eq!(detect_language("Смотри, это мой новый laptop, правда классный?"), Some("ru"));
+}
+
+#[test]
+fn counting_stats() -> EmptyRes {
+    let texts = [
+        (1, "What is that thing on the table?"),
+        (2, "Привет, как дела у тебя?"),
+        (1, "ok"),
+        (1, "Where are you going with this?"),
+        (2, "Nie wiem, czy to jest prawda"),
+    ];
+    let msgs = texts.iter().enumerate()
+        .map(|(idx, (user_id, _))| create_regular_message(idx, *user_id))
+        .collect_vec();
+    let dao_holder = create_simple_dao(false, "test", msgs, 2, &|_, _, msg| {
+        let idx = msg.source_id_option.unwrap() as usize;
+        msg.text = vec![RichText::make_plain(texts[idx].1.to_owned())];
+        // Last two messages are sent a month later
+        if idx >= 3 {
+            msg.timestamp += 31 * 24 * 60 * 60;
+        }
+    });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.ds_uuid();
+    let chat_id = dao.chats(&ds_uuid)?[0].chat.id;
+    let month = |idx: usize| -> Result<String> {
+        let ts = dao.first_messages(&dao.chats(&ds_uuid)?[0].chat, usize::MAX)?[idx].timestamp;
+        local_timestamp_string(ts, "%Y-%m")
+    };
+
+    let stat = |from_id: i64, month: String, language: Option<&str>, messages_count: i32| LanguageStat {
+        chat_id,
+        from_id,
+        month,
+        language: language.map(|l| l.to_owned()),
+        messages_count,
+    };
+    let mut expected = vec![
+        stat(1, month(0)?, None, 1),
+        stat(1, month(0)?, Some("en"), 1),
+        stat(1, month(3)?, Some("en"), 1),
+        stat(2, month(1)?, Some("ru"), 1),
+        stat(2, month(4)?, Some("pl"), 1),
+    ];
+    assert_ne!(month(0)?, month(3)?);
+    expected.sort_by(|a, b| (a.from_id, &a.month, &a.language).cmp(&(b.from_id, &b.month, &b.language)));
+    assert_eq!(language_stats(dao, &ds_uuid, None)?, expected);
+    assert_eq!(language_stats(dao, &ds_uuid, Some(chat_id))?, expected);
+    assert!(language_stats(dao, &ds_uuid, Some(chat_id + 1)).is_err());
+    Ok(())
+}
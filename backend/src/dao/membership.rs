@@ -0,0 +1,126 @@
+use itertools::Itertools;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "membership_tests.rs"]
+mod tests;
+
+/// Member is identified by user ID if their name could be resolved, by the name itself otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MemberKey {
+    User(i64),
+    Name(String),
+}
+
+/// Reconstruct periods of chat membership from group creation, invite and removal service messages.
+///
+/// `member_ids` only tell who was a member at the time of export, while service messages refer to members by names.
+/// Names are resolved against chat members first, and then against all dataset users.
+/// Senders (including those missing from `member_ids`) with no preceding join event are considered to have joined
+/// before the earliest message, so their intervals have no join time.
+/// Chat members that never showed up in messages get an interval without bounds.
+///
+/// Intervals are listed in order of their first evidence, intervals of the same member never overlap.
+pub fn membership_timeline(dao: &dyn ChatHistoryReader, cwd: &ChatWithDetails) -> Result<Vec<MembershipInterval>> {
+    let ds_users = dao.users(&cwd.chat.ds_uuid)?;
+    let resolve = |name: &str| -> MemberKey {
+        cwd.resolve_member(name)
+            .or_else(|| ds_users.iter().find(|u| u.pretty_name() == name))
+            .map(|u| MemberKey::User(u.id))
+            .unwrap_or_else(|| MemberKey::Name(name.to_owned()))
+    };
+
+    let mut timeline = Timeline::default();
+    let mut offset = 0;
+    loop {
+        let msgs = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+        for msg in msgs.iter() {
+            let sender = MemberKey::User(msg.from_id);
+            use message_service::SealedValueOptional::*;
+            match msg.typed() {
+                message_service_pat!(GroupCreate(MessageServiceGroupCreate { members, .. })) => {
+                    timeline.join(sender, msg.timestamp);
+                    for member in members.iter() {
+                        timeline.join(resolve(member), msg.timestamp);
+                    }
+                }
+                message_service_pat!(GroupInviteMembers(MessageServiceGroupInviteMembers { members })) => {
+                    let keys = members.iter().map(|m| resolve(m)).collect_vec();
+                    // Joining by link is recorded as self-invitation, possibly with no members listed
+                    if keys.is_empty() || keys.contains(&sender) {
+                        timeline.join(sender, msg.timestamp);
+                    } else {
+                        timeline.ensure_joined(sender, Some(msg.timestamp));
+                    }
+                    for key in keys {
+                        timeline.join(key, msg.timestamp);
+                    }
+                }
+                message_service_pat!(GroupRemoveMembers(MessageServiceGroupRemoveMembers { members })) => {
+                    let keys = members.iter().map(|m| resolve(m)).collect_vec();
+                    // Otherwise member left on their own
+                    if !keys.contains(&sender) {
+                        timeline.ensure_joined(sender, Some(msg.timestamp));
+                    }
+                    for key in keys {
+                        timeline.leave(key, msg.timestamp);
+                    }
+                }
+                _ => timeline.ensure_joined(sender, Some(msg.timestamp)),
+            }
+        }
+        if msgs.len() < BATCH_SIZE { break; }
+        offset += BATCH_SIZE;
+    }
+
+    for member_id in cwd.chat.member_ids.iter() {
+        timeline.ensure_joined(MemberKey::User(*member_id), None);
+    }
+
+    Ok(timeline.intervals.into_iter()
+        .map(|(key, joined_timestamp, left_timestamp)| {
+            let (user_id, member_name) = match key {
+                MemberKey::User(id) => {
+                    let name = ds_users.iter().find(|u| u.id == id).map(|u| u.pretty_name());
+                    (Some(id), name.unwrap_or_else(|| UNKNOWN.to_owned()))
+                }
+                MemberKey::Name(name) => (None, name),
+            };
+            MembershipInterval { user_id, member_name, joined_timestamp, left_timestamp }
+        })
+        .collect_vec())
+}
+
+#[derive(Default)]
+struct Timeline {
+    /// (member, joined, left)
+    intervals: Vec<(MemberKey, Option<i64>, Option<i64>)>,
+    /// Indexes of intervals that aren't closed yet
+    open: HashMap<MemberKey, usize>,
+}
+
+impl Timeline {
+    fn join(&mut self, key: MemberKey, timestamp: i64) {
+        if self.open.contains_key(&key) { return; }
+        self.open.insert(key.clone(), self.intervals.len());
+        self.intervals.push((key, Some(timestamp), None));
+    }
+
+    /// Member is evidently there. If they weren't seen before, they were there since before the earliest message,
+    /// otherwise they re-joined with no trace, no later than the given time (if known).
+    fn ensure_joined(&mut self, key: MemberKey, timestamp_option: Option<i64>) {
+        if self.open.contains_key(&key) { return; }
+        let seen_before = self.intervals.iter().any(|(k, _, _)| *k == key);
+        self.open.insert(key.clone(), self.intervals.len());
+        self.intervals.push((key, if seen_before { timestamp_option } else { None }, None));
+    }
+
+    fn leave(&mut self, key: MemberKey, timestamp: i64) {
+        match self.open.remove(&key) {
+            Some(idx) => self.intervals[idx].2 = Some(timestamp),
+            None => self.intervals.push((key, None, Some(timestamp))),
+        }
+    }
+}
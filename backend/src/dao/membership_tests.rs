@@ -0,0 +1,67 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use message_service::SealedValueOptional::*;
+
+use super::*;
+
+#[test]
+fn reconstructing_timeline() -> EmptyRes {
+    let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect_vec();
+    let msgs = vec![
+        service(0, 1, GroupCreate(MessageServiceGroupCreate { title: "Chat".to_owned(), members: names(&["User 2"]) })),
+        create_regular_message(1, 3),
+        service(2, 1, GroupInviteMembers(MessageServiceGroupInviteMembers { members: names(&["User 4", "Stranger"]) })),
+        // Left on their own
+        service(3, 2, GroupRemoveMembers(MessageServiceGroupRemoveMembers { members: names(&["User 2"]) })),
+        // Re-joined without a trace
+        create_regular_message(4, 2),
+        service(5, 1, GroupRemoveMembers(MessageServiceGroupRemoveMembers { members: names(&["Stranger"]) })),
+    ];
+    let ts = msgs.iter().map(|m| m.timestamp).collect_vec();
+    let dao_holder = create_simple_dao(false, "test", msgs, 4, &|_, _, _| {});
+    let dao = dao_holder.dao.as_ref();
+    let cwd = dao.chats(&dao.ds_uuid())?.remove(0);
+
+    let interval = |user_id: Option<i64>, member_name: &str, joined: Option<i64>, left: Option<i64>| MembershipInterval {
+        user_id,
+        member_name: member_name.to_owned(),
+        joined_timestamp: joined,
+        left_timestamp: left,
+    };
+    assert_eq!(membership_timeline(dao, &cwd)?, vec![
+        interval(Some(1), "User 1", Some(ts[0]), None),
+        interval(Some(2), "User 2", Some(ts[0]), Some(ts[3])),
+        interval(Some(3), "User 3", None, None),
+        interval(Some(4), "User 4", Some(ts[2]), None),
+        interval(None, "Stranger", Some(ts[2]), Some(ts[5])),
+        interval(Some(2), "User 2", Some(ts[4]), None),
+    ]);
+    Ok(())
+}
+
+#[test]
+fn members_without_messages() -> EmptyRes {
+    let dao_holder = create_simple_dao(false, "test", vec![create_regular_message(0, 2)], 3, &|_, _, _| {});
+    let dao = dao_holder.dao.as_ref();
+    let cwd = dao.chats(&dao.ds_uuid())?.remove(0);
+
+    let timeline = membership_timeline(dao, &cwd)?;
+    assert_eq!(timeline.iter().map(|i| i.user_id).collect_vec(), vec![Some(2), Some(1), Some(3)]);
+    assert!(timeline.iter().all(|i| i.joined_timestamp.is_none() && i.left_timestamp.is_none()));
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn service(idx: usize, user_id: usize, svo: message_service::SealedValueOptional) -> Message {
+    Message {
+        text: vec![],
+        searchable_string: "".to_owned(),
+        typed: Some(message_service!(svo)),
+        ..create_regular_message(idx, user_id)
+    }
+}
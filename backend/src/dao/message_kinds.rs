@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "message_kinds_tests.rs"]
+mod tests;
+
+/// How a message communicates, `None` for regular messages with neither text nor contents (e.g. deleted ones).
+///
+/// Text accompanying media (i.e. a caption) makes a message a text one, as does text accompanying links.
+pub fn message_kind(msg: &Message) -> Option<MessageKind> {
+    use rich_text_element::Val;
+    let message_regular_pat! { contents, .. } = msg.typed() else {
+        return Some(MessageKind::Service);
+    };
+    let (links, prose): (Vec<_>, Vec<_>) = msg.text.iter()
+        .partition(|rte| matches!(rte.val, Some(Val::Link(_))));
+    let prose = prose.iter().filter_map(|rte| rte.get_text()).join(" ");
+    let prose_words = prose.split_whitespace().filter(|w| !is_url(w)).collect_vec();
+    let has_links = !links.is_empty() || prose_words.len() < prose.split_whitespace().count();
+    Some(match prose_words.as_slice() {
+        [] if has_links => MessageKind::LinkOnly,
+        [] if !contents.is_empty() => MessageKind::MediaOnly,
+        [] => return None,
+        words if words.iter().all(|w| w.chars().all(is_emoji_char)) => MessageKind::EmojiOnly,
+        _ => MessageKind::Text,
+    })
+}
+
+/// Number of messages per chat, sender, month and kind, for the whole dataset or a single chat.
+/// Messages of no particular kind (see [message_kind]) are not counted.
+///
+/// Stats are ordered by chat ID, sender ID, month and kind.
+pub fn message_kind_stats(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid, chat_id_option: Option<i64>) -> Result<Vec<MessageKindStat>> {
+    let cwds = dao.chats(ds_uuid)?.into_iter()
+        .filter(|cwd| chat_id_option.is_none_or(|id| cwd.chat.id == id))
+        .collect_vec();
+    if let Some(chat_id) = chat_id_option {
+        ensure!(!cwds.is_empty(), "Chat {chat_id} not found");
+    }
+
+    let mut counts: BTreeMap<(i64, i64, String, i32), i32> = BTreeMap::new();
+    for cwd in cwds.iter() {
+        let mut offset = 0;
+        loop {
+            let msgs = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+            for msg in msgs.iter() {
+                let Some(kind) = message_kind(msg) else { continue };
+                let month = local_timestamp_string(msg.timestamp, "%Y-%m")?;
+                *counts.entry((cwd.chat.id, msg.from_id, month, kind as i32)).or_default() += 1;
+            }
+            if msgs.len() < BATCH_SIZE { break; }
+            offset += BATCH_SIZE;
+        }
+    }
+    Ok(counts.into_iter()
+        .map(|((chat_id, from_id, month, kind), messages_count)| MessageKindStat {
+            chat_id,
+            from_id,
+            month,
+            kind,
+            messages_count,
+        })
+        .collect_vec())
+}
+
+fn is_url(word: &str) -> bool {
+    let word = word.to_ascii_lowercase();
+    ["http://", "https://", "www."].iter().any(|prefix| word.starts_with(prefix) && word.len() > prefix.len())
+}
+
+/// Whether a character is a part of an emoji, including modifiers and joiners emoji sequences are made of.
+/// Based on code point ranges, so some pictographic symbols not rendered as emoji are counted too.
+fn is_emoji_char(c: char) -> bool {
+    matches!(c,
+        '\u{00A9}' | '\u{00AE}' | '\u{203C}' | '\u{2049}' | '\u{2122}' | '\u{2139}' |
+        '\u{2194}'..='\u{21AA}' | '\u{231A}'..='\u{23FF}' | '\u{24C2}' | '\u{25AA}'..='\u{25FE}' |
+        '\u{2600}'..='\u{27BF}' | '\u{2934}' | '\u{2935}' | '\u{2B00}'..='\u{2BFF}' |
+        '\u{3030}' | '\u{303D}' | '\u{3297}' | '\u{3299}' |
+        '\u{1F000}'..='\u{1FAFF}' |
+        // Zero-width joiner, variation selector, keycap and tags
+        '\u{200D}' | '\u{FE0F}' | '\u{20E3}' | '\u{E0020}'..='\u{E007F}')
+}
@@ -0,0 +1,100 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn classifying_messages() {
+    let msg = |text: Vec<RichTextElement>, with_contents: bool| {
+        let mut msg = create_regular_message(1, 1);
+        msg.text = text;
+        if !with_contents {
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![];
+        }
+        msg
+    };
+    let plain = |s: &str| RichText::make_plain(s.to_owned());
+    let link = || RichText::make_link(Some("this".to_owned()), "https://example.com".to_owned(), false);
+
+    assert_eq!(message_kind(&msg(vec![plain("Hello there")], false)), Some(MessageKind::Text));
+    assert_eq!(message_kind(&msg(vec![plain("Look at this")], true)), Some(MessageKind::Text));
+    assert_eq!(message_kind(&msg(vec![plain("See "), link()], false)), Some(MessageKind::Text));
+    assert_eq!(message_kind(&msg(vec![plain("wow 😀")], false)), Some(MessageKind::Text));
+
+    assert_eq!(message_kind(&msg(vec![plain("😀 👍🏻")], false)), Some(MessageKind::EmojiOnly));
+    assert_eq!(message_kind(&msg(vec![plain("❤️")], true)), Some(MessageKind::EmojiOnly));
+    assert_eq!(message_kind(&msg(vec![plain("👨‍👩‍👧")], false)), Some(MessageKind::EmojiOnly));
+
+    assert_eq!(message_kind(&msg(vec![], true)), Some(MessageKind::MediaOnly));
+    assert_eq!(message_kind(&msg(vec![plain("  ")], true)), Some(MessageKind::MediaOnly));
+
+    assert_eq!(message_kind(&msg(vec![link()], false)), Some(MessageKind::LinkOnly));
+    assert_eq!(message_kind(&msg(vec![plain("https://example.com")], true)), Some(MessageKind::LinkOnly));
+
+    assert_eq!(message_kind(&msg(vec![], false)), None);
+
+    let service = Message {
+        typed: Some(message_service!(message_service::SealedValueOptional::GroupEditTitle(
+            MessageServiceGroupEditTitle { title: "New title".to_owned() }
+        ))),
+        ..create_regular_message(1, 1)
+    };
+    assert_eq!(message_kind(&service), Some(MessageKind::Service));
+}
+
+#[test]
+fn counting_stats() -> EmptyRes {
+    let texts = [
+        (1, "Hello there"),
+        (1, "😀"),
+        (2, "🎉🎉"),
+        (2, "www.example.com"),
+        (2, "Bye"),
+    ];
+    let msgs = texts.iter().enumerate()
+        .map(|(idx, (user_id, _))| create_regular_message(idx, *user_id))
+        .collect_vec();
+    let dao_holder = create_simple_dao(false, "test", msgs, 2, &|_, _, msg| {
+        let idx = msg.source_id_option.unwrap() as usize;
+        msg.text = vec![RichText::make_plain(texts[idx].1.to_owned())];
+        let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+        mr.contents = vec![];
+        // Last message is sent a month later
+        if idx == 4 {
+            msg.timestamp += 31 * 24 * 60 * 60;
+        }
+    });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.ds_uuid();
+    let chat_id = dao.chats(&ds_uuid)?[0].chat.id;
+    let month = |idx: usize| -> Result<String> {
+        let ts = dao.first_messages(&dao.chats(&ds_uuid)?[0].chat, usize::MAX)?[idx].timestamp;
+        local_timestamp_string(ts, "%Y-%m")
+    };
+
+    let stat = |from_id: i64, month: String, kind: MessageKind, messages_count: i32| MessageKindStat {
+        chat_id,
+        from_id,
+        month,
+        kind: kind as i32,
+        messages_count,
+    };
+    let expected = vec![
+        stat(1, month(0)?, MessageKind::Text, 1),
+        stat(1, month(0)?, MessageKind::EmojiOnly, 1),
+        stat(2, month(0)?, MessageKind::EmojiOnly, 1),
+        stat(2, month(0)?, MessageKind::LinkOnly, 1),
+        stat(2, month(4)?, MessageKind::Text, 1),
+    ];
+    assert_ne!(month(0)?, month(4)?);
+    assert_eq!(message_kind_stats(dao, &ds_uuid, None)?, expected);
+    assert_eq!(message_kind_stats(dao, &ds_uuid, Some(chat_id))?, expected);
+    assert!(message_kind_stats(dao, &ds_uuid, Some(chat_id + 1)).is_err());
+    Ok(())
+}
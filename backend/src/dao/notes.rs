@@ -0,0 +1,75 @@
+use itertools::Itertools;
+
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "notes_tests.rs"]
+mod tests;
+
+/// How many messages sharing a timestamp are looked through when re-targeting a note.
+const SAME_TIMESTAMP_LOOKAHEAD: usize = 100;
+
+/// Whether the note is attached to a message with the given IDs (rather than to another message or to the chat).
+pub fn is_note_on(note: &Note, source_id_option: Option<i64>, internal_id: i64) -> bool {
+    match (note.message_source_id_option, note.message_internal_id_option) {
+        (Some(source_id), _) => source_id_option == Some(source_id),
+        (None, Some(internal_id_2)) => internal_id == internal_id_2,
+        (None, None) => false,
+    }
+}
+
+/// Notes whose text contains the given one. Search is case-insensitive.
+pub fn search_notes(notes: Vec<Note>, text: &str) -> Vec<Note> {
+    let text = text.to_lowercase();
+    notes.into_iter().filter(|note| note.text.to_lowercase().contains(&text)).collect_vec()
+}
+
+/// Re-target notes of a chat to another chat with the same messages but different internal IDs,
+/// e.g. a copy in another database or a merge result.
+///
+/// Chat notes and notes on messages with source IDs are kept as-is. Notes on messages without source IDs
+/// are matched by message timestamp, sender and text. Notes whose messages can't be found are dropped.
+/// Note IDs are kept, and are expected to be reassigned on insertion.
+pub fn retarget_notes(src_dao: &dyn ChatHistoryReader,
+                      src_chat: &Chat,
+                      dst_dao: &dyn ChatHistoryReader,
+                      dst_chat: &Chat,
+                      notes: Vec<Note>) -> Result<Vec<Note>> {
+    let mut res = Vec::with_capacity(notes.len());
+    for note in notes {
+        let message_internal_id_option = match (note.message_source_id_option, note.message_internal_id_option) {
+            (None, Some(internal_id)) => {
+                let Some(src_msg) = src_dao.message_option_by_internal_id(src_chat, MessageInternalId(internal_id))? else {
+                    log::warn!("Note #{} refers to a missing message, dropping it", note.id);
+                    continue;
+                };
+                match find_same_message(dst_dao, dst_chat, &src_msg)? {
+                    Some(dst_msg) => Some(dst_msg.internal_id),
+                    None => {
+                        log::warn!("Message of note #{} is not found in chat {}, dropping it",
+                                   note.id, dst_chat.qualified_name());
+                        continue;
+                    }
+                }
+            }
+            _ => note.message_internal_id_option,
+        };
+        res.push(Note {
+            ds_uuid: dst_chat.ds_uuid.clone(),
+            chat_id: dst_chat.id,
+            message_internal_id_option,
+            ..note
+        });
+    }
+    Ok(res)
+}
+
+fn find_same_message(dao: &dyn ChatHistoryReader, chat: &Chat, msg: &Message) -> Result<Option<Message>> {
+    let Some(first) = dao.first_message_on_or_after(chat, Timestamp(msg.timestamp))? else { return Ok(None) };
+    if first.timestamp != msg.timestamp { return Ok(None); }
+    let next = dao.messages_after(chat, first.internal_id(), SAME_TIMESTAMP_LOOKAHEAD)?;
+    Ok(std::iter::once(first)
+        .chain(next.into_iter().take_while(|m| m.timestamp == msg.timestamp))
+        .find(|m| m.from_id == msg.from_id && m.searchable_string == msg.searchable_string))
+}
@@ -0,0 +1,71 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::utils::test_utils::*;
+
+use super::*;
+
+fn note(chat: &Chat, id: i64, source_id: Option<i64>, internal_id: Option<i64>, text: &str) -> Note {
+    Note {
+        id,
+        ds_uuid: chat.ds_uuid.clone(),
+        chat_id: chat.id,
+        message_source_id_option: source_id,
+        message_internal_id_option: internal_id,
+        text: text.to_owned(),
+        timestamp: 0,
+    }
+}
+
+#[test]
+fn matching_and_searching() {
+    let chat = create_group_chat(&ZERO_PB_UUID, 1, "One", vec![1, 2], 0);
+    let notes = vec![
+        note(&chat, 1, None, None, "Whole chat"),
+        note(&chat, 2, Some(5), None, "By source ID"),
+        note(&chat, 3, None, Some(500), "by INTERNAL id"),
+    ];
+
+    assert!(!is_note_on(&notes[0], Some(5), 500));
+    assert!(is_note_on(&notes[1], Some(5), 123));
+    assert!(!is_note_on(&notes[1], None, 500));
+    assert!(is_note_on(&notes[2], None, 500));
+    assert!(!is_note_on(&notes[2], Some(6), 600));
+
+    let ids = |notes: Vec<Note>| notes.into_iter().map(|n| n.id).collect_vec();
+    assert_eq!(ids(search_notes(notes.clone(), "id")), vec![2, 3]);
+    assert_eq!(ids(search_notes(notes.clone(), "Internal")), vec![3]);
+    assert_eq!(ids(search_notes(notes.clone(), "")), vec![1, 2, 3]);
+    assert_eq!(ids(search_notes(notes, "nothing")), Vec::<i64>::new());
+}
+
+#[test]
+fn retargeting() -> EmptyRes {
+    let msgs = (1..=3).map(|i| create_regular_message(i, 1)).collect_vec();
+    // Messages have no source IDs, and internal IDs differ between DAOs. Third message is absent in destination.
+    let src_holder = create_simple_dao(true, "One", msgs.clone(), 2, &|_, _, m| {
+        m.source_id_option = None;
+    });
+    let dst_holder = create_simple_dao(false, "Two", msgs.into_iter().take(2).collect_vec(), 2, &|_, _, m| {
+        m.source_id_option = None;
+        m.internal_id += 1;
+    });
+    let (src_dao, dst_dao) = (src_holder.dao.as_ref(), dst_holder.dao.as_ref());
+    let src_chat = &src_dao.chats(&src_dao.datasets()?[0].uuid)?.remove(0).chat;
+    let dst_chat = &dst_dao.chats(&dst_dao.datasets()?[0].uuid)?.remove(0).chat;
+
+    let notes = vec![
+        note(src_chat, 1, None, None, "Whole chat"),
+        note(src_chat, 2, Some(10), None, "By source ID"),
+        note(src_chat, 3, None, Some(200), "Second"),
+        note(src_chat, 4, None, Some(300), "Third"),
+    ];
+    let retargeted = retarget_notes(src_dao, src_chat, dst_dao, dst_chat, notes)?;
+    assert_eq!(retargeted, vec![
+        note(dst_chat, 1, None, None, "Whole chat"),
+        note(dst_chat, 2, Some(10), None, "By source ID"),
+        note(dst_chat, 3, None, Some(201), "Second"),
+    ]);
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use base64::prelude::*;
+use itertools::Itertools;
+
+use crate::dao::{chat_order_key, ChatHistoryReader};
+use crate::prelude::*;
+
+/// Continuation token for keyset pagination, passed to clients as an opaque string.
+/// Refers to the sort key of the last returned entity rather than to its position, so fetching the next page
+/// doesn't require re-reading everything before it, and entities added or removed in the meantime don't break it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageToken {
+    Messages { last_id: MessageInternalId, backward: bool },
+    /// See [chat_order_key]
+    Chats { last_order_key: i64, last_id: ChatId },
+    /// Search hits across DAOs, see [crate::dao::search::search_all]
+    Search { last_timestamp: Timestamp, last_id: MessageInternalId, ds_uuid: PbUuid, key: String },
+}
+
+impl PageToken {
+    pub fn encode(&self) -> String {
+        let raw = match self {
+            PageToken::Messages { last_id, backward: false } => format!("m>{}", **last_id),
+            PageToken::Messages { last_id, backward: true } => format!("m<{}", **last_id),
+            PageToken::Chats { last_order_key, last_id } => format!("c>{last_order_key}:{}", **last_id),
+            // Key goes last, as it might contain a separator
+            PageToken::Search { last_timestamp, last_id, ds_uuid, key } =>
+                format!("s>{}:{}:{}:{key}", **last_timestamp, **last_id, ds_uuid.value),
+        };
+        BASE64_URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(token: &str) -> Result<Self> {
+        let malformed = || anyhow!("Malformed page token: {token}");
+        let raw = BASE64_URL_SAFE_NO_PAD.decode(token).ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(malformed)?;
+        let (kind, rest) = raw.split_at_checked(2).ok_or_else(malformed)?;
+        let parse_i64 = |s: &str| s.parse::<i64>().map_err(|_| malformed());
+        Ok(match (kind, rest.splitn(4, ':').collect_vec().as_slice()) {
+            ("m>", [id]) => PageToken::Messages { last_id: MessageInternalId(parse_i64(id)?), backward: false },
+            ("m<", [id]) => PageToken::Messages { last_id: MessageInternalId(parse_i64(id)?), backward: true },
+            ("c>", [order_key, id]) => PageToken::Chats { last_order_key: parse_i64(order_key)?, last_id: ChatId(parse_i64(id)?) },
+            ("s>", [timestamp, id, ds_uuid, key]) => PageToken::Search {
+                last_timestamp: Timestamp(parse_i64(timestamp)?),
+                last_id: MessageInternalId(parse_i64(id)?),
+                ds_uuid: PbUuid { value: (*ds_uuid).to_owned() },
+                key: (*key).to_owned(),
+            },
+            _ => return Err(malformed()),
+        })
+    }
+}
+
+/// Return up to `limit` messages (in chronological order) following the page token, along with the token
+/// for the next page, if there might be one.
+/// Without a token, returns either the first or (if `backward` is set) the last messages of the chat.
+/// Token carries its own direction, so `backward` is ignored when it's given.
+/// If filter is given, only matching messages are returned.
+pub fn messages_page(dao: &dyn ChatHistoryReader,
+                     chat: &Chat,
+                     page_token: Option<&str>,
+                     limit: usize,
+                     backward: bool,
+                     filter_option: Option<&MessageFilter>) -> Result<(Vec<Message>, Option<String>)> {
+    ensure!(limit > 0, "Limit is zero!");
+    let (anchor_option, backward) = match page_token.map(PageToken::decode).transpose()? {
+        None => (None, backward),
+        Some(PageToken::Messages { last_id, backward }) => (Some(last_id), backward),
+        Some(token) => bail!("Not a messages page token: {token:?}"),
+    };
+    let msgs = match (filter_option, anchor_option) {
+        (Some(filter), _) => dao.messages_filtered(chat, filter, anchor_option, backward, limit)?,
+        (None, None) if backward => dao.last_messages(chat, limit)?,
+        (None, None) => dao.first_messages(chat, limit)?,
+        (None, Some(anchor)) if backward => dao.messages_before(chat, anchor, limit)?,
+        (None, Some(anchor)) => dao.messages_after(chat, anchor, limit)?,
+    };
+    let edge_msg = if backward { msgs.first() } else { msgs.last() };
+    let next_token = edge_msg
+        .filter(|_| msgs.len() == limit)
+        .map(|m| PageToken::Messages { last_id: m.internal_id(), backward }.encode());
+    Ok((msgs, next_token))
+}
+
+/// Return up to `limit` chats following the page token (or from the start), along with the token
+/// for the next page, if there is one.
+/// Chats should be ordered by [chat_order_key], as returned by [ChatHistoryReader::chats].
+pub fn chats_page(cwds: Vec<ChatWithDetails>,
+                  page_token: Option<&str>,
+                  limit: usize) -> Result<(Vec<ChatWithDetails>, Option<String>)> {
+    ensure!(limit > 0, "Limit is zero!");
+    let start = match page_token.map(PageToken::decode).transpose()? {
+        None => 0,
+        Some(PageToken::Chats { last_order_key, last_id }) =>
+            cwds.partition_point(|cwd| chat_order_key(cwd) <= (last_order_key, *last_id)),
+        Some(token) => bail!("Not a chats page token: {token:?}"),
+    };
+    let has_more = cwds.len() > start + limit;
+    let page = cwds.into_iter().skip(start).take(limit).collect_vec();
+    let next_token = page.last()
+        .filter(|_| has_more)
+        .map(|cwd| {
+            let (last_order_key, last_id) = chat_order_key(cwd);
+            PageToken::Chats { last_order_key, last_id: ChatId(last_id) }.encode()
+        });
+    Ok((page, next_token))
+}
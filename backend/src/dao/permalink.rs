@@ -0,0 +1,104 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+/// Stable link to a message, formatted as `chm://<ds_uuid>/<chat_id>/<message_id>`.
+///
+/// Message ID is a source ID if the message has one, otherwise it's an internal ID prefixed by `i`,
+/// e.g. `chm://e2b1.../123/456` vs `chm://e2b1.../123/i789`.
+/// Source IDs survive re-imports and merges, internal IDs are only stable within a single DAO.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permalink {
+    pub ds_uuid: PbUuid,
+    pub chat_id: ChatId,
+    pub message_id: PermalinkMessageId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermalinkMessageId {
+    Source(MessageSourceId),
+    Internal(MessageInternalId),
+}
+
+impl Permalink {
+    pub const SCHEME: &'static str = "chm://";
+
+    pub fn for_message(chat: &Chat, msg: &Message) -> Self {
+        let message_id = match msg.source_id_option {
+            Some(source_id) => PermalinkMessageId::Source(MessageSourceId(source_id)),
+            None => PermalinkMessageId::Internal(msg.internal_id()),
+        };
+        Permalink { ds_uuid: chat.ds_uuid.clone(), chat_id: chat.id(), message_id }
+    }
+}
+
+impl Display for Permalink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}/{}/", Self::SCHEME, self.ds_uuid.value, *self.chat_id)?;
+        match self.message_id {
+            PermalinkMessageId::Source(id) => write!(f, "{}", *id),
+            PermalinkMessageId::Internal(id) => write!(f, "i{}", *id),
+        }
+    }
+}
+
+impl FromStr for Permalink {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(rest) = s.trim().strip_prefix(Self::SCHEME) else {
+            bail!("Not a permalink: {s}");
+        };
+        let Some((ds_uuid, chat_id, message_id)) = rest.trim_end_matches('/').split('/').collect_tuple() else {
+            bail!("Malformed permalink: {s}");
+        };
+        let ds_uuid = Uuid::parse_str(ds_uuid).with_context(|| format!("Malformed dataset UUID in permalink: {s}"))?;
+        let chat_id = chat_id.parse::<i64>().with_context(|| format!("Malformed chat ID in permalink: {s}"))?;
+        let message_id = match message_id.strip_prefix('i') {
+            Some(id) => PermalinkMessageId::Internal(MessageInternalId(id.parse()?)),
+            None => PermalinkMessageId::Source(MessageSourceId(message_id.parse()?)),
+        };
+        Ok(Permalink {
+            ds_uuid: PbUuid { value: ds_uuid.to_string() },
+            chat_id: ChatId(chat_id),
+            message_id,
+        })
+    }
+}
+
+/// Message referenced by a permalink, along with its surrounding messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedPermalink {
+    pub chat: Chat,
+    pub messages_before: Vec<Message>,
+    pub message: Message,
+    pub messages_after: Vec<Message>,
+}
+
+/// Find a message referenced by the permalink, returning up to `context_limit` messages from each side of it.
+/// Returns `None` if either the chat or the message no longer exists.
+pub fn resolve_permalink(dao: &dyn ChatHistoryReader,
+                         permalink: &Permalink,
+                         context_limit: usize) -> Result<Option<ResolvedPermalink>> {
+    let Some(cwd) = dao.chat_option(&permalink.ds_uuid, *permalink.chat_id)? else {
+        return Ok(None);
+    };
+    let chat = cwd.chat;
+    let internal_id = match permalink.message_id {
+        PermalinkMessageId::Source(id) => dao.message_option(&chat, id)?.map(|m| m.internal_id()),
+        PermalinkMessageId::Internal(id) => dao.message_option_by_internal_id(&chat, id)?.map(|m| m.internal_id()),
+    };
+    let Some(internal_id) = internal_id else {
+        return Ok(None);
+    };
+    let mut messages_before = dao.messages_around(&chat, internal_id, context_limit, context_limit)?;
+    let idx = messages_before.iter().position(|m| m.internal_id() == internal_id).unwrap();
+    let messages_after = messages_before.split_off(idx + 1);
+    let message = messages_before.pop().unwrap();
+    Ok(Some(ResolvedPermalink { chat, messages_before, message, messages_after }))
+}
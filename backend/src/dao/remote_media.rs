@@ -0,0 +1,240 @@
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use indexmap::IndexMap;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+use crate::utils::http_client::get_range_blocking;
+
+#[cfg(test)]
+#[path = "remote_media_tests.rs"]
+mod tests;
+
+/// Remote files are fetched in chunks of this size, one range request per chunk.
+pub const CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Fetched chunks are kept on disk, up to this total size.
+pub const MAX_CACHE_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Remote media root has to be an HTTP(S) URL.
+pub fn parse_base_url(base_url: &str) -> Result<Url> {
+    let url = Url::parse(base_url).with_context(|| format!("Invalid remote media root {base_url}"))?;
+    ensure!(matches!(url.scheme(), "http" | "https"), "Remote media root {base_url} is not an HTTP(S) URL");
+    Ok(url)
+}
+
+/// URL of a file under a remote media root, given its path relative to dataset root as stored in a database.
+pub fn remote_url(base_url: &str, path: &str) -> Result<String> {
+    ensure_relative(path)?;
+    let mut url = parse_base_url(base_url)?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Remote media root {base_url} cannot be a base URL"))?
+        .pop_if_empty()
+        .extend(path.split('/').filter(|segment| !segment.is_empty()));
+    Ok(url.to_string())
+}
+
+/// Where a dataset media file is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaSource {
+    Local(PathBuf),
+    /// File URL under the dataset remote media root
+    Remote(String),
+}
+
+/// Resolve a dataset media file, given its path relative to dataset root.
+/// File is fetched from the dataset remote media root if it has one.
+pub fn media_source(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid, path: &str) -> Result<MediaSource> {
+    ensure_relative(path)?;
+    Ok(match dao.remote_media_root(ds_uuid)? {
+        Some(base_url) => MediaSource::Remote(remote_url(&base_url, path)?),
+        None => MediaSource::Local(dao.dataset_root(ds_uuid)?.to_absolute(path)),
+    })
+}
+
+impl MediaSource {
+    /// Bytes `offset..offset + length` of a file (fewer if it ends sooner), along with its total size.
+    pub fn read(&self,
+                offset: u64,
+                length: u64,
+                http_client: &impl HttpClient,
+                cache: &RemoteMediaCache) -> Result<(Vec<u8>, u64)> {
+        match self {
+            MediaSource::Remote(url) => cache.read(http_client, url, offset, length),
+            MediaSource::Local(abs_path) => {
+                let mut file = fs::File::open(abs_path).with_context(|| format!("Cannot open {}", abs_path.display()))?;
+                let total_size = file.metadata()?.len();
+                file.seek(SeekFrom::Start(offset))?;
+                let mut bytes = Vec::new();
+                file.take(length).read_to_end(&mut bytes)?;
+                Ok((bytes, total_size))
+            }
+        }
+    }
+}
+
+/// Shortcut for [media_source] followed by [MediaSource::read].
+pub fn read_media(dao: &dyn ChatHistoryReader,
+                  ds_uuid: &PbUuid,
+                  path: &str,
+                  offset: u64,
+                  length: u64,
+                  http_client: &impl HttpClient,
+                  cache: &RemoteMediaCache) -> Result<(Vec<u8>, u64)> {
+    media_source(dao, ds_uuid, path)?.read(offset, length, http_client, cache)
+}
+
+/// Stored paths always use `/` separators, but `\` and drive prefixes (`C:`) would be honored on Windows.
+fn ensure_relative(path: &str) -> EmptyRes {
+    let is_drive_prefix = |segment: &str| matches!(segment.as_bytes(), [letter, b':', ..] if letter.is_ascii_alphabetic());
+    ensure!(!path.starts_with('/') && !path.contains('\\') &&
+                !path.split('/').any(|segment| segment == ".." || is_drive_prefix(segment)),
+            "Path {path} is outside of dataset root");
+    Ok(())
+}
+
+/// Chunks of remote files fetched earlier, kept in a local directory.
+/// Least recently used chunks are evicted once their total size exceeds a limit.
+///
+/// Cache can be shared between threads, it's only locked while looking up or storing chunks,
+/// not while they're being fetched.
+pub struct RemoteMediaCache {
+    /// Directory given explicitly, a private temporary one is used otherwise
+    dir_option: Option<PathBuf>,
+    max_size: u64,
+    chunk_size: u64,
+    state: Mutex<CacheState>,
+}
+
+struct CacheState {
+    /// Private temporary directory, created on first use unless a directory is given explicitly
+    tmp_dir_option: Option<TempDir>,
+    /// (URL, chunk index) -> chunk size, least recently used first
+    chunks: IndexMap<(String, u64), u64>,
+    total_size: u64,
+    /// Total sizes of remote files by URL, as reported when their chunks were fetched
+    file_sizes: HashMap<String, u64>,
+}
+
+impl Default for RemoteMediaCache {
+    fn default() -> Self {
+        RemoteMediaCache::new_inner(None, MAX_CACHE_SIZE, CHUNK_SIZE)
+    }
+}
+
+impl RemoteMediaCache {
+    /// Directory is created on first use, and is removed along with the cache.
+    pub fn new(dir: PathBuf, max_size: u64, chunk_size: u64) -> Self {
+        RemoteMediaCache::new_inner(Some(dir), max_size, chunk_size)
+    }
+
+    fn new_inner(dir_option: Option<PathBuf>, max_size: u64, chunk_size: u64) -> Self {
+        assert!(chunk_size > 0, "Chunk size should be positive");
+        let state = CacheState { tmp_dir_option: None, chunks: IndexMap::new(), total_size: 0, file_sizes: HashMap::new() };
+        RemoteMediaCache { dir_option, max_size, chunk_size, state: Mutex::new(state) }
+    }
+
+    /// Bytes `offset..offset + length` of a remote file (fewer if it ends sooner), along with its total size.
+    pub fn read(&self, http_client: &impl HttpClient, url: &str, offset: u64, length: u64) -> Result<(Vec<u8>, u64)> {
+        let end = offset.saturating_add(length);
+        let mut bytes = Vec::new();
+        let mut chunk_idx = offset / self.chunk_size;
+        loop {
+            let chunk_start = chunk_idx * self.chunk_size;
+            let (chunk, total_size) = self.chunk(http_client, url, chunk_idx)?;
+            let from = (offset.saturating_sub(chunk_start) as usize).min(chunk.len());
+            let to = (end - chunk_start).min(chunk.len() as u64) as usize;
+            bytes.extend_from_slice(&chunk[from..to.max(from)]);
+
+            chunk_idx += 1;
+            if chunk_idx * self.chunk_size >= end.min(total_size) {
+                return Ok((bytes, total_size));
+            }
+        }
+    }
+
+    /// Chunk along with the total file size.
+    fn chunk(&self, http_client: &impl HttpClient, url: &str, chunk_idx: u64) -> Result<(Vec<u8>, u64)> {
+        let key = (url.to_owned(), chunk_idx);
+        {
+            let mut state = self.lock();
+            if let Some(size) = state.chunks.shift_remove(&key) {
+                match fs::read(self.chunk_path(&state, url, chunk_idx)) {
+                    Ok(chunk) => {
+                        // Re-inserted as the most recently used
+                        state.chunks.insert(key, size);
+                        return Ok((chunk, state.file_sizes[url]));
+                    }
+                    Err(e) => {
+                        log::warn!("Cached chunk {chunk_idx} of {url} is unreadable, fetching it again: {e}");
+                        state.total_size -= size;
+                    }
+                }
+            }
+        }
+
+        log::debug!("Fetching chunk {chunk_idx} of {url}");
+        let (chunk, total_size) = get_range_blocking(http_client, url, chunk_idx * self.chunk_size, self.chunk_size)?;
+        let mut state = self.lock();
+        state.file_sizes.insert(url.to_owned(), total_size);
+        self.store(&mut state, key, &chunk)?;
+        Ok((chunk, total_size))
+    }
+
+    fn store(&self, state: &mut CacheState, key: (String, u64), chunk: &[u8]) -> EmptyRes {
+        let size = chunk.len() as u64;
+        if size > self.max_size { return Ok(()); }
+        // Same chunk might have been fetched by another thread in the meantime
+        if let Some(old_size) = state.chunks.shift_remove(&key) {
+            state.total_size -= old_size;
+        }
+        while state.total_size + size > self.max_size {
+            let ((url, chunk_idx), evicted_size) = state.chunks.shift_remove_index(0).expect("Cache size is off");
+            if let Err(e) = fs::remove_file(self.chunk_path(state, &url, chunk_idx)) {
+                log::warn!("Failed to remove cached chunk {chunk_idx} of {url}: {e}");
+            }
+            state.total_size -= evicted_size;
+        }
+        match self.dir_option {
+            Some(ref dir) => fs::create_dir_all(dir)?,
+            None if state.tmp_dir_option.is_none() =>
+                state.tmp_dir_option = Some(tempfile::Builder::new().prefix("chm-remote-media-").tempdir()?),
+            None => {}
+        }
+        fs::write(self.chunk_path(state, &key.0, key.1), chunk)?;
+        state.total_size += size;
+        state.chunks.insert(key, size);
+        Ok(())
+    }
+
+    fn chunk_path(&self, state: &CacheState, url: &str, chunk_idx: u64) -> PathBuf {
+        let dir = self.dir_option.as_deref()
+            .or(state.tmp_dir_option.as_ref().map(|d| d.path()))
+            .expect("Cache directory should exist once chunks are stored");
+        dir.join(format!("{}_{chunk_idx}", hex::encode(Sha256::digest(url.as_bytes()))))
+    }
+
+    /// Lock poisoned by a panicked thread is recovered, the cache is still usable.
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for RemoteMediaCache {
+    fn drop(&mut self) {
+        // Temporary directory is removed on its own
+        if let Some(ref dir) = self.dir_option {
+            if dir.exists() {
+                if let Err(e) = fs::remove_dir_all(dir) {
+                    log::warn!("Failed to remove remote media cache {}: {e}", dir.display());
+                }
+            }
+        }
+    }
+}
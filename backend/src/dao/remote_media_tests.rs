@@ -0,0 +1,95 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn remote_urls() -> EmptyRes {
+    assert_eq!(remote_url("https://example.com/archive", "chats/a b.jpg")?, "https://example.com/archive/chats/a%20b.jpg");
+    assert_eq!(remote_url("https://example.com/archive/", "chats/100%.jpg")?, "https://example.com/archive/chats/100%25.jpg");
+    assert_eq!(remote_url("http://example.com", "a.jpg")?, "http://example.com/a.jpg");
+
+    assert!(remote_url("https://example.com/archive", "../secret.txt").is_err());
+    assert!(remote_url("https://example.com/archive", "/etc/passwd").is_err());
+    assert!(remote_url("https://example.com/archive", r"chats\..\..\secret.txt").is_err());
+    assert!(remote_url("https://example.com/archive", "C:/Windows/win.ini").is_err());
+    assert!(remote_url("https://example.com/archive", "C:secret.txt").is_err());
+    assert!(remote_url("ftp://example.com/archive", "a.jpg").is_err());
+    assert!(remote_url("not a URL", "a.jpg").is_err());
+    Ok(())
+}
+
+#[test]
+fn reading_remote_chunks() -> EmptyRes {
+    // Mock client responds with a requested URL
+    let url = "https://example.com/media/file.bin";
+    let url_bytes = url.as_bytes();
+    let total_size = url_bytes.len() as u64;
+    let http_client = MockHttpClient::new();
+    let tmp_dir = TmpDir::new();
+    let cache = RemoteMediaCache::new(tmp_dir.path.join("cache"), 16, 8);
+
+    // Spans two chunks
+    assert_eq!(cache.read(&http_client, url, 3, 10)?, (url_bytes[3..13].to_vec(), total_size));
+    assert_eq!(http_client.calls_copy().len(), 2);
+
+    // Cached chunks are reused
+    assert_eq!(cache.read(&http_client, url, 0, 16)?, (url_bytes[0..16].to_vec(), total_size));
+    assert_eq!(http_client.calls_copy().len(), 2);
+
+    // Reading up to the end evicts least recently used chunks
+    assert_eq!(cache.read(&http_client, url, 20, 100)?, (url_bytes[20..].to_vec(), total_size));
+    assert_eq!(http_client.calls_copy().len(), 5);
+    assert!(cache.lock().total_size <= 16);
+    assert_eq!(cache.read(&http_client, url, 0, 1)?, (url_bytes[0..1].to_vec(), total_size));
+    assert_eq!(http_client.calls_copy().len(), 6);
+
+    // Past the end
+    assert_eq!(cache.read(&http_client, url, 100, 10)?, (vec![], total_size));
+    assert_eq!(cache.read(&http_client, url, 0, 0)?, (vec![], total_size));
+    Ok(())
+}
+
+#[test]
+fn reading_local_media() -> EmptyRes {
+    let dao_holder = create_simple_dao(false, "test", vec![create_regular_message(1, 1)], 1, &|_, _, _| {});
+    let ds_uuid = dao_holder.dao.ds_uuid();
+    let ds_root = dao_holder.dao.dataset_root(&ds_uuid)?;
+    let path = create_random_file(&ds_root.0);
+    let rel_path = ds_root.to_relative(&path)?;
+    let content = fs::read(&path)?;
+
+    let http_client = MockHttpClient::new();
+    let cache = RemoteMediaCache::default();
+    let read = |offset, length| {
+        read_media(dao_holder.dao.as_ref(), &ds_uuid, &rel_path, offset, length, &http_client, &cache)
+    };
+    assert_eq!(read(0, 10)?, (content[0..10].to_vec(), 256));
+    assert_eq!(read(250, 10)?, (content[250..].to_vec(), 256));
+    assert!(http_client.calls_copy().is_empty());
+
+    assert!(read_media(dao_holder.dao.as_ref(), &ds_uuid, "../x.bin", 0, 10, &http_client, &cache).is_err());
+    Ok(())
+}
+
+#[test]
+fn default_cache_dir() -> EmptyRes {
+    let url = "https://example.com/media/file.bin";
+    let http_client = MockHttpClient::new();
+    let cache = RemoteMediaCache::default();
+    assert!(cache.lock().tmp_dir_option.is_none());
+
+    cache.read(&http_client, url, 0, 10)?;
+    let dir = cache.lock().tmp_dir_option.as_ref().unwrap().path().to_path_buf();
+    assert!(path_file_name(&dir)?.starts_with("chm-remote-media-"));
+    assert_eq!(list_all_files(&dir, false)?.len(), 1);
+
+    drop(cache);
+    assert!(!dir.exists());
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use std::cmp;
+
+use itertools::Itertools;
+
+use crate::dao::ChatHistoryReader;
+use crate::dao::aliases::AliasGroup;
+use crate::dao::paging::PageToken;
+use crate::prelude::*;
+
+/// Search all datasets of all given DAOs, returning up to `limit` newest messages in total following the page token
+/// (or from the start), along with the token for the next page, if there is one.
+/// Results are grouped by DAO and dataset, in the order they were given; groups without hits are omitted.
+/// Hits sent at the same time are taken in group order.
+/// If a person is given, only messages sent by any of their aliases are considered.
+pub fn search_all<'a>(daos: impl IntoIterator<Item=(&'a str, &'a dyn ChatHistoryReader)>,
+                      text: &str,
+                      from_option: Option<&AliasGroup>,
+                      page_token: Option<&str>,
+                      limit: usize) -> Result<(Vec<SearchResultGroup>, Option<String>)> {
+    ensure!(!text.trim().is_empty(), "Search text is empty!");
+    ensure!(limit > 0, "Limit is zero!");
+    let last_hit_option = match page_token.map(PageToken::decode).transpose()? {
+        None => None,
+        Some(PageToken::Search { last_timestamp, last_id, ds_uuid, key }) => Some((last_timestamp, last_id, ds_uuid, key)),
+        Some(token) => bail!("Not a search page token: {token:?}"),
+    };
+
+    let daos = daos.into_iter().collect_vec();
+    let mut datasets = vec![];
+    for (key, dao) in daos.iter() {
+        for ds in dao.datasets()? {
+            datasets.push((*key, *dao, ds.uuid));
+        }
+    }
+    let last_group_idx_option = match last_hit_option {
+        Some((_, _, ref ds_uuid, ref key)) => Some(datasets.iter().position(|(k, _, u)| *k == key.as_str() && u == ds_uuid)
+            .with_context(|| format!("Dataset {} of {key} from the page token is no longer loaded", ds_uuid.value))?),
+        None => None,
+    };
+
+    let mut groups = vec![];
+    for (group_idx, (key, dao, ds_uuid)) in datasets.into_iter().enumerate() {
+        let from_ids_option = match from_option {
+            Some(group) => match group.get(&ds_uuid) {
+                Some(ids) => Some(ids.as_slice()),
+                None => continue,
+            },
+            None => None,
+        };
+        // Hits from groups preceding the last one can't have the same timestamp, hits from groups following it can
+        let after_option = last_hit_option.as_ref().zip(last_group_idx_option).map(|((ts, id, _, _), last_group_idx)|
+            match group_idx.cmp(&last_group_idx) {
+                cmp::Ordering::Less => (*ts, MessageInternalId(i64::MIN)),
+                cmp::Ordering::Equal => (*ts, *id),
+                cmp::Ordering::Greater => (*ts, MessageInternalId(i64::MAX)),
+            });
+        // Each dataset can't contribute more than a global limit, one extra hit tells whether there's more
+        let hits = dao.search_messages(&ds_uuid, text, from_ids_option, after_option, limit + 1)?;
+        groups.push((group_idx, SearchResultGroup { key: key.to_owned(), ds_uuid, hits }));
+    }
+
+    // Only keep globally newest hits
+    let order_key = |group_idx: usize, hit: &SearchHit| (cmp::Reverse(hit.message.timestamp), group_idx, cmp::Reverse(hit.message.internal_id));
+    let all_keys = groups.iter()
+        .flat_map(|(group_idx, g)| g.hits.iter().map(|h| order_key(*group_idx, h)))
+        .sorted()
+        .collect_vec();
+    let last_key_option = all_keys.get(limit).map(|_| all_keys[limit - 1]);
+    let next_token = match last_key_option {
+        Some((cmp::Reverse(last_timestamp), last_group_idx, cmp::Reverse(last_id))) => {
+            let (_, last_group) = groups.iter().find(|(group_idx, _)| *group_idx == last_group_idx).unwrap();
+            Some(PageToken::Search {
+                last_timestamp: Timestamp(last_timestamp),
+                last_id: MessageInternalId(last_id),
+                ds_uuid: last_group.ds_uuid.clone(),
+                key: last_group.key.clone(),
+            }.encode())
+        }
+        None => None,
+    };
+    if let Some(last_key) = last_key_option {
+        for (group_idx, group) in groups.iter_mut() {
+            let group_idx = *group_idx;
+            group.hits.retain(|h| order_key(group_idx, h) <= last_key);
+        }
+    }
+
+    let groups = groups.into_iter().map(|(_, g)| g).filter(|g| !g.hits.is_empty()).collect_vec();
+    Ok((groups, next_token))
+}
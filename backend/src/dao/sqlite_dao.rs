@@ -4,21 +4,25 @@ use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 use chrono::Local;
-use diesel::{delete, insert_into, sql_query, sql_types, update};
+use const_format::concatcp;
+use diesel::{delete, insert_into, insert_or_ignore_into, sql_query, sql_types, update};
 use diesel::migration::MigrationSource;
 use diesel::prelude::*;
 use diesel::sqlite::Sqlite;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use itertools::{Either, Itertools};
+use unicode_normalization::UnicodeNormalization;
 use uuid::Uuid;
 
 use mapping::*;
+use crate::utils::file_clone::clone_file;
 
 use super::*;
 
-mod mapping;
-mod utils;
+// Also used to convert legacy storage, see loader::legacy_h2
+pub(crate) mod mapping;
+pub(crate) mod utils;
 
 #[cfg(test)]
 #[path = "sqlite_dao_tests.rs"]
@@ -90,6 +94,90 @@ impl SqliteDao {
         self.storage_path().join(BACKUPS_DIR_NAME)
     }
 
+    /// Directory holding contents of trash items, each one in a separate database.
+    pub fn trash_path(&self) -> PathBuf {
+        self.storage_path().join(TRASH_DIR_NAME)
+    }
+
+    fn trash_item_db_file(&self, id: i64) -> PathBuf {
+        self.trash_path().join(format!("{TRASH_ITEM_PREFIX}{id}")).join(SqliteDao::FILENAME)
+    }
+
+    /// Copy a whole dataset, or a single chat of it, into a new trash item.
+    fn put_to_trash(&self, ds_uuid: &PbUuid, chat_option: Option<&Chat>) -> EmptyRes {
+        let ds = self.datasets()?.into_iter().find(|ds| ds.uuid == *ds_uuid)
+            .with_context(|| format!("Dataset {} not found", ds_uuid.value))?;
+        let cwds = match chat_option {
+            Some(chat) => vec![self.chat_option(ds_uuid, chat.id)?
+                .with_context(|| format!("Chat {} not found", chat.qualified_name()))?],
+            None => self.chats(ds_uuid)?,
+        };
+        let item = TrashItem {
+            id: 0,
+            timestamp: Local::now().timestamp(),
+            ds_uuid: ds_uuid.clone(),
+            chat_id_option: chat_option.map(|c| c.id),
+            name: chat_option.map(|c| name_or_unnamed(&c.name_option)).unwrap_or_else(|| ds.alias.clone()),
+            chats_count: cwds.len() as i32,
+            messages_count: cwds.iter().map(|cwd| cwd.chat.msg_count).sum(),
+        };
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        let id = insert_into(trash_item::table)
+            .values(utils::trash_item::serialize(&item, uuid.as_bytes()))
+            .returning(trash_item::columns::id)
+            .get_result::<i64>(&mut conn)?;
+        let db_file = self.trash_item_db_file(id);
+        let item_dir = db_file.parent().unwrap();
+        let res = (|| {
+            fs::create_dir_all(item_dir)?;
+            let trash_dao = SqliteDao::create(&db_file)?;
+            match chat_option {
+                Some(_) => trash_dao.copy_chats_from(self, &ds, &cwds),
+                None => trash_dao.copy_datasets_from(self, std::slice::from_ref(ds_uuid)),
+            }
+        })();
+        if let Err(e) = res {
+            // Item is useless without its contents
+            delete(trash_item::table).filter(trash_item::columns::id.eq(id)).execute(&mut conn)?;
+            if item_dir.exists() {
+                fs::remove_dir_all(item_dir)?;
+            }
+            return Err(e.context("Failed to put into trash"));
+        }
+        Ok(())
+    }
+
+    /// Redact strings (see [ChatHistoryWriter::redact_strings]) in trash items that may hold copies of the same chats.
+    fn redact_strings_in_trash(&self, ds_uuid: &PbUuid, chat_id_option: Option<ChatId>, strings: &[String]) -> EmptyRes {
+        for item in self.trash()? {
+            let chat_matches = match (chat_id_option, item.chat_id_option) {
+                (Some(chat_id), Some(item_chat_id)) => chat_id.0 == item_chat_id,
+                _ => true,
+            };
+            if item.ds_uuid != *ds_uuid || !chat_matches { continue; }
+            let mut trash_dao = SqliteDao::load(&self.trash_item_db_file(item.id))?;
+            trash_dao.redact_strings(ds_uuid, chat_id_option, strings)
+                .with_context(|| format!("Failed to redact trash item {}", item.id))?;
+        }
+        Ok(())
+    }
+
+    fn remove_trash_item(&self, id: i64) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        let deleted_rows = delete(trash_item::table).filter(trash_item::columns::id.eq(id)).execute(&mut conn)?;
+        ensure!(deleted_rows == 1, "{deleted_rows} rows changed when deleting trash item {id}");
+        let item_dir = self.trash_item_db_file(id).parent().unwrap().to_path_buf();
+        if item_dir.exists() {
+            fs::remove_dir_all(item_dir)?;
+        }
+        Ok(())
+    }
+
     fn choose_final_backup_path(&self, ext_suffix: &str) -> Result<PathBuf> {
         let backup_path = self.backup_path();
         let now_str = Local::now().format("%Y-%m-%d_%H-%M-%S");
@@ -113,7 +201,7 @@ impl SqliteDao {
         Ok(conn_pool.get()?)
     }
 
-    pub fn copy_datasets_from(&self, src: &dyn ChatHistoryDao, src_dataset_uuids: &[PbUuid]) -> EmptyRes {
+    pub fn copy_datasets_from(&self, src: &dyn ChatHistoryReader, src_dataset_uuids: &[PbUuid]) -> EmptyRes {
         measure(|| {
             let src_datasets = src.datasets()?
                 .into_iter()
@@ -142,76 +230,43 @@ impl SqliteDao {
                     conn.transaction(|txn| {
                         insert_into(dataset::table).values(&raw_ds).execute(txn)?;
 
-                        let raw_users_with_pictures: Vec<(RawUser, Vec<RawProfilePicture>)> =
-                            src.users(ds_uuid)?.iter().map(|u| {
-                                ensure!(u.id > 0, "IDs should be positive!");
-                                let raw_user = utils::user::serialize(u, *u == src_myself, &raw_ds.uuid);
-                                let raw_pictures: Vec<RawProfilePicture> =
-                                    u.profile_pictures.iter()
-                                        .map(|pp| (pp, src_ds_root.to_absolute(&pp.path)))
-                                        .filter(|(_, path)| path.exists())
-                                        .enumerate()
-                                        .map(|(idx, (pp, path))| {
-                                            utils::user::profile_picture::serialize_and_copy(
-                                                u.id(), &raw_ds.uuid, &path,
-                                                pp.frame_option.as_ref(), idx, &dst_ds_root,
-                                            )
-                                        })
-                                        .try_collect()?;
-                                Ok((raw_user, raw_pictures))
-                            }).try_collect()?;
-                        let (raw_users, raw_pictures): (Vec<RawUser>, Vec<Vec<RawProfilePicture>>) =
-                            raw_users_with_pictures.into_iter().unzip();
-                        let raw_pictures = raw_pictures.into_iter().flatten().collect_vec();
+                        let (raw_users, raw_pictures) = serialize_users_and_copy_pictures(
+                            &src.users(ds_uuid)?, src_myself.id, &raw_ds.uuid, &src_ds_root, &dst_ds_root)?;
                         insert_into(user::table).values(&raw_users).execute(txn)?;
                         insert_into(profile_picture::table).values(&raw_pictures).execute(txn)?;
+
+                        let raw_redaction_log = src.redaction_log(ds_uuid)?.iter()
+                            .map(|entry| utils::redaction_log::serialize(entry, &raw_ds.uuid))
+                            .collect_vec();
+                        insert_into(redaction_log::table).values(&raw_redaction_log).execute(txn)?;
+
+                        insert_import_batches(txn, &src.import_batches(ds_uuid)?, &raw_ds.uuid)?;
+                        insert_user_identities(txn, &src.user_identities(ds_uuid)?, &raw_ds.uuid)?;
+
+                        let raw_aliases: Vec<RawUserAlias> = src.user_aliases()?.iter()
+                            .filter(|alias| alias.ds_uuid == *ds_uuid)
+                            .map(utils::user_alias::serialize)
+                            .try_collect()?;
+                        insert_or_ignore_into(user_alias::table).values(&raw_aliases).execute(txn)?;
                         ok(())
                     })?;
 
                     for src_cwd in src.chats(ds_uuid)?.iter() {
-                        ensure!(src_cwd.chat.id > 0, "IDs should be positive!");
                         ensure!(src_cwd.chat.member_ids.first() == Some(&src_myself.id),
                                 "First member of chat {} was not myself!", src_cwd.chat.qualified_name());
-
-                        conn.transaction(|txn| {
-                            let mut raw_chat = utils::chat::serialize(&src_cwd.chat, &raw_ds.uuid)?;
-                            if let Some(ref img) = src_cwd.chat.img_path_option {
-                                raw_chat.img_path =
-                                    copy_chat_file(img, None, None, &subpaths::ROOT,
-                                                   src_cwd.chat.id, &src_ds_root, &dst_ds_root)?;
-                            }
-                            insert_into(chat::table).values(raw_chat).execute(txn)?;
-                            insert_into(chat_member::table)
-                                .values(src_cwd.chat.member_ids.iter()
-                                    .enumerate()
-                                    .map(|(order, &user_id)|
-                                        RawChatMember {
-                                            ds_uuid: raw_ds.uuid.clone(),
-                                            chat_id: src_cwd.chat.id,
-                                            user_id,
-                                            order: order as i32,
-                                        })
-                                    .collect_vec())
-                                .execute(txn)?;
-                            ok(())
-                        })?;
-
-                        const BATCH_SIZE: usize = 5_000;
-                        let mut offset: usize = 0;
-                        loop {
-                            let src_msgs = src.scroll_messages(&src_cwd.chat, offset, BATCH_SIZE)?;
-
-                            // Copy messages
-                            conn.transaction(|txn| {
-                                self.copy_messages(txn, &src_msgs, src_cwd.chat.id,
-                                                   &raw_ds.uuid, &src_ds_root, &dst_ds_root)
-                            })?;
-
-                            if src_msgs.len() < BATCH_SIZE { break; }
-                            offset += BATCH_SIZE;
-                        }
+                        self.copy_chat_from(&mut conn, src, src_cwd, &raw_ds.uuid, &src_ds_root, &dst_ds_root)?;
                     }
 
+                    let raw_retention_rules: Vec<RawRetentionRule> = src.retention_rules(ds_uuid)?.iter()
+                        .map(|rule| utils::retention_rule::serialize(rule, &raw_ds.uuid))
+                        .try_collect()?;
+                    insert_into(retention_rule::table).values(&raw_retention_rules).execute(&mut conn)?;
+
+                    let raw_media_annotations = src.media_annotations(ds_uuid)?.iter()
+                        .map(|annotation| utils::media_annotation::serialize(annotation, &raw_ds.uuid))
+                        .collect_vec();
+                    insert_into(media_annotation::table).values(&raw_media_annotations).execute(&mut conn)?;
+
                     vacuum(&mut conn)?;
 
                     Ok(())
@@ -224,7 +279,7 @@ impl SqliteDao {
 
             for src_ds in src_datasets.iter() {
                 let ds_uuid = &src_ds.uuid;
-                let diff = get_datasets_diff(src, ds_uuid, self, ds_uuid, 1)?;
+                let diff = get_datasets_diff(src, ds_uuid, self, ds_uuid, 1, &DiffOptions::default())?;
                 ensure!(diff.is_empty(), "{}", diff.iter().join("\n\n"))
             }
 
@@ -232,6 +287,128 @@ impl SqliteDao {
         }, |_, t| log::info!("Dao '{}' fully copied {t} ms", src.name()))
     }
 
+    /// Copy given chats of a dataset along with their members and messages, dataset is created if missing.
+    /// Users already present here are left as-is.
+    fn copy_chats_from(&self, src: &dyn ChatHistoryReader, ds: &Dataset, src_cwds: &[ChatWithDetails]) -> EmptyRes {
+        let src_myself = src.myself(&ds.uuid)?;
+        let src_ds_root = src.dataset_root(&ds.uuid)?;
+        let ds_exists = self.datasets()?.iter().any(|ds2| ds2.uuid == ds.uuid);
+        let (existing_user_ids, existing_chat_ids): (HashSet<i64>, HashSet<i64>) = if ds_exists {
+            (self.users(&ds.uuid)?.iter().map(|u| u.id).collect(),
+             self.chats(&ds.uuid)?.iter().map(|cwd| cwd.chat.id).collect())
+        } else {
+            Default::default()
+        };
+        for src_cwd in src_cwds.iter() {
+            ensure!(!existing_chat_ids.contains(&src_cwd.chat.id),
+                    "Chat {} already exists!", src_cwd.chat.qualified_name());
+            ensure!(src_cwd.chat.member_ids.first() == Some(&src_myself.id),
+                    "First member of chat {} was not myself!", src_cwd.chat.qualified_name());
+        }
+
+        let raw_ds = utils::dataset::serialize(ds);
+        let dst_ds_root = self.dataset_root(&ds.uuid)?;
+        let new_users = src_cwds.iter()
+            .flat_map(|cwd| cwd.members.iter())
+            .filter(|u| !existing_user_ids.contains(&u.id))
+            .unique_by(|u| u.id)
+            .cloned()
+            .collect_vec();
+        let (raw_users, raw_pictures) =
+            serialize_users_and_copy_pictures(&new_users, src_myself.id, &raw_ds.uuid, &src_ds_root, &dst_ds_root)?;
+
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        conn.transaction(|txn| {
+            if !ds_exists {
+                insert_into(dataset::table).values(&raw_ds).execute(txn)?;
+                store_searchable_string_settings(txn, &src.searchable_string_settings(&ds.uuid)?, &raw_ds.uuid)?;
+                store_blocked_users_policy(txn, &src.blocked_users_policy(&ds.uuid)?, &raw_ds.uuid)?;
+                store_remote_media_root(txn, src.remote_media_root(&ds.uuid)?.as_deref(), &raw_ds.uuid)?;
+                for template in src.merge_templates(&ds.uuid)? {
+                    store_merge_template(txn, &template)?;
+                }
+            }
+            insert_into(user::table).values(&raw_users).execute(txn)?;
+            insert_into(profile_picture::table).values(&raw_pictures).execute(txn)?;
+            insert_import_batches(txn, &src.import_batches(&ds.uuid)?, &raw_ds.uuid)?;
+            insert_user_identities(txn, &src.user_identities(&ds.uuid)?, &raw_ds.uuid)?;
+            ok(())
+        })?;
+        for src_cwd in src_cwds.iter() {
+            self.copy_chat_from(&mut conn, src, src_cwd, &raw_ds.uuid, &src_ds_root, &dst_ds_root)?;
+        }
+
+        self.invalidate_cache()
+    }
+
+    /// Copy a chat with all its members and messages, chat members must be already present.
+    fn copy_chat_from(&self,
+                      conn: &mut SqliteConnection,
+                      src: &dyn ChatHistoryReader,
+                      src_cwd: &ChatWithDetails,
+                      raw_uuid: &[u8],
+                      src_ds_root: &DatasetRoot,
+                      dst_ds_root: &DatasetRoot) -> EmptyRes {
+        ensure!(src_cwd.chat.id > 0, "IDs should be positive!");
+
+        use schema::*;
+        conn.transaction(|txn| {
+            let mut raw_chat = utils::chat::serialize(&src_cwd.chat, raw_uuid)?;
+            if let Some(ref img) = src_cwd.chat.img_path_option {
+                raw_chat.img_path =
+                    copy_chat_file(img, None, None, &subpaths::ROOT,
+                                   src_cwd.chat.id, src_ds_root, dst_ds_root)?;
+            }
+            insert_into(chat::table).values(raw_chat).execute(txn)?;
+            insert_into(chat_member::table)
+                .values(src_cwd.chat.member_ids.iter()
+                    .enumerate()
+                    .map(|(order, &user_id)|
+                        RawChatMember {
+                            ds_uuid: raw_uuid.to_vec(),
+                            chat_id: src_cwd.chat.id,
+                            user_id,
+                            order: order as i32,
+                        })
+                    .collect_vec())
+                .execute(txn)?;
+
+            let former_names = src.chat_name_history(&src_cwd.chat)?.into_iter()
+                .filter(|entry| entry.timestamp_option.is_none())
+                .map(|entry| entry.name)
+                .collect_vec();
+            insert_chat_former_names(txn, &former_names, raw_uuid, src_cwd.chat.id)?;
+            ok(())
+        })?;
+
+        const BATCH_SIZE: usize = 5_000;
+        let mut offset: usize = 0;
+        loop {
+            let src_msgs = src.scroll_messages(&src_cwd.chat, offset, BATCH_SIZE)?;
+            let import_batch_uuids = src.message_import_batch_uuids(
+                &src_cwd.chat, &src_msgs.iter().map(|m| m.internal_id()).collect_vec())?;
+
+            // Copy messages
+            conn.transaction(|txn| {
+                self.copy_messages(txn, &src_msgs, &import_batch_uuids, src_cwd.chat.id, raw_uuid, src_ds_root, dst_ds_root)
+            })?;
+
+            if src_msgs.len() < BATCH_SIZE { break; }
+            offset += BATCH_SIZE;
+        }
+
+        // Internal IDs of messages might have changed, so notes need to be re-targeted
+        let src_notes = src.notes(&src_cwd.chat.ds_uuid, Some(src_cwd.chat.id))?;
+        if !src_notes.is_empty() {
+            let dst_chat = Chat { ds_uuid: utils::import_batch::deserialize_uuid(raw_uuid)?, ..src_cwd.chat.clone() };
+            let dst_notes = notes::retarget_notes(src, &src_cwd.chat, self, &dst_chat, src_notes)?;
+            insert_notes(conn, &dst_notes)?;
+        }
+        Ok(())
+    }
+
     fn fetch_messages<F>(&self, get_raw_messages: F) -> Result<Vec<Message>>
         where F: Fn(&mut SqliteConnection) -> Result<Vec<RawMessage>>
     {
@@ -239,15 +416,27 @@ impl SqliteDao {
         utils::message::fetch(&mut conn, get_raw_messages)
     }
 
+    /// Import batch UUIDs should be aligned with source messages.
     fn copy_messages(&self,
                      conn: &mut SqliteConnection,
                      src_msgs: &[Message],
+                     import_batch_uuids: &[Option<PbUuid>],
                      chat_id: i64,
                      raw_uuid: &[u8],
                      src_ds_root: &DatasetRoot,
                      dst_ds_root: &DatasetRoot) -> EmptyRes {
-        let full_raw_msgs: Vec<FullRawMessage> = src_msgs.iter()
-            .map(|m| utils::message::serialize_and_copy_files(m, chat_id, raw_uuid, src_ds_root, dst_ds_root))
+        ensure!(src_msgs.len() == import_batch_uuids.len(), "Import batches don't match messages");
+        // Messages come with searchable strings made by default, so only configured datasets need them remade
+        let settings_option = load_searchable_string_settings(conn, raw_uuid)?;
+        let full_raw_msgs: Vec<FullRawMessage> = src_msgs.iter().zip(import_batch_uuids)
+            .map(|(m, batch_uuid_option)| {
+                let mut full = utils::message::serialize_and_copy_files(m, chat_id, raw_uuid, src_ds_root, dst_ds_root)?;
+                full.m.import_batch_uuid = batch_uuid_option.as_ref().map(utils::import_batch::serialize_uuid).transpose()?;
+                if let Some(ref settings) = settings_option {
+                    full.m.searchable_string = make_searchable_string_with(&m.text, m.typed(), settings);
+                }
+                ok(full)
+            })
             .try_collect()?;
 
         // Don't see a way around cloning here.
@@ -283,11 +472,92 @@ impl SqliteDao {
         Ok(())
     }
 
+    /// Copy chat folders from the given DAO, skipping references to chats not present here.
+    pub fn copy_chat_folders_from(&mut self, src: &dyn ChatHistoryReader) -> EmptyRes {
+        let existing_chats: HashSet<(PbUuid, i64)> = self.datasets()?.into_iter()
+            .map(|ds| ok(self.chats(&ds.uuid)?.into_iter().map(move |cwd| (ds.uuid.clone(), cwd.chat.id))))
+            .flatten_ok()
+            .try_collect()?;
+        let mut new_ids: HashMap<i64, i64> = HashMap::new();
+        // Parents go first, so their new IDs are known by the time children are inserted
+        for folder in src.chat_folders()? {
+            let parent_id_option = match folder.parent_id_option {
+                Some(parent_id) => Some(*new_ids.get(&parent_id).context("Parent folder wasn't copied")?),
+                None => None,
+            };
+            let old_id = folder.id;
+            let chats = folder.chats.into_iter()
+                .filter(|c| existing_chats.contains(&(c.ds_uuid.clone(), c.chat_id)))
+                .collect_vec();
+            let new_folder = self.insert_chat_folder(ChatFolder { parent_id_option, chats, ..folder })?;
+            new_ids.insert(old_id, new_folder.id);
+        }
+        Ok(())
+    }
+
+    /// Copy the whole audit log from the given DAO, keeping entries as they are.
+    pub fn copy_audit_log_from(&mut self, src: &dyn ChatHistoryReader) -> EmptyRes {
+        let raw_entries: Vec<RawAuditLogEntry> =
+            src.audit_log()?.iter().map(utils::audit_log::serialize).try_collect()?;
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        insert_into(audit_log::table).values(&raw_entries).execute(&mut conn)?;
+        Ok(())
+    }
+
+    fn validate_chat_folder(&self, folder: &ChatFolder, is_new: bool) -> EmptyRes {
+        ensure!(!folder.name.trim().is_empty(), "Folder name can't be empty");
+
+        let folders = self.chat_folders()?;
+        if !is_new {
+            ensure!(folders.iter().any(|f| f.id == folder.id), "Folder {} not found", folder.id);
+        }
+        if let Some(parent_id) = folder.parent_id_option {
+            ensure!(is_new || parent_id != folder.id, "Folder can't be its own parent");
+            let parent = folders.iter().find(|f| f.id == parent_id)
+                .with_context(|| format!("Parent folder {parent_id} not found"))?;
+            ensure!(parent.parent_id_option.is_none(), "Folders can only be nested one level deep");
+            ensure!(is_new || !folders.iter().any(|f| f.parent_id_option == Some(folder.id)),
+                    "Folder with subfolders can't be nested");
+        }
+
+        let datasets = self.datasets()?.into_iter().map(|ds| ds.uuid).collect_vec();
+        let mut seen = HashSet::new();
+        for chat_ref in folder.chats.iter() {
+            ensure!(seen.insert((&chat_ref.ds_uuid, chat_ref.chat_id)),
+                    "Chat {} is included in folder more than once", chat_ref.chat_id);
+            ensure!(datasets.contains(&chat_ref.ds_uuid), "Dataset {} not found", chat_ref.ds_uuid.value);
+            ensure!(self.chat_option(&chat_ref.ds_uuid, chat_ref.chat_id)?.is_some(),
+                    "Chat {} not found in dataset {}", chat_ref.chat_id, chat_ref.ds_uuid.value);
+        }
+        Ok(())
+    }
+
     pub fn vacuum(&self) -> EmptyRes {
         let mut conn = self.get_conn()?;
         vacuum(&mut conn)?;
         Ok(())
     }
+
+    /// Redacted data would otherwise survive in free pages of the database file and in the backups,
+    /// so the file is rebuilt and backups are replaced by a fresh one.
+    fn purge_redacted_leftovers(&mut self) -> EmptyRes {
+        {
+            let mut conn = self.get_conn()?;
+            // Cannot be run within a transaction
+            sql_query("VACUUM").execute(&mut conn)?;
+        }
+
+        let backups = list_backups(&self.backup_path())?;
+        if !backups.is_empty() {
+            for f in backups.iter() {
+                fs::remove_file(f)?;
+            }
+            self.backup()?.join().map_err(|_| anyhow!("Backup compression failed!"))?;
+        }
+        Ok(())
+    }
 }
 
 impl WithCache for SqliteDao {
@@ -343,7 +613,7 @@ impl WithCache for SqliteDao {
     }
 }
 
-impl ChatHistoryDao for SqliteDao {
+impl ChatHistoryReader for SqliteDao {
     fn name(&self) -> &str {
         &self.name
     }
@@ -556,119 +826,548 @@ impl ChatHistoryDao for SqliteDao {
         }).map(|mut v| v.pop())
     }
 
-    fn as_mutable(&mut self) -> Result<&mut dyn MutableChatHistoryDao> {
-        Ok(self)
+    fn message_option_by_internal_id(&self, chat: &Chat, internal_id: MessageInternalId) -> Result<Option<Message>> {
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value)?;
+        self.fetch_messages(|conn| {
+            use schema::*;
+            Ok(message::table
+                .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(message::columns::chat_id.eq(chat.id))
+                .filter(message::columns::internal_id.eq(*internal_id))
+                .select(RawMessage::as_select())
+                .load(conn)?)
+        }).map(|mut v| v.pop())
     }
 
-    fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryDao> {
-        Ok(self)
+    fn messages_filtered(&self,
+                         chat: &Chat,
+                         filter: &MessageFilter,
+                         anchor_option: Option<MessageInternalId>,
+                         backward: bool,
+                         limit: usize) -> Result<Vec<Message>> {
+        use utils::EnumSerialization;
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value)?;
+        let element_type_option = filter.content_type.map(ContentType::serialize).transpose()?;
+        let mut msgs = self.fetch_messages(|conn| {
+            use schema::*;
+            let mut query = message::table
+                .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(message::columns::chat_id.eq(chat.id))
+                .into_boxed();
+            if let Some(from_id) = filter.from_id {
+                query = query.filter(message::columns::from_id.eq(from_id));
+            }
+            if let Some(service) = filter.service {
+                query = query.filter(message::columns::tpe.eq(if service { "service" } else { "regular" }));
+            }
+            if let Some(deleted) = filter.deleted {
+                query = query.filter(message::columns::is_deleted.eq(utils::serialize_bool(deleted)));
+            }
+            if let Some(topic_id) = filter.topic_id {
+                query = query.filter(message::columns::topic_id.eq(topic_id));
+            }
+            if let Some(user_id) = filter.mentioned_user_id {
+                query = query.filter(diesel::dsl::exists(
+                    message_text_element::table
+                        .filter(message_text_element::columns::message_internal_id.eq(message::columns::internal_id.nullable()))
+                        .filter(message_text_element::columns::user_id.eq(user_id))
+                        .filter(message_text_element::columns::text_version.is_null())
+                ));
+            }
+            if let Some(ref element_type) = element_type_option {
+                query = query
+                    .filter(message::columns::tpe.eq("regular"))
+                    .filter(diesel::dsl::exists(
+                        message_content::table
+                            .filter(message_content::columns::message_internal_id.eq(message::columns::internal_id.nullable()))
+                            .filter(message_content::columns::element_type.eq(element_type))
+                    ));
+            }
+            query = match (anchor_option, backward) {
+                (Some(anchor), false) => query.filter(message::columns::internal_id.gt(*anchor)),
+                (Some(anchor), true) => query.filter(message::columns::internal_id.lt(*anchor)),
+                (None, _) => query,
+            };
+            query = if backward {
+                query.order_by(message::columns::internal_id.desc())
+            } else {
+                query.order_by(message::columns::internal_id.asc())
+            };
+            Ok(query
+                .limit(limit as i64)
+                .select(RawMessage::as_select())
+                .load(conn)?)
+        })?;
+        if backward { msgs.reverse(); }
+        Ok(msgs)
     }
-}
-
-impl MutableChatHistoryDao for SqliteDao {
-    fn backup(&mut self) -> Result<JoinHandle<()>> {
-        // Diesel does not expose backup API, so we use rusqlite for that.
-        use rusqlite::*;
-        use std::io::Write;
 
-        const PAGES_PER_STEP: std::ffi::c_int = 1024;
-        const PAUSE_BETWEEN_PAGES: std::time::Duration = std::time::Duration::ZERO;
-        const MAX_BACKUPS: usize = 3;
-
-        measure(|| {
-            let backup_path = self.backup_path();
-            if !backup_path.exists() {
-                fs::create_dir(&backup_path)?;
-            }
+    fn first_message_on_or_after(&self, chat: &Chat, ts: Timestamp) -> Result<Option<Message>> {
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value)?;
+        self.fetch_messages(|conn| {
+            use schema::*;
+            Ok(message::table
+                .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(message::columns::chat_id.eq(chat.id))
+                .filter(message::columns::time_sent.ge(*ts))
+                .order_by(message::columns::internal_id.asc())
+                .limit(1)
+                .select(RawMessage::as_select())
+                .load(conn)?)
+        }).map(|mut v| v.pop())
+    }
 
-            let filename = path_file_name(&self.db_file)?;
-            let backup_file = backup_path.join(filename);
-            ensure!(!backup_file.exists(), "File {filename} already exists in the backups dir, last backup was incomplete?");
+    fn chat_links(&self, chat: &Chat) -> Result<Vec<ChatLink>> {
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value)?;
+        let mut conn = self.get_conn()?;
 
-            {
-                let src_conn = Connection::open(&self.db_file)?;
-                let mut dst_conn = Connection::open(&backup_file)?;
-                let backup = backup::Backup::new(&src_conn, &mut dst_conn)?;
-                backup.run_to_completion(PAGES_PER_STEP, PAUSE_BETWEEN_PAGES, None)?;
-            }
+        use schema::*;
+        let occurrences: Vec<(Option<String>, i64, i64)> = message_text_element::table
+            .inner_join(message::table)
+            .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(message::columns::chat_id.eq(chat.id))
+            .filter(message_text_element::columns::element_type.eq("link"))
+            .filter(message_text_element::columns::text_version.is_null())
+            .order_by((message::columns::internal_id.asc(), message_text_element::columns::id.asc()))
+            .select((message_text_element::columns::href,
+                     message::columns::internal_id,
+                     message::columns::time_sent))
+            .load(&mut conn)?;
+        Ok(collect_chat_links(occurrences.into_iter().filter_map(|(href, internal_id, time_sent)| {
+            href.map(|href| (href, MessageInternalId(internal_id), Timestamp(time_sent)))
+        })))
+    }
 
-            let list_backups = move || ok(list_all_files(&backup_path, false)?
-                .into_iter()
-                .filter(|f| {
-                    let name = path_file_name(f).unwrap();
-                    name.starts_with(BACKUP_NAME_PREFIX) && name.ends_with(".zip")
-                })
-                .sorted()
-                .collect_vec());
+    fn search_messages(&self,
+                       ds_uuid: &PbUuid,
+                       text: &str,
+                       from_ids_option: Option<&[i64]>,
+                       after_option: Option<(Timestamp, MessageInternalId)>,
+                       limit: usize) -> Result<Vec<SearchHit>> {
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        // Searchable strings of transliterating datasets hold both original and transliterated text,
+        // so the transliterated query matches both
+        let transliterated_option = self.searchable_string_settings(ds_uuid)?.transliterate()
+            .then(|| transliterate(text)).flatten();
+        let text = transliterated_option.as_deref().unwrap_or(text);
+        // SQLite LIKE is case-insensitive for ASCII only
+        let pattern = format!("%{}%", text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let mut conn = self.get_conn()?;
 
-            let archive_path = self.choose_final_backup_path(".zip")?;
+        use schema::*;
+        let mut query = message::table
+            .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(message::columns::searchable_string.like(pattern).escape('\\'))
+            .into_boxed();
+        if let Some(from_ids) = from_ids_option {
+            query = query.filter(message::columns::from_id.eq_any(from_ids));
+        }
+        if let Some((Timestamp(ts), MessageInternalId(id))) = after_option {
+            query = query.filter(message::columns::time_sent.lt(ts)
+                .or(message::columns::time_sent.eq(ts).and(message::columns::internal_id.lt(id))));
+        }
+        let chat_id_by_internal_id: HashMap<i64, i64> = query
+            .order_by((message::columns::time_sent.desc(), message::columns::internal_id.desc()))
+            .limit(limit as i64)
+            .select((message::columns::internal_id, message::columns::chat_id))
+            .load::<(i64, i64)>(&mut conn)?
+            .into_iter()
+            .collect();
+        let msgs = utils::message::fetch(&mut conn, |conn| {
+            Ok(message::table
+                .filter(message::columns::internal_id.eq_any(chat_id_by_internal_id.keys()))
+                .order_by((message::columns::time_sent.desc(), message::columns::internal_id.desc()))
+                .select(RawMessage::as_select())
+                .load(conn)?)
+        })?;
+        Ok(msgs.into_iter()
+            .map(|message| SearchHit { chat_id: chat_id_by_internal_id[&message.internal_id], message })
+            .collect_vec())
+    }
 
-            let zip_jh = std::thread::spawn(move || {
-                // Wrapping logic in a closure to allow using ? operator
-                let inner = || -> EmptyRes {
-                    // TODO: This is (relatively) slow, Deflated-compression of ~170 MB DB takes ~6 sec on my machine
-                    //       in release mode. Can we do better?
-                    {
-                        let backup_bytes = fs::read(&backup_file)?;
-                        let mut archive = fs::OpenOptions::new()
-                            .write(true)
-                            .create_new(true)
-                            .open(archive_path)?;
-                        let mut zip = zip::ZipWriter::new(&mut archive);
+    fn messages_calendar(&self, chat: &Chat) -> Result<Vec<CalendarDay>> {
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value)?;
+        let mut conn = self.get_conn()?;
+        // SQLite 'localtime' modifier uses the same system timezone as LOCAL_TZ
+        let date_counts: Vec<DateCountWrapper> = sql_query(r"
+            SELECT date(time_sent, 'unixepoch', 'localtime') AS date, COUNT(*) AS count
+            FROM message
+            WHERE ds_uuid = ? AND chat_id = ?
+            GROUP BY 1
+            ORDER BY 1
+        ")
+            .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+            .bind::<sql_types::BigInt, _>(chat.id)
+            .load(&mut conn)?;
+        Ok(date_counts.into_iter()
+            .map(|dc| CalendarDay { date: dc.date, messages_count: dc.count as i32 })
+            .collect_vec())
+    }
 
-                        let options = zip::write::FileOptions::<'_, ()>::default().compression_method(zip::CompressionMethod::Deflated);
-                        zip.start_file(path_file_name(&backup_file)?, options)?;
-                        let mut buf = backup_bytes.as_slice();
-                        while !buf.is_empty() {
-                            let res = zip.write(buf)?;
-                            ensure!(res != 0, "Failed writing a backup, zip file no longer accepts source bytes!");
-                            buf = &buf[res..]
-                        }
-                        zip.finish()?;
-                    }
+    fn chat_folders(&self) -> Result<Vec<ChatFolder>> {
+        let mut conn = self.get_conn()?;
 
-                    fs::remove_file(&backup_file)?;
+        use schema::*;
+        let raw_folders = chat_folder::table
+            .order_by((chat_folder::columns::order.asc(), chat_folder::columns::id.asc()))
+            .select(RawChatFolder::as_select())
+            .load(&mut conn)?;
+        let mut raw_chats_by_folder = chat_folder_chat::table
+            .order_by((chat_folder_chat::columns::folder_id.asc(), chat_folder_chat::columns::order.asc()))
+            .select(RawChatFolderChat::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .into_group_map_by(|rc| rc.folder_id);
 
-                    for f in list_backups()?.iter().rev().skip(MAX_BACKUPS) {
-                        fs::remove_file(f)?;
-                    }
-                    Ok(())
-                };
-                measure(|| {
-                    // Panic in case something bad happens
-                    inner().expect("Backup compression failed!");
-                }, |_, t| log::info!("Backup compressed in {t} ms"))
-            });
+        let folders: Vec<ChatFolder> = raw_folders.into_iter()
+            .map(|raw| {
+                let raw_chats = raw.id.and_then(|id| raw_chats_by_folder.remove(&id)).unwrap_or_default();
+                utils::chat_folder::deserialize(raw, raw_chats)
+            })
+            .try_collect()?;
 
-            Ok(zip_jh)
-        }, |_, t| log::info!("Backup done in {t} ms"))
+        // Each top-level folder is followed by its subfolders
+        let (top_level, nested): (Vec<_>, Vec<_>) = folders.into_iter().partition(|f| f.parent_id_option.is_none());
+        Ok(top_level.into_iter()
+            .flat_map(|parent| {
+                let children = nested.iter().filter(|f| f.parent_id_option == Some(parent.id)).cloned().collect_vec();
+                std::iter::once(parent).chain(children)
+            })
+            .collect_vec())
     }
 
-    fn insert_dataset(&mut self, ds: Dataset) -> Result<Dataset> {
-        self.invalidate_cache()?;
+    fn redaction_log(&self, ds_uuid: &PbUuid) -> Result<Vec<RedactionLogEntry>> {
         let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
 
-        let raw_ds = utils::dataset::serialize(&ds);
+        use schema::*;
+        redaction_log::table
+            .filter(redaction_log::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .order_by(redaction_log::columns::id.asc())
+            .select(RawRedactionLogEntry::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::redaction_log::deserialize)
+            .try_collect()
+    }
 
-        insert_into(schema::dataset::dsl::dataset)
-            .values(raw_ds)
-            .execute(&mut conn)?;
+    fn trash(&self) -> Result<Vec<TrashItem>> {
+        let mut conn = self.get_conn()?;
 
-        Ok(ds)
+        use schema::*;
+        trash_item::table
+            .order_by(trash_item::columns::id.asc())
+            .select(RawTrashItem::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::trash_item::deserialize)
+            .try_collect()
     }
 
-    fn update_dataset(&mut self, old_uuid: PbUuid, ds: Dataset) -> Result<Dataset> {
-        ensure!(old_uuid == ds.uuid, "Changing dataset UUID is not supported");
-
-        self.invalidate_cache()?;
+    fn audit_log(&self) -> Result<Vec<AuditLogEntry>> {
         let mut conn = self.get_conn()?;
 
-        let raw_ds = utils::dataset::serialize(&ds);
+        use schema::*;
+        audit_log::table
+            .order_by(audit_log::columns::id.asc())
+            .select(RawAuditLogEntry::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::audit_log::deserialize)
+            .try_collect()
+    }
 
-        let uuid = Uuid::parse_str(&old_uuid.value).expect("Invalid UUID!");
+    fn retention_rules(&self, ds_uuid: &PbUuid) -> Result<Vec<RetentionRule>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
 
         use schema::*;
-        let updated_rows = update(dataset::dsl::dataset)
+        retention_rule::table
+            .filter(retention_rule::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .order_by(retention_rule::columns::id.asc())
+            .select(RawRetentionRule::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::retention_rule::deserialize)
+            .try_collect()
+    }
+
+    fn import_batches(&self, ds_uuid: &PbUuid) -> Result<Vec<ImportBatch>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
+
+        use schema::*;
+        import_batch::table
+            .filter(import_batch::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .order_by(import_batch::columns::time.asc())
+            .select(RawImportBatch::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::import_batch::deserialize)
+            .try_collect()
+    }
+
+    fn message_import_batch_uuids(&self, chat: &Chat, internal_ids: &[MessageInternalId]) -> Result<Vec<Option<PbUuid>>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value).expect("Invalid UUID!");
+
+        use schema::*;
+        let raw_uuids: HashMap<i64, Option<Vec<u8>>> = message::table
+            .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(message::columns::chat_id.eq(chat.id))
+            .filter(message::columns::internal_id.eq_any(internal_ids.iter().map(|id| **id)))
+            .select((message::columns::internal_id, message::columns::import_batch_uuid))
+            .load::<(i64, Option<Vec<u8>>)>(&mut conn)?
+            .into_iter()
+            .collect();
+        internal_ids.iter()
+            .map(|id| raw_uuids.get(&**id).cloned().flatten()
+                .map(|raw| utils::import_batch::deserialize_uuid(&raw))
+                .transpose())
+            .try_collect()
+    }
+
+    fn media_annotations(&self, ds_uuid: &PbUuid) -> Result<Vec<MediaAnnotation>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
+
+        use schema::*;
+        Ok(media_annotation::table
+            .filter(media_annotation::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .order_by((media_annotation::columns::path.asc(),
+                       media_annotation::columns::source.asc(),
+                       media_annotation::columns::tag.asc()))
+            .select(RawMediaAnnotation::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::media_annotation::deserialize)
+            .collect_vec())
+    }
+
+    fn user_identities(&self, ds_uuid: &PbUuid) -> Result<Vec<UserIdentity>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
+
+        use schema::*;
+        Ok(user_identity::table
+            .filter(user_identity::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .order_by((user_identity::columns::source.asc(),
+                       user_identity::columns::source_key.asc()))
+            .select(RawUserIdentity::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::user_identity::deserialize)
+            .collect_vec())
+    }
+
+    fn user_aliases(&self) -> Result<Vec<UserAlias>> {
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        user_alias::table
+            .order_by((user_alias::columns::ds_uuid.asc(),
+                       user_alias::columns::user_id.asc(),
+                       user_alias::columns::other_ds_uuid.asc(),
+                       user_alias::columns::other_user_id.asc()))
+            .select(RawUserAlias::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::user_alias::deserialize)
+            .try_collect()
+    }
+
+    fn chat_name_history(&self, chat: &Chat) -> Result<Vec<ChatNameEntry>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value)?;
+
+        use schema::*;
+        let former_names: Vec<String> = chat_former_name::table
+            .filter(chat_former_name::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(chat_former_name::columns::chat_id.eq(chat.id))
+            .order_by(chat_former_name::columns::name.asc())
+            .select(chat_former_name::columns::name)
+            .load(&mut conn)?;
+        let titles: Vec<(i64, Option<String>)> = message_content::table
+            .inner_join(message::table)
+            .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(message::columns::chat_id.eq(chat.id))
+            .filter(message::columns::subtype.eq_any(["group_create", "group_edit_title"]))
+            .order_by((message::columns::time_sent.asc(), message::columns::internal_id.asc()))
+            .select((message::columns::time_sent, message_content::columns::title))
+            .load(&mut conn)?;
+
+        Ok(former_names.into_iter()
+            .map(|name| ChatNameEntry { name, timestamp_option: None })
+            .chain(titles.into_iter().filter_map(|(timestamp, title_option)|
+                title_option.map(|name| ChatNameEntry { name, timestamp_option: Some(timestamp) })))
+            .collect_vec())
+    }
+
+    fn notes(&self, ds_uuid: &PbUuid, chat_id_option: Option<i64>) -> Result<Vec<Note>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+
+        use schema::*;
+        let mut query = note::table
+            .filter(note::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .into_boxed();
+        if let Some(chat_id) = chat_id_option {
+            query = query.filter(note::columns::chat_id.eq(chat_id));
+        }
+        query
+            .order_by((note::columns::chat_id.asc(), note::columns::id.asc()))
+            .select(RawNote::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .map(utils::note::deserialize)
+            .try_collect()
+    }
+
+    fn searchable_string_settings(&self, ds_uuid: &PbUuid) -> Result<SearchableStringSettings> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        Ok(load_searchable_string_settings(&mut conn, uuid.as_bytes())?.unwrap_or_default())
+    }
+
+    fn blocked_users_policy(&self, ds_uuid: &PbUuid) -> Result<BlockedUsersPolicy> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        Ok(load_blocked_users_policy(&mut conn, uuid.as_bytes())?.unwrap_or_default())
+    }
+
+    fn remote_media_root(&self, ds_uuid: &PbUuid) -> Result<Option<String>> {
+        use schema::*;
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        Ok(remote_media_root::table
+            .filter(remote_media_root::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .select(remote_media_root::columns::base_url)
+            .first::<String>(&mut conn)
+            .optional()?)
+    }
+
+    fn merge_templates(&self, ds_uuid: &PbUuid) -> Result<Vec<MergeTemplate>> {
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        use schema::*;
+        let raw_templates = merge_template::table
+            .filter(merge_template::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .select(RawMergeTemplate::as_select())
+            .load(&mut conn)?;
+        let mut raw_decisions = merge_template_decision::table
+            .filter(merge_template_decision::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .order_by((merge_template_decision::columns::entity_type.asc(),
+                       merge_template_decision::columns::entity_id.asc()))
+            .select(RawMergeTemplateDecision::as_select())
+            .load(&mut conn)?
+            .into_iter()
+            .into_group_map_by(|raw| raw.source_type.clone());
+        let mut templates: Vec<MergeTemplate> = raw_templates.into_iter()
+            .map(|raw| {
+                let decisions = raw_decisions.remove(&raw.source_type).unwrap_or_default();
+                utils::merge_template::deserialize(raw, decisions)
+            })
+            .try_collect()?;
+        templates.sort_by_key(|t| t.source_type);
+        Ok(templates)
+    }
+}
+
+impl ChatHistoryWriter for SqliteDao {
+    fn backup(&mut self) -> Result<JoinHandle<()>> {
+        // Diesel does not expose backup API, so we use rusqlite for that.
+        use rusqlite::*;
+        use std::io::Write;
+
+        const PAGES_PER_STEP: std::ffi::c_int = 1024;
+        const PAUSE_BETWEEN_PAGES: std::time::Duration = std::time::Duration::ZERO;
+        const MAX_BACKUPS: usize = 3;
+
+        measure(|| {
+            let backup_path = self.backup_path();
+            if !backup_path.exists() {
+                fs::create_dir(&backup_path)?;
+            }
+
+            let filename = path_file_name(&self.db_file)?;
+            let backup_file = backup_path.join(filename);
+            ensure!(!backup_file.exists(), "File {filename} already exists in the backups dir, last backup was incomplete?");
+
+            {
+                let src_conn = Connection::open(&self.db_file)?;
+                let mut dst_conn = Connection::open(&backup_file)?;
+                let backup = backup::Backup::new(&src_conn, &mut dst_conn)?;
+                backup.run_to_completion(PAGES_PER_STEP, PAUSE_BETWEEN_PAGES, None)?;
+            }
+
+            let archive_path = self.choose_final_backup_path(".zip")?;
+
+            let zip_jh = std::thread::spawn(move || {
+                // Wrapping logic in a closure to allow using ? operator
+                let inner = || -> EmptyRes {
+                    // TODO: This is (relatively) slow, Deflated-compression of ~170 MB DB takes ~6 sec on my machine
+                    //       in release mode. Can we do better?
+                    {
+                        let backup_bytes = fs::read(&backup_file)?;
+                        let mut archive = fs::OpenOptions::new()
+                            .write(true)
+                            .create_new(true)
+                            .open(archive_path)?;
+                        let mut zip = zip::ZipWriter::new(&mut archive);
+
+                        let options = zip::write::FileOptions::<'_, ()>::default().compression_method(zip::CompressionMethod::Deflated);
+                        zip.start_file(path_file_name(&backup_file)?, options)?;
+                        let mut buf = backup_bytes.as_slice();
+                        while !buf.is_empty() {
+                            let res = zip.write(buf)?;
+                            ensure!(res != 0, "Failed writing a backup, zip file no longer accepts source bytes!");
+                            buf = &buf[res..]
+                        }
+                        zip.finish()?;
+                    }
+
+                    fs::remove_file(&backup_file)?;
+
+                    for f in list_backups(&backup_path)?.iter().rev().skip(MAX_BACKUPS) {
+                        fs::remove_file(f)?;
+                    }
+                    Ok(())
+                };
+                measure(|| {
+                    // Panic in case something bad happens
+                    inner().expect("Backup compression failed!");
+                }, |_, t| log::info!("Backup compressed in {t} ms"))
+            });
+
+            Ok(zip_jh)
+        }, |_, t| log::info!("Backup done in {t} ms"))
+    }
+
+    fn insert_dataset(&mut self, ds: Dataset) -> Result<Dataset> {
+        self.invalidate_cache()?;
+        let mut conn = self.get_conn()?;
+
+        let raw_ds = utils::dataset::serialize(&ds);
+
+        insert_into(schema::dataset::dsl::dataset)
+            .values(raw_ds)
+            .execute(&mut conn)?;
+
+        Ok(ds)
+    }
+
+    fn update_dataset(&mut self, old_uuid: PbUuid, ds: Dataset) -> Result<Dataset> {
+        ensure!(old_uuid == ds.uuid, "Changing dataset UUID is not supported");
+
+        self.invalidate_cache()?;
+        let mut conn = self.get_conn()?;
+
+        let raw_ds = utils::dataset::serialize(&ds);
+
+        let uuid = Uuid::parse_str(&old_uuid.value).expect("Invalid UUID!");
+
+        use schema::*;
+        let updated_rows = update(dataset::dsl::dataset)
             .filter(dataset::columns::uuid.eq(uuid.as_bytes().as_slice()))
             .set(raw_ds)
             .execute(&mut conn)?;
@@ -679,6 +1378,7 @@ impl MutableChatHistoryDao for SqliteDao {
     }
 
     fn delete_dataset(&mut self, ds_uuid: PbUuid) -> EmptyRes {
+        self.put_to_trash(&ds_uuid, None)?;
         self.invalidate_cache()?;
         let mut conn = self.get_conn()?;
 
@@ -714,6 +1414,9 @@ impl MutableChatHistoryDao for SqliteDao {
                 .execute(conn)?;
 
             // Chats
+            delete(chat_folder_chat::dsl::chat_folder_chat)
+                .filter(chat_folder_chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
             delete(chat_member::dsl::chat_member)
                 .filter(chat_member::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
                 .execute(conn)?;
@@ -729,6 +1432,50 @@ impl MutableChatHistoryDao for SqliteDao {
                 .filter(user::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
                 .execute(conn)?;
 
+            delete(redaction_log::dsl::redaction_log)
+                .filter(redaction_log::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(retention_rule::dsl::retention_rule)
+                .filter(retention_rule::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(import_batch::dsl::import_batch)
+                .filter(import_batch::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(media_annotation::dsl::media_annotation)
+                .filter(media_annotation::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(user_identity::dsl::user_identity)
+                .filter(user_identity::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(user_alias::dsl::user_alias)
+                .filter(user_alias::columns::ds_uuid.eq(uuid.as_bytes().as_slice())
+                    .or(user_alias::columns::other_ds_uuid.eq(uuid.as_bytes().as_slice())))
+                .execute(conn)?;
+            delete(chat_former_name::dsl::chat_former_name)
+                .filter(chat_former_name::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(note::dsl::note)
+                .filter(note::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(searchable_string_settings::dsl::searchable_string_settings)
+                .filter(searchable_string_settings::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(blocked_users_policy::dsl::blocked_users_policy)
+                .filter(blocked_users_policy::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(blocked_user::dsl::blocked_user)
+                .filter(blocked_user::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(remote_media_root::dsl::remote_media_root)
+                .filter(remote_media_root::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(merge_template_decision::dsl::merge_template_decision)
+                .filter(merge_template_decision::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+            delete(merge_template::dsl::merge_template)
+                .filter(merge_template::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .execute(conn)?;
+
             // Finally, dataset itself
             let deleted_rows = delete(dataset::dsl::dataset)
                 .filter(dataset::columns::uuid.eq(uuid.as_bytes().as_slice()))
@@ -827,6 +1574,33 @@ impl MutableChatHistoryDao for SqliteDao {
                     .filter(chat_member::columns::user_id.eq(*old_id))
                     .set(chat_member::columns::user_id.eq(user.id))
                     .execute(conn)?;
+
+                update(message_text_element::dsl::message_text_element)
+                    .filter(message_text_element::columns::user_id.eq(*old_id))
+                    .filter(message_text_element::columns::message_internal_id.eq_any(
+                        message::table
+                            .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                            .select(message::columns::internal_id.nullable())
+                    ))
+                    .set(message_text_element::columns::user_id.eq(user.id))
+                    .execute(conn)?;
+
+                update(user_identity::dsl::user_identity)
+                    .filter(user_identity::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(user_identity::columns::user_id.eq(*old_id))
+                    .set(user_identity::columns::user_id.eq(user.id))
+                    .execute(conn)?;
+
+                update(user_alias::dsl::user_alias)
+                    .filter(user_alias::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(user_alias::columns::user_id.eq(*old_id))
+                    .set(user_alias::columns::user_id.eq(user.id))
+                    .execute(conn)?;
+                update(user_alias::dsl::user_alias)
+                    .filter(user_alias::columns::other_ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(user_alias::columns::other_user_id.eq(*old_id))
+                    .set(user_alias::columns::other_user_id.eq(user.id))
+                    .execute(conn)?;
             }
 
             // Update user name in "members" string field
@@ -939,6 +1713,13 @@ impl MutableChatHistoryDao for SqliteDao {
         let uuid_bytes = Vec::from(uuid.as_bytes().as_slice());
         let raw_chat = utils::chat::serialize(&chat, &uuid_bytes)?;
         let id_changed = chat.id != *old_id;
+        let old_name_option: Option<String> = schema::chat::table
+            .filter(schema::chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+            .filter(schema::chat::columns::id.eq(*old_id))
+            .select(schema::chat::columns::name)
+            .first::<Option<String>>(&mut conn)
+            .optional()?
+            .flatten();
 
         conn.transaction(|conn| {
             use schema::*;
@@ -970,6 +1751,36 @@ impl MutableChatHistoryDao for SqliteDao {
                     .set(message::columns::chat_id.eq(raw_chat.id))
                     .execute(conn)?;
 
+                update(chat_folder_chat::dsl::chat_folder_chat)
+                    .filter(chat_folder_chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(chat_folder_chat::columns::chat_id.eq(*old_id))
+                    .set(chat_folder_chat::columns::chat_id.eq(raw_chat.id))
+                    .execute(conn)?;
+
+                update(redaction_log::dsl::redaction_log)
+                    .filter(redaction_log::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(redaction_log::columns::chat_id.eq(*old_id))
+                    .set(redaction_log::columns::chat_id.eq(raw_chat.id))
+                    .execute(conn)?;
+
+                update(retention_rule::dsl::retention_rule)
+                    .filter(retention_rule::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(retention_rule::columns::chat_id.eq(*old_id))
+                    .set(retention_rule::columns::chat_id.eq(raw_chat.id))
+                    .execute(conn)?;
+
+                update(chat_former_name::dsl::chat_former_name)
+                    .filter(chat_former_name::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(chat_former_name::columns::chat_id.eq(*old_id))
+                    .set(chat_former_name::columns::chat_id.eq(raw_chat.id))
+                    .execute(conn)?;
+
+                update(note::dsl::note)
+                    .filter(note::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                    .filter(note::columns::chat_id.eq(*old_id))
+                    .set(note::columns::chat_id.eq(raw_chat.id))
+                    .execute(conn)?;
+
                 let ds_root = self.dataset_root(&chat.ds_uuid)?;
 
                 let old_rel_path = chat_root_rel_path(*old_id);
@@ -1009,8 +1820,27 @@ impl MutableChatHistoryDao for SqliteDao {
                         .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
                         .bind::<sql_types::BigInt, _>(chat.id)
                         .execute(conn)?;
+
+                    sql_query(r"
+                        UPDATE message_text_element
+                        SET path = REPLACE(path, ?, ?)
+                        WHERE message_internal_id IN (
+                            SELECT internal_id FROM message
+                            WHERE ds_uuid = ? AND chat_id = ?
+                        )
+                    ")
+                        .bind::<sql_types::Text, _>(&old_rel_path)
+                        .bind::<sql_types::Text, _>(&new_rel_path)
+                        .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                        .bind::<sql_types::BigInt, _>(chat.id)
+                        .execute(conn)?;
                 }
             }
+
+            // Keep the previous name so that chat could still be found by it
+            if let Some(old_name) = old_name_option.filter(|old_name| chat.name_option.as_ref() != Some(old_name)) {
+                insert_chat_former_names(conn, &[old_name], &uuid_bytes, raw_chat.id)?;
+            }
             ok(())
         })?;
 
@@ -1018,6 +1848,7 @@ impl MutableChatHistoryDao for SqliteDao {
     }
 
     fn delete_chat(&mut self, chat: Chat) -> EmptyRes {
+        self.put_to_trash(&chat.ds_uuid, Some(&chat))?;
         self.invalidate_cache()?;
         let mut conn = self.get_conn()?;
 
@@ -1042,7 +1873,15 @@ impl MutableChatHistoryDao for SqliteDao {
                     SELECT internal_id FROM message
                     WHERE ds_uuid = ? AND chat_id = ?
                 )
+                UNION ALL
+                SELECT rte.path, NULL AS thumbnail_path FROM message_text_element rte
+                WHERE rte.message_internal_id IN (
+                    SELECT internal_id FROM message
+                    WHERE ds_uuid = ? AND chat_id = ?
+                )
             ")
+                .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                .bind::<sql_types::BigInt, _>(chat.id)
                 .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
                 .bind::<sql_types::BigInt, _>(chat.id)
                 .load::<PathsWrapper>(conn)?
@@ -1076,6 +1915,22 @@ impl MutableChatHistoryDao for SqliteDao {
                 .execute(conn)?;
 
             // Chat
+            delete(chat_folder_chat::dsl::chat_folder_chat)
+                .filter(chat_folder_chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(chat_folder_chat::columns::chat_id.eq(chat.id))
+                .execute(conn)?;
+            delete(retention_rule::dsl::retention_rule)
+                .filter(retention_rule::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(retention_rule::columns::chat_id.eq(chat.id))
+                .execute(conn)?;
+            delete(chat_former_name::dsl::chat_former_name)
+                .filter(chat_former_name::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(chat_former_name::columns::chat_id.eq(chat.id))
+                .execute(conn)?;
+            delete(note::dsl::note)
+                .filter(note::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(note::columns::chat_id.eq(chat.id))
+                .execute(conn)?;
             delete(chat_member::dsl::chat_member)
                 .filter(chat_member::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
                 .filter(chat_member::columns::chat_id.eq(chat.id))
@@ -1171,23 +2026,748 @@ impl MutableChatHistoryDao for SqliteDao {
         Ok(())
     }
 
-    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> EmptyRes {
+    fn override_myself(&mut self, ds_uuid: &PbUuid, new_myself_id: UserId, chat_id_option: Option<ChatId>) -> EmptyRes {
+        let old_myself_id = self.myself(ds_uuid)?.id();
+        ensure!(self.user_option(ds_uuid, *new_myself_id)?.is_some(), "User {} not found", *new_myself_id);
+        if new_myself_id == old_myself_id { return Ok(()); }
+        let cwds = match chat_id_option {
+            Some(chat_id) => {
+                let cwd = self.chat_option(ds_uuid, *chat_id)?.context("Chat not found")?;
+                ensure!(cwd.chat.member_ids.contains(&*new_myself_id),
+                        "User {} is not a member of chat {}", *new_myself_id, cwd.chat.qualified_name());
+                vec![cwd]
+            }
+            None => self.chats(ds_uuid)?,
+        };
+
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
         let mut conn = self.get_conn()?;
+        conn.transaction(|conn| {
+            use schema::*;
+            match chat_id_option {
+                Some(chat_id) => {
+                    sql_query(r"
+                        UPDATE message
+                        SET from_id = CASE from_id WHEN ? THEN ? ELSE ? END
+                        WHERE ds_uuid = ? AND chat_id = ? AND from_id IN (?, ?)
+                    ")
+                        .bind::<sql_types::BigInt, _>(*old_myself_id)
+                        .bind::<sql_types::BigInt, _>(*new_myself_id)
+                        .bind::<sql_types::BigInt, _>(*old_myself_id)
+                        .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                        .bind::<sql_types::BigInt, _>(*chat_id)
+                        .bind::<sql_types::BigInt, _>(*old_myself_id)
+                        .bind::<sql_types::BigInt, _>(*new_myself_id)
+                        .execute(conn)?;
+                }
+                None => {
+                    for (user_id, is_myself) in [(old_myself_id, false), (new_myself_id, true)] {
+                        update(user::table)
+                            .filter(user::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                            .filter(user::columns::id.eq(*user_id))
+                            .set(user::columns::is_myself.eq(utils::serialize_bool(is_myself)))
+                            .execute(conn)?;
+                    }
+                    for cwd in cwds.iter() {
+                        let member_ids = std::iter::once(*new_myself_id)
+                            .chain(cwd.chat.member_ids.iter().cloned().filter(|id| *id != *new_myself_id));
+                        let raw_members = member_ids.enumerate().map(|(order, user_id)| RawChatMember {
+                            ds_uuid: Vec::from(uuid.as_bytes().as_slice()),
+                            chat_id: cwd.chat.id,
+                            user_id,
+                            order: order as i32,
+                        }).collect_vec();
+                        delete(chat_member::table)
+                            .filter(chat_member::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                            .filter(chat_member::columns::chat_id.eq(cwd.chat.id))
+                            .execute(conn)?;
+                        insert_into(chat_member::table).values(&raw_members).execute(conn)?;
+                    }
+                }
+            }
+            ok(())
+        })?;
 
-        let dst_ds_root = self.dataset_root(&chat.ds_uuid)?;
-        let uuid = Uuid::parse_str(&chat.ds_uuid.value).expect("Invalid UUID!");
-        let uuid_bytes = Vec::from(uuid.as_bytes().as_slice());
+        self.invalidate_cache()
+    }
 
-        self.copy_messages(&mut conn, &msgs, chat.id,
-                           &uuid_bytes, src_ds_root, &dst_ds_root)?;
+    fn insert_chat_folder(&mut self, folder: ChatFolder) -> Result<ChatFolder> {
+        self.validate_chat_folder(&folder, true)?;
+        let mut conn = self.get_conn()?;
 
-        Ok(())
+        conn.transaction(|conn| {
+            use schema::*;
+
+            let (mut raw_folder, _) = utils::chat_folder::serialize(&folder);
+            raw_folder.id = None;
+            let id = insert_into(chat_folder::table)
+                .values(&raw_folder)
+                .returning(chat_folder::columns::id)
+                .get_result::<i64>(conn)?;
+
+            let folder = ChatFolder { id, ..folder };
+            let (_, raw_chats) = utils::chat_folder::serialize(&folder);
+            insert_into(chat_folder_chat::table).values(&raw_chats).execute(conn)?;
+            Ok(folder)
+        })
     }
-}
 
-impl ShiftableChatHistoryDao for SqliteDao {
-    fn shift_dataset_time(&mut self, uuid: &PbUuid, hours_shift: i32) -> EmptyRes {
-        // Messages aren't cached so no need to invalidate cache
+    fn update_chat_folder(&mut self, folder: ChatFolder) -> Result<ChatFolder> {
+        self.validate_chat_folder(&folder, false)?;
+        let mut conn = self.get_conn()?;
+
+        conn.transaction(|conn| {
+            use schema::*;
+
+            let (raw_folder, raw_chats) = utils::chat_folder::serialize(&folder);
+            let updated_rows = update(chat_folder::table)
+                .filter(chat_folder::columns::id.eq(folder.id))
+                .set((chat_folder::columns::name.eq(&raw_folder.name),
+                      chat_folder::columns::parent_id.eq(raw_folder.parent_id),
+                      chat_folder::columns::order.eq(raw_folder.order)))
+                .execute(conn)?;
+            ensure!(updated_rows == 1, "{updated_rows} rows changed when updaing folder {}", folder.id);
+
+            delete(chat_folder_chat::table)
+                .filter(chat_folder_chat::columns::folder_id.eq(folder.id))
+                .execute(conn)?;
+            insert_into(chat_folder_chat::table).values(&raw_chats).execute(conn)?;
+            Ok(folder)
+        })
+    }
+
+    fn delete_chat_folder(&mut self, id: i64) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+
+        conn.transaction(|conn| {
+            use schema::*;
+
+            let mut ids: Vec<i64> = chat_folder::table
+                .filter(chat_folder::columns::parent_id.eq(id))
+                .select(chat_folder::columns::id)
+                .load(conn)?;
+            ids.push(id);
+
+            delete(chat_folder_chat::table)
+                .filter(chat_folder_chat::columns::folder_id.eq_any(&ids))
+                .execute(conn)?;
+            // Subfolders are deleted first to satisfy FK
+            delete(chat_folder::table)
+                .filter(chat_folder::columns::parent_id.eq(id))
+                .execute(conn)?;
+            let deleted_rows = delete(chat_folder::table)
+                .filter(chat_folder::columns::id.eq(id))
+                .execute(conn)?;
+            ensure!(deleted_rows == 1, "{deleted_rows} rows changed when deleting folder {id}");
+            Ok(())
+        })
+    }
+
+    fn redact_messages(&mut self, chat: &Chat, internal_ids: &[MessageInternalId]) -> Result<RedactionLogEntry> {
+        let ids = internal_ids.iter().map(|id| id.0).unique().collect_vec();
+        ensure!(!ids.is_empty(), "No messages to redact");
+
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value).expect("Invalid UUID!");
+        let ds_root = self.dataset_root(&chat.ds_uuid)?;
+
+        use schema::*;
+        let (entry, relative_paths) = conn.transaction(|conn| {
+            let found_count: i64 = message::table
+                .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(message::columns::chat_id.eq(chat.id))
+                .filter(message::columns::internal_id.eq_any(&ids))
+                .count()
+                .get_result(conn)?;
+            ensure!(found_count as usize == ids.len(),
+                    "Only {found_count} of {} messages to redact found in chat {}", ids.len(), chat.qualified_name());
+
+            let load_paths = |raw: Vec<(Option<String>, Option<String>)>| -> Vec<String> {
+                raw.into_iter().flat_map(|(p, tp)| [p, tp]).flatten().unique().collect_vec()
+            };
+            let mut raw_paths: Vec<(Option<String>, Option<String>)> = message_content::table
+                .filter(message_content::columns::message_internal_id.eq_any(&ids))
+                .select((message_content::columns::path, message_content::columns::thumbnail_path))
+                .load(conn)?;
+            let rte_paths: Vec<Option<String>> = message_text_element::table
+                .filter(message_text_element::columns::message_internal_id.eq_any(&ids))
+                .select(message_text_element::columns::path)
+                .load(conn)?;
+            raw_paths.extend(rte_paths.into_iter().map(|p| (p, None)));
+            let relative_paths = load_paths(raw_paths);
+
+            delete(message_content::table)
+                .filter(message_content::columns::message_internal_id.eq_any(&ids))
+                .execute(conn)?;
+            delete(message_text_element::table)
+                .filter(message_text_element::columns::message_internal_id.eq_any(&ids))
+                .execute(conn)?;
+            delete(message::table)
+                .filter(message::columns::internal_id.eq_any(&ids))
+                .execute(conn)?;
+
+            update(chat::table)
+                .filter(chat::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                .filter(chat::columns::id.eq(chat.id))
+                .set(chat::columns::msg_count.eq(chat::columns::msg_count - ids.len() as i32))
+                .execute(conn)?;
+
+            // Files might still be used by other messages of the dataset
+            let mut used_paths: HashSet<String> = load_paths(message_content::table
+                .filter(message_content::columns::message_internal_id.eq_any(
+                    message::table
+                        .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                        .select(message::columns::internal_id.nullable())))
+                .filter(message_content::columns::path.eq_any(&relative_paths)
+                    .or(message_content::columns::thumbnail_path.eq_any(&relative_paths)))
+                .select((message_content::columns::path, message_content::columns::thumbnail_path))
+                .load(conn)?).into_iter().collect();
+            let used_rte_paths: Vec<Option<String>> = message_text_element::table
+                .filter(message_text_element::columns::message_internal_id.eq_any(
+                    message::table
+                        .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                        .select(message::columns::internal_id.nullable())))
+                .filter(message_text_element::columns::path.eq_any(&relative_paths))
+                .select(message_text_element::columns::path)
+                .load(conn)?;
+            used_paths.extend(used_rte_paths.into_iter().flatten());
+            let relative_paths = relative_paths.into_iter().filter(|p| !used_paths.contains(p)).collect_vec();
+
+            let entry = RedactionLogEntry {
+                ds_uuid: chat.ds_uuid.clone(),
+                timestamp: Local::now().timestamp(),
+                chat_id_option: Some(chat.id),
+                deleted_messages_count: ids.len() as i32,
+                scrubbed_messages_count: 0,
+                scrubbed_strings_count: 0,
+            };
+            insert_into(redaction_log::table)
+                .values(utils::redaction_log::serialize(&entry, uuid.as_bytes()))
+                .execute(conn)?;
+            ok((entry, relative_paths))
+        })?;
+
+        // Files are removed only after changes are committed
+        for relative in relative_paths.iter() {
+            let path = ds_root.to_absolute(relative);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        drop(conn);
+        self.purge_redacted_leftovers()?;
+        Ok(entry)
+    }
+
+    fn redact_strings(&mut self,
+                      ds_uuid: &PbUuid,
+                      chat_id_option: Option<ChatId>,
+                      strings: &[String]) -> Result<RedactionLogEntry> {
+        let strings = strings.iter().filter(|s| !s.is_empty()).unique().collect_vec();
+        ensure!(!strings.is_empty(), "No strings to redact");
+        ensure!(!strings.iter().any(|s| REDACTED_PLACEHOLDER.contains(s.as_str())),
+                "Strings to redact must not be a part of {REDACTED_PLACEHOLDER}");
+        let settings = self.searchable_string_settings(ds_uuid)?;
+
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value).expect("Invalid UUID!");
+
+        // Textual columns that may contain user-provided text
+        const MESSAGE_COLUMNS: &[&str] = &["forward_from_name"];
+        const RTE_COLUMNS: &[&str] = &["text", "href", "handle"];
+        const CONTENT_COLUMNS: &[&str] = &["file_name", "emoji", "title", "performer", "ocr_text", "address",
+                                           "location_updates", "poll_question", "poll_options", "first_name",
+                                           "last_name", "phone_number", "emails", "members"];
+
+        // Parameters: ?1 - dataset UUID, ?2 - chat ID (nullable), ?3 - string to redact, ?4 - placeholder
+        const SCOPE: &str = "SELECT internal_id FROM message WHERE ds_uuid = ?1 AND (?2 IS NULL OR chat_id = ?2)";
+        let contains = |prefix: &str, columns: &[&str]|
+            columns.iter().map(|c| format!("INSTR(COALESCE({prefix}{c}, ''), ?3) > 0")).join(" OR ");
+        let replace = |columns: &[&str]|
+            columns.iter().map(|c| format!("{c} = REPLACE({c}, ?3, ?4)")).join(", ");
+
+        let select_affected_sql = format!(r"
+            SELECT m.internal_id FROM message m
+            WHERE m.ds_uuid = ?1 AND (?2 IS NULL OR m.chat_id = ?2) AND (
+              {} OR
+              EXISTS (SELECT 1 FROM message_text_element rte
+                      WHERE rte.message_internal_id = m.internal_id AND ({})) OR
+              EXISTS (SELECT 1 FROM message_content mc
+                      WHERE mc.message_internal_id = m.internal_id AND ({}))
+            )
+        ", contains("m.", MESSAGE_COLUMNS), contains("rte.", RTE_COLUMNS), contains("mc.", CONTENT_COLUMNS));
+        let update_sqls = [
+            format!("UPDATE message SET {} WHERE ds_uuid = ?1 AND (?2 IS NULL OR chat_id = ?2) AND ({})",
+                    replace(MESSAGE_COLUMNS), contains("", MESSAGE_COLUMNS)),
+            format!("UPDATE message_text_element SET {} WHERE message_internal_id IN ({SCOPE}) AND ({})",
+                    replace(RTE_COLUMNS), contains("", RTE_COLUMNS)),
+            format!("UPDATE message_content SET {} WHERE message_internal_id IN ({SCOPE}) AND ({})",
+                    replace(CONTENT_COLUMNS), contains("", CONTENT_COLUMNS)),
+            // Former names are a part of the primary key, names that become identical are collapsed
+            format!("UPDATE OR REPLACE chat_former_name SET {} WHERE ds_uuid = ?1 AND (?2 IS NULL OR chat_id = ?2) AND ({})",
+                    replace(&["name"]), contains("", &["name"])),
+            format!("UPDATE note SET {} WHERE ds_uuid = ?1 AND (?2 IS NULL OR chat_id = ?2) AND ({})",
+                    replace(&["text"]), contains("", &["text"])),
+        ];
+
+        use schema::*;
+        let entry = conn.transaction(|conn| {
+            let mut affected_ids: HashSet<i64> = HashSet::new();
+            for s in strings.iter() {
+                affected_ids.extend(sql_query(&select_affected_sql)
+                    .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                    .bind::<sql_types::Nullable<sql_types::BigInt>, _>(chat_id_option.map(|id| id.0))
+                    .bind::<sql_types::Text, _>(s.as_str())
+                    .load::<InternalIdWrapper>(conn)?
+                    .into_iter()
+                    .map(|w| w.internal_id));
+
+                for sql in update_sqls.iter() {
+                    sql_query(sql)
+                        .bind::<sql_types::Binary, _>(uuid.as_bytes().as_slice())
+                        .bind::<sql_types::Nullable<sql_types::BigInt>, _>(chat_id_option.map(|id| id.0))
+                        .bind::<sql_types::Text, _>(s.as_str())
+                        .bind::<sql_types::Text, _>(REDACTED_PLACEHOLDER)
+                        .execute(conn)?;
+                }
+            }
+
+            // Searchable strings are derived from the rest of the message
+            let affected_ids = affected_ids.into_iter().sorted().collect_vec();
+            for ids_chunk in affected_ids.chunks(BATCH_SIZE) {
+                let msgs = utils::message::fetch(conn, |conn| {
+                    Ok(message::table
+                        .filter(message::columns::internal_id.eq_any(ids_chunk))
+                        .select(RawMessage::as_select())
+                        .load(conn)?)
+                })?;
+                for msg in msgs {
+                    update(message::table)
+                        .filter(message::columns::internal_id.eq(msg.internal_id))
+                        .set(message::columns::searchable_string.eq(make_searchable_string_with(&msg.text, msg.typed(), &settings)))
+                        .execute(conn)?;
+                }
+            }
+
+            let entry = RedactionLogEntry {
+                ds_uuid: ds_uuid.clone(),
+                timestamp: Local::now().timestamp(),
+                chat_id_option: chat_id_option.map(|id| id.0),
+                deleted_messages_count: 0,
+                scrubbed_messages_count: affected_ids.len() as i32,
+                scrubbed_strings_count: strings.len() as i32,
+            };
+            insert_into(redaction_log::table)
+                .values(utils::redaction_log::serialize(&entry, uuid.as_bytes()))
+                .execute(conn)?;
+            ok(entry)
+        })?;
+
+        drop(conn);
+        // Trash items keep full copies of deleted chats
+        let strings = strings.into_iter().cloned().collect_vec();
+        self.redact_strings_in_trash(ds_uuid, chat_id_option, &strings)?;
+        self.purge_redacted_leftovers()?;
+        Ok(entry)
+    }
+
+    fn insert_retention_rule(&mut self, rule: RetentionRule) -> Result<RetentionRule> {
+        ensure!(rule.max_age_days > 0, "Retention period must be positive");
+        RetentionAction::resolve(rule.action)?;
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == rule.ds_uuid), "Dataset {} not found", rule.ds_uuid.value);
+        if let Some(chat_id) = rule.chat_id_option {
+            ensure!(self.chat_option(&rule.ds_uuid, chat_id)?.is_some(), "Chat {chat_id} not found");
+        }
+
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&rule.ds_uuid.value).expect("Invalid UUID!");
+
+        use schema::*;
+        let id = insert_into(retention_rule::table)
+            .values(utils::retention_rule::serialize(&rule, uuid.as_bytes())?)
+            .returning(retention_rule::columns::id)
+            .get_result::<i64>(&mut conn)?;
+        Ok(RetentionRule { id, ..rule })
+    }
+
+    fn delete_retention_rule(&mut self, id: i64) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        let deleted_rows = delete(retention_rule::table)
+            .filter(retention_rule::columns::id.eq(id))
+            .execute(&mut conn)?;
+        ensure!(deleted_rows == 1, "{deleted_rows} rows changed when deleting retention rule {id}");
+        Ok(())
+    }
+
+    fn apply_retention_rules(&mut self,
+                             ds_uuid: &PbUuid,
+                             now: Timestamp,
+                             dry_run: bool) -> Result<Vec<RetentionRuleReport>> {
+        let rules = self.retention_rules(ds_uuid)?;
+        if rules.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut conn = self.get_conn()?;
+        let ds_root = self.dataset_root(ds_uuid)?;
+
+        let (reports, freed_paths) = conn.transaction(|conn| {
+            let mut reports = vec![];
+            let mut freed_paths = vec![];
+            for rule in rules {
+                let (report, paths) = apply_retention_rule(conn, rule, &ds_root, now, dry_run)?;
+                log::info!("Retention rule #{} affected {} messages, {} files ({} bytes){}",
+                           report.rule.id, report.affected_messages_count,
+                           report.deleted_files_count, report.deleted_files_bytes,
+                           if dry_run { " (dry run)" } else { "" });
+                reports.push(report);
+                freed_paths.extend(paths);
+            }
+            ok((reports, freed_paths))
+        })?;
+
+        // Files are removed only after changes are committed
+        for path in freed_paths {
+            fs::remove_file(path)?;
+        }
+
+        Ok(reports)
+    }
+
+    fn record_audit_entry(&mut self, entry: AuditLogEntry) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        insert_into(audit_log::table)
+            .values(utils::audit_log::serialize(&entry)?)
+            .execute(&mut conn)?;
+        Ok(())
+    }
+
+    fn restore_from_trash(&mut self, id: i64) -> EmptyRes {
+        let item = self.trash()?.into_iter().find(|item| item.id == id)
+            .with_context(|| format!("Trash item {id} not found"))?;
+        {
+            let trash_dao = SqliteDao::load(&self.trash_item_db_file(id))?;
+            match item.chat_id_option {
+                Some(chat_id) => {
+                    let ds = self.datasets()?.into_iter().find(|ds| ds.uuid == item.ds_uuid)
+                        .with_context(|| format!("Dataset {} no longer exists, restore it first", item.ds_uuid.value))?;
+                    let cwd = trash_dao.chat_option(&item.ds_uuid, chat_id)?
+                        .with_context(|| format!("Chat {chat_id} not found in trash item {id}"))?;
+                    self.copy_chats_from(&trash_dao, &ds, &[cwd])?;
+                }
+                None => self.copy_datasets_from(&trash_dao, std::slice::from_ref(&item.ds_uuid))?,
+            }
+        }
+        self.remove_trash_item(id)
+    }
+
+    fn purge_trash(&mut self, deleted_before: Timestamp) -> Result<usize> {
+        let ids = self.trash()?.into_iter()
+            .filter(|item| item.timestamp < deleted_before.0)
+            .map(|item| item.id)
+            .collect_vec();
+        for &id in ids.iter() {
+            self.remove_trash_item(id)?;
+        }
+        Ok(ids.len())
+    }
+
+    fn compact(&mut self) -> Result<CompactionReport> {
+        let size_before_bytes = fs::metadata(&self.db_file)?.len() as i64;
+        measure(|| {
+            let mut conn = self.get_conn()?;
+            // Cannot be run within a transaction
+            sql_query("VACUUM").execute(&mut conn)?;
+            sql_query("ANALYZE").execute(&mut conn)?;
+            ok(())
+        }, |_, t| log::info!("Database {} compacted in {t} ms", self.db_file.display()))?;
+        let size_after_bytes = fs::metadata(&self.db_file)?.len() as i64;
+        Ok(CompactionReport { size_before_bytes, size_after_bytes })
+    }
+
+    fn insert_messages(&mut self, msgs: Vec<Message>, chat: &Chat, src_ds_root: &DatasetRoot) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+
+        let dst_ds_root = self.dataset_root(&chat.ds_uuid)?;
+        let uuid = Uuid::parse_str(&chat.ds_uuid.value).expect("Invalid UUID!");
+        let uuid_bytes = Vec::from(uuid.as_bytes().as_slice());
+
+        self.copy_messages(&mut conn, &msgs, &vec![None; msgs.len()], chat.id,
+                           &uuid_bytes, src_ds_root, &dst_ds_root)?;
+
+        Ok(())
+    }
+
+    fn copy_import_batches_from(&mut self, src: &dyn ChatHistoryReader, src_ds_uuid: &PbUuid, dst_ds_uuid: &PbUuid) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+        let raw_uuid = utils::import_batch::serialize_uuid(dst_ds_uuid)?;
+        insert_import_batches(&mut conn, &src.import_batches(src_ds_uuid)?, &raw_uuid)
+    }
+
+    fn insert_messages_from(&mut self,
+                            src: &dyn ChatHistoryReader,
+                            src_chat: &Chat,
+                            msgs: Vec<Message>,
+                            chat: &Chat,
+                            src_ds_root: &DatasetRoot) -> EmptyRes {
+        let import_batch_uuids =
+            src.message_import_batch_uuids(src_chat, &msgs.iter().map(|m| m.internal_id()).collect_vec())?;
+        let mut conn = self.get_conn()?;
+
+        let dst_ds_root = self.dataset_root(&chat.ds_uuid)?;
+        let raw_uuid = utils::import_batch::serialize_uuid(&chat.ds_uuid)?;
+
+        self.copy_messages(&mut conn, &msgs, &import_batch_uuids, chat.id,
+                           &raw_uuid, src_ds_root, &dst_ds_root)
+    }
+
+    fn set_media_annotations(&mut self, ds_uuid: &PbUuid, path: &str, source: &str, tags: &[String]) -> EmptyRes {
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == *ds_uuid), "Dataset {} not found", ds_uuid.value);
+        let mut conn = self.get_conn()?;
+        let raw_uuid = Uuid::parse_str(&ds_uuid.value)?.as_bytes().to_vec();
+
+        let raw_annotations = tags.iter()
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .unique()
+            .map(|tag| utils::media_annotation::serialize(&MediaAnnotation {
+                path: path.to_owned(),
+                tag: tag.to_owned(),
+                source: source.to_owned(),
+            }, &raw_uuid))
+            .collect_vec();
+
+        use schema::*;
+        conn.transaction(|txn| {
+            delete(media_annotation::dsl::media_annotation)
+                .filter(media_annotation::columns::ds_uuid.eq(raw_uuid.as_slice()))
+                .filter(media_annotation::columns::path.eq(path))
+                .filter(media_annotation::columns::source.eq(source))
+                .execute(txn)?;
+            insert_into(media_annotation::table).values(&raw_annotations).execute(txn)?;
+            Ok(())
+        })
+    }
+
+    fn insert_user_identities(&mut self, ds_uuid: &PbUuid, identities: &[UserIdentity]) -> EmptyRes {
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == *ds_uuid), "Dataset {} not found", ds_uuid.value);
+        let mut conn = self.get_conn()?;
+        let raw_uuid = Uuid::parse_str(&ds_uuid.value)?.as_bytes().to_vec();
+        insert_user_identities(&mut conn, identities, &raw_uuid)
+    }
+
+    fn insert_chat_former_names(&mut self, chat: &Chat, names: &[String]) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+        let raw_uuid = Uuid::parse_str(&chat.ds_uuid.value)?.as_bytes().to_vec();
+        insert_chat_former_names(&mut conn, names, &raw_uuid, chat.id)
+    }
+
+    fn insert_note(&mut self, note: Note) -> Result<Note> {
+        ensure!(!note.text.trim().is_empty(), "Note text is empty");
+        let chat = self.chat_option(&note.ds_uuid, note.chat_id)?
+            .with_context(|| format!("Chat {} not found", note.chat_id))?.chat;
+        let note = match (note.message_source_id_option, note.message_internal_id_option) {
+            (None, None) => note,
+            (Some(source_id), None) => {
+                ensure!(self.message_option(&chat, MessageSourceId(source_id))?.is_some(),
+                        "Message {source_id} not found in chat {}", chat.qualified_name());
+                note
+            }
+            (None, Some(internal_id)) => {
+                let msg = self.message_option_by_internal_id(&chat, MessageInternalId(internal_id))?
+                    .with_context(|| format!("Message #{internal_id} not found in chat {}", chat.qualified_name()))?;
+                // Source ID survives re-imports, so it's preferred whenever message has one
+                match msg.source_id_option {
+                    Some(source_id) => Note { message_source_id_option: Some(source_id), message_internal_id_option: None, ..note },
+                    None => note,
+                }
+            }
+            (Some(_), Some(_)) => bail!("Note should refer to a message either by source ID or by internal ID"),
+        };
+
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        let id = insert_into(note::table)
+            .values(utils::note::serialize(&note)?)
+            .returning(note::columns::id)
+            .get_result::<i64>(&mut conn)?;
+        Ok(Note { id, ..note })
+    }
+
+    fn update_note(&mut self, id: i64, text: &str, timestamp: Timestamp) -> Result<Note> {
+        ensure!(!text.trim().is_empty(), "Note text is empty");
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        let updated_rows = update(note::table)
+            .filter(note::columns::id.eq(id))
+            .set((note::columns::text.eq(text),
+                  note::columns::time.eq(*timestamp)))
+            .execute(&mut conn)?;
+        ensure!(updated_rows == 1, "{updated_rows} rows changed when updating note {id}");
+
+        let raw_note = note::table
+            .filter(note::columns::id.eq(id))
+            .select(RawNote::as_select())
+            .first(&mut conn)?;
+        utils::note::deserialize(raw_note)
+    }
+
+    fn delete_note(&mut self, id: i64) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+
+        use schema::*;
+        let deleted_rows = delete(note::table)
+            .filter(note::columns::id.eq(id))
+            .execute(&mut conn)?;
+        ensure!(deleted_rows == 1, "{deleted_rows} rows changed when deleting note {id}");
+        Ok(())
+    }
+
+    fn update_searchable_string_settings(&mut self, ds_uuid: &PbUuid, settings: &SearchableStringSettings) -> EmptyRes {
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == *ds_uuid), "Dataset {} not found", ds_uuid.value);
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        store_searchable_string_settings(&mut conn, settings, uuid.as_bytes())
+    }
+
+    fn recompute_searchable_strings(&mut self, ds_uuid: &PbUuid) -> Result<usize> {
+        const BATCH_SIZE: usize = 5_000;
+        let settings = self.searchable_string_settings(ds_uuid)?;
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+
+        use schema::*;
+        conn.transaction(|conn| {
+            let mut changed_count = 0;
+            let mut last_internal_id = i64::MIN;
+            loop {
+                let msgs = utils::message::fetch(conn, |conn| {
+                    Ok(message::table
+                        .filter(message::columns::ds_uuid.eq(uuid.as_bytes().as_slice()))
+                        .filter(message::columns::internal_id.gt(last_internal_id))
+                        .order_by(message::columns::internal_id.asc())
+                        .limit(BATCH_SIZE as i64)
+                        .select(RawMessage::as_select())
+                        .load(conn)?)
+                })?;
+                let Some(last_msg) = msgs.last() else { break };
+                last_internal_id = last_msg.internal_id;
+                for msg in msgs.iter() {
+                    let searchable_string = make_searchable_string_with(&msg.text, msg.typed(), &settings);
+                    if searchable_string != msg.searchable_string {
+                        update(message::table)
+                            .filter(message::columns::internal_id.eq(msg.internal_id))
+                            .set(message::columns::searchable_string.eq(searchable_string))
+                            .execute(conn)?;
+                        changed_count += 1;
+                    }
+                }
+            }
+            Ok(changed_count)
+        })
+    }
+
+    fn update_blocked_users_policy(&mut self, ds_uuid: &PbUuid, policy: &BlockedUsersPolicy) -> EmptyRes {
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == *ds_uuid), "Dataset {} not found", ds_uuid.value);
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        conn.transaction(|conn| store_blocked_users_policy(conn, policy, uuid.as_bytes()))
+    }
+
+    fn apply_blocked_users_policy(&mut self, ds_uuid: &PbUuid) -> Result<usize> {
+        let policy = self.blocked_users_policy(ds_uuid)?;
+        let tombstone = match policy.action() {
+            _ if policy.user_ids.is_empty() => return Ok(0),
+            BlockedUserAction::Import => return Ok(0),
+            BlockedUserAction::Tombstone => true,
+            BlockedUserAction::Skip => false,
+        };
+
+        let mut conn = self.get_conn()?;
+        let ds_root = self.dataset_root(ds_uuid)?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+
+        let (affected_count, freed_paths) = conn.transaction(|conn| {
+            apply_blocked_users_policy(conn, uuid.as_bytes(), &policy.user_ids, tombstone, &ds_root)
+        })?;
+        log::info!("Blocked users policy affected {affected_count} messages, {} files deleted", freed_paths.len());
+
+        // Files are removed only after changes are committed
+        for path in freed_paths {
+            fs::remove_file(path)?;
+        }
+
+        Ok(affected_count)
+    }
+
+    fn store_merge_template(&mut self, template: &MergeTemplate) -> EmptyRes {
+        let ds_uuid = &template.ds_uuid;
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == *ds_uuid), "Dataset {} not found", ds_uuid.value);
+        let mut conn = self.get_conn()?;
+        conn.transaction(|conn| store_merge_template(conn, template))
+    }
+
+    fn update_remote_media_root(&mut self, ds_uuid: &PbUuid, base_url_option: Option<&str>) -> EmptyRes {
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == *ds_uuid), "Dataset {} not found", ds_uuid.value);
+        if let Some(base_url) = base_url_option {
+            remote_media::parse_base_url(base_url)?;
+        }
+        let mut conn = self.get_conn()?;
+        let uuid = Uuid::parse_str(&ds_uuid.value)?;
+        conn.transaction(|conn| store_remote_media_root(conn, base_url_option, uuid.as_bytes()))
+    }
+
+    fn insert_user_alias(&mut self, alias: &UserAlias) -> EmptyRes {
+        ensure!(self.datasets()?.iter().any(|ds| ds.uuid == alias.ds_uuid), "Dataset {} not found", alias.ds_uuid.value);
+        ensure!(alias.ds_uuid != alias.other_ds_uuid, "Users of the same dataset cannot be aliased, merge them instead");
+        ensure!(self.user_option(&alias.ds_uuid, alias.user_id)?.is_some(),
+                "User {} not found in dataset {}", alias.user_id, alias.ds_uuid.value);
+        if self.user_aliases()?.iter().any(|a| aliases::same_alias(a, alias)) {
+            return Ok(());
+        }
+        let mut conn = self.get_conn()?;
+        insert_into(schema::user_alias::table).values(&utils::user_alias::serialize(alias)?).execute(&mut conn)?;
+        Ok(())
+    }
+
+    fn delete_user_alias(&mut self, alias: &UserAlias) -> EmptyRes {
+        let mut conn = self.get_conn()?;
+        let reversed = UserAlias {
+            ds_uuid: alias.other_ds_uuid.clone(),
+            user_id: alias.other_user_id,
+            other_ds_uuid: alias.ds_uuid.clone(),
+            other_user_id: alias.user_id,
+        };
+
+        use schema::*;
+        conn.transaction(|txn| {
+            for raw in [utils::user_alias::serialize(alias)?, utils::user_alias::serialize(&reversed)?] {
+                delete(user_alias::dsl::user_alias)
+                    .filter(user_alias::columns::ds_uuid.eq(&raw.ds_uuid))
+                    .filter(user_alias::columns::user_id.eq(raw.user_id))
+                    .filter(user_alias::columns::other_ds_uuid.eq(&raw.other_ds_uuid))
+                    .filter(user_alias::columns::other_user_id.eq(raw.other_user_id))
+                    .execute(txn)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn as_shiftable(&mut self) -> Result<&mut dyn ShiftableChatHistoryWriter> {
+        Ok(self)
+    }
+}
+
+impl ShiftableChatHistoryWriter for SqliteDao {
+    fn shift_dataset_time(&mut self, uuid: &PbUuid, hours_shift: i32) -> EmptyRes {
+        // Messages aren't cached so no need to invalidate cache
         let mut conn = self.get_conn()?;
 
         let uuid = Uuid::parse_str(&uuid.value).expect("Invalid UUID!");
@@ -1210,8 +2790,194 @@ impl ShiftableChatHistoryDao for SqliteDao {
 // Helpers
 //
 
+/// Apply a single retention rule, returning its report and absolute paths of files to be removed.
+/// Rule is evaluated but not applied for a dry run.
+fn apply_retention_rule(conn: &mut SqliteConnection,
+                        rule: RetentionRule,
+                        ds_root: &DatasetRoot,
+                        now: Timestamp,
+                        dry_run: bool) -> Result<(RetentionRuleReport, Vec<PathBuf>)> {
+    use sql_types::{BigInt, Binary, Integer, Nullable};
+
+    let uuid = Uuid::parse_str(&rule.ds_uuid.value).expect("Invalid UUID!");
+    let action = RetentionAction::resolve(rule.action)?;
+    let threshold = now.0 - rule.max_age_days as i64 * 24 * 60 * 60;
+    let tombstones_only = action == RetentionAction::DeleteTombstones;
+
+    // Parameters: ?1 - dataset UUID, ?2 - chat ID (nullable), ?3 - timestamp threshold, ?4 - tombstones only
+    const IN_SCOPE: &str = "((?2 IS NULL OR m.chat_id = ?2) AND m.time_sent < ?3 AND (?4 = 0 OR m.is_deleted = 1))";
+    const SCOPE: &str = concatcp!("SELECT m.internal_id FROM message m WHERE m.ds_uuid = ?1 AND ", IN_SCOPE);
+    macro_rules! query {
+        ($sql:expr) => {
+            sql_query($sql)
+                .bind::<Binary, _>(uuid.as_bytes().as_slice())
+                .bind::<Nullable<BigInt>, _>(rule.chat_id_option)
+                .bind::<BigInt, _>(threshold)
+                .bind::<Integer, _>(tombstones_only as i32)
+        };
+    }
+    let load_paths = |raw: Vec<PathsWrapper>| -> HashSet<String> {
+        raw.into_iter().flat_map(|p| [p.path, p.thumbnail_path]).flatten().collect()
+    };
+
+    let affected_messages_count = match action {
+        RetentionAction::DeleteMedia => query!(concatcp!(SCOPE, r"
+            AND (EXISTS (
+              SELECT 1 FROM message_content mc
+              WHERE mc.message_internal_id = m.internal_id
+                AND (mc.path IS NOT NULL OR mc.thumbnail_path IS NOT NULL)
+            ) OR EXISTS (
+              SELECT 1 FROM message_text_element rte
+              WHERE rte.message_internal_id = m.internal_id
+                AND rte.path IS NOT NULL
+            ))
+        ")).load::<InternalIdWrapper>(conn)?.len(),
+        _ => query!(SCOPE).load::<InternalIdWrapper>(conn)?.len(),
+    };
+
+    // Files might still be used by other messages of the dataset
+    let candidate_paths = load_paths(query!(concatcp!(r"
+        SELECT mc.path, mc.thumbnail_path FROM message_content mc
+        WHERE mc.message_internal_id IN (", SCOPE, ")
+        UNION ALL
+        SELECT rte.path, NULL AS thumbnail_path FROM message_text_element rte
+        WHERE rte.message_internal_id IN (", SCOPE, ")
+    ")).load::<PathsWrapper>(conn)?);
+    let used_paths = load_paths(query!(concatcp!(r"
+        SELECT mc.path, mc.thumbnail_path FROM message_content mc
+        INNER JOIN message m ON m.internal_id = mc.message_internal_id
+        WHERE m.ds_uuid = ?1 AND NOT ", IN_SCOPE, "
+        UNION ALL
+        SELECT rte.path, NULL AS thumbnail_path FROM message_text_element rte
+        INNER JOIN message m ON m.internal_id = rte.message_internal_id
+        WHERE m.ds_uuid = ?1 AND NOT ", IN_SCOPE, "
+    ")).load::<PathsWrapper>(conn)?);
+    let freed_paths = candidate_paths.difference(&used_paths)
+        .map(|p| ds_root.to_absolute(p))
+        .filter(|p| p.is_file())
+        .sorted()
+        .collect_vec();
+    let deleted_files_bytes = freed_paths.iter()
+        .map(|p| ok(fs::metadata(p)?.len() as i64))
+        .sum::<Result<i64>>()?;
+
+    if !dry_run {
+        match action {
+            RetentionAction::DeleteMedia => {
+                query!(concatcp!(r"
+                    UPDATE message_content SET path = NULL, thumbnail_path = NULL
+                    WHERE message_internal_id IN (", SCOPE, ")
+                ")).execute(conn)?;
+                query!(concatcp!(r"
+                    UPDATE message_text_element SET path = NULL
+                    WHERE message_internal_id IN (", SCOPE, ")
+                ")).execute(conn)?;
+            }
+            RetentionAction::DeleteTombstones | RetentionAction::DeleteMessages => {
+                query!(concatcp!(r"
+                    UPDATE chat SET msg_count = msg_count - (
+                      SELECT COUNT(*) FROM message m
+                      WHERE m.ds_uuid = chat.ds_uuid AND m.chat_id = chat.id AND ", IN_SCOPE, "
+                    )
+                    WHERE chat.ds_uuid = ?1
+                ")).execute(conn)?;
+                query!(concatcp!("DELETE FROM message_content WHERE message_internal_id IN (", SCOPE, ")")).execute(conn)?;
+                query!(concatcp!("DELETE FROM message_text_element WHERE message_internal_id IN (", SCOPE, ")")).execute(conn)?;
+                query!(concatcp!("DELETE FROM message WHERE internal_id IN (", SCOPE, ")")).execute(conn)?;
+            }
+        }
+    }
+
+    let report = RetentionRuleReport {
+        rule,
+        affected_messages_count: affected_messages_count as i32,
+        deleted_files_count: freed_paths.len() as i32,
+        deleted_files_bytes,
+    };
+    Ok((report, if dry_run { vec![] } else { freed_paths }))
+}
+
+/// Returns the number of affected messages, along with files no longer used by the dataset that should be removed.
+fn apply_blocked_users_policy(conn: &mut SqliteConnection,
+                              raw_uuid: &[u8],
+                              user_ids: &[i64],
+                              tombstone: bool,
+                              ds_root: &DatasetRoot) -> Result<(usize, Vec<PathBuf>)> {
+    use sql_types::{Binary, Integer};
+
+    // Parameters: ?1 - dataset UUID, ?2 - tombstone rather than delete.
+    // When tombstoning, service messages and messages that are tombstones already are out of scope.
+    // User IDs are numbers, so they're safe to be inlined.
+    let in_scope = format!(r"(m.from_id IN ({}) AND (?2 = 0 OR (m.type = 'regular' AND (
+          m.is_deleted = 0 OR m.searchable_string != ''
+          OR EXISTS (SELECT 1 FROM message_content c WHERE c.message_internal_id = m.internal_id)
+          OR EXISTS (SELECT 1 FROM message_text_element t WHERE t.message_internal_id = m.internal_id)
+        ))))", user_ids.iter().join(", "));
+    let scope = format!("SELECT m.internal_id FROM message m WHERE m.ds_uuid = ?1 AND {in_scope}");
+    macro_rules! query {
+        ($sql:expr) => {
+            sql_query($sql)
+                .bind::<Binary, _>(raw_uuid)
+                .bind::<Integer, _>(tombstone as i32)
+        };
+    }
+    let load_paths = |raw: Vec<PathsWrapper>| -> HashSet<String> {
+        raw.into_iter().flat_map(|p| [p.path, p.thumbnail_path]).flatten().collect()
+    };
+
+    let affected_count = query!(&scope).load::<InternalIdWrapper>(conn)?.len();
+    if affected_count == 0 {
+        return Ok((0, vec![]));
+    }
+
+    // Files might still be used by other messages of the dataset
+    let candidate_paths = load_paths(query!(format!(r"
+        SELECT mc.path, mc.thumbnail_path FROM message_content mc
+        WHERE mc.message_internal_id IN ({scope})
+        UNION ALL
+        SELECT rte.path, NULL AS thumbnail_path FROM message_text_element rte
+        WHERE rte.message_internal_id IN ({scope})
+    ")).load::<PathsWrapper>(conn)?);
+    let used_paths = load_paths(query!(format!(r"
+        SELECT mc.path, mc.thumbnail_path FROM message_content mc
+        INNER JOIN message m ON m.internal_id = mc.message_internal_id
+        WHERE m.ds_uuid = ?1 AND NOT {in_scope}
+        UNION ALL
+        SELECT rte.path, NULL AS thumbnail_path FROM message_text_element rte
+        INNER JOIN message m ON m.internal_id = rte.message_internal_id
+        WHERE m.ds_uuid = ?1 AND NOT {in_scope}
+    ")).load::<PathsWrapper>(conn)?);
+    let freed_paths = candidate_paths.difference(&used_paths)
+        .map(|p| ds_root.to_absolute(p))
+        .filter(|p| p.is_file())
+        .sorted()
+        .collect_vec();
+
+    if tombstone {
+        // Message stays in scope as long as it has content or text elements, so the order matters
+        query!(format!("UPDATE message SET is_deleted = 1, searchable_string = '' WHERE internal_id IN ({scope})")).execute(conn)?;
+        query!(format!("DELETE FROM message_content WHERE message_internal_id IN ({scope})")).execute(conn)?;
+        query!(format!("DELETE FROM message_text_element WHERE message_internal_id IN ({scope})")).execute(conn)?;
+    } else {
+        query!(format!(r"
+            UPDATE chat SET msg_count = msg_count - (
+              SELECT COUNT(*) FROM message m
+              WHERE m.ds_uuid = chat.ds_uuid AND m.chat_id = chat.id AND {in_scope}
+            )
+            WHERE chat.ds_uuid = ?1
+        ")).execute(conn)?;
+        query!(format!("DELETE FROM message_content WHERE message_internal_id IN ({scope})")).execute(conn)?;
+        query!(format!("DELETE FROM message_text_element WHERE message_internal_id IN ({scope})")).execute(conn)?;
+        query!(format!("DELETE FROM message WHERE internal_id IN ({scope})")).execute(conn)?;
+    }
+
+    Ok((affected_count, freed_paths))
+}
+
 const BACKUPS_DIR_NAME: &str = "_backups";
 const BACKUP_NAME_PREFIX: &str = "backup_";
+const TRASH_DIR_NAME: &str = "_trash";
+const TRASH_ITEM_PREFIX: &str = "item_";
 
 fn chat_root_rel_path(chat_id: i64) -> String {
     format!("chat_{chat_id}")
@@ -1245,24 +3011,136 @@ mod subpaths {
 /// If source file doesn't exist, return None.
 /// If destination file already exists, check if it's the same as source file.
 /// If source file doesn't have an extension, use MIME type to determine and add it.
+fn insert_import_batches(conn: &mut SqliteConnection, batches: &[ImportBatch], raw_uuid: &[u8]) -> EmptyRes {
+    let raw_batches: Vec<RawImportBatch> = batches.iter()
+        .map(|b| utils::import_batch::serialize(b, raw_uuid))
+        .try_collect()?;
+    insert_or_ignore_into(schema::import_batch::table).values(&raw_batches).execute(conn)?;
+    Ok(())
+}
+
+fn insert_user_identities(conn: &mut SqliteConnection, identities: &[UserIdentity], raw_uuid: &[u8]) -> EmptyRes {
+    let raw_identities = identities.iter()
+        .map(|i| utils::user_identity::serialize(i, raw_uuid))
+        .collect_vec();
+    insert_or_ignore_into(schema::user_identity::table).values(&raw_identities).execute(conn)?;
+    Ok(())
+}
+
+fn insert_chat_former_names(conn: &mut SqliteConnection, names: &[String], raw_uuid: &[u8], chat_id: i64) -> EmptyRes {
+    let raw_names = names.iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .unique()
+        .map(|name| RawChatFormerName { ds_uuid: raw_uuid.to_vec(), chat_id, name: name.to_owned() })
+        .collect_vec();
+    insert_or_ignore_into(schema::chat_former_name::table).values(&raw_names).execute(conn)?;
+    Ok(())
+}
+
+/// Absent if dataset settings were never configured.
+fn load_searchable_string_settings(conn: &mut SqliteConnection, raw_uuid: &[u8]) -> Result<Option<SearchableStringSettings>> {
+    use schema::*;
+    searchable_string_settings::table
+        .filter(searchable_string_settings::columns::ds_uuid.eq(raw_uuid))
+        .select(RawSearchableStringSettings::as_select())
+        .first(conn)
+        .optional()?
+        .map(utils::searchable_string_settings::deserialize)
+        .transpose()
+}
+
+fn store_searchable_string_settings(conn: &mut SqliteConnection,
+                                    settings: &SearchableStringSettings,
+                                    raw_uuid: &[u8]) -> EmptyRes {
+    diesel::replace_into(schema::searchable_string_settings::table)
+        .values(utils::searchable_string_settings::serialize(settings, raw_uuid)?)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Absent if dataset policy was never configured.
+fn load_blocked_users_policy(conn: &mut SqliteConnection, raw_uuid: &[u8]) -> Result<Option<BlockedUsersPolicy>> {
+    use schema::*;
+    let Some(raw_policy) = blocked_users_policy::table
+        .filter(blocked_users_policy::columns::ds_uuid.eq(raw_uuid))
+        .select(RawBlockedUsersPolicy::as_select())
+        .first(conn)
+        .optional()? else { return Ok(None) };
+    let raw_users = blocked_user::table
+        .filter(blocked_user::columns::ds_uuid.eq(raw_uuid))
+        .order_by(blocked_user::columns::user_id.asc())
+        .select(RawBlockedUser::as_select())
+        .load(conn)?;
+    utils::blocked_users_policy::deserialize(raw_policy, raw_users).map(Some)
+}
+
+/// Replaces previously stored policy, should be called within a transaction.
+fn store_blocked_users_policy(conn: &mut SqliteConnection,
+                              policy: &BlockedUsersPolicy,
+                              raw_uuid: &[u8]) -> EmptyRes {
+    use schema::*;
+    let (raw_policy, raw_users) = utils::blocked_users_policy::serialize(policy, raw_uuid)?;
+    diesel::replace_into(blocked_users_policy::table).values(&raw_policy).execute(conn)?;
+    delete(blocked_user::table).filter(blocked_user::columns::ds_uuid.eq(raw_uuid)).execute(conn)?;
+    insert_into(blocked_user::table).values(&raw_users).execute(conn)?;
+    Ok(())
+}
+
+/// Replaces (or clears, if `None`) previously stored base URL, should be called within a transaction.
+fn store_remote_media_root(conn: &mut SqliteConnection, base_url_option: Option<&str>, raw_uuid: &[u8]) -> EmptyRes {
+    use schema::*;
+    match base_url_option {
+        Some(base_url) => {
+            diesel::replace_into(remote_media_root::table)
+                .values(&RawRemoteMediaRoot { ds_uuid: raw_uuid.to_vec(), base_url: base_url.to_owned() })
+                .execute(conn)?;
+        }
+        None => {
+            delete(remote_media_root::table).filter(remote_media_root::columns::ds_uuid.eq(raw_uuid)).execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces previously stored template for the same source type, should be called within a transaction.
+fn store_merge_template(conn: &mut SqliteConnection, template: &MergeTemplate) -> EmptyRes {
+    use schema::*;
+    let (raw_template, raw_decisions) = utils::merge_template::serialize(template)?;
+    diesel::replace_into(merge_template::table).values(&raw_template).execute(conn)?;
+    delete(merge_template_decision::table)
+        .filter(merge_template_decision::columns::ds_uuid.eq(&raw_template.ds_uuid))
+        .filter(merge_template_decision::columns::source_type.eq(&raw_template.source_type))
+        .execute(conn)?;
+    insert_into(merge_template_decision::table).values(&raw_decisions).execute(conn)?;
+    Ok(())
+}
+
+/// Notes are inserted as-is except for their IDs, which are assigned anew.
+fn insert_notes(conn: &mut SqliteConnection, notes: &[Note]) -> EmptyRes {
+    let raw_notes: Vec<RawNote> = notes.iter().map(utils::note::serialize).try_collect()?;
+    insert_into(schema::note::table).values(&raw_notes).execute(conn)?;
+    Ok(())
+}
+
 fn copy_file(src_file: &Path,
              src_mime: Option<&str>,
              thumbnail_dst_main_path: Option<&str>,
              subpath_prefix: &str,
              subpath: &Subpath,
              dst_ds_root: &DatasetRoot) -> Result<Option<String>> {
-    let src_absolute_path = path_to_str(src_file)?;
     let src_meta = fs::metadata(src_file);
     if let Ok(src_meta) = src_meta {
-        ensure!(src_meta.is_file(), "Not a file: {src_absolute_path}");
+        ensure!(src_meta.is_file(), "Not a file: {}", src_file.display());
 
         let ext =
             if let Some(ext) = src_file.extension() {
-                Some(ext.to_str().unwrap())
+                // Extension is only reused in names we choose ourselves, so it doesn't need to be exact
+                Some(ext.to_string_lossy().into_owned())
             } else {
-                src_mime.and_then(mime2ext::mime2ext)
+                src_mime.and_then(mime2ext::mime2ext).map(|ext| ext.to_owned())
             };
-        let ext_suffix = ext.map(|ext| format!(".{ext}")).unwrap_or_default();
+        let ext_suffix = ext.as_ref().map(|ext| format!(".{ext}")).unwrap_or_default();
 
         let dst_rel_path: String =
             if let Some(main_path) = thumbnail_dst_main_path {
@@ -1274,10 +3152,14 @@ fn copy_file(src_file: &Path,
                     // Using first two characters of hash as a prefix for better file distribution, same what git does
                     let (prefix, name) = hash.split_at(2);
                     format!("{prefix}/{name}{ext_suffix}")
-                } else if let Some(ext) = ext {
-                    path_file_name(&src_file.with_extension(ext)).unwrap().to_owned()
                 } else {
-                    path_file_name(src_file).unwrap().to_owned()
+                    let file_name = os_str_to_storage_string(src_file.file_name().context("File has no name")?);
+                    let file_name = match ext {
+                        Some(ext) if src_file.extension().is_none() => format!("{file_name}.{ext}"),
+                        _ => file_name,
+                    };
+                    let dst_dir = dst_ds_root.to_absolute(&format!("{subpath_prefix}/{}", subpath.path_fragment));
+                    portable_file_name(&dst_dir, file_name, src_file)?
                 };
                 format!("{subpath_prefix}/{}/{inner_path}", subpath.path_fragment)
             };
@@ -1288,9 +3170,9 @@ fn copy_file(src_file: &Path,
             // Assume hash collisions don't exist
             ensure!(subpath.use_hashing || files_are_equal(src_file, &dst_file)?,
                     "File already exists: {}, and it doesn't match source {}",
-                    dst_file.display(), src_absolute_path)
+                    dst_file.display(), src_file.display())
         } else {
-            fs::copy(src_file, dst_file)?;
+            clone_file(src_file, &dst_file, is_sqlite_dataset_file(src_file))?;
         }
 
         Ok(Some(dst_rel_path))
@@ -1300,6 +3182,48 @@ fn copy_file(src_file: &Path,
     }
 }
 
+/// Makes sure a file name won't clash with another file in the same directory on case-insensitive filesystems
+/// (macOS, Windows) and on those normalizing Unicode (macOS), so the dataset stays usable when moved there.
+///
+/// Name that only differs from an existing one by case or normalization form is replaced by that existing name if
+/// the content is the same, or gets a suffix derived from its content otherwise.
+fn portable_file_name(dst_dir: &Path, file_name: String, src_file: &Path) -> Result<String> {
+    if !dst_dir.is_dir() { return Ok(file_name); }
+    let portable_key = |name: &str| name.nfc().collect::<String>().to_lowercase();
+    let key = portable_key(&file_name);
+    let mut similar_names = vec![];
+    for entry in fs::read_dir(dst_dir)? {
+        let Ok(entry_name) = entry?.file_name().into_string() else { continue };
+        if entry_name == file_name { return Ok(file_name); }
+        if portable_key(&entry_name) == key { similar_names.push(entry_name); }
+    }
+    for similar_name in similar_names.iter() {
+        if files_are_equal(src_file, &dst_dir.join(similar_name))? {
+            return Ok(similar_name.clone());
+        }
+    }
+    if similar_names.is_empty() { return Ok(file_name); }
+
+    let hash_prefix = format!("{:032X}", file_hash(src_file)?)[..8].to_owned();
+    let renamed = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{stem}_{hash_prefix}.{ext}"),
+        _ => format!("{file_name}_{hash_prefix}"),
+    };
+    log::info!("File {file_name} collides with {} in {}, renaming it to {renamed}",
+               similar_names.iter().join(", "), dst_dir.display());
+    Ok(renamed)
+}
+
+/// Files of datasets stored by [SqliteDao] are never modified in place (only added or removed),
+/// so it's safe to hard link them when copying datasets, e.g. into a new database or into trash.
+/// Other files (e.g. loader sources) are only ever shared via copy-on-write reflinks.
+fn is_sqlite_dataset_file(file: &Path) -> bool {
+    file.ancestors().skip(1).any(|dir| {
+        dir.file_name().and_then(|n| n.to_str()).is_some_and(|n| Uuid::parse_str(n).is_ok())
+            && dir.parent().is_some_and(|p| p.join(SqliteDao::FILENAME).is_file())
+    })
+}
+
 fn copy_chat_file(src_rel_path: &str,
                   src_mime: Option<&str>,
                   thumbnail_dst_main_path: Option<&str>,
@@ -1324,6 +3248,49 @@ fn defer_fk(conn: &mut SqliteConnection) -> EmptyRes {
     ok(())
 }
 
+/// Serialize users of a dataset, copying their profile pictures.
+fn serialize_users_and_copy_pictures(users: &[User],
+                                     myself_id: i64,
+                                     raw_uuid: &[u8],
+                                     src_ds_root: &DatasetRoot,
+                                     dst_ds_root: &DatasetRoot) -> Result<(Vec<RawUser>, Vec<RawProfilePicture>)> {
+    let raw_users_with_pictures: Vec<(RawUser, Vec<RawProfilePicture>)> = users.iter().map(|u| {
+        ensure!(u.id > 0, "IDs should be positive!");
+        let raw_user = utils::user::serialize(u, u.id == myself_id, raw_uuid);
+        let raw_pictures: Vec<RawProfilePicture> =
+            u.profile_pictures.iter()
+                .map(|pp| (pp, src_ds_root.to_absolute(&pp.path)))
+                .filter(|(_, path)| path.exists())
+                .enumerate()
+                .map(|(idx, (pp, path))| {
+                    utils::user::profile_picture::serialize_and_copy(
+                        u.id(), raw_uuid, &path,
+                        pp.frame_option.as_ref(), idx, dst_ds_root,
+                    )
+                })
+                .try_collect()?;
+        Ok((raw_user, raw_pictures))
+    }).try_collect()?;
+    let (raw_users, raw_pictures): (Vec<RawUser>, Vec<Vec<RawProfilePicture>>) =
+        raw_users_with_pictures.into_iter().unzip();
+    Ok((raw_users, raw_pictures.into_iter().flatten().collect_vec()))
+}
+
+/// Compressed backups, oldest first.
+fn list_backups(backup_path: &Path) -> Result<Vec<PathBuf>> {
+    if !backup_path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(list_all_files(backup_path, false)?
+        .into_iter()
+        .filter(|f| {
+            let name = path_file_name(f).unwrap();
+            name.starts_with(BACKUP_NAME_PREFIX) && name.ends_with(".zip")
+        })
+        .sorted()
+        .collect_vec())
+}
+
 fn vacuum(conn: &mut SqliteConnection) -> EmptyRes {
     sql_query("PRAGMA defer_foreign_keys = true").execute(conn)?;
     ok(())
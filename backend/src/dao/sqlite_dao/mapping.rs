@@ -45,6 +45,8 @@ pub mod schema {
             img_path -> Nullable<Text>,
             msg_count -> Integer,
             main_chat_id -> Nullable<BigInt>,
+            archived -> Integer,
+            hidden -> Integer,
         }
     }
 
@@ -71,8 +73,13 @@ pub mod schema {
             is_deleted -> Integer,
             from_id -> BigInt,
             forward_from_name -> Nullable<Text>,
+            forward_from_id -> Nullable<BigInt>,
             reply_to_message_id -> Nullable<BigInt>,
             searchable_string -> Text,
+            topic_id -> Nullable<BigInt>,
+            topic_title -> Nullable<Text>,
+            import_batch_uuid -> Nullable<Binary>,
+            ephemeral_duration_sec -> Nullable<Integer>,
         }
     }
 
@@ -84,20 +91,29 @@ pub mod schema {
             path -> Nullable<Text>,
             thumbnail_path -> Nullable<Text>,
             emoji -> Nullable<Text>,
+            sticker_pack_id -> Nullable<Text>,
+            sticker_pack_name -> Nullable<Text>,
             width -> Nullable<Integer>,
             height -> Nullable<Integer>,
             mime_type -> Nullable<Text>,
             title -> Nullable<Text>,
             performer -> Nullable<Text>,
             duration_sec -> Nullable<Integer>,
+            waveform -> Nullable<Binary>,
+            ocr_text -> Nullable<Text>,
             is_one_time -> Nullable<Integer>,
             lat -> Nullable<Text>,
             lon -> Nullable<Text>,
             address -> Nullable<Text>,
+            location_updates -> Nullable<Text>,
             poll_question -> Nullable<Text>,
+            poll_options -> Nullable<Text>,
+            poll_is_quiz -> Nullable<Integer>,
+            poll_total_voters -> Nullable<Integer>,
             first_name -> Nullable<Text>,
             last_name -> Nullable<Text>,
             phone_number -> Nullable<Text>,
+            emails -> Nullable<Text>,
             members -> Nullable<Text>,
             discard_reason -> Nullable<Text>,
             pinned_message_id -> Nullable<BigInt>,
@@ -115,6 +131,187 @@ pub mod schema {
             href -> Nullable<Text>,
             hidden -> Nullable<Integer>,
             language -> Nullable<Text>,
+            user_id -> Nullable<BigInt>,
+            handle -> Nullable<Text>,
+            emoji_id -> Nullable<Text>,
+            path -> Nullable<Text>,
+            quoted_source_id -> Nullable<BigInt>,
+            text_version -> Nullable<Integer>,
+            text_version_time -> Nullable<BigInt>,
+        }
+    }
+
+    diesel::table! {
+        chat_folder (id) {
+            id -> BigInt,
+            name -> Text,
+            parent_id -> Nullable<BigInt>,
+            order -> Integer,
+        }
+    }
+
+    diesel::table! {
+        chat_folder_chat (folder_id, ds_uuid, chat_id) {
+            folder_id -> BigInt,
+            ds_uuid -> Binary,
+            chat_id -> BigInt,
+            order -> Integer,
+        }
+    }
+
+    diesel::table! {
+        redaction_log (id) {
+            id -> BigInt,
+            ds_uuid -> Binary,
+            time -> BigInt,
+            chat_id -> Nullable<BigInt>,
+            deleted_messages_count -> Integer,
+            scrubbed_messages_count -> Integer,
+            scrubbed_strings_count -> Integer,
+        }
+    }
+
+    diesel::table! {
+        retention_rule (id) {
+            id -> BigInt,
+            ds_uuid -> Binary,
+            chat_id -> Nullable<BigInt>,
+            action -> Text,
+            max_age_days -> Integer,
+        }
+    }
+
+    diesel::table! {
+        audit_log (id) {
+            id -> BigInt,
+            time -> BigInt,
+            client -> Nullable<Text>,
+            operation -> Text,
+            ds_uuid -> Nullable<Binary>,
+            chat_id -> Nullable<BigInt>,
+            affected_chats_count -> Integer,
+            affected_messages_count -> Integer,
+            parameters -> Text,
+        }
+    }
+
+    diesel::table! {
+        trash_item (id) {
+            id -> BigInt,
+            time -> BigInt,
+            ds_uuid -> Binary,
+            chat_id -> Nullable<BigInt>,
+            name -> Text,
+            chats_count -> Integer,
+            messages_count -> Integer,
+        }
+    }
+
+    diesel::table! {
+        import_batch (ds_uuid, uuid) {
+            ds_uuid -> Binary,
+            uuid -> Binary,
+            source_path -> Text,
+            source_hash -> Text,
+            loader_name -> Text,
+            loader_version -> Text,
+            time -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        media_annotation (ds_uuid, path, source, tag) {
+            ds_uuid -> Binary,
+            path -> Text,
+            tag -> Text,
+            source -> Text,
+        }
+    }
+
+    diesel::table! {
+        user_identity (ds_uuid, source, source_key) {
+            ds_uuid -> Binary,
+            source -> Text,
+            source_key -> Text,
+            user_id -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        user_alias (ds_uuid, user_id, other_ds_uuid, other_user_id) {
+            ds_uuid -> Binary,
+            user_id -> BigInt,
+            other_ds_uuid -> Binary,
+            other_user_id -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        chat_former_name (ds_uuid, chat_id, name) {
+            ds_uuid -> Binary,
+            chat_id -> BigInt,
+            name -> Text,
+        }
+    }
+
+    diesel::table! {
+        note (id) {
+            id -> BigInt,
+            ds_uuid -> Binary,
+            chat_id -> BigInt,
+            message_source_id -> Nullable<BigInt>,
+            message_internal_id -> Nullable<BigInt>,
+            text -> Text,
+            time -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        searchable_string_settings (ds_uuid) {
+            ds_uuid -> Binary,
+            include_file_names -> Integer,
+            include_coordinates -> Integer,
+            phone_number_format -> Text,
+            transliterate -> Integer,
+        }
+    }
+
+    diesel::table! {
+        blocked_users_policy (ds_uuid) {
+            ds_uuid -> Binary,
+            action -> Text,
+        }
+    }
+
+    diesel::table! {
+        blocked_user (ds_uuid, user_id) {
+            ds_uuid -> Binary,
+            user_id -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        merge_template (ds_uuid, source_type) {
+            ds_uuid -> Binary,
+            source_type -> Text,
+            time -> BigInt,
+        }
+    }
+
+    diesel::table! {
+        merge_template_decision (ds_uuid, source_type, entity_type, entity_id) {
+            ds_uuid -> Binary,
+            source_type -> Text,
+            entity_type -> Text,
+            entity_id -> BigInt,
+            decision -> Text,
+        }
+    }
+
+    diesel::table! {
+        remote_media_root (ds_uuid) {
+            ds_uuid -> Binary,
+            base_url -> Text,
         }
     }
 
@@ -132,13 +329,46 @@ pub mod schema {
     diesel::joinable!(message_content -> message (message_internal_id));
     diesel::joinable!(message_text_element -> message (message_internal_id));
     diesel::joinable!(user -> dataset (ds_uuid));
+    diesel::joinable!(chat_folder_chat -> chat_folder (folder_id));
+    diesel::joinable!(redaction_log -> dataset (ds_uuid));
+    diesel::joinable!(retention_rule -> dataset (ds_uuid));
+    diesel::joinable!(import_batch -> dataset (ds_uuid));
+    diesel::joinable!(media_annotation -> dataset (ds_uuid));
+    diesel::joinable!(user_identity -> dataset (ds_uuid));
+    diesel::joinable!(user_alias -> dataset (ds_uuid));
+    diesel::joinable!(chat_former_name -> dataset (ds_uuid));
+    diesel::joinable!(note -> dataset (ds_uuid));
+    diesel::joinable!(searchable_string_settings -> dataset (ds_uuid));
+    diesel::joinable!(blocked_users_policy -> dataset (ds_uuid));
+    diesel::joinable!(blocked_user -> dataset (ds_uuid));
+    diesel::joinable!(merge_template -> dataset (ds_uuid));
+    diesel::joinable!(merge_template_decision -> dataset (ds_uuid));
+    diesel::joinable!(remote_media_root -> dataset (ds_uuid));
 
     diesel::allow_tables_to_appear_in_same_query!(
         chat,
+        chat_folder,
+        chat_folder_chat,
         chat_member,
         dataset,
         message,
         message_content,
+        redaction_log,
+        retention_rule,
+        audit_log,
+        trash_item,
+        import_batch,
+        media_annotation,
+        user_identity,
+        user_alias,
+        chat_former_name,
+        note,
+        searchable_string_settings,
+        blocked_users_policy,
+        blocked_user,
+        merge_template,
+        merge_template_decision,
+        remote_media_root,
         message_text_element,
         refinery_schema_history,
         user,
@@ -200,6 +430,10 @@ pub struct RawChat {
     pub img_path: Option<String>,
     pub msg_count: i32,
     pub main_chat_id: Option<i64>,
+    /// Boolean value
+    pub archived: i32,
+    /// Boolean value
+    pub hidden: i32,
 }
 
 // We cannot use #[diesel(belongs_to(...))] because Diesel doesn't support multi-column foreign keys.
@@ -216,6 +450,211 @@ pub struct RawChatMember {
     pub order: i32,
 }
 
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::chat_folder)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawChatFolder {
+    #[diesel(deserialize_as = i64)]
+    pub id: Option<i64>,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub order: i32,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::chat_folder_chat)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawChatFolderChat {
+    pub folder_id: i64,
+    pub ds_uuid: Vec<u8>,
+    pub chat_id: i64,
+    pub order: i32,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::redaction_log)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawRedactionLogEntry {
+    #[diesel(deserialize_as = i64)]
+    pub id: Option<i64>,
+    pub ds_uuid: Vec<u8>,
+    pub time: i64,
+    pub chat_id: Option<i64>,
+    pub deleted_messages_count: i32,
+    pub scrubbed_messages_count: i32,
+    pub scrubbed_strings_count: i32,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::retention_rule)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawRetentionRule {
+    #[diesel(deserialize_as = i64)]
+    pub id: Option<i64>,
+    pub ds_uuid: Vec<u8>,
+    pub chat_id: Option<i64>,
+    pub action: String,
+    pub max_age_days: i32,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::audit_log)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawAuditLogEntry {
+    #[diesel(deserialize_as = i64)]
+    pub id: Option<i64>,
+    pub time: i64,
+    pub client: Option<String>,
+    pub operation: String,
+    pub ds_uuid: Option<Vec<u8>>,
+    pub chat_id: Option<i64>,
+    pub affected_chats_count: i32,
+    pub affected_messages_count: i32,
+    pub parameters: String,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::trash_item)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawTrashItem {
+    #[diesel(deserialize_as = i64)]
+    pub id: Option<i64>,
+    pub time: i64,
+    pub ds_uuid: Vec<u8>,
+    pub chat_id: Option<i64>,
+    pub name: String,
+    pub chats_count: i32,
+    pub messages_count: i32,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::import_batch)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawImportBatch {
+    pub ds_uuid: Vec<u8>,
+    pub uuid: Vec<u8>,
+    pub source_path: String,
+    pub source_hash: String,
+    pub loader_name: String,
+    pub loader_version: String,
+    pub time: i64,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::media_annotation)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawMediaAnnotation {
+    pub ds_uuid: Vec<u8>,
+    pub path: String,
+    pub tag: String,
+    pub source: String,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::user_identity)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawUserIdentity {
+    pub ds_uuid: Vec<u8>,
+    pub source: String,
+    pub source_key: String,
+    pub user_id: i64,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::user_alias)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawUserAlias {
+    pub ds_uuid: Vec<u8>,
+    pub user_id: i64,
+    pub other_ds_uuid: Vec<u8>,
+    pub other_user_id: i64,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::chat_former_name)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawChatFormerName {
+    pub ds_uuid: Vec<u8>,
+    pub chat_id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::note)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(treat_none_as_null = true)]
+pub struct RawNote {
+    #[diesel(deserialize_as = i64)]
+    pub id: Option<i64>,
+    pub ds_uuid: Vec<u8>,
+    pub chat_id: i64,
+    pub message_source_id: Option<i64>,
+    pub message_internal_id: Option<i64>,
+    pub text: String,
+    pub time: i64,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = schema::searchable_string_settings)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawSearchableStringSettings {
+    pub ds_uuid: Vec<u8>,
+    pub include_file_names: i32,
+    pub include_coordinates: i32,
+    pub phone_number_format: String,
+    pub transliterate: i32,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::blocked_users_policy)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawBlockedUsersPolicy {
+    pub ds_uuid: Vec<u8>,
+    pub action: String,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::blocked_user)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawBlockedUser {
+    pub ds_uuid: Vec<u8>,
+    pub user_id: i64,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::merge_template)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawMergeTemplate {
+    pub ds_uuid: Vec<u8>,
+    pub source_type: String,
+    pub time: i64,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::merge_template_decision)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawMergeTemplateDecision {
+    pub ds_uuid: Vec<u8>,
+    pub source_type: String,
+    pub entity_type: String,
+    pub entity_id: i64,
+    pub decision: String,
+}
+
+#[derive(Debug, PartialEq, Selectable, Queryable, Insertable)]
+#[diesel(table_name = schema::remote_media_root)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RawRemoteMediaRoot {
+    pub ds_uuid: Vec<u8>,
+    pub base_url: String,
+}
+
 #[derive(Debug, PartialEq, QueryableByName)]
 #[diesel(table_name = schema::chat)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -251,8 +690,13 @@ pub struct RawMessage {
     pub is_deleted: i32,
     pub from_id: i64,
     pub forward_from_name: Option<String>,
+    pub forward_from_id: Option<i64>,
     pub reply_to_message_id: Option<i64>,
     pub searchable_string: String,
+    pub topic_id: Option<i64>,
+    pub topic_title: Option<String>,
+    pub import_batch_uuid: Option<Vec<u8>>,
+    pub ephemeral_duration_sec: Option<i32>,
 }
 
 #[derive(Debug, PartialEq, Default, Identifiable, Selectable, Queryable, Insertable, Associations)]
@@ -272,20 +716,32 @@ pub struct RawMessageContent {
     pub thumbnail_path: Option<String>,
     pub file_name: Option<String>,
     pub emoji: Option<String>,
+    pub sticker_pack_id: Option<String>,
+    pub sticker_pack_name: Option<String>,
     pub width: Option<i32>,
     pub height: Option<i32>,
     pub mime_type: Option<String>,
     pub title: Option<String>,
     pub performer: Option<String>,
     pub duration_sec: Option<i32>,
+    pub waveform: Option<Vec<u8>>,
+    pub ocr_text: Option<String>,
     pub is_one_time: Option<i32>,
     pub lat: Option<String>,
     pub lon: Option<String>,
     pub address: Option<String>,
+    /// JSON-serialized list of live location updates
+    pub location_updates: Option<String>,
     pub poll_question: Option<String>,
+    /// JSON-serialized list of poll options
+    pub poll_options: Option<String>,
+    /// Boolean value
+    pub poll_is_quiz: Option<i32>,
+    pub poll_total_voters: Option<i32>,
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub phone_number: Option<String>,
+    pub emails: Option<String>,
     pub members: Option<String>,
     pub discard_reason: Option<String>,
     pub pinned_message_id: Option<i64>,
@@ -310,6 +766,24 @@ pub struct UserIdWrapper {
     pub id: i64,
 }
 
+/// Needed specifically for selecting message internal IDs through sql_query.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(table_name = schema::message)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct InternalIdWrapper {
+    pub internal_id: i64,
+}
+
+/// Needed specifically for selecting per-day message counts through sql_query.
+#[derive(Debug, PartialEq, QueryableByName)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DateCountWrapper {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub date: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
+
 #[derive(Debug, PartialEq, Identifiable, Selectable, Queryable, Insertable, Associations)]
 #[diesel(belongs_to(RawMessage, foreign_key = message_internal_id))]
 #[diesel(table_name = schema::message_text_element)]
@@ -326,6 +800,20 @@ pub struct RawRichTextElement {
     /// Boolean value
     pub hidden: Option<i32>,
     pub language: Option<String>,
+    /// Only for mentions
+    pub user_id: Option<i64>,
+    /// Only for mentions
+    pub handle: Option<String>,
+    /// Only for custom emojis
+    pub emoji_id: Option<String>,
+    /// Only for custom emojis
+    pub path: Option<String>,
+    /// Only for blockquotes
+    pub quoted_source_id: Option<i64>,
+    /// Index of a former text version this element belongs to, `None` for the current text
+    pub text_version: Option<i32>,
+    /// Only for former text versions
+    pub text_version_time: Option<i64>,
 }
 
 pub struct FullRawMessage {
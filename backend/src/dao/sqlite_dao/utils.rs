@@ -24,7 +24,7 @@ pub fn deserialize_arr(v: Option<String>) -> Vec<String> {
     }
 }
 
-fn serialize_bool(b: bool) -> i32 {
+pub fn serialize_bool(b: bool) -> i32 {
     if b { 1 } else { 0 }
 }
 
@@ -69,12 +69,67 @@ impl_enum_serialization!(SourceType, {
     Signal      => "signal",
     TinderDb    => "tinder",
     BadooDb     => "badoo",
-    Mra         => "mra"
+    Mra         => "mra",
+    Twitter     => "twitter",
+    Reddit      => "reddit",
+    Vk          => "vk",
+    GoogleChat  => "google_chat",
+    Viber       => "viber"
 });
 
 impl_enum_serialization!(ChatType, {
     Personal     => "personal",
-    PrivateGroup => "private_group"
+    PrivateGroup => "private_group",
+    SelfChat     => "self"
+});
+
+// Matches `message_content.element_type` of regular messages
+impl_enum_serialization!(ContentType, {
+    Sticker       => "sticker",
+    Photo         => "photo",
+    VoiceMsg      => "voice_message",
+    Audio         => "audio",
+    VideoMsg      => "video_message",
+    Video         => "video",
+    File          => "file",
+    Location      => "location",
+    Poll          => "poll",
+    SharedContact => "shared_contact"
+});
+
+impl_enum_serialization!(RetentionAction, {
+    DeleteMedia      => "delete_media",
+    DeleteTombstones => "delete_tombstones",
+    DeleteMessages   => "delete_messages"
+});
+
+impl_enum_serialization!(BlockedUserAction, {
+    Import    => "import",
+    Tombstone => "tombstone",
+    Skip      => "skip"
+});
+
+impl_enum_serialization!(PhoneNumberFormat, {
+    AsIs       => "as_is",
+    DigitsOnly => "digits_only",
+    Both       => "both",
+    Omit       => "omit"
+});
+
+impl_enum_serialization!(UserMergeType, {
+    Retain             => "retain",
+    Add                => "add",
+    DontAdd            => "dont_add",
+    Replace            => "replace",
+    MatchOrDontReplace => "match_or_dont_replace"
+});
+
+impl_enum_serialization!(ChatMergeType, {
+    Retain    => "retain",
+    Add       => "add",
+    DontAdd   => "dont_add",
+    Merge     => "merge",
+    DontMerge => "dont_merge"
 });
 
 //
@@ -214,6 +269,8 @@ pub mod chat {
             img_path: chat.img_path_option.clone(),
             msg_count: chat.msg_count,
             main_chat_id: chat.main_chat_id,
+            archived: serialize_bool(chat.archived()),
+            hidden: serialize_bool(chat.hidden()),
         })
     }
 
@@ -243,6 +300,9 @@ pub mod chat {
                     .unwrap_or(Ok(vec![]))?,
                 msg_count: raw.chat.msg_count,
                 main_chat_id: raw.chat.main_chat_id,
+                // Unset flags are left absent, same as in freshly loaded chats
+                archived: deserialize_bool(raw.chat.archived).then_some(true),
+                hidden: deserialize_bool(raw.chat.hidden).then_some(true),
             },
             last_msg_option,
             members: vec![] /* Will be set right next */,
@@ -265,6 +325,393 @@ pub mod chat {
     }
 }
 
+pub mod chat_folder {
+    use super::*;
+
+    pub fn serialize(folder: &ChatFolder) -> (RawChatFolder, Vec<RawChatFolderChat>) {
+        let raw_chats = folder.chats.iter()
+            .enumerate()
+            .map(|(order, chat_ref)| RawChatFolderChat {
+                folder_id: folder.id,
+                ds_uuid: Vec::from(Uuid::parse_str(&chat_ref.ds_uuid.value).expect("Invalid UUID!").as_bytes()),
+                chat_id: chat_ref.chat_id,
+                order: order as i32,
+            })
+            .collect_vec();
+        (RawChatFolder {
+            id: Some(folder.id),
+            name: folder.name.clone(),
+            parent_id: folder.parent_id_option,
+            order: folder.order,
+        }, raw_chats)
+    }
+
+    /// Chats are expected to be already sorted.
+    pub fn deserialize(raw: RawChatFolder, raw_chats: Vec<RawChatFolderChat>) -> Result<ChatFolder> {
+        Ok(ChatFolder {
+            id: raw.id.context("Folder ID is not set")?,
+            name: raw.name,
+            parent_id_option: raw.parent_id,
+            order: raw.order,
+            chats: raw_chats.into_iter()
+                .map(|rc| ok(ChatRef {
+                    ds_uuid: PbUuid { value: Uuid::from_slice(&rc.ds_uuid)?.to_string() },
+                    chat_id: rc.chat_id,
+                }))
+                .try_collect()?,
+        })
+    }
+}
+
+pub mod redaction_log {
+    use super::*;
+
+    pub fn serialize(entry: &RedactionLogEntry, raw_uuid: &[u8]) -> RawRedactionLogEntry {
+        RawRedactionLogEntry {
+            id: None,
+            ds_uuid: raw_uuid.to_vec(),
+            time: entry.timestamp,
+            chat_id: entry.chat_id_option,
+            deleted_messages_count: entry.deleted_messages_count,
+            scrubbed_messages_count: entry.scrubbed_messages_count,
+            scrubbed_strings_count: entry.scrubbed_strings_count,
+        }
+    }
+
+    pub fn deserialize(raw: RawRedactionLogEntry) -> Result<RedactionLogEntry> {
+        Ok(RedactionLogEntry {
+            ds_uuid: PbUuid { value: Uuid::from_slice(&raw.ds_uuid)?.to_string() },
+            timestamp: raw.time,
+            chat_id_option: raw.chat_id,
+            deleted_messages_count: raw.deleted_messages_count,
+            scrubbed_messages_count: raw.scrubbed_messages_count,
+            scrubbed_strings_count: raw.scrubbed_strings_count,
+        })
+    }
+}
+
+pub mod audit_log {
+    use super::*;
+
+    pub fn serialize(entry: &AuditLogEntry) -> Result<RawAuditLogEntry> {
+        Ok(RawAuditLogEntry {
+            id: None,
+            time: entry.timestamp,
+            client: entry.client_option.clone(),
+            operation: entry.operation.clone(),
+            ds_uuid: entry.ds_uuid_option.as_ref()
+                .map(|uuid| ok(Uuid::parse_str(&uuid.value)?.as_bytes().to_vec()))
+                .transpose()?,
+            chat_id: entry.chat_id_option,
+            affected_chats_count: entry.affected_chats_count,
+            affected_messages_count: entry.affected_messages_count,
+            parameters: entry.parameters.clone(),
+        })
+    }
+
+    pub fn deserialize(raw: RawAuditLogEntry) -> Result<AuditLogEntry> {
+        Ok(AuditLogEntry {
+            timestamp: raw.time,
+            client_option: raw.client,
+            operation: raw.operation,
+            ds_uuid_option: raw.ds_uuid
+                .map(|uuid| ok(PbUuid { value: Uuid::from_slice(&uuid)?.to_string() }))
+                .transpose()?,
+            chat_id_option: raw.chat_id,
+            affected_chats_count: raw.affected_chats_count,
+            affected_messages_count: raw.affected_messages_count,
+            parameters: raw.parameters,
+        })
+    }
+}
+
+pub mod trash_item {
+    use super::*;
+
+    /// Item ID is discarded.
+    pub fn serialize(item: &TrashItem, raw_uuid: &[u8]) -> RawTrashItem {
+        RawTrashItem {
+            id: None,
+            time: item.timestamp,
+            ds_uuid: raw_uuid.to_vec(),
+            chat_id: item.chat_id_option,
+            name: item.name.clone(),
+            chats_count: item.chats_count,
+            messages_count: item.messages_count,
+        }
+    }
+
+    pub fn deserialize(raw: RawTrashItem) -> Result<TrashItem> {
+        Ok(TrashItem {
+            id: raw.id.context("Trash item ID is not set")?,
+            timestamp: raw.time,
+            ds_uuid: PbUuid { value: Uuid::from_slice(&raw.ds_uuid)?.to_string() },
+            chat_id_option: raw.chat_id,
+            name: raw.name,
+            chats_count: raw.chats_count,
+            messages_count: raw.messages_count,
+        })
+    }
+}
+
+pub mod import_batch {
+    use super::*;
+
+    pub fn serialize(batch: &ImportBatch, raw_uuid: &[u8]) -> Result<RawImportBatch> {
+        Ok(RawImportBatch {
+            ds_uuid: raw_uuid.to_vec(),
+            uuid: serialize_uuid(&batch.uuid)?,
+            source_path: batch.source_path.clone(),
+            source_hash: batch.source_hash.clone(),
+            loader_name: batch.loader_name.clone(),
+            loader_version: batch.loader_version.clone(),
+            time: batch.import_timestamp,
+        })
+    }
+
+    pub fn deserialize(raw: RawImportBatch) -> Result<ImportBatch> {
+        Ok(ImportBatch {
+            uuid: deserialize_uuid(&raw.uuid)?,
+            source_path: raw.source_path,
+            source_hash: raw.source_hash,
+            loader_name: raw.loader_name,
+            loader_version: raw.loader_version,
+            import_timestamp: raw.time,
+        })
+    }
+
+    pub fn serialize_uuid(uuid: &PbUuid) -> Result<Vec<u8>> {
+        Ok(Uuid::parse_str(&uuid.value)?.as_bytes().to_vec())
+    }
+
+    pub fn deserialize_uuid(raw_uuid: &[u8]) -> Result<PbUuid> {
+        Ok(PbUuid { value: Uuid::from_slice(raw_uuid)?.to_string() })
+    }
+}
+
+pub mod media_annotation {
+    use super::*;
+
+    pub fn serialize(annotation: &MediaAnnotation, raw_uuid: &[u8]) -> RawMediaAnnotation {
+        RawMediaAnnotation {
+            ds_uuid: raw_uuid.to_vec(),
+            path: annotation.path.clone(),
+            tag: annotation.tag.clone(),
+            source: annotation.source.clone(),
+        }
+    }
+
+    pub fn deserialize(raw: RawMediaAnnotation) -> MediaAnnotation {
+        MediaAnnotation {
+            path: raw.path,
+            tag: raw.tag,
+            source: raw.source,
+        }
+    }
+}
+
+pub mod user_identity {
+    use super::*;
+
+    pub fn serialize(identity: &UserIdentity, raw_uuid: &[u8]) -> RawUserIdentity {
+        RawUserIdentity {
+            ds_uuid: raw_uuid.to_vec(),
+            source: identity.source.clone(),
+            source_key: identity.source_key.clone(),
+            user_id: identity.user_id,
+        }
+    }
+
+    pub fn deserialize(raw: RawUserIdentity) -> UserIdentity {
+        UserIdentity {
+            source: raw.source,
+            source_key: raw.source_key,
+            user_id: raw.user_id,
+        }
+    }
+}
+
+pub mod user_alias {
+    use super::*;
+
+    pub fn serialize(alias: &UserAlias) -> Result<RawUserAlias> {
+        Ok(RawUserAlias {
+            ds_uuid: import_batch::serialize_uuid(&alias.ds_uuid)?,
+            user_id: alias.user_id,
+            other_ds_uuid: import_batch::serialize_uuid(&alias.other_ds_uuid)?,
+            other_user_id: alias.other_user_id,
+        })
+    }
+
+    pub fn deserialize(raw: RawUserAlias) -> Result<UserAlias> {
+        Ok(UserAlias {
+            ds_uuid: import_batch::deserialize_uuid(&raw.ds_uuid)?,
+            user_id: raw.user_id,
+            other_ds_uuid: import_batch::deserialize_uuid(&raw.other_ds_uuid)?,
+            other_user_id: raw.other_user_id,
+        })
+    }
+}
+
+pub mod retention_rule {
+    use super::*;
+
+    /// Rule ID is discarded.
+    pub fn serialize(rule: &RetentionRule, raw_uuid: &[u8]) -> Result<RawRetentionRule> {
+        Ok(RawRetentionRule {
+            id: None,
+            ds_uuid: raw_uuid.to_vec(),
+            chat_id: rule.chat_id_option,
+            action: RetentionAction::serialize(rule.action)?,
+            max_age_days: rule.max_age_days,
+        })
+    }
+
+    pub fn deserialize(raw: RawRetentionRule) -> Result<RetentionRule> {
+        Ok(RetentionRule {
+            id: raw.id.context("Retention rule ID is not set")?,
+            ds_uuid: PbUuid { value: Uuid::from_slice(&raw.ds_uuid)?.to_string() },
+            chat_id_option: raw.chat_id,
+            action: RetentionAction::deserialize(&raw.action)?,
+            max_age_days: raw.max_age_days,
+        })
+    }
+}
+
+pub mod searchable_string_settings {
+    use super::*;
+
+    /// Absent fields are replaced by their defaults.
+    pub fn serialize(settings: &SearchableStringSettings, raw_uuid: &[u8]) -> Result<RawSearchableStringSettings> {
+        Ok(RawSearchableStringSettings {
+            ds_uuid: raw_uuid.to_vec(),
+            include_file_names: serialize_bool(settings.include_file_names()),
+            include_coordinates: serialize_bool(settings.include_coordinates()),
+            phone_number_format: PhoneNumberFormat::serialize(settings.phone_number_format() as i32)?,
+            transliterate: serialize_bool(settings.transliterate()),
+        })
+    }
+
+    pub fn deserialize(raw: RawSearchableStringSettings) -> Result<SearchableStringSettings> {
+        Ok(SearchableStringSettings {
+            include_file_names: Some(deserialize_bool(raw.include_file_names)),
+            include_coordinates: Some(deserialize_bool(raw.include_coordinates)),
+            phone_number_format: Some(PhoneNumberFormat::deserialize(&raw.phone_number_format)?),
+            transliterate: Some(deserialize_bool(raw.transliterate)),
+        })
+    }
+}
+
+pub mod blocked_users_policy {
+    use super::*;
+
+    pub fn serialize(policy: &BlockedUsersPolicy, raw_uuid: &[u8]) -> Result<(RawBlockedUsersPolicy, Vec<RawBlockedUser>)> {
+        let raw_policy = RawBlockedUsersPolicy {
+            ds_uuid: raw_uuid.to_vec(),
+            action: BlockedUserAction::serialize(policy.action() as i32)?,
+        };
+        let raw_users = policy.user_ids.iter()
+            .unique()
+            .map(|&user_id| RawBlockedUser { ds_uuid: raw_uuid.to_vec(), user_id })
+            .collect_vec();
+        Ok((raw_policy, raw_users))
+    }
+
+    pub fn deserialize(raw_policy: RawBlockedUsersPolicy, raw_users: Vec<RawBlockedUser>) -> Result<BlockedUsersPolicy> {
+        Ok(BlockedUsersPolicy {
+            user_ids: raw_users.into_iter().map(|raw| raw.user_id).collect_vec(),
+            action: Some(BlockedUserAction::deserialize(&raw_policy.action)?),
+        })
+    }
+}
+
+pub mod merge_template {
+    use super::*;
+
+    const USER: &str = "user";
+    const CHAT: &str = "chat";
+
+    pub fn serialize(template: &MergeTemplate) -> Result<(RawMergeTemplate, Vec<RawMergeTemplateDecision>)> {
+        let raw_uuid = import_batch::serialize_uuid(&template.ds_uuid)?;
+        let source_type = SourceType::serialize(template.source_type)?;
+        let raw_template = RawMergeTemplate {
+            ds_uuid: raw_uuid.clone(),
+            source_type: source_type.clone(),
+            time: template.timestamp,
+        };
+        let decision = |entity_type: &str, entity_id: i64, decision: String| RawMergeTemplateDecision {
+            ds_uuid: raw_uuid.clone(),
+            source_type: source_type.clone(),
+            entity_type: entity_type.to_owned(),
+            entity_id,
+            decision,
+        };
+        let mut raw_decisions = Vec::with_capacity(template.user_merges.len() + template.chat_merges.len());
+        for um in template.user_merges.iter() {
+            raw_decisions.push(decision(USER, um.user_id, UserMergeType::serialize(um.tpe)?));
+        }
+        for cm in template.chat_merges.iter() {
+            raw_decisions.push(decision(CHAT, cm.chat_id, ChatMergeType::serialize(cm.tpe)?));
+        }
+        Ok((raw_template, raw_decisions))
+    }
+
+    pub fn deserialize(raw_template: RawMergeTemplate, raw_decisions: Vec<RawMergeTemplateDecision>) -> Result<MergeTemplate> {
+        let mut user_merges = vec![];
+        let mut chat_merges = vec![];
+        for raw in raw_decisions {
+            match raw.entity_type.as_str() {
+                USER => user_merges.push(UserMerge {
+                    tpe: UserMergeType::deserialize(&raw.decision)?,
+                    user_id: raw.entity_id,
+                }),
+                CHAT => chat_merges.push(ChatMerge {
+                    tpe: ChatMergeType::deserialize(&raw.decision)?,
+                    chat_id: raw.entity_id,
+                    message_merges: vec![],
+                }),
+                x => bail!("Unrecognized merge template entity type {x}"),
+            }
+        }
+        Ok(MergeTemplate {
+            ds_uuid: import_batch::deserialize_uuid(&raw_template.ds_uuid)?,
+            source_type: SourceType::deserialize(&raw_template.source_type)?,
+            timestamp: raw_template.time,
+            user_merges,
+            chat_merges,
+        })
+    }
+}
+
+pub mod note {
+    use super::*;
+
+    /// Note ID is discarded.
+    pub fn serialize(note: &Note) -> Result<RawNote> {
+        Ok(RawNote {
+            id: None,
+            ds_uuid: import_batch::serialize_uuid(&note.ds_uuid)?,
+            chat_id: note.chat_id,
+            message_source_id: note.message_source_id_option,
+            message_internal_id: note.message_internal_id_option,
+            text: note.text.clone(),
+            time: note.timestamp,
+        })
+    }
+
+    pub fn deserialize(raw: RawNote) -> Result<Note> {
+        Ok(Note {
+            id: raw.id.context("Note ID is not set")?,
+            ds_uuid: import_batch::deserialize_uuid(&raw.ds_uuid)?,
+            chat_id: raw.chat_id,
+            message_source_id_option: raw.message_source_id,
+            message_internal_id_option: raw.message_internal_id,
+            text: raw.text,
+            timestamp: raw.time,
+        })
+    }
+}
+
 pub mod message {
     use super::*;
 
@@ -316,7 +763,8 @@ pub mod message {
                                     raw_uuid: &[u8],
                                     src_ds_root: &DatasetRoot,
                                     dst_ds_root: &DatasetRoot) -> Result<FullRawMessage> {
-        let (tpe, subtype, mc, time_edited, is_deleted, forward_from_name, reply_to_message_id) =
+        let (tpe, subtype, mc, time_edited, is_deleted, forward_from_name, forward_from_id, reply_to_message_id,
+             ephemeral_duration_sec) =
             match m.typed.as_ref().unwrap() {
                 crate::message::Typed::Regular(mr) => {
                     let content: Result<Vec<_>> = mr.contents.iter()
@@ -330,11 +778,13 @@ pub mod message {
                      mr.edit_timestamp_option,
                      serialize_bool(mr.is_deleted),
                      mr.forward_from_name_option.clone(),
-                     mr.reply_to_message_id_option)
+                     mr.forward_from_id_option,
+                     mr.reply_to_message_id_option,
+                     mr.ephemeral_duration_sec_option)
                 }
                 message_service_pat!(ms) => {
                     let (subtype, mc) = serialize_service_and_copy_files(ms, chat_id, src_ds_root, dst_ds_root)?;
-                    ("service", Some(subtype), mc.into_iter().collect_vec(), None, serialize_bool(false), None, None)
+                    ("service", Some(subtype), mc.into_iter().collect_vec(), None, serialize_bool(false), None, None, None, None)
                 }
                 message_service_pat_unreachable!() => { unreachable!() }
             };
@@ -351,11 +801,16 @@ pub mod message {
                 is_deleted,
                 from_id: m.from_id,
                 forward_from_name,
+                forward_from_id,
                 reply_to_message_id,
                 searchable_string: m.searchable_string.clone(),
+                topic_id: m.topic_option.as_ref().map(|t| t.id),
+                topic_title: m.topic_option.as_ref().map(|t| t.title.clone()),
+                import_batch_uuid: None,
+                ephemeral_duration_sec,
             },
             mc,
-            rtes: m.text.iter().map(serialize_rte).try_collect()?,
+            rtes: serialize_texts_and_copy_files(m, chat_id, src_ds_root, dst_ds_root)?,
         })
     }
 
@@ -384,6 +839,8 @@ pub mod message {
                     mime_type: v.mime_type_option.clone(),
                     thumbnail_path,
                     emoji: v.emoji_option.clone(),
+                    sticker_pack_id: v.pack_id_option.clone(),
+                    sticker_pack_name: v.pack_name_option.clone(),
                     ..Default::default()
                 }
             }
@@ -396,6 +853,7 @@ pub mod message {
                     file_name: v.file_name_option.clone(),
                     mime_type: Some(v.mime_type.clone()),
                     duration_sec: v.duration_sec_option,
+                    waveform: v.waveform_option.clone(),
                     ..Default::default()
                 }
             }
@@ -410,6 +868,7 @@ pub mod message {
                     performer: v.performer_option.clone(),
                     mime_type: Some(v.mime_type.clone()),
                     duration_sec: v.duration_sec_option,
+                    waveform: v.waveform_option.clone(),
                     thumbnail_path,
                     ..Default::default()
                 }
@@ -467,11 +926,15 @@ pub mod message {
                 lat: Some(v.lat_str.clone()),
                 lon: Some(v.lon_str.clone()),
                 duration_sec: v.duration_sec_option,
+                location_updates: if v.updates.is_empty() { None } else { Some(serde_json::to_string(&v.updates)?) },
                 ..Default::default()
             },
             Poll(v) => RawMessageContent {
                 element_type: "poll".to_owned(),
                 poll_question: Some(v.question.clone()),
+                poll_options: if v.options.is_empty() { None } else { Some(serde_json::to_string(&v.options)?) },
+                poll_is_quiz: v.is_quiz.map(serialize_bool),
+                poll_total_voters: v.total_voters_option,
                 ..Default::default()
             },
             SharedContact(v) => {
@@ -482,6 +945,7 @@ pub mod message {
                     first_name: v.first_name_option.clone(),
                     last_name: v.last_name_option.clone(),
                     phone_number: v.phone_number_option.clone(),
+                    emails: serialize_arr(&v.emails),
                     ..Default::default()
                 }
             }
@@ -503,6 +967,9 @@ pub mod message {
             height: Some(photo.height),
             mime_type: photo.mime_type_option.clone(),
             is_one_time: Some(serialize_bool(photo.is_one_time)),
+            lat: photo.lat_str_option.clone(),
+            lon: photo.lon_str_option.clone(),
+            ocr_text: photo.ocr_text_option.clone(),
             ..Default::default()
         })
     }
@@ -582,10 +1049,36 @@ pub mod message {
     }
 
 
+    /// Current text elements go first, followed by elements of former versions marked with their version index.
+    fn serialize_texts_and_copy_files(m: &Message,
+                                      chat_id: i64,
+                                      src_ds_root: &DatasetRoot,
+                                      dst_ds_root: &DatasetRoot) -> Result<Vec<RawRichTextElement>> {
+        let mut result: Vec<RawRichTextElement> = m.text.iter()
+            .map(|rte| serialize_rte_and_copy_files(rte, chat_id, src_ds_root, dst_ds_root))
+            .try_collect()?;
+        for (idx, version) in m.text_history.iter().enumerate() {
+            for rte in version.text.iter() {
+                result.push(RawRichTextElement {
+                    text_version: Some(idx as i32),
+                    text_version_time: Some(version.timestamp),
+                    ..serialize_rte_and_copy_files(rte, chat_id, src_ds_root, dst_ds_root)?
+                });
+            }
+        }
+        Ok(result)
+    }
+
     /// Ignores message internal ID.
-    fn serialize_rte(rte: &RichTextElement) -> Result<RawRichTextElement> {
+    fn serialize_rte_and_copy_files(rte: &RichTextElement,
+                                    chat_id: i64,
+                                    src_ds_root: &DatasetRoot,
+                                    dst_ds_root: &DatasetRoot) -> Result<RawRichTextElement> {
         use rich_text_element::Val::*;
         let (mut language, mut hidden, mut href) = (None, None, None);
+        let (mut user_id, mut handle) = (None, None);
+        let (mut emoji_id, mut path) = (None, None);
+        let mut quoted_source_id = None;
         let (text, tpe): (Option<String>, &str) = match rte.val.as_ref().unwrap() {
             Plain(v) =>
                 (Some(v.text.clone()), "plain"),
@@ -608,10 +1101,26 @@ pub mod message {
                 language = v.language_option.clone();
                 (Some(v.text.clone()), "prefmt_block")
             }
-            Blockquote(v) =>
-                (Some(v.text.clone()), "blockquote"),
+            Blockquote(v) => {
+                quoted_source_id = v.quoted_source_id_option;
+                (Some(v.text.clone()), "blockquote")
+            }
             Spoiler(v) =>
                 (Some(v.text.clone()), "spoiler"),
+            Mention(v) => {
+                user_id = v.user_id_option;
+                handle = v.handle_option.clone();
+                (Some(v.text.clone()), "mention")
+            }
+            Hashtag(v) =>
+                (Some(v.text.clone()), if v.is_cashtag { "cashtag" } else { "hashtag" }),
+            CustomEmoji(v) => {
+                emoji_id = v.emoji_id_option.clone();
+                path = v.path_option.as_ref().map(|p|
+                    sqlite_dao::copy_chat_file(p, None, None, &subpaths::STICKERS, chat_id, src_ds_root, dst_ds_root)
+                ).transpose()?.flatten();
+                (Some(v.text.clone()), "custom_emoji")
+            }
         };
         Ok(RawRichTextElement {
             id: None,
@@ -621,11 +1130,28 @@ pub mod message {
             href,
             hidden,
             language,
+            user_id,
+            handle,
+            emoji_id,
+            path,
+            quoted_source_id,
+            text_version: None,
+            text_version_time: None,
         })
     }
 
     pub fn deserialize(raw: FullRawMessage) -> Result<Message> {
-        let text = raw.rtes.into_iter().map(deserialize_rte).try_collect()?;
+        let (current_rtes, former_rtes): (Vec<_>, Vec<_>) = raw.rtes.into_iter().partition(|rte| rte.text_version.is_none());
+        let text = current_rtes.into_iter().map(deserialize_rte).try_collect()?;
+        let text_history: Vec<MessageTextVersion> = former_rtes.into_iter()
+            .into_group_map_by(|rte| rte.text_version.unwrap())
+            .into_iter()
+            .sorted_by_key(|(version, _)| *version)
+            .map(|(_, rtes)| ok(MessageTextVersion {
+                timestamp: rtes[0].text_version_time.context("Former text version time is not set")?,
+                text: rtes.into_iter().map(deserialize_rte).try_collect()?,
+            }))
+            .try_collect()?;
         let typed = match raw.m.tpe.as_str() {
             "regular" => {
                 let contents: Result<Vec<_>> = raw.mc.into_iter()
@@ -638,6 +1164,8 @@ pub mod message {
                     edit_timestamp_option: raw.m.time_edited,
                     is_deleted: deserialize_bool(raw.m.is_deleted),
                     forward_from_name_option: raw.m.forward_from_name,
+                    forward_from_id_option: raw.m.forward_from_id,
+                    ephemeral_duration_sec_option: raw.m.ephemeral_duration_sec,
                     reply_to_message_id_option: raw.m.reply_to_message_id,
                     contents,
                 }
@@ -650,14 +1178,20 @@ pub mod message {
             },
             tpe => bail!("Unknown message type {}!", tpe)
         };
-        Ok(Message::new(
+        let mut msg = Message::new(
             raw.m.internal_id.expect("Message has no internal ID!"),
             raw.m.source_id,
             raw.m.time_sent,
             UserId(raw.m.from_id),
             text,
             typed,
-        ))
+        );
+        msg.text_history = text_history;
+        msg.topic_option = match (raw.m.topic_id, raw.m.topic_title) {
+            (Some(id), title) => Some(MessageTopic { id, title: title.unwrap_or_default() }),
+            (None, _) => None,
+        };
+        Ok(msg)
     }
 
     fn deserialize_content(raw: RawMessageContent) -> Result<content::SealedValueOptional> {
@@ -676,6 +1210,8 @@ pub mod message {
                 mime_type_option: raw.mime_type,
                 thumbnail_path_option: raw.thumbnail_path,
                 emoji_option: raw.emoji,
+                pack_id_option: raw.sticker_pack_id,
+                pack_name_option: raw.sticker_pack_name,
             }),
             "photo" => Photo(deserialize_photo(raw)?),
             "voice_message" => VoiceMsg(ContentVoiceMsg {
@@ -683,6 +1219,7 @@ pub mod message {
                 file_name_option: raw.file_name,
                 mime_type: get_or_bail!(raw.mime_type),
                 duration_sec_option: raw.duration_sec,
+                waveform_option: raw.waveform,
             }),
             "audio" => Audio(ContentAudio {
                 path_option: raw.path,
@@ -691,6 +1228,7 @@ pub mod message {
                 performer_option: raw.performer,
                 mime_type: get_or_bail!(raw.mime_type),
                 duration_sec_option: raw.duration_sec,
+                waveform_option: raw.waveform,
                 thumbnail_path_option: raw.thumbnail_path,
             }),
             "video_message" => VideoMsg(ContentVideoMsg {
@@ -727,15 +1265,26 @@ pub mod message {
                 lat_str: get_or_bail!(raw.lat),
                 lon_str: get_or_bail!(raw.lon),
                 duration_sec_option: raw.duration_sec,
+                updates: match raw.location_updates {
+                    Some(updates) => serde_json::from_str(&updates)?,
+                    None => vec![],
+                },
             }),
             "poll" => Poll(ContentPoll {
                 question: get_or_bail!(raw.poll_question),
+                options: match raw.poll_options {
+                    Some(options) => serde_json::from_str(&options)?,
+                    None => vec![],
+                },
+                is_quiz: raw.poll_is_quiz.map(deserialize_bool),
+                total_voters_option: raw.poll_total_voters,
             }),
             "shared_contact" => SharedContact(ContentSharedContact {
                 first_name_option: raw.first_name,
                 last_name_option: raw.last_name,
                 phone_number_option: raw.phone_number,
                 vcard_path_option: raw.path,
+                emails: deserialize_arr(raw.emails),
             }),
             tpe => bail!("Unknown content type {}!", tpe)
         })
@@ -752,6 +1301,9 @@ pub mod message {
             height: get_or_bail!(raw.height),
             mime_type_option: raw.mime_type,
             is_one_time: deserialize_bool(get_or_bail!(raw.is_one_time)),
+            lat_str_option: raw.lat,
+            lon_str_option: raw.lon,
+            ocr_text_option: raw.ocr_text,
         })
     }
 
@@ -861,8 +1413,12 @@ pub mod message {
                                           raw.hidden.map(deserialize_bool).unwrap_or_default()),
             "prefmt_inline" => RichText::make_prefmt_inline(text_or_bail!()),
             "prefmt_block" => RichText::make_prefmt_block(text_or_bail!(), raw.language),
-            "blockquote" => RichText::make_blockquote(text_or_bail!()),
+            "blockquote" => RichText::make_quote(text_or_bail!(), raw.quoted_source_id),
             "spoiler" => RichText::make_spoiler(text_or_bail!()),
+            "mention" => RichText::make_mention(text_or_bail!(), raw.user_id, raw.handle),
+            "hashtag" => RichText::make_hashtag(text_or_bail!(), false),
+            "cashtag" => RichText::make_hashtag(text_or_bail!(), true),
+            "custom_emoji" => RichText::make_custom_emoji(text_or_bail!(), raw.emoji_id, raw.path),
             x => bail!("Unknown rich text element {x}!")
         })
     }
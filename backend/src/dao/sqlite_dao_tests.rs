@@ -2,11 +2,19 @@
 
 use std::cmp;
 use std::fs::File;
+use std::io::Read;
 
 use pretty_assertions::{assert_eq, assert_ne};
 use regex::Regex;
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::dao::duplicates::find_near_duplicates;
+use crate::dao::fingerprint::dataset_fingerprint;
+use crate::dao::paging::*;
+use crate::dao::aliases::AliasGroup;
+use crate::dao::search::search_all;
+use crate::dao::permalink::*;
+use crate::dao::remote_media::{read_media, RemoteMediaCache};
 use crate::entity_utils::*;
 use crate::loader::Loader;
 use crate::protobuf::history::message::*;
@@ -63,6 +71,95 @@ fn relevant_files_are_copied() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn only_database_files_are_hard_linked() -> EmptyRes {
+    let daos = init();
+
+    // Loader sources might be modified in place, so they are never hard linked
+    let src_files = dataset_files(daos.src_dao.as_ref(), &daos.ds_uuid);
+    assert!(src_files.iter().filter(|f| f.exists()).all(|f| !is_sqlite_dataset_file(f)));
+
+    let dst_files = dataset_files(&daos.dst_dao, &daos.ds_uuid);
+    assert!(dst_files.iter().any(|f| f.exists()));
+    assert!(dst_files.iter().filter(|f| f.exists()).all(|f| is_sqlite_dataset_file(f)));
+    Ok(())
+}
+
+#[test]
+fn exotic_file_names_are_copied() -> EmptyRes {
+    let file_names = ["Фото 😀 (1).jpg", "50%.pdf", "100%41 テスト"];
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=file_names.len()).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            let file_name = file_names[msg.source_id_option.unwrap() as usize - 1];
+            create_named_file(&ds_root.to_absolute(file_name), file_name.as_bytes());
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![content!(File {
+                path_option: Some(file_name.to_owned()),
+                file_name_option: Some(file_name.to_owned()),
+                mime_type_option: None,
+                thumbnail_path_option: None,
+            })];
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+
+    let src_files = dataset_files(daos.src_dao.as_ref(), &daos.ds_uuid);
+    let dst_files = dataset_files(&daos.dst_dao, &daos.ds_uuid);
+    assert_eq!(src_files.len(), file_names.len());
+    assert_files(&src_files, &dst_files);
+    for (dst_file, file_name) in dst_files.iter().zip(file_names) {
+        assert!(dst_file.file_name().unwrap().to_str().unwrap().starts_with(file_name.split('.').next().unwrap()));
+    }
+    Ok(())
+}
+
+#[test]
+fn colliding_file_names_are_renamed() -> EmptyRes {
+    // Sources lie in different directories, so they could coexist on any filesystem
+    let nfc_name = "caf\u{e9}.pdf";
+    let nfd_name = "cafe\u{301}.pdf";
+    let sources = [
+        ("a/Report.pdf", "first"),
+        ("b/report.pdf", "second"),
+        (&*format!("c/{nfc_name}"), "same"),
+        (&*format!("d/{nfd_name}"), "same"),
+    ].map(|(path, content)| (path.to_owned(), content));
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=sources.len()).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            let (path, content) = &sources[msg.source_id_option.unwrap() as usize - 1];
+            let file = ds_root.to_absolute(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            create_named_file(&file, content.as_bytes());
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![content!(File {
+                path_option: Some(path.clone()),
+                file_name_option: None,
+                mime_type_option: None,
+                thumbnail_path_option: None,
+            })];
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+
+    let src_files = dataset_files(daos.src_dao.as_ref(), &daos.ds_uuid);
+    let dst_files = dataset_files(&daos.dst_dao, &daos.ds_uuid);
+    assert_files(&src_files, &dst_files);
+
+    let dst_names = dst_files.iter().map(|f| path_file_name(f).unwrap().to_owned()).collect_vec();
+    assert_eq!(dst_names[0], "Report.pdf");
+    assert!(Regex::new(r"^report_[0-9A-F]{8}\.pdf$").unwrap().is_match(&dst_names[1]), "{}", dst_names[1]);
+    // Same content is stored once
+    assert_eq!(dst_names[2], nfc_name);
+    assert_eq!(dst_names[3], nfc_name);
+    Ok(())
+}
+
 /// Messages and chats are equal
 #[test]
 fn fetching() -> EmptyRes {
@@ -97,19 +194,19 @@ fn fetching() -> EmptyRes {
         assert!(practically_eq(&all_src_msgs, &all_dst_msgs)?);
         let last_idx = all_src_msgs.len() - 1;
 
-        let fetch = |f: &dyn Fn(&dyn ChatHistoryDao, &ChatWithDetails, &[Message]) -> Result<Vec<Message>>| {
+        let fetch = |f: &dyn Fn(&dyn ChatHistoryReader, &ChatWithDetails, &[Message]) -> Result<Vec<Message>>| {
             let src_msgs = f(daos.src_dao.as_ref(), src_cwd, &all_src_msgs)?;
             let dst_msgs = f(&daos.dst_dao, dst_cwd, &all_dst_msgs)?;
             ok((src_msgs, dst_msgs))
         };
-        let fetch_abbrev = |f: &dyn Fn(&dyn ChatHistoryDao, &ChatWithDetails, &[Message]) -> Result<(Vec<Message>, usize, Vec<Message>)>| {
+        let fetch_abbrev = |f: &dyn Fn(&dyn ChatHistoryReader, &ChatWithDetails, &[Message]) -> Result<(Vec<Message>, usize, Vec<Message>)>| {
             let src_res = f(daos.src_dao.as_ref(), src_cwd, &all_src_msgs)?;
             let dst_res = f(&daos.dst_dao, dst_cwd, &all_dst_msgs)?;
             ok((src_res, dst_res))
         };
 
         // An unfortunate shortcoming of Rust not supporting generics for closures
-        let count = |f: &dyn Fn(&dyn ChatHistoryDao, &ChatWithDetails, &[Message]) -> Result<usize>| {
+        let count = |f: &dyn Fn(&dyn ChatHistoryReader, &ChatWithDetails, &[Message]) -> Result<usize>| {
             let src_msgs = f(daos.src_dao.as_ref(), src_cwd, &all_src_msgs)?;
             let dst_msgs = f(&daos.dst_dao, dst_cwd, &all_dst_msgs)?;
             ok((src_msgs, dst_msgs))
@@ -275,7 +372,7 @@ fn fetching_corner_cases() -> EmptyRes {
                          dao_holder.tmp_dir.path.clone(),
                          Some(dao_holder.tmp_dir));
 
-    let mut dao_vec: Vec<(&dyn ChatHistoryDao, &str)> = vec![];
+    let mut dao_vec: Vec<(&dyn ChatHistoryReader, &str)> = vec![];
     dao_vec.push((daos.src_dao.as_ref(), "in-memory"));
     dao_vec.push((&daos.dst_dao, "sqlite"));
     for (dao, clue) in dao_vec {
@@ -373,7 +470,7 @@ fn update_dataset_same_uuid() -> EmptyRes {
     let ds = dao.insert_dataset(Dataset { uuid: ZERO_PB_UUID.clone(), alias: "My Dataset".to_owned() })?;
     dao.insert_user(create_user(&ds.uuid, 1), true)?;
 
-    let ds = dao.as_mutable()?.update_dataset(ds.uuid.clone(), Dataset { uuid: ds.uuid.clone(), alias: "Renamed Dataset".to_owned() })?;
+    let ds = dao.update_dataset(ds.uuid.clone(), Dataset { uuid: ds.uuid.clone(), alias: "Renamed Dataset".to_owned() })?;
     assert_eq!(dao.datasets()?.remove(0), ds);
 
     Ok(())
@@ -529,7 +626,7 @@ fn update_user() -> EmptyRes {
     if let Some(message_service_pat!(GroupCreate(MessageServiceGroupCreate { members, .. }))) =
         dao.first_messages(&group_chat, 1)?.remove(0).typed
     {
-        assert_eq!(members.as_ref(), vec!["MYSELF FN", "U1 FN U1 LN", UNNAMED]);
+        assert_eq!(members, vec!["MYSELF FN", "U1 FN U1 LN", UNNAMED]);
     }
 
     Ok(())
@@ -650,6 +747,123 @@ fn update_chat_change_id() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn chat_name_history() -> EmptyRes {
+    let service = |idx: usize, svo: message_service::SealedValueOptional| Message {
+        text: vec![],
+        searchable_string: "".to_owned(),
+        typed: Some(message_service!(svo)),
+        ..create_regular_message(idx, 1)
+    };
+    let msgs = vec![
+        service(1, message_service::SealedValueOptional::GroupCreate(MessageServiceGroupCreate {
+            title: "Chat Zero".to_owned(),
+            members: vec![],
+        })),
+        create_regular_message(2, 2),
+        service(3, message_service::SealedValueOptional::GroupEditTitle(MessageServiceGroupEditTitle {
+            title: "Chat One".to_owned(),
+        })),
+    ];
+    let dao_holder = create_simple_dao(false, "test", msgs, 2, &|_, _, _| {});
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let cwd = dao.chats(&daos.ds_uuid)?.remove(0);
+    assert_eq!(cwd.chat.name_option.as_deref(), Some("Chat One"));
+
+    let entry = |name: &str, timestamp_option: Option<i64>| ChatNameEntry { name: name.to_owned(), timestamp_option };
+    let msgs = dao.first_messages(&cwd.chat, usize::MAX)?;
+    let title_entries = vec![entry("Chat Zero", Some(msgs[0].timestamp)), entry("Chat One", Some(msgs[2].timestamp))];
+    assert_eq!(daos.src_dao.chat_name_history(&cwd.chat)?, title_entries);
+    assert_eq!(dao.chat_name_history(&cwd.chat)?, title_entries);
+    assert_eq!(dao.former_chat_names(&cwd.chat)?, vec!["Chat Zero".to_owned()]);
+
+    // Renaming remembers the previous name, which survives chat ID change
+    let chat = dao.update_chat(cwd.id(), Chat { name_option: Some("Chat Two".to_owned()), ..cwd.chat.clone() })?;
+    let chat = dao.update_chat(ChatId(chat.id), Chat { id: 112233, ..chat })?;
+    assert_eq!(dao.chat_name_history(&chat)?,
+               [vec![entry("Chat One", None)], title_entries.clone()].concat());
+    assert_eq!(dao.former_chat_names(&chat)?, vec!["Chat One".to_owned(), "Chat Zero".to_owned()]);
+
+    dao.insert_chat_former_names(&chat, &["Chat Alpha".to_owned(), " ".to_owned(), "Chat One".to_owned()])?;
+    assert_eq!(dao.chat_name_history(&chat)?,
+               [vec![entry("Chat Alpha", None), entry("Chat One", None)], title_entries].concat());
+
+    dao.delete_chat(chat.clone())?;
+    assert_eq!(dao.chat_name_history(&chat)?, vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn notes() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let cwd = dao.chats(&daos.ds_uuid)?.remove(0);
+    let msgs = dao.first_messages(&cwd.chat, 2)?;
+
+    let note = |source_id: Option<i64>, internal_id: Option<i64>, text: &str| Note {
+        id: -1,
+        ds_uuid: daos.ds_uuid.clone(),
+        chat_id: cwd.chat.id,
+        message_source_id_option: source_id,
+        message_internal_id_option: internal_id,
+        text: text.to_owned(),
+        timestamp: 100,
+    };
+    let chat_note = dao.insert_note(note(None, None, "Chat note"))?;
+    // Message is referenced by source ID whenever it has one
+    let msg_note = dao.insert_note(note(None, Some(msgs[1].internal_id), "Message note"))?;
+    assert_eq!(msg_note.message_source_id_option, msgs[1].source_id_option);
+    assert_eq!(msg_note.message_internal_id_option, None);
+    assert_ne!(chat_note.id, msg_note.id);
+    assert_eq!(dao.notes(&daos.ds_uuid, None)?, vec![chat_note.clone(), msg_note.clone()]);
+
+    assert!(dao.insert_note(note(None, None, "  ")).is_err());
+    assert!(dao.insert_note(note(Some(123456789), None, "Missing message")).is_err());
+    assert!(dao.insert_note(Note { chat_id: 123456789, ..note(None, None, "Missing chat") }).is_err());
+
+    let msg_note = dao.update_note(msg_note.id, "Updated", Timestamp(200))?;
+    assert_eq!((msg_note.text.as_str(), msg_note.timestamp), ("Updated", 200));
+
+    // Notes follow chat ID change
+    let chat = dao.update_chat(cwd.id(), Chat { id: 112233, ..cwd.chat.clone() })?;
+    assert_eq!(dao.notes(&daos.ds_uuid, Some(cwd.chat.id))?, vec![]);
+    assert_eq!(dao.notes(&daos.ds_uuid, Some(chat.id))?.len(), 2);
+
+    dao.delete_note(chat_note.id)?;
+    assert!(dao.delete_note(chat_note.id).is_err());
+    assert_eq!(dao.notes(&daos.ds_uuid, None)?, vec![Note { chat_id: chat.id, ..msg_note }]);
+
+    dao.delete_chat(chat)?;
+    assert_eq!(dao.notes(&daos.ds_uuid, None)?, vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn update_chat_flags() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+
+    let cwd = dao.chats(&daos.ds_uuid)?.remove(0);
+    assert!(!cwd.chat.archived() && !cwd.chat.hidden());
+
+    let chat = Chat { archived: Some(true), hidden: Some(true), ..cwd.chat.clone() };
+    dao.update_chat(cwd.id(), chat.clone())?;
+    let new_cwd = dao.chat_option(&daos.ds_uuid, *cwd.id())?.unwrap();
+    assert_eq!(new_cwd.chat, chat);
+    // Flags are user-assigned and don't affect practical equality
+    assert!(Tup::new(&cwd.chat, &daos.dst_ds_root, &cwd)
+        .practically_equals(&Tup::new(&new_cwd.chat, &daos.dst_ds_root, &new_cwd))?);
+
+    dao.update_chat(cwd.id(), Chat { archived: Some(false), ..chat })?;
+    let new_cwd = dao.chat_option(&daos.ds_uuid, *cwd.id())?.unwrap();
+    assert!(!new_cwd.chat.archived() && new_cwd.chat.hidden());
+
+    Ok(())
+}
+
 #[test]
 fn delete_chat() -> EmptyRes {
     let daos = init();
@@ -782,6 +996,1272 @@ fn combine_chats() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn redact_messages() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+
+    let cwd = dao.chats(&daos.ds_uuid)?.into_iter()
+        .find(|cwd| cwd.chat.tpe == ChatType::PrivateGroup as i32).unwrap();
+    let msgs = dao.first_messages(&cwd.chat, usize::MAX)?;
+    let (to_redact, to_keep): (Vec<_>, Vec<_>) =
+        msgs.into_iter().partition(|m| !m.files(&daos.dst_ds_root).is_empty());
+    assert!(!to_redact.is_empty());
+    let redacted_files = to_redact.iter().flat_map(|m| m.files(&daos.dst_ds_root)).collect_vec();
+    let kept_files = to_keep.iter().flat_map(|m| m.files(&daos.dst_ds_root)).collect_vec();
+    for f in redacted_files.iter() {
+        assert!(f.exists());
+    }
+
+    // Messages from other chats can't be redacted this way
+    let other_cwd = dao.chats(&daos.ds_uuid)?.into_iter().find(|cwd2| cwd2.id() != cwd.id()).unwrap();
+    let other_msg = dao.first_messages(&other_cwd.chat, 1)?.remove(0);
+    assert!(dao.redact_messages(&cwd.chat, &[other_msg.internal_id()]).is_err());
+    assert_eq!(dao.redaction_log(&daos.ds_uuid)?, vec![]);
+
+    let ids = to_redact.iter().map(|m| m.internal_id()).collect_vec();
+    let entry = dao.redact_messages(&cwd.chat, &ids)?;
+    assert_eq!(entry.chat_id_option, Some(*cwd.id()));
+    assert_eq!(entry.deleted_messages_count, ids.len() as i32);
+    assert_eq!(dao.redaction_log(&daos.ds_uuid)?, vec![entry]);
+
+    let new_cwd = dao.chat_option(&daos.ds_uuid, *cwd.id())?.unwrap();
+    assert_eq!(new_cwd.chat.msg_count as usize, to_keep.len());
+    assert_eq!(dao.first_messages(&new_cwd.chat, usize::MAX)?, to_keep);
+    for f in redacted_files.iter() {
+        assert!(!f.exists() || kept_files.contains(f), "File {} was not deleted!", f.display());
+    }
+    for f in kept_files.iter() {
+        assert!(f.exists(), "File {} does not exist!", f.display());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn redact_strings() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+
+    let all_msgs = |dao: &SqliteDao| -> Result<Vec<(ChatId, Message)>> {
+        dao.chats(&daos.ds_uuid)?.into_iter()
+            .map(|cwd| ok(dao.first_messages(&cwd.chat, usize::MAX)?.into_iter().map(move |m| (cwd.id(), m))))
+            .flatten_ok()
+            .try_collect()
+    };
+    let msgs_containing = |msgs: &[(ChatId, Message)], s: &str| -> Vec<(ChatId, MessageInternalId)> {
+        msgs.iter()
+            .filter(|(_, m)| m.searchable_string.contains(s) || format!("{:?}", m.typed()).contains(s))
+            .map(|(chat_id, m)| (*chat_id, m.internal_id()))
+            .collect_vec()
+    };
+
+    let old_msgs = all_msgs(&dao)?;
+    // Pick a word present in multiple chats
+    let word = old_msgs.iter()
+        .flat_map(|(_, m)| m.searchable_string.split(' ').filter(|w| w.len() > 3).map(|w| w.to_owned()).collect_vec())
+        .find(|w| msgs_containing(&old_msgs, w).iter().map(|(chat_id, _)| chat_id).unique().count() > 1)
+        .unwrap();
+    let affected = msgs_containing(&old_msgs, &word);
+    let chat_id = affected[0].0;
+
+    assert!(dao.redact_strings(&daos.ds_uuid, None, &[]).is_err());
+    assert!(dao.redact_strings(&daos.ds_uuid, None, &["REDACT".to_owned()]).is_err());
+
+    // Single chat
+    let entry = dao.redact_strings(&daos.ds_uuid, Some(chat_id), &[word.clone(), word.clone()])?;
+    assert_eq!(entry.chat_id_option, Some(*chat_id));
+    assert_eq!(entry.scrubbed_strings_count, 1);
+    assert_eq!(entry.scrubbed_messages_count as usize, affected.iter().filter(|(c, _)| *c == chat_id).count());
+    let new_msgs = all_msgs(&dao)?;
+    assert_eq!(msgs_containing(&new_msgs, &word), affected.iter().filter(|(c, _)| *c != chat_id).cloned().collect_vec());
+
+    // Whole dataset
+    let entry2 = dao.redact_strings(&daos.ds_uuid, None, std::slice::from_ref(&word))?;
+    assert_eq!(entry2.chat_id_option, None);
+    assert_eq!(entry2.scrubbed_messages_count as usize, affected.iter().filter(|(c, _)| *c != chat_id).count());
+    let new_msgs = all_msgs(&dao)?;
+    assert_eq!(msgs_containing(&new_msgs, &word), vec![]);
+    assert_eq!(new_msgs.len(), old_msgs.len());
+    for ((_, old_msg), (_, new_msg)) in old_msgs.iter().zip(new_msgs.iter()) {
+        let redacted = old_msg.searchable_string.replace(&word, REDACTED_PLACEHOLDER);
+        assert_eq!(new_msg.searchable_string, redacted);
+    }
+
+    assert_eq!(dao.redaction_log(&daos.ds_uuid)?, vec![entry, entry2]);
+
+    Ok(())
+}
+
+#[test]
+fn redact_strings_in_former_names_and_notes() -> EmptyRes {
+    const SECRET: &str = "Sup3rS3cret";
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let chats = dao.chats(&daos.ds_uuid)?.into_iter().map(|cwd| cwd.chat).collect_vec();
+    assert!(chats.len() > 1);
+
+    let note = |chat: &Chat, text: String| Note {
+        id: -1,
+        ds_uuid: daos.ds_uuid.clone(),
+        chat_id: chat.id,
+        message_source_id_option: None,
+        message_internal_id_option: None,
+        text,
+        timestamp: 100,
+    };
+    for chat in chats.iter() {
+        dao.insert_chat_former_names(chat, &[format!("{SECRET} 1"), format!("{SECRET} 2"), "Public".to_owned()])?;
+        dao.insert_note(note(chat, format!("Note about {SECRET}")))?;
+    }
+    let former_names = |dao: &SqliteDao, chat: &Chat| -> Result<Vec<String>> {
+        Ok(dao.chat_name_history(chat)?.into_iter().filter(|e| e.timestamp_option.is_none()).map(|e| e.name).collect_vec())
+    };
+    let note_texts = |dao: &SqliteDao, chat: &Chat| -> Result<Vec<String>> {
+        Ok(dao.notes(&daos.ds_uuid, Some(chat.id))?.into_iter().map(|n| n.text).collect_vec())
+    };
+
+    dao.redact_strings(&daos.ds_uuid, Some(ChatId(chats[0].id)), &[SECRET.to_owned()])?;
+    assert_eq!(former_names(&dao, &chats[0])?, vec!["Public".to_owned(), "[REDACTED] 1".to_owned(), "[REDACTED] 2".to_owned()]);
+    assert_eq!(note_texts(&dao, &chats[0])?, vec!["Note about [REDACTED]".to_owned()]);
+    assert_eq!(note_texts(&dao, &chats[1])?, vec![format!("Note about {SECRET}")]);
+
+    // Names that become identical are collapsed
+    dao.redact_strings(&daos.ds_uuid, None, &[format!("{SECRET} 1"), format!("{SECRET} 2")])?;
+    assert_eq!(former_names(&dao, &chats[1])?, vec!["Public".to_owned(), "[REDACTED]".to_owned()]);
+    assert_eq!(note_texts(&dao, &chats[1])?, vec![format!("Note about {SECRET}")]);
+
+    Ok(())
+}
+
+#[test]
+fn redacted_data_is_purged() -> EmptyRes {
+    const SECRET_STRING: &str = "Sup3rS3cretPassw0rd";
+    const SECRET_MESSAGE: &str = "Sup3rS3cretM3ssag3";
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, m| {
+            let text = match m.source_id_option {
+                Some(5) => format!("My password is {SECRET_STRING}"),
+                Some(7) => SECRET_MESSAGE.to_owned(),
+                _ => return,
+            };
+            m.text = vec![RichText::make_plain(text)];
+            m.searchable_string = make_searchable_string(&m.text, m.typed());
+        });
+    let src_dao = dao_holder.dao.as_ref();
+    let ds_uuid = src_dao.ds_uuid();
+
+    let (mut dao, _dao_tmpdir) = create_sqlite_dao();
+    dao.copy_datasets_from(src_dao, &[ds_uuid.clone()])?;
+    dao.backup()?.join().unwrap();
+
+    // Raw bytes of the database file and of every backup
+    let raw_contents = |dao: &SqliteDao| -> Result<Vec<Vec<u8>>> {
+        let mut res = vec![fs::read(&dao.db_file)?];
+        for f in list_backups(&dao.backup_path())? {
+            let mut archive = zip::ZipArchive::new(File::open(f)?)?;
+            for idx in 0..archive.len() {
+                let mut bytes = vec![];
+                archive.by_index(idx)?.read_to_end(&mut bytes)?;
+                res.push(bytes);
+            }
+        }
+        Ok(res)
+    };
+    let contains = |bytes: &[u8], s: &str| bytes.windows(s.len()).any(|w| w == s.as_bytes());
+
+    let contents = raw_contents(&dao)?;
+    assert_eq!(contents.len(), 2);
+    assert!(contents.iter().all(|bytes| contains(bytes, SECRET_STRING) && contains(bytes, SECRET_MESSAGE)));
+
+    dao.redact_strings(&ds_uuid, None, &[SECRET_STRING.to_owned()])?;
+    let contents = raw_contents(&dao)?;
+    assert_eq!(contents.len(), 2);
+    assert!(contents.iter().all(|bytes| !contains(bytes, SECRET_STRING) && contains(bytes, SECRET_MESSAGE)));
+
+    let chat = dao.chats(&ds_uuid)?.remove(0).chat;
+    let msg = dao.first_messages(&chat, usize::MAX)?.into_iter().find(|m| m.source_id_option == Some(7)).unwrap();
+    dao.redact_messages(&chat, &[msg.internal_id()])?;
+    let contents = raw_contents(&dao)?;
+    assert_eq!(contents.len(), 2);
+    assert!(contents.iter().all(|bytes| !contains(bytes, SECRET_MESSAGE)));
+
+    Ok(())
+}
+
+#[test]
+fn retention_rules() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            let idx = msg.source_id_option.unwrap();
+            let mr = coerce_enum!(msg.typed.as_mut(), Some(message::Typed::Regular(mr)) => mr);
+            mr.is_deleted = idx % 2 == 0;
+            if idx % 3 == 0 {
+                let file = create_random_file(&ds_root.0);
+                mr.contents = vec![content!(File {
+                    path_option: Some(ds_root.to_relative(&file).unwrap()),
+                    file_name_option: None,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                })];
+            }
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+    let ds_root = daos.dst_ds_root;
+    let chat = dao.chats(&ds_uuid)?.remove(0).chat;
+
+    let msgs_by_idx = |dao: &SqliteDao| -> Result<HashMap<i64, Message>> {
+        Ok(dao.first_messages(&chat, usize::MAX)?.into_iter()
+            .map(|m| (m.source_id_option.unwrap(), m))
+            .collect())
+    };
+    let old_msgs = msgs_by_idx(&dao)?;
+    let files_of = |msgs: &HashMap<i64, Message>, idx: i64| msgs[&idx].files(&ds_root);
+    let file3 = files_of(&old_msgs, 3).remove(0);
+    let file6 = files_of(&old_msgs, 6).remove(0);
+
+    // Messages 1-5 are older than a day
+    let now = Timestamp(old_msgs[&6].timestamp + 24 * 60 * 60);
+    let rule = |chat_id_option: Option<i64>, action: RetentionAction, max_age_days: i32| RetentionRule {
+        id: -1,
+        ds_uuid: ds_uuid.clone(),
+        chat_id_option,
+        action: action as i32,
+        max_age_days,
+    };
+
+    assert!(dao.insert_retention_rule(rule(None, RetentionAction::DeleteMedia, 0)).is_err());
+    assert!(dao.insert_retention_rule(rule(Some(12345), RetentionAction::DeleteMedia, 1)).is_err());
+    assert_eq!(dao.apply_retention_rules(&ds_uuid, now, false)?, vec![]);
+
+    let media_rule = dao.insert_retention_rule(rule(None, RetentionAction::DeleteMedia, 1))?;
+    let tombstones_rule = dao.insert_retention_rule(rule(Some(chat.id), RetentionAction::DeleteTombstones, 1))?;
+    assert!(media_rule.id > 0 && tombstones_rule.id > 0);
+    assert_eq!(dao.retention_rules(&ds_uuid)?, vec![media_rule.clone(), tombstones_rule.clone()]);
+
+    let expected_reports = vec![
+        RetentionRuleReport {
+            rule: media_rule.clone(),
+            affected_messages_count: 1,
+            deleted_files_count: 1,
+            deleted_files_bytes: fs::metadata(&file3)?.len() as i64,
+        },
+        RetentionRuleReport {
+            rule: tombstones_rule.clone(),
+            affected_messages_count: 2,
+            deleted_files_count: 0,
+            deleted_files_bytes: 0,
+        },
+    ];
+
+    // Dry run
+    assert_eq!(dao.apply_retention_rules(&ds_uuid, now, true)?, expected_reports);
+    assert_eq!(msgs_by_idx(&dao)?, old_msgs);
+    assert!(file3.exists());
+
+    // Actual run
+    assert_eq!(dao.apply_retention_rules(&ds_uuid, now, false)?, expected_reports);
+    let new_msgs = msgs_by_idx(&dao)?;
+    assert_eq!(new_msgs.keys().copied().sorted().collect_vec(), vec![1, 3, 5, 6, 7, 8, 9, 10]);
+    assert_eq!(dao.chat_option(&ds_uuid, chat.id)?.unwrap().chat.msg_count, 8);
+    assert!(!file3.exists());
+    assert!(files_of(&new_msgs, 3).is_empty());
+    assert!(file6.exists());
+    assert_eq!(files_of(&new_msgs, 6), vec![file6.clone()]);
+
+    // Rules are idempotent
+    let reports = dao.apply_retention_rules(&ds_uuid, now, false)?;
+    assert!(reports.iter().all(|r| r.affected_messages_count == 0 && r.deleted_files_count == 0));
+
+    dao.delete_retention_rule(media_rule.id)?;
+    dao.delete_retention_rule(tombstones_rule.id)?;
+    assert!(dao.delete_retention_rule(media_rule.id).is_err());
+    let messages_rule = dao.insert_retention_rule(rule(Some(chat.id), RetentionAction::DeleteMessages, 1))?;
+    assert_eq!(dao.retention_rules(&ds_uuid)?, vec![messages_rule.clone()]);
+
+    assert_eq!(dao.apply_retention_rules(&ds_uuid, now, false)?, vec![RetentionRuleReport {
+        rule: messages_rule,
+        affected_messages_count: 3,
+        deleted_files_count: 0,
+        deleted_files_bytes: 0,
+    }]);
+    let new_msgs = msgs_by_idx(&dao)?;
+    assert_eq!(new_msgs.keys().copied().sorted().collect_vec(), vec![6, 7, 8, 9, 10]);
+    assert_eq!(dao.chat_option(&ds_uuid, chat.id)?.unwrap().chat.msg_count, 5);
+    assert!(file6.exists());
+
+    // Rules are deleted along with the chat
+    dao.delete_chat(chat)?;
+    assert_eq!(dao.retention_rules(&ds_uuid)?, vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn audit_log() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    assert_eq!(dao.audit_log()?, vec![]);
+
+    let entry1 = AuditLogEntry {
+        timestamp: 1700000000,
+        client_option: Some("me".to_owned()),
+        operation: "DeleteChat".to_owned(),
+        ds_uuid_option: Some(daos.ds_uuid.clone()),
+        chat_id_option: Some(123),
+        affected_chats_count: 1,
+        affected_messages_count: 42,
+        parameters: "chat 'Something' (#123)".to_owned(),
+    };
+    let entry2 = AuditLogEntry {
+        timestamp: 1700000001,
+        client_option: None,
+        operation: "DeleteChatFolder".to_owned(),
+        ds_uuid_option: None,
+        chat_id_option: None,
+        affected_chats_count: 0,
+        affected_messages_count: 0,
+        parameters: "folder #1".to_owned(),
+    };
+    dao.record_audit_entry(entry1.clone())?;
+    dao.record_audit_entry(entry2.clone())?;
+    assert_eq!(dao.audit_log()?, vec![entry1.clone(), entry2.clone()]);
+
+    // Entries outlive the dataset they refer to
+    dao.delete_dataset(daos.ds_uuid.clone())?;
+    assert_eq!(dao.audit_log()?, vec![entry1.clone(), entry2.clone()]);
+
+    let (mut new_dao, _tmp_dir) = create_sqlite_dao();
+    new_dao.copy_audit_log_from(&dao)?;
+    assert_eq!(new_dao.audit_log()?, vec![entry1, entry2]);
+
+    Ok(())
+}
+
+#[test]
+fn override_myself() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+    let myself = dao.myself(&ds_uuid)?;
+
+    let cwd = dao.chats(&ds_uuid)?.into_iter()
+        .filter(|cwd| cwd.chat.member_ids.len() > 1)
+        .max_by_key(|cwd| cwd.chat.msg_count)
+        .unwrap();
+    let other_id = UserId(cwd.chat.member_ids[1]);
+    let from_ids = |dao: &SqliteDao| -> Result<Vec<UserId>> {
+        Ok(dao.first_messages(&cwd.chat, usize::MAX)?.iter().map(|m| UserId(m.from_id)).collect_vec())
+    };
+    let old_from_ids = from_ids(&dao)?;
+    assert!(old_from_ids.contains(&myself.id()));
+
+    // Unknown user or non-member
+    assert!(dao.override_myself(&ds_uuid, UserId(-1), Some(cwd.id())).is_err());
+    let non_member = dao.users(&ds_uuid)?.into_iter().find(|u| !cwd.chat.member_ids.contains(&u.id));
+    if let Some(non_member) = non_member {
+        assert!(dao.override_myself(&ds_uuid, non_member.id(), Some(cwd.id())).is_err());
+    }
+
+    // Single chat
+    dao.override_myself(&ds_uuid, other_id, Some(cwd.id()))?;
+    assert_eq!(dao.myself(&ds_uuid)?, myself);
+    let swapped = |id: &UserId| if *id == myself.id() { other_id } else if *id == other_id { myself.id() } else { *id };
+    assert_eq!(from_ids(&dao)?, old_from_ids.iter().map(swapped).collect_vec());
+
+    // Swapping back
+    dao.override_myself(&ds_uuid, other_id, Some(cwd.id()))?;
+    assert_eq!(from_ids(&dao)?, old_from_ids);
+
+    // Whole dataset
+    let old_chats = dao.chats(&ds_uuid)?;
+    dao.override_myself(&ds_uuid, other_id, None)?;
+    assert_eq!(dao.myself(&ds_uuid)?.id(), other_id);
+    assert!(dao.users(&ds_uuid)?.iter().any(|u| u.id == myself.id));
+    for (old_cwd, new_cwd) in old_chats.iter().zip(dao.chats(&ds_uuid)?.iter()) {
+        assert_eq!(new_cwd.chat.member_ids[0], *other_id);
+        assert_eq!(new_cwd.chat.member_ids.iter().sorted().collect_vec(),
+                   old_cwd.chat.member_ids.iter().chain([&*other_id]).unique().sorted().collect_vec());
+        assert_eq!(new_cwd.members[0].id, *other_id);
+    }
+    assert_eq!(from_ids(&dao)?, old_from_ids);
+    Ok(())
+}
+
+#[test]
+fn compact() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+
+    let chats = dao.chats(&ds_uuid)?;
+    let cwd = chats.iter().max_by_key(|cwd| cwd.chat.msg_count).unwrap();
+    dao.delete_chat(cwd.chat.clone())?;
+    let users = dao.users(&ds_uuid)?;
+
+    let report = dao.compact()?;
+    assert!(report.size_after_bytes <= report.size_before_bytes);
+    assert_eq!(report.size_after_bytes as u64, fs::metadata(&dao.db_file)?.len());
+
+    // Nothing is lost
+    assert_eq!(dao.chats(&ds_uuid)?.len(), chats.len() - 1);
+    assert_eq!(dao.users(&ds_uuid)?, users);
+    Ok(())
+}
+
+#[test]
+fn trash() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+    assert_eq!(dao.trash()?, vec![]);
+
+    let source_ids = |dao: &SqliteDao, chat: &Chat| -> Result<Vec<Option<i64>>> {
+        Ok(dao.first_messages(chat, usize::MAX)?.iter().map(|m| m.source_id_option).collect_vec())
+    };
+
+    let old_users = dao.users(&ds_uuid)?;
+    let cwd = dao.chat_option(&ds_uuid, *CHAT_ID_TO_DELETE)?.unwrap();
+    let old_source_ids = source_ids(&dao, &cwd.chat)?;
+    dao.delete_chat(cwd.chat.clone())?;
+    assert!(dao.chat_option(&ds_uuid, *CHAT_ID_TO_DELETE)?.is_none());
+
+    let items = dao.trash()?;
+    assert_eq!(items.len(), 1);
+    let item = &items[0];
+    assert_eq!(item.ds_uuid, ds_uuid);
+    assert_eq!(item.chat_id_option, Some(*CHAT_ID_TO_DELETE));
+    assert_eq!(item.name, name_or_unnamed(&cwd.chat.name_option));
+    assert_eq!((item.chats_count, item.messages_count), (1, cwd.chat.msg_count));
+    assert!(dao.trash_path().exists());
+
+    // Chat is restored along with the orphan users deleted with it
+    dao.restore_from_trash(item.id)?;
+    assert_eq!(dao.trash()?, vec![]);
+    let restored_cwd = dao.chat_option(&ds_uuid, *CHAT_ID_TO_DELETE)?.unwrap();
+    assert_eq!(restored_cwd.chat, cwd.chat);
+    assert_eq!(source_ids(&dao, &restored_cwd.chat)?, old_source_ids);
+    assert_eq!(dao.users(&ds_uuid)?, old_users);
+    assert!(dao.restore_from_trash(item.id).is_err());
+
+    // Whole dataset
+    let chats_count = dao.chats(&ds_uuid)?.len();
+    dao.delete_dataset(ds_uuid.clone())?;
+    assert_eq!(dao.datasets()?, vec![]);
+    let item = dao.trash()?.remove(0);
+    assert_eq!(item.chat_id_option, None);
+    assert_eq!(item.chats_count as usize, chats_count);
+    dao.restore_from_trash(item.id)?;
+    assert_eq!(dao.chats(&ds_uuid)?.len(), chats_count);
+    assert_eq!(dao.users(&ds_uuid)?, old_users);
+
+    // Purging
+    dao.delete_chat(restored_cwd.chat)?;
+    let item = dao.trash()?.remove(0);
+    assert_eq!(dao.purge_trash(Timestamp(item.timestamp))?, 0);
+    assert_eq!(dao.purge_trash(Timestamp(item.timestamp + 1))?, 1);
+    assert_eq!(dao.trash()?, vec![]);
+    assert_eq!(dao.trash_path().read_dir()?.count(), 0);
+    assert!(dao.restore_from_trash(item.id).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn redact_strings_in_trash() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+
+    let cwd = dao.chat_option(&ds_uuid, *CHAT_ID_TO_DELETE)?.unwrap();
+    let searchable_strings = |dao: &SqliteDao| -> Result<Vec<String>> {
+        Ok(dao.first_messages(&cwd.chat, usize::MAX)?.into_iter().map(|m| m.searchable_string).collect_vec())
+    };
+    let word = searchable_strings(&dao)?.iter()
+        .flat_map(|s| s.split(' ').filter(|w| w.len() > 3).map(|w| w.to_owned()).collect_vec())
+        .next()
+        .unwrap();
+    let expected = searchable_strings(&dao)?.iter().map(|s| s.replace(&word, REDACTED_PLACEHOLDER)).collect_vec();
+
+    dao.delete_chat(cwd.chat.clone())?;
+    dao.redact_strings(&ds_uuid, Some(cwd.id()), std::slice::from_ref(&word))?;
+
+    // Trash item itself isn't logged as a separate redaction
+    assert_eq!(dao.redaction_log(&ds_uuid)?.len(), 1);
+    dao.restore_from_trash(dao.trash()?.remove(0).id)?;
+    assert_eq!(searchable_strings(&dao)?, expected);
+
+    Ok(())
+}
+
+#[test]
+fn message_provenance() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+
+    let batch = daos.src_dao.import_batches(&ds_uuid)?.remove(0);
+    assert_eq!(batch.source_path, path_to_str(&daos.src_dir)?);
+    assert_eq!(batch.loader_name, "Telegram");
+    assert_eq!(batch.loader_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(dao.import_batches(&ds_uuid)?, vec![batch.clone()]);
+
+    let cwd = dao.chat_option(&ds_uuid, *CHAT_ID_TO_DELETE)?.unwrap();
+    let provenances = |dao: &SqliteDao, chat: &Chat| -> Result<Vec<Option<ImportBatch>>> {
+        dao.first_messages(chat, usize::MAX)?.iter()
+            .map(|m| dao.message_provenance(chat, m.internal_id()))
+            .try_collect()
+    };
+    let expected = vec![Some(batch.clone()); cwd.chat.msg_count as usize];
+    assert_eq!(provenances(&dao, &cwd.chat)?, expected);
+    assert_eq!(dao.message_provenance(&cwd.chat, MessageInternalId(-1))?, None);
+
+    // Provenance survives a round trip through trash
+    dao.delete_chat(cwd.chat.clone())?;
+    dao.restore_from_trash(dao.trash()?.remove(0).id)?;
+    assert_eq!(dao.import_batches(&ds_uuid)?, vec![batch.clone()]);
+    assert_eq!(provenances(&dao, &cwd.chat)?, expected);
+
+    // Messages inserted on their own have unknown provenance
+    let msg = Message { source_id_option: None, from_id: dao.myself(&ds_uuid)?.id, ..create_regular_message(0, 1) };
+    dao.insert_messages(vec![msg], &cwd.chat, &daos.dst_ds_root)?;
+    let last_msg = dao.last_messages(&cwd.chat, 1)?.remove(0);
+    assert_eq!(dao.message_provenance(&cwd.chat, last_msg.internal_id())?, None);
+
+    dao.delete_dataset(ds_uuid.clone())?;
+    assert_eq!(dao.import_batches(&ds_uuid)?, vec![]);
+    Ok(())
+}
+
+#[test]
+fn fingerprint() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+
+    // Same data in a different DAO and a different file layout
+    let src_fingerprint = dataset_fingerprint(daos.src_dao.as_ref(), &daos.ds_uuid)?;
+    let dst_fingerprint = dataset_fingerprint(&dao, &daos.ds_uuid)?;
+    assert_eq!(src_fingerprint.len(), 64);
+    assert_eq!(src_fingerprint, dst_fingerprint);
+    assert_eq!(dataset_fingerprint(&dao, &daos.ds_uuid)?, dst_fingerprint);
+
+    // Copy has the same fingerprint
+    let (copy_dao, _copy_tmpdir) = create_sqlite_dao();
+    copy_dao.copy_datasets_from(&dao, std::slice::from_ref(&daos.ds_uuid))?;
+    assert_eq!(dataset_fingerprint(&copy_dao, &daos.ds_uuid)?, dst_fingerprint);
+
+    // File corruption is detected
+    let file = dataset_files(&dao, &daos.ds_uuid).into_iter().find(|f| f.is_file()).unwrap();
+    let file_content = fs::read(&file)?;
+    fs::write(&file, b"corrupted")?;
+    assert_ne!(dataset_fingerprint(&dao, &daos.ds_uuid)?, dst_fingerprint);
+    fs::write(&file, file_content)?;
+    assert_eq!(dataset_fingerprint(&dao, &daos.ds_uuid)?, dst_fingerprint);
+
+    // Data change is detected
+    let cwd = dao.chats(&daos.ds_uuid)?.remove(0);
+    dao.update_chat(cwd.id(), Chat { name_option: Some("Renamed".to_owned()), ..cwd.chat })?;
+    assert_ne!(dataset_fingerprint(&dao, &daos.ds_uuid)?, dst_fingerprint);
+
+    Ok(())
+}
+
+#[test]
+fn permalinks() -> EmptyRes {
+    let daos = init();
+    let dao = &daos.dst_dao;
+
+    let cwd = dao.chats(&daos.ds_uuid)?.into_iter().max_by_key(|cwd| cwd.chat.msg_count).unwrap();
+    let chat = &cwd.chat;
+    assert!(chat.msg_count >= 5);
+    let msgs = dao.first_messages(chat, chat.msg_count as usize)?;
+    let msg = &msgs[2];
+
+    // Source ID link
+    let permalink = Permalink::for_message(chat, msg);
+    let permalink_str = permalink.to_string();
+    assert_eq!(permalink_str,
+               format!("chm://{}/{}/{}", daos.ds_uuid.value, chat.id, msg.source_id_option.unwrap()));
+    assert_eq!(permalink_str.parse::<Permalink>()?, permalink);
+
+    let resolved = resolve_permalink(dao, &permalink, 2)?.unwrap();
+    assert_eq!(&resolved.chat, chat);
+    assert_eq!(&resolved.message, msg);
+    assert_eq!(resolved.messages_before, msgs[0..2].to_vec());
+    assert_eq!(resolved.messages_after, msgs[3..5].to_vec());
+
+    let resolved = resolve_permalink(dao, &permalink, 0)?.unwrap();
+    assert_eq!(&resolved.message, msg);
+    assert!(resolved.messages_before.is_empty() && resolved.messages_after.is_empty());
+
+    // Internal ID link
+    let permalink = Permalink { message_id: PermalinkMessageId::Internal(msgs[0].internal_id()), ..permalink };
+    let permalink_str = permalink.to_string();
+    assert_eq!(permalink_str, format!("chm://{}/{}/i{}", daos.ds_uuid.value, chat.id, msgs[0].internal_id));
+    assert_eq!(permalink_str.parse::<Permalink>()?, permalink);
+
+    let resolved = resolve_permalink(dao, &permalink, 2)?.unwrap();
+    assert_eq!(&resolved.message, &msgs[0]);
+    assert_eq!(resolved.messages_before, vec![]);
+    assert_eq!(resolved.messages_after, msgs[1..3].to_vec());
+
+    // Dangling links
+    let missing_msg = Permalink { message_id: PermalinkMessageId::Internal(MessageInternalId(i64::MAX)), ..permalink.clone() };
+    assert_eq!(resolve_permalink(dao, &missing_msg, 2)?, None);
+    let missing_chat = Permalink { chat_id: ChatId(-1), ..permalink.clone() };
+    assert_eq!(resolve_permalink(dao, &missing_chat, 2)?, None);
+
+    // Malformed links
+    for malformed in [
+        "http://example.com",
+        "chm://",
+        "chm://not-a-uuid/1/2",
+        &format!("chm://{}/1", daos.ds_uuid.value),
+        &format!("chm://{}/x/2", daos.ds_uuid.value),
+        &format!("chm://{}/1/ix", daos.ds_uuid.value),
+        &format!("chm://{}/1/2/3", daos.ds_uuid.value),
+    ] {
+        assert!(malformed.parse::<Permalink>().is_err(), "{malformed}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn date_navigation() -> EmptyRes {
+    let daos = init();
+    let src_dao = daos.src_dao.as_ref();
+    let dst_dao = &daos.dst_dao;
+
+    for src_cwd in src_dao.chats(&daos.ds_uuid)? {
+        let src_chat = &src_cwd.chat;
+        let dst_chat = &dst_dao.chat_option(&daos.ds_uuid, src_chat.id)?.unwrap().chat;
+        let src_msgs = src_dao.first_messages(src_chat, src_chat.msg_count as usize)?;
+
+        let src_calendar = src_dao.messages_calendar(src_chat)?;
+        assert_eq!(dst_dao.messages_calendar(dst_chat)?, src_calendar);
+        assert_eq!(src_calendar.iter().map(|d| d.messages_count).sum::<i32>(), src_chat.msg_count);
+
+        let timestamps = src_msgs.iter()
+            .flat_map(|m| [m.timestamp - 1, m.timestamp, m.timestamp + 1])
+            .chain([Timestamp::MIN.0, Timestamp::MAX.0]);
+        for ts in timestamps {
+            let src_msg = src_dao.first_message_on_or_after(src_chat, Timestamp(ts))?;
+            let dst_msg = dst_dao.first_message_on_or_after(dst_chat, Timestamp(ts))?;
+            assert_eq!(dst_msg.as_ref().map(|m| m.source_id_option), src_msg.as_ref().map(|m| m.source_id_option));
+            if let Some(msg) = dst_msg {
+                assert!(msg.timestamp >= ts);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn chat_links() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=5).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, msg| {
+            let link = |href: &str| RichText::make_link(None, href.to_owned(), false);
+            match msg.source_id_option.unwrap() {
+                1 => msg.text.push(link("https://b.com")),
+                2 => msg.text.extend([link("https://a.com"), link("https://b.com"), link("https://a.com")]),
+                4 => msg.text.insert(0, link("https://c.com")),
+                5 => msg.text.push(link("https://a.com")),
+                _ => {}
+            }
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let src_dao = daos.src_dao.as_ref();
+    let src_chat = src_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let dst_chat = daos.dst_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+
+    let to_source_ids = |dao: &dyn ChatHistoryReader, chat: &Chat, links: Vec<ChatLink>| -> Result<Vec<_>> {
+        let msgs = dao.first_messages(chat, chat.msg_count as usize)?;
+        let source_id = |id: i64| msgs.iter().find(|m| m.internal_id == id).unwrap().source_id_option.unwrap();
+        Ok(links.into_iter()
+            .map(|l| (l.href, l.message_internal_ids.into_iter().map(source_id).collect_vec(), l.first_shared_timestamp))
+            .collect_vec())
+    };
+    let ts = |idx: usize| create_regular_message(idx, 1).timestamp;
+    let expected = vec![
+        ("https://b.com".to_owned(), vec![1, 2], ts(1)),
+        ("https://a.com".to_owned(), vec![2, 5], ts(2)),
+        ("https://c.com".to_owned(), vec![4], ts(4)),
+    ];
+    assert_eq!(to_source_ids(src_dao, &src_chat, src_dao.chat_links(&src_chat)?)?, expected);
+    assert_eq!(to_source_ids(&daos.dst_dao, &dst_chat, daos.dst_dao.chat_links(&dst_chat)?)?, expected);
+
+    Ok(())
+}
+
+#[test]
+fn searchable_string_settings() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, msg| {
+            let content = match msg.source_id_option {
+                Some(1) => content!(File {
+                    path_option: None,
+                    file_name_option: Some("report.pdf".to_owned()),
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                }),
+                Some(2) => content!(Location {
+                    title_option: Some("Pub".to_owned()),
+                    address_option: None,
+                    lat_str: "51.51".to_owned(),
+                    lon_str: "-0.13".to_owned(),
+                    duration_sec_option: None,
+                    updates: vec![],
+                }),
+                _ => content!(SharedContact {
+                    first_name_option: Some("Alice".to_owned()),
+                    last_name_option: None,
+                    phone_number_option: Some("+1 (555) 123-45-67".to_owned()),
+                    vcard_path_option: None,
+                    emails: vec![],
+                }),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![content];
+            msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let ds_uuid = &daos.ds_uuid;
+    let search = |dao: &SqliteDao, text: &str| -> Result<Vec<i64>> {
+        Ok(dao.search_messages(ds_uuid, text, None, None, 100)?.into_iter()
+            .map(|h| h.message.source_id_option.unwrap())
+            .collect_vec())
+    };
+
+    assert_eq!(dao.searchable_string_settings(ds_uuid)?, SearchableStringSettings::default());
+    assert_eq!(search(&dao, "report.pdf")?, vec![1]);
+    assert_eq!(search(&dao, "51.51")?, vec![2]);
+    assert_eq!(search(&dao, "(555) 123")?, vec![3]);
+    assert_eq!(search(&dao, "15551234567")?, Vec::<i64>::new());
+
+    // Settings alone don't change anything
+    let settings = SearchableStringSettings {
+        include_file_names: Some(false),
+        include_coordinates: Some(false),
+        phone_number_format: Some(PhoneNumberFormat::Both as i32),
+        transliterate: None,
+    };
+    dao.update_searchable_string_settings(ds_uuid, &settings)?;
+    assert_eq!(dao.searchable_string_settings(ds_uuid)?, settings);
+    assert_eq!(search(&dao, "report.pdf")?, vec![1]);
+
+    assert_eq!(dao.recompute_searchable_strings(ds_uuid)?, 3);
+    assert_eq!(dao.recompute_searchable_strings(ds_uuid)?, 0);
+    assert_eq!(search(&dao, "report.pdf")?, Vec::<i64>::new());
+    assert_eq!(search(&dao, "51.51")?, Vec::<i64>::new());
+    assert_eq!(search(&dao, "Pub")?, vec![2]);
+    assert_eq!(search(&dao, "(555) 123")?, vec![3]);
+    assert_eq!(search(&dao, "+15551234567")?, vec![3]);
+
+    // Newly inserted messages follow the settings
+    let chat = dao.chats(ds_uuid)?.remove(0).chat;
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let mut msg = dao.first_messages(&chat, 1)?.remove(0);
+    msg.source_id_option = Some(4);
+    msg.timestamp += 1000;
+    msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+    dao.insert_messages(vec![msg], &chat, &ds_root)?;
+    assert_eq!(search(&dao, "report.pdf")?, Vec::<i64>::new());
+
+    Ok(())
+}
+
+#[test]
+fn transliterated_search() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, msg| {
+            let text = match msg.source_id_option {
+                Some(1) => "Привет, как дела?",
+                Some(2) => "privet, vse khorosho",
+                _ => "Hello",
+            };
+            msg.text = vec![RichText::make_plain(text.to_owned())];
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![];
+            msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let ds_uuid = &daos.ds_uuid;
+    let search = |dao: &SqliteDao, text: &str| -> Result<Vec<i64>> {
+        Ok(dao.search_messages(ds_uuid, text, None, None, 100)?.into_iter()
+            .map(|h| h.message.source_id_option.unwrap())
+            .sorted()
+            .collect_vec())
+    };
+
+    assert_eq!(search(&dao, "privet")?, vec![2]);
+    assert_eq!(search(&dao, "привет")?, vec![1]);
+
+    dao.update_searchable_string_settings(ds_uuid, &SearchableStringSettings {
+        transliterate: Some(true),
+        ..Default::default()
+    })?;
+    assert_eq!(dao.recompute_searchable_strings(ds_uuid)?, 1);
+    assert_eq!(search(&dao, "privet")?, vec![1, 2]);
+    assert_eq!(search(&dao, "Привет")?, vec![1, 2]);
+    assert_eq!(search(&dao, "хорошо")?, vec![2]);
+    assert_eq!(search(&dao, "kak dela")?, vec![1]);
+    assert_eq!(search(&dao, "hello")?, vec![3]);
+
+    Ok(())
+}
+
+#[test]
+fn search() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=10).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, msg| {
+            if msg.source_id_option == Some(5) {
+                msg.text = vec![RichText::make_plain("Sale: 50% off!".to_owned())];
+                msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+            }
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let src_dao = daos.src_dao.as_ref();
+    let dst_dao = &daos.dst_dao;
+    let chat_id = src_dao.chats(&daos.ds_uuid)?.remove(0).chat.id;
+
+    let source_ids = |hits: &[SearchHit]| {
+        assert!(hits.iter().all(|h| h.chat_id == chat_id));
+        hits.iter().map(|h| h.message.source_id_option.unwrap()).collect_vec()
+    };
+    for dao in [src_dao as &dyn ChatHistoryReader, dst_dao as &dyn ChatHistoryReader] {
+        assert_eq!(source_ids(&dao.search_messages(&daos.ds_uuid, "HELLO", None, None, 3)?), vec![10, 9, 8]);
+        assert_eq!(source_ids(&dao.search_messages(&daos.ds_uuid, "there, 1", None, None, 100)?), vec![10, 1]);
+        assert_eq!(source_ids(&dao.search_messages(&daos.ds_uuid, "0% o", None, None, 100)?), vec![5]);
+        assert_eq!(source_ids(&dao.search_messages(&daos.ds_uuid, "_", None, None, 100)?), Vec::<i64>::new());
+        assert_eq!(source_ids(&dao.search_messages(&daos.ds_uuid, "no such text", None, None, 100)?), Vec::<i64>::new());
+    }
+
+    // Same data in both DAOs, so their hits have the same timestamps
+    let daos_list = [("src", src_dao as &dyn ChatHistoryReader), ("dst", dst_dao as &dyn ChatHistoryReader)];
+    let (groups, token) = search_all(daos_list, "hello", None, None, 3)?;
+    assert_eq!(groups.iter().map(|g| (g.key.as_str(), &g.ds_uuid, source_ids(&g.hits))).collect_vec(),
+               vec![("src", &daos.ds_uuid, vec![10, 9]), ("dst", &daos.ds_uuid, vec![10])]);
+    let (groups, _) = search_all(daos_list, "hello", None, token.as_deref(), 3)?;
+    assert_eq!(groups.iter().map(|g| (g.key.as_str(), source_ids(&g.hits))).collect_vec(),
+               vec![("src", vec![8]), ("dst", vec![9, 8])]);
+
+    let (groups, token) = search_all(daos_list, "Sale", None, None, 100)?;
+    assert_eq!(groups.iter().map(|g| (g.key.as_str(), source_ids(&g.hits))).collect_vec(),
+               vec![("src", vec![5]), ("dst", vec![5])]);
+    assert_eq!(token, None);
+
+    // Paging through everything yields the same hits as a single page, in the same order
+    let flatten = |groups: Vec<SearchResultGroup>| groups.into_iter()
+        .flat_map(|g| g.hits.into_iter().map(move |h| (g.key.clone(), h.message.source_id_option.unwrap())))
+        .collect_vec();
+    let (all_groups, _) = search_all(daos_list, "hello", None, None, 100)?;
+    let all_hits = flatten(all_groups).into_iter()
+        .sorted_by_key(|(key, id)| (cmp::Reverse(*id), key != "src"))
+        .collect_vec();
+    assert_eq!(all_hits.len(), 18);
+    for limit in [1, 2, 3] {
+        let mut hits = vec![];
+        let mut token = None;
+        loop {
+            let (groups, next_token) = search_all(daos_list, "hello", None, token.as_deref(), limit)?;
+            let page = flatten(groups);
+            assert!(!page.is_empty() && page.len() <= limit);
+            hits.extend(page.into_iter().sorted_by_key(|(key, id)| (cmp::Reverse(*id), key != "src")));
+            token = next_token;
+            if token.is_none() { break; }
+        }
+        assert_eq!(hits, all_hits);
+    }
+
+    assert_eq!(search_all(daos_list, "no such text", None, None, 100)?, (vec![], None));
+    assert!(search_all(daos_list, " ", None, None, 100).is_err());
+    let (_, chats_token) = chats_page(src_dao.chats(&daos.ds_uuid)?, None, 1)?;
+    assert!(search_all(daos_list, "hello", None, chats_token.as_deref(), 1).is_err());
+    let (_, token) = search_all(daos_list, "hello", None, None, 1)?;
+    assert!(search_all([("dst", dst_dao as &dyn ChatHistoryReader)], "hello", None, token.as_deref(), 1).is_err());
+
+    // Filtering by sender
+    assert_eq!(source_ids(&dst_dao.search_messages(&daos.ds_uuid, "hello", Some(&[1]), None, 2)?), vec![10, 9]);
+    assert_eq!(source_ids(&dst_dao.search_messages(&daos.ds_uuid, "hello", Some(&[2]), None, 100)?), Vec::<i64>::new());
+    let person = AliasGroup::from([(daos.ds_uuid.clone(), vec![1])]);
+    let (groups, _) = search_all([("dst", dst_dao as &dyn ChatHistoryReader)], "Sale", Some(&person), None, 100)?;
+    assert_eq!(groups.iter().map(|g| (g.key.as_str(), source_ids(&g.hits))).collect_vec(), vec![("dst", vec![5])]);
+    let stranger = AliasGroup::from([(PbUuid::random(), vec![1])]);
+    assert_eq!(search_all([("dst", dst_dao as &dyn ChatHistoryReader)], "Sale", Some(&stranger), None, 100)?, (vec![], None));
+
+    Ok(())
+}
+
+#[test]
+fn user_aliases() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+    let other_ds_uuid = PbUuid::random();
+
+    let old_id = UserId(777777777);
+    let new_id = UserId(112233);
+    let alias = UserAlias { ds_uuid: ds_uuid.clone(), user_id: *old_id, other_ds_uuid: other_ds_uuid.clone(), other_user_id: 5 };
+    let reversed = UserAlias { ds_uuid: other_ds_uuid.clone(), user_id: 5, other_ds_uuid: ds_uuid.clone(), other_user_id: *old_id };
+
+    assert_eq!(dao.user_aliases()?, vec![]);
+    dao.insert_user_alias(&alias)?;
+    dao.insert_user_alias(&alias)?;
+    assert_eq!(dao.user_aliases()?, vec![alias.clone()]);
+
+    // Unknown user, unknown dataset and aliasing within a dataset are rejected
+    assert!(dao.insert_user_alias(&UserAlias { user_id: 123456789, ..alias.clone() }).is_err());
+    assert!(dao.insert_user_alias(&reversed).is_err());
+    assert!(dao.insert_user_alias(&UserAlias { other_ds_uuid: ds_uuid.clone(), ..alias.clone() }).is_err());
+
+    // Alias follows user ID change
+    let old_user = dao.users(&ds_uuid)?.into_iter().find(|u| u.id() == old_id).unwrap();
+    dao.update_user(old_id, User { id: *new_id, ..old_user })?;
+    let alias = UserAlias { user_id: *new_id, ..alias };
+    assert_eq!(dao.user_aliases()?, vec![alias.clone()]);
+
+    // Deletion ignores direction
+    let reversed = UserAlias { other_user_id: *new_id, ..reversed };
+    dao.delete_user_alias(&reversed)?;
+    assert_eq!(dao.user_aliases()?, vec![]);
+
+    // Aliases of a deleted dataset are gone as well
+    dao.insert_user_alias(&alias)?;
+    dao.delete_dataset(ds_uuid)?;
+    assert_eq!(dao.user_aliases()?, vec![]);
+
+    Ok(())
+}
+
+#[test]
+fn near_duplicates() -> EmptyRes {
+    const ANNOUNCEMENT: &str = "Dear colleagues, the office will be closed on Friday due to scheduled maintenance \
+                                of the heating system. Please take your laptops home and work remotely that day.";
+    const CHAIN: &str = "Forward this message to ten friends within an hour, or the next seven years \
+                         of your life will be filled with nothing but bad luck and rainy weekends!";
+    let texts = [
+        (1, vec![ANNOUNCEMENT.to_owned(), "Hello".to_owned(), CHAIN.to_owned(), CHAIN.to_owned()]),
+        (2, vec![ANNOUNCEMENT.to_uppercase().replace("DAY.", "day!!!"), "Hi".to_owned()]),
+        (3, vec![ANNOUNCEMENT.replace("that day", "that week"), CHAIN[..40].to_owned()]),
+    ];
+    let users = (1..=2).map(|i| create_user(&ZERO_PB_UUID, i)).collect_vec();
+    let mut idx = 0;
+    let cwms = texts.iter().map(|(chat_id, chat_texts)| {
+        let messages = chat_texts.iter().map(|text| {
+            idx += 1;
+            let mut msg = create_regular_message(idx, 1);
+            msg.text = vec![RichText::make_plain(text.clone())];
+            msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+            msg
+        }).collect_vec();
+        let chat = create_group_chat(&ZERO_PB_UUID, *chat_id, &chat_id.to_string(), vec![1, 2], messages.len());
+        ChatWithMessages { chat, messages }
+    }).collect_vec();
+    let dao_holder = create_dao("test", users, cwms, |_, _| {});
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+
+    for dao in [daos.src_dao.as_ref() as &dyn ChatHistoryReader, &daos.dst_dao as &dyn ChatHistoryReader] {
+        let chats = dao.chats(&daos.ds_uuid)?.into_iter().map(|cwd| (cwd.chat.id, cwd.chat)).collect::<HashMap<_, _>>();
+        // Chats order is DAO-specific
+        let source_ids = |groups: Vec<NearDuplicateGroup>| -> Result<Vec<Vec<(i64, i64)>>> {
+            groups.into_iter().map(|g| g.messages.into_iter().map(|m| {
+                let msg = dao.message_option_by_internal_id(&chats[&m.chat_id], MessageInternalId(m.message_internal_id))?.unwrap();
+                Ok((m.chat_id, msg.source_id_option.unwrap()))
+            }).process_results(|refs| refs.sorted().collect_vec())).try_collect()
+        };
+
+        // Chain message is only repeated within the same chat, its prefix in another chat is too short
+        let groups = find_near_duplicates(dao, &daos.ds_uuid, 100, 0.8)?;
+        assert_eq!(source_ids(groups)?, vec![vec![(1, 1), (2, 5), (3, 7)]]);
+
+        let groups = find_near_duplicates(dao, &daos.ds_uuid, 100, 1.0)?;
+        assert_eq!(source_ids(groups)?, vec![vec![(1, 1), (2, 5)]]);
+
+        let groups = find_near_duplicates(dao, &daos.ds_uuid, 30, 0.8)?;
+        assert_eq!(source_ids(groups)?, vec![vec![(1, 1), (2, 5), (3, 7)]]);
+
+        let groups = find_near_duplicates(dao, &daos.ds_uuid, 1000, 0.8)?;
+        assert_eq!(source_ids(groups)?, Vec::<Vec<(i64, i64)>>::new());
+
+        assert!(find_near_duplicates(dao, &daos.ds_uuid, 0, 0.8).is_err());
+        assert!(find_near_duplicates(dao, &daos.ds_uuid, 100, 0.0).is_err());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn paging() -> EmptyRes {
+    let daos = init();
+    let dao = &daos.dst_dao;
+
+    let cwd = dao.chats(&daos.ds_uuid)?.into_iter().max_by_key(|cwd| cwd.chat.msg_count).unwrap();
+    let chat = &cwd.chat;
+    let all_msgs = dao.first_messages(chat, chat.msg_count as usize)?;
+    assert!(all_msgs.len() > 3);
+
+    for backward in [false, true] {
+        let mut pages = vec![];
+        let mut token = None;
+        loop {
+            let (page, next_token) = messages_page(dao, chat, token.as_deref(), 3, backward, None)?;
+            assert!(page.len() <= 3);
+            pages.push(page);
+            token = next_token;
+            if token.is_none() { break; }
+        }
+        if backward { pages.reverse(); }
+        assert_eq!(pages.concat(), all_msgs);
+    }
+
+    // Token carries its direction
+    let (page, token) = messages_page(dao, chat, None, 2, true, None)?;
+    assert_eq!(page, all_msgs.smart_slice(-2..));
+    let (page, _) = messages_page(dao, chat, token.as_deref(), 1, false, None)?;
+    assert_eq!(page, all_msgs.smart_slice(-3..=-3));
+
+    let cwds = dao.chats(&daos.ds_uuid)?;
+    assert!(cwds.len() > 2);
+    let mut pages = vec![];
+    let mut token = None;
+    loop {
+        let (page, next_token) = chats_page(cwds.clone(), token.as_deref(), 2)?;
+        pages.push(page);
+        token = next_token;
+        if token.is_none() { break; }
+    }
+    assert_eq!(pages.len(), cwds.len().div_ceil(2));
+    assert_eq!(pages.concat(), cwds);
+
+    // Bad tokens
+    let (_, msgs_token) = messages_page(dao, chat, None, 1, false, None)?;
+    let (_, chats_token) = chats_page(cwds.clone(), None, 1)?;
+    assert!(messages_page(dao, chat, chats_token.as_deref(), 1, false, None).is_err());
+    assert!(chats_page(cwds.clone(), msgs_token.as_deref(), 1).is_err());
+    assert!(messages_page(dao, chat, Some("garbage"), 1, false, None).is_err());
+    assert!(chats_page(cwds.clone(), Some(""), 1).is_err());
+
+    // Removing the last returned chat doesn't break the token
+    let (page, token) = chats_page(cwds.clone(), None, 2)?;
+    let (next_page, _) = chats_page(cwds.clone(), token.as_deref(), 1)?;
+    let cwds_without_last = cwds.iter().filter(|cwd| cwd.id() != page[1].id()).cloned().collect_vec();
+    assert_eq!(chats_page(cwds_without_last, token.as_deref(), 1)?.0, next_page);
+
+    Ok(())
+}
+
+#[test]
+fn messages_filtered() -> EmptyRes {
+    let daos = init();
+    let src_dao = daos.src_dao.as_ref();
+    let dst_dao = &daos.dst_dao;
+
+    let users = src_dao.users(&daos.ds_uuid)?;
+    let mut filters = vec![
+        MessageFilter::default(),
+        MessageFilter { service: Some(true), ..Default::default() },
+        MessageFilter { service: Some(false), ..Default::default() },
+        MessageFilter { deleted: Some(true), ..Default::default() },
+        MessageFilter { deleted: Some(false), ..Default::default() },
+        MessageFilter { content_type: Some(ContentType::Photo as i32), ..Default::default() },
+        MessageFilter { content_type: Some(ContentType::Sticker as i32), service: Some(false), ..Default::default() },
+        MessageFilter { content_type: Some(ContentType::File as i32), service: Some(true), ..Default::default() },
+    ];
+    filters.extend(users.iter().map(|u| MessageFilter { from_id: Some(u.id), ..Default::default() }));
+    filters.push(MessageFilter { from_id: Some(users[0].id), content_type: Some(ContentType::Photo as i32), ..Default::default() });
+
+    let mut non_empty_count = 0;
+    for src_cwd in src_dao.chats(&daos.ds_uuid)? {
+        let src_chat = &src_cwd.chat;
+        let dst_cwd = dst_dao.chat_option(&daos.ds_uuid, src_chat.id)?.unwrap();
+        let dst_chat = &dst_cwd.chat;
+        let src_msgs = src_dao.first_messages(src_chat, src_chat.msg_count as usize)?;
+        let dst_msgs = dst_dao.first_messages(dst_chat, dst_chat.msg_count as usize)?;
+
+        for filter in filters.iter() {
+            let expected = src_msgs.iter().filter(|m| filter.matches(m).unwrap()).cloned().collect_vec();
+            if !expected.is_empty() { non_empty_count += 1; }
+
+            let src_filtered = src_dao.messages_filtered(src_chat, filter, None, false, usize::MAX)?;
+            assert_eq!(src_filtered, expected);
+            let dst_filtered = dst_dao.messages_filtered(dst_chat, filter, None, false, i64::MAX as usize)?;
+            assert!(Tup::new(&expected, &daos.src_ds_root, &src_cwd)
+                .practically_equals(&Tup::new(&dst_filtered, &daos.dst_ds_root, &dst_cwd))?, "{filter:?}");
+
+            // Paging through filtered messages from both directions, with anchors
+            for backward in [false, true] {
+                let mut pages = vec![];
+                let mut token = None;
+                loop {
+                    let (page, next_token) = messages_page(dst_dao, dst_chat, token.as_deref(), 2, backward, Some(filter))?;
+                    pages.push(page);
+                    token = next_token;
+                    if token.is_none() { break; }
+                }
+                if backward { pages.reverse(); }
+                assert_eq!(pages.concat(), dst_filtered);
+            }
+
+            // Anchor doesn't have to match the filter
+            if let Some(anchor) = dst_msgs.get(dst_msgs.len() / 2) {
+                let expected_after = dst_filtered.iter()
+                    .filter(|m| m.internal_id > anchor.internal_id).take(3).cloned().collect_vec();
+                assert_eq!(dst_dao.messages_filtered(dst_chat, filter, Some(anchor.internal_id()), false, 3)?,
+                           expected_after);
+                let mut expected_before = dst_filtered.iter()
+                    .filter(|m| m.internal_id < anchor.internal_id).cloned().collect_vec();
+                expected_before.drain(..expected_before.len().saturating_sub(3));
+                assert_eq!(dst_dao.messages_filtered(dst_chat, filter, Some(anchor.internal_id()), true, 3)?,
+                           expected_before);
+            }
+        }
+    }
+    assert!(non_empty_count > filters.len());
+
+    Ok(())
+}
+
+#[test]
+fn message_topics() -> EmptyRes {
+    let src_dir = resource("telegram_2025-06_service-messages");
+    let src_dao = LOADER.with(|loader| loader.parse(&src_dir, &client::NoChooser, None))?;
+    let daos = init_from(src_dao, src_dir, None);
+    let src_dao = daos.src_dao.as_ref();
+    let dst_dao = &daos.dst_dao;
+
+    let src_chat = src_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let dst_chat = dst_dao.chat_option(&daos.ds_uuid, src_chat.id)?.unwrap().chat;
+    let src_msgs = src_dao.first_messages(&src_chat, src_chat.msg_count as usize)?;
+    let dst_msgs = dst_dao.first_messages(&dst_chat, dst_chat.msg_count as usize)?;
+    let topic = MessageTopic { id: 11201, title: "Cats".to_owned() };
+    assert_eq!(src_msgs.iter().filter(|m| m.topic_option.as_ref() == Some(&topic)).count(), 2);
+    assert_eq!(src_msgs.iter().map(|m| &m.topic_option).collect_vec(),
+               dst_msgs.iter().map(|m| &m.topic_option).collect_vec());
+
+    let filter = MessageFilter { topic_id: Some(topic.id), ..Default::default() };
+    let dst_filtered = dst_dao.messages_filtered(&dst_chat, &filter, None, false, usize::MAX)?;
+    assert_eq!(dst_filtered, dst_msgs[..2].to_vec());
+    assert_eq!(src_dao.messages_filtered(&src_chat, &filter, None, false, usize::MAX)?, src_msgs[..2].to_vec());
+
+    let filter = MessageFilter { topic_id: Some(-1), ..Default::default() };
+    assert!(dst_dao.messages_filtered(&dst_chat, &filter, None, false, usize::MAX)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn chat_folders() -> EmptyRes {
+    let daos = init();
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+
+    let chat_ids = dao.chats(&ds_uuid)?.into_iter().map(|cwd| cwd.id())
+        .filter(|&id| id != CHAT_ID_TO_DELETE).collect_vec();
+    assert_eq!(chat_ids.len(), 3);
+    let chat_ref = |id: ChatId| ChatRef { ds_uuid: ds_uuid.clone(), chat_id: *id };
+
+    assert_eq!(dao.chat_folders()?, vec![]);
+
+    let folder_b = dao.insert_chat_folder(ChatFolder {
+        id: -1,
+        name: "B".to_owned(),
+        parent_id_option: None,
+        order: 2,
+        chats: vec![chat_ref(chat_ids[1]), chat_ref(chat_ids[0])],
+    })?;
+    let folder_a = dao.insert_chat_folder(ChatFolder {
+        id: -1,
+        name: "A".to_owned(),
+        parent_id_option: None,
+        order: 1,
+        chats: vec![chat_ref(chat_ids[2])],
+    })?;
+    let folder_b1 = dao.insert_chat_folder(ChatFolder {
+        id: -1,
+        name: "B1".to_owned(),
+        parent_id_option: Some(folder_b.id),
+        order: 1,
+        chats: vec![chat_ref(CHAT_ID_TO_DELETE), chat_ref(chat_ids[2])],
+    })?;
+    assert!(folder_b.id > 0 && folder_a.id > 0 && folder_b1.id > 0);
+    assert_eq!(dao.chat_folders()?, vec![folder_a.clone(), folder_b.clone(), folder_b1.clone()]);
+
+    // Invalid folders
+    let nested = |parent_id: i64| ChatFolder {
+        id: -1,
+        name: "Nested".to_owned(),
+        parent_id_option: Some(parent_id),
+        order: 1,
+        chats: vec![],
+    };
+    assert!(dao.insert_chat_folder(nested(folder_b1.id)).is_err());
+    assert!(dao.insert_chat_folder(nested(12345)).is_err());
+    assert!(dao.insert_chat_folder(ChatFolder { name: "".to_owned(), ..nested(folder_a.id) }).is_err());
+    assert!(dao.insert_chat_folder(ChatFolder { chats: vec![chat_ref(ChatId(12345))], ..nested(folder_a.id) }).is_err());
+    assert!(dao.update_chat_folder(ChatFolder { parent_id_option: Some(folder_a.id), ..folder_b.clone() }).is_err());
+    assert_eq!(dao.chat_folders()?.len(), 3);
+
+    // Reordering
+    let folder_b = dao.update_chat_folder(ChatFolder {
+        order: 0,
+        chats: vec![chat_ref(chat_ids[0]), chat_ref(chat_ids[1])],
+        ..folder_b
+    })?;
+    assert_eq!(dao.chat_folders()?, vec![folder_b.clone(), folder_b1.clone(), folder_a.clone()]);
+
+    // Chat changes are reflected
+    dao.delete_chat(dao.chats(&ds_uuid)?.into_iter().find(|cwd| cwd.id() == CHAT_ID_TO_DELETE).unwrap().chat)?;
+    let chat = dao.chats(&ds_uuid)?.into_iter().find(|cwd| cwd.id() == chat_ids[2]).unwrap().chat;
+    let new_id = ChatId(112233);
+    dao.update_chat(chat_ids[2], Chat { id: *new_id, ..chat })?;
+    let folders = dao.chat_folders()?;
+    assert_eq!(folders[1].chats, vec![chat_ref(new_id)]);
+    assert_eq!(folders[2].chats, vec![chat_ref(new_id)]);
+
+    // Deleting parent folder deletes its subfolders
+    dao.delete_chat_folder(folder_b.id)?;
+    assert_eq!(dao.chat_folders()?.iter().map(|f| f.name.as_str()).collect_vec(), vec!["A"]);
+
+    // Folders are preserved on copy
+    let (mut dao2, _tmp_dir) = create_sqlite_dao();
+    dao2.copy_datasets_from(&dao, &[ds_uuid.clone()])?;
+    dao2.copy_chat_folders_from(&dao)?;
+    assert_eq!(dao2.chat_folders()?.into_iter().map(|f| (f.name, f.chats)).collect_vec(),
+               vec![("A".to_owned(), vec![chat_ref(new_id)])]);
+
+    Ok(())
+}
+
 #[test]
 fn shift_dataset_time() -> EmptyRes {
     let daos = init();
@@ -913,8 +2393,255 @@ fn backups() -> EmptyRes {
     assert!(get_datasets_diff(
         &dst_dao, ds_uuid,
         &loaded_dao, ds_uuid,
-        10)?.is_empty());
+        10, &DiffOptions::default())?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn blocked_users_policy() -> EmptyRes {
+    // Odd messages are from user 1, even ones are from user 2, the last one being a service message
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=6).map(|idx| create_regular_message(idx, 2 - idx % 2)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            match msg.source_id_option {
+                Some(2) => {
+                    let file = create_random_file(&ds_root.0);
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.contents = vec![content!(File {
+                        path_option: Some(ds_root.to_relative(&file).unwrap()),
+                        file_name_option: None,
+                        mime_type_option: None,
+                        thumbnail_path_option: None,
+                    })];
+                }
+                Some(6) => {
+                    msg.typed = Some(message_service!(message_service::SealedValueOptional::GroupEditTitle(
+                        MessageServiceGroupEditTitle { title: "New title".to_owned() }
+                    )));
+                }
+                _ => {}
+            }
+            msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+    let ds_root = daos.dst_ds_root;
+    let chat = dao.chats(&ds_uuid)?.remove(0).chat;
+
+    let msgs_by_idx = |dao: &SqliteDao| -> Result<HashMap<i64, Message>> {
+        Ok(dao.first_messages(&chat, usize::MAX)?.into_iter()
+            .map(|m| (m.source_id_option.unwrap(), m))
+            .collect())
+    };
+    let old_msgs = msgs_by_idx(&dao)?;
+    let file2 = old_msgs[&2].files(&ds_root).remove(0);
+    let policy = |action: BlockedUserAction| BlockedUsersPolicy {
+        user_ids: vec![2],
+        action: Some(action as i32),
+    };
+
+    assert_eq!(dao.blocked_users_policy(&ds_uuid)?, BlockedUsersPolicy::default());
+    assert_eq!(dao.apply_blocked_users_policy(&ds_uuid)?, 0);
+
+    dao.update_blocked_users_policy(&ds_uuid, &policy(BlockedUserAction::Import))?;
+    assert_eq!(dao.blocked_users_policy(&ds_uuid)?, policy(BlockedUserAction::Import));
+    assert_eq!(dao.apply_blocked_users_policy(&ds_uuid)?, 0);
+
+    // Tombstones, service message is kept
+    dao.update_blocked_users_policy(&ds_uuid, &policy(BlockedUserAction::Tombstone))?;
+    assert_eq!(dao.apply_blocked_users_policy(&ds_uuid)?, 2);
+    assert_eq!(dao.apply_blocked_users_policy(&ds_uuid)?, 0);
+    let new_msgs = msgs_by_idx(&dao)?;
+    assert_eq!(new_msgs.len(), 6);
+    for idx in [2, 4] {
+        let msg = &new_msgs[&idx];
+        assert!(msg.text.is_empty());
+        assert_eq!(msg.searchable_string, "");
+        let message::Typed::Regular(mr) = msg.typed() else { unreachable!() };
+        assert!(mr.is_deleted);
+        assert!(mr.contents.is_empty());
+    }
+    for idx in [1, 3, 5, 6] {
+        assert_eq!(new_msgs[&idx], old_msgs[&idx]);
+    }
+    assert!(!file2.exists());
+
+    // Removal
+    dao.update_blocked_users_policy(&ds_uuid, &policy(BlockedUserAction::Skip))?;
+    assert_eq!(dao.apply_blocked_users_policy(&ds_uuid)?, 3);
+    let new_msgs = msgs_by_idx(&dao)?;
+    assert_eq!(new_msgs.keys().copied().sorted().collect_vec(), vec![1, 3, 5]);
+    assert_eq!(dao.chat_option(&ds_uuid, chat.id)?.unwrap().chat.msg_count, 3);
+
+    Ok(())
+}
+
+#[test]
+fn remote_media_root() -> EmptyRes {
+    let dao_holder = create_simple_dao(false, "test", vec![create_regular_message(1, 1)], 1, &|_, _, _| {});
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+    let base_url = "https://example.com/archive";
+
+    assert_eq!(dao.remote_media_root(&ds_uuid)?, None);
+    assert!(dao.update_remote_media_root(&ds_uuid, Some("/mnt/archive")).is_err());
+    assert!(dao.update_remote_media_root(&PbUuid::random(), Some(base_url)).is_err());
+
+    dao.update_remote_media_root(&ds_uuid, Some(base_url))?;
+    assert_eq!(dao.remote_media_root(&ds_uuid)?, Some(base_url.to_owned()));
+
+    // Media is fetched remotely, mock client responds with a requested URL
+    let http_client = MockHttpClient::new();
+    let tmp_dir = TmpDir::new();
+    let cache = RemoteMediaCache::new(tmp_dir.path.join("cache"), 1024, 1024);
+    let url = "https://example.com/archive/chats/a.jpg";
+    assert_eq!(read_media(&dao, &ds_uuid, "chats/a.jpg", 8, 100, &http_client, &cache)?,
+               (url.as_bytes()[8..].to_vec(), url.len() as u64));
+    assert_eq!(http_client.calls_copy(), vec![url.to_owned()]);
+
+    // Copied along with a dataset
+    let (dao2, _dao2_tmp_dir) = create_sqlite_dao();
+    dao2.copy_datasets_from(&dao, &[ds_uuid.clone()])?;
+    assert_eq!(dao2.remote_media_root(&ds_uuid)?, Some(base_url.to_owned()));
+
+    dao.update_remote_media_root(&ds_uuid, None)?;
+    assert_eq!(dao.remote_media_root(&ds_uuid)?, None);
+    Ok(())
+}
+
+#[test]
+fn mentions() -> EmptyRes {
+    let mention = |user_id_option: Option<i64>, handle: &str|
+        RichText::make_mention(format!("@{handle}"), user_id_option, Some(handle.to_owned()));
+    let mut msgs = (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    msgs[0].text.push(mention(Some(2), "u2"));
+    msgs[1].text.push(mention(None, "stranger"));
+    msgs[2].text.push(RichText::make_hashtag("#tag".to_owned(), false));
+    msgs[2].text.push(RichText::make_hashtag("$USD".to_owned(), true));
+    for m in msgs.iter_mut() {
+        m.searchable_string = make_searchable_string(&m.text, m.typed());
+    }
+    let expected_texts = msgs.iter().map(|m| m.text.clone()).collect_vec();
+
+    let dao_holder = create_simple_dao(false, "test", msgs, 2, &|_, _, _| {});
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let ds_uuid = daos.ds_uuid.clone();
+    let chat = dao.chats(&ds_uuid)?.remove(0).chat;
+    let dst_msgs = dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(dst_msgs.into_iter().map(|m| m.text).collect_vec(), expected_texts);
+
+    let mentioning = |dao: &SqliteDao, user_id: i64| -> Result<Vec<Option<i64>>> {
+        let filter = MessageFilter { mentioned_user_id: Some(user_id), ..Default::default() };
+        Ok(dao.messages_filtered(&chat, &filter, None, false, i64::MAX as usize)?
+            .into_iter().map(|m| m.source_id_option).collect_vec())
+    };
+    assert_eq!(mentioning(&dao, 2)?, vec![Some(1)]);
+    assert_eq!(mentioning(&dao, 1)?, vec![]);
+
+    // Mentions follow user ID change
+    let user = dao.users(&ds_uuid)?.into_iter().find(|u| u.id == 2).unwrap();
+    dao.update_user(UserId(2), User { id: 20, ..user })?;
+    assert_eq!(mentioning(&dao, 2)?, vec![]);
+    assert_eq!(mentioning(&dao, 20)?, vec![Some(1)]);
+    Ok(())
+}
 
+#[test]
+fn custom_emojis() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            // First two messages share the same emoji image, last one has none
+            let idx = msg.source_id_option.unwrap();
+            let path_option = if idx < 3 {
+                let file = ds_root.to_absolute("emojis/party.webp");
+                if !file.exists() {
+                    fs::create_dir_all(file.parent().unwrap()).unwrap();
+                    fs::write(&file, b"party").unwrap();
+                }
+                Some(ds_root.to_relative(&file).unwrap())
+            } else {
+                None
+            };
+            msg.text.push(RichText::make_custom_emoji("🎉".to_owned(), Some("123".to_owned()), path_option));
+            msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+        });
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let mut dao = daos.dst_dao;
+    let chat = dao.chats(&daos.ds_uuid)?.remove(0).chat;
+
+    let src_files = dataset_files(daos.src_dao.as_ref(), &daos.ds_uuid);
+    let dst_files = dataset_files(&dao, &daos.ds_uuid);
+    assert_eq!(src_files.len(), 2);
+    assert_files(&src_files, &dst_files);
+
+    let msgs = dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(msgs[0].text.last(), msgs[1].text.last());
+    let emoji = coerce_enum!(msgs[0].text.last().unwrap().val.as_ref(),
+                             Some(rich_text_element::Val::CustomEmoji(v)) => v);
+    assert_eq!(emoji.emoji_id_option.as_deref(), Some("123"));
+    assert_ne!(emoji.path_option, None);
+    assert_eq!(msgs[2].files_relative(), Vec::<&str>::new());
+
+    // Image is only removed with the last message using it
+    dao.redact_messages(&chat, &[msgs[0].internal_id()])?;
+    assert!(dst_files[0].exists());
+    dao.redact_messages(&chat, &[msgs[1].internal_id()])?;
+    assert!(!dst_files[0].exists());
+    Ok(())
+}
+
+#[test]
+fn quote_references() -> EmptyRes {
+    let mut msgs = (1..=2).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    msgs[0].text.push(RichText::make_blockquote("Plain quote".to_owned()));
+    msgs[1].text.push(RichText::make_quote("Hello there, 1!".to_owned(), Some(1)));
+    let expected_texts = msgs.iter().map(|m| m.text.clone()).collect_vec();
+
+    let dao_holder = create_simple_dao(false, "test", msgs, 2, &|_, _, _| {});
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let chat = daos.dst_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let dst_msgs = daos.dst_dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(dst_msgs.into_iter().map(|m| m.text).collect_vec(), expected_texts);
+    Ok(())
+}
+
+#[test]
+fn text_history() -> EmptyRes {
+    let mut msgs = (1..=2).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    msgs[0].text_history = vec![
+        MessageTextVersion { timestamp: msgs[0].timestamp, text: vec![RichText::make_plain("Helo".to_owned())] },
+        MessageTextVersion {
+            timestamp: msgs[0].timestamp + 5,
+            text: vec![
+                RichText::make_plain("Hello, ".to_owned()),
+                RichText::make_mention("@u2".to_owned(), Some(2), Some("u2".to_owned())),
+            ],
+        },
+    ];
+    let expected_msgs = msgs.clone();
+
+    let dao_holder = create_simple_dao(false, "test", msgs, 2, &|_, _, _| {});
+    let daos = init_from(dao_holder.dao, dao_holder.tmp_dir.path.clone(), Some(dao_holder.tmp_dir));
+    let chat = daos.dst_dao.chats(&daos.ds_uuid)?.remove(0).chat;
+    let dst_msgs = daos.dst_dao.first_messages(&chat, usize::MAX)?;
+    assert_eq!(dst_msgs.iter().map(|m| &m.text).collect_vec(), expected_msgs.iter().map(|m| &m.text).collect_vec());
+    assert_eq!(dst_msgs.iter().map(|m| &m.text_history).collect_vec(),
+               expected_msgs.iter().map(|m| &m.text_history).collect_vec());
+
+    // Former texts are not searched for mentions
+    let filter = MessageFilter { mentioned_user_id: Some(2), ..Default::default() };
+    assert_eq!(daos.dst_dao.messages_filtered(&chat, &filter, None, false, i64::MAX as usize)?, vec![]);
     Ok(())
 }
 
@@ -938,7 +2665,7 @@ struct TestDaos {
 
 fn init() -> TestDaos {
     let src_dir = resource(TELEGRAM_DIR);
-    let mut src_dao = LOADER.with(|loader| loader.parse(&src_dir, &client::NoChooser).unwrap());
+    let mut src_dao = LOADER.with(|loader| loader.parse(&src_dir, &client::NoChooser, None).unwrap());
 
     {
         // Amend user with profile pic
@@ -979,3 +2706,4 @@ fn create_sqlite_dao() -> (SqliteDao, TmpDir) {
     let dao = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME)).unwrap();
     (dao, tmp_dir)
 }
+
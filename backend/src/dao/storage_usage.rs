@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use itertools::Itertools;
+use prost::Message as ProstMessage;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "storage_usage_tests.rs"]
+mod tests;
+
+/// Storage usage of chats computed earlier, by database key, dataset and chat ID.
+///
+/// Chat is re-scanned if its message count or last message has changed, or if new audit log entries
+/// might have touched it (e.g. a redaction), otherwise its cached usage is reused.
+#[derive(Default)]
+pub struct StorageUsageCache {
+    chats: HashMap<(String, PbUuid, i64), (ChatSignature, ChatUsage)>,
+}
+
+impl StorageUsageCache {
+    /// Forget everything cached for a database, e.g. once it's closed.
+    pub fn forget(&mut self, dao_key: &str) {
+        self.chats.retain(|(key, _, _), _| key != dao_key);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ChatSignature {
+    msg_count: i32,
+    last_msg_internal_id_option: Option<i64>,
+    audit_entries_count: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChatUsage {
+    rows_bytes: i64,
+    /// Media kind -> (files count, bytes)
+    media: BTreeMap<MediaKind, (i32, i64)>,
+}
+
+/// Disk space taken by a dataset, see [StorageUsage].
+/// Only chats that changed since the previous call with the same cache are re-scanned.
+pub fn storage_usage(dao: &dyn ChatHistoryReader,
+                     dao_key: &str,
+                     ds_uuid: &PbUuid,
+                     cache: &mut StorageUsageCache) -> Result<StorageUsage> {
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let audit_log = dao.audit_log()?;
+    let audit_log = audit_log.iter()
+        .filter(|e| e.ds_uuid_option.as_ref().is_none_or(|uuid| uuid == ds_uuid))
+        .collect_vec();
+
+    let mut rows_bytes: i64 = dao.users(ds_uuid)?.iter().map(|u| u.encoded_len() as i64).sum();
+    let mut media: BTreeMap<MediaKind, (i32, i64)> = BTreeMap::new();
+    let mut chat_ids = HashSet::new();
+    let mut num_rescanned = 0;
+    for cwd in dao.chats(ds_uuid)? {
+        let chat_id = cwd.chat.id;
+        chat_ids.insert(chat_id);
+        let signature = ChatSignature {
+            msg_count: cwd.chat.msg_count,
+            last_msg_internal_id_option: cwd.last_msg_option.as_ref().map(|m| m.internal_id),
+            audit_entries_count: audit_log.iter().filter(|e| e.chat_id_option.is_none_or(|id| id == chat_id)).count(),
+        };
+        let cache_key = (dao_key.to_owned(), ds_uuid.clone(), chat_id);
+        let usage = match cache.chats.get(&cache_key) {
+            Some((cached_signature, usage)) if *cached_signature == signature => usage.clone(),
+            _ => {
+                let usage = chat_usage(dao, &ds_root, &cwd.chat)?;
+                cache.chats.insert(cache_key, (signature, usage.clone()));
+                num_rescanned += 1;
+                usage
+            }
+        };
+        rows_bytes += cwd.chat.encoded_len() as i64 + usage.rows_bytes;
+        for (kind, (files_count, bytes)) in usage.media {
+            let entry = media.entry(kind).or_default();
+            entry.0 += files_count;
+            entry.1 += bytes;
+        }
+    }
+    // Chats that are gone
+    cache.chats.retain(|(key, uuid, chat_id), _| key != dao_key || uuid != ds_uuid || chat_ids.contains(chat_id));
+    log::debug!("Storage usage of dataset {}: re-scanned {num_rescanned} of {} chats", ds_uuid.value, chat_ids.len());
+
+    Ok(StorageUsage {
+        rows_bytes,
+        media: media.into_iter()
+            .map(|(kind, (files_count, bytes))| MediaUsage { kind: kind as i32, files_count, bytes })
+            .collect_vec(),
+    })
+}
+
+fn chat_usage(dao: &dyn ChatHistoryReader, ds_root: &DatasetRoot, chat: &Chat) -> Result<ChatUsage> {
+    let mut usage = ChatUsage::default();
+    let mut seen_paths = HashSet::new();
+    let mut offset = 0;
+    loop {
+        let msgs = dao.scroll_messages(chat, offset, BATCH_SIZE)?;
+        for msg in msgs.iter() {
+            usage.rows_bytes += msg.encoded_len() as i64;
+            let message_regular_pat! { contents, .. } = msg.typed() else { continue };
+            for content in contents {
+                let Some((kind, path, _)) = content_media_file(content) else { continue };
+                if !seen_paths.insert(path.clone()) { continue; }
+                let Ok(meta) = fs::metadata(ds_root.to_absolute(path)) else { continue };
+                let entry = usage.media.entry(kind).or_default();
+                entry.0 += 1;
+                entry.1 += meta.len() as i64;
+            }
+        }
+        if msgs.len() < BATCH_SIZE { break; }
+        offset += BATCH_SIZE;
+    }
+    Ok(usage)
+}
@@ -0,0 +1,86 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn computing_storage_usage() -> EmptyRes {
+    let photo = |ds_root: &DatasetRoot| content!(Photo {
+        path_option: Some(ds_root.to_relative(&create_random_file(&ds_root.0)).unwrap()),
+        width: 0,
+        height: 0,
+        mime_type_option: None,
+        is_one_time: false,
+        lat_str_option: None,
+        lon_str_option: None,
+        ocr_text_option: None,
+    });
+    let mut dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            let contents = match msg.source_id_option {
+                Some(1) => vec![photo(ds_root)],
+                Some(2) => vec![content!(File {
+                    path_option: Some(ds_root.to_relative(&create_random_file(&ds_root.0)).unwrap()),
+                    file_name_option: None,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                })],
+                // File is missing
+                Some(3) => vec![content!(File {
+                    path_option: Some("missing.pdf".to_owned()),
+                    file_name_option: None,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                })],
+                _ => unreachable!(),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = contents;
+        });
+    let ds_uuid = dao_holder.dao.ds_uuid();
+    let ds_root = dao_holder.dao.dataset_root(&ds_uuid)?;
+    let mut cache = StorageUsageCache::default();
+
+    // Random files are 256 bytes long
+    let media_usage = |kind: MediaKind, files_count: i32| MediaUsage { kind: kind as i32, files_count, bytes: files_count as i64 * 256 };
+
+    let usage = storage_usage(dao_holder.dao.as_ref(), "key", &ds_uuid, &mut cache)?;
+    assert_eq!(usage.media, vec![media_usage(MediaKind::Photo, 1), media_usage(MediaKind::File, 1)]);
+    assert!(usage.rows_bytes > 0);
+
+    // Unchanged chat is not re-scanned, so a removed file is still counted
+    let photo_path = match dao_holder.dao.cwms[&ds_uuid][0].messages[0].typed() {
+        message_regular_pat! { contents, .. } => content_media_file(&contents[0]).unwrap().1.clone(),
+        _ => unreachable!(),
+    };
+    fs::remove_file(ds_root.to_absolute(&photo_path))?;
+    assert_eq!(storage_usage(dao_holder.dao.as_ref(), "key", &ds_uuid, &mut cache)?, usage);
+
+    // New message makes chat re-scanned
+    let mut msg = create_regular_message(4, 1);
+    msg.internal_id = 4;
+    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+    mr.contents = vec![photo(&ds_root), photo(&ds_root)];
+    let cwm = &mut dao_holder.dao.cwms.get_mut(&ds_uuid).unwrap()[0];
+    cwm.messages.push(msg);
+    cwm.chat.msg_count += 1;
+
+    let new_usage = storage_usage(dao_holder.dao.as_ref(), "key", &ds_uuid, &mut cache)?;
+    assert_eq!(new_usage.media, vec![media_usage(MediaKind::Photo, 2), media_usage(MediaKind::File, 1)]);
+    assert!(new_usage.rows_bytes > usage.rows_bytes);
+
+    // Forgotten database is computed from scratch
+    cache.forget("key");
+    assert!(cache.chats.is_empty());
+    assert_eq!(storage_usage(dao_holder.dao.as_ref(), "key", &ds_uuid, &mut cache)?, new_usage);
+    Ok(())
+}
@@ -0,0 +1,506 @@
+use std::path::Path;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::export::stickers::{convert_sticker, StickerFormat};
+use crate::prelude::*;
+
+pub mod archive;
+pub mod chat_zip;
+pub mod columnar;
+pub mod docx;
+pub mod flat_sqlite;
+pub mod geo;
+pub mod media;
+pub mod html;
+pub mod site;
+pub mod stickers;
+pub mod transcript;
+
+//
+// Context model
+//
+// This is what templates get to work with, so renaming/removing fields breaks user-supplied templates!
+//
+
+/// Root template context for a single chat.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatContext {
+    pub dataset: DatasetContext,
+    pub chat: ChatInfoContext,
+    /// Chat members, self first.
+    pub members: Vec<UserContext>,
+    /// All chat messages, oldest first.
+    pub messages: Vec<MessageContext>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DatasetContext {
+    pub uuid: String,
+    pub alias: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatInfoContext {
+    pub id: i64,
+    pub name: String,
+    /// `personal` or `private_group`
+    pub tpe: String,
+    /// Source the chat was loaded from, e.g. `telegram` or `whatsapp_db`
+    pub source_type: String,
+    pub msg_count: i32,
+    pub img_href: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserContext {
+    pub id: i64,
+    pub name: String,
+    pub is_myself: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageContext {
+    pub internal_id: i64,
+    pub source_id: Option<i64>,
+    /// Epoch seconds
+    pub timestamp: i64,
+    /// Local date, `YYYY-MM-DD`
+    pub date: String,
+    /// Local time, `HH:MM:SS`
+    pub time: String,
+    pub from_id: i64,
+    pub from_name: String,
+    pub is_myself: bool,
+    /// `regular` or `service`
+    pub kind: &'static str,
+    /// Visible text elements, hidden links are omitted.
+    pub text: Vec<TextElementContext>,
+    /// Text rendered as plain, with spoilers marked, see [PlainTextStyle::Spoilers]
+    pub plain_text: String,
+    /// Thread within a chat this message belongs to, if any
+    pub topic: Option<TopicContext>,
+
+    // Regular messages only
+
+    /// Epoch seconds, refers to deletion time if message is deleted
+    pub edit_timestamp: Option<i64>,
+    pub is_deleted: bool,
+    pub forward_from_name: Option<String>,
+    /// For messages sent in disappearing mode, how long message was set to live
+    pub ephemeral_duration_sec: Option<i32>,
+    /// Human-readable form of `ephemeral_duration_sec`, e.g. `7 days`
+    pub disappears_after: Option<String>,
+    /// Source ID of a message this one replies to
+    pub reply_to_source_id: Option<i64>,
+    pub contents: Vec<ContentContext>,
+
+    // Service messages only
+
+    /// Human-readable description of a service event, e.g. `Invited members: Alice, Bob`
+    pub service_description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TopicContext {
+    pub id: i64,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextElementContext {
+    /// One of `plain`, `bold`, `italic`, `underline`, `strikethrough`, `link`, `prefmt_inline`, `prefmt_block`,
+    /// `blockquote`, `spoiler`, `mention`, `hashtag`, `custom_emoji`
+    pub kind: &'static str,
+    /// For links without text, this is the link itself
+    pub text: String,
+    pub href: Option<String>,
+    /// Only for `prefmt_block`, if known
+    pub language: Option<String>,
+    /// Only for `custom_emoji`, if its image is available
+    pub img_href: Option<String>,
+    /// Only for `blockquote`, if it quotes a message of the same chat
+    pub quoted_source_id: Option<i64>,
+    /// Element rendered as HTML, see [RichText::to_html]
+    pub html: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentContext {
+    /// One of `sticker`, `photo`, `voice_msg`, `audio`, `video_msg`, `video`, `file`, `location`, `poll`,
+    /// `shared_contact`
+    pub kind: String,
+    /// Absent if file is not known or not found
+    pub href: Option<String>,
+    pub thumbnail_href: Option<String>,
+    /// Original file name, if known
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration_sec: Option<i32>,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// Only for `sticker`, name (or ID if name is unknown) of the sticker pack
+    pub sticker_pack: Option<String>,
+    /// Only for `audio` and `voice_msg`, amplitude envelope (0-255 per bucket) if it was precomputed
+    pub waveform: Vec<u8>,
+    /// Only for `poll`
+    pub poll_options: Vec<PollOptionContext>,
+    /// Textual representation of this content, for templates that don't care about specifics
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PollOptionContext {
+    pub text: String,
+    pub voters: Option<i32>,
+    pub is_chosen: bool,
+    pub is_correct: bool,
+}
+
+impl ChatContext {
+    /// Only keep messages within the given topic, if it's specified.
+    pub fn retain_topic(&mut self, topic_id_option: Option<i64>) {
+        if let Some(topic_id) = topic_id_option {
+            self.messages.retain(|m| m.topic.as_ref().is_some_and(|t| t.id == topic_id));
+        }
+    }
+}
+
+/// Output file name stem for a chat export, e.g. `chat_123` or `chat_123_topic_456`.
+pub fn chat_file_stem(chat: &Chat, topic_id_option: Option<i64>) -> String {
+    match topic_id_option {
+        None => format!("chat_{}", chat.id),
+        Some(topic_id) => format!("chat_{}_topic_{topic_id}", chat.id),
+    }
+}
+
+/// Build a template context for the given chat.
+///
+/// `media_href` maps an existing file (absolute path) to a link to be used by the template.
+/// If `sticker_format_option` is set, animated stickers are converted to that format and linked instead.
+pub fn chat_context(dao: &dyn ChatHistoryReader,
+                    cwd: &ChatWithDetails,
+                    sticker_format_option: Option<StickerFormat>,
+                    media_href: &dyn Fn(&Path) -> Result<String>) -> Result<ChatContext> {
+    let chat = &cwd.chat;
+    let ds_uuid = cwd.ds_uuid();
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let myself_id = dao.myself(ds_uuid)?.id;
+    let users: HashMap<i64, User> = dao.users(ds_uuid)?.into_iter().map(|u| (u.id, u)).collect();
+    let user_name = |id: i64| users.get(&id).map(|u| u.pretty_name()).unwrap_or_else(|| UNKNOWN.to_owned());
+
+    let href_option = |rel_path: Option<&String>| media_href_option(&ds_root, rel_path, media_href);
+    let sticker_href_option = |rel_path: Option<&String>| match sticker_format_option {
+        Some(format) => media_href_option(&ds_root, rel_path, &|path| media_href(&convert_sticker(path, format))),
+        None => href_option(rel_path),
+    };
+
+    let mut messages = vec![];
+    let mut offset = 0;
+    loop {
+        let batch = dao.scroll_messages(chat, offset, BATCH_SIZE)?;
+        for msg in batch.iter() {
+            messages.push(message_context(msg, myself_id, &user_name, &href_option, &sticker_href_option)?);
+        }
+        if batch.len() < BATCH_SIZE { break; }
+        offset += BATCH_SIZE;
+    }
+
+    let members = cwd.members.iter()
+        .sorted_by_key(|u| u.id != myself_id)
+        .map(|u| UserContext { id: u.id, name: u.pretty_name(), is_myself: u.id == myself_id })
+        .collect_vec();
+
+    Ok(ChatContext {
+        dataset: dataset_context(dao, ds_uuid)?,
+        chat: chat_info_context(chat, &ds_root, media_href)?,
+        members,
+        messages,
+    })
+}
+
+pub fn dataset_context(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid) -> Result<DatasetContext> {
+    let ds = dao.datasets()?.into_iter().find(|ds| ds.uuid == *ds_uuid)
+        .with_context(|| format!("Dataset {} not found", ds_uuid.value))?;
+    Ok(DatasetContext { uuid: ds.uuid.value, alias: ds.alias })
+}
+
+pub fn chat_info_context(chat: &Chat,
+                         ds_root: &DatasetRoot,
+                         media_href: &dyn Fn(&Path) -> Result<String>) -> Result<ChatInfoContext> {
+    Ok(ChatInfoContext {
+        id: chat.id,
+        name: name_or_unnamed(&chat.name_option),
+        tpe: enum_value_name(ChatType::resolve(chat.tpe)?.as_str_name(), "CHAT_TYPE_"),
+        source_type: enum_value_name(SourceType::resolve(chat.source_type)?.as_str_name(), "SOURCE_TYPE_"),
+        msg_count: chat.msg_count,
+        img_href: media_href_option(ds_root, chat.img_path_option.as_ref(), media_href)?,
+    })
+}
+
+fn media_href_option(ds_root: &DatasetRoot,
+                     rel_path: Option<&String>,
+                     media_href: &dyn Fn(&Path) -> Result<String>) -> Result<Option<String>> {
+    rel_path.map(|p| ds_root.to_absolute(p)).filter(|p| p.exists()).map(|p| media_href(&p)).transpose()
+}
+
+fn message_context(msg: &Message,
+                   myself_id: i64,
+                   user_name: &dyn Fn(i64) -> String,
+                   href_option: &dyn Fn(Option<&String>) -> Result<Option<String>>,
+                   sticker_href_option: &dyn Fn(Option<&String>) -> Result<Option<String>>) -> Result<MessageContext> {
+    let mut ctx = MessageContext {
+        internal_id: msg.internal_id,
+        source_id: msg.source_id_option,
+        timestamp: msg.timestamp,
+        date: local_date_string(msg.timestamp)?,
+        time: local_timestamp_string(msg.timestamp, "%H:%M:%S")?,
+        from_id: msg.from_id,
+        from_name: user_name(msg.from_id),
+        is_myself: msg.from_id == myself_id,
+        kind: "regular",
+        text: msg.text.iter()
+            .map(|rte| text_element_context(rte, sticker_href_option))
+            .flatten_ok()
+            .try_collect()?,
+        plain_text: RichText::to_plain(&msg.text, PlainTextStyle::Spoilers),
+        topic: msg.topic_option.as_ref().map(|t| TopicContext { id: t.id, title: t.title.clone() }),
+        edit_timestamp: None,
+        is_deleted: false,
+        forward_from_name: None,
+        ephemeral_duration_sec: None,
+        disappears_after: None,
+        reply_to_source_id: None,
+        contents: vec![],
+        service_description: None,
+    };
+    match msg.typed() {
+        message::Typed::Regular(mr) => {
+            ctx.edit_timestamp = mr.edit_timestamp_option;
+            ctx.is_deleted = mr.is_deleted;
+            ctx.forward_from_name = mr.forward_origin_option().map(|fo| match fo {
+                ForwardOrigin::User(id) => user_name(id.0),
+                ForwardOrigin::Named(name) => name.to_owned(),
+                ForwardOrigin::Unknown => UNKNOWN.to_owned(),
+            });
+            ctx.ephemeral_duration_sec = mr.ephemeral_duration_sec_option;
+            ctx.disappears_after = mr.ephemeral_duration_sec_option.map(duration_description);
+            ctx.reply_to_source_id = mr.reply_to_message_id_option;
+            ctx.contents = mr.contents.iter()
+                .map(|c| content_context(c, href_option, sticker_href_option))
+                .try_collect()?;
+        }
+        message_service_pat!(ms) => {
+            ctx.kind = "service";
+            ctx.service_description = Some(service_description(ms));
+        }
+        message_service_pat_unreachable!() => unreachable!()
+    }
+    Ok(ctx)
+}
+
+/// Duration in the largest unit it's a whole number of, e.g. `90 seconds` or `7 days`.
+fn duration_description(secs: i32) -> String {
+    const UNITS: [(i32, &str); 4] = [(24 * 60 * 60, "day"), (60 * 60, "hour"), (60, "minute"), (1, "second")];
+    let (unit_secs, unit) = UNITS.into_iter().find(|(unit_secs, _)| secs % unit_secs == 0).unwrap();
+    let n = secs / unit_secs;
+    format!("{n} {unit}{}", if n == 1 { "" } else { "s" })
+}
+
+fn text_element_context(rte: &RichTextElement,
+                        sticker_href_option: &dyn Fn(Option<&String>) -> Result<Option<String>>)
+                        -> Result<Option<TextElementContext>> {
+    use rich_text_element::Val;
+    let (mut img_href, mut quoted_source_id) = (None, None);
+    let (kind, text, href, language) = match rte.val.as_ref().unwrap() {
+        Val::Plain(v) => ("plain", v.text.clone(), None, None),
+        Val::Bold(v) => ("bold", v.text.clone(), None, None),
+        Val::Italic(v) => ("italic", v.text.clone(), None, None),
+        Val::Underline(v) => ("underline", v.text.clone(), None, None),
+        Val::Strikethrough(v) => ("strikethrough", v.text.clone(), None, None),
+        Val::Link(v) if v.hidden => return Ok(None),
+        Val::Link(v) => ("link", v.text_option.clone().unwrap_or_else(|| v.href.clone()), Some(v.href.clone()), None),
+        Val::PrefmtInline(v) => ("prefmt_inline", v.text.clone(), None, None),
+        Val::PrefmtBlock(v) => ("prefmt_block", v.text.clone(), None, v.language_option.clone()),
+        Val::Blockquote(v) => {
+            quoted_source_id = v.quoted_source_id_option;
+            ("blockquote", v.text.clone(), None, None)
+        }
+        Val::Spoiler(v) => ("spoiler", v.text.clone(), None, None),
+        Val::Mention(v) => ("mention", v.text.clone(), None, None),
+        Val::Hashtag(v) => ("hashtag", v.text.clone(), None, None),
+        Val::CustomEmoji(v) => {
+            img_href = sticker_href_option(v.path_option.as_ref())?;
+            ("custom_emoji", v.text.clone(), None, None)
+        }
+    };
+    let html = RichText::to_html(std::slice::from_ref(rte));
+    Ok(Some(TextElementContext { kind, text, href, language, img_href, quoted_source_id, html }))
+}
+
+fn content_context(content: &Content,
+                   href_option: &dyn Fn(Option<&String>) -> Result<Option<String>>,
+                   sticker_href_option: &dyn Fn(Option<&String>) -> Result<Option<String>>) -> Result<ContentContext> {
+    use content::SealedValueOptional::*;
+    let mut ctx = ContentContext {
+        kind: enum_value_name(content.content_type().as_str_name(), "CONTENT_TYPE_"),
+        href: None,
+        thumbnail_href: None,
+        file_name: content.file_name().cloned(),
+        mime_type: None,
+        width: None,
+        height: None,
+        duration_sec: None,
+        title: None,
+        performer: None,
+        sticker_pack: None,
+        waveform: vec![],
+        poll_options: vec![],
+        description: String::new(),
+    };
+    let dimension = |v: i32| if v > 0 { Some(v) } else { None };
+    match content.sealed_value_optional.as_ref().unwrap() {
+        Sticker(v) => {
+            ctx.href = sticker_href_option(v.path_option.as_ref())?;
+            ctx.thumbnail_href = href_option(v.thumbnail_path_option.as_ref())?;
+            ctx.mime_type = v.mime_type_option.clone();
+            (ctx.width, ctx.height) = (dimension(v.width), dimension(v.height));
+            ctx.sticker_pack = v.pack_name_option.clone().or_else(|| v.pack_id_option.clone());
+            ctx.description = format!("Sticker{}", v.emoji_option.as_ref().map(|e| format!(" {e}")).unwrap_or_default());
+            if let Some(ref pack) = ctx.sticker_pack {
+                ctx.description.push_str(&format!(" ({pack})"));
+            }
+        }
+        Photo(v) => {
+            ctx.href = href_option(v.path_option.as_ref())?;
+            ctx.mime_type = v.mime_type_option.clone();
+            (ctx.width, ctx.height) = (dimension(v.width), dimension(v.height));
+            ctx.description = "Photo".to_owned();
+        }
+        VoiceMsg(v) => {
+            ctx.href = href_option(v.path_option.as_ref())?;
+            ctx.mime_type = Some(v.mime_type.clone());
+            ctx.duration_sec = v.duration_sec_option;
+            ctx.waveform = v.waveform_option.clone().unwrap_or_default();
+            ctx.description = "Voice message".to_owned();
+        }
+        Audio(v) => {
+            ctx.href = href_option(v.path_option.as_ref())?;
+            ctx.thumbnail_href = href_option(v.thumbnail_path_option.as_ref())?;
+            ctx.mime_type = Some(v.mime_type.clone());
+            ctx.duration_sec = v.duration_sec_option;
+            ctx.title = v.title_option.clone();
+            ctx.performer = v.performer_option.clone();
+            ctx.waveform = v.waveform_option.clone().unwrap_or_default();
+            ctx.description = "Audio".to_owned();
+        }
+        VideoMsg(v) => {
+            ctx.href = href_option(v.path_option.as_ref())?;
+            ctx.thumbnail_href = href_option(v.thumbnail_path_option.as_ref())?;
+            ctx.mime_type = Some(v.mime_type.clone());
+            (ctx.width, ctx.height) = (dimension(v.width), dimension(v.height));
+            ctx.duration_sec = v.duration_sec_option;
+            ctx.description = "Video message".to_owned();
+        }
+        Video(v) => {
+            ctx.href = href_option(v.path_option.as_ref())?;
+            ctx.thumbnail_href = href_option(v.thumbnail_path_option.as_ref())?;
+            ctx.mime_type = Some(v.mime_type.clone());
+            (ctx.width, ctx.height) = (dimension(v.width), dimension(v.height));
+            ctx.duration_sec = v.duration_sec_option;
+            ctx.title = v.title_option.clone();
+            ctx.performer = v.performer_option.clone();
+            ctx.description = "Video".to_owned();
+        }
+        File(v) => {
+            ctx.href = href_option(v.path_option.as_ref())?;
+            ctx.thumbnail_href = href_option(v.thumbnail_path_option.as_ref())?;
+            ctx.mime_type = v.mime_type_option.clone();
+            ctx.description = "File".to_owned();
+        }
+        Location(v) => {
+            ctx.title = v.title_option.clone();
+            ctx.duration_sec = v.duration_sec_option;
+            let name = [v.title_option.as_deref(), v.address_option.as_deref()].into_iter().flatten().join(", ");
+            ctx.description = if name.is_empty() {
+                format!("Location: {}, {}", v.lat_str, v.lon_str)
+            } else {
+                format!("Location: {name} ({}, {})", v.lat_str, v.lon_str)
+            };
+            if !v.updates.is_empty() {
+                ctx.description.push_str(&format!(", {} update(s)", v.updates.len()));
+            }
+        }
+        Poll(v) => {
+            ctx.title = Some(v.question.clone());
+            ctx.poll_options = v.options.iter().map(|o| PollOptionContext {
+                text: o.text.clone(),
+                voters: o.voters_option,
+                is_chosen: o.is_chosen,
+                is_correct: o.is_correct,
+            }).collect();
+            let kind = if v.is_quiz() { "Quiz" } else { "Poll" };
+            ctx.description = if v.options.is_empty() {
+                format!("{kind}: {}", v.question)
+            } else {
+                let options = v.options.iter().map(|o| match o.voters_option {
+                    Some(voters) => format!("{}: {voters}", o.text),
+                    None => o.text.clone(),
+                }).join(", ");
+                format!("{kind}: {} ({options})", v.question)
+            };
+        }
+        SharedContact(v) => {
+            ctx.href = href_option(v.vcard_path_option.as_ref())?;
+            let name = [v.first_name_option.as_deref(), v.last_name_option.as_deref()].into_iter().flatten().join(" ");
+            ctx.title = Some(name.clone()).filter(|n| !n.is_empty());
+            ctx.description = [Some(name.as_str()).filter(|n| !n.is_empty()), v.phone_number_option.as_deref()]
+                .into_iter().flatten().chain(v.emails.iter().map(|e| e.as_str())).join(", ");
+            ctx.description = format!("Contact: {}", ctx.description);
+        }
+    }
+    Ok(ctx)
+}
+
+fn service_description(ms: &message_service::SealedValueOptional) -> String {
+    use message_service::SealedValueOptional::*;
+    match ms {
+        PhoneCall(v) => {
+            let mut s = "Call".to_owned();
+            if let Some(duration) = v.duration_sec_option {
+                s.push_str(&format!(" ({}:{:02})", duration / 60, duration % 60));
+            }
+            if let Some(ref reason) = v.discard_reason_option {
+                s.push_str(&format!(", {reason}"));
+            }
+            if !v.members.is_empty() {
+                s.push_str(&format!(", members: {}", v.members.join(", ")));
+            }
+            s
+        }
+        SuggestProfilePhoto(_) => "Suggested profile photo".to_owned(),
+        PinMessage(v) => format!("Pinned message #{}", v.message_source_id),
+        ClearHistory(_) => "History cleared".to_owned(),
+        BlockUser(v) => (if v.is_blocked { "User blocked" } else { "User unblocked" }).to_owned(),
+        StatusTextChanged(_) => "Status changed".to_owned(),
+        Notice(_) => "Notice".to_owned(),
+        GroupCreate(v) if v.members.is_empty() => format!("Created group {}", v.title),
+        GroupCreate(v) => format!("Created group {} with members: {}", v.title, v.members.join(", ")),
+        GroupEditTitle(v) => format!("Changed group title to {}", v.title),
+        GroupEditPhoto(_) => "Changed group photo".to_owned(),
+        GroupDeletePhoto(_) => "Deleted group photo".to_owned(),
+        GroupInviteMembers(v) => format!("Invited members: {}", v.members.join(", ")),
+        GroupRemoveMembers(v) => format!("Removed members: {}", v.members.join(", ")),
+        GroupMigrateFrom(v) => format!("Migrated from group {}", v.title),
+        GroupMigrateTo(_) => "Migrated to supergroup".to_owned(),
+    }
+}
+
+/// `CONTENT_TYPE_VOICE_MSG` -> `voice_msg`
+fn enum_value_name(full_name: &str, prefix: &str) -> String {
+    full_name.strip_prefix(prefix).unwrap_or(full_name).to_lowercase()
+}
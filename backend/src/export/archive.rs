@@ -0,0 +1,97 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use zip::AesMode;
+use zip::write::FileOptions;
+
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "archive_tests.rs"]
+mod tests;
+
+/// Run an export of a single file (e.g. a DOCX document) into a private temporary directory and pack the result
+/// into a ZIP archive in the output directory, encrypted with AES-256 using the given password.
+/// Plaintext export never touches the output directory and is removed regardless of the outcome.
+///
+/// Returns the archive path, which is the exported file name with `.zip` appended, e.g. `chat_123.docx.zip`.
+/// Existing archive with the same name is replaced.
+pub fn export_file_encrypted(output_dir: &Path,
+                             password: &str,
+                             export: impl FnOnce(&Path) -> Result<PathBuf>) -> Result<PathBuf> {
+    ensure_password(password)?;
+    let tmp_dir = export_tmp_dir()?;
+    let exported = export(tmp_dir.path())?;
+    let archive_path = output_dir.join(format!("{}.zip", path_file_name(&exported)?));
+    pack_encrypted(&exported, &archive_path, password)?;
+    Ok(archive_path)
+}
+
+/// Same as [export_file_encrypted], but for an export spanning a whole directory (e.g. a static site).
+/// Output directory itself is left untouched, archive is placed next to it as `<output_dir>.zip`.
+pub fn export_dir_encrypted(output_dir: &Path,
+                            password: &str,
+                            export: impl FnOnce(&Path) -> EmptyRes) -> Result<PathBuf> {
+    ensure_password(password)?;
+    let mut archive_name = output_dir.file_name().context("Path has no file name")?.to_os_string();
+    archive_name.push(".zip");
+    let archive_path = output_dir.with_file_name(archive_name);
+
+    let tmp_dir = export_tmp_dir()?;
+    export(tmp_dir.path())?;
+    pack_encrypted(tmp_dir.path(), &archive_path, password)?;
+    Ok(archive_path)
+}
+
+fn ensure_password(password: &str) -> EmptyRes {
+    ensure!(!password.is_empty(), "Archive password must not be empty");
+    Ok(())
+}
+
+fn export_tmp_dir() -> Result<tempfile::TempDir> {
+    Ok(tempfile::Builder::new().prefix("chm-export-").tempdir()?)
+}
+
+/// Pack a single file or a whole directory contents into a ZIP archive, encrypted with AES-256 using the given password.
+/// Such archives can be opened by 7-Zip and most other archivers, no special tooling is needed.
+///
+/// Archive is written to a temporary file first and then moved in place, replacing an existing one.
+/// Source is left as-is.
+fn pack_encrypted(src: &Path, archive_path: &Path, password: &str) -> EmptyRes {
+    ensure!(src.exists(), "Nothing to pack, {} does not exist", src.display());
+    let archive_dir = archive_path.parent().context("Archive path has no parent")?;
+    fs::create_dir_all(archive_dir)?;
+
+    // Entry name -> file, names are relative to the source directory and always use forward slashes
+    let entries: Vec<(String, PathBuf)> = if src.is_dir() {
+        list_all_files(src, true)?.into_iter().sorted().map(|path| -> Result<(String, PathBuf)> {
+            let name = path.strip_prefix(src)?.components()
+                .map(|c| c.as_os_str().to_str().with_context(|| format!("Invalid path {}", path.display())))
+                .collect::<Result<Vec<_>>>()?
+                .join("/");
+            Ok((name, path))
+        }).try_collect()?
+    } else {
+        vec![(path_file_name(src)?.to_owned(), src.to_path_buf())]
+    };
+
+    let options = FileOptions::<'_, ()>::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .with_aes_encryption(AesMode::Aes256, password);
+
+    // Partial archive is removed once dropped
+    let mut tmp_file = tempfile::Builder::new().prefix(".chm-archive-").tempfile_in(archive_dir)?;
+    (|| -> EmptyRes {
+        let mut zip = zip::ZipWriter::new(tmp_file.as_file_mut());
+        for (name, path) in entries.iter() {
+            zip.start_file(name.as_str(), options)?;
+            io::copy(&mut File::open(path)?, &mut zip)?;
+        }
+        zip.finish()?;
+        Ok(())
+    })().with_context(|| format!("Failed to write {}", archive_path.display()))?;
+    tmp_file.persist(archive_path)?;
+    Ok(())
+}
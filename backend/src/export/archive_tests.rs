@@ -0,0 +1,93 @@
+#![allow(unused_imports)]
+
+use std::io::Read;
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn directory() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let output_dir = tmp_dir.path.join("site");
+    fs::create_dir_all(&output_dir)?;
+    fs::write(output_dir.join("index.html"), "<html>Previous export</html>")?;
+
+    let export = |dir: &Path| -> EmptyRes {
+        fs::create_dir_all(dir.join("media/photos"))?;
+        fs::write(dir.join("index.html"), "<html></html>")?;
+        fs::write(dir.join("media/photos/photo.jpg"), b"\xFF\xD8\xFF")?;
+        Ok(())
+    };
+    let archive_path = export_dir_encrypted(&output_dir, "s3cr3t", export)?;
+    assert_eq!(archive_path, tmp_dir.path.join("site.zip"));
+    // Output directory is not touched
+    assert_eq!(list_all_files(&output_dir, true)?, vec![output_dir.join("index.html")]);
+
+    // Archive is replaced on re-export
+    let archive_path = export_dir_encrypted(&output_dir, "s3cr3t", export)?;
+    assert_eq!(list_all_files(&tmp_dir.path, false)?, vec![archive_path.clone()]);
+
+    let mut zip = zip::ZipArchive::new(File::open(&archive_path)?)?;
+    assert_eq!(zip.file_names().sorted().collect_vec(), vec!["index.html", "media/photos/photo.jpg"]);
+    assert!(zip.by_name("index.html").is_err());
+    assert!(zip.by_name_decrypt("index.html", b"wrong").is_err());
+
+    let mut content = vec![];
+    zip.by_name_decrypt("media/photos/photo.jpg", b"s3cr3t")?.read_to_end(&mut content)?;
+    assert_eq!(content, b"\xFF\xD8\xFF");
+    Ok(())
+}
+
+#[test]
+fn single_file() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let mut exported_path = PathBuf::new();
+    let archive_path = export_file_encrypted(&tmp_dir.path, "s3cr3t", |dir| {
+        exported_path = dir.join("chat_1.docx");
+        fs::write(&exported_path, "Not really a DOCX")?;
+        Ok(exported_path.clone())
+    })?;
+    assert_eq!(archive_path, tmp_dir.path.join("chat_1.docx.zip"));
+    // Plaintext is not left behind
+    assert!(!exported_path.exists());
+    assert_eq!(list_all_files(&tmp_dir.path, true)?, vec![archive_path.clone()]);
+
+    let mut zip = zip::ZipArchive::new(File::open(&archive_path)?)?;
+    let mut content = String::new();
+    zip.by_name_decrypt("chat_1.docx", b"s3cr3t")?.read_to_string(&mut content)?;
+    assert_eq!(content, "Not really a DOCX");
+    Ok(())
+}
+
+#[test]
+fn failed_export() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let mut exported_path = PathBuf::new();
+    let res = export_file_encrypted(&tmp_dir.path, "s3cr3t", |dir| {
+        exported_path = dir.join("chat_1.docx");
+        fs::write(&exported_path, "Half-written DOCX")?;
+        bail!("Export failed")
+    });
+    assert!(res.is_err());
+    assert!(!exported_path.exists());
+    assert_eq!(list_all_files(&tmp_dir.path, true)?, Vec::<PathBuf>::new());
+    Ok(())
+}
+
+#[test]
+fn empty_password() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let mut exported = false;
+    let res = export_file_encrypted(&tmp_dir.path, "", |dir| {
+        exported = true;
+        Ok(dir.join("chat_1.docx"))
+    });
+    assert!(res.is_err());
+    // Password is checked before exporting anything
+    assert!(!exported);
+    Ok(())
+}
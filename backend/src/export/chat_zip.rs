@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use zip::write::{FileOptions, ZipWriter};
+
+use crate::dao::ChatHistoryReader;
+use crate::export::{chat_context, chat_file_stem};
+use crate::export::html::{HtmlExporter, percent_encode_path};
+use crate::export::stickers::StickerFormat;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "chat_zip_tests.rs"]
+mod tests;
+
+/// Media files are placed into this archive directory, keeping their paths relative to dataset root.
+const MEDIA_DIR: &str = "media";
+
+/// Write a ZIP archive with a chat HTML page (see [HtmlExporter::export_chat]), template assets and all media files
+/// it references into the given writer, e.g. a response body, as it's being generated.
+///
+/// Writer doesn't need to be seekable: archive is assembled in an anonymous temporary file (as ZIP writer needs to
+/// seek back to fill in entry headers) and then copied into it. Only the page itself is kept in memory.
+/// Media files are stored as-is rather than compressed, as they rarely compress well.
+pub fn write_chat_zip(exporter: &HtmlExporter,
+                      dao: &dyn ChatHistoryReader,
+                      cwd: &ChatWithDetails,
+                      topic_id_option: Option<i64>,
+                      sticker_format_option: Option<StickerFormat>,
+                      mut writer: impl Write) -> EmptyRes {
+    let ds_root = dao.dataset_root(cwd.ds_uuid())?;
+    // Entry name -> file, in order of first reference
+    let media: RefCell<IndexMap<String, PathBuf>> = RefCell::new(IndexMap::new());
+    let media_href = |path: &Path| -> Result<String> {
+        let rel_path = ds_root.to_relative(path)?.replace('\\', "/");
+        let name = format!("{MEDIA_DIR}/{rel_path}");
+        let href = percent_encode_path(&name);
+        media.borrow_mut().entry(name).or_insert_with(|| path.to_path_buf());
+        Ok(href)
+    };
+    let mut ctx = chat_context(dao, cwd, sticker_format_option, &media_href)?;
+    ctx.retain_topic(topic_id_option);
+    let html = exporter.render_chat(&ctx)?;
+    drop(ctx);
+
+    let deflated = FileOptions::<'_, ()>::default().compression_method(zip::CompressionMethod::Deflated);
+    let stored = FileOptions::<'_, ()>::default().compression_method(zip::CompressionMethod::Stored);
+
+    let mut zip = ZipWriter::new(tempfile::tempfile()?);
+    zip.start_file(format!("{}.html", chat_file_stem(&cwd.chat, topic_id_option)), deflated)?;
+    zip.write_all(html.as_bytes())?;
+    drop(html);
+    for (path, name) in exporter.assets() {
+        zip.start_file(name.as_str(), deflated)?;
+        io::copy(&mut File::open(path).with_context(|| format!("Failed to open {}", path.display()))?, &mut zip)?;
+    }
+    for (name, path) in media.into_inner() {
+        zip.start_file(name, stored)?;
+        io::copy(&mut File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?, &mut zip)?;
+    }
+    let mut file = zip.finish()?;
+    file.seek(SeekFrom::Start(0))?;
+    io::copy(&mut file, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
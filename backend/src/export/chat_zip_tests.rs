@@ -0,0 +1,86 @@
+#![allow(unused_imports)]
+
+use std::fs;
+use std::io::{Cursor, Read};
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn chat_zip() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            let path = create_random_file(&ds_root.0);
+            let photo = content!(Photo {
+                path_option: Some(ds_root.to_relative(&path).unwrap()),
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                is_one_time: false,
+                lat_str_option: None,
+                lon_str_option: None,
+                ocr_text_option: None,
+            });
+            let contents = match msg.source_id_option {
+                Some(1) => vec![],
+                Some(2) => vec![photo.clone(), photo],
+                Some(3) => vec![content!(File {
+                    path_option: Some("missing.pdf".to_owned()),
+                    file_name_option: None,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                })],
+                _ => unreachable!(),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = contents;
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+    let photo_path = match dao.first_messages(&cwd.chat, usize::MAX)?[1].typed() {
+        message_regular_pat! { contents, .. } => match contents[0].sealed_value_optional {
+            Some(content::SealedValueOptional::Photo(ref photo)) => photo.path_option.clone().unwrap(),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+
+    let template_dir = TmpDir::new();
+    fs::write(template_dir.path.join("logo.png"), b"not really a PNG")?;
+    let exporter = HtmlExporter::new(Some(&template_dir.path))?;
+
+    // Vec is not seekable
+    let mut bytes: Vec<u8> = vec![];
+    write_chat_zip(&exporter, dao, &cwd, None, None, &mut bytes)?;
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let media_name = format!("media/{photo_path}");
+    assert_eq!(zip.file_names().sorted().collect_vec(), vec![
+        format!("chat_{}.html", cwd.chat.id).as_str(),
+        "logo.png",
+        media_name.as_str(),
+    ]);
+
+    let mut read_entry = |name: &str| -> Result<Vec<u8>> {
+        let mut res = vec![];
+        zip.by_name(name)?.read_to_end(&mut res)?;
+        Ok(res)
+    };
+    let html = String::from_utf8(read_entry(&format!("chat_{}.html", cwd.chat.id))?)?;
+    // Slashes are escaped by templates
+    assert!(html.contains(&format!(r#"src="{}""#, percent_encode_path(&media_name).replace('/', "&#x2F;"))));
+    assert!(!html.contains("file:"));
+    assert_eq!(read_entry(&media_name)?, fs::read(ds_root.to_absolute(&photo_path))?);
+    assert_eq!(read_entry("logo.png")?, b"not really a PNG");
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::collections::{btree_map, BTreeMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{ArrayRef, BooleanArray, Int32Array, Int64Array, RecordBatch, StringArray, TimestampSecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use itertools::Itertools;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::dao::ChatHistoryReader;
+use crate::export::{chat_context, ChatInfoContext, MessageContext};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "columnar_tests.rs"]
+mod tests;
+
+/// Name of a file within each partition directory.
+pub const PARQUET_FILE_NAME: &str = "messages.parquet";
+
+/// Schema of exported messages. Partition values (dataset UUID and year) are only present in directory names,
+/// as per Hive partitioning convention understood by DuckDB, Spark, pandas and others.
+pub fn messages_schema() -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Second, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("chat_id", DataType::Int64, false),
+        Field::new("chat_name", DataType::Utf8, false),
+        // `personal` or `private_group`
+        Field::new("chat_type", DataType::Utf8, false),
+        Field::new("internal_id", DataType::Int64, false),
+        Field::new("source_id", DataType::Int64, true),
+        Field::new("timestamp", timestamp.clone(), false),
+        Field::new("from_id", DataType::Int64, false),
+        Field::new("from_name", DataType::Utf8, false),
+        Field::new("is_myself", DataType::Boolean, false),
+        // `regular` or `service`
+        Field::new("kind", DataType::Utf8, false),
+        // Plain text, see [PlainTextStyle::Spoilers]
+        Field::new("text", DataType::Utf8, false),
+        // Refers to deletion time if message is deleted
+        Field::new("edit_timestamp", timestamp, true),
+        Field::new("is_deleted", DataType::Boolean, false),
+        Field::new("forward_from_name", DataType::Utf8, true),
+        Field::new("reply_to_source_id", DataType::Int64, true),
+        // Set for messages sent in disappearing mode
+        Field::new("ephemeral_duration_sec", DataType::Int32, true),
+        // Kinds of attached contents, e.g. `photo`
+        Field::new("content_kinds", DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))), false),
+        Field::new("service_description", DataType::Utf8, true),
+    ]))
+}
+
+/// Export all messages of a dataset as Parquet files partitioned by dataset and (local) year, i.e.
+/// `<output_dir>/dataset=<uuid>/year=<YYYY>/messages.parquet`, see [messages_schema].
+/// Dataset directory should either be absent or empty.
+///
+/// Returns paths to created files, ordered by year.
+pub fn export_parquet(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid, output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let ds_dir = output_dir.join(format!("dataset={}", ds_uuid.value));
+    if ds_dir.exists() {
+        ensure!(fs::read_dir(&ds_dir)?.next().is_none(), "Directory {} is not empty!", ds_dir.display());
+    }
+
+    let schema = messages_schema();
+    let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let mut writers: BTreeMap<String, (PathBuf, ArrowWriter<File>)> = BTreeMap::new();
+    for cwd in dao.chats(ds_uuid)? {
+        // Media are not exported
+        let ctx = chat_context(dao, &cwd, None, &|_| ok(String::new()))?;
+        for (year, msgs) in ctx.messages.iter().chunk_by(|m| m.date[..4].to_owned()).into_iter() {
+            let (_, writer) = match writers.entry(year) {
+                btree_map::Entry::Occupied(e) => e.into_mut(),
+                btree_map::Entry::Vacant(e) => {
+                    let dir = ds_dir.join(format!("year={}", e.key()));
+                    fs::create_dir_all(&dir)?;
+                    let path = dir.join(PARQUET_FILE_NAME);
+                    let writer = ArrowWriter::try_new(File::create(&path)?, schema.clone(), Some(props.clone()))?;
+                    e.insert((path, writer))
+                }
+            };
+            writer.write(&record_batch(&schema, &ctx.chat, &msgs.collect_vec())?)?;
+        }
+    }
+
+    let mut paths = vec![];
+    for (path, writer) in writers.into_values() {
+        writer.close()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn record_batch(schema: &SchemaRef, chat: &ChatInfoContext, msgs: &[&MessageContext]) -> Result<RecordBatch> {
+    let mut content_kinds = ListBuilder::new(StringBuilder::new());
+    for msg in msgs {
+        content_kinds.append_value(msg.contents.iter().map(|c| Some(&c.kind)));
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(msgs.iter().map(|_| chat.id))),
+        Arc::new(StringArray::from_iter_values(msgs.iter().map(|_| &chat.name))),
+        Arc::new(StringArray::from_iter_values(msgs.iter().map(|_| &chat.tpe))),
+        Arc::new(Int64Array::from_iter_values(msgs.iter().map(|m| m.internal_id))),
+        Arc::new(Int64Array::from_iter(msgs.iter().map(|m| m.source_id))),
+        Arc::new(TimestampSecondArray::from_iter_values(msgs.iter().map(|m| m.timestamp)).with_timezone("UTC")),
+        Arc::new(Int64Array::from_iter_values(msgs.iter().map(|m| m.from_id))),
+        Arc::new(StringArray::from_iter_values(msgs.iter().map(|m| &m.from_name))),
+        Arc::new(BooleanArray::from_iter(msgs.iter().map(|m| Some(m.is_myself)))),
+        Arc::new(StringArray::from_iter_values(msgs.iter().map(|m| m.kind))),
+        Arc::new(StringArray::from_iter_values(msgs.iter().map(|m| &m.plain_text))),
+        Arc::new(TimestampSecondArray::from_iter(msgs.iter().map(|m| m.edit_timestamp)).with_timezone("UTC")),
+        Arc::new(BooleanArray::from_iter(msgs.iter().map(|m| Some(m.is_deleted)))),
+        Arc::new(StringArray::from_iter(msgs.iter().map(|m| m.forward_from_name.as_ref()))),
+        Arc::new(Int64Array::from_iter(msgs.iter().map(|m| m.reply_to_source_id))),
+        Arc::new(Int32Array::from_iter(msgs.iter().map(|m| m.ephemeral_duration_sec))),
+        Arc::new(content_kinds.finish()),
+        Arc::new(StringArray::from_iter(msgs.iter().map(|m| m.service_description.as_ref()))),
+    ];
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
@@ -0,0 +1,76 @@
+#![allow(unused_imports)]
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::Int64Type;
+use itertools::Itertools;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn parquet() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        [1, 2, 1].into_iter().enumerate().map(|(idx, user_id)| create_regular_message(idx + 1, user_id)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            if let message::Typed::Regular(mr) = msg.typed_mut() {
+                *mr = MessageRegular::default();
+            }
+            match msg.source_id_option {
+                // A year before the rest
+                Some(1) => msg.timestamp -= 365 * 24 * 60 * 60,
+                Some(3) => {
+                    let path = create_random_file(&ds_root.0);
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.contents = vec![content!(Photo {
+                        path_option: Some(ds_root.to_relative(&path).unwrap()),
+                        width: 0,
+                        height: 0,
+                        mime_type_option: None,
+                        is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
+                    })];
+                }
+                _ => {}
+            }
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+
+    let output_dir = TmpDir::new();
+    let paths = export_parquet(dao, &ds_uuid, &output_dir.path)?;
+    let ds_dir = output_dir.path.join(format!("dataset={}", ds_uuid.value));
+    assert_eq!(paths, vec![
+        ds_dir.join("year=2018").join(PARQUET_FILE_NAME),
+        ds_dir.join("year=2019").join(PARQUET_FILE_NAME),
+    ]);
+    assert!(export_parquet(dao, &ds_uuid, &output_dir.path).is_err());
+
+    let read = |path: &Path| -> Result<Vec<RecordBatch>> {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+        ok(reader.try_collect()?)
+    };
+
+    let batches = read(&paths[0])?;
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+    assert_eq!(batches[0].schema(), messages_schema());
+
+    let batches = read(&paths[1])?;
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    let source_ids = batch.column_by_name("source_id").unwrap().as_primitive::<Int64Type>().iter().collect_vec();
+    assert_eq!(source_ids, vec![Some(2), Some(3)]);
+    let texts = batch.column_by_name("text").unwrap().as_string::<i32>().iter().collect_vec();
+    assert_eq!(texts, vec![Some("Hello there, 2!"), Some("Hello there, 3!")]);
+    let content_kinds = batch.column_by_name("content_kinds").unwrap().as_list::<i32>();
+    assert_eq!(content_kinds.value(0).len(), 0);
+    assert_eq!(content_kinds.value(1).as_string::<i32>().iter().collect_vec(), vec![Some("photo")]);
+    Ok(())
+}
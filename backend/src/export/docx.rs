@@ -0,0 +1,376 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::dao::ChatHistoryReader;
+use crate::export::{chat_context, chat_file_stem, ContentContext, MessageContext, TextElementContext};
+use crate::export::stickers::StickerFormat;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "docx_tests.rs"]
+mod tests;
+
+/// English Metric Units used by DOCX drawings, 914400 per inch.
+const EMU_PER_PIXEL: u64 = 9_525; // Assuming 96 DPI
+/// Embedded images are scaled down to fit this size.
+const MAX_IMAGE_WIDTH_EMU: u64 = 6 * 914_400;
+const MAX_IMAGE_HEIGHT_EMU: u64 = 4 * 914_400;
+
+/// Content kinds embedded into a document as images, if their format is supported.
+const IMAGE_KINDS: [&str; 2] = ["photo", "sticker"];
+
+const NS_ATTRS: &str = concat!(
+    r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" "#,
+    r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" "#,
+    r#"xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" "#,
+    r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" "#,
+    r#"xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture""#,
+);
+
+const CONTENT_TYPES_XML: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+    r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+    r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+    r#"<Default Extension="png" ContentType="image/png"/>"#,
+    r#"<Default Extension="jpeg" ContentType="image/jpeg"/>"#,
+    r#"<Default Extension="gif" ContentType="image/gif"/>"#,
+    r#"<Override PartName="/word/document.xml" "#,
+    r#"ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>"#,
+    r#"<Override PartName="/word/styles.xml" "#,
+    r#"ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>"#,
+    r#"</Types>"#,
+);
+
+const ROOT_RELS_XML: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" "#,
+    r#"Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" "#,
+    r#"Target="word/document.xml"/>"#,
+    r#"</Relationships>"#,
+);
+
+const STYLES_XML: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">"#,
+    r#"<w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii="Calibri" w:hAnsi="Calibri" w:cs="Calibri"/>"#,
+    r#"<w:sz w:val="22"/></w:rPr></w:rPrDefault>"#,
+    r#"<w:pPrDefault><w:pPr><w:spacing w:after="60"/></w:pPr></w:pPrDefault></w:docDefaults>"#,
+    r#"<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/>"#,
+    r#"<w:pPr><w:spacing w:after="120"/></w:pPr><w:rPr><w:b/><w:sz w:val="36"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/>"#,
+    r#"<w:pPr><w:keepNext/><w:spacing w:before="240" w:after="120"/><w:outlineLvl w:val="1"/></w:pPr>"#,
+    r#"<w:rPr><w:b/><w:sz w:val="26"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Sender"><w:name w:val="Sender"/><w:basedOn w:val="Normal"/>"#,
+    r#"<w:pPr><w:keepNext/><w:spacing w:before="160" w:after="40"/></w:pPr><w:rPr><w:b/></w:rPr></w:style>"#,
+    r#"<w:style w:type="paragraph" w:styleId="Service"><w:name w:val="Service"/><w:basedOn w:val="Normal"/>"#,
+    r#"<w:pPr><w:spacing w:before="160"/></w:pPr><w:rPr><w:i/><w:color w:val="595959"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="character" w:styleId="Time"><w:name w:val="Time"/>"#,
+    r#"<w:rPr><w:color w:val="808080"/><w:sz w:val="18"/></w:rPr></w:style>"#,
+    r#"<w:style w:type="character" w:styleId="Hyperlink"><w:name w:val="Hyperlink"/>"#,
+    r#"<w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr></w:style>"#,
+    r#"</w:styles>"#,
+);
+
+/// Export a chat (or only its single topic) as a Word document into `chat_<id>.docx` file in the given directory,
+/// returns the file path.
+/// Photos and stickers in PNG, JPEG or GIF format are embedded, other media are mentioned by their file names.
+/// Animated stickers can only be embedded if converted, see [StickerFormat].
+pub fn export_docx(dao: &dyn ChatHistoryReader,
+                   cwd: &ChatWithDetails,
+                   topic_id_option: Option<i64>,
+                   sticker_format_option: Option<StickerFormat>,
+                   output_dir: &Path) -> Result<PathBuf> {
+    let mut ctx = chat_context(dao, cwd, sticker_format_option, &|path| ok(path_to_str(path)?.to_owned()))?;
+    ctx.retain_topic(topic_id_option);
+
+    let mut doc = DocxBuilder::default();
+    doc.paragraph(Some("Title"), &run(&ctx.chat.name, ""));
+    let members = ctx.members.iter().map(|m| m.name.as_str()).join(", ");
+    doc.paragraph(None, &run(&format!("Participants: {members}"), ""));
+    if let (Some(first), Some(last)) = (ctx.messages.first(), ctx.messages.last()) {
+        doc.paragraph(None, &run(&format!("Period: {} \u{2013} {}", first.date, last.date), ""));
+    }
+
+    let mut prev_date = "";
+    let mut prev_sender = None;
+    for msg in ctx.messages.iter() {
+        if msg.date != prev_date {
+            doc.paragraph(Some("Heading2"), &run(&msg.date, ""));
+            prev_date = &msg.date;
+            prev_sender = None;
+        }
+        if msg.kind == "service" {
+            let description = msg.service_description.as_deref().unwrap_or_default();
+            let runs = [
+                run(&msg.time, r#"<w:rStyle w:val="Time"/>"#),
+                run(&format!("\t{}: {description}", msg.from_name), ""),
+                if msg.text.is_empty() { String::new() } else { run(": ", "") },
+                text_runs(&mut doc, &msg.text),
+            ].concat();
+            doc.paragraph(Some("Service"), &runs);
+            prev_sender = None;
+            continue;
+        }
+        if prev_sender != Some(msg.from_id) {
+            doc.paragraph(Some("Sender"), &run(&msg.from_name, ""));
+            prev_sender = Some(msg.from_id);
+        }
+        let runs = [
+            run(&msg.time, r#"<w:rStyle w:val="Time"/>"#),
+            run("\t", ""),
+            text_runs(&mut doc, &msg.text),
+            remarks_run(msg),
+        ].concat();
+        doc.paragraph(None, &runs);
+        for content in msg.contents.iter() {
+            content_paragraph(&mut doc, content)?;
+        }
+    }
+
+    fs::create_dir_all(output_dir)?;
+    let path = output_dir.join(format!("{}.docx", chat_file_stem(&cwd.chat, topic_id_option)));
+    doc.write(&path)?;
+    Ok(path)
+}
+
+fn text_runs(doc: &mut DocxBuilder, text: &[TextElementContext]) -> String {
+    text.iter().map(|el| {
+        let props = match el.kind {
+            "bold" => "<w:b/>",
+            "italic" => "<w:i/>",
+            "underline" => r#"<w:u w:val="single"/>"#,
+            "strikethrough" => "<w:strike/>",
+            "prefmt_inline" | "prefmt_block" => r#"<w:rFonts w:ascii="Courier New" w:hAnsi="Courier New"/>"#,
+            "blockquote" => r#"<w:i/><w:color w:val="595959"/>"#,
+            "spoiler" => r#"<w:shd w:val="clear" w:color="auto" w:fill="D9D9D9"/>"#,
+            "mention" | "hashtag" => r#"<w:color w:val="2A6EBB"/>"#,
+            _ => "",
+        };
+        match el.href {
+            Some(ref href) => {
+                let r_id = doc.add_relationship(RelationshipType::Hyperlink, href.clone());
+                format!(r#"<w:hyperlink r:id="{r_id}">{}</w:hyperlink>"#, run(&el.text, r#"<w:rStyle w:val="Hyperlink"/>"#))
+            }
+            None => run(&el.text, props),
+        }
+    }).join("")
+}
+
+fn remarks_run(msg: &MessageContext) -> String {
+    let mut remarks = vec![];
+    if let Some(ref name) = msg.forward_from_name {
+        remarks.push(format!("forwarded from {name}"));
+    }
+    if msg.is_deleted {
+        remarks.push("deleted".to_owned());
+    } else if msg.edit_timestamp.is_some() {
+        remarks.push("edited".to_owned());
+    }
+    if let Some(ref duration) = msg.disappears_after {
+        remarks.push(format!("disappearing after {duration}"));
+    }
+    if remarks.is_empty() { return String::new(); }
+    run(&format!(" ({})", remarks.join(", ")), r#"<w:i/><w:color w:val="808080"/>"#)
+}
+
+fn content_paragraph(doc: &mut DocxBuilder, content: &ContentContext) -> EmptyRes {
+    if IMAGE_KINDS.contains(&content.kind.as_str()) && let Some(ref path) = content.href {
+        let bytes = fs::read(path)?;
+        if let Some((ext, (w, h))) = image_format(&bytes).and_then(|(ext, dims)| {
+            let known_dims = content.width.zip(content.height).map(|(w, h)| (w as u32, h as u32));
+            known_dims.or(dims).map(|dims| (ext, dims))
+        }) {
+            let r_id = doc.add_image(bytes, ext);
+            let (cx, cy) = fit_image(w as u64 * EMU_PER_PIXEL, h as u64 * EMU_PER_PIXEL);
+            let drawing = drawing(&r_id, doc.media.len(), cx, cy);
+            doc.paragraph(None, &format!("<w:r>{drawing}</w:r>"));
+            return Ok(());
+        }
+    }
+    let file_name = content.file_name.as_deref()
+        .or(content.href.as_deref().and_then(|p| Path::new(p).file_name()).and_then(|n| n.to_str()));
+    let text = match file_name {
+        Some(name) => format!("[{}: {name}]", content.description),
+        None => format!("[{}]", content.description),
+    };
+    doc.paragraph(None, &run(&text, "<w:i/>"));
+    Ok(())
+}
+
+/// Scale image size down (keeping aspect ratio) to fit maximum dimensions.
+fn fit_image(cx: u64, cy: u64) -> (u64, u64) {
+    let (cx, cy) = (cx.max(1), cy.max(1));
+    let scale = f64::min(1.0, f64::min(MAX_IMAGE_WIDTH_EMU as f64 / cx as f64, MAX_IMAGE_HEIGHT_EMU as f64 / cy as f64));
+    ((cx as f64 * scale) as u64, (cy as f64 * scale) as u64)
+}
+
+fn drawing(r_id: &str, idx: usize, cx: u64, cy: u64) -> String {
+    format!(concat!(
+        r#"<w:drawing><wp:inline distT="0" distB="0" distL="0" distR="0">"#,
+        r#"<wp:extent cx="{cx}" cy="{cy}"/><wp:docPr id="{idx}" name="Picture {idx}"/>"#,
+        r#"<a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture">"#,
+        r#"<pic:pic><pic:nvPicPr><pic:cNvPr id="{idx}" name="Picture {idx}"/><pic:cNvPicPr/></pic:nvPicPr>"#,
+        r#"<pic:blipFill><a:blip r:embed="{r_id}"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill>"#,
+        r#"<pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm>"#,
+        r#"<a:prstGeom prst="rect"><a:avLst/></a:prstGeom></pic:spPr></pic:pic>"#,
+        r#"</a:graphicData></a:graphic></wp:inline></w:drawing>"#,
+    ), r_id = r_id, idx = idx, cx = cx, cy = cy)
+}
+
+/// Text run with the given run properties, line breaks and tabs are preserved.
+fn run(text: &str, props: &str) -> String {
+    if text.is_empty() { return String::new(); }
+    let mut res = format!("<w:r><w:rPr>{props}</w:rPr>");
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 { res.push_str("<w:br/>"); }
+        for (j, part) in line.split('\t').enumerate() {
+            if j > 0 { res.push_str("<w:tab/>"); }
+            if !part.is_empty() {
+                res.push_str(&format!(r#"<w:t xml:space="preserve">{}</w:t>"#, xml_escape(part)));
+            }
+        }
+    }
+    res.push_str("</w:r>");
+    res
+}
+
+/// Escapes XML special characters, dropping characters not allowed in XML altogether.
+pub(super) fn xml_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\t' | '\n' | '\r' => res.push(c),
+            c if (c as u32) < 0x20 || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Detects PNG, JPEG or GIF image by its header, returning its file extension and dimensions, if found.
+fn image_format(bytes: &[u8]) -> Option<(&'static str, Option<(u32, u32)>)> {
+    let be16 = |i: usize| bytes.get(i..i + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as u32);
+    let be32 = |i: usize| bytes.get(i..i + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let le16 = |i: usize| bytes.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(("png", be32(16).zip(be32(20))))
+    } else if bytes.starts_with(b"GIF8") {
+        Some(("gif", le16(6).zip(le16(8))))
+    } else if bytes.starts_with(&[0xFF, 0xD8]) {
+        // Looking for a start-of-frame segment
+        let mut i = 2;
+        let mut dims = None;
+        while i + 4 <= bytes.len() && bytes[i] == 0xFF {
+            let marker = bytes[i + 1];
+            match marker {
+                0xFF => { i += 1; continue; }
+                0x01 | 0xD0..=0xD7 => { i += 2; continue; }
+                0xC0..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                    dims = be16(i + 7).zip(be16(i + 5));
+                    break;
+                }
+                _ => i += 2 + be16(i + 2)? as usize,
+            }
+        }
+        Some(("jpeg", dims))
+    } else {
+        None
+    }
+}
+
+enum RelationshipType {
+    Image,
+    Hyperlink,
+}
+
+struct Relationship {
+    id: String,
+    tpe: RelationshipType,
+    target: String,
+}
+
+#[derive(Default)]
+struct DocxBuilder {
+    body: String,
+    relationships: Vec<Relationship>,
+    /// File names (within `word/media`) and content of embedded files
+    media: Vec<(String, Vec<u8>)>,
+}
+
+impl DocxBuilder {
+    fn paragraph(&mut self, style_option: Option<&str>, runs: &str) {
+        self.body.push_str("<w:p>");
+        if let Some(style) = style_option {
+            self.body.push_str(&format!(r#"<w:pPr><w:pStyle w:val="{style}"/></w:pPr>"#));
+        }
+        self.body.push_str(runs);
+        self.body.push_str("</w:p>");
+    }
+
+    fn add_relationship(&mut self, tpe: RelationshipType, target: String) -> String {
+        let id = format!("rId{}", self.relationships.len() + 1);
+        self.relationships.push(Relationship { id: id.clone(), tpe, target });
+        id
+    }
+
+    fn add_image(&mut self, bytes: Vec<u8>, ext: &str) -> String {
+        let file_name = format!("image{}.{ext}", self.media.len() + 1);
+        self.media.push((file_name.clone(), bytes));
+        self.add_relationship(RelationshipType::Image, format!("media/{file_name}"))
+    }
+
+    fn write(self, path: &Path) -> EmptyRes {
+        let document_xml = format!(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            r#"<w:document {}><w:body>{}"#,
+            r#"<w:sectPr><w:pgSz w:w="11906" w:h="16838"/>"#,
+            r#"<w:pgMar w:top="1134" w:right="1134" w:bottom="1134" w:left="1134" w:header="0" w:footer="0" w:gutter="0"/>"#,
+            r#"</w:sectPr></w:body></w:document>"#,
+        ), NS_ATTRS, self.body);
+
+        let rels = self.relationships.iter().map(|rel| match rel.tpe {
+            RelationshipType::Image => format!(
+                r#"<Relationship Id="{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="{}"/>"#,
+                rel.id, xml_escape(&rel.target)),
+            RelationshipType::Hyperlink => format!(
+                r#"<Relationship Id="{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="{}" TargetMode="External"/>"#,
+                rel.id, xml_escape(&rel.target)),
+        }).join("");
+        let document_rels_xml = format!(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">{}</Relationships>"#,
+        ), rels);
+
+        let mut file = File::create(path)?;
+        let mut zip = zip::ZipWriter::new(&mut file);
+        let options = zip::write::FileOptions::<'_, ()>::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, content) in [
+            ("[Content_Types].xml", CONTENT_TYPES_XML.as_bytes()),
+            ("_rels/.rels", ROOT_RELS_XML.as_bytes()),
+            ("word/document.xml", document_xml.as_bytes()),
+            ("word/styles.xml", STYLES_XML.as_bytes()),
+            ("word/_rels/document.xml.rels", document_rels_xml.as_bytes()),
+        ] {
+            zip.start_file(name, options)?;
+            zip.write_all(content)?;
+        }
+        for (name, content) in self.media.iter() {
+            // Images are compressed already
+            zip.start_file(format!("word/media/{name}"), options.compression_method(zip::CompressionMethod::Stored))?;
+            zip.write_all(content)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+#![allow(unused_imports)]
+
+use std::fs;
+use std::io::Read;
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+/// PNG signature followed by an IHDR chunk of a 2000x1000 image, enough for format detection.
+const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x07\xd0\0\0\x03\xe8\x08\x02\0\0\0";
+
+#[test]
+fn docx() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            if let message::Typed::Regular(mr) = msg.typed_mut() {
+                *mr = MessageRegular::default();
+            }
+            match msg.source_id_option {
+                Some(1) => {
+                    msg.text = vec![RichText::make_bold("Bold & <brave>".to_owned()),
+                                    RichText::make_plain(" see ".to_owned()),
+                                    RichText::make_link(Some("this".to_owned()), "https://example.com/?a=1&b=2".to_owned(), false)];
+                }
+                Some(2) => {
+                    msg.text = vec![];
+                    let path = ds_root.0.join("image.png");
+                    fs::write(&path, PNG_HEADER).unwrap();
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.contents = vec![content!(Photo {
+                        path_option: Some(ds_root.to_relative(&path).unwrap()),
+                        width: 0,
+                        height: 0,
+                        mime_type_option: None,
+                        is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
+                    })];
+                }
+                Some(3) => {
+                    msg.text = vec![];
+                    let path = create_random_file(&ds_root.0);
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.contents = vec![content!(File {
+                        path_option: Some(ds_root.to_relative(&path).unwrap()),
+                        file_name_option: Some("report.pdf".to_owned()),
+                        mime_type_option: None,
+                        thumbnail_path_option: None,
+                    })];
+                }
+                _ => unreachable!(),
+            }
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+
+    let output_dir = TmpDir::new();
+    let path = export_docx(dao, &cwd, None, None, &output_dir.path)?;
+    assert_eq!(path, output_dir.path.join(format!("chat_{}.docx", cwd.chat.id)));
+
+    let mut file = fs::File::open(&path)?;
+    let mut zip = zip::ZipArchive::new(&mut file)?;
+    assert_eq!(zip.file_names().sorted().collect_vec(), vec![
+        "[Content_Types].xml",
+        "_rels/.rels",
+        "word/_rels/document.xml.rels",
+        "word/document.xml",
+        "word/media/image1.png",
+        "word/styles.xml",
+    ]);
+    let mut read_entry = |name: &str| -> Result<String> {
+        let mut res = String::new();
+        zip.by_name(name)?.read_to_string(&mut res)?;
+        Ok(res)
+    };
+
+    let document = read_entry("word/document.xml")?;
+    assert!(document.contains(">Chat One</w:t>"));
+    assert!(document.contains("<w:b/></w:rPr><w:t xml:space=\"preserve\">Bold &amp; &lt;brave&gt;</w:t>"));
+    assert!(document.contains("<w:hyperlink r:id=\"rId1\">"));
+    assert!(document.contains("<a:blip r:embed=\"rId2\"/>"));
+    // Scaled down to 6 inches wide
+    assert!(document.contains("<wp:extent cx=\"5486400\" cy=\"2743200\"/>"));
+    assert!(document.contains("[File: report.pdf]"));
+    assert_eq!(document.matches("<w:pStyle w:val=\"Sender\"/>").count(), 1);
+
+    let rels = read_entry("word/_rels/document.xml.rels")?;
+    assert!(rels.contains("Target=\"https://example.com/?a=1&amp;b=2\" TargetMode=\"External\""));
+    assert!(rels.contains("Target=\"media/image1.png\""));
+    Ok(())
+}
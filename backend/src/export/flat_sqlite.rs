@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::dao::ChatHistoryReader;
+use crate::export::{chat_context, dataset_context};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "flat_sqlite_tests.rs"]
+mod tests;
+
+/// Schema of the exported database, is a part of its documentation (and gets stored in it as such).
+/// Should only be changed in a backwards-compatible way, bumping [SCHEMA_VERSION].
+pub const SCHEMA: &str = r"
+-- Exported dataset, a single row.
+CREATE TABLE dataset (
+  uuid           TEXT NOT NULL,
+  alias          TEXT NOT NULL,
+  schema_version INTEGER NOT NULL
+);
+
+CREATE TABLE users (
+  id           INTEGER NOT NULL PRIMARY KEY,
+  first_name   TEXT,
+  last_name    TEXT,
+  username     TEXT,
+  phone_number TEXT,
+  -- Name as shown by the app
+  pretty_name  TEXT NOT NULL,
+  -- 1 for the dataset owner, 0 otherwise
+  is_myself    INTEGER NOT NULL
+);
+
+CREATE TABLE chats (
+  id           INTEGER NOT NULL PRIMARY KEY,
+  name         TEXT NOT NULL,
+  -- 'personal' or 'private_group'
+  type         TEXT NOT NULL,
+  -- Source the chat was loaded from, e.g. 'telegram' or 'whatsapp_db'
+  source_type  TEXT NOT NULL,
+  -- For chats combined into another one, ID of that chat
+  main_chat_id INTEGER,
+  msg_count    INTEGER NOT NULL
+);
+
+CREATE TABLE chat_members (
+  chat_id INTEGER NOT NULL REFERENCES chats (id),
+  user_id INTEGER NOT NULL REFERENCES users (id),
+  PRIMARY KEY (chat_id, user_id)
+);
+
+CREATE TABLE messages (
+  chat_id             INTEGER NOT NULL REFERENCES chats (id),
+  -- Unique within a chat, defines message order
+  internal_id         INTEGER NOT NULL,
+  -- ID in the original source, if any
+  source_id           INTEGER,
+  -- Epoch seconds
+  timestamp           INTEGER NOT NULL,
+  -- Local date and time, 'YYYY-MM-DD HH:MM:SS'
+  date_time           TEXT NOT NULL,
+  from_id             INTEGER NOT NULL REFERENCES users (id),
+  from_name           TEXT NOT NULL,
+  -- 'regular' or 'service'
+  kind                TEXT NOT NULL,
+  -- Plain text without formatting, link targets follow link texts in parentheses, spoilers are enclosed in '‖'
+  text                TEXT NOT NULL,
+  -- Regular messages only.
+  -- Epoch seconds, refers to deletion time if message is deleted
+  edit_timestamp      INTEGER,
+  is_deleted          INTEGER NOT NULL,
+  forward_from_name   TEXT,
+  -- Source ID of a message this one replies to
+  reply_to_source_id  INTEGER,
+  -- Set for messages sent in disappearing mode
+  ephemeral_duration_sec INTEGER,
+  -- Service messages only, human-readable description of an event, e.g. 'Invited members: Alice, Bob'
+  service_description TEXT,
+  PRIMARY KEY (chat_id, internal_id)
+);
+
+CREATE INDEX messages_timestamp ON messages (timestamp);
+CREATE INDEX messages_from_id ON messages (from_id);
+
+-- Message attachments, zero or more per message.
+CREATE TABLE media (
+  chat_id             INTEGER NOT NULL,
+  message_internal_id INTEGER NOT NULL,
+  -- Order within a message, starting from 0
+  idx                 INTEGER NOT NULL,
+  -- One of 'sticker', 'photo', 'voice_msg', 'audio', 'video_msg', 'video', 'file', 'location', 'poll',
+  -- 'shared_contact'
+  kind                TEXT NOT NULL,
+  -- Path relative to the dataset root, absent if file is not known or not found
+  path                TEXT,
+  -- Original file name, if known
+  file_name           TEXT,
+  mime_type           TEXT,
+  width               INTEGER,
+  height              INTEGER,
+  duration_sec        INTEGER,
+  title               TEXT,
+  performer           TEXT,
+  -- Textual representation of this content, e.g. 'Photo' or 'Location: 12.34, 56.78'
+  description         TEXT NOT NULL,
+  PRIMARY KEY (chat_id, message_internal_id, idx),
+  FOREIGN KEY (chat_id, message_internal_id) REFERENCES messages (chat_id, internal_id)
+);
+";
+
+pub const SCHEMA_VERSION: i32 = 1;
+
+/// Export the whole dataset into a new standalone SQLite database with a flat [SCHEMA],
+/// meant to be queried directly with SQL or analysis tools rather than loaded back into the app.
+pub fn export_flat_sqlite(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid, db_file: &Path) -> EmptyRes {
+    ensure!(!db_file.exists(), "File {} already exists!", db_file.display());
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let myself_id = dao.myself(ds_uuid)?.id;
+
+    let mut conn = Connection::open(db_file)?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+
+    let dataset = dataset_context(dao, ds_uuid)?;
+    tx.execute("INSERT INTO dataset VALUES (?, ?, ?)", params![dataset.uuid, dataset.alias, SCHEMA_VERSION])?;
+
+    {
+        let mut insert_user = tx.prepare("INSERT INTO users VALUES (?, ?, ?, ?, ?, ?, ?)")?;
+        for user in dao.users(ds_uuid)? {
+            insert_user.execute(params![user.id, user.first_name_option, user.last_name_option, user.username_option,
+                                        user.phone_number_option, user.pretty_name(), user.id == myself_id])?;
+        }
+
+        let mut insert_chat = tx.prepare("INSERT INTO chats VALUES (?, ?, ?, ?, ?, ?)")?;
+        let mut insert_member = tx.prepare("INSERT INTO chat_members VALUES (?, ?)")?;
+        let mut insert_message = tx.prepare("INSERT INTO messages VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
+        let mut insert_media = tx.prepare("INSERT INTO media VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
+        for cwd in dao.chats(ds_uuid)? {
+            let ctx = chat_context(dao, &cwd, None, &|path| ok(ds_root.to_relative(path)?.replace('\\', "/")))?;
+            let chat_id = ctx.chat.id;
+            insert_chat.execute(params![chat_id, ctx.chat.name, ctx.chat.tpe, ctx.chat.source_type,
+                                        cwd.chat.main_chat_id, ctx.chat.msg_count])?;
+            for member in ctx.members.iter() {
+                insert_member.execute(params![chat_id, member.id])?;
+            }
+            for msg in ctx.messages {
+                insert_message.execute(params![
+                    chat_id, msg.internal_id, msg.source_id, msg.timestamp, format!("{} {}", msg.date, msg.time),
+                    msg.from_id, msg.from_name, msg.kind, msg.plain_text, msg.edit_timestamp, msg.is_deleted,
+                    msg.forward_from_name, msg.reply_to_source_id, msg.ephemeral_duration_sec, msg.service_description
+                ])?;
+                for (idx, c) in msg.contents.into_iter().enumerate() {
+                    insert_media.execute(params![
+                        chat_id, msg.internal_id, idx, c.kind, c.href, c.file_name, c.mime_type,
+                        c.width, c.height, c.duration_sec, c.title, c.performer, c.description
+                    ])?;
+                }
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+#![allow(unused_imports)]
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn flat_sqlite() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        [1, 2, 1].into_iter().enumerate().map(|(idx, user_id)| create_regular_message(idx + 1, user_id)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            if let message::Typed::Regular(mr) = msg.typed_mut() {
+                *mr = MessageRegular::default();
+            }
+            if msg.source_id_option == Some(2) {
+                msg.text = vec![RichText::make_bold("Look".to_owned()), RichText::make_plain(" here".to_owned())];
+                let path = create_random_file(&ds_root.0);
+                let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                mr.contents = vec![content!(Photo {
+                    path_option: Some(ds_root.to_relative(&path).unwrap()),
+                    width: 640,
+                    height: 480,
+                    mime_type_option: None,
+                    is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
+                })];
+            }
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+
+    let output_dir = TmpDir::new();
+    let db_file = output_dir.path.join("research.sqlite");
+    export_flat_sqlite(dao, &ds_uuid, &db_file)?;
+    assert!(export_flat_sqlite(dao, &ds_uuid, &db_file).is_err());
+
+    let conn = Connection::open(&db_file)?;
+    let (uuid, version): (String, i32) = conn.query_row("SELECT uuid, schema_version FROM dataset", [], |r| Ok((r.get(0)?, r.get(1)?)))?;
+    assert_eq!(uuid, ds_uuid.value);
+    assert_eq!(version, SCHEMA_VERSION);
+
+    let users: Vec<(i64, String, bool)> = conn.prepare("SELECT id, pretty_name, is_myself FROM users ORDER BY id")?
+        .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+        .try_collect()?;
+    assert_eq!(users, vec![(1, "User 1".to_owned(), true), (2, "User 2".to_owned(), false)]);
+
+    let (chat_name, chat_type, msg_count): (String, String, i32) =
+        conn.query_row("SELECT name, type, msg_count FROM chats WHERE id = ?", [cwd.chat.id],
+                       |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+    assert_eq!((chat_name.as_str(), chat_type.as_str(), msg_count), ("Chat One", "private_group", 3));
+    let member_count: i32 = conn.query_row("SELECT COUNT(*) FROM chat_members", [], |r| r.get(0))?;
+    assert_eq!(member_count, 2);
+
+    let messages: Vec<(Option<i64>, String, String)> =
+        conn.prepare("SELECT source_id, from_name, text FROM messages ORDER BY internal_id")?
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .try_collect()?;
+    assert_eq!(messages, vec![
+        (Some(1), "User 1".to_owned(), "Hello there, 1!".to_owned()),
+        (Some(2), "User 2".to_owned(), "Look here".to_owned()),
+        (Some(3), "User 1".to_owned(), "Hello there, 3!".to_owned()),
+    ]);
+
+    let (source_id, kind, width, path): (i64, String, i32, Option<String>) = conn.query_row(r"
+        SELECT m.source_id, md.kind, md.width, md.path
+        FROM media md
+        INNER JOIN messages m ON m.chat_id = md.chat_id AND m.internal_id = md.message_internal_id
+    ", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))?;
+    assert_eq!((source_id, kind.as_str(), width), (2, "photo", 640));
+    assert!(path.is_some_and(|p| !p.starts_with('/')));
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, SecondsFormat};
+use itertools::Itertools;
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::export::chat_file_stem;
+use crate::export::docx::xml_escape;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "geo_tests.rs"]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeoFormat {
+    Gpx,
+    Kml,
+    GeoJson,
+}
+
+impl GeoFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            GeoFormat::Gpx => "gpx",
+            GeoFormat::Kml => "kml",
+            GeoFormat::GeoJson => "geojson",
+        }
+    }
+}
+
+/// Single location content (or a photo with GPS EXIF), with live location updates (if any) following the initial point.
+struct Track {
+    message_internal_id: i64,
+    from_id: i64,
+    source: GeoPointSource,
+    name: String,
+    /// Location title or address
+    title: Option<String>,
+    description: Option<String>,
+    /// Latitude, longitude and timestamp
+    points: Vec<(String, String, i64)>,
+}
+
+/// Export all locations shared in a chat (as well as photos with GPS EXIF) into `chat_<id>_locations.<gpx|kml|geojson>`
+/// file in the given directory, returns the file path.
+/// Static locations become waypoints (placemarks, points), live locations with known updates become tracks
+/// (paths, line strings).
+pub fn export_locations(dao: &dyn ChatHistoryReader,
+                        cwd: &ChatWithDetails,
+                        format: GeoFormat,
+                        output_dir: &Path) -> Result<PathBuf> {
+    let tracks = collect_tracks(dao, cwd)?;
+    let name = name_or_unnamed(&cwd.chat.name_option);
+    let output = match format {
+        GeoFormat::Gpx => render_gpx(&name, &tracks)?,
+        GeoFormat::Kml => render_kml(&name, &tracks)?,
+        GeoFormat::GeoJson => render_geojson(&tracks)?,
+    };
+
+    fs::create_dir_all(output_dir)?;
+    let path = output_dir.join(format!("{}_locations.{}", chat_file_stem(&cwd.chat, None), format.extension()));
+    fs::write(&path, output)?;
+    Ok(path)
+}
+
+/// Places mentioned in a chat, optionally limited to a single sender.
+/// Live locations are represented by their initial position.
+pub fn geo_points(dao: &dyn ChatHistoryReader, cwd: &ChatWithDetails, from_id_option: Option<i64>) -> Result<Vec<GeoPoint>> {
+    Ok(collect_tracks(dao, cwd)?
+        .into_iter()
+        .filter(|t| from_id_option.is_none_or(|from_id| t.from_id == from_id))
+        .map(|t| {
+            let (lat_str, lon_str, timestamp) = t.points.into_iter().next().expect("Track without points");
+            GeoPoint {
+                message_internal_id: t.message_internal_id,
+                from_id: t.from_id,
+                timestamp,
+                lat_str,
+                lon_str,
+                source: t.source as i32,
+                title_option: t.title.or(t.description),
+            }
+        })
+        .collect())
+}
+
+fn collect_tracks(dao: &dyn ChatHistoryReader, cwd: &ChatWithDetails) -> Result<Vec<Track>> {
+    let users: HashMap<i64, User> = dao.users(cwd.ds_uuid())?.into_iter().map(|u| (u.id, u)).collect();
+    let user_name = |id: i64| users.get(&id).map(|u| u.pretty_name()).unwrap_or_else(|| UNKNOWN.to_owned());
+
+    let mut tracks = vec![];
+    let mut offset = 0;
+    loop {
+        let batch = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+        for msg in batch.iter() {
+            let message_regular_pat! { contents, .. } = msg.typed() else { continue };
+            for content in contents.iter() {
+                match content.sealed_value_optional.as_ref() {
+                    Some(content::SealedValueOptional::Location(loc)) => {
+                        let title = loc.title_option.clone().unwrap_or_else(||
+                            if loc.duration_sec_option.is_some() { "Live location" } else { "Location" }.to_owned());
+                        let points = std::iter::once((loc.lat_str.clone(), loc.lon_str.clone(), msg.timestamp))
+                            .chain(loc.updates.iter().map(|p| (p.lat_str.clone(), p.lon_str.clone(), p.timestamp)))
+                            .collect_vec();
+                        tracks.push(Track {
+                            message_internal_id: msg.internal_id,
+                            from_id: msg.from_id,
+                            source: GeoPointSource::Location,
+                            name: format!("{}: {title}", user_name(msg.from_id)),
+                            title: loc.title_option.clone(),
+                            description: loc.address_option.clone(),
+                            points,
+                        });
+                    }
+                    Some(content::SealedValueOptional::Photo(ContentPhoto {
+                        lat_str_option: Some(lat_str), lon_str_option: Some(lon_str), ..
+                    })) => {
+                        tracks.push(Track {
+                            message_internal_id: msg.internal_id,
+                            from_id: msg.from_id,
+                            source: GeoPointSource::PhotoExif,
+                            name: format!("{}: Photo", user_name(msg.from_id)),
+                            title: None,
+                            description: None,
+                            points: vec![(lat_str.clone(), lon_str.clone(), msg.timestamp)],
+                        });
+                    }
+                    _ => { /* No geodata */ }
+                }
+            }
+        }
+        if batch.len() < BATCH_SIZE { break; }
+        offset += BATCH_SIZE;
+    }
+    Ok(tracks)
+}
+
+fn iso_time(timestamp: i64) -> Result<String> {
+    let dt = DateTime::from_timestamp(timestamp, 0).with_context(|| format!("Invalid timestamp {timestamp}"))?;
+    Ok(dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn render_gpx(name: &str, tracks: &[Track]) -> Result<String> {
+    let mut res = String::new();
+    res.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    res.push('\n');
+    res.push_str(r#"<gpx version="1.1" creator="Chat History Manager" xmlns="http://www.topografix.com/GPX/1/1">"#);
+    res.push('\n');
+    res.push_str(&format!("  <metadata><name>{}</name></metadata>\n", xml_escape(name)));
+    let name_and_desc = |t: &Track| {
+        let mut res = format!("<name>{}</name>", xml_escape(&t.name));
+        if let Some(ref desc) = t.description {
+            res.push_str(&format!("<desc>{}</desc>", xml_escape(desc)));
+        }
+        res
+    };
+    // GPX schema requires all waypoints to go before tracks
+    for t in tracks.iter().filter(|t| t.points.len() == 1) {
+        let (lat, lon, ts) = &t.points[0];
+        res.push_str(&format!("  <wpt lat=\"{lat}\" lon=\"{lon}\"><time>{}</time>{}</wpt>\n",
+                              iso_time(*ts)?, name_and_desc(t)));
+    }
+    for t in tracks.iter().filter(|t| t.points.len() > 1) {
+        res.push_str(&format!("  <trk>{}<trkseg>\n", name_and_desc(t)));
+        for (lat, lon, ts) in t.points.iter() {
+            res.push_str(&format!("    <trkpt lat=\"{lat}\" lon=\"{lon}\"><time>{}</time></trkpt>\n", iso_time(*ts)?));
+        }
+        res.push_str("  </trkseg></trk>\n");
+    }
+    res.push_str("</gpx>\n");
+    Ok(res)
+}
+
+fn render_kml(name: &str, tracks: &[Track]) -> Result<String> {
+    let mut res = String::new();
+    res.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    res.push('\n');
+    res.push_str(r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#);
+    res.push('\n');
+    res.push_str(&format!("<Document><name>{}</name>\n", xml_escape(name)));
+    for t in tracks.iter() {
+        res.push_str(&format!("  <Placemark><name>{}</name>", xml_escape(&t.name)));
+        if let Some(ref desc) = t.description {
+            res.push_str(&format!("<description>{}</description>", xml_escape(desc)));
+        }
+        // KML coordinates go in longitude-latitude order
+        let coordinates = t.points.iter().map(|(lat, lon, _)| format!("{lon},{lat}")).join(" ");
+        let (first_ts, last_ts) = (t.points.first().unwrap().2, t.points.last().unwrap().2);
+        if t.points.len() == 1 {
+            res.push_str(&format!("<TimeStamp><when>{}</when></TimeStamp>", iso_time(first_ts)?));
+            res.push_str(&format!("<Point><coordinates>{coordinates}</coordinates></Point>"));
+        } else {
+            res.push_str(&format!("<TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>",
+                                  iso_time(first_ts)?, iso_time(last_ts)?));
+            res.push_str(&format!("<LineString><coordinates>{coordinates}</coordinates></LineString>"));
+        }
+        res.push_str("</Placemark>\n");
+    }
+    res.push_str("</Document>\n</kml>\n");
+    Ok(res)
+}
+
+fn render_geojson(tracks: &[Track]) -> Result<String> {
+    // GeoJSON coordinates go in longitude-latitude order
+    let coordinates = |lat: &str, lon: &str| -> Result<serde_json::Value> {
+        Ok(serde_json::json!([lon.parse::<f64>()?, lat.parse::<f64>()?]))
+    };
+    let features: Vec<serde_json::Value> = tracks.iter().map(|t| {
+        let geometry = if t.points.len() == 1 {
+            let (lat, lon, _) = &t.points[0];
+            serde_json::json!({ "type": "Point", "coordinates": coordinates(lat, lon)? })
+        } else {
+            let line: Vec<_> = t.points.iter().map(|(lat, lon, _)| coordinates(lat, lon)).try_collect()?;
+            serde_json::json!({ "type": "LineString", "coordinates": line })
+        };
+        let times: Vec<_> = t.points.iter().map(|(_, _, ts)| iso_time(*ts)).try_collect()?;
+        let source = match t.source {
+            GeoPointSource::Location => "location",
+            GeoPointSource::PhotoExif => "photo_exif",
+        };
+        ok(serde_json::json!({
+            "type": "Feature",
+            "geometry": geometry,
+            "properties": {
+                "name": t.name,
+                "description": t.description,
+                "source": source,
+                "message_internal_id": t.message_internal_id,
+                "times": times,
+            },
+        }))
+    }).try_collect()?;
+    let collection = serde_json::json!({ "type": "FeatureCollection", "features": features });
+    Ok(serde_json::to_string_pretty(&collection)?)
+}
@@ -0,0 +1,134 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn gpx_and_kml() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, _, msg| {
+            if let message::Typed::Regular(mr) = msg.typed_mut() {
+                *mr = MessageRegular::default();
+            }
+            msg.timestamp = 1687757170 + msg.source_id_option.unwrap() * 100;
+            let location = |updates: Vec<ContentLocationPoint>| content!(Location {
+                title_option: Some("Bar & Grill".to_owned()),
+                address_option: None,
+                lat_str: "-8.70385650".to_owned(),
+                lon_str: "115.21673666".to_owned(),
+                duration_sec_option: if updates.is_empty() { None } else { Some(900) },
+                updates,
+            });
+            let contents = match msg.source_id_option {
+                Some(1) => vec![location(vec![])],
+                Some(2) => vec![location(vec![ContentLocationPoint {
+                    lat_str: "-8.70412345".to_owned(),
+                    lon_str: "115.21701234".to_owned(),
+                    timestamp: 1687757970,
+                }])],
+                Some(3) => vec![],
+                _ => unreachable!(),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = contents;
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+
+    let output_dir = TmpDir::new();
+    let path = export_locations(dao, &cwd, GeoFormat::Gpx, &output_dir.path)?;
+    assert_eq!(path, output_dir.path.join(format!("chat_{}_locations.gpx", cwd.chat.id)));
+    let gpx = fs::read_to_string(&path)?;
+    assert_eq!(gpx.matches("<wpt ").count(), 1);
+    assert!(gpx.contains(concat!(
+        r#"<wpt lat="-8.70385650" lon="115.21673666"><time>2023-06-26T05:27:50Z</time>"#,
+        r#"<name>User 1: Bar &amp; Grill</name></wpt>"#)));
+    assert_eq!(gpx.matches("<trk>").count(), 1);
+    assert!(gpx.contains(r#"<trkpt lat="-8.70385650" lon="115.21673666"><time>2023-06-26T05:29:30Z</time></trkpt>"#));
+    assert!(gpx.contains(r#"<trkpt lat="-8.70412345" lon="115.21701234"><time>2023-06-26T05:39:30Z</time></trkpt>"#));
+    // Waypoints go first
+    assert!(gpx.find("<wpt ").unwrap() < gpx.find("<trk>").unwrap());
+
+    let path = export_locations(dao, &cwd, GeoFormat::Kml, &output_dir.path)?;
+    assert_eq!(path, output_dir.path.join(format!("chat_{}_locations.kml", cwd.chat.id)));
+    let kml = fs::read_to_string(&path)?;
+    assert_eq!(kml.matches("<Placemark>").count(), 2);
+    assert!(kml.contains("<Point><coordinates>115.21673666,-8.70385650</coordinates></Point>"));
+    assert!(kml.contains(concat!(
+        "<TimeSpan><begin>2023-06-26T05:29:30Z</begin><end>2023-06-26T05:39:30Z</end></TimeSpan>",
+        "<LineString><coordinates>115.21673666,-8.70385650 115.21701234,-8.70412345</coordinates></LineString>")));
+    Ok(())
+}
+
+#[test]
+fn photos_and_geojson() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, idx % 2 + 1)).collect_vec(),
+        2,
+        &|_, _, msg| {
+            msg.timestamp = 1687757170 + msg.source_id_option.unwrap() * 100;
+            let photo = |coordinates: Option<(&str, &str)>| content!(Photo {
+                path_option: None,
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                is_one_time: false,
+                lat_str_option: coordinates.map(|c| c.0.to_owned()),
+                lon_str_option: coordinates.map(|c| c.1.to_owned()),
+                ocr_text_option: None,
+            });
+            let contents = match msg.source_id_option {
+                Some(1) => vec![photo(None)],
+                Some(2) => vec![photo(Some(("51.500000", "-0.125000")))],
+                Some(3) => vec![content!(Location {
+                    title_option: Some("Pub".to_owned()),
+                    address_option: None,
+                    lat_str: "51.51".to_owned(),
+                    lon_str: "-0.13".to_owned(),
+                    duration_sec_option: None,
+                    updates: vec![],
+                })],
+                _ => unreachable!(),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = contents;
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+
+    let points = geo_points(dao, &cwd, None)?;
+    assert_eq!(points.iter().map(|p| (p.from_id, p.source(), p.title_option.clone())).collect_vec(), vec![
+        (1, GeoPointSource::PhotoExif, None),
+        (2, GeoPointSource::Location, Some("Pub".to_owned())),
+    ]);
+    assert_eq!(points[0].lat_str, "51.500000");
+    assert_eq!(points[0].timestamp, 1687757170 + 200);
+    assert_eq!(geo_points(dao, &cwd, Some(2))?.len(), 1);
+
+    let output_dir = TmpDir::new();
+    let path = export_locations(dao, &cwd, GeoFormat::GeoJson, &output_dir.path)?;
+    assert_eq!(path, output_dir.path.join(format!("chat_{}_locations.geojson", cwd.chat.id)));
+    let geojson: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0]["geometry"], serde_json::json!({ "type": "Point", "coordinates": [-0.125, 51.5] }));
+    assert_eq!(features[0]["properties"]["source"], "photo_exif");
+    assert_eq!(features[1]["properties"]["name"], "User 2: Pub");
+    assert_eq!(features[1]["properties"]["times"], serde_json::json!(["2023-06-26T05:31:10Z"]));
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tera::{Context as TeraContext, Tera};
+
+use crate::dao::ChatHistoryReader;
+use crate::export::{chat_context, chat_file_stem, ChatContext};
+use crate::export::stickers::StickerFormat;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "html_tests.rs"]
+mod tests;
+
+/// Template rendering a single chat, gets [ChatContext] as its context.
+pub const CHAT_TEMPLATE: &str = "chat.html";
+/// Static site templates, see [crate::export::site].
+pub const INDEX_TEMPLATE: &str = "index.html";
+pub const GALLERY_TEMPLATE: &str = "gallery.html";
+pub const SEARCH_TEMPLATE: &str = "search.html";
+/// Printable transcript, see [crate::export::transcript].
+pub const TRANSCRIPT_TEMPLATE: &str = "transcript.html";
+
+/// Templates that are used unless overridden by a user-supplied template directory.
+const BUILTIN_TEMPLATES: [(&str, &str); 8] = [
+    ("base.html", include_str!("../../resources/main/export/html/base.html")),
+    (CHAT_TEMPLATE, include_str!("../../resources/main/export/html/chat.html")),
+    ("message.html", include_str!("../../resources/main/export/html/message.html")),
+    ("style.css", include_str!("../../resources/main/export/html/style.css")),
+    (INDEX_TEMPLATE, include_str!("../../resources/main/export/html/index.html")),
+    (GALLERY_TEMPLATE, include_str!("../../resources/main/export/html/gallery.html")),
+    (SEARCH_TEMPLATE, include_str!("../../resources/main/export/html/search.html")),
+    (TRANSCRIPT_TEMPLATE, include_str!("../../resources/main/export/html/transcript.html")),
+];
+
+/// Files with these extensions in a template directory are treated as templates, everything else is a static asset.
+const TEMPLATE_EXTENSIONS: [&str; 4] = ["html", "htm", "css", "js"];
+
+/// Exports chats as HTML pages using [Tera](https://keats.github.io/tera/docs/) templates.
+///
+/// User-supplied template directory may override any of the built-in templates (`base.html`, `chat.html`,
+/// `message.html`, `style.css`, static site and transcript ones) by having a file with the same name, or add new ones
+/// to be included/extended.
+/// Other files there (images, fonts) are copied alongside exported pages as-is.
+pub struct HtmlExporter {
+    tera: Tera,
+    assets: Vec<(PathBuf, String)>,
+    /// Hash of all templates in use, changes whenever rendered output might change
+    fingerprint: String,
+}
+
+impl HtmlExporter {
+    pub fn new(template_dir_option: Option<&Path>) -> Result<Self> {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(BUILTIN_TEMPLATES).context("Failed to load built-in templates")?;
+        let mut hasher = Sha256::new();
+        for (name, content) in BUILTIN_TEMPLATES {
+            hasher.update(name);
+            hasher.update(content);
+        }
+
+        let mut assets = vec![];
+        if let Some(template_dir) = template_dir_option {
+            ensure!(template_dir.is_dir(), "Template directory {} does not exist", template_dir.display());
+            let mut templates = vec![];
+            // Sorted so that fingerprint doesn't depend on directory listing order
+            for path in list_all_files(template_dir, true)?.into_iter().sorted() {
+                let name = path.strip_prefix(template_dir)?.components()
+                    .map(|c| c.as_os_str().to_str().with_context(|| format!("Invalid path {}", path.display())))
+                    .collect::<Result<Vec<_>>>()?
+                    .join("/");
+                let is_template = path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| TEMPLATE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+                if is_template {
+                    hasher.update(&name);
+                    hasher.update(fs::read(&path)?);
+                    templates.push((path, Some(name)));
+                } else {
+                    assets.push((path, name));
+                }
+            }
+            tera.add_template_files(templates)
+                .with_context(|| format!("Failed to load templates from {}", template_dir.display()))?;
+        }
+        Ok(HtmlExporter { tera, assets, fingerprint: format!("{:x}", hasher.finalize()) })
+    }
+
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn render_chat(&self, ctx: &ChatContext) -> Result<String> {
+        self.render(CHAT_TEMPLATE, ctx, false)
+    }
+
+    /// `in_site` is exposed to templates to tell a standalone page from a static site one.
+    pub fn render(&self, template: &str, ctx: &impl Serialize, in_site: bool) -> Result<String> {
+        let mut tera_ctx = TeraContext::from_serialize(ctx)?;
+        tera_ctx.insert("in_site", &in_site);
+        self.tera.render(template, &tera_ctx).with_context(|| format!("Failed to render {template}"))
+    }
+
+    /// Export a chat (or only its single topic) into `chat_<id>.html` file in the given directory,
+    /// returns the file path.
+    /// Media files are referenced where they are, not copied.
+    pub fn export_chat(&self,
+                       dao: &dyn ChatHistoryReader,
+                       cwd: &ChatWithDetails,
+                       topic_id_option: Option<i64>,
+                       sticker_format_option: Option<StickerFormat>,
+                       output_dir: &Path) -> Result<PathBuf> {
+        let mut ctx = chat_context(dao, cwd, sticker_format_option, &|path| file_uri(path))?;
+        ctx.retain_topic(topic_id_option);
+        let html = self.render_chat(&ctx)?;
+        fs::create_dir_all(output_dir)?;
+        self.copy_assets(output_dir)?;
+        let path = output_dir.join(format!("{}.html", chat_file_stem(&cwd.chat, topic_id_option)));
+        fs::write(&path, html)?;
+        Ok(path)
+    }
+
+    /// Non-template files from a template directory along with their names relative to it.
+    pub fn assets(&self) -> &[(PathBuf, String)] {
+        &self.assets
+    }
+
+    pub fn copy_assets(&self, output_dir: &Path) -> EmptyRes {
+        for (src, name) in self.assets.iter() {
+            let dst = output_dir.join(name);
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(src, &dst).with_context(|| format!("Failed to copy {}", src.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Absolute `file://` URI of the given path.
+fn file_uri(path: &Path) -> Result<String> {
+    let path = fs::canonicalize(path)?;
+    let path_str = path_to_str(&path)?.replace('\\', "/");
+    let path_str = path_str.strip_prefix("//?/").unwrap_or(&path_str); // Windows verbatim prefix
+    let prefix = if path_str.starts_with('/') { "file://" } else { "file:///" };
+    Ok(format!("{prefix}{}", percent_encode_path(path_str)))
+}
+
+/// Percent-encode everything but unreserved characters, slashes and colons.
+pub fn percent_encode_path(path_str: &str) -> String {
+    let mut res = String::with_capacity(path_str.len());
+    for b in path_str.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => res.push(b as char),
+            _ => res.push_str(&format!("%{b:02X}")),
+        }
+    }
+    res
+}
@@ -0,0 +1,114 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+fn create_dao_for_export() -> InMemoryDaoHolder {
+    create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|idx| create_regular_message(idx, idx % 2 + 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            match msg.source_id_option {
+                Some(1) => {
+                    msg.text = vec![RichText::make_plain("<script>alert(1)</script> ".to_owned()),
+                                    RichText::make_bold("bold".to_owned())];
+                }
+                Some(2) => {
+                    let path = create_random_file(&ds_root.0);
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.contents = vec![content!(Photo {
+                        path_option: Some(ds_root.to_relative(&path).unwrap()),
+                        width: 0,
+                        height: 0,
+                        mime_type_option: None,
+                        is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
+                    })];
+                }
+                Some(3) => {
+                    msg.text.push(RichText::make_quote("Quoted".to_owned(), Some(1)));
+                    let path = create_random_file(&ds_root.0);
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.contents = vec![content!(VoiceMsg {
+                        path_option: Some(ds_root.to_relative(&path).unwrap()),
+                        file_name_option: None,
+                        mime_type: "audio/ogg".to_owned(),
+                        duration_sec_option: Some(3),
+                        waveform_option: Some(vec![0, 255, 85]),
+                    })];
+                }
+                _ => {}
+            }
+        })
+}
+
+#[test]
+fn default_templates() -> EmptyRes {
+    let dao_holder = create_dao_for_export();
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+    let output_dir = TmpDir::new();
+
+    let exporter = HtmlExporter::new(None)?;
+    let path = exporter.export_chat(dao, &cwd, None, None, &output_dir.path)?;
+    assert_eq!(path, output_dir.path.join(format!("chat_{}.html", cwd.chat.id)));
+
+    let html = fs::read_to_string(&path)?;
+    assert!(html.contains("<title>Chat One</title>"));
+    assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt; <b>bold</b>"));
+    assert!(!html.contains("<script>"));
+    // Tera escapes slashes as well
+    assert!(html.contains(r#"<a href="file:&#x2F;&#x2F;"#));
+    assert!(html.contains("Hello there, 3!"));
+    assert!(html.contains(r##"<blockquote><a class="quote-ref" href="#src-1">Quoted</a></blockquote>"##));
+    assert!(html.contains(".message.myself {"));
+    assert!(html.contains(concat!(
+        r#"<svg class="waveform" viewBox="0 0 9 32" preserveAspectRatio="none">"#,
+        r#"<rect x="0" y="31" width="2" height="1"/><rect x="3" y="1" width="2" height="31"/>"#,
+        r#"<rect x="6" y="21" width="2" height="11"/></svg>"#)));
+
+    // All test messages are sent within the same day
+    assert_eq!(html.matches(r#"class="date-separator""#).count(), 1);
+    Ok(())
+}
+
+#[test]
+fn user_templates() -> EmptyRes {
+    let dao_holder = create_dao_for_export();
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+    let template_dir = TmpDir::new();
+    let output_dir = TmpDir::new();
+
+    fs::write(template_dir.path.join("message.html"),
+              r#"<p class="custom">{{ message.from_name }}: {{ message.text | map(attribute="text") | join(sep="") }}</p>"#)?;
+    fs::write(template_dir.path.join("style.css"), "body { background: url(img/bg.bin); }")?;
+    fs::create_dir(template_dir.path.join("img"))?;
+    create_random_named_file(&template_dir.path.join("img").join("bg.bin"));
+
+    let exporter = HtmlExporter::new(Some(&template_dir.path))?;
+    let path = exporter.export_chat(dao, &cwd, None, None, &output_dir.path)?;
+    let html = fs::read_to_string(&path)?;
+    assert!(html.contains(r#"<p class="custom">User 2: &lt;script&gt;alert(1)&lt;&#x2F;script&gt; bold</p>"#));
+    assert!(html.contains(r#"<p class="custom">User 1: Hello there, 2!</p>"#));
+    assert!(html.contains("body { background: url(img/bg.bin); }"));
+    assert!(html.contains("<title>Chat One</title>"));
+    assert!(files_are_equal(&template_dir.path.join("img").join("bg.bin"), &output_dir.path.join("img").join("bg.bin"))?);
+
+    fs::write(template_dir.path.join("chat.html"), "{% for message in messages %}")?;
+    assert!(HtmlExporter::new(Some(&template_dir.path)).is_err());
+    assert!(HtmlExporter::new(Some(&template_dir.path.join("no-such-dir"))).is_err());
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "media_tests.rs"]
+mod tests;
+
+/// Characters not allowed in file names on at least one of the supported platforms.
+const FORBIDDEN_FILE_NAME_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Copy media files of a chat into the given directory, without exporting messages themselves.
+/// Returns paths of copied files, oldest first.
+///
+/// Only media of given kinds (all if empty) sent within the given time range (inclusive, epoch seconds) are copied.
+/// Files are named `<date>_<time>_<sender>_<original name>`, a numeric suffix is added on collision.
+/// Missing files are skipped, a file referenced by several messages is only copied once.
+pub fn export_chat_media(dao: &dyn ChatHistoryReader,
+                         cwd: &ChatWithDetails,
+                         kinds: &[MediaKind],
+                         from_timestamp_option: Option<i64>,
+                         to_timestamp_option: Option<i64>,
+                         output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let ds_root = dao.dataset_root(cwd.ds_uuid())?;
+    let users: HashMap<i64, User> = dao.users(cwd.ds_uuid())?.into_iter().map(|u| (u.id, u)).collect();
+    let user_name = |id: i64| users.get(&id).map(|u| u.pretty_name()).unwrap_or_else(|| UNKNOWN.to_owned());
+
+    fs::create_dir_all(output_dir)?;
+    let mut copied_paths = HashSet::new();
+    let mut result = vec![];
+    let mut offset = 0;
+    loop {
+        let batch = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+        for msg in batch.iter() {
+            if from_timestamp_option.is_some_and(|from| msg.timestamp < from) ||
+                to_timestamp_option.is_some_and(|to| msg.timestamp > to) { continue; }
+            let message_regular_pat! { contents, .. } = msg.typed() else { continue };
+            for content in contents.iter() {
+                let Some((kind, path, file_name_option)) = content_media_file(content) else { continue };
+                if !kinds.is_empty() && !kinds.contains(&kind) { continue; }
+                if copied_paths.contains(path) { continue; }
+
+                let src = ds_root.to_absolute(path);
+                if !src.exists() {
+                    log::debug!("Skipping missing media file {}", src.display());
+                    continue;
+                }
+                let original_name = match file_name_option {
+                    Some(file_name) => file_name.as_str(),
+                    None => path_file_name(&src)?,
+                };
+                let date_time = local_timestamp_string(msg.timestamp, "%Y-%m-%d_%H-%M-%S")?;
+                let prefix = format!("{date_time}_{}", user_name(msg.from_id));
+                let dst = free_path(output_dir, &sanitize_file_name(&prefix), &sanitize_file_name(original_name));
+                fs::copy(&src, &dst).with_context(|| format!("Couldn't copy {} to {}", src.display(), dst.display()))?;
+                copied_paths.insert(path.clone());
+                result.push(dst);
+            }
+        }
+        if batch.len() < BATCH_SIZE { break; }
+        offset += BATCH_SIZE;
+    }
+    log::info!("Copied {} media files of chat {}", result.len(), cwd.chat.qualified_name());
+    Ok(result)
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_control() || FORBIDDEN_FILE_NAME_CHARS.contains(&c) { '_' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+/// `<prefix>_<name>` in the given directory, with a numeric suffix added to the name stem if such file already exists.
+fn free_path(dir: &Path, prefix: &str, name: &str) -> PathBuf {
+    let path = dir.join(format!("{prefix}_{name}"));
+    if !path.exists() { return path; }
+    let (stem, ext_option) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    };
+    (2..).map(|idx| dir.join(match ext_option {
+        Some(ext) => format!("{prefix}_{stem}_{idx}.{ext}"),
+        None => format!("{prefix}_{stem}_{idx}"),
+    })).find(|p| !p.exists()).unwrap()
+}
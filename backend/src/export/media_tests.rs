@@ -0,0 +1,100 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn exporting_media() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=4).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            msg.timestamp = 1687757170 + msg.source_id_option.unwrap() * 100;
+            let file = |file_name: &str| {
+                let path = create_random_file(&ds_root.0);
+                content!(File {
+                    path_option: Some(ds_root.to_relative(&path).unwrap()),
+                    file_name_option: Some(file_name.to_owned()),
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                })
+            };
+            let photo = || {
+                let path = create_random_file(&ds_root.0);
+                content!(Photo {
+                    path_option: Some(ds_root.to_relative(&path).unwrap()),
+                    width: 0,
+                    height: 0,
+                    mime_type_option: None,
+                    is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
+                })
+            };
+            let contents = match msg.source_id_option {
+                Some(1) => vec![file("report.pdf"), file("report.pdf")],
+                Some(2) => vec![photo()],
+                Some(3) => vec![file("a/b?.txt")],
+                // File is missing
+                Some(4) => vec![content!(File {
+                    path_option: Some("missing.pdf".to_owned()),
+                    file_name_option: None,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                })],
+                _ => unreachable!(),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = contents;
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+    let prefix = |idx: i64| -> Result<String> {
+        let date_time = local_timestamp_string(1687757170 + idx * 100, "%Y-%m-%d_%H-%M-%S")?;
+        let from_id = dao.first_messages(&cwd.chat, usize::MAX)?[0].from_id;
+        let user = dao.users(&ds_uuid)?.into_iter().find(|u| u.id == from_id).unwrap();
+        Ok(format!("{date_time}_{}", user.pretty_name()))
+    };
+    let photo_path = match dao.first_messages(&cwd.chat, usize::MAX)?[1].typed() {
+        message_regular_pat! { contents, .. } => match contents[0].sealed_value_optional {
+            Some(content::SealedValueOptional::Photo(ref photo)) => photo.path_option.clone().unwrap(),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    let file_names = |paths: Vec<PathBuf>| paths.iter().map(|p| path_file_name(p).unwrap().to_owned()).collect_vec();
+
+    let output_dir = TmpDir::new();
+    let paths = export_chat_media(dao, &cwd, &[], None, None, &output_dir.path)?;
+    assert_eq!(file_names(paths), vec![
+        format!("{}_report.pdf", prefix(1)?),
+        format!("{}_report_2.pdf", prefix(1)?),
+        format!("{}_{}", prefix(2)?, path_file_name(Path::new(&photo_path))?),
+        format!("{}_a_b_.txt", prefix(3)?),
+    ]);
+
+    let output_dir = TmpDir::new();
+    let paths = export_chat_media(dao, &cwd, &[MediaKind::Photo], None, None, &output_dir.path)?;
+    assert_eq!(paths.len(), 1);
+    assert!(path_file_name(&paths[0])?.starts_with(&prefix(2)?));
+
+    let output_dir = TmpDir::new();
+    let from = Some(1687757170 + 200);
+    let paths = export_chat_media(dao, &cwd, &[MediaKind::File], from, None, &output_dir.path)?;
+    assert_eq!(file_names(paths), vec![format!("{}_a_b_.txt", prefix(3)?)]);
+
+    let to = Some(1687757170 + 100);
+    let paths = export_chat_media(dao, &cwd, &[], None, to, &output_dir.path)?;
+    assert_eq!(paths.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use prost::Message as ProstMessage;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::export::{chat_context, chat_info_context, dataset_context, ChatInfoContext, ContentContext, DatasetContext,
+                    MessageContext};
+use crate::export::html::{CHAT_TEMPLATE, GALLERY_TEMPLATE, HtmlExporter, INDEX_TEMPLATE, percent_encode_path, SEARCH_TEMPLATE};
+use crate::export::stickers::StickerFormat;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "site_tests.rs"]
+mod tests;
+
+/// Script defining `SEARCH_INDEX` constant, loaded by the search page.
+pub const SEARCH_INDEX_FILE: &str = "search_index.js";
+/// Media files are copied here, keeping their paths relative to the dataset root.
+pub const MEDIA_DIR: &str = "media";
+/// Export progress is tracked here, see [SiteManifest].
+pub const MANIFEST_DIR: &str = ".export";
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Content kinds shown in the media gallery.
+const GALLERY_KINDS: [&str; 3] = ["photo", "video", "video_msg"];
+/// Search result snippets are truncated to this many characters.
+const SNIPPET_MAX_LEN: usize = 300;
+
+//
+// Context model, in addition to [ChatContext] used for chat pages
+//
+
+/// Context of the [INDEX_TEMPLATE].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SiteIndexContext {
+    pub dataset: DatasetContext,
+    /// In the same order as shown by the app.
+    pub chats: Vec<SiteChatContext>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SiteChatContext {
+    pub chat: ChatInfoContext,
+    /// Relative link to the chat page
+    pub href: String,
+    /// Local date, `YYYY-MM-DD`
+    pub last_message_date: Option<String>,
+}
+
+/// Context of the [GALLERY_TEMPLATE].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GalleryContext {
+    pub dataset: DatasetContext,
+    /// Photos and videos grouped by chat, oldest first within a chat.
+    pub items: Vec<GalleryItemContext>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GalleryItemContext {
+    pub chat_id: i64,
+    pub chat_name: String,
+    /// Relative link to the message on a chat page
+    pub message_href: String,
+    pub date: String,
+    pub content: ContentContext,
+}
+
+/// Context of the [SEARCH_TEMPLATE], the page is expected to load [SEARCH_INDEX_FILE] script.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchContext {
+    pub dataset: DatasetContext,
+}
+
+/// Word-based search index, searching is done by the page itself.
+#[derive(Debug, Default, Serialize)]
+struct SearchIndex {
+    /// Message link, date and time, author name and text snippet.
+    docs: Vec<(String, String, String, String)>,
+    /// Lowercase word -> ascending indices of docs containing it.
+    terms: BTreeMap<String, Vec<usize>>,
+}
+
+/// Search index entry of a single message, along with words it's found by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SearchDoc {
+    doc: (String, String, String, String),
+    words: Vec<String>,
+}
+
+impl SearchDoc {
+    fn new(message_href: String, msg: &MessageContext) -> Option<Self> {
+        let text = msg.text.iter().map(|el| el.text.as_str())
+            .chain(msg.service_description.as_deref())
+            .chain(msg.contents.iter().flat_map(|c| [c.title.as_deref(), c.file_name.as_deref()].into_iter().flatten()))
+            .join(" ");
+        let words = text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .unique()
+            .collect_vec();
+        if words.is_empty() { return None; }
+
+        Some(SearchDoc {
+            doc: (
+                message_href,
+                format!("{} {}", msg.date, msg.time),
+                msg.from_name.clone(),
+                truncate_to(text.trim().to_owned(), SNIPPET_MAX_LEN),
+            ),
+            words,
+        })
+    }
+}
+
+impl SearchIndex {
+    fn add(&mut self, search_doc: SearchDoc) {
+        let doc_idx = self.docs.len();
+        self.docs.push(search_doc.doc);
+        for word in search_doc.words {
+            self.terms.entry(word).or_default().push(doc_idx);
+        }
+    }
+}
+
+/// Chats whose pages have been written by a (possibly interrupted) site export, stored in [MANIFEST_DIR].
+/// Chats whose inputs didn't change since are not rendered again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SiteManifest {
+    ds_uuid: String,
+    /// Chat ID -> hash of everything its page depends on
+    completed_chats: BTreeMap<i64, String>,
+}
+
+impl SiteManifest {
+    fn load(output_dir: &Path) -> Result<Option<Self>> {
+        let path = output_dir.join(MANIFEST_DIR).join(MANIFEST_FILE);
+        if !path.exists() { return Ok(None); }
+        Ok(Some(serde_json::from_slice(&fs::read(&path)?)
+            .with_context(|| format!("Malformed export manifest {}", path.display()))?))
+    }
+
+    /// Written atomically, so that an interrupted write doesn't leave a broken manifest behind.
+    fn save(&self, output_dir: &Path) -> EmptyRes {
+        let path = output_dir.join(MANIFEST_DIR).join(MANIFEST_FILE);
+        write_atomically(&path, &serde_json::to_vec(self)?)
+    }
+}
+
+/// What a completed chat contributes to dataset-wide pages, stored in [MANIFEST_DIR] next to the manifest.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ChatArtifacts {
+    gallery_items: Vec<GalleryItemContext>,
+    search_docs: Vec<SearchDoc>,
+}
+
+impl ChatArtifacts {
+    fn path(output_dir: &Path, chat_id: i64) -> PathBuf {
+        output_dir.join(MANIFEST_DIR).join(format!("chat_{chat_id}.json"))
+    }
+}
+
+/// Export the whole dataset as a static website that can be browsed without the app:
+/// index page with all chats, a page per chat, media gallery and a search page backed by a pre-built index.
+/// Referenced media files are copied into [MEDIA_DIR].
+///
+/// Output directory should either be absent, empty, or contain a previous (possibly interrupted) export
+/// of the same dataset. In the latter case export is resumed: pages of chats that didn't change since
+/// (as well as templates and sticker format) are kept as-is, and already copied media files are not copied again.
+///
+/// Returns a path to the index page.
+pub fn export_site(exporter: &HtmlExporter,
+                   dao: &dyn ChatHistoryReader,
+                   ds_uuid: &PbUuid,
+                   sticker_format_option: Option<StickerFormat>,
+                   output_dir: &Path) -> Result<PathBuf> {
+    let mut manifest = match SiteManifest::load(output_dir)? {
+        Some(manifest) => {
+            ensure!(manifest.ds_uuid == ds_uuid.value,
+                    "Directory {} contains an export of another dataset!", output_dir.display());
+            manifest
+        }
+        None => {
+            if output_dir.exists() {
+                ensure!(fs::read_dir(output_dir)?.next().is_none(), "Directory {} is not empty!", output_dir.display());
+            }
+            SiteManifest { ds_uuid: ds_uuid.value.clone(), ..Default::default() }
+        }
+    };
+    fs::create_dir_all(output_dir.join(MEDIA_DIR))?;
+    fs::create_dir_all(output_dir.join(MANIFEST_DIR))?;
+
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let media_href = |path: &Path| -> Result<String> {
+        let rel_path = ds_root.to_relative(path)?.replace('\\', "/");
+        let dst = output_dir.join(MEDIA_DIR).join(&rel_path);
+        if !dst.exists() {
+            fs::create_dir_all(dst.parent().unwrap())?;
+            let tmp_dst = dst.with_file_name(format!("{}.part", path_file_name(&dst)?));
+            fs::copy(path, &tmp_dst).with_context(|| format!("Failed to copy {}", path.display()))?;
+            fs::rename(&tmp_dst, &dst)?;
+        }
+        Ok(format!("{MEDIA_DIR}/{}", percent_encode_path(&rel_path)))
+    };
+
+    let dataset = dataset_context(dao, ds_uuid)?;
+    let common_inputs_hash = common_inputs_hash(exporter, dao, ds_uuid, sticker_format_option)?;
+    let cwds = dao.chats(ds_uuid)?;
+    let mut chats = vec![];
+    let mut gallery_items = vec![];
+    let mut search_index = SearchIndex::default();
+    let mut num_reused = 0;
+    for cwd in cwds.iter() {
+        let chat_id = cwd.chat.id;
+        let href = format!("chat_{chat_id}.html");
+        let artifacts_path = ChatArtifacts::path(output_dir, chat_id);
+        let inputs_hash = chat_inputs_hash(dao, cwd, &common_inputs_hash)?;
+
+        let is_completed = manifest.completed_chats.get(&chat_id) == Some(&inputs_hash)
+            && output_dir.join(&href).exists() && artifacts_path.exists();
+        let (chat_info, artifacts) = if is_completed {
+            num_reused += 1;
+            let artifacts: ChatArtifacts = serde_json::from_slice(&fs::read(&artifacts_path)?)?;
+            (chat_info_context(&cwd.chat, &ds_root, &media_href)?, artifacts)
+        } else {
+            let ctx = chat_context(dao, cwd, sticker_format_option, &media_href)?;
+            let mut artifacts = ChatArtifacts::default();
+            for msg in ctx.messages.iter() {
+                let message_href = format!("{href}#msg-{}", msg.internal_id);
+                for content in msg.contents.iter() {
+                    if content.href.is_some() && GALLERY_KINDS.contains(&content.kind.as_str()) {
+                        artifacts.gallery_items.push(GalleryItemContext {
+                            chat_id: ctx.chat.id,
+                            chat_name: ctx.chat.name.clone(),
+                            message_href: message_href.clone(),
+                            date: msg.date.clone(),
+                            content: content.clone(),
+                        });
+                    }
+                }
+                artifacts.search_docs.extend(SearchDoc::new(message_href, msg));
+            }
+            write_atomically(&output_dir.join(&href), exporter.render(CHAT_TEMPLATE, &ctx, true)?.as_bytes())?;
+            write_atomically(&artifacts_path, &serde_json::to_vec(&artifacts)?)?;
+            manifest.completed_chats.insert(chat_id, inputs_hash);
+            manifest.save(output_dir)?;
+            (ctx.chat, artifacts)
+        };
+
+        gallery_items.extend(artifacts.gallery_items);
+        artifacts.search_docs.into_iter().for_each(|doc| search_index.add(doc));
+        let last_message_date = cwd.last_msg_option.as_ref().map(|m| local_date_string(m.timestamp)).transpose()?;
+        chats.push(SiteChatContext { chat: chat_info, href, last_message_date });
+    }
+    if num_reused > 0 {
+        log::info!("Reused pages of {num_reused} unchanged chat(s) from a previous export");
+    }
+
+    // Chats deleted since the previous export
+    let chat_ids: HashSet<i64> = cwds.iter().map(|cwd| cwd.chat.id).collect();
+    let stale_chat_ids = manifest.completed_chats.keys().filter(|id| !chat_ids.contains(id)).cloned().collect_vec();
+    for chat_id in stale_chat_ids {
+        for path in [output_dir.join(format!("chat_{chat_id}.html")), ChatArtifacts::path(output_dir, chat_id)] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        manifest.completed_chats.remove(&chat_id);
+    }
+    manifest.save(output_dir)?;
+
+    let gallery = GalleryContext { dataset: dataset.clone(), items: gallery_items };
+    fs::write(output_dir.join(GALLERY_TEMPLATE), exporter.render(GALLERY_TEMPLATE, &gallery, true)?)?;
+
+    let search = SearchContext { dataset: dataset.clone() };
+    fs::write(output_dir.join(SEARCH_TEMPLATE), exporter.render(SEARCH_TEMPLATE, &search, true)?)?;
+    fs::write(output_dir.join(SEARCH_INDEX_FILE),
+              format!("const SEARCH_INDEX = {};\n", serde_json::to_string(&search_index)?))?;
+
+    let index = SiteIndexContext { dataset, chats };
+    let index_path = output_dir.join(INDEX_TEMPLATE);
+    fs::write(&index_path, exporter.render(INDEX_TEMPLATE, &index, true)?)?;
+
+    exporter.copy_assets(output_dir)?;
+    Ok(index_path)
+}
+
+/// Hash of inputs shared by all chat pages: templates, sticker format, dataset and its users.
+fn common_inputs_hash(exporter: &HtmlExporter,
+                      dao: &dyn ChatHistoryReader,
+                      ds_uuid: &PbUuid,
+                      sticker_format_option: Option<StickerFormat>) -> Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    hasher.update(exporter.fingerprint());
+    hasher.update(format!("{sticker_format_option:?}"));
+    let ds = dao.datasets()?.into_iter().find(|ds| ds.uuid == *ds_uuid).context("Dataset not found")?;
+    hasher.update(ds.encode_length_delimited_to_vec());
+    hasher.update(dao.myself(ds_uuid)?.id.to_le_bytes());
+    for user in dao.users(ds_uuid)? {
+        hasher.update(user.encode_length_delimited_to_vec());
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Hash of everything a chat page depends on, as a lowercase hex string.
+/// Media files are only referenced by their paths, their contents are not hashed.
+fn chat_inputs_hash(dao: &dyn ChatHistoryReader, cwd: &ChatWithDetails, common_inputs_hash: &[u8]) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(common_inputs_hash);
+    hasher.update(cwd.chat.encode_length_delimited_to_vec());
+    let mut offset = 0;
+    loop {
+        let msgs = dao.scroll_messages(&cwd.chat, offset, BATCH_SIZE)?;
+        for msg in msgs.iter() {
+            hasher.update(msg.encode_length_delimited_to_vec());
+        }
+        if msgs.len() < BATCH_SIZE { break; }
+        offset += BATCH_SIZE;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Write to a temporary file first and then rename it, so that the target is never left half-written.
+fn write_atomically(path: &Path, bytes: &[u8]) -> EmptyRes {
+    let tmp_path = path.with_file_name(format!("{}.part", path_file_name(path)?));
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,108 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn static_site() -> EmptyRes {
+    let users = (1..=2).map(|i| create_user(&ZERO_PB_UUID, i)).collect_vec();
+    let cwms = (1..=2).map(|chat_id| {
+        let messages = (1..=3).map(|idx| create_regular_message(idx + chat_id as usize * 10, 1)).collect_vec();
+        let chat = create_group_chat(&ZERO_PB_UUID, chat_id, &chat_id.to_string(), vec![1, 2], messages.len());
+        ChatWithMessages { chat, messages }
+    }).collect_vec();
+    let dao_holder = create_dao("test", users, cwms, |ds_root, msg| {
+        if msg.source_id_option == Some(12) {
+            msg.text = vec![RichText::make_plain("Look at this picture".to_owned())];
+            let path = create_random_file(&ds_root.0);
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![content!(Photo {
+                path_option: Some(ds_root.to_relative(&path).unwrap()),
+                width: 0,
+                height: 0,
+                mime_type_option: None,
+                is_one_time: false,
+                lat_str_option: None,
+                lon_str_option: None,
+                ocr_text_option: None,
+            })];
+        }
+    });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+    let chats = dao.chats(&ds_uuid)?;
+    let output_dir = TmpDir::new();
+    let exporter = HtmlExporter::new(None)?;
+
+    let index_path = export_site(&exporter, dao, &ds_uuid, None, &output_dir.path)?;
+    assert_eq!(index_path, output_dir.path.join("index.html"));
+    let index = fs::read_to_string(&index_path)?;
+    assert!(index.contains(r#"<nav class="site-nav">"#));
+    for cwd in chats.iter() {
+        assert!(index.contains(&format!(r#"<a class="chat-link" href="chat_{}.html">"#, cwd.chat.id)));
+        let chat_page = fs::read_to_string(output_dir.path.join(format!("chat_{}.html", cwd.chat.id)))?;
+        assert!(chat_page.contains(r#"<nav class="site-nav">"#));
+        assert!(chat_page.contains(&format!("<h1>Chat {}</h1>", cwd.chat.id)));
+    }
+
+    // Media is copied under the same relative paths
+    let msg = dao.scroll_messages(&chats.iter().find(|cwd| cwd.chat.id == 1).unwrap().chat, 1, 1)?.remove(0);
+    let rel_path = msg.files_relative().remove(0).to_owned();
+    assert!(files_are_equal(&ds_root.to_absolute(&rel_path), &output_dir.path.join(MEDIA_DIR).join(&rel_path))?);
+    for chat in [chats[0].chat.clone(), chats[1].chat.clone()] {
+        let img_rel_path = chat.img_path_option.unwrap();
+        assert!(output_dir.path.join(MEDIA_DIR).join(img_rel_path).exists());
+    }
+
+    let gallery = fs::read_to_string(output_dir.path.join("gallery.html"))?;
+    assert_eq!(gallery.matches(r#"class="gallery-item""#).count(), 1);
+    assert!(gallery.contains(&format!(r#"href="chat_1.html#msg-{}""#, msg.internal_id)));
+    assert!(gallery.contains(&format!(r#"<img src="media&#x2F;{rel_path}""#)));
+
+    let search = fs::read_to_string(output_dir.path.join("search.html"))?;
+    assert!(search.contains(r#"<script src="search_index.js"></script>"#));
+    let search_index = fs::read_to_string(output_dir.path.join(SEARCH_INDEX_FILE))?;
+    let search_index_len = search_index.len();
+    let search_index = search_index.strip_prefix("const SEARCH_INDEX = ").unwrap().trim_end().strip_suffix(';').unwrap();
+    let search_index: serde_json::Value = serde_json::from_str(search_index)?;
+    let docs = search_index["docs"].as_array().unwrap();
+    assert_eq!(docs.len(), 6);
+    let picture_docs = search_index["terms"]["picture"].as_array().unwrap();
+    assert_eq!(picture_docs.len(), 1);
+    let picture_doc = docs[picture_docs[0].as_u64().unwrap() as usize].as_array().unwrap();
+    assert_eq!(picture_doc[0], format!("chat_1.html#msg-{}", msg.internal_id));
+    assert_eq!(picture_doc[2], "User 1");
+    assert_eq!(picture_doc[3], "Look at this picture");
+    assert_eq!(search_index["terms"]["hello"].as_array().unwrap().len(), 5);
+
+    // Export is resumed, unchanged chats are not rendered again
+    let chat_1_path = output_dir.path.join("chat_1.html");
+    fs::write(&chat_1_path, "Previously exported")?;
+    fs::remove_file(output_dir.path.join("chat_2.html"))?;
+    export_site(&exporter, dao, &ds_uuid, None, &output_dir.path)?;
+    assert_eq!(fs::read_to_string(&chat_1_path)?, "Previously exported");
+    assert!(fs::read_to_string(output_dir.path.join("chat_2.html"))?.contains("<h1>Chat 2</h1>"));
+    assert_eq!(fs::read_to_string(output_dir.path.join("gallery.html"))?, gallery);
+    assert_eq!(fs::read_to_string(output_dir.path.join(SEARCH_INDEX_FILE))?.len(), search_index_len);
+
+    // Changing inputs invalidates previous pages
+    export_site(&exporter, dao, &ds_uuid, Some(StickerFormat::Gif), &output_dir.path)?;
+    assert!(fs::read_to_string(&chat_1_path)?.contains("<h1>Chat 1</h1>"));
+
+    // Output directory must be empty, unless it's a previous export of the same dataset
+    let other_dir = TmpDir::new();
+    fs::write(other_dir.path.join("some_file.txt"), "")?;
+    assert!(export_site(&exporter, dao, &ds_uuid, None, &other_dir.path).is_err());
+
+    let other_manifest = SiteManifest { ds_uuid: "other".to_owned(), ..Default::default() };
+    other_manifest.save(&output_dir.path)?;
+    assert!(export_site(&exporter, dao, &ds_uuid, None, &output_dir.path).is_err());
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "stickers_tests.rs"]
+mod tests;
+
+const FFMPEG: &str = "ffmpeg";
+/// Renders Lottie animations, part of [python-lottie](https://gitlab.com/mattbas/python-lottie)
+const LOTTIE_CONVERT: &str = "lottie_convert.py";
+
+/// Telegram animated stickers (gzipped Lottie), WebP (WhatsApp, Signal) and WebM (Telegram video stickers).
+const ANIMATED_EXTENSIONS: [&str; 3] = ["tgs", "webp", "webm"];
+
+/// Format animated stickers are converted to on export, so that they can be rendered outside the original apps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickerFormat {
+    Gif,
+    Apng,
+}
+
+impl StickerFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            StickerFormat::Gif => "gif",
+            StickerFormat::Apng => "png",
+        }
+    }
+}
+
+/// Converted sticker is cached next to the original one, e.g. `sticker.tgs` -> `sticker.tgs.gif`.
+/// Returns `None` if the file is not an animated sticker.
+fn converted_path_for(path: &Path, format: StickerFormat) -> Option<PathBuf> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if !ANIMATED_EXTENSIONS.contains(&ext.as_str()) { return None; }
+    let mut file_name = path.file_name()?.to_os_string();
+    file_name.push(".");
+    file_name.push(format.extension());
+    Some(path.with_file_name(file_name))
+}
+
+/// Convert an animated sticker (absolute path) into the given format, returning a path to the converted file.
+/// Conversion result is cached, so that repeated exports don't convert the same sticker again.
+///
+/// Non-animated stickers are returned as-is, and so are the ones that failed to convert, e.g. because
+/// `ffmpeg` (or `lottie_convert.py` for TGS) is not available on `PATH`.
+pub fn convert_sticker(path: &Path, format: StickerFormat) -> PathBuf {
+    let Some(converted_path) = converted_path_for(path, format) else { return path.to_path_buf() };
+    if converted_path.exists() { return converted_path; }
+    match convert(path, &converted_path, format) {
+        Ok(()) => converted_path,
+        Err(e) => {
+            log::warn!("Couldn't convert sticker {}: {e}", path.display());
+            path.to_path_buf()
+        }
+    }
+}
+
+fn convert(path: &Path, converted_path: &Path, format: StickerFormat) -> EmptyRes {
+    let is_lottie = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tgs"));
+    if is_lottie {
+        // Lottie can only be rendered to GIF, APNG is then made from it
+        let gif_path = converted_path_for(path, StickerFormat::Gif).unwrap();
+        if !gif_path.exists() {
+            run(Command::new(LOTTIE_CONVERT).arg(path).arg(&gif_path), LOTTIE_CONVERT)?;
+        }
+        if format == StickerFormat::Gif { return Ok(()); }
+        return run_ffmpeg(&gif_path, converted_path, format);
+    }
+    run_ffmpeg(path, converted_path, format)
+}
+
+fn run_ffmpeg(path: &Path, converted_path: &Path, format: StickerFormat) -> EmptyRes {
+    let mut cmd = Command::new(FFMPEG);
+    cmd.args(["-v", "error", "-n", "-i"]).arg(path);
+    match format {
+        // Palette generation keeps colors decent, transparency is preserved
+        StickerFormat::Gif => cmd.args([
+            "-filter_complex", "[0:v]split[a][b];[a]palettegen=reserve_transparent=1[p];[b][p]paletteuse",
+            "-loop", "0", "-f", "gif"]),
+        StickerFormat::Apng => cmd.args(["-plays", "0", "-f", "apng"]),
+    };
+    run(cmd.arg(converted_path), FFMPEG)?;
+    ensure!(converted_path.exists(), "{FFMPEG} produced no output");
+    Ok(())
+}
+
+fn run(cmd: &mut Command, name: &str) -> EmptyRes {
+    let output = cmd.output().with_context(|| format!("Couldn't run {name}"))?;
+    ensure!(output.status.success(), "{name} failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    Ok(())
+}
@@ -0,0 +1,36 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn converted_path() {
+    assert_eq!(converted_path_for(Path::new("/data/stickers/sticker.tgs"), StickerFormat::Gif),
+               Some(PathBuf::from("/data/stickers/sticker.tgs.gif")));
+    assert_eq!(converted_path_for(Path::new("/data/stickers/sticker.WEBM"), StickerFormat::Apng),
+               Some(PathBuf::from("/data/stickers/sticker.WEBM.png")));
+    assert_eq!(converted_path_for(Path::new("/data/stickers/sticker.png"), StickerFormat::Gif), None);
+    assert_eq!(converted_path_for(Path::new("/data/stickers/sticker"), StickerFormat::Gif), None);
+}
+
+#[test]
+fn non_animated_sticker_is_kept() {
+    let path = Path::new("/non/existent/sticker.jpg");
+    assert_eq!(convert_sticker(path, StickerFormat::Gif), path.to_path_buf());
+}
+
+#[test]
+fn cached_conversion_is_reused() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("sticker.webp");
+    fs::write(&path, b"not really a webp")?;
+    let converted_path = tmp_dir.path.join("sticker.webp.gif");
+    fs::write(&converted_path, b"not really a gif")?;
+    assert_eq!(convert_sticker(&path, StickerFormat::Gif), converted_path);
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::dao::ChatHistoryReader;
+use crate::dao::notes::is_note_on;
+use crate::export::{chat_context, chat_file_stem, ChatContext, ChatInfoContext, DatasetContext, MessageContext, UserContext};
+use crate::export::html::{HtmlExporter, TRANSCRIPT_TEMPLATE};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "transcript_tests.rs"]
+mod tests;
+
+//
+// Context model
+//
+
+/// Context of the [TRANSCRIPT_TEMPLATE].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TranscriptContext {
+    pub dataset: DatasetContext,
+    pub chat: ChatInfoContext,
+    /// Chat members, self first.
+    pub members: Vec<UserContext>,
+    /// Local dates of the first and the last messages, `YYYY-MM-DD`
+    pub first_date: Option<String>,
+    pub last_date: Option<String>,
+    pub blocks: Vec<TranscriptBlock>,
+    /// Media attached to messages and user notes on them, numbered from 1 in order of appearance.
+    pub footnotes: Vec<TranscriptFootnote>,
+    /// Texts of user notes on the chat itself
+    pub chat_notes: Vec<String>,
+}
+
+/// Either a single service event, or consecutive messages sent by the same user on the same day.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TranscriptBlock {
+    /// `messages` or `service`
+    pub kind: &'static str,
+    /// Local date, `YYYY-MM-DD`
+    pub date: String,
+    pub from_name: String,
+    pub lines: Vec<TranscriptLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TranscriptLine {
+    /// Local time, `HH:MM:SS`
+    pub time: String,
+    /// Plain text, link targets are appended after link texts. For service events, includes event description.
+    pub text: String,
+    /// Numbers of footnotes for attached media and notes
+    pub footnotes: Vec<usize>,
+    /// Remarks such as `edited` or `forwarded from Alice`
+    pub remarks: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TranscriptFootnote {
+    pub number: usize,
+    /// Local date and time of the message, `YYYY-MM-DD HH:MM:SS`
+    pub date_time: String,
+    pub from_name: String,
+    /// Textual representation of the media, e.g. `Photo`, or a note text
+    pub description: String,
+    /// Original file name if known, stored file name otherwise
+    pub file_name: Option<String>,
+}
+
+/// Export a chat (or only its single topic) as a printable transcript into `chat_<id>_transcript.html` file
+/// in the given directory, returns the file path. User notes, if included, become footnotes.
+pub fn export_transcript(exporter: &HtmlExporter,
+                         dao: &dyn ChatHistoryReader,
+                         cwd: &ChatWithDetails,
+                         topic_id_option: Option<i64>,
+                         include_notes: bool,
+                         output_dir: &Path) -> Result<PathBuf> {
+    // Media are only mentioned by name
+    let mut ctx = chat_context(dao, cwd, None, &|path| ok(path_file_name(path)?.to_owned()))?;
+    ctx.retain_topic(topic_id_option);
+    let notes = if include_notes { dao.notes(&cwd.chat.ds_uuid, Some(cwd.chat.id))? } else { vec![] };
+    let html = exporter.render(TRANSCRIPT_TEMPLATE, &transcript_context(ctx, &notes), false)?;
+    fs::create_dir_all(output_dir)?;
+    let path = output_dir.join(format!("{}_transcript.html", chat_file_stem(&cwd.chat, topic_id_option)));
+    fs::write(&path, html)?;
+    Ok(path)
+}
+
+pub fn transcript_context(ctx: ChatContext, notes: &[Note]) -> TranscriptContext {
+    let first_date = ctx.messages.first().map(|m| m.date.clone());
+    let last_date = ctx.messages.last().map(|m| m.date.clone());
+
+    let mut blocks: Vec<TranscriptBlock> = vec![];
+    let mut footnotes = vec![];
+    for msg in ctx.messages {
+        let mut line = TranscriptLine {
+            time: msg.time.clone(),
+            text: plain_text(&msg),
+            footnotes: vec![],
+            remarks: vec![],
+        };
+        for content in msg.contents.iter() {
+            let number = footnotes.len() + 1;
+            footnotes.push(TranscriptFootnote {
+                number,
+                date_time: format!("{} {}", msg.date, msg.time),
+                from_name: msg.from_name.clone(),
+                description: content.description.clone(),
+                file_name: content.file_name.clone().or_else(|| content.href.clone()),
+            });
+            line.footnotes.push(number);
+        }
+        for note in notes.iter().filter(|n| is_note_on(n, msg.source_id, msg.internal_id)) {
+            let number = footnotes.len() + 1;
+            footnotes.push(TranscriptFootnote {
+                number,
+                date_time: format!("{} {}", msg.date, msg.time),
+                from_name: msg.from_name.clone(),
+                description: format!("Note: {}", note.text),
+                file_name: None,
+            });
+            line.footnotes.push(number);
+        }
+        if let Some(ref name) = msg.forward_from_name {
+            line.remarks.push(format!("forwarded from {name}"));
+        }
+        if let Some(id) = msg.reply_to_source_id {
+            line.remarks.push(format!("in reply to message #{id}"));
+        }
+        if msg.is_deleted {
+            line.remarks.push("deleted".to_owned());
+        } else if msg.edit_timestamp.is_some() {
+            line.remarks.push("edited".to_owned());
+        }
+        if let Some(ref duration) = msg.disappears_after {
+            line.remarks.push(format!("disappearing after {duration}"));
+        }
+
+        let kind = if msg.kind == "service" { "service" } else { "messages" };
+        let continues_block = |block: &TranscriptBlock|
+            kind == "messages" && block.kind == kind && block.date == msg.date && block.from_name == msg.from_name;
+        match blocks.last_mut() {
+            Some(block) if continues_block(block) => block.lines.push(line),
+            _ => blocks.push(TranscriptBlock { kind, date: msg.date, from_name: msg.from_name, lines: vec![line] }),
+        }
+    }
+
+    TranscriptContext {
+        dataset: ctx.dataset,
+        chat: ctx.chat,
+        members: ctx.members,
+        first_date,
+        last_date,
+        blocks,
+        footnotes,
+        chat_notes: notes.iter()
+            .filter(|n| n.message_source_id_option.is_none() && n.message_internal_id_option.is_none())
+            .map(|n| n.text.clone())
+            .collect_vec(),
+    }
+}
+
+fn plain_text(msg: &MessageContext) -> String {
+    match msg.service_description {
+        Some(ref desc) if msg.plain_text.is_empty() => desc.clone(),
+        Some(ref desc) => format!("{desc}: {}", msg.plain_text),
+        None => msg.plain_text.clone(),
+    }
+}
@@ -0,0 +1,158 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn transcript() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        [1, 1, 2, 2, 1].into_iter().enumerate().map(|(idx, user_id)| create_regular_message(idx + 1, user_id)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            // Get rid of forwards, replies, edits and contents
+            if let message::Typed::Regular(mr) = msg.typed_mut() {
+                *mr = MessageRegular::default();
+            }
+            match msg.source_id_option {
+                Some(2) => {
+                    msg.text = vec![RichText::make_plain("See ".to_owned()),
+                                    RichText::make_link(Some("this".to_owned()), "https://example.com".to_owned(), false)];
+                    let path = create_random_file(&ds_root.0);
+                    let edit_timestamp = msg.timestamp + 60;
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.edit_timestamp_option = Some(edit_timestamp);
+                    mr.contents = vec![content!(File {
+                        path_option: Some(ds_root.to_relative(&path).unwrap()),
+                        file_name_option: Some("report.pdf".to_owned()),
+                        mime_type_option: None,
+                        thumbnail_path_option: None,
+                    })];
+                }
+                Some(3) => {
+                    msg.text = vec![];
+                    msg.typed = Some(message::Typed::Service(MessageService {
+                        sealed_value_optional: Some(message_service::SealedValueOptional::GroupEditTitle(
+                            MessageServiceGroupEditTitle { title: "New title".to_owned() }
+                        ))
+                    }));
+                }
+                Some(4 | 5) => {
+                    let duration = if msg.source_id_option == Some(4) { 7 * 24 * 60 * 60 } else { 90 };
+                    let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+                    mr.ephemeral_duration_sec_option = Some(duration);
+                }
+                _ => {}
+            }
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+    let ctx = chat_context(dao, &cwd, None, &|path| ok(path_file_name(path)?.to_owned()))?;
+    let date = ctx.messages[0].date.clone();
+    let times = ctx.messages.iter().map(|m| m.time.clone()).collect_vec();
+
+    let transcript = transcript_context(ctx, &[]);
+    assert_eq!(transcript.first_date.as_ref(), Some(&date));
+    assert_eq!(transcript.last_date.as_ref(), Some(&date));
+    let line = |idx: usize, text: &str, footnotes: Vec<usize>, remarks: Vec<&str>| TranscriptLine {
+        time: times[idx].clone(),
+        text: text.to_owned(),
+        footnotes,
+        remarks: remarks.into_iter().map(|s| s.to_owned()).collect_vec(),
+    };
+    assert_eq!(transcript.blocks, vec![
+        TranscriptBlock {
+            kind: "messages",
+            date: date.clone(),
+            from_name: "User 1".to_owned(),
+            lines: vec![
+                line(0, "Hello there, 1!", vec![], vec![]),
+                line(1, "See this (https://example.com)", vec![1], vec!["edited"]),
+            ],
+        },
+        TranscriptBlock {
+            kind: "service",
+            date: date.clone(),
+            from_name: "User 2".to_owned(),
+            lines: vec![line(2, "Changed group title to New title", vec![], vec![])],
+        },
+        TranscriptBlock {
+            kind: "messages",
+            date: date.clone(),
+            from_name: "User 2".to_owned(),
+            lines: vec![line(3, "Hello there, 4!", vec![], vec!["disappearing after 7 days"])],
+        },
+        TranscriptBlock {
+            kind: "messages",
+            date: date.clone(),
+            from_name: "User 1".to_owned(),
+            lines: vec![line(4, "Hello there, 5!", vec![], vec!["disappearing after 90 seconds"])],
+        },
+    ]);
+    assert_eq!(transcript.footnotes, vec![TranscriptFootnote {
+        number: 1,
+        date_time: format!("{date} {}", times[1]),
+        from_name: "User 1".to_owned(),
+        description: "File".to_owned(),
+        file_name: Some("report.pdf".to_owned()),
+    }]);
+
+    let output_dir = TmpDir::new();
+    let path = export_transcript(&HtmlExporter::new(None)?, dao, &cwd, None, false, &output_dir.path)?;
+    assert_eq!(path, output_dir.path.join(format!("chat_{}_transcript.html", cwd.chat.id)));
+    let html = fs::read_to_string(&path)?;
+    assert!(html.contains("<title>Transcript - Chat One</title>"));
+    assert_eq!(html.matches("<h2>").count(), 1);
+    assert!(html.contains("[1] File: report.pdf"));
+    Ok(())
+}
+
+#[test]
+fn transcript_notes() -> EmptyRes {
+    let dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=3).map(|i| create_regular_message(i, 1)).collect_vec(),
+        2,
+        &|_, _, msg| {
+            if let message::Typed::Regular(mr) = msg.typed_mut() {
+                *mr = MessageRegular::default();
+            }
+        });
+    let dao = dao_holder.dao.as_ref();
+    let ds_uuid = dao.datasets()?.remove(0).uuid;
+    let cwd = dao.chats(&ds_uuid)?.remove(0);
+    let ctx = chat_context(dao, &cwd, None, &|path| ok(path_file_name(path)?.to_owned()))?;
+    let internal_id_3 = ctx.messages[2].internal_id;
+    let note = |id: i64, source_id: Option<i64>, internal_id: Option<i64>, text: &str| Note {
+        id,
+        ds_uuid: ds_uuid.clone(),
+        chat_id: cwd.chat.id,
+        message_source_id_option: source_id,
+        message_internal_id_option: internal_id,
+        text: text.to_owned(),
+        timestamp: 0,
+    };
+    let notes = vec![
+        note(1, None, None, "About the chat"),
+        note(2, None, Some(internal_id_3), "Third"),
+        note(3, Some(2), None, "Second"),
+    ];
+
+    let transcript = transcript_context(ctx, &notes);
+    assert_eq!(transcript.chat_notes, vec!["About the chat".to_owned()]);
+    let lines = transcript.blocks.iter().flat_map(|b| b.lines.iter()).collect_vec();
+    assert_eq!(lines.iter().map(|l| l.footnotes.clone()).collect_vec(), vec![vec![], vec![1], vec![2]]);
+    assert_eq!(transcript.footnotes.iter().map(|f| f.description.as_str()).collect_vec(),
+               vec!["Note: Second", "Note: Third"]);
+    assert!(transcript.footnotes.iter().all(|f| f.file_name.is_none()));
+    Ok(())
+}
@@ -1,8 +1,95 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
 use tonic::{Response, Status};
+use tonic::codec::CompressionEncoding;
+
+use crate::prelude::*;
 use crate::StdResult;
 
 pub mod server;
 pub mod client;
 
+#[cfg(test)]
+#[path = "grpc_tests.rs"]
+mod tests;
+
 type StatusResult<T> = StdResult<T, Status>;
 type TonicResult<T> = StatusResult<Response<T>>;
+
+/// Large chat fetches easily exceed tonic default limit of 4 MiB.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 256 * 1024 * 1024;
+
+static GRPC_CONFIG: OnceLock<GrpcConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrpcCompression {
+    Gzip,
+    Zstd,
+}
+
+impl GrpcCompression {
+    fn encoding(self) -> CompressionEncoding {
+        match self {
+            GrpcCompression::Gzip => CompressionEncoding::Gzip,
+            GrpcCompression::Zstd => CompressionEncoding::Zstd,
+        }
+    }
+}
+
+impl FromStr for GrpcCompression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "gzip" => Ok(GrpcCompression::Gzip),
+            "zstd" => Ok(GrpcCompression::Zstd),
+            _ => err!("Unknown gRPC compression {s}, expected gzip or zstd"),
+        }
+    }
+}
+
+/// Settings of all gRPC servers and clients within a process, see [configure_grpc].
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    /// Compression of outgoing messages, compressed incoming messages are accepted regardless.
+    /// Other side has to support it too, otherwise messages are sent uncompressed.
+    pub compression_option: Option<GrpcCompression>,
+    /// Max size of a single message, both incoming and outgoing.
+    pub max_message_size: usize,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        GrpcConfig { compression_option: None, max_message_size: DEFAULT_MAX_MESSAGE_SIZE }
+    }
+}
+
+/// Configured by [configure_grpc], or default settings on first use.
+pub fn grpc_config() -> &'static GrpcConfig {
+    GRPC_CONFIG.get_or_init(GrpcConfig::default)
+}
+
+/// Has to be called before any gRPC server or client is created.
+pub fn configure_grpc(config: GrpcConfig) -> EmptyRes {
+    ensure!(config.max_message_size > 0, "Max gRPC message size should be positive");
+    GRPC_CONFIG.set(config)
+        .map_err(|_| anyhow!("gRPC is already in use, it can no longer be configured"))
+}
+
+/// Apply [grpc_config] to a generated gRPC server or client.
+macro_rules! with_grpc_config {
+    ($service:expr) => {{
+        let config = $crate::grpc::grpc_config();
+        let service = $service
+            .accept_compressed(tonic::codec::CompressionEncoding::Gzip)
+            .accept_compressed(tonic::codec::CompressionEncoding::Zstd)
+            .max_decoding_message_size(config.max_message_size)
+            .max_encoding_message_size(config.max_message_size);
+        match config.compression_option {
+            Some(compression) => service.send_compressed(compression.encoding()),
+            None => service,
+        }
+    }};
+}
+pub(crate) use with_grpc_config;
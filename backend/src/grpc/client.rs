@@ -51,9 +51,9 @@ pub async fn create_clients(remote_port: u16) -> Result<ChatHistoryManagerGrpcCl
     let uri = format!("http://localhost:{remote_port}");
     log::info!("Connecting to clients at URI {uri}");
     let channel = Endpoint::new(uri)?.connect_lazy();
-    let loader = HistoryLoaderServiceClient::new(channel.clone());
-    let dao = HistoryDaoServiceClient::new(channel.clone());
-    let merger = MergeServiceClient::new(channel);
+    let loader = with_grpc_config!(HistoryLoaderServiceClient::new(channel.clone()));
+    let dao = with_grpc_config!(HistoryDaoServiceClient::new(channel.clone()));
+    let merger = with_grpc_config!(MergeServiceClient::new(channel));
     Ok(ChatHistoryManagerGrpcClients { loader, dao, merger })
 }
 
@@ -68,6 +68,10 @@ impl UserInputBlockingRequester for NoChooser {
     fn ask_for_text(&self, _prompt: &str) -> Result<String> {
         err!("No way to ask user!")
     }
+
+    fn choose_reimport_action(&self, _source_path: &str, _previous_imports: &[PreviousImport]) -> Result<(ReimportAction, usize)> {
+        err!("No way to choose re-import action!")
+    }
 }
 
 #[derive(Clone)]
@@ -100,6 +104,10 @@ impl UserInputBlockingRequester for PredefinedInput {
             .clone()
             .ok_or_else(|| anyhow!("No text provided!"))
     }
+
+    fn choose_reimport_action(&self, _source_path: &str, _previous_imports: &[PreviousImport]) -> Result<(ReimportAction, usize)> {
+        err!("No re-import action provided!")
+    }
 }
 
 pub async fn debug_request_myself(port: u16) -> Result<usize> {
@@ -26,7 +26,7 @@ impl UserInputGrpcRequester {
         let channel = self.channel.clone();
 
         // We cannot use the current thread since when called via RPC, current thread is already used for async tasks.
-        let mut client = UserInputServiceClient::new(channel);
+        let mut client = with_grpc_config!(UserInputServiceClient::new(channel));
         log::info!("Sending ChooseMyselfRequest");
         let response = create_request(&mut client)
             .await
@@ -66,4 +66,22 @@ impl UserInputRequester for UserInputGrpcRequester {
             Ok(res.user_input)
         }).await
     }
+
+    async fn choose_reimport_action(&self, source_path: &str, previous_imports: &[PreviousImport]) -> Result<(ReimportAction, usize)> {
+        let source_path = source_path.to_owned();
+        let previous_imports = previous_imports.to_vec();
+        let len = previous_imports.len();
+
+        self.request_and_process(|client| {
+            Box::pin(client.choose_reimport_action(ChooseReimportActionRequest { source_path, previous_imports }))
+        }, move |res| {
+            let action = ReimportAction::try_from(res.action)?;
+            let picked = res.picked_option;
+            if action != ReimportAction::NewDataset && (picked < 0 || picked as usize >= len) {
+                err!("Choice out of range!")
+            } else {
+                Ok((action, picked.max(0) as usize))
+            }
+        }).await
+    }
 }
@@ -1,14 +1,23 @@
 use std::fmt::Debug;
 use std::future::Future;
 use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
 use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::Local;
+use futures::FutureExt;
 use indexmap::IndexMap;
 use tokio::runtime::Handle;
 use tonic::{Code, Request, Response, Status, transport::Server};
+use tonic::service::Interceptor;
+use tonic::service::interceptor::InterceptedService;
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::{ChatHistoryReader, ChatHistoryWriter, LoadedDao};
+use crate::dao::gallery::MediaAnnotator;
+use crate::dao::remote_media::RemoteMediaCache;
+use crate::dao::storage_usage::StorageUsageCache;
 use crate::loader::Loader;
 use crate::prelude::*;
 use crate::protobuf::history::user_input_service_server::UserInputServiceServer;
@@ -20,17 +29,35 @@ use super::*;
 
 use super::client;
 
+use access::{AccessInterceptor, AccessProfiles, AccessScoped};
+use audit::Auditor;
+use import_pool::ImportPool;
+use limits::{RateLimiter, RequestLimits};
+
+pub mod access;
+mod audit;
+pub mod limits;
+#[cfg(feature = "embedded")]
+pub mod embedded;
 mod history_loader_service;
+mod import_pool;
 mod history_dao_service;
 mod merge_service;
 mod user_info_service;
 
+#[cfg(test)]
+#[path = "server_tests.rs"]
+mod tests;
+
 pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
     tonic::include_file_descriptor_set!("grpc_reflection_descriptor");
 
+/// How often retention rules of loaded databases are applied, and their trash is purged.
+const RETENTION_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
 // Abosulte path to data source
 type DaoKey = String;
-type DaoRwLock = RwLock<Box<dyn ChatHistoryDao>>;
+type DaoRwLock = RwLock<LoadedDao>;
 
 trait GeneralServerTrait
 where
@@ -47,16 +74,13 @@ where
     {
         log::debug!(">>> Request:  {}", truncate_to(format!("{:?}", req.get_ref()), 150));
         let self_clone = Arc::clone(self);
-        let response_result = logic(self_clone, req.into_inner())
+        // Panic should only fail this request, locks it might've poisoned are recovered on next use
+        let response_result = AssertUnwindSafe(logic(self_clone, req.into_inner()))
+            .catch_unwind()
             .await
+            .unwrap_or_else(|panic| err!("Request handling panicked: {}", panic_message(&*panic)))
             .map(Response::new);
-        log::debug!("<<< Response: {}", truncate_to(format!("{:?}", response_result), 150));
-        response_result.map_err(|err| {
-            let status = err.downcast::<Status>()
-                .unwrap_or_else(|err| Status::new(Code::Internal, error_message(&err)));
-            eprintln!("Request failed! Error was:\n{:?}", status.message());
-            status
-        })
+        finish_request(response_result)
     }
 
     async fn process_request_blocking<Q, P, L>(self: &Arc<Self>, req: Request<Q>, mut blocking_logic: L) -> TonicResult<P>
@@ -70,52 +94,93 @@ where
         let response_result = self.get_tokio_handle()
             .spawn_blocking(move || blocking_logic(self_clone, req.into_inner()))
             .await
-            .map_err(|e| Status::new(Code::Internal, format!("Blocking task failed: {:?}", e)))?
+            .map_err(|e| match e.try_into_panic() {
+                // Panic should only fail this request, locks it might've poisoned are recovered on next use
+                Ok(panic) => Status::new(Code::Internal, format!("Request handling panicked: {}", panic_message(&*panic))),
+                Err(e) => Status::new(Code::Internal, format!("Blocking task failed: {:?}", e)),
+            })?
             .map(Response::new);
-        log::debug!("<<< Response: {}", truncate_to(format!("{:?}", response_result), 150));
-        response_result.map_err(|err| {
-            let status = err.downcast::<Status>()
-                .unwrap_or_else(|err| Status::new(Code::Internal, error_message(&err)));
-            eprintln!("Request failed! Error was:\n{:?}", status.message());
-            status
-        })
+        finish_request(response_result)
     }
 }
 
+fn finish_request<P: Debug>(response_result: Result<Response<P>>) -> TonicResult<P> {
+    log::debug!("<<< Response: {}", truncate_to(format!("{:?}", response_result), 150));
+    response_result.map_err(|err| {
+        let status = err.downcast::<Status>()
+            .unwrap_or_else(|err| Status::new(Code::Internal, error_message(&err)));
+        eprintln!("Request failed! Error was:\n{:?}", status.message());
+        status
+    })
+}
+
 // Should be used wrapped as Arc<Self>
 struct ChatHistoryManagerServer {
     tokio_handle: Handle,
     loader: Loader,
+    /// Tags media files on request, if configured
+    media_annotator_option: Option<Box<dyn MediaAnnotator>>,
     user_input_requester: Box<dyn UserInputBlockingRequester>,
     loaded_daos: RwLock<IndexMap<DaoKey, DaoRwLock>>,
+    /// How long deleted entities are kept in trash
+    trash_retention: Duration,
+    storage_usage_cache: Mutex<StorageUsageCache>,
+    /// Chunks of media files fetched from remote media roots of datasets
+    remote_media_cache: RemoteMediaCache,
+    import_pool: ImportPool,
 }
 
 impl ChatHistoryManagerServer
 where
     Self: GeneralServerTrait,
 {
-    pub fn new_wrapped(tokio_handle: Handle, loader: Loader, user_input_requester: Box<dyn UserInputBlockingRequester>) -> Arc<Self> {
+    pub fn new_wrapped(tokio_handle: Handle,
+                       loader: Loader,
+                       media_annotator_option: Option<Box<dyn MediaAnnotator>>,
+                       user_input_requester: Box<dyn UserInputBlockingRequester>,
+                       trash_retention: Duration) -> Arc<Self> {
+        let import_pool = ImportPool::new(tokio_handle.clone(), import_pool::DEFAULT_MAX_CONCURRENT_IMPORTS);
         Arc::new(ChatHistoryManagerServer {
             tokio_handle,
             loader,
+            media_annotator_option,
             user_input_requester,
             loaded_daos: RwLock::new(IndexMap::new()),
+            trash_retention,
+            storage_usage_cache: Mutex::new(StorageUsageCache::default()),
+            remote_media_cache: RemoteMediaCache::default(),
+            import_pool,
         })
     }
 
-    async fn process_request_with_dao<Q, P, L>(self: &Arc<Self>, req: Request<Q>, key: DaoKey, mut blocking_logic: L) -> TonicResult<P>
+    /// Same as [GeneralServerTrait::process_request_blocking], but for requests parsing histories from disk,
+    /// which are run on the [ImportPool].
+    async fn process_import_request<Q, P, L>(self: &Arc<Self>, req: Request<Q>, import_logic: L) -> TonicResult<P>
         where Q: Debug + Send + 'static,
               P: Debug + Send + 'static,
-              L: FnMut(Arc<Self>, Q, &dyn ChatHistoryDao) -> Result<P> + Send + 'static {
+              L: FnOnce(Arc<Self>, Q) -> Result<P> + Send + 'static {
+        log::debug!(">>> Request:  {}", truncate_to(format!("{:?}", req.get_ref()), 150));
+        let self_clone = Arc::clone(self);
+        if self.import_pool.available() == 0 {
+            log::info!("Import is queued until ongoing ones are finished");
+        }
+        let response_result = self.import_pool
+            .run(move || import_logic(self_clone, req.into_inner()))
+            .await
+            .and_then(|result| result)
+            .map(Response::new);
+        finish_request(response_result)
+    }
+
+    async fn process_request_with_dao<Q, P, L>(self: &Arc<Self>, req: Request<Q>, key: DaoKey, mut blocking_logic: L) -> TonicResult<P>
+        where Q: AccessScoped + Debug + Send + 'static,
+              P: Debug + Send + 'static,
+              L: FnMut(Arc<Self>, Q, &dyn ChatHistoryReader) -> Result<P> + Send + 'static {
+        access::ensure_access(&req)?;
         self.process_request_blocking(
             req,
             move |self_clone, req| {
-                let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
-                let dao = loaded_daos.get(&key)
-                    .ok_or_else(|| anyhow!("Database with key {key} is not loaded!"))?;
-                let dao = read_or_status(dao)?;
-                let dao = dao.as_ref();
-                blocking_logic(Arc::clone(&self_clone), req, dao)
+                self_clone.with_dao(&key, |dao| blocking_logic(Arc::clone(&self_clone), req, dao))
             },
         ).await
     }
@@ -123,7 +188,8 @@ where
     async fn process_request_with_dao_mut<Q, P, L>(self: &Arc<Self>, req: Request<Q>, key: DaoKey, mut blocking_logic: L) -> TonicResult<P>
         where Q: Debug + Send + 'static,
               P: Debug + Send + 'static,
-              L: FnMut(Arc<Self>, Q, &mut dyn ChatHistoryDao) -> Result<P> + Send + 'static {
+              L: FnMut(Arc<Self>, Q, &mut dyn ChatHistoryWriter) -> Result<P> + Send + 'static {
+        access::ensure_full_access(&req)?;
         self.process_request_blocking(
             req,
             move |self_clone, req| {
@@ -131,13 +197,121 @@ where
                 let dao = loaded_daos.get(&key)
                     .ok_or_else(|| anyhow!("Database with key {key} is not loaded!"))?;
                 let mut dao = write_or_status(dao)?;
-                let dao = dao.as_mut();
+                let dao = dao.writer()?;
                 blocking_logic(Arc::clone(&self_clone), req, dao)
             },
         ).await
     }
 }
 
+impl ChatHistoryManagerServer {
+    /// Run the logic with a loaded DAO, which stays read-locked only until the logic is done.
+    fn with_dao<R>(&self, key: &DaoKey, logic: impl FnOnce(&dyn ChatHistoryReader) -> Result<R>) -> Result<R> {
+        let loaded_daos = read_or_status(&self.loaded_daos)?;
+        let dao = loaded_daos.get(key)
+            .ok_or_else(|| anyhow!("Database with key {key} is not loaded!"))?;
+        let dao = read_or_status(dao)?;
+        logic(dao.reader())
+    }
+
+    /// Apply retention rules of all datasets of all loaded databases.
+    /// Failure to apply rules of one database doesn't prevent others from being processed.
+    fn apply_retention_rules(&self) -> EmptyRes {
+        let now = Timestamp(Local::now().timestamp());
+        let loaded_daos = read_or_status(&self.loaded_daos)?;
+        for (key, dao) in loaded_daos.iter() {
+            let mut dao = write_or_status(dao)?;
+            // Loaded histories are read-only, there's nothing to apply to them
+            let LoadedDao::Writable(dao) = &mut *dao else { continue };
+            let res: EmptyRes = (|| {
+                for ds in dao.datasets()? {
+                    if dao.retention_rules(&ds.uuid)?.is_empty() { continue; }
+                    log::info!("Applying retention rules to dataset '{}' of {key}", ds.alias);
+                    let reports = dao.apply_retention_rules(&ds.uuid, now, false)?;
+                    Auditor::server("ApplyRetentionRules").record_retention(dao.as_mut(), &ds.uuid, &reports)?;
+                }
+                Ok(())
+            })();
+            if let Err(e) = res {
+                log::warn!("Failed to apply retention rules of {key}: {}", error_message(&e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply blocked users policies of all datasets of all loaded databases, catching up with messages
+    /// that got in some other way than a merge.
+    /// Failure to apply policies of one database doesn't prevent others from being processed.
+    fn apply_blocked_users_policies(&self) -> EmptyRes {
+        let loaded_daos = read_or_status(&self.loaded_daos)?;
+        for (key, dao) in loaded_daos.iter() {
+            let mut dao = write_or_status(dao)?;
+            let LoadedDao::Writable(dao) = &mut *dao else { continue };
+            let res: EmptyRes = (|| {
+                for ds in dao.datasets()? {
+                    if dao.blocked_users_policy(&ds.uuid)?.action() == BlockedUserAction::Import { continue; }
+                    let affected_count = dao.apply_blocked_users_policy(&ds.uuid)?;
+                    if affected_count > 0 {
+                        log::info!("Blocked users policy affected {affected_count} message(s) of dataset '{}' of {key}", ds.alias);
+                        Auditor::server("ApplyBlockedUsersPolicy").record(dao.as_mut(), AuditLogEntry {
+                            ds_uuid_option: Some(ds.uuid.clone()),
+                            affected_messages_count: affected_count as i32,
+                            ..Default::default()
+                        })?;
+                    }
+                }
+                Ok(())
+            })();
+            if let Err(e) = res {
+                log::warn!("Failed to apply blocked users policies of {key}: {}", error_message(&e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Permanently remove trash items of all loaded databases that are older than the trash retention period.
+    fn purge_trash(&self) -> EmptyRes {
+        let deleted_before = Timestamp(Local::now().timestamp() - self.trash_retention.as_secs() as i64);
+        let loaded_daos = read_or_status(&self.loaded_daos)?;
+        for (key, dao) in loaded_daos.iter() {
+            let mut dao = write_or_status(dao)?;
+            let LoadedDao::Writable(dao) = &mut *dao else { continue };
+            let res: EmptyRes = (|| {
+                let count = dao.purge_trash(deleted_before)?;
+                if count > 0 {
+                    log::info!("Purged {count} trash item(s) of {key}");
+                    Auditor::server("PurgeTrash").record(dao.as_mut(), AuditLogEntry {
+                        parameters: format!("{count} item(s)"),
+                        ..Default::default()
+                    })?;
+                }
+                Ok(())
+            })();
+            if let Err(e) = res {
+                log::warn!("Failed to purge trash of {key}: {}", error_message(&e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Periodically applies retention rules and blocked users policies of loaded databases and purges their trash
+/// in a background thread.
+fn spawn_retention_executor(server: Arc<ChatHistoryManagerServer>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(RETENTION_PERIOD);
+        if let Err(e) = server.apply_retention_rules() {
+            log::warn!("Failed to apply retention rules: {}", error_message(&e));
+        }
+        if let Err(e) = server.apply_blocked_users_policies() {
+            log::warn!("Failed to apply blocked users policies: {}", error_message(&e));
+        }
+        if let Err(e) = server.purge_trash() {
+            log::warn!("Failed to purge trash: {}", error_message(&e));
+        }
+    });
+}
+
 impl GeneralServerTrait for ChatHistoryManagerServer {
     fn get_tokio_handle(&self) -> &Handle {
         &self.tokio_handle
@@ -166,14 +340,38 @@ impl<R: UserInputRequester> GeneralServerTrait for UserInputServer<R> {
 }
 
 // https://betterprogramming.pub/building-a-grpc-server-with-rust-be2c52f0860e
-pub async fn start_server(port: u16, remote_port: u16, loader: Loader) -> EmptyRes {
+pub async fn start_server(port: u16,
+                          remote_port: u16,
+                          loader: Loader,
+                          media_annotator_option: Option<Box<dyn MediaAnnotator>>,
+                          access_profiles: Option<AccessProfiles>,
+                          request_limits: RequestLimits,
+                          trash_retention: Duration) -> EmptyRes {
+    request_limits.validate()?;
     let addr = format!("127.0.0.1:{port}").parse::<SocketAddr>().unwrap();
 
     let handle = Handle::current();
     let user_input_requester = client::create_user_input_requester(remote_port).await?;
-    let chm_server = ChatHistoryManagerServer::new_wrapped(handle, loader, user_input_requester);
+    let chm_server = ChatHistoryManagerServer::new_wrapped(handle, loader, media_annotator_option, user_input_requester, trash_retention);
+    spawn_retention_executor(Arc::clone(&chm_server));
 
     log::info!("Server listening on {}", addr);
+    if let Some(ref profiles) = access_profiles {
+        log::info!("Access is limited to profiles: {}", profiles.names().join(", "));
+    }
+    let mut access_interceptor = AccessInterceptor { profiles: access_profiles.map(Arc::new) };
+    let rate_limiter_option = RateLimiter::new(&request_limits).map(Arc::new);
+    if let Some(rps) = request_limits.requests_per_sec_option {
+        log::info!("Requests are limited to {rps}/s per client, with a burst of {}", request_limits.burst);
+    }
+    let interceptor = move |req: Request<()>| -> StatusResult<Request<()>> {
+        let req = access_interceptor.call(req)?;
+        if let Some(ref rate_limiter) = rate_limiter_option {
+            rate_limiter.check(&req)?;
+        }
+        Ok(req)
+    };
+    let max_request_size = request_limits.max_request_size();
 
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
@@ -183,11 +381,21 @@ pub async fn start_server(port: u16, remote_port: u16, loader: Loader) -> EmptyR
     // We need to wrap services in tonic_web::enable to enable Cross-Origin Resource Sharing (CORS),
     // i.e. setting Access-Control-Allow-* response headers.
     // See https://github.com/hyperium/tonic/pull/1326
+    // Responses can be large, but requests are capped separately to reject runaway payloads early.
     Server::builder()
         .accept_http1(true)
-        .add_service(tonic_web::enable(HistoryLoaderServiceServer::new(Arc::clone(&chm_server))))
-        .add_service(tonic_web::enable(HistoryDaoServiceServer::new(Arc::clone(&chm_server))))
-        .add_service(tonic_web::enable(MergeServiceServer::new(chm_server)))
+        .add_service(tonic_web::enable(InterceptedService::new(
+            with_grpc_config!(HistoryLoaderServiceServer::new(Arc::clone(&chm_server)))
+                .max_decoding_message_size(max_request_size),
+            interceptor.clone())))
+        .add_service(tonic_web::enable(InterceptedService::new(
+            with_grpc_config!(HistoryDaoServiceServer::new(Arc::clone(&chm_server)))
+                .max_decoding_message_size(max_request_size),
+            interceptor.clone())))
+        .add_service(tonic_web::enable(InterceptedService::new(
+            with_grpc_config!(MergeServiceServer::new(chm_server))
+                .max_decoding_message_size(max_request_size),
+            interceptor)))
         .add_service(reflection_service)
         .serve(addr)
         .await?;
@@ -213,7 +421,7 @@ pub async fn start_user_input_server<R: UserInputRequester>(remote_port: u16, as
     // See https://github.com/hyperium/tonic/pull/1326
     Server::builder()
         .accept_http1(true)
-        .add_service(tonic_web::enable(UserInputServiceServer::new(Arc::clone(&server))))
+        .add_service(tonic_web::enable(with_grpc_config!(UserInputServiceServer::new(Arc::clone(&server)))))
         .add_service(reflection_service)
         .serve(addr)
         .await?;
@@ -221,14 +429,30 @@ pub async fn start_user_input_server<R: UserInputRequester>(remote_port: u16, as
     Ok(())
 }
 
+// Lock is poisoned when a request panics while holding it. The panic has already been reported as a failure
+// of that request, so the lock is recovered rather than failing every subsequent request.
+// Note that a DAO might've been left mid-modification, which is why this is logged.
+
 fn lock_or_status<T>(target: &Mutex<T>) -> StatusResult<MutexGuard<'_, T>> {
-    target.lock().map_err(|_| Status::new(Code::Internal, "Mutex is poisoned!"))
+    Ok(target.lock().unwrap_or_else(|poisoned| {
+        log::warn!("Mutex was poisoned by a panicked request, recovering");
+        target.clear_poison();
+        poisoned.into_inner()
+    }))
 }
 
 fn read_or_status<T>(target: &RwLock<T>) -> StatusResult<RwLockReadGuard<'_, T>> {
-    target.read().map_err(|_| Status::new(Code::Internal, "RwLock is poisoned!"))
+    Ok(target.read().unwrap_or_else(|poisoned| {
+        log::warn!("RwLock was poisoned by a panicked request, recovering");
+        target.clear_poison();
+        poisoned.into_inner()
+    }))
 }
 
 fn write_or_status<T>(target: &RwLock<T>) -> StatusResult<RwLockWriteGuard<'_, T>> {
-    target.write().map_err(|_| Status::new(Code::Internal, "RwLock is poisoned!"))
+    Ok(target.write().unwrap_or_else(|poisoned| {
+        log::warn!("RwLock was poisoned by a panicked request, recovering");
+        target.clear_poison();
+        poisoned.into_inner()
+    }))
 }
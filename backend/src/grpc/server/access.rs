@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+use serde::Deserialize;
+use tonic::service::Interceptor;
+
+use super::*;
+
+#[cfg(test)]
+#[path = "access_tests.rs"]
+mod tests;
+
+const AUTHORIZATION_HEADER: &str = "authorization";
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// Named profile whose token holder can browse designated datasets only.
+///
+/// Restricted profiles are read-only: anything that modifies loaded databases, loads/closes them,
+/// merges or writes files on the server (e.g. exports) is denied.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AccessProfile {
+    pub name: String,
+    pub token: String,
+    /// UUIDs of visible datasets, absent for a profile with full access
+    #[serde(default)]
+    pub datasets: Option<HashSet<String>>,
+}
+
+impl AccessProfile {
+    pub fn can_see(&self, ds_uuid: &PbUuid) -> bool {
+        self.datasets.as_ref().is_none_or(|dss| dss.contains(&ds_uuid.value))
+    }
+}
+
+/// Profiles the server accepts, loaded from a JSON file with an array of [AccessProfile]s, e.g.
+/// ```json
+/// [
+///   { "name": "me",  "token": "<long random string>" },
+///   { "name": "mom", "token": "<another one>", "datasets": ["<dataset UUID>"] }
+/// ]
+/// ```
+/// Once profiles are configured, every request must carry `authorization: Bearer <token>` metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessProfiles(Vec<AccessProfile>);
+
+impl AccessProfiles {
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&json).with_context(|| format!("Failed to parse access profiles from {}", path.display()))
+    }
+
+    fn parse(json: &str) -> Result<Self> {
+        let profiles: Vec<AccessProfile> = serde_json::from_str(json)?;
+        ensure!(!profiles.is_empty(), "No access profiles defined");
+        for p in profiles.iter() {
+            ensure!(!p.token.trim().is_empty(), "Profile {} has an empty token", p.name);
+        }
+        ensure!(profiles.iter().map(|p| &p.token).all_unique(), "Profile tokens must be unique");
+        ensure!(profiles.iter().map(|p| &p.name).all_unique(), "Profile names must be unique");
+        Ok(AccessProfiles(profiles))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.0.iter().map(|p| p.name.as_str()).collect_vec()
+    }
+
+    fn find_by_token(&self, token: &str) -> Option<&AccessProfile> {
+        self.0.iter().find(|p| p.token == token)
+    }
+}
+
+/// Authenticates requests by their bearer tokens if profiles are configured, and attaches the matching
+/// [AccessProfile] to a request for handlers to check.
+#[derive(Clone)]
+pub struct AccessInterceptor {
+    pub profiles: Option<Arc<AccessProfiles>>,
+}
+
+impl Interceptor for AccessInterceptor {
+    fn call(&mut self, mut req: Request<()>) -> StatusResult<Request<()>> {
+        let Some(ref profiles) = self.profiles else { return Ok(req) };
+        let token = req.metadata().get(AUTHORIZATION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix(BEARER_PREFIX))
+            .ok_or_else(|| Status::unauthenticated("Access token is required"))?;
+        let profile = profiles.find_by_token(token.trim())
+            .ok_or_else(|| Status::unauthenticated("Unknown access token"))?;
+        req.extensions_mut().insert(Arc::new(profile.clone()));
+        Ok(req)
+    }
+}
+
+/// Restricted profile the request was made with, `None` means full access.
+pub fn restricted_profile<Q>(req: &Request<Q>) -> Option<Arc<AccessProfile>> {
+    req.extensions().get::<Arc<AccessProfile>>().filter(|p| p.datasets.is_some()).cloned()
+}
+
+/// Who made the request: profile name if profiles are configured, remote IP address otherwise (if known).
+/// Port is left out as it changes from one connection to another.
+pub fn client_name<Q>(req: &Request<Q>) -> Option<String> {
+    match req.extensions().get::<Arc<AccessProfile>>() {
+        Some(profile) => Some(profile.name.clone()),
+        None => req.remote_addr().map(|addr| addr.ip().to_string()),
+    }
+}
+
+pub fn ensure_full_access<Q>(req: &Request<Q>) -> StatusResult<()> {
+    match restricted_profile(req) {
+        None => Ok(()),
+        Some(p) => Err(Status::permission_denied(format!("Profile {} has read-only access", p.name))),
+    }
+}
+
+pub fn ensure_access<Q: AccessScoped>(req: &Request<Q>) -> StatusResult<()> {
+    let Some(profile) = restricted_profile(req) else { return Ok(()) };
+    let ds_uuids = match req.get_ref().access_scope() {
+        AccessScope::Any => return Ok(()),
+        AccessScope::Dataset(ds_uuid) => vec![ds_uuid],
+        AccessScope::Datasets(ds_uuids) => ds_uuids,
+        AccessScope::FullAccessOnly => return ensure_full_access(req),
+    };
+    match ds_uuids.into_iter().find(|ds_uuid| !profile.can_see(ds_uuid)) {
+        None => Ok(()),
+        Some(ds_uuid) =>
+            Err(Status::permission_denied(format!("Profile {} cannot access dataset {}", profile.name, ds_uuid.value))),
+    }
+}
+
+/// What a read-only request needs access to.
+pub enum AccessScope {
+    /// Not specific to a dataset and harmless to anyone, e.g. DAO name
+    Any,
+    Dataset(PbUuid),
+    /// All of the given datasets, e.g. when comparing two of them
+    Datasets(Vec<PbUuid>),
+    /// Writes files on the server, or is otherwise not meant for restricted profiles
+    FullAccessOnly,
+}
+
+pub trait AccessScoped {
+    fn access_scope(&self) -> AccessScope;
+}
+
+macro_rules! access_scoped_impl {
+    ($scope:ident: $($class:ident),+ $(,)?) => {
+        $( impl AccessScoped for $class {
+            fn access_scope(&self) -> AccessScope { access_scoped_impl!(@scope $scope, self) }
+        } )+
+    };
+    (@scope any, $self:ident) => { AccessScope::Any };
+    (@scope ds_uuid, $self:ident) => { AccessScope::Dataset($self.ds_uuid.clone()) };
+    (@scope chat, $self:ident) => { AccessScope::Dataset($self.chat.ds_uuid.clone()) };
+    (@scope full, $self:ident) => { AccessScope::FullAccessOnly };
+}
+
+// Datasets and chat folders responses are filtered instead
+access_scoped_impl!(any: NameRequest, StoragePathRequest, IsLoadedRequest, DatasetsRequest, ChatFoldersRequest);
+access_scoped_impl!(ds_uuid: DatasetRootRequest, UsersRequest, ChatsRequest, FingerprintRequest, NearDuplicatesRequest,
+                    RedactionLogRequest, RetentionRulesRequest, MediaAnnotationsRequest, GalleryRequest, LanguageStatsRequest,
+                    NotesRequest, SearchableStringSettingsRequest, MessageKindStatsRequest, BlockedUsersPolicyRequest,
+                    StorageUsageRequest, ReadMediaRequest);
+access_scoped_impl!(chat: ScrollMessagesRequest, MessagesPageRequest, LastMessagesRequest, MessagesBeforeRequest,
+                    MessagesAfterRequest, MessagesAroundRequest, MessagesSliceRequest, MessagesAbbreviatedSliceRequest,
+                    MessageOptionRequest, FirstMessageOnOrAfterRequest, MessagesCalendarRequest, ChatLinksRequest,
+                    MembershipTimelineRequest, MessageProvenanceRequest, ChatGeoPointsRequest,
+                    InteractionMatrixRequest, ChatNameHistoryRequest);
+access_scoped_impl!(full: SaveAsRequest, AuditLogRequest, ListTrashRequest, ExportChatHtmlRequest, ExportChatDocxRequest, ExportChatLocationsRequest,
+                    ExportChatMediaRequest, ExportSiteRequest, ExportFlatSqliteRequest, ExportParquetRequest, UserAliasesRequest);
+// Merge service requests
+access_scoped_impl!(full: AnalyzeRequest, MergeRequest, MergeTemplateRequest);
+
+impl AccessScoped for CompareChatsRequest {
+    fn access_scope(&self) -> AccessScope {
+        AccessScope::Datasets(vec![self.master_ds_uuid.clone(), self.slave_ds_uuid.clone()])
+    }
+}
+
+impl AccessScoped for ResolvePermalinkRequest {
+    fn access_scope(&self) -> AccessScope {
+        match self.permalink.parse::<crate::dao::permalink::Permalink>() {
+            Ok(permalink) => AccessScope::Dataset(permalink.ds_uuid),
+            // Will fail anyway
+            Err(_) => AccessScope::Any,
+        }
+    }
+}
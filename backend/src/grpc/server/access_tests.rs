@@ -0,0 +1,111 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+const PROFILES_JSON: &str = r#"[
+    { "name": "me",  "token": "full-token" },
+    { "name": "mom", "token": "restricted-token", "datasets": ["00000000-0000-0000-0000-000000000001"] }
+]"#;
+
+#[test]
+fn parsing_profiles() -> EmptyRes {
+    let profiles = AccessProfiles::parse(PROFILES_JSON)?;
+    assert_eq!(profiles.names(), vec!["me", "mom"]);
+
+    let full = profiles.find_by_token("full-token").unwrap();
+    assert_eq!(full.datasets, None);
+    assert!(full.can_see(&uuid(2)));
+
+    let restricted = profiles.find_by_token("restricted-token").unwrap();
+    assert!(restricted.can_see(&uuid(1)));
+    assert!(!restricted.can_see(&uuid(2)));
+
+    assert!(profiles.find_by_token("whatever").is_none());
+    Ok(())
+}
+
+#[test]
+fn parsing_invalid_profiles() {
+    assert!(AccessProfiles::parse("[]").is_err());
+    assert!(AccessProfiles::parse(r#"[{ "name": "me", "token": " " }]"#).is_err());
+    assert!(AccessProfiles::parse(r#"[
+        { "name": "me",  "token": "token" },
+        { "name": "mom", "token": "token" }
+    ]"#).is_err());
+    assert!(AccessProfiles::parse(r#"[
+        { "name": "me", "token": "token1" },
+        { "name": "me", "token": "token2" }
+    ]"#).is_err());
+}
+
+#[test]
+fn intercepting_requests() -> EmptyRes {
+    let mut no_profiles = AccessInterceptor { profiles: None };
+    let req = no_profiles.call(Request::new(())).unwrap();
+    assert!(restricted_profile(&req).is_none());
+    assert_eq!(client_name(&req), None);
+
+    let mut interceptor = AccessInterceptor { profiles: Some(Arc::new(AccessProfiles::parse(PROFILES_JSON)?)) };
+
+    let status = interceptor.call(Request::new(())).unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    let status = interceptor.call(request_with_token("wrong-token")).unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    let req = interceptor.call(request_with_token("full-token")).unwrap();
+    assert!(restricted_profile(&req).is_none());
+    assert_eq!(client_name(&req), Some("me".to_owned()));
+    assert!(ensure_full_access(&req).is_ok());
+
+    let req = interceptor.call(request_with_token("restricted-token")).unwrap();
+    assert_eq!(restricted_profile(&req).map(|p| p.name.clone()), Some("mom".to_owned()));
+    assert_eq!(ensure_full_access(&req).unwrap_err().code(), tonic::Code::PermissionDenied);
+    Ok(())
+}
+
+#[test]
+fn checking_access_scope() -> EmptyRes {
+    let mut interceptor = AccessInterceptor { profiles: Some(Arc::new(AccessProfiles::parse(PROFILES_JSON)?)) };
+    let profile = restricted_profile(&interceptor.call(request_with_token("restricted-token")).unwrap()).unwrap();
+
+    assert!(ensure_access(&with_profile(NameRequest { key: "key".to_owned() }, &profile)).is_ok());
+    assert!(ensure_access(&with_profile(UsersRequest { key: "key".to_owned(), ds_uuid: uuid(1) }, &profile)).is_ok());
+    let status = ensure_access(&with_profile(UsersRequest { key: "key".to_owned(), ds_uuid: uuid(2) }, &profile))
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    let compare = |master: u8, slave: u8| with_profile(CompareChatsRequest {
+        master_dao_key: "key".to_owned(),
+        master_ds_uuid: uuid(master),
+        slave_dao_key: "key".to_owned(),
+        slave_ds_uuid: uuid(slave),
+        chat_id_pairs: vec![],
+    }, &profile);
+    assert!(ensure_access(&compare(1, 1)).is_ok());
+    assert_eq!(ensure_access(&compare(1, 2)).unwrap_err().code(), tonic::Code::PermissionDenied);
+    assert_eq!(ensure_access(&compare(2, 1)).unwrap_err().code(), tonic::Code::PermissionDenied);
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn uuid(n: u8) -> PbUuid {
+    PbUuid { value: format!("00000000-0000-0000-0000-00000000000{n}") }
+}
+
+fn request_with_token(token: &str) -> Request<()> {
+    let mut req = Request::new(());
+    req.metadata_mut().insert(AUTHORIZATION_HEADER, format!("{BEARER_PREFIX}{token}").parse().unwrap());
+    req
+}
+
+fn with_profile<Q>(q: Q, profile: &Arc<AccessProfile>) -> Request<Q> {
+    let mut req = Request::new(q);
+    req.extensions_mut().insert(profile.clone());
+    req
+}
@@ -0,0 +1,55 @@
+use chrono::Local;
+
+use super::*;
+
+#[cfg(test)]
+#[path = "audit_tests.rs"]
+mod tests;
+
+/// Records mutating operations into the audit log of a DAO they were performed on.
+pub struct Auditor {
+    client_option: Option<String>,
+    operation: String,
+}
+
+impl Auditor {
+    /// Operation is named after the request type, e.g. `DeleteChatRequest` -> `DeleteChat`.
+    pub fn new<Q>(req: &Request<Q>) -> Self {
+        let type_name = std::any::type_name::<Q>();
+        let operation = type_name.rsplit("::").next().unwrap_or(type_name);
+        let operation = operation.strip_suffix("Request").unwrap_or(operation);
+        Auditor { client_option: access::client_name(req), operation: operation.to_owned() }
+    }
+
+    /// For operations the server performs on its own, e.g. periodic retention runs.
+    pub fn server(operation: &str) -> Self {
+        Auditor { client_option: None, operation: operation.to_owned() }
+    }
+
+    /// Record an entry, its timestamp, client and operation are filled in by the auditor.
+    pub fn record(&self, dao: &mut dyn ChatHistoryWriter, entry: AuditLogEntry) -> EmptyRes {
+        let entry = AuditLogEntry {
+            timestamp: Local::now().timestamp(),
+            client_option: self.client_option.clone(),
+            operation: self.operation.clone(),
+            ..entry
+        };
+        dao.record_audit_entry(entry)
+    }
+
+    /// Record a retention run, unless it didn't affect anything.
+    pub fn record_retention(&self,
+                            dao: &mut dyn ChatHistoryWriter,
+                            ds_uuid: &PbUuid,
+                            reports: &[RetentionRuleReport]) -> EmptyRes {
+        let affected_messages_count: i32 = reports.iter().map(|r| r.affected_messages_count).sum();
+        if affected_messages_count == 0 { return Ok(()); }
+        let deleted_files_count: i32 = reports.iter().map(|r| r.deleted_files_count).sum();
+        self.record(dao, AuditLogEntry {
+            ds_uuid_option: Some(ds_uuid.clone()),
+            affected_messages_count,
+            parameters: format!("{} rule(s), {deleted_files_count} file(s) deleted", reports.len()),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,65 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::sqlite_dao::SqliteDao;
+
+use super::*;
+
+#[test]
+fn operation_names() {
+    let req = Request::new(DeleteChatFolderRequest { key: "key".to_owned(), id: 1 });
+    assert_eq!(Auditor::new(&req).operation, "DeleteChatFolder");
+    assert_eq!(Auditor::new(&req).client_option, None);
+    assert_eq!(Auditor::server("ApplyRetentionRules").operation, "ApplyRetentionRules");
+}
+
+#[test]
+fn recording() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let mut dao = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME))?;
+    let dao = &mut dao;
+    let auditor = Auditor::server("ApplyRetentionRules");
+
+    // Nothing affected, nothing recorded
+    auditor.record_retention(dao, &ZERO_PB_UUID, &[])?;
+    assert_eq!(dao.audit_log()?, vec![]);
+
+    let rule = RetentionRule {
+        id: 1,
+        ds_uuid: ZERO_PB_UUID.clone(),
+        chat_id_option: None,
+        action: RetentionAction::DeleteMessages as i32,
+        max_age_days: 30,
+    };
+    let reports = [
+        RetentionRuleReport {
+            rule: rule.clone(),
+            affected_messages_count: 3,
+            deleted_files_count: 1,
+            deleted_files_bytes: 10,
+        },
+        RetentionRuleReport {
+            rule,
+            affected_messages_count: 2,
+            deleted_files_count: 0,
+            deleted_files_bytes: 0,
+        },
+    ];
+    auditor.record_retention(dao, &ZERO_PB_UUID, &reports)?;
+
+    let log = dao.audit_log()?;
+    assert_eq!(log.len(), 1);
+    assert!(log[0].timestamp > 0);
+    assert_eq!(log[0], AuditLogEntry {
+        timestamp: log[0].timestamp,
+        client_option: None,
+        operation: "ApplyRetentionRules".to_owned(),
+        ds_uuid_option: Some(ZERO_PB_UUID.clone()),
+        chat_id_option: None,
+        affected_chats_count: 0,
+        affected_messages_count: 5,
+        parameters: "2 rule(s), 1 file(s) deleted".to_owned(),
+    });
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::str::FromStr;
+
+use prost::bytes::{Buf, BufMut};
+use tonic::body::BoxBody;
+use tonic::client::{Grpc, GrpcService};
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::{Body, Bytes, StdError};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::server::NamedService;
+
+use super::*;
+
+type SharedServer = Arc<ChatHistoryManagerServer>;
+
+/// Backend running within the caller process (e.g. desktop UI), serving the same requests as the gRPC server
+/// but without a network hop. User input is requested from the caller directly rather than through
+/// a reverse gRPC connection. There's no access control, caller has full access.
+#[derive(Clone)]
+pub struct EmbeddedBackend {
+    server: SharedServer,
+}
+
+impl EmbeddedBackend {
+    /// Must be called within a Tokio runtime.
+    pub fn new(loader: Loader,
+               media_annotator_option: Option<Box<dyn MediaAnnotator>>,
+               user_input_requester: Box<dyn UserInputBlockingRequester>,
+               trash_retention: Duration) -> Self {
+        let server = ChatHistoryManagerServer::new_wrapped(
+            Handle::current(), loader, media_annotator_option, user_input_requester, trash_retention);
+        spawn_retention_executor(Arc::clone(&server));
+        EmbeddedBackend { server }
+    }
+
+    /// Perform a unary request given a full gRPC method path (e.g. `/history.HistoryDaoService/Chats`)
+    /// and a serialized request message, returning a serialized response message.
+    /// Failed requests yield [Status] errors, same as over gRPC.
+    pub async fn call(&self, method: &str, request: Vec<u8>) -> Result<Vec<u8>> {
+        let path = PathAndQuery::from_str(method).with_context(|| format!("Malformed method path {method}"))?;
+        let service_name = method.strip_prefix('/').and_then(|m| m.split_once('/')).map(|(s, _)| s).unwrap_or_default();
+        let server = Arc::clone(&self.server);
+        let response = if service_name == HistoryLoaderServiceServer::<SharedServer>::NAME {
+            unary(HistoryLoaderServiceServer::new(server), path, request).await
+        } else if service_name == HistoryDaoServiceServer::<SharedServer>::NAME {
+            unary(HistoryDaoServiceServer::new(server), path, request).await
+        } else if service_name == MergeServiceServer::<SharedServer>::NAME {
+            unary(MergeServiceServer::new(server), path, request).await
+        } else {
+            bail!("Unknown service in method path {method}")
+        };
+        Ok(response?)
+    }
+}
+
+async fn unary<S>(service: S, path: PathAndQuery, request: Vec<u8>) -> StatusResult<Vec<u8>>
+where
+    S: GrpcService<BoxBody>,
+    S::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <S::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let mut grpc = Grpc::new(service);
+    grpc.ready().await.map_err(|e| {
+        let e: StdError = e.into();
+        Status::unknown(format!("Service is not ready: {e}"))
+    })?;
+    let response = grpc.unary(Request::new(request), path, RawCodec).await?;
+    Ok(response.into_inner())
+}
+
+/// Passes already serialized messages as-is.
+#[derive(Clone, Copy, Default)]
+struct RawCodec;
+
+impl Codec for RawCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = RawCodec;
+    type Decoder = RawCodec;
+
+    fn encoder(&mut self) -> Self::Encoder { RawCodec }
+
+    fn decoder(&mut self) -> Self::Decoder { RawCodec }
+}
+
+impl Encoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> StdResult<(), Self::Error> {
+        dst.put_slice(&item);
+        Ok(())
+    }
+}
+
+impl Decoder for RawCodec {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> StdResult<Option<Self::Item>, Self::Error> {
+        let mut item = vec![0; src.remaining()];
+        src.copy_to_slice(&mut item);
+        Ok(Some(item))
+    }
+}
@@ -1,10 +1,35 @@
 use std::fs;
+use std::path::Path;
 use std::sync::Mutex;
 
 use itertools::Itertools;
 use tonic::Request;
 
+use crate::dao::duplicates;
+use crate::dao::duplicates::find_near_duplicates;
+use crate::dao::fingerprint::dataset_fingerprint;
+use crate::dao::gallery::{annotate_media, gallery};
+use crate::dao::interactions::{self, interaction_matrix};
+use crate::dao::language::language_stats;
+use crate::dao::membership::membership_timeline;
+use crate::dao::message_kinds::message_kind_stats;
+use crate::dao::notes::search_notes;
+use crate::dao::paging::{chats_page, messages_page};
+use crate::dao::permalink::{Permalink, resolve_permalink};
 use crate::dao::sqlite_dao::SqliteDao;
+use crate::dao::remote_media::media_source;
+use crate::dao::storage_usage::storage_usage;
+use crate::export::archive::{export_dir_encrypted, export_file_encrypted};
+use crate::export::columnar::export_parquet;
+use crate::export::docx::export_docx;
+use crate::export::geo::{export_locations, geo_points, GeoFormat};
+use crate::export::media::export_chat_media;
+use crate::export::flat_sqlite::export_flat_sqlite;
+use crate::export::html::HtmlExporter;
+use crate::export::site::export_site;
+use crate::export::stickers::StickerFormat;
+use crate::export::transcript::export_transcript;
+use crate::utils::http_client::shared_http_client;
 use crate::protobuf::history::history_dao_service_server::HistoryDaoService;
 
 use super::*;
@@ -21,6 +46,11 @@ macro_rules! with_dao_mut_by_key {
         let key = $req.get_ref().key.clone();
         $self.process_request_with_dao_mut($req, key, move |#[allow(unused)] $self_clone, #[allow(unused)] $req, $dao| { $code }).await
     }};
+    // Same, but also provides an auditor to record the operation with
+    ($self:ident, $self_clone:ident, $req:ident, $dao:ident, $auditor:ident, $code:block) => {{
+        let $auditor = Auditor::new(&$req);
+        with_dao_mut_by_key!($self, $self_clone, $req, $dao, $code)
+    }};
 }
 
 #[tonic::async_trait]
@@ -46,13 +76,15 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
                 }
             }
             let new_db_file = new_storage_path.join(SqliteDao::FILENAME);
-            let sqlite_dao = SqliteDao::create(&new_db_file)?;
+            let mut sqlite_dao = SqliteDao::create(&new_db_file)?;
             sqlite_dao.copy_datasets_from(dao, &dao.datasets()?.into_iter().map(|ds| ds.uuid).collect_vec())?;
+            sqlite_dao.copy_chat_folders_from(dao)?;
+            sqlite_dao.copy_audit_log_from(dao)?;
             let new_key = path_to_str(&new_db_file)?.to_owned();
             let name = sqlite_dao.name().to_owned();
             let storage_path = path_to_str(sqlite_dao.storage_path())?.to_owned();
             lock_or_status(&new_key_clone)?.replace(new_key.clone());
-            lock_or_status(&new_dao_clone)?.replace(DaoRwLock::new(Box::new(sqlite_dao)));
+            lock_or_status(&new_dao_clone)?.replace(DaoRwLock::new(LoadedDao::Writable(Box::new(sqlite_dao))));
             Ok(LoadedFile { key: new_key, name, storage_path })
         });
 
@@ -81,19 +113,36 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
     }
 
     async fn datasets(&self, req: Request<DatasetsRequest>) -> TonicResult<DatasetsResponse> {
+        let profile = access::restricted_profile(&req);
         with_dao_by_key!(self, self_clone, req, dao, {
-            Ok(DatasetsResponse { datasets: dao.datasets()? })
+            let mut datasets = dao.datasets()?;
+            if let Some(ref profile) = profile {
+                datasets.retain(|ds| profile.can_see(&ds.uuid));
+            }
+            Ok(DatasetsResponse { datasets })
         })
     }
 
     async fn dataset_root(&self, req: Request<DatasetRootRequest>) -> TonicResult<DatasetRootResponse> {
         with_dao_by_key!(self, self_clone, req, dao, {
             Ok(DatasetRootResponse {
-                path: dao.dataset_root(&req.ds_uuid)?.0.to_str().unwrap().to_owned()
+                path: dao.dataset_root(&req.ds_uuid)?.0.to_str().unwrap().to_owned(),
+                remote_media_root: dao.remote_media_root(&req.ds_uuid)?,
             })
         })
     }
 
+    async fn read_media(&self, req: Request<ReadMediaRequest>) -> TonicResult<ReadMediaResponse> {
+        access::ensure_access(&req)?;
+        self.process_request_blocking(req, |self_clone, req| {
+            // Remote file might take a while to fetch, DAO shouldn't stay locked all that time
+            let source = self_clone.with_dao(&req.key, |dao| media_source(dao, &req.ds_uuid, &req.path))?;
+            let (bytes, total_size) =
+                source.read(req.offset, req.length, shared_http_client(), &self_clone.remote_media_cache)?;
+            Ok(ReadMediaResponse { bytes, total_size })
+        }).await
+    }
+
 
     async fn users(&self, req: Request<UsersRequest>) -> TonicResult<UsersResponse> {
         with_dao_by_key!(self, self_clone, req, dao, {
@@ -103,12 +152,34 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
 
     async fn chats(&self, req: Request<ChatsRequest>) -> TonicResult<ChatsResponse> {
         with_dao_by_key!(self, self_clone, req, dao, {
-            Ok(ChatsResponse {
-                cwds: dao.chats(&req.ds_uuid)?
-                    .into_iter()
-                    .map(|cwd| cwd.into())
-                    .collect_vec()
-            })
+            let cwds = dao.chats(&req.ds_uuid)?
+                .into_iter()
+                .filter(|cwd| req.archived.is_none_or(|archived| cwd.chat.archived() == archived))
+                .filter(|cwd| req.hidden.is_none_or(|hidden| cwd.chat.hidden() == hidden))
+                .collect_vec();
+            let (cwds, next_page_token) = match req.limit {
+                Some(limit) => {
+                    ensure!(limit > 0, "Limit must be positive!");
+                    chats_page(cwds, req.page_token.as_deref(), limit as usize)?
+                }
+                None => {
+                    ensure!(req.page_token.is_none(), "Page token requires a limit!");
+                    (cwds, None)
+                }
+            };
+            let cwds: Vec<ChatWithDetailsPb> = cwds.into_iter()
+                .map(|cwd| {
+                    let former_names = dao.former_chat_names(&cwd.chat)?;
+                    ok(ChatWithDetailsPb { former_names, ..ChatWithDetailsPb::from(cwd) })
+                })
+                .try_collect()?;
+            Ok(ChatsResponse { cwds, next_page_token })
+        })
+    }
+
+    async fn chat_name_history(&self, req: Request<ChatNameHistoryRequest>) -> TonicResult<ChatNameHistoryResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(ChatNameHistoryResponse { entries: dao.chat_name_history(&req.chat)? })
         })
     }
 
@@ -120,6 +191,19 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
         })
     }
 
+    async fn messages_page(&self, req: Request<MessagesPageRequest>) -> TonicResult<MessagesPageResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            ensure!(req.limit > 0, "Limit must be positive!");
+            let (messages, next_page_token) = messages_page(dao,
+                                                            &req.chat,
+                                                            req.page_token.as_deref(),
+                                                            req.limit as usize,
+                                                            req.backward.unwrap_or(false),
+                                                            req.filter.as_ref())?;
+            Ok(MessagesPageResponse { messages, next_page_token })
+        })
+    }
+
     async fn last_messages(&self, req: Request<LastMessagesRequest>) -> TonicResult<MessagesResponse> {
         with_dao_by_key!(self, self_clone, req, dao, {
             Ok(MessagesResponse {
@@ -148,6 +232,17 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
         })
     }
 
+    async fn messages_around(&self, req: Request<MessagesAroundRequest>) -> TonicResult<MessagesResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(MessagesResponse {
+                messages: dao.messages_around(&req.chat,
+                                              MessageInternalId(req.message_internal_id),
+                                              req.before_limit as usize,
+                                              req.after_limit as usize)?
+            })
+        })
+    }
+
     async fn messages_slice(&self, req: Request<MessagesSliceRequest>) -> TonicResult<MessagesResponse> {
         with_dao_by_key!(self, self_clone, req, dao, {
             Ok(MessagesResponse {
@@ -188,6 +283,26 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
         })
     }
 
+    async fn first_message_on_or_after(&self, req: Request<FirstMessageOnOrAfterRequest>) -> TonicResult<MessageOptionResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(MessageOptionResponse {
+                message: dao.first_message_on_or_after(&req.chat, Timestamp(req.timestamp))?
+            })
+        })
+    }
+
+    async fn chat_links(&self, req: Request<ChatLinksRequest>) -> TonicResult<ChatLinksResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(ChatLinksResponse { links: dao.chat_links(&req.chat)? })
+        })
+    }
+
+    async fn messages_calendar(&self, req: Request<MessagesCalendarRequest>) -> TonicResult<MessagesCalendarResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(MessagesCalendarResponse { days: dao.messages_calendar(&req.chat)? })
+        })
+    }
+
     async fn is_loaded(&self, req: Request<IsLoadedRequest>) -> TonicResult<IsLoadedResponse> {
         with_dao_by_key!(self, self_clone, req, dao, {
             Ok(IsLoadedResponse {
@@ -196,76 +311,714 @@ impl HistoryDaoService for Arc<ChatHistoryManagerServer> {
         })
     }
 
+    async fn chat_folders(&self, req: Request<ChatFoldersRequest>) -> TonicResult<ChatFoldersResponse> {
+        let profile = access::restricted_profile(&req);
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let mut folders = dao.chat_folders()?;
+            if let Some(ref profile) = profile {
+                for folder in folders.iter_mut() {
+                    folder.chats.retain(|c| profile.can_see(&c.ds_uuid));
+                }
+            }
+            Ok(ChatFoldersResponse { folders })
+        })
+    }
+
+    async fn redaction_log(&self, req: Request<RedactionLogRequest>) -> TonicResult<RedactionLogResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(RedactionLogResponse { entries: dao.redaction_log(&req.ds_uuid)? })
+        })
+    }
+
+    async fn audit_log(&self, req: Request<AuditLogRequest>) -> TonicResult<AuditLogResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(AuditLogResponse { entries: dao.audit_log()? })
+        })
+    }
+
+    async fn list_trash(&self, req: Request<ListTrashRequest>) -> TonicResult<ListTrashResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(ListTrashResponse { items: dao.trash()? })
+        })
+    }
+
+    async fn retention_rules(&self, req: Request<RetentionRulesRequest>) -> TonicResult<RetentionRulesResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(RetentionRulesResponse { rules: dao.retention_rules(&req.ds_uuid)? })
+        })
+    }
+
+    async fn fingerprint(&self, req: Request<FingerprintRequest>) -> TonicResult<FingerprintResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(FingerprintResponse { fingerprint: dataset_fingerprint(dao, &req.ds_uuid)? })
+        })
+    }
+
+    async fn near_duplicates(&self, req: Request<NearDuplicatesRequest>) -> TonicResult<NearDuplicatesResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let groups = find_near_duplicates(
+                dao,
+                &req.ds_uuid,
+                req.min_text_length.map(|l| l as usize).unwrap_or(duplicates::DEFAULT_MIN_TEXT_LENGTH),
+                req.min_similarity.unwrap_or(duplicates::DEFAULT_MIN_SIMILARITY))?;
+            Ok(NearDuplicatesResponse { groups })
+        })
+    }
+
+    async fn membership_timeline(&self, req: Request<MembershipTimelineRequest>) -> TonicResult<MembershipTimelineResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            Ok(MembershipTimelineResponse { intervals: membership_timeline(dao, &cwd)? })
+        })
+    }
+
+    async fn language_stats(&self, req: Request<LanguageStatsRequest>) -> TonicResult<LanguageStatsResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(LanguageStatsResponse { stats: language_stats(dao, &req.ds_uuid, req.chat_id)? })
+        })
+    }
+
+    async fn message_kind_stats(&self, req: Request<MessageKindStatsRequest>) -> TonicResult<MessageKindStatsResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(MessageKindStatsResponse { stats: message_kind_stats(dao, &req.ds_uuid, req.chat_id)? })
+        })
+    }
+
+    async fn storage_usage(&self, req: Request<StorageUsageRequest>) -> TonicResult<StorageUsageResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let mut cache = lock_or_status(&self_clone.storage_usage_cache)?;
+            Ok(StorageUsageResponse { usage: storage_usage(dao, &req.key, &req.ds_uuid, &mut cache)? })
+        })
+    }
+
+    async fn interaction_matrix(&self, req: Request<InteractionMatrixRequest>) -> TonicResult<InteractionMatrixResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            interaction_matrix(dao, &cwd, req.max_gap_sec.unwrap_or(interactions::DEFAULT_MAX_GAP_SEC))
+        })
+    }
+
+    async fn message_provenance(&self, req: Request<MessageProvenanceRequest>) -> TonicResult<MessageProvenanceResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(MessageProvenanceResponse {
+                batch: dao.message_provenance(&req.chat, MessageInternalId(req.message_internal_id))?
+            })
+        })
+    }
+
+    async fn media_annotations(&self, req: Request<MediaAnnotationsRequest>) -> TonicResult<MediaAnnotationsResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(MediaAnnotationsResponse { annotations: dao.media_annotations(&req.ds_uuid)? })
+        })
+    }
+
+    async fn user_aliases(&self, req: Request<UserAliasesRequest>) -> TonicResult<UserAliasesResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(UserAliasesResponse { aliases: dao.user_aliases()? })
+        })
+    }
+
+    async fn notes(&self, req: Request<NotesRequest>) -> TonicResult<NotesResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let mut notes = dao.notes(&req.ds_uuid, req.chat_id)?;
+            if let Some(ref text) = req.text {
+                notes = search_notes(notes, text);
+            }
+            Ok(NotesResponse { notes })
+        })
+    }
+
+    async fn searchable_string_settings(&self, req: Request<SearchableStringSettingsRequest>) -> TonicResult<SearchableStringSettingsResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(SearchableStringSettingsResponse { settings: dao.searchable_string_settings(&req.ds_uuid)? })
+        })
+    }
+
+    async fn blocked_users_policy(&self, req: Request<BlockedUsersPolicyRequest>) -> TonicResult<BlockedUsersPolicyResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            Ok(BlockedUsersPolicyResponse { policy: dao.blocked_users_policy(&req.ds_uuid)? })
+        })
+    }
+
+    async fn gallery(&self, req: Request<GalleryRequest>) -> TonicResult<GalleryResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let items = gallery(dao, &req.ds_uuid, req.chat_id, &req.tags, req.offset as usize, req.limit as usize)?;
+            Ok(GalleryResponse { items })
+        })
+    }
+
+    async fn export_chat_html(&self, req: Request<ExportChatHtmlRequest>) -> TonicResult<ExportChatHtmlResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            let exporter = HtmlExporter::new(req.template_dir.as_deref().map(Path::new))?;
+            let sticker_format_option = resolve_sticker_format(req.sticker_conversion)?;
+            let path = exporter.export_chat(dao, &cwd, req.topic_id, sticker_format_option, Path::new(&req.output_dir))?;
+            Ok(ExportChatHtmlResponse { path: path_to_str(&path)?.to_owned() })
+        })
+    }
+
+    async fn export_chat_transcript(&self, req: Request<ExportChatHtmlRequest>) -> TonicResult<ExportChatHtmlResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            let exporter = HtmlExporter::new(req.template_dir.as_deref().map(Path::new))?;
+            let path = export_transcript(&exporter, dao, &cwd, req.topic_id, req.include_notes.unwrap_or(false),
+                                         Path::new(&req.output_dir))?;
+            Ok(ExportChatHtmlResponse { path: path_to_str(&path)?.to_owned() })
+        })
+    }
+
+    async fn export_site(&self, req: Request<ExportSiteRequest>) -> TonicResult<ExportSiteResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let exporter = HtmlExporter::new(req.template_dir.as_deref().map(Path::new))?;
+            let output_dir = Path::new(&req.output_dir);
+            let sticker_format_option = resolve_sticker_format(req.sticker_conversion)?;
+            let index_path = match req.archive_password {
+                Some(ref password) => export_dir_encrypted(output_dir, password, |dir| {
+                    export_site(&exporter, dao, &req.ds_uuid, sticker_format_option, dir).map(|_| ())
+                })?,
+                None => export_site(&exporter, dao, &req.ds_uuid, sticker_format_option, output_dir)?,
+            };
+            Ok(ExportSiteResponse { index_path: path_to_str(&index_path)?.to_owned() })
+        })
+    }
+
+    async fn export_chat_docx(&self, req: Request<ExportChatDocxRequest>) -> TonicResult<ExportChatDocxResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            let sticker_format_option = resolve_sticker_format(req.sticker_conversion)?;
+            let output_dir = Path::new(&req.output_dir);
+            let path = match req.archive_password {
+                Some(ref password) => export_file_encrypted(output_dir, password, |dir| {
+                    export_docx(dao, &cwd, req.topic_id, sticker_format_option, dir)
+                })?,
+                None => export_docx(dao, &cwd, req.topic_id, sticker_format_option, output_dir)?,
+            };
+            Ok(ExportChatDocxResponse { path: path_to_str(&path)?.to_owned() })
+        })
+    }
+
+    async fn chat_geo_points(&self, req: Request<ChatGeoPointsRequest>) -> TonicResult<ChatGeoPointsResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            Ok(ChatGeoPointsResponse { points: geo_points(dao, &cwd, req.from_id)? })
+        })
+    }
+
+    async fn export_chat_locations(&self, req: Request<ExportChatLocationsRequest>) -> TonicResult<ExportChatLocationsResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            let format = match LocationExportFormat::resolve(req.format)? {
+                LocationExportFormat::Gpx => GeoFormat::Gpx,
+                LocationExportFormat::Kml => GeoFormat::Kml,
+                LocationExportFormat::Geojson => GeoFormat::GeoJson,
+            };
+            let path = export_locations(dao, &cwd, format, Path::new(&req.output_dir))?;
+            Ok(ExportChatLocationsResponse { path: path_to_str(&path)?.to_owned() })
+        })
+    }
+
+    async fn export_chat_media(&self, req: Request<ExportChatMediaRequest>) -> TonicResult<ExportChatMediaResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let cwd = dao.chat_option(&req.chat.ds_uuid, req.chat.id)?
+                .with_context(|| format!("Chat {} not found", req.chat.qualified_name()))?;
+            let kinds: Vec<_> = req.kinds.iter().map(|k| MediaKind::resolve(*k)).try_collect()?;
+            let paths = export_chat_media(dao, &cwd, &kinds, req.from_timestamp, req.to_timestamp,
+                                          Path::new(&req.output_dir))?;
+            let paths = paths.iter().map(|p| path_to_str(p).map(|s| s.to_owned())).try_collect()?;
+            Ok(ExportChatMediaResponse { paths })
+        })
+    }
+
+    async fn export_flat_sqlite(&self, req: Request<ExportFlatSqliteRequest>) -> TonicResult<Empty> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            export_flat_sqlite(dao, &req.ds_uuid, Path::new(&req.output_file))?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn export_parquet(&self, req: Request<ExportParquetRequest>) -> TonicResult<ExportParquetResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            let paths = export_parquet(dao, &req.ds_uuid, Path::new(&req.output_dir))?;
+            let paths = paths.iter().map(|p| path_to_str(p).map(|s| s.to_owned())).try_collect()?;
+            Ok(ExportParquetResponse { paths })
+        })
+    }
+
+    async fn resolve_permalink(&self, req: Request<ResolvePermalinkRequest>) -> TonicResult<ResolvePermalinkResponse> {
+        with_dao_by_key!(self, self_clone, req, dao, {
+            ensure!(req.context_limit >= 0, "Context limit is negative!");
+            let permalink: Permalink = req.permalink.parse()?;
+            Ok(match resolve_permalink(dao, &permalink, req.context_limit as usize)? {
+                Some(resolved) => ResolvePermalinkResponse {
+                    chat: Some(resolved.chat),
+                    messages_before: resolved.messages_before,
+                    message: Some(resolved.message),
+                    messages_after: resolved.messages_after,
+                },
+                None => ResolvePermalinkResponse {
+                    chat: None,
+                    messages_before: vec![],
+                    message: None,
+                    messages_after: vec![],
+                }
+            })
+        })
+    }
+
     //
     // Mutable DAO endpoints
     //
 
     async fn backup(&self, req: Request<BackupRequest>) -> TonicResult<Empty> {
         with_dao_mut_by_key!(self, self_clone, req, dao, {
-            // If DAO does not support backups, silently ignore the call
-            if let Ok(dao_m) = dao.as_mutable() {
-                dao_m.backup()?;
-            }
+            dao.backup()?;
             Ok(Empty {})
         })
     }
 
     async fn update_dataset(&self, req: Request<UpdateDatasetRequest>) -> TonicResult<UpdateDatasetResponse> {
-        with_dao_mut_by_key!(self, self_clone, req, dao, {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
             let dataset = req.dataset.clone();
-            let dataset = dao.as_mutable()?.update_dataset(dataset.uuid.clone(), dataset)?;
+            let dataset = dao.update_dataset(dataset.uuid.clone(), dataset)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(dataset.uuid.clone()),
+                parameters: format!("alias '{}'", dataset.alias),
+                ..Default::default()
+            })?;
             Ok(UpdateDatasetResponse { dataset })
         })
     }
 
     async fn delete_dataset(&self, req: Request<DeleteDatasetRequest>) -> TonicResult<Empty> {
-        with_dao_mut_by_key!(self, self_clone, req, dao, {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
             let uuid = req.uuid.clone();
-            dao.as_mutable()?.delete_dataset(uuid)?;
+            let alias = dao.datasets()?.into_iter()
+                .find(|ds| ds.uuid == uuid)
+                .map(|ds| ds.alias)
+                .unwrap_or_default();
+            let chats = dao.chats(&uuid)?;
+            dao.delete_dataset(uuid.clone())?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(uuid),
+                affected_chats_count: chats.len() as i32,
+                affected_messages_count: chats.iter().map(|cwd| cwd.chat.msg_count).sum(),
+                parameters: format!("alias '{alias}'"),
+                ..Default::default()
+            })?;
             Ok(Empty {})
         })
     }
 
     async fn shift_dataset_time(&self, req: Request<ShiftDatasetTimeRequest>) -> TonicResult<Empty> {
-        with_dao_mut_by_key!(self, self_clone, req, dao, {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
             let uuid = req.uuid.clone();
             dao.as_shiftable()?.shift_dataset_time(&uuid, req.hours_shift)?;
+            let chats = dao.chats(&uuid)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(uuid),
+                affected_chats_count: chats.len() as i32,
+                affected_messages_count: chats.iter().map(|cwd| cwd.chat.msg_count).sum(),
+                parameters: format!("shift by {} hour(s)", req.hours_shift),
+                ..Default::default()
+            })?;
             Ok(Empty {})
         })
     }
 
     async fn update_user(&self, req: Request<UpdateUserRequest>) -> TonicResult<UpdateUserResponse> {
-        with_dao_mut_by_key!(self, self_clone, req, dao, {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
             let user = req.user.clone();
-            let user = dao.as_mutable()?.update_user(user.id(), user)?;
+            let user = dao.update_user(user.id(), user)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(user.ds_uuid.clone()),
+                parameters: format!("user #{}", user.id),
+                ..Default::default()
+            })?;
             Ok(UpdateUserResponse { user })
         })
     }
 
     async fn update_chat(&self, req: Request<UpdateChatRequest>) -> TonicResult<UpdateChatResponse> {
-        with_dao_mut_by_key!(self, self_clone, req, dao, {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
             let uuid = req.uuid.clone();
             let old_cwd = dao.chat_option(&uuid, req.old_id)?.context("Chat not found")?;
             let chat = Chat { id: req.new_id, ..old_cwd.chat };
-            let chat = dao.as_mutable()?.update_chat(ChatId(req.old_id), chat)?;
+            let chat = dao.update_chat(ChatId(req.old_id), chat)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(uuid),
+                chat_id_option: Some(chat.id),
+                affected_chats_count: 1,
+                parameters: format!("chat ID #{} -> #{}", req.old_id, req.new_id),
+                ..Default::default()
+            })?;
+            Ok(UpdateChatResponse { chat })
+        })
+    }
+
+    async fn update_chat_flags(&self, req: Request<UpdateChatFlagsRequest>) -> TonicResult<UpdateChatResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let old_cwd = dao.chat_option(&req.uuid, req.id)?.context("Chat not found")?;
+            let chat = Chat { archived: Some(req.archived), hidden: Some(req.hidden), ..old_cwd.chat };
+            let chat = dao.update_chat(ChatId(req.id), chat)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.uuid.clone()),
+                chat_id_option: Some(chat.id),
+                affected_chats_count: 1,
+                parameters: format!("archived: {}, hidden: {}", req.archived, req.hidden),
+                ..Default::default()
+            })?;
             Ok(UpdateChatResponse { chat })
         })
     }
 
     async fn delete_chat(&self, req: Request<DeleteChatRequest>) -> TonicResult<Empty> {
-        with_dao_mut_by_key!(self, self_clone, req, dao, {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
             let chat = req.chat.clone();
-            dao.as_mutable()?.delete_chat(chat)?;
+            dao.delete_chat(chat)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.chat.ds_uuid.clone()),
+                chat_id_option: Some(req.chat.id),
+                affected_chats_count: 1,
+                affected_messages_count: req.chat.msg_count,
+                parameters: format!("chat {}", req.chat.qualified_name()),
+                ..Default::default()
+            })?;
             Ok(Empty {})
         })
     }
 
     async fn combine_chats(&self, req: Request<CombineChatsRequest>) -> TonicResult<Empty> {
-        with_dao_mut_by_key!(self, self_clone, req, dao, {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
             let master_chat = req.master_chat.clone();
             let slave_chat = req.slave_chat.clone();
-            dao.as_mutable()?.combine_chats(master_chat, slave_chat)?;
+            dao.combine_chats(master_chat, slave_chat)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.master_chat.ds_uuid.clone()),
+                chat_id_option: Some(req.master_chat.id),
+                affected_chats_count: 2,
+                parameters: format!("master chat {}, slave chat {}",
+                                    req.master_chat.qualified_name(), req.slave_chat.qualified_name()),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn override_myself(&self, req: Request<OverrideMyselfRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.override_myself(&req.ds_uuid, UserId(req.new_myself_id), req.chat_id.map(ChatId))?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                chat_id_option: req.chat_id,
+                parameters: format!("new myself #{}", req.new_myself_id),
+                ..Default::default()
+            })?;
             Ok(Empty {})
         })
     }
+
+    async fn insert_chat_folder(&self, req: Request<InsertChatFolderRequest>) -> TonicResult<InsertChatFolderResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let folder = dao.insert_chat_folder(req.folder.clone())?;
+            auditor.record(dao, AuditLogEntry {
+                parameters: format!("folder '{}' (#{})", folder.name, folder.id),
+                ..Default::default()
+            })?;
+            Ok(InsertChatFolderResponse { folder })
+        })
+    }
+
+    async fn update_chat_folder(&self, req: Request<UpdateChatFolderRequest>) -> TonicResult<UpdateChatFolderResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let folder = dao.update_chat_folder(req.folder.clone())?;
+            auditor.record(dao, AuditLogEntry {
+                parameters: format!("folder '{}' (#{})", folder.name, folder.id),
+                ..Default::default()
+            })?;
+            Ok(UpdateChatFolderResponse { folder })
+        })
+    }
+
+    async fn delete_chat_folder(&self, req: Request<DeleteChatFolderRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.delete_chat_folder(req.id)?;
+            auditor.record(dao, AuditLogEntry {
+                parameters: format!("folder #{}", req.id),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn redact_messages(&self, req: Request<RedactMessagesRequest>) -> TonicResult<RedactionResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let internal_ids = req.internal_ids.iter().map(|&id| MessageInternalId(id)).collect_vec();
+            let entry = dao.redact_messages(&req.chat, &internal_ids)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.chat.ds_uuid.clone()),
+                chat_id_option: Some(req.chat.id),
+                affected_chats_count: 1,
+                affected_messages_count: entry.deleted_messages_count,
+                parameters: format!("chat {}", req.chat.qualified_name()),
+                ..Default::default()
+            })?;
+            Ok(RedactionResponse { entry })
+        })
+    }
+
+    async fn redact_strings(&self, req: Request<RedactStringsRequest>) -> TonicResult<RedactionResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let entry = dao.redact_strings(&req.ds_uuid, req.chat_id_option.map(ChatId), &req.strings)?;
+            // Redacted strings themselves must not end up in the log
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                chat_id_option: req.chat_id_option,
+                affected_chats_count: if req.chat_id_option.is_some() { 1 } else { 0 },
+                affected_messages_count: entry.scrubbed_messages_count,
+                parameters: format!("{} string(s), {} occurrence(s)", req.strings.len(), entry.scrubbed_strings_count),
+                ..Default::default()
+            })?;
+            Ok(RedactionResponse { entry })
+        })
+    }
+
+    async fn insert_retention_rule(&self, req: Request<InsertRetentionRuleRequest>) -> TonicResult<InsertRetentionRuleResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let rule = dao.insert_retention_rule(req.rule.clone())?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(rule.ds_uuid.clone()),
+                chat_id_option: rule.chat_id_option,
+                parameters: format!("rule #{}: {:?} after {} day(s)",
+                                    rule.id, RetentionAction::resolve(rule.action)?, rule.max_age_days),
+                ..Default::default()
+            })?;
+            Ok(InsertRetentionRuleResponse { rule })
+        })
+    }
+
+    async fn delete_retention_rule(&self, req: Request<DeleteRetentionRuleRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.delete_retention_rule(req.id)?;
+            auditor.record(dao, AuditLogEntry {
+                parameters: format!("rule #{}", req.id),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn apply_retention_rules(&self, req: Request<ApplyRetentionRulesRequest>) -> TonicResult<ApplyRetentionRulesResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let now = Timestamp(Local::now().timestamp());
+            let reports = dao.apply_retention_rules(&req.ds_uuid, now, req.dry_run)?;
+            if !req.dry_run {
+                auditor.record_retention(dao, &req.ds_uuid, &reports)?;
+            }
+            Ok(ApplyRetentionRulesResponse { reports })
+        })
+    }
+
+    async fn set_media_annotations(&self, req: Request<SetMediaAnnotationsRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.set_media_annotations(&req.ds_uuid, &req.path, &req.source, &req.tags)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                parameters: format!("{} by {}: {}", req.path, req.source, req.tags.iter().join(", ")),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn annotate_media(&self, req: Request<AnnotateMediaRequest>) -> TonicResult<AnnotateMediaResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let annotator = self_clone.media_annotator_option.as_ref()
+                .context("Media annotator is not configured on the server")?;
+            let annotated_files_count = annotate_media(dao, &req.ds_uuid, annotator.as_ref())?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                parameters: format!("{} file(s) by {}", annotated_files_count, annotator.name()),
+                ..Default::default()
+            })?;
+            Ok(AnnotateMediaResponse { annotated_files_count: annotated_files_count as i32 })
+        })
+    }
+
+    async fn insert_user_alias(&self, req: Request<UserAliasRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.insert_user_alias(&req.alias)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.alias.ds_uuid.clone()),
+                parameters: format_user_alias(&req.alias),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn delete_user_alias(&self, req: Request<UserAliasRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.delete_user_alias(&req.alias)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.alias.ds_uuid.clone()),
+                parameters: format_user_alias(&req.alias),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn insert_note(&self, req: Request<InsertNoteRequest>) -> TonicResult<NoteResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let note = Note { timestamp: Local::now().timestamp(), ..req.note.clone() };
+            let note = dao.insert_note(note)?;
+            // Note text is private, so it's not recorded
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(note.ds_uuid.clone()),
+                chat_id_option: Some(note.chat_id),
+                parameters: format!("note #{}", note.id),
+                ..Default::default()
+            })?;
+            Ok(NoteResponse { note })
+        })
+    }
+
+    async fn update_note(&self, req: Request<UpdateNoteRequest>) -> TonicResult<NoteResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let now = Timestamp(Local::now().timestamp());
+            let note = dao.update_note(req.id, &req.text, now)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(note.ds_uuid.clone()),
+                chat_id_option: Some(note.chat_id),
+                parameters: format!("note #{}", note.id),
+                ..Default::default()
+            })?;
+            Ok(NoteResponse { note })
+        })
+    }
+
+    async fn delete_note(&self, req: Request<DeleteNoteRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.delete_note(req.id)?;
+            auditor.record(dao, AuditLogEntry {
+                parameters: format!("note #{}", req.id),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn update_searchable_string_settings(&self, req: Request<UpdateSearchableStringSettingsRequest>) -> TonicResult<RecomputeSearchableStringsResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.update_searchable_string_settings(&req.ds_uuid, &req.settings)?;
+            let changed_count = dao.recompute_searchable_strings(&req.ds_uuid)?;
+            let settings = &req.settings;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                parameters: format!("file names: {}, coordinates: {}, phone numbers: {:?}, transliterate: {}; \
+                                     {changed_count} message(s) changed",
+                                    settings.include_file_names(), settings.include_coordinates(),
+                                    settings.phone_number_format(), settings.transliterate()),
+                ..Default::default()
+            })?;
+            Ok(RecomputeSearchableStringsResponse { changed_messages_count: changed_count as i32 })
+        })
+    }
+
+    async fn update_blocked_users_policy(&self, req: Request<UpdateBlockedUsersPolicyRequest>) -> TonicResult<ApplyBlockedUsersPolicyResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.update_blocked_users_policy(&req.ds_uuid, &req.policy)?;
+            let affected_count = dao.apply_blocked_users_policy(&req.ds_uuid)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                affected_messages_count: affected_count as i32,
+                parameters: format!("{:?} messages of users {:?}", req.policy.action(), req.policy.user_ids),
+                ..Default::default()
+            })?;
+            Ok(ApplyBlockedUsersPolicyResponse { affected_messages_count: affected_count as i32 })
+        })
+    }
+
+    async fn apply_blocked_users_policy(&self, req: Request<ApplyBlockedUsersPolicyRequest>) -> TonicResult<ApplyBlockedUsersPolicyResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let affected_count = dao.apply_blocked_users_policy(&req.ds_uuid)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                affected_messages_count: affected_count as i32,
+                ..Default::default()
+            })?;
+            Ok(ApplyBlockedUsersPolicyResponse { affected_messages_count: affected_count as i32 })
+        })
+    }
+
+    async fn update_remote_media_root(&self, req: Request<UpdateRemoteMediaRootRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            dao.update_remote_media_root(&req.ds_uuid, req.remote_media_root.as_deref())?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                parameters: req.remote_media_root.clone().unwrap_or_else(|| "cleared".to_owned()),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn recompute_searchable_strings(&self, req: Request<RecomputeSearchableStringsRequest>) -> TonicResult<RecomputeSearchableStringsResponse> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let changed_count = dao.recompute_searchable_strings(&req.ds_uuid)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(req.ds_uuid.clone()),
+                parameters: format!("{changed_count} message(s) changed"),
+                ..Default::default()
+            })?;
+            Ok(RecomputeSearchableStringsResponse { changed_messages_count: changed_count as i32 })
+        })
+    }
+
+    async fn restore(&self, req: Request<RestoreRequest>) -> TonicResult<Empty> {
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let item = dao.trash()?.into_iter().find(|item| item.id == req.id)
+                .with_context(|| format!("Trash item {} not found", req.id))?;
+            dao.restore_from_trash(item.id)?;
+            auditor.record(dao, AuditLogEntry {
+                ds_uuid_option: Some(item.ds_uuid),
+                chat_id_option: item.chat_id_option,
+                affected_chats_count: item.chats_count,
+                affected_messages_count: item.messages_count,
+                parameters: format!("trash item #{}, '{}'", item.id, item.name),
+                ..Default::default()
+            })?;
+            Ok(Empty {})
+        })
+    }
+
+    async fn compact(&self, req: Request<CompactRequest>) -> TonicResult<CompactionReport> {
+        // Holding a write lock, so no reads can happen mid-way
+        with_dao_mut_by_key!(self, self_clone, req, dao, auditor, {
+            let report = dao.compact()?;
+            auditor.record(dao, AuditLogEntry {
+                parameters: format!("{} -> {} bytes", report.size_before_bytes, report.size_after_bytes),
+                ..Default::default()
+            })?;
+            Ok(report)
+        })
+    }
+}
+
+fn resolve_sticker_format(sticker_conversion: Option<i32>) -> Result<Option<StickerFormat>> {
+    sticker_conversion.map(|sc| Ok(match StickerConversion::resolve(sc)? {
+        StickerConversion::Gif => StickerFormat::Gif,
+        StickerConversion::Apng => StickerFormat::Apng,
+    })).transpose()
+}
+
+fn format_user_alias(alias: &UserAlias) -> String {
+    format!("user #{} = user #{} of dataset {}", alias.user_id, alias.other_user_id, alias.other_ds_uuid.value)
 }
@@ -1,7 +1,13 @@
 use std::fs;
+use std::path::Path;
 
+use itertools::Itertools;
 use tonic::Request;
 
+use crate::dao::fingerprint::file_sha256;
+use crate::loader::synthetic;
+use crate::merge::comparison::{find_divergences, verify_against_source};
+use crate::merge::sync::append_sync;
 use crate::protobuf::history::history_loader_service_server::*;
 
 use super::*;
@@ -9,60 +15,287 @@ use super::*;
 #[tonic::async_trait]
 impl HistoryLoaderService for Arc<ChatHistoryManagerServer> {
     async fn load(&self, req: Request<LoadRequest>) -> TonicResult<LoadResponse> {
-        self.process_request_blocking(req, move |self_clone, req| {
+        access::ensure_full_access(&req)?;
+        let auditor = Auditor::new(&req);
+        self.process_import_request(req, move |self_clone, req| {
             let path = fs::canonicalize(&req.path)?;
 
             if let Some(dao) = read_or_status(&self_clone.loaded_daos)?.get(&req.key) {
                 let dao = read_or_status(dao)?;
-                return Ok(LoadResponse { name: dao.name().to_owned() });
+                return Ok(LoadResponse { name: dao.reader().name().to_owned(), existing_key: None, divergences: vec![] });
             }
 
-            let dao = self_clone.loader.load(&path, self_clone.user_input_requester.as_ref())?;
-            let response = LoadResponse { name: dao.name().to_owned() };
+            let previous_imports = find_previous_imports(&self_clone, &path)?;
+            if !previous_imports.is_empty() {
+                let path_str = path_to_str(&path)?;
+                let (action, idx) =
+                    self_clone.user_input_requester.choose_reimport_action(path_str, &previous_imports)?;
+                match action {
+                    ReimportAction::Skip => {
+                        let prev = &previous_imports[idx];
+                        log::info!("{path_str} was already imported into {}, skipping", prev.dao_name);
+                        return Ok(LoadResponse {
+                            name: prev.dao_name.clone(),
+                            existing_key: Some(prev.dao_key.clone()),
+                            divergences: vec![],
+                        });
+                    }
+                    ReimportAction::NewDataset => { /* Proceed as usual */ }
+                    ReimportAction::AppendSync => {
+                        let prev = &previous_imports[idx];
+                        let src = self_clone.loader.load(&path, self_clone.user_input_requester.as_ref(), req.media_policy.as_ref())?;
+                        let src = src.reader();
+                        let src_ds_uuid = src.datasets()?.first().context("Loaded file has no datasets")?.uuid.clone();
+
+                        let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
+                        let dst = loaded_daos.get(&prev.dao_key)
+                            .ok_or_else(|| anyhow!("Database with key {} is not loaded!", prev.dao_key))?;
+                        let mut dst = write_or_status(dst)?;
+                        let dst = dst.writer()?;
+                        let report = append_sync(src, &src_ds_uuid, dst, &prev.dataset.uuid)?;
+                        auditor.record(dst, AuditLogEntry {
+                            ds_uuid_option: Some(prev.dataset.uuid.clone()),
+                            affected_chats_count: report.new_chats as i32,
+                            affected_messages_count: report.new_messages as i32,
+                            parameters: format!("append-sync from {path_str}, {} new user(s)", report.new_users),
+                            ..Default::default()
+                        })?;
+                        return Ok(LoadResponse {
+                            name: prev.dao_name.clone(),
+                            existing_key: Some(prev.dao_key.clone()),
+                            divergences: vec![],
+                        });
+                    }
+                }
+            }
+
+            let dao = self_clone.loader.load(&path, self_clone.user_input_requester.as_ref(), req.media_policy.as_ref())?;
+            let name = dao.reader().name().to_owned();
             write_or_status(&self_clone.loaded_daos)?.insert(req.key.clone(), DaoRwLock::new(dao));
-            Ok(response)
+            let divergences = find_loaded_divergences(&self_clone, &req.key)?;
+            for divergence in divergences.iter() {
+                log::warn!("Dataset '{}' of {} diverged from its copy in {}", divergence.dataset.alias, req.key, divergence.other_dao_key);
+            }
+            Ok(LoadResponse { name, existing_key: None, divergences })
         }).await
     }
 
     async fn get_loaded_files(&self, req: Request<Empty>) -> TonicResult<GetLoadedFilesResponse> {
-        self.process_request_blocking(req, |self_clone, _| {
-            fn dao_to_loaded_file((k, dao): (&DaoKey, &DaoRwLock)) -> StatusResult<LoadedFile> {
+        let profile = access::restricted_profile(&req);
+        self.process_request_blocking(req, move |self_clone, _| {
+            let mut files = vec![];
+            for (k, dao) in read_or_status(&self_clone.loaded_daos)?.iter() {
                 let dao = read_or_status(dao)?;
-                Ok(LoadedFile {
+                let dao = dao.reader();
+                // Restricted profiles only see files having datasets visible to them
+                if let Some(ref profile) = profile {
+                    if !dao.datasets()?.iter().any(|ds| profile.can_see(&ds.uuid)) { continue; }
+                }
+                files.push(LoadedFile {
                     key: k.clone(),
                     name: dao.name().to_owned(),
                     storage_path: path_to_str(dao.storage_path()).expect("storage path").to_owned()
-                })
+                });
             }
-            let files: StatusResult<Vec<_>> = read_or_status(&self_clone.loaded_daos)?.iter()
-                .map(dao_to_loaded_file)
-                .collect();
-            Ok(GetLoadedFilesResponse { files: files? })
+            Ok(GetLoadedFilesResponse { files })
         }).await
     }
 
     async fn close(&self, req: Request<CloseRequest>) -> TonicResult<Empty> {
+        access::ensure_full_access(&req)?;
         self.process_request_blocking(req, |self_clone, req| {
             let dao = write_or_status(&self_clone.loaded_daos)?.shift_remove(&req.key);
             if dao.is_none() {
                 bail!("Database {} is not open!", req.key)
             }
+            lock_or_status(&self_clone.storage_usage_cache)?.forget(&req.key);
             Ok(Empty {})
         }).await
     }
 
     async fn ensure_same(&self, req: Request<EnsureSameRequest>) -> TonicResult<EnsureSameResponse> {
-        const MAX_DIFFS: usize = 10;
+        access::ensure_full_access(&req)?;
+        const DEFAULT_MAX_DIFFS: usize = 10;
 
         self.process_request_blocking(req, |self_clone, req| {
+            let max_diffs = match req.max_diffs {
+                Some(max_diffs) => {
+                    ensure!(max_diffs > 0, "Max diffs must be positive!");
+                    max_diffs as usize
+                }
+                None => DEFAULT_MAX_DIFFS,
+            };
+            let options = dao::DiffOptions {
+                timestamp_precision_sec: req.timestamp_precision_sec,
+                ignore_text_formatting: req.ignore_text_formatting.unwrap_or_default(),
+                check_searchable_strings: req.check_searchable_strings.unwrap_or_default(),
+                chat_ids: req.chat_ids.clone(),
+            };
             let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
             let master_dao = read_or_status(&loaded_daos[&req.master_dao_key])?;
             let slave_dao = read_or_status(&loaded_daos[&req.slave_dao_key])?;
             let diffs = dao::get_datasets_diff(
-                (*master_dao).as_ref(), &req.master_ds_uuid,
-                (*slave_dao).as_ref(), &req.slave_ds_uuid,
-                MAX_DIFFS)?;
+                master_dao.reader(), &req.master_ds_uuid,
+                slave_dao.reader(), &req.slave_ds_uuid,
+                max_diffs, &options)?;
             Ok(EnsureSameResponse { diffs })
         }).await
     }
+
+    async fn find_divergences(&self, req: Request<FindDivergencesRequest>) -> TonicResult<FindDivergencesResponse> {
+        access::ensure_full_access(&req)?;
+        self.process_request_blocking(req, |self_clone, req| {
+            Ok(FindDivergencesResponse { divergences: find_loaded_divergences(&self_clone, &req.key)? })
+        }).await
+    }
+
+    async fn verify_against_source(&self, req: Request<VerifyAgainstSourceRequest>) -> TonicResult<VerifyAgainstSourceResponse> {
+        access::ensure_full_access(&req)?;
+        const DEFAULT_MAX_MESSAGES_PER_CHAT: usize = 100;
+
+        self.process_import_request(req, |self_clone, req| {
+            let max_messages = match req.max_messages_per_chat {
+                Some(max_messages) => {
+                    ensure!(max_messages > 0, "Max messages must be positive!");
+                    max_messages as usize
+                }
+                None => DEFAULT_MAX_MESSAGES_PER_CHAT,
+            };
+            let src_dao = self_clone.loader.load(Path::new(&req.source_path), self_clone.user_input_requester.as_ref(), None)?;
+            let src_dao = src_dao.reader();
+            let src_ds = src_dao.datasets()?.into_iter().next().context("Loaded file has no datasets")?;
+
+            let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
+            let dao = loaded_daos.get(&req.key).ok_or_else(|| anyhow!("Database with key {} is not loaded!", req.key))?;
+            let dao = read_or_status(dao)?;
+            let dao = dao.reader();
+            let ds = dao.datasets()?.into_iter().find(|ds| ds.uuid == req.ds_uuid)
+                .with_context(|| format!("Dataset {} not found", req.ds_uuid.value))?;
+
+            let losses = verify_against_source(dao, &ds, src_dao, &src_ds)?;
+            let losses = losses.into_iter().map(|loss| {
+                let missing_messages = loss.missing.iter().take(max_messages)
+                    .map(|id| src_dao.message_option_by_internal_id(&loss.src_cwd.chat, id.generalize())?
+                        .with_context(|| format!("Message {} not found in source", **id)))
+                    .try_collect()?;
+                ok(SourceChatLoss {
+                    chat: loss.src_cwd.chat,
+                    chat_missing: loss.chat_missing,
+                    missing_messages_count: loss.missing.len() as i32,
+                    missing_messages,
+                    missing_ephemeral_messages_count_option: Some(loss.missing_ephemeral_count as i32),
+                })
+            }).try_collect()?;
+            Ok(VerifyAgainstSourceResponse { losses })
+        }).await
+    }
+
+    async fn search_all(&self, req: Request<SearchAllRequest>) -> TonicResult<SearchAllResponse> {
+        access::ensure_full_access(&req)?;
+        self.process_request_blocking(req, |self_clone, req| {
+            ensure!(req.limit > 0, "Limit must be positive!");
+            let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
+            let mut daos = Vec::with_capacity(loaded_daos.len());
+            for (key, dao) in loaded_daos.iter() {
+                daos.push((key, read_or_status(dao)?));
+            }
+            let person_option = match req.from {
+                Some(ref from) => {
+                    let aliases = dao::aliases::all_aliases(daos.iter().map(|(_, dao)| dao.reader()))?;
+                    Some(dao::aliases::alias_group(&aliases, &from.ds_uuid, from.user_id))
+                }
+                None => None,
+            };
+            let (groups, next_page_token) = dao::search::search_all(
+                daos.iter().map(|(key, dao)| (key.as_str(), dao.reader())),
+                &req.text,
+                person_option.as_ref(),
+                req.page_token.as_deref(),
+                req.limit as usize)?;
+            Ok(SearchAllResponse { groups, next_page_token })
+        }).await
+    }
+
+    async fn person_aliases(&self, req: Request<PersonAliasesRequest>) -> TonicResult<PersonAliasesResponse> {
+        access::ensure_full_access(&req)?;
+        self.process_request_blocking(req, |self_clone, req| {
+            let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
+            let mut daos = Vec::with_capacity(loaded_daos.len());
+            for (key, dao) in loaded_daos.iter() {
+                daos.push((key, read_or_status(dao)?));
+            }
+            let aliases = dao::aliases::all_aliases(daos.iter().map(|(_, dao)| dao.reader()))?;
+            let person = dao::aliases::alias_group(&aliases, &req.user.ds_uuid, req.user.user_id);
+            let mut users = vec![];
+            for (key, dao) in daos.iter() {
+                let dao = dao.reader();
+                for ds in dao.datasets()? {
+                    let Some(user_ids) = person.get(&ds.uuid) else { continue };
+                    for user_id in user_ids {
+                        if let Some(user) = dao.user_option(&ds.uuid, *user_id)? {
+                            users.push(AliasedUser { key: (*key).clone(), user });
+                        }
+                    }
+                }
+            }
+            Ok(PersonAliasesResponse { users })
+        }).await
+    }
+
+    async fn generate_synthetic(&self, req: Request<GenerateSyntheticRequest>) -> TonicResult<LoadedFile> {
+        access::ensure_full_access(&req)?;
+        self.process_request_blocking(req, |self_clone, req| {
+            ensure!(!read_or_status(&self_clone.loaded_daos)?.contains_key(&req.key), "Key {} is already taken", req.key);
+            ensure!(req.chats_count >= 0 && req.messages_per_chat >= 0, "Counts cannot be negative");
+            let seed = req.seed_option.unwrap_or_else(rand::random);
+            let dao = synthetic::generate_synthetic_dao(format!("Synthetic data (seed {seed})"),
+                                                        req.chats_count as usize,
+                                                        req.messages_per_chat as usize,
+                                                        seed)?;
+            let response = LoadedFile {
+                key: req.key.clone(),
+                name: dao.name().to_owned(),
+                storage_path: path_to_str(dao.storage_path())?.to_owned(),
+            };
+            write_or_status(&self_clone.loaded_daos)?.insert(req.key.clone(), DaoRwLock::new(LoadedDao::ReadOnly(dao)));
+            Ok(response)
+        }).await
+    }
+}
+
+/// Datasets of open databases that have an import of the same file as the given one, compared by content hash.
+fn find_previous_imports(server: &ChatHistoryManagerServer, path: &Path) -> Result<Vec<PreviousImport>> {
+    if !path.is_file() { return Ok(vec![]); }
+    let source_hash = hex::encode(file_sha256(path)?);
+    let mut res = vec![];
+    for (key, dao) in read_or_status(&server.loaded_daos)?.iter() {
+        let dao = read_or_status(dao)?;
+        let dao = dao.reader();
+        for dataset in dao.datasets()? {
+            let batch_option = dao.import_batches(&dataset.uuid)?.into_iter()
+                .find(|b| b.source_hash == source_hash);
+            if let Some(batch) = batch_option {
+                res.push(PreviousImport {
+                    dao_key: key.clone(),
+                    dao_name: dao.name().to_owned(),
+                    dataset,
+                    batch,
+                });
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Divergences of a loaded database datasets from their copies in other loaded databases.
+fn find_loaded_divergences(server: &ChatHistoryManagerServer, key: &str) -> Result<Vec<DatasetDivergence>> {
+    let loaded_daos = read_or_status(&server.loaded_daos)?;
+    let dao = loaded_daos.get(key).ok_or_else(|| anyhow!("Database with key {key} is not loaded!"))?;
+    let dao = read_or_status(dao)?;
+    let other_daos: Vec<(&str, RwLockReadGuard<LoadedDao>)> = loaded_daos.iter()
+        .filter(|(other_key, _)| other_key.as_str() != key)
+        .map(|(other_key, other_dao)| Ok((other_key.as_str(), read_or_status(other_dao)?)))
+        .collect::<Result<_>>()?;
+    let other_daos = other_daos.iter().map(|(other_key, other_dao)| (*other_key, other_dao.reader())).collect_vec();
+    find_divergences(dao.reader(), &other_daos)
 }
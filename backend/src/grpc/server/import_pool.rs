@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use tokio::sync::{oneshot, Semaphore};
+
+use super::*;
+
+#[cfg(test)]
+#[path = "import_pool_tests.rs"]
+mod tests;
+
+/// Imports are IO-heavy, running too many of them at once just makes every one of them slower.
+pub const DEFAULT_MAX_CONCURRENT_IMPORTS: usize = 2;
+
+/// Runs imports of (potentially huge) histories on dedicated threads rather than on Tokio blocking pool,
+/// which is shared with all other requests, so that the server stays responsive while importing.
+/// Imports exceeding the concurrency limit wait for their turn without occupying a thread.
+///
+/// Import threads are entered into the server's Tokio runtime, as loaders make blocking HTTP requests
+/// (see [crate::utils::http_client::get_bytes_blocking]) which need its reactor.
+pub struct ImportPool {
+    tokio_handle: Handle,
+    permits: Arc<Semaphore>,
+    next_id: AtomicUsize,
+}
+
+impl ImportPool {
+    pub fn new(tokio_handle: Handle, max_concurrent: usize) -> Self {
+        assert!(max_concurrent > 0, "Max concurrent imports should be positive");
+        ImportPool {
+            tokio_handle,
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of imports that can be started right away.
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// Runs the logic on a new thread once there's a free slot, panic is reported as an error.
+    pub async fn run<T, L>(&self, logic: L) -> Result<T>
+    where
+        T: Send + 'static,
+        L: FnOnce() -> T + Send + 'static,
+    {
+        let permit = Arc::clone(&self.permits).acquire_owned().await?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        let tokio_handle = self.tokio_handle.clone();
+        thread::Builder::new()
+            .name(format!("import-{id}"))
+            .spawn(move || {
+                let _guard = tokio_handle.enter();
+                let result = std::panic::catch_unwind(AssertUnwindSafe(logic));
+                drop(permit);
+                // Requester might've gone away in the meantime, nothing to do about it
+                let _ = tx.send(result);
+            })
+            .context("Failed to spawn an import thread")?;
+        match rx.await.context("Import thread has stopped unexpectedly")? {
+            Ok(result) => Ok(result),
+            Err(panic) => err!("Import panicked: {}", panic_message(&*panic)),
+        }
+    }
+}
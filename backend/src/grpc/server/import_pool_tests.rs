@@ -0,0 +1,70 @@
+#![allow(unused_imports)]
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+use futures::future::join_all;
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+use tokio::runtime::Runtime;
+
+use crate::utils::http_client::{get_bytes_blocking, shared_http_client};
+
+use super::*;
+
+#[test]
+fn running_imports() -> EmptyRes {
+    let runtime = Runtime::new()?;
+    let pool = ImportPool::new(runtime.handle().clone(), 2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    let imports = (0..6).map(|idx| {
+        let running = Arc::clone(&running);
+        let max_running = Arc::clone(&max_running);
+        pool.run(move || {
+            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now_running, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            running.fetch_sub(1, Ordering::SeqCst);
+            idx
+        })
+    });
+    let results: Vec<usize> = runtime.block_on(join_all(imports)).into_iter().try_collect()?;
+    assert_eq!(results, (0..6).collect_vec());
+    assert!(max_running.load(Ordering::SeqCst) <= 2);
+    assert_eq!(pool.available(), 2);
+    Ok(())
+}
+
+#[test]
+fn reporting_panics() -> EmptyRes {
+    let runtime = Runtime::new()?;
+    let pool = ImportPool::new(runtime.handle().clone(), 1);
+    let result = runtime.block_on(pool.run(|| -> usize { panic!("Oops") }));
+    assert!(error_message(&result.unwrap_err()).contains("Oops"));
+
+    // Slot is released
+    assert_eq!(pool.available(), 1);
+    assert_eq!(runtime.block_on(pool.run(|| 42))?, 42);
+    Ok(())
+}
+
+/// Loaders make blocking HTTP requests (e.g. to download media), these need a Tokio reactor.
+#[test]
+fn making_http_requests() -> EmptyRes {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/media", listener.local_addr()?);
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0_u8; 1024];
+        let _ = stream.read(&mut buf).unwrap();
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nHello").unwrap();
+    });
+
+    let runtime = Runtime::new()?;
+    let pool = ImportPool::new(runtime.handle().clone(), 1);
+    let response = runtime.block_on(pool.run(move || get_bytes_blocking(shared_http_client(), &url)))??;
+    assert!(matches!(response, HttpResponse::Ok(ref body) if body == b"Hello"));
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::*;
+
+#[cfg(test)]
+#[path = "limits_tests.rs"]
+mod tests;
+
+/// Buckets of idle clients are forgotten once this many clients are tracked.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/// Guards against a misbehaving client (e.g. a frontend stuck in a request loop) hogging the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestLimits {
+    /// Sustained number of requests per second a single client can make, unlimited if `None`
+    pub requests_per_sec_option: Option<u32>,
+    /// Number of requests a client can make in a quick succession before the sustained rate kicks in
+    pub burst: u32,
+    /// Max size of a single request message, max gRPC message size is used if `None`
+    pub max_request_size_option: Option<usize>,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits { requests_per_sec_option: None, burst: 50, max_request_size_option: None }
+    }
+}
+
+impl RequestLimits {
+    pub fn validate(&self) -> EmptyRes {
+        ensure!(self.requests_per_sec_option != Some(0), "Request rate limit should be positive");
+        ensure!(self.burst > 0, "Request burst should be positive");
+        ensure!(self.max_request_size_option != Some(0), "Max request size should be positive");
+        Ok(())
+    }
+
+    pub fn max_request_size(&self) -> usize {
+        self.max_request_size_option.unwrap_or(grpc_config().max_message_size)
+    }
+}
+
+/// Token bucket rate limiter, tracking clients by [access::client_name].
+/// Has to be applied after [super::access::AccessInterceptor] for profiles to be known.
+pub struct RateLimiter {
+    requests_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limits: &RequestLimits) -> Option<Self> {
+        limits.requests_per_sec_option.map(|requests_per_sec| RateLimiter {
+            requests_per_sec: requests_per_sec as f64,
+            burst: limits.burst as f64,
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn check<Q>(&self, req: &Request<Q>) -> StatusResult<()> {
+        let client = access::client_name(req).unwrap_or_else(|| "unknown".to_owned());
+        if self.try_acquire(&client, Instant::now())? {
+            Ok(())
+        } else {
+            log::warn!("Client {client} exceeded the rate limit, rejecting request");
+            Err(Status::resource_exhausted(format!("Too many requests from {client}, slow down")))
+        }
+    }
+
+    fn try_acquire(&self, client: &str, now: Instant) -> StatusResult<bool> {
+        let mut buckets = lock_or_status(&self.buckets)?;
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(client) {
+            // Fully refilled bucket is no different from a fresh one
+            buckets.retain(|_, b| self.refilled(b, now) < self.burst);
+        }
+        let bucket = buckets.entry(client.to_owned()).or_insert(TokenBucket { tokens: self.burst, updated: now });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn refilled(&self, bucket: &TokenBucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.requests_per_sec).min(self.burst)
+    }
+}
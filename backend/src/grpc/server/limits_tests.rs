@@ -0,0 +1,54 @@
+#![allow(unused_imports)]
+
+use std::time::Duration;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+#[test]
+fn validating_limits() {
+    assert!(RequestLimits::default().validate().is_ok());
+    assert!(RequestLimits { requests_per_sec_option: Some(0), ..Default::default() }.validate().is_err());
+    assert!(RequestLimits { burst: 0, ..Default::default() }.validate().is_err());
+    assert!(RequestLimits { max_request_size_option: Some(0), ..Default::default() }.validate().is_err());
+
+    assert!(RateLimiter::new(&RequestLimits::default()).is_none());
+}
+
+#[test]
+fn limiting_request_rate() -> EmptyRes {
+    let limits = RequestLimits { requests_per_sec_option: Some(2), burst: 3, max_request_size_option: None };
+    let limiter = RateLimiter::new(&limits).unwrap();
+    let start = Instant::now();
+
+    // Burst is allowed right away
+    for _ in 0..3 {
+        assert!(limiter.try_acquire("me", start)?);
+    }
+    assert!(!limiter.try_acquire("me", start)?);
+
+    // Other clients are unaffected
+    assert!(limiter.try_acquire("mom", start)?);
+
+    // Sustained rate is restored over time
+    let later = start + Duration::from_millis(500);
+    assert!(limiter.try_acquire("me", later)?);
+    assert!(!limiter.try_acquire("me", later)?);
+
+    // Bucket never holds more than a burst
+    let much_later = start + Duration::from_secs(60);
+    for _ in 0..3 {
+        assert!(limiter.try_acquire("me", much_later)?);
+    }
+    assert!(!limiter.try_acquire("me", much_later)?);
+    Ok(())
+}
+
+#[test]
+fn rejecting_requests() {
+    let limits = RequestLimits { requests_per_sec_option: Some(1), burst: 1, max_request_size_option: None };
+    let limiter = RateLimiter::new(&limits).unwrap();
+    assert!(limiter.check(&Request::new(())).is_ok());
+    assert_eq!(limiter.check(&Request::new(())).unwrap_err().code(), tonic::Code::ResourceExhausted);
+}
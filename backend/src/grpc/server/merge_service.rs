@@ -6,8 +6,10 @@ use tonic::Request;
 use path_dedot::*;
 
 use crate::merge::analyzer::*;
+use crate::merge::comparison::diff_chats;
 use crate::merge::merger;
 use crate::merge::merger::{ChatMergeDecision, MessagesMergeDecision, UserMergeDecision};
+use crate::merge::templates;
 use crate::protobuf::history::merge_service_server::*;
 
 use super::*;
@@ -70,8 +72,39 @@ impl MergeService for Arc<ChatHistoryManagerServer> {
         }, |analysis| Ok(AnalyzeResponse { analysis })).await
     }
 
+    async fn compare_chats(&self, req: Request<CompareChatsRequest>) -> TonicResult<CompareChatsResponse> {
+        self.process_merge_service_request(req, |_, req, m_dao, m_ds, s_dao, s_ds| {
+            let mut comparisons = Vec::with_capacity(req.chat_id_pairs.len());
+            for pair @ ChatIdPair { master_chat_id, slave_chat_id } in req.chat_id_pairs.iter() {
+                let m_cwd = m_dao.chat_option(&m_ds.uuid, *master_chat_id)?
+                    .with_context(|| format!("Master chat {} not found!", *master_chat_id))?;
+                let s_cwd = s_dao.chat_option(&s_ds.uuid, *slave_chat_id)?
+                    .with_context(|| format!("Slave chat {} not found!", *slave_chat_id))?;
+                let diff = diff_chats(m_dao, &m_ds, &m_cwd, s_dao, &s_ds, &s_cwd)?;
+                comparisons.push(ChatComparison {
+                    chat_ids: pair.clone(),
+                    matching_count: diff.matching_count as i32,
+                    removed_master_msg_ids: diff.removed.iter().map(|id| **id).collect_vec(),
+                    added_slave_msg_ids: diff.added.iter().map(|id| **id).collect_vec(),
+                    edited: diff.edited.iter()
+                        .map(|(m_id, s_id)| EditedMessage { master_msg_id: **m_id, slave_msg_id: **s_id })
+                        .collect_vec(),
+                    supersedes: diff.supersedes(),
+                })
+            }
+            Ok(comparisons)
+        }, |comparisons| Ok(CompareChatsResponse { comparisons })).await
+    }
+
+    async fn merge_template(&self, req: Request<MergeTemplateRequest>) -> TonicResult<MergeTemplateResponse> {
+        self.process_merge_service_request(req, |_, _, m_dao, m_ds, s_dao, s_ds| {
+            templates::applicable_template(m_dao, &m_ds, s_dao, &s_ds)
+        }, |template_option| Ok(MergeTemplateResponse { template_option })).await
+    }
+
     async fn merge(&self, req: Request<MergeRequest>) -> TonicResult<MergeResponse> {
-        self.process_merge_service_request(req, |self_clone, req, m_dao, m_ds, s_dao, s_ds| {
+        let auditor = Auditor::new(&req);
+        self.process_merge_service_request(req, move |self_clone, req, m_dao, m_ds, s_dao, s_ds| {
             let sqlite_dao_dir = Path::new(&req.new_database_dir);
             let sqlite_dao_dir = sqlite_dao_dir.parse_dot()?;
             if !sqlite_dao_dir.exists() {
@@ -138,16 +171,25 @@ impl MergeService for Arc<ChatHistoryManagerServer> {
                     }
                 })
             ).try_collect()?;
-            let (dao, ds) = merger::merge_datasets(&sqlite_dao_dir,
-                                                   m_dao, &m_ds,
-                                                   s_dao, &s_ds,
-                                                   user_merges, chat_merges)?;
+            let (mut dao, ds) = merger::merge_datasets(&sqlite_dao_dir,
+                                                       m_dao, &m_ds,
+                                                       s_dao, &s_ds,
+                                                       user_merges, chat_merges)?;
+            let chats = dao.chats(&ds.uuid)?;
+            auditor.record(&mut dao, AuditLogEntry {
+                ds_uuid_option: Some(ds.uuid.clone()),
+                affected_chats_count: chats.len() as i32,
+                affected_messages_count: chats.iter().map(|cwd| cwd.chat.msg_count).sum(),
+                parameters: format!("master dataset {} of {}, slave dataset {} of {}",
+                                    m_ds.uuid.value, req.master_dao_key, s_ds.uuid.value, req.slave_dao_key),
+                ..Default::default()
+            })?;
             let key = path_to_str(&dao.db_file)?.to_owned();
-            Ok((self_clone, key, DaoRwLock::new(Box::new(dao)), ds))
+            Ok((self_clone, key, DaoRwLock::new(LoadedDao::Writable(Box::new(dao))), ds))
         }, |(self_clone, key, dao_lock, ds): (Self, DaoKey, DaoRwLock, Dataset)| {
             let dao = read_or_status(&dao_lock)?;
-            let name = dao.name().to_owned();
-            let storage_path = path_to_str(dao.storage_path())?.to_owned();
+            let name = dao.reader().name().to_owned();
+            let storage_path = path_to_str(dao.reader().storage_path())?.to_owned();
             drop(dao);
             write_or_status(&self_clone.loaded_daos)?.insert(key.clone(), dao_lock);
             Ok(MergeResponse {
@@ -168,8 +210,8 @@ trait MergeServiceHelper {
               Process: FnMut(
                   Self,
                   Q,
-                  &dyn ChatHistoryDao, Dataset,
-                  &dyn ChatHistoryDao, Dataset,
+                  &dyn ChatHistoryReader, Dataset,
+                  &dyn ChatHistoryReader, Dataset,
               ) -> Result<R1> + Send + 'static,
               Finalize: FnMut(R1) -> Result<R2> + Send + 'static;
 }
@@ -184,10 +226,11 @@ impl MergeServiceHelper for Arc<ChatHistoryManagerServer> {
               Process: FnMut(
                   Self,
                   Q,
-                  &dyn ChatHistoryDao, Dataset,
-                  &dyn ChatHistoryDao, Dataset,
+                  &dyn ChatHistoryReader, Dataset,
+                  &dyn ChatHistoryReader, Dataset,
               ) -> Result<R1> + Send + 'static,
               Finalize: FnMut(R1) -> Result<R2> + Send + 'static {
+        access::ensure_access(&req)?;
         self.process_request_blocking(req, move |self_clone, req| {
             let pre_res = {
                 let loaded_daos = read_or_status(&self_clone.loaded_daos)?;
@@ -197,6 +240,7 @@ impl MergeServiceHelper for Arc<ChatHistoryManagerServer> {
 
                 let m_dao = read_or_status(m_dao)?;
                 let s_dao = read_or_status(s_dao)?;
+                let (m_dao, s_dao) = (m_dao.reader(), s_dao.reader());
 
                 let m_ds_uuid = req.master_ds_uuid();
                 let s_ds_uuid = req.slave_ds_uuid();
@@ -206,14 +250,14 @@ impl MergeServiceHelper for Arc<ChatHistoryManagerServer> {
                 let s_ds = s_dao.datasets()?.into_iter().find(|ds| &ds.uuid == s_ds_uuid)
                     .context("Slave dataset not found!")?;
 
-                process(self_clone.clone(), req, &**m_dao, m_ds, &**s_dao, s_ds)?
+                process(self_clone.clone(), req, m_dao, m_ds, s_dao, s_ds)?
             };
             finalize(pre_res)
         }).await
     }
 }
 
-trait MergeServiceRequest {
+trait MergeServiceRequest: access::AccessScoped {
     fn master_dao_key(&self) -> &String;
     fn master_ds_uuid(&self) -> &PbUuid;
     fn slave_dao_key(&self) -> &String;
@@ -231,3 +275,5 @@ macro_rules! merge_req_impl {
 }
 merge_req_impl!(AnalyzeRequest);
 merge_req_impl!(MergeRequest);
+merge_req_impl!(CompareChatsRequest);
+merge_req_impl!(MergeTemplateRequest);
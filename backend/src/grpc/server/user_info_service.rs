@@ -38,4 +38,21 @@ impl<R: UserInputRequester> UserInputService for Arc<UserInputServer<R>> {
         })
         .await
     }
+
+    async fn choose_reimport_action(
+        &self,
+        request: Request<ChooseReimportActionRequest>,
+    ) -> TonicResult<ChooseReimportActionResponse> {
+        self.process_request(request, move |self_clone, request| async move {
+            let (action, picked_option) = self_clone
+                .async_requester
+                .choose_reimport_action(&request.source_path, &request.previous_imports)
+                .await?;
+            Ok(ChooseReimportActionResponse {
+                action: action as i32,
+                picked_option: picked_option as i32,
+            })
+        })
+        .await
+    }
 }
@@ -0,0 +1,35 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+#[test]
+fn recovering_poisoned_locks() -> EmptyRes {
+    let rw_lock = RwLock::new(1);
+    let _ = std::panic::catch_unwind(|| {
+        let mut guard = rw_lock.write().unwrap();
+        *guard = 2;
+        panic!("Oops");
+    });
+    assert!(rw_lock.is_poisoned());
+    assert_eq!(*read_or_status(&rw_lock)?, 2);
+    assert!(!rw_lock.is_poisoned());
+
+    let _ = std::panic::catch_unwind(|| {
+        let _guard = rw_lock.read().unwrap();
+        panic!("Oops");
+    });
+    *write_or_status(&rw_lock)? = 3;
+    assert_eq!(*read_or_status(&rw_lock)?, 3);
+
+    let mutex = Mutex::new(1);
+    let _ = std::panic::catch_unwind(|| {
+        let _guard = mutex.lock().unwrap();
+        panic!("Oops");
+    });
+    assert!(mutex.is_poisoned());
+    assert_eq!(*lock_or_status(&mutex)?, 1);
+    assert!(!mutex.is_poisoned());
+    Ok(())
+}
@@ -0,0 +1,18 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+#[test]
+fn parsing_compression() -> EmptyRes {
+    assert_eq!("gzip".parse::<GrpcCompression>()?, GrpcCompression::Gzip);
+    assert_eq!("ZSTD".parse::<GrpcCompression>()?, GrpcCompression::Zstd);
+    assert!("brotli".parse::<GrpcCompression>().is_err());
+    Ok(())
+}
+
+#[test]
+fn invalid_config() {
+    assert!(configure_grpc(GrpcConfig { max_message_size: 0, ..Default::default() }).is_err());
+}
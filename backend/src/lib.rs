@@ -7,12 +7,21 @@ use prelude::*;
 
 use crate::dao::in_memory_dao::InMemoryDao;
 use crate::loader::Loader;
+use crate::utils::http_client::{shared_http_client, slice_range};
+
+pub use crate::grpc::{configure_grpc, GrpcCompression, GrpcConfig};
+pub use crate::grpc::server::limits::RequestLimits;
+pub use crate::utils::http_client::{configure_shared_http_client, HttpClientConfig};
+
+#[cfg(feature = "embedded")]
+pub use crate::grpc::server::embedded::EmbeddedBackend;
 
 mod protobuf;
 mod loader;
 mod merge;
 mod grpc;
 mod dao;
+mod export;
 mod utils;
 
 pub mod prelude {
@@ -36,6 +45,8 @@ pub mod prelude {
     pub use chat_history_manager_core::message_service_pat_unreachable;
     pub use chat_history_manager_core::content;
     pub use chat_history_manager_core::utils::entity_utils::*;
+    pub use chat_history_manager_core::utils::message_builder::MessageBuilder;
+    pub use chat_history_manager_core::utils::rich_text::PlainTextStyle;
 }
 
 //
@@ -44,16 +55,54 @@ pub mod prelude {
 
 pub fn parse_file(path: &str, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
     thread_local! {
-        static LOADER: Loader = Loader::new(&ReqwestHttpClient);
+        static LOADER: Loader = Loader::new(shared_http_client());
     }
     LOADER.with(|loader| {
-        loader.parse(Path::new(path), user_input_requester)
+        loader.parse(Path::new(path), user_input_requester, None)
     })
 }
 
-pub async fn start_server(port: u16, remote_port: u16) -> EmptyRes {
-    let loader = Loader::new(&ReqwestHttpClient);
-    grpc::server::start_server(port, remote_port, loader).await
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+/// If `access_profiles_path` is given, only requests authenticated by one of the profiles defined there are served,
+/// see [grpc::server::access::AccessProfiles].
+/// Deleted datasets and chats are kept in trash of loaded databases for `trash_retention_days`.
+/// If `ocr_engine` is given, text on photos of parsed histories is recognized, see [loader::ocr::parse_ocr_engine].
+/// If `media_annotator` is given, media can be tagged on request, see [dao::gallery::parse_media_annotator].
+/// Clients are throttled and their request sizes are capped according to `request_limits`.
+pub async fn start_server(port: u16,
+                          remote_port: u16,
+                          access_profiles_path: Option<&Path>,
+                          request_limits: RequestLimits,
+                          trash_retention_days: u32,
+                          ocr_engine: Option<&str>,
+                          media_annotator: Option<&str>) -> EmptyRes {
+    let mut loader = Loader::new(shared_http_client());
+    if let Some(ocr_engine) = ocr_engine {
+        loader = loader.with_ocr(loader::ocr::parse_ocr_engine(ocr_engine)?);
+    }
+    let media_annotator_option = media_annotator.map(dao::gallery::parse_media_annotator).transpose()?;
+    let access_profiles = access_profiles_path.map(grpc::server::access::AccessProfiles::load).transpose()?;
+    let trash_retention = std::time::Duration::from_secs(trash_retention_days as u64 * 24 * 60 * 60);
+    grpc::server::start_server(port, remote_port, loader, media_annotator_option, access_profiles, request_limits,
+                               trash_retention).await
+}
+
+/// Same as [start_server], but backend is run within the current process and requests are made through
+/// the returned handle. User input is requested through the given requester directly.
+/// Must be called within a Tokio runtime.
+#[cfg(feature = "embedded")]
+pub fn start_embedded(trash_retention_days: u32,
+                      ocr_engine: Option<&str>,
+                      media_annotator: Option<&str>,
+                      user_input_requester: Box<dyn UserInputBlockingRequester>) -> Result<EmbeddedBackend> {
+    let mut loader = Loader::new(shared_http_client());
+    if let Some(ocr_engine) = ocr_engine {
+        loader = loader.with_ocr(loader::ocr::parse_ocr_engine(ocr_engine)?);
+    }
+    let media_annotator_option = media_annotator.map(dao::gallery::parse_media_annotator).transpose()?;
+    let trash_retention = std::time::Duration::from_secs(trash_retention_days as u64 * 24 * 60 * 60);
+    Ok(EmbeddedBackend::new(loader, media_annotator_option, user_input_requester, trash_retention))
 }
 
 pub async fn start_user_input_server<R: UserInputRequester>(remote_port: u16, async_requester: R) -> EmptyRes {
@@ -76,42 +125,43 @@ pub enum HttpResponse {
     },
 }
 
+/// See [utils::http_client::SharedHttpClient] for the real implementation,
+/// and [utils::http_client::get_bytes_blocking] for use within loaders.
 pub trait HttpClient: Send + Sync {
-    fn get_bytes(&self, url: &str) -> Result<HttpResponse>;
-}
-
-pub struct ReqwestHttpClient;
-
-impl HttpClient for ReqwestHttpClient {
-    fn get_bytes(&self, url: &str) -> Result<HttpResponse> {
-        let handle = Handle::current();
-        let url = url.to_owned();
-        let join_handle = handle.spawn(async move {
-            let res = reqwest::get(&url).await?;
-            let status = res.status();
-            if status.is_success() {
-                let body = res.bytes().await?.to_vec();
-                Ok(HttpResponse::Ok(body))
-            } else {
-                let headers = res.headers().clone();
-                let body = res.bytes().await?.to_vec();
-                Ok(HttpResponse::Failure { status, headers, body })
+    fn get_bytes(&self, url: &str) -> impl Future<Output = Result<HttpResponse>> + Send;
+
+    /// Bytes `offset..offset + length` of a resource (fewer if it ends sooner), along with its total size.
+    /// Default implementation downloads the whole resource.
+    fn get_range(&self, url: &str, offset: u64, length: u64) -> impl Future<Output = Result<(Vec<u8>, u64)>> + Send {
+        async move {
+            match self.get_bytes(url).await? {
+                HttpResponse::Ok(body) => Ok(slice_range(body, offset, length)),
+                HttpResponse::Failure { status, .. } => err!("Request to {url} failed: {status}"),
             }
-        });
-        handle.block_on(join_handle)?
+        }
     }
+
+    /// Offline client fails all requests, callers are expected to check this and do without network instead.
+    fn is_offline(&self) -> bool { false }
 }
 
 pub trait UserInputRequester: Send + Sync + 'static {
     fn choose_myself(&self, users: &[User]) -> impl Future<Output = Result<usize>> + Send;
 
     fn ask_for_text(&self, prompt: &str) -> impl Future<Output = Result<String>> + Send;
+
+    /// Returns chosen action and index of a previous import it applies to.
+    fn choose_reimport_action(&self, source_path: &str, previous_imports: &[PreviousImport])
+                              -> impl Future<Output = Result<(ReimportAction, usize)>> + Send;
 }
 
 pub trait UserInputBlockingRequester: Send + Sync {
     fn choose_myself(&self, users: &[User]) -> Result<usize>;
 
     fn ask_for_text(&self, prompt: &str) -> Result<String>;
+
+    /// Returns chosen action and index of a previous import it applies to.
+    fn choose_reimport_action(&self, source_path: &str, previous_imports: &[PreviousImport]) -> Result<(ReimportAction, usize)>;
 }
 
 pub fn wrap_async_user_input_requester<R>(handle: Handle, requester: R) -> impl UserInputBlockingRequester
@@ -154,6 +204,15 @@ where
                 requester.ask_for_text(&prompt).await
             })
         }
+
+        fn choose_reimport_action(&self, source_path: &str, previous_imports: &[PreviousImport]) -> Result<(ReimportAction, usize)> {
+            let requester = self.requester.clone();
+            let source_path = source_path.to_owned();
+            let previous_imports = previous_imports.to_vec();
+            self.ask_for_user_input(async move {
+                requester.choose_reimport_action(&source_path, &previous_imports).await
+            })
+        }
     }
 
     Wrapper { handle, requester }
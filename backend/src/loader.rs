@@ -6,23 +6,50 @@ use chrono::Local;
 use itertools::{Either, Itertools};
 
 use crate::prelude::*;
-use crate::dao::ChatHistoryDao;
+use crate::dao::{ChatHistoryReader, LoadedDao};
+use crate::dao::fingerprint::file_sha256;
 use crate::dao::sqlite_dao::SqliteDao;
+use crate::loader::avatars::FoundAvatars;
 use crate::loader::badoo_android::BadooAndroidDataLoader;
+use crate::loader::google_chat::GoogleChatDataLoader;
 use crate::loader::mra::MailRuAgentDataLoader;
+use crate::loader::ocr::OcrEngine;
+use crate::loader::reddit::RedditDataLoader;
 use crate::loader::signal::SignalDataLoader;
+use crate::loader::signal_android::SignalAndroidDataLoader;
 use crate::loader::telegram::TelegramDataLoader;
 use crate::loader::tinder_android::TinderAndroidDataLoader;
+use crate::loader::twitter::TwitterDataLoader;
+use crate::loader::viber_desktop::ViberDesktopDataLoader;
+use crate::loader::vk::VkDataLoader;
 use crate::loader::whatsapp_android::WhatsAppAndroidDataLoader;
 use crate::loader::whatsapp_text::WhatsAppTextDataLoader;
 
+mod avatars;
+mod legacy_h2;
 mod telegram;
 mod tinder_android;
 mod whatsapp_android;
 mod whatsapp_text;
 mod signal;
+mod signal_android;
 mod badoo_android;
 mod mra;
+mod twitter;
+mod reddit;
+mod vk;
+mod google_chat;
+mod viber_desktop;
+mod self_chats;
+mod media_policy;
+pub mod synthetic;
+mod exif;
+pub mod ocr;
+mod senders;
+mod vcard;
+#[cfg(feature = "ffmpeg")]
+mod video_metadata;
+mod waveform;
 
 trait DataLoader: Send + Sync {
     fn name(&self) -> String;
@@ -49,15 +76,56 @@ trait DataLoader: Send + Sync {
                 uuid: PbUuid::random(),
                 alias: format!("{}, loaded @ {now_str}", self.src_alias()),
             };
-            self.load_inner(path, ds, user_input_requester)
+            let ds_uuid = ds.uuid.clone();
+            let mut dao = self.load_inner(path, ds, user_input_requester)?;
+            dao.import_batches.insert(ds_uuid.clone(), self.import_batch(path)?);
+            senders::resolve_senders(&mut dao)?;
+            self_chats::normalize_self_chats(&mut dao)?;
+            let identities = self.user_identities(&dao, &ds_uuid)?;
+            dao.user_identities.insert(ds_uuid, identities);
+            let found_avatars = self.find_avatars(&dao)?;
+            avatars::resolve_avatars(&mut dao, found_avatars)?;
+            vcard::complete_shared_contacts(&mut dao)?;
+            waveform::compute_waveforms(&mut dao)?;
+            exif::extract_photo_geo(&mut dao)?;
+            #[cfg(feature = "ffmpeg")]
+            video_metadata::enrich_videos(&mut dao)?;
+            Ok(dao)
         }, |_, t| log::info!("File {} loaded in {t} ms", root_path_str))
     }
 
     fn load_inner(&self, path: &Path, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>>;
+
+    /// Provenance of a dataset loaded from the given file, source hash is empty if it's a directory.
+    fn import_batch(&self, path: &Path) -> Result<ImportBatch> {
+        Ok(ImportBatch {
+            uuid: PbUuid::random(),
+            source_path: path_to_str(path)?.to_owned(),
+            source_hash: if path.is_file() { hex::encode(file_sha256(path)?) } else { String::new() },
+            loader_name: self.name(),
+            loader_version: env!("CARGO_PKG_VERSION").to_owned(),
+            import_timestamp: Local::now().timestamp(),
+        })
+    }
+
+    /// Source-specific keys of users of a freshly loaded dataset, to be recorded in its identity registry.
+    /// User IDs assigned by loaders are either native to the source or derived from a source-specific key
+    /// (see [hash_to_id]), so by default IDs themselves serve as keys.
+    fn user_identities(&self, dao: &InMemoryDao, ds_uuid: &PbUuid) -> Result<Vec<UserIdentity>> {
+        Ok(dao.users(ds_uuid)?.into_iter()
+            .map(|u| UserIdentity { source: self.name(), source_key: u.id.to_string(), user_id: u.id })
+            .collect_vec())
+    }
+
+    /// Locate user and chat avatars for a freshly loaded dataset, to be linked by [avatars::resolve_avatars].
+    fn find_avatars(&self, _dao: &InMemoryDao) -> Result<FoundAvatars> {
+        Ok(FoundAvatars::default())
+    }
 }
 
 pub struct Loader {
     loaders: Vec<Box<dyn DataLoader + 'static>>,
+    ocr_engine_option: Option<Box<dyn OcrEngine>>,
 }
 
 impl Loader {
@@ -68,25 +136,49 @@ impl Loader {
                 Box::new(WhatsAppAndroidDataLoader),
                 Box::new(WhatsAppTextDataLoader),
                 Box::new(SignalDataLoader),
+                Box::new(SignalAndroidDataLoader),
                 Box::new(TinderAndroidDataLoader { http_client }),
                 Box::new(BadooAndroidDataLoader),
                 Box::new(MailRuAgentDataLoader),
+                Box::new(TwitterDataLoader),
+                Box::new(RedditDataLoader),
+                Box::new(VkDataLoader),
+                Box::new(GoogleChatDataLoader),
+                Box::new(ViberDesktopDataLoader),
             ],
+            ocr_engine_option: None,
         }
     }
 
-    /// If the given file is an internal Sqlite DB, open it, otherwise attempt to parse a file as a foreign history.
-    pub fn load(&self, path: &Path, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<dyn ChatHistoryDao>> {
+    /// Recognize text on photos of parsed histories, making it searchable.
+    pub fn with_ocr(self, ocr_engine: Box<dyn OcrEngine>) -> Self {
+        Loader { ocr_engine_option: Some(ocr_engine), ..self }
+    }
+
+    /// If the given file is an internal Sqlite DB, open it, if it's a legacy storage dump, convert it,
+    /// otherwise attempt to parse a file as a foreign history.
+    ///
+    /// Media policy only applies to foreign histories, see [Loader::parse].
+    pub fn load(&self,
+                path: &Path,
+                user_input_requester: &dyn UserInputBlockingRequester,
+                media_policy_option: Option<&MediaPolicy>) -> Result<LoadedDao> {
         let filename = path_file_name(path)?;
         if filename == SqliteDao::FILENAME {
-            Ok(Box::new(SqliteDao::load(path)?))
+            Ok(LoadedDao::Writable(Box::new(SqliteDao::load(path)?)))
+        } else if filename == legacy_h2::DATASET_CSV {
+            Ok(LoadedDao::ReadOnly(legacy_h2::load_legacy_dump(path)?))
         } else {
-            Ok(self.parse(path, user_input_requester)?)
+            Ok(LoadedDao::ReadOnly(self.parse(path, user_input_requester, media_policy_option)?))
         }
     }
 
-    /// Parses a history in a foreign format
-    pub fn parse(&self, path: &Path, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+    /// Parses a history in a foreign format.
+    /// If media policy is given, media it doesn't allow are replaced with placeholders.
+    pub fn parse(&self,
+                 path: &Path,
+                 user_input_requester: &dyn UserInputBlockingRequester,
+                 media_policy_option: Option<&MediaPolicy>) -> Result<Box<InMemoryDao>> {
         ensure!(path.exists(), "File not found");
         let (named_errors, loads): (Vec<_>, Vec<_>) =
             self.loaders.iter()
@@ -95,8 +187,16 @@ impl Loader {
                     Err(why) => Either::Left((loader.name(), why)),
                 });
         match loads.first() {
-            Some(load) =>
-                load(),
+            Some(load) => {
+                let mut dao = catch_panic(load)?;
+                if let Some(policy) = media_policy_option {
+                    media_policy::apply_media_policy(&mut dao, policy)?;
+                }
+                if let Some(ref ocr_engine) = self.ocr_engine_option {
+                    ocr::recognize_photos(&mut dao, ocr_engine.as_ref())?;
+                }
+                Ok(dao)
+            }
             None => {
                 // Report why everyone rejected the file.
                 err!("No loader accepted the file:\n{}",
@@ -106,6 +206,17 @@ impl Loader {
     }
 }
 
+/// Loaders deal with arbitrary user-provided files, so a bug triggered by a malformed input should fail
+/// the load rather than whatever is running it.
+/// Under fuzzing, panics are left as-is for the fuzzer to catch.
+fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    if cfg!(fuzzing) {
+        return f();
+    }
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+        .unwrap_or_else(|panic| err!("Loader failed unexpectedly: {}", panic_message(&*panic)))
+}
+
 fn ensure_file_presence(root_file: &Path) -> Result<&str> {
     let root_file_str = path_to_str(root_file)?;
     if !root_file.exists() {
@@ -133,8 +244,11 @@ fn first_line(path: &Path) -> Result<String> {
 pub mod android {
     use const_format::concatcp;
     use rusqlite::Connection;
+    use tempfile::{NamedTempFile, TempDir};
 
+    use crate::dao::in_memory_dao::InMemoryDao;
     use crate::loader::DataLoader;
+    use crate::loader::avatars::FoundAvatars;
     use crate::prelude::*;
 
     pub const DATABASES: &str = "databases";
@@ -151,9 +265,17 @@ pub mod android {
     pub trait AndroidDataLoader: Send + Sync {
         const NAME: &'static str;
         const DB_FILENAME: &'static str;
+        /// Extensions of encrypted [AndroidDataLoader::DB_FILENAME] backups this loader can decrypt,
+        /// e.g. `crypt15` to accept `msgstore.db.crypt15`.
+        const ENCRYPTED_DB_EXTENSIONS: &'static [&'static str] = &[];
 
         type Users;
 
+        /// Decrypt an encrypted database backup into a plain database at `dst`.
+        fn decrypt_db(&self, _path: &Path, _dst: &Path, _user_input_requester: &dyn UserInputBlockingRequester) -> EmptyRes {
+            bail!("{} can't decrypt databases", Self::NAME)
+        }
+
         fn tweak_conn(&self, _path: &Path, conn: &Connection) -> EmptyRes;
 
         fn parse_users(&self, conn: &Connection, ds_uuid: &PbUuid, path: &Path) -> Result<Self::Users>;
@@ -162,6 +284,10 @@ pub mod android {
 
         fn parse_chats(&self, conn: &Connection, ds_uuid: &PbUuid, path: &Path, users: &mut Self::Users)
                        -> Result<Vec<ChatWithMessages>>;
+
+        fn find_avatars(&self, _dao: &InMemoryDao) -> Result<FoundAvatars> {
+            Ok(FoundAvatars::default())
+        }
     }
 
     impl<ADL> DataLoader for ADL
@@ -174,19 +300,59 @@ pub mod android {
 
         fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
             let filename = path_file_name(path)?;
-            if filename != ADL::DB_FILENAME { bail!("File is not {}", ADL::DB_FILENAME); }
+            if filename != ADL::DB_FILENAME && encrypted_db_extension::<ADL>(filename).is_none() {
+                bail!("File is not {}", ADL::DB_FILENAME);
+            }
             Ok(())
         }
 
-        fn load_inner(&self, path: &Path, ds: Dataset, _user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
-            parse_android_db(self, path, ds)
+        fn load_inner(&self, path: &Path, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+            parse_android_db(self, path, ds, user_input_requester)
+        }
+
+        fn find_avatars(&self, dao: &InMemoryDao) -> Result<FoundAvatars> {
+            AndroidDataLoader::find_avatars(self, dao)
+        }
+    }
+
+    fn encrypted_db_extension<ADL: AndroidDataLoader>(filename: &str) -> Option<&str> {
+        filename.strip_prefix(ADL::DB_FILENAME)
+            .and_then(|s| s.strip_prefix('.'))
+            .filter(|ext| ADL::ENCRYPTED_DB_EXTENSIONS.contains(ext))
+    }
+
+    /// Decrypted database, only accessible by the current user and removed once parsing is done.
+    struct TempDbFile {
+        // Declared before the directory to be removed before it
+        file: NamedTempFile,
+        _dir: TempDir,
+    }
+
+    impl TempDbFile {
+        fn new(db_filename: &str) -> Result<Self> {
+            let dir = tempfile::Builder::new().prefix("chm-decrypted-").tempdir()?;
+            let file = tempfile::Builder::new().prefix(db_filename).tempfile_in(dir.path())?;
+            Ok(TempDbFile { file, _dir: dir })
         }
     }
 
-    fn parse_android_db<ADL: AndroidDataLoader>(adl: &ADL, path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+    fn parse_android_db<ADL: AndroidDataLoader>(adl: &ADL,
+                                                path: &Path,
+                                                ds: Dataset,
+                                                user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+        let temp_db_file = if path_file_name(path)? == ADL::DB_FILENAME {
+            None
+        } else {
+            let temp_db_file = TempDbFile::new(ADL::DB_FILENAME)?;
+            log::info!("Decrypting {} into {}", path.display(), temp_db_file.file.path().display());
+            adl.decrypt_db(path, temp_db_file.file.path(), user_input_requester)?;
+            Some(temp_db_file)
+        };
+        let db_path = temp_db_file.as_ref().map_or(path, |f| f.file.path());
+
         let path = path.parent().unwrap();
 
-        let conn = Connection::open(path.join(ADL::DB_FILENAME))?;
+        let conn = Connection::open(db_path)?;
         adl.tweak_conn(path, &conn)?;
 
         let path = if path_file_name(path)? == DATABASES {
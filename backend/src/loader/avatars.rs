@@ -0,0 +1,105 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+
+use crate::dao::ChatHistoryReader;
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "avatars_tests.rs"]
+mod tests;
+
+/// Avatars found outside the dataset root are copied here (relative to dataset root).
+pub const AVATARS_DIR: &str = "_avatars";
+
+/// Avatar files located by a specific loader, absolute paths.
+#[derive(Default, Debug)]
+pub struct FoundAvatars {
+    /// Most recent picture should go first.
+    pub users: HashMap<UserId, Vec<PathBuf>>,
+    pub chats: HashMap<ChatId, PathBuf>,
+}
+
+/// Links found avatars to users and chats of a single-dataset DAO, copying files into dataset root when needed.
+///
+/// Existing user profile pictures are kept as-is. Chat image is only replaced if it's absent or doesn't exist;
+/// personal chat without an image of its own will use the first profile picture of its interlocutor.
+pub fn resolve_avatars(dao: &mut InMemoryDao, found: FoundAvatars) -> EmptyRes {
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Avatars can only be resolved for a single dataset"))?.uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+    let myself_id = dao.myself(&ds_uuid)?.id();
+
+    let mut num_users = 0;
+    for user in dao.users(&ds_uuid)? {
+        if !user.profile_pictures.is_empty() { continue; }
+        let Some(paths) = found.users.get(&user.id()) else { continue; };
+        let profile_pictures = paths.iter()
+            .map(|p| link_avatar(&ds_root, p))
+            .flatten_ok()
+            .map_ok(|path| ProfilePicture { path, frame_option: None })
+            .collect::<Result<Vec<_>>>()?;
+        if profile_pictures.is_empty() { continue; }
+        dao.put_user(User { profile_pictures, ..user })?;
+        num_users += 1;
+    }
+
+    let mut num_chats = 0;
+    let users = dao.users(&ds_uuid)?.into_iter().map(|u| (u.id(), u)).collect::<HashMap<_, _>>();
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        let chat = &mut cwm.chat;
+        if chat.img_path_option.as_ref().is_some_and(|p| ds_root.to_absolute(p).exists()) { continue; }
+
+        let found_path = match found.chats.get(&ChatId(chat.id)) {
+            Some(path) => link_avatar(&ds_root, path)?,
+            None => None,
+        };
+        let img_path_option = found_path.or_else(|| {
+            if chat.tpe != ChatType::Personal as i32 { return None; }
+            chat.member_ids.iter()
+                .filter(|&&id| id != *myself_id)
+                .filter_map(|id| users.get(&UserId(*id)))
+                .find_map(|u| u.profile_pictures.first())
+                .map(|pp| pp.path.clone())
+        });
+        if img_path_option.is_some() {
+            chat.img_path_option = img_path_option;
+            num_chats += 1;
+        }
+    }
+
+    log::debug!("Resolved avatars for {num_users} users and {num_chats} chats");
+    Ok(())
+}
+
+/// Returns a path relative to dataset root, copying the file there if needed. Missing files are skipped.
+fn link_avatar(ds_root: &DatasetRoot, path: &Path) -> Result<Option<String>> {
+    if !path.is_file() {
+        log::warn!("Avatar {} not found", path.display());
+        return Ok(None);
+    }
+    // Might be under dataset root through a symlink
+    if let Ok(rel_path) = ds_root.to_relative(path) {
+        return Ok(Some(rel_path));
+    }
+
+    let dst_dir = ds_root.0.join(AVATARS_DIR);
+    fs::create_dir_all(&dst_dir)?;
+    // Name might not be a valid Unicode
+    let file_name = path.file_name().context("Avatar has no file name")?;
+    let mut dst_path = dst_dir.join(file_name);
+    let mut idx = 1;
+    while dst_path.exists() && fs::read(&dst_path)? != fs::read(path)? {
+        let mut indexed_name = OsString::from(format!("{idx}_"));
+        indexed_name.push(file_name);
+        dst_path = dst_dir.join(indexed_name);
+        idx += 1;
+    }
+    if !dst_path.exists() {
+        fs::copy(path, &dst_path)?;
+    }
+    Ok(Some(ds_root.to_relative(&dst_path)?))
+}
@@ -0,0 +1,106 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn resolve_copy_and_link() -> EmptyRes {
+    let users = (1..=3).map(|i| create_user(&ZERO_PB_UUID, i)).collect_vec();
+    let cwms = vec![
+        ChatWithMessages { chat: create_personal_chat(&ZERO_PB_UUID, 2, &users[1], vec![1, 2], 0), messages: vec![] },
+        ChatWithMessages { chat: create_personal_chat(&ZERO_PB_UUID, 3, &users[2], vec![1, 3], 0), messages: vec![] },
+        ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 10, "Group", vec![1, 2, 3], 0), messages: vec![] },
+        ChatWithMessages { chat: create_group_chat(&ZERO_PB_UUID, 11, "Other group", vec![1, 2], 0), messages: vec![] },
+    ];
+    let mut holder = create_dao("Avatars", users, cwms, |_, _| {});
+    let dao = holder.dao.as_mut();
+    let ds_uuid = dao.ds_uuid();
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+
+    // create_dao assigns images to all chats, leave only one of them intact
+    let chat3_img = dao.cwms[&ds_uuid][1].chat.img_path_option.clone();
+    for cwm in dao.cwms.get_mut(&ds_uuid).unwrap() {
+        if cwm.chat.id != 3 { cwm.chat.img_path_option = None; }
+    }
+
+    let outside_dir = TmpDir::new();
+    let user2_avatar = outside_dir.path.join("avatar.jpg");
+    create_random_named_file(&user2_avatar);
+    let group_avatar = outside_dir.path.join("group.jpg");
+    create_random_named_file(&group_avatar);
+    let user3_avatar = ds_root.0.join("user3.jpg");
+    create_random_named_file(&user3_avatar);
+
+    let found = FoundAvatars {
+        users: HashMap::from([
+            (UserId(2), vec![user2_avatar.clone(), outside_dir.path.join("missing.jpg")]),
+            (UserId(3), vec![user3_avatar.clone()]),
+        ]),
+        chats: HashMap::from([
+            (ChatId(3), group_avatar.clone()),
+            (ChatId(10), group_avatar.clone()),
+        ]),
+    };
+    resolve_avatars(dao, found)?;
+
+    let users = dao.users(&ds_uuid)?.into_iter().map(|u| (*u.id(), u)).collect::<HashMap<_, _>>();
+    assert_eq!(users[&1].profile_pictures, vec![]);
+    assert_eq!(users[&2].profile_pictures, vec![ProfilePicture {
+        path: format!("{AVATARS_DIR}/avatar.jpg"),
+        frame_option: None,
+    }]);
+    assert_eq!(users[&3].profile_pictures, vec![ProfilePicture {
+        path: "user3.jpg".to_owned(),
+        frame_option: None,
+    }]);
+    assert_eq!(fs::read(ds_root.to_absolute(&format!("{AVATARS_DIR}/avatar.jpg")))?, fs::read(&user2_avatar)?);
+    assert_eq!(fs::read(ds_root.to_absolute(&format!("{AVATARS_DIR}/group.jpg")))?, fs::read(&group_avatar)?);
+
+    let chat_img = |id: i64| dao.cwms[&ds_uuid].iter().find(|cwm| cwm.chat.id == id).unwrap().chat.img_path_option.clone();
+    // Personal chat falls back to interlocutor's avatar
+    assert_eq!(chat_img(2), Some(format!("{AVATARS_DIR}/avatar.jpg")));
+    // Existing chat image is kept
+    assert_eq!(chat_img(3), chat3_img);
+    assert_eq!(chat_img(10), Some(format!("{AVATARS_DIR}/group.jpg")));
+    assert_eq!(chat_img(11), None);
+
+    Ok(())
+}
+
+#[test]
+fn resolve_name_clash() -> EmptyRes {
+    let users = (1..=3).map(|i| create_user(&ZERO_PB_UUID, i)).collect_vec();
+    let mut holder = create_dao("Avatars", users, vec![], |_, _| {});
+    let dao = holder.dao.as_mut();
+    let ds_uuid = dao.ds_uuid();
+
+    let outside_dir1 = TmpDir::new();
+    let outside_dir2 = TmpDir::new();
+    let avatar1 = outside_dir1.path.join("photo.jpg");
+    let avatar2 = outside_dir2.path.join("photo.jpg");
+    create_random_named_file(&avatar1);
+    create_random_named_file(&avatar2);
+
+    let found = FoundAvatars {
+        users: HashMap::from([
+            (UserId(2), vec![avatar1.clone()]),
+            (UserId(3), vec![avatar2.clone(), avatar1.clone()]),
+        ]),
+        chats: HashMap::new(),
+    };
+    resolve_avatars(dao, found)?;
+
+    let users = dao.users(&ds_uuid)?.into_iter().map(|u| (*u.id(), u)).collect::<HashMap<_, _>>();
+    let paths = |id: i64| users[&id].profile_pictures.iter().map(|pp| pp.path.clone()).collect_vec();
+    assert_eq!(paths(2), vec![format!("{AVATARS_DIR}/photo.jpg")]);
+    assert_eq!(paths(3), vec![format!("{AVATARS_DIR}/1_photo.jpg"), format!("{AVATARS_DIR}/photo.jpg")]);
+
+    Ok(())
+}
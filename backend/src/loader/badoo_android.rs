@@ -152,6 +152,7 @@ impl AndroidDataLoader for BadooAndroidDataLoader {
                                 file_name_option: None,
                                 mime_type: "".to_string(),
                                 duration_sec_option,
+                                waveform_option: None,
                             })])
                         }
                         "TEXT" => {
@@ -185,6 +186,8 @@ impl AndroidDataLoader for BadooAndroidDataLoader {
                         edit_timestamp_option: None,
                         is_deleted: false,
                         forward_from_name_option: None,
+                        forward_from_id_option: None,
+                        ephemeral_duration_sec_option: None,
                         reply_to_message_id_option,
                         contents,
                     },
@@ -204,6 +207,8 @@ impl AndroidDataLoader for BadooAndroidDataLoader {
                         member_ids: vec![*MYSELF_ID, user.id],
                         msg_count: messages.len() as i32,
                         main_chat_id: None,
+                        archived: None,
+                        hidden: None,
                     },
                     messages,
                 });
@@ -7,7 +7,7 @@ use chrono::prelude::*;
 use lazy_static::lazy_static;
 use pretty_assertions::{assert_eq, assert_ne};
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::entity_utils::*;
 use crate::protobuf::history::content::SealedValueOptional::*;
 use crate::protobuf::history::message::*;
@@ -61,6 +61,8 @@ fn loading_2023_12() -> EmptyRes {
             member_ids: vec![myself.id, member.id],
             msg_count: 4,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -73,6 +75,8 @@ fn loading_2023_12() -> EmptyRes {
             from_id: member.id,
             text: vec![RichText::make_plain("Hello there!".to_owned())],
             searchable_string: "Hello there!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
         assert_eq!(msgs[1], Message {
@@ -82,10 +86,14 @@ fn loading_2023_12() -> EmptyRes {
             from_id: myself.id,
             text: vec![RichText::make_plain("Reply there!".to_owned())],
             searchable_string: "Reply there!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: Some(4313483375),
                 contents: vec![],
             }),
@@ -97,10 +105,14 @@ fn loading_2023_12() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(VoiceMsg {
@@ -108,6 +120,7 @@ fn loading_2023_12() -> EmptyRes {
                         file_name_option: None,
                         mime_type: "".to_owned(),
                         duration_sec_option: Some(23),
+                        waveform_option: None,
                     })
                 ],
             }),
@@ -119,6 +132,8 @@ fn loading_2023_12() -> EmptyRes {
             from_id: member.id,
             text: vec![RichText::make_plain("Abcde reacted to your profile: 🤔".to_owned())],
             searchable_string: "Abcde reacted to your profile: 🤔".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
     }
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "exif_tests.rs"]
+mod tests;
+
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_GPS_LAT_REF: u16 = 0x0001;
+const TAG_GPS_LAT: u16 = 0x0002;
+const TAG_GPS_LON_REF: u16 = 0x0003;
+const TAG_GPS_LON: u16 = 0x0004;
+
+/// For every photo whose file is present in a freshly loaded dataset, extract GPS coordinates from its EXIF (if any).
+pub fn extract_photo_geo(dao: &mut InMemoryDao) -> EmptyRes {
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Photo geodata can only be extracted for a single dataset"))?.uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+
+    let mut num_located = 0;
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        for msg in cwm.messages.iter_mut() {
+            let Some(message::Typed::Regular(mr)) = msg.typed.as_mut() else { continue };
+            for content in mr.contents.iter_mut() {
+                let Some(content::SealedValueOptional::Photo(photo)) = content.sealed_value_optional.as_mut() else {
+                    continue;
+                };
+                if photo.lat_str_option.is_some() { continue; }
+                let Some(path) = photo.path_option.as_ref().map(|p| ds_root.to_absolute(p)) else { continue };
+                if !path.exists() { continue; }
+                match read_gps(&path) {
+                    Ok(Some((lat, lon))) => {
+                        photo.lat_str_option = Some(format!("{lat:.6}"));
+                        photo.lon_str_option = Some(format!("{lon:.6}"));
+                        num_located += 1;
+                    }
+                    Ok(None) => { /* No geodata or unsupported format */ }
+                    Err(e) => log::warn!("Couldn't read EXIF of {}: {e}", path.display()),
+                }
+            }
+        }
+    }
+    log::info!("Found geodata for {num_located} photos");
+    Ok(())
+}
+
+/// Reads GPS latitude and longitude (in signed decimal degrees) from EXIF of a JPEG file.
+/// Returns `None` if the file is not a JPEG or carries no GPS coordinates.
+pub fn read_gps(path: &Path) -> Result<Option<(f64, f64)>> {
+    let bytes = fs::read(path)?;
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Ok(None);
+    }
+    let mut offset = 2;
+    while offset + 4 <= bytes.len() {
+        ensure!(bytes[offset] == 0xFF, "Malformed JPEG segment at offset {offset}");
+        let marker = bytes[offset + 1];
+        // Start of scan - image data follows, no more metadata
+        if marker == 0xDA || marker == 0xD9 { break; }
+        let size = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+        let data = bytes.get(offset + 4..offset + 2 + size).context("Truncated JPEG segment")?;
+        if marker == 0xE1 && data.starts_with(b"Exif\0\0") {
+            return tiff_gps(&data[6..]);
+        }
+        offset += 2 + size;
+    }
+    Ok(None)
+}
+
+fn tiff_gps(tiff: &[u8]) -> Result<Option<(f64, f64)>> {
+    let reader = match tiff.get(0..2) {
+        Some(b"II") => TiffReader { tiff, big_endian: false },
+        Some(b"MM") => TiffReader { tiff, big_endian: true },
+        _ => bail!("Unknown TIFF byte order"),
+    };
+    ensure!(reader.u16(2)? == 42, "Not a TIFF header");
+    let ifd0 = reader.u32(4)? as usize;
+    let Some(gps_ifd) = reader.find_entry(ifd0, TAG_GPS_IFD)? else { return Ok(None) };
+    let gps_ifd = reader.u32(gps_ifd + 8)? as usize;
+
+    let coordinate = |value_tag: u16, ref_tag: u16, negative_ref: u8| -> Result<Option<f64>> {
+        let Some(value) = reader.find_entry(gps_ifd, value_tag)? else { return Ok(None) };
+        let Some(reference) = reader.find_entry(gps_ifd, ref_tag)? else { return Ok(None) };
+        // Three rationals (degrees, minutes, seconds) don't fit into an entry, so value is an offset
+        let value_offset = reader.u32(value + 8)? as usize;
+        let mut res = 0.0;
+        for (idx, divisor) in [1.0, 60.0, 3600.0].into_iter().enumerate() {
+            let num = reader.u32(value_offset + idx * 8)?;
+            let den = reader.u32(value_offset + idx * 8 + 4)?;
+            if den == 0 { return Ok(None); }
+            res += num as f64 / den as f64 / divisor;
+        }
+        // Reference is a single ASCII char, stored inline
+        let reference = *tiff.get(reference + 8).context("Truncated GPS reference")?;
+        Ok(Some(if reference == negative_ref { -res } else { res }))
+    };
+
+    let lat = coordinate(TAG_GPS_LAT, TAG_GPS_LAT_REF, b'S')?;
+    let lon = coordinate(TAG_GPS_LON, TAG_GPS_LON_REF, b'W')?;
+    Ok(match (lat, lon) {
+        // Some cameras write zero coordinates when there's no GPS fix
+        (Some(lat), Some(lon)) if lat == 0.0 && lon == 0.0 => None,
+        (Some(lat), Some(lon)) => Some((lat, lon)),
+        _ => None,
+    })
+}
+
+struct TiffReader<'a> {
+    tiff: &'a [u8],
+    big_endian: bool,
+}
+
+impl TiffReader<'_> {
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let b: [u8; 2] = self.tiff.get(offset..offset + 2).context("Truncated TIFF")?.try_into()?;
+        Ok(if self.big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let b: [u8; 4] = self.tiff.get(offset..offset + 4).context("Truncated TIFF")?.try_into()?;
+        Ok(if self.big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+
+    /// Offset of a 12-byte IFD entry with the given tag.
+    fn find_entry(&self, ifd_offset: usize, tag: u16) -> Result<Option<usize>> {
+        let num_entries = self.u16(ifd_offset)? as usize;
+        for idx in 0..num_entries {
+            let entry = ifd_offset + 2 + idx * 12;
+            if self.u16(entry)? == tag {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+}
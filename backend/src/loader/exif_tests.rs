@@ -0,0 +1,138 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn gps_little_endian() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("photo.jpg");
+    let jpeg = make_jpeg(false, b'S', [(8, 1), (42, 1), (1388, 100)], b'E', [(115, 1), (13, 1), (0, 1)]);
+    create_named_file(&path, &jpeg);
+    let (lat, lon) = read_gps(&path)?.unwrap();
+    assert_eq!(format!("{lat:.6}"), "-8.703856");
+    assert_eq!(format!("{lon:.6}"), "115.216667");
+    Ok(())
+}
+
+#[test]
+fn gps_big_endian() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("photo.jpg");
+    create_named_file(&path, &make_jpeg(true, b'N', [(51, 1), (30, 1), (0, 1)], b'W', [(0, 1), (7, 1), (30, 1)]));
+    let (lat, lon) = read_gps(&path)?.unwrap();
+    assert_eq!(format!("{lat:.6}"), "51.500000");
+    assert_eq!(format!("{lon:.6}"), "-0.125000");
+    Ok(())
+}
+
+#[test]
+fn no_gps() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+
+    let path = tmp_dir.path.join("zeros.jpg");
+    create_named_file(&path, &make_jpeg(false, b'N', [(0, 1); 3], b'E', [(0, 1); 3]));
+    assert_eq!(read_gps(&path)?, None);
+
+    let path = tmp_dir.path.join("not-a-jpeg.png");
+    create_named_file(&path, b"\x89PNG\r\n\x1a\n");
+    assert_eq!(read_gps(&path)?, None);
+    Ok(())
+}
+
+#[test]
+fn extracting_into_dao() -> EmptyRes {
+    let msgs = (1..=2).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let mut dao_holder = create_simple_dao(false, "Photos", msgs, 2, &|_, ds_root, msg| {
+        let idx = msg.source_id_option.unwrap();
+        let rel_path = format!("photo_{idx}.jpg");
+        let bytes = if idx == 1 {
+            make_jpeg(false, b'N', [(10, 1), (30, 1), (0, 1)], b'E', [(20, 1), (15, 1), (0, 1)])
+        } else {
+            vec![0xFF, 0xD8, 0xFF, 0xD9]
+        };
+        create_named_file(&ds_root.to_absolute(&rel_path), &bytes);
+        let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+        mr.contents = vec![content!(Photo {
+            path_option: Some(rel_path),
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        })];
+    });
+    let dao = dao_holder.dao.as_mut();
+    extract_photo_geo(dao)?;
+
+    let ds_uuid = dao.ds_uuid();
+    let coordinates = dao.cwms[&ds_uuid][0].messages.iter().map(|m| {
+        let message_regular_pat! { contents, .. } = m.typed() else { unreachable!() };
+        let Some(content::SealedValueOptional::Photo(photo)) = contents[0].sealed_value_optional.as_ref() else {
+            unreachable!()
+        };
+        (photo.lat_str_option.clone(), photo.lon_str_option.clone())
+    }).collect_vec();
+    assert_eq!(coordinates, vec![
+        (Some("10.500000".to_owned()), Some("20.250000".to_owned())),
+        (None, None),
+    ]);
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+/// Minimal JPEG with an EXIF segment containing only GPS coordinates, each given as (numerator, denominator)
+/// rationals for degrees, minutes and seconds.
+fn make_jpeg(big_endian: bool, lat_ref: u8, lat: [(u32, u32); 3], lon_ref: u8, lon: [(u32, u32); 3]) -> Vec<u8> {
+    let u16_bytes = |v: u16| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+    let u32_bytes = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+    let entry = |tiff: &mut Vec<u8>, tag: u16, tpe: u16, count: u32, value: [u8; 4]| {
+        tiff.extend(u16_bytes(tag));
+        tiff.extend(u16_bytes(tpe));
+        tiff.extend(u32_bytes(count));
+        tiff.extend(value);
+    };
+    const IFD0_OFFSET: u32 = 8;
+    const GPS_IFD_OFFSET: u32 = IFD0_OFFSET + 2 + 12 + 4;
+    const LAT_OFFSET: u32 = GPS_IFD_OFFSET + 2 + 4 * 12 + 4;
+    const LON_OFFSET: u32 = LAT_OFFSET + 3 * 8;
+
+    let mut tiff = Vec::from(if big_endian { b"MM" } else { b"II" });
+    tiff.extend(u16_bytes(42));
+    tiff.extend(u32_bytes(IFD0_OFFSET));
+
+    tiff.extend(u16_bytes(1));
+    entry(&mut tiff, TAG_GPS_IFD, 4, 1, u32_bytes(GPS_IFD_OFFSET));
+    tiff.extend(u32_bytes(0));
+
+    tiff.extend(u16_bytes(4));
+    entry(&mut tiff, TAG_GPS_LAT_REF, 2, 2, [lat_ref, 0, 0, 0]);
+    entry(&mut tiff, TAG_GPS_LAT, 5, 3, u32_bytes(LAT_OFFSET));
+    entry(&mut tiff, TAG_GPS_LON_REF, 2, 2, [lon_ref, 0, 0, 0]);
+    entry(&mut tiff, TAG_GPS_LON, 5, 3, u32_bytes(LON_OFFSET));
+    tiff.extend(u32_bytes(0));
+    for (num, den) in lat.into_iter().chain(lon) {
+        tiff.extend(u32_bytes(num));
+        tiff.extend(u32_bytes(den));
+    }
+    assert_eq!(tiff.len() as u32, LON_OFFSET + 3 * 8);
+
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    jpeg.extend((2 + 6 + tiff.len() as u16).to_be_bytes());
+    jpeg.extend(b"Exif\0\0");
+    jpeg.extend(tiff);
+    jpeg.extend([0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xD9]);
+    jpeg
+}
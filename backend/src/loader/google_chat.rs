@@ -0,0 +1,553 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use simd_json::BorrowedValue;
+use simd_json::prelude::*;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::loader::DataLoader;
+use crate::prelude::*;
+use crate::protobuf::history::message_service::SealedValueOptional::*;
+
+#[cfg(test)]
+#[path = "google_chat_tests.rs"]
+mod tests;
+
+const HANGOUTS_DIR: &str = "Hangouts";
+const HANGOUTS_JSON: &str = "Hangouts.json";
+
+const GOOGLE_CHAT_DIR: &str = "Google Chat";
+const GROUPS_DIR: &str = "Groups";
+const USERS_DIR: &str = "Users";
+const GROUP_INFO_JSON: &str = "group_info.json";
+const MESSAGES_JSON: &str = "messages.json";
+const USER_INFO_JSON: &str = "user_info.json";
+
+/// Google Chat names direct message folders `DM <id>` and space folders `Space <id>`.
+const DM_DIR_PREFIX: &str = "DM ";
+
+const DRIVE_LINK_PREFIX: &str = "https://drive.google.com/open?id=";
+
+/// Hangouts event types that carry nothing worth importing.
+const IGNORED_HANGOUTS_EVENT_TYPES: &[&str] = &["OTR_MODIFICATION", "GROUP_LINK_SHARING_MODIFICATION"];
+
+/// Loader for Google Takeout export of (classic) Hangouts and Google Chat.
+/// Takes either Takeout root folder, `Hangouts` folder or `Hangouts.json` in it, or `Google Chat` folder.
+///
+/// Hangouts users are identified by their GAIA IDs, Google Chat users - by emails, so the same person
+/// will be imported as two different users if present in both.
+pub struct GoogleChatDataLoader;
+
+impl DataLoader for GoogleChatDataLoader {
+    fn name(&self) -> String { "Google Chat".to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        let sources = get_sources(path)?;
+        if let Some(ref hangouts_json) = sources.hangouts_json_option {
+            if !super::first_line(hangouts_json)?.contains("\"conversations\"") {
+                bail!("{HANGOUTS_JSON} does not start with conversations");
+            }
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, path: &Path, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+        parse_takeout(get_sources(path)?, ds, user_input_requester)
+    }
+}
+
+struct Sources {
+    /// Dataset root, all paths are relative to it
+    root: PathBuf,
+    hangouts_json_option: Option<PathBuf>,
+    /// `Google Chat` folder path relative to root, either empty or ending with `/`
+    google_chat_rel_prefix_option: Option<String>,
+}
+
+fn get_sources(path: &Path) -> Result<Sources> {
+    if !path.is_dir() {
+        ensure!(path_file_name(path)? == HANGOUTS_JSON, "File is not {HANGOUTS_JSON}");
+        let root = path.parent().unwrap().to_path_buf();
+        return Ok(Sources { root, hangouts_json_option: Some(path.to_path_buf()), google_chat_rel_prefix_option: None });
+    }
+    let root = path.to_path_buf();
+    if path.join(HANGOUTS_JSON).is_file() {
+        return Ok(Sources { hangouts_json_option: Some(path.join(HANGOUTS_JSON)), root, google_chat_rel_prefix_option: None });
+    }
+    if path.join(GROUPS_DIR).is_dir() {
+        return Ok(Sources { root, hangouts_json_option: None, google_chat_rel_prefix_option: Some("".to_owned()) });
+    }
+    let hangouts_json = path.join(HANGOUTS_DIR).join(HANGOUTS_JSON);
+    let hangouts_json_option = if hangouts_json.is_file() { Some(hangouts_json) } else { None };
+    let google_chat_rel_prefix_option =
+        if path.join(GOOGLE_CHAT_DIR).join(GROUPS_DIR).is_dir() { Some(format!("{GOOGLE_CHAT_DIR}/")) } else { None };
+    ensure!(hangouts_json_option.is_some() || google_chat_rel_prefix_option.is_some(),
+            "Neither {HANGOUTS_DIR}/{HANGOUTS_JSON} nor {GOOGLE_CHAT_DIR}/{GROUPS_DIR} found");
+    Ok(Sources { root, hangouts_json_option, google_chat_rel_prefix_option })
+}
+
+/// Users by their string keys - GAIA IDs for Hangouts, emails (or names, if missing) for Google Chat.
+struct Users {
+    ds_uuid: PbUuid,
+    by_key: IndexMap<String, User>,
+    /// Own Hangouts GAIA ID is aliased to own Google Chat email, if both are known
+    aliases: HashMap<String, String, Hasher>,
+    myself_key_option: Option<String>,
+}
+
+impl Users {
+    fn key<'a>(&'a self, key: &'a str) -> &'a str {
+        self.aliases.get(key).map(|s| s.as_str()).unwrap_or(key)
+    }
+
+    /// Registers a user unless already present, filling in missing details otherwise.
+    fn add(&mut self, key: &str, name_option: Option<String>, email_option: Option<String>) -> UserId {
+        let key = self.key(key).to_owned();
+        let user = self.by_key.entry(key.clone()).or_insert_with(|| User {
+            ds_uuid: self.ds_uuid.clone(),
+            id: super::hash_to_id(&key),
+            first_name_option: None,
+            last_name_option: None,
+            username_option: None,
+            phone_number_option: None,
+            profile_pictures: vec![],
+        });
+        if user.first_name_option.is_none() {
+            user.first_name_option = name_option;
+        }
+        if user.username_option.is_none() {
+            user.username_option = email_option;
+        }
+        UserId(user.id)
+    }
+
+    /// Falls back to the key itself for users with no name known.
+    fn name_of(&self, key: &str) -> String {
+        self.by_key.get(self.key(key)).and_then(|u| u.pretty_name_option()).unwrap_or_else(|| key.to_owned())
+    }
+}
+
+fn parse_takeout(sources: Sources, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+    let mut users = Users {
+        ds_uuid: ds.uuid.clone(),
+        by_key: IndexMap::new(),
+        aliases: HashMap::default(),
+        myself_key_option: None,
+    };
+
+    // Own email should be known before parsing Hangouts, for own GAIA ID to be aliased to it
+    if let Some(ref google_chat_rel_prefix) = sources.google_chat_rel_prefix_option {
+        if let Some((email, name)) = parse_user_info(&sources.root.join(google_chat_rel_prefix))? {
+            users.add(&email, Some(name), Some(email.clone()));
+            users.myself_key_option = Some(email);
+        }
+    }
+
+    let mut cwms = vec![];
+    if let Some(ref hangouts_json) = sources.hangouts_json_option {
+        cwms.extend(parse_hangouts(hangouts_json, &ds.uuid, &mut users)?);
+    }
+    if let Some(ref google_chat_rel_prefix) = sources.google_chat_rel_prefix_option {
+        cwms.extend(parse_google_chat(&sources.root, google_chat_rel_prefix, &ds.uuid, &mut users)?);
+    }
+
+    let myself_key = match users.myself_key_option.clone() {
+        Some(key) => key,
+        None => {
+            let users_vec = users.by_key.values().cloned().collect_vec();
+            let myself_idx = user_input_requester.choose_myself(&users_vec)?;
+            users.by_key.get_index(myself_idx).unwrap().0.clone()
+        }
+    };
+    let myself_id = UserId(users.by_key[&myself_key].id);
+
+    // Own user should go first, both in users and chat members
+    let (myself, others): (Vec<User>, Vec<User>) = users.by_key.into_values().partition(|u| u.id == *myself_id);
+    let users = myself.into_iter().chain(others).collect_vec();
+    for cwm in cwms.iter_mut() {
+        cwm.chat.member_ids.retain(|id| *id != *myself_id);
+        cwm.chat.member_ids.insert(0, *myself_id);
+        // Personal chats are named after the other member
+        if cwm.chat.tpe == ChatType::Personal as i32 {
+            cwm.chat.name_option = cwm.chat.member_ids.get(1)
+                .and_then(|id| users.iter().find(|u| u.id == *id))
+                .and_then(|u| u.pretty_name_option());
+        }
+    }
+
+    let mut result = Box::new(InMemoryDao::new_single(
+        format!("Google Chat ({})", path_file_name(&sources.root)?),
+        ds,
+        sources.root,
+        myself_id,
+        users,
+        cwms,
+    ));
+    result.remove_orphan_users();
+    Ok(result)
+}
+
+fn finish_messages(messages: &mut [Message]) {
+    messages.sort_by_key(|m| (m.timestamp, m.source_id_option));
+    for (idx, m) in messages.iter_mut().enumerate() {
+        m.internal_id = idx as i64;
+    }
+}
+
+fn make_chat(ds_uuid: &PbUuid, id: i64, name_option: Option<String>, tpe: ChatType, member_ids: Vec<UserId>, msg_count: usize) -> Chat {
+    Chat {
+        ds_uuid: ds_uuid.clone(),
+        id,
+        name_option,
+        source_type: SourceType::GoogleChat as i32,
+        tpe: tpe as i32,
+        img_path_option: None,
+        member_ids: member_ids.iter().map(|id| **id).collect_vec(),
+        msg_count: msg_count as i32,
+        main_chat_id: None,
+        archived: None,
+        hidden: None,
+    }
+}
+
+//
+// Hangouts
+//
+
+fn parse_hangouts(path: &Path, ds_uuid: &PbUuid, users: &mut Users) -> Result<Vec<ChatWithMessages>> {
+    let mut content = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let parsed = simd_json::to_borrowed_value(&mut content)?;
+    let conversations = as_array!(get_field!(parsed, HANGOUTS_JSON, "conversations")?, HANGOUTS_JSON, "conversations");
+
+    // Every conversation states own GAIA ID, it's the same everywhere
+    let self_gaia_option = conversations.iter().find_map(|c| {
+        c.get("conversation")?.get("conversation")?
+            .get("self_conversation_state")?.get("self_read_state")?
+            .get("participant_id")?.get("gaia_id")?.as_str()
+    });
+    if let Some(self_gaia) = self_gaia_option {
+        match users.myself_key_option {
+            Some(ref myself_key) => { users.aliases.insert(self_gaia.to_owned(), myself_key.clone()); }
+            None => users.myself_key_option = Some(self_gaia.to_owned()),
+        }
+    }
+
+    let mut result = vec![];
+    for conversation_json in conversations {
+        result.push(parse_hangouts_conversation(conversation_json, ds_uuid, users)?);
+    }
+    Ok(result)
+}
+
+fn parse_hangouts_conversation(conversation_json: &BorrowedValue, ds_uuid: &PbUuid, users: &mut Users) -> Result<ChatWithMessages> {
+    let outer = get_field_object!(conversation_json, "conversations", "conversation");
+    let conversation_id = get_field_str!(get_field_object!(outer, "conversation", "conversation_id"), "conversation.conversation_id", "id");
+    let json_path = format!("conversation[{conversation_id}]");
+    let conversation = get_field_object!(outer, json_path, "conversation");
+
+    let mut member_ids = vec![];
+    for participant in as_array!(get_field!(conversation, json_path, "participant_data")?, json_path, "participant_data") {
+        let gaia_id = get_field_str!(get_field_object!(participant, json_path, "id"), json_path, "gaia_id");
+        let name_option = participant.get("fallback_name").and_then(|v| v.as_str()).map(|s| s.to_owned());
+        member_ids.push(users.add(gaia_id, name_option, None));
+    }
+
+    let tpe = match get_field_str!(conversation, json_path, "type") {
+        "STICKY_ONE_TO_ONE" | "ONE_TO_ONE" => ChatType::Personal,
+        "GROUP" => ChatType::PrivateGroup,
+        other => bail!("Unknown {json_path} type: {other}"),
+    };
+
+    let mut messages = vec![];
+    for event in as_array!(get_field!(conversation_json, "conversations", "events")?, json_path, "events") {
+        if let Some(msg) = parse_hangouts_event(event, &json_path, users)? {
+            if !member_ids.contains(&UserId(msg.from_id)) {
+                member_ids.push(UserId(msg.from_id));
+            }
+            messages.push(msg);
+        }
+    }
+    finish_messages(&mut messages);
+
+    let name_option = match tpe {
+        ChatType::Personal => None,
+        _ => conversation.get("name").and_then(|v| v.as_str()).map(|s| s.to_owned()),
+    };
+
+    Ok(ChatWithMessages {
+        chat: make_chat(ds_uuid, super::hash_to_id(conversation_id), name_option, tpe, member_ids, messages.len()),
+        messages,
+    })
+}
+
+/// Timestamps are strings of microseconds since epoch.
+fn parse_hangouts_timestamp(s: &str, json_path: &str) -> Result<Timestamp> {
+    let micros = s.parse::<i64>().with_context(|| format!("{json_path}.timestamp is not a number: {s}"))?;
+    Ok(Timestamp(micros / 1_000_000))
+}
+
+fn parse_hangouts_event(event: &BorrowedValue, json_path: &str, users: &mut Users) -> Result<Option<Message>> {
+    let event_id = get_field_str!(event, json_path, "event_id");
+    let json_path = format!("{json_path}.event[{event_id}]");
+    let event_type = get_field_str!(event, json_path, "event_type");
+    if IGNORED_HANGOUTS_EVENT_TYPES.contains(&event_type) {
+        return Ok(None);
+    }
+
+    let sender_gaia = get_field_str!(get_field_object!(event, json_path, "sender_id"), json_path, "gaia_id");
+    let from_id = users.add(sender_gaia, None, None);
+    let timestamp = parse_hangouts_timestamp(get_field_str!(event, json_path, "timestamp"), &json_path)?;
+    let source_id = super::hash_to_id(event_id);
+
+    let names_of = |ids_json: &BorrowedValue, field: &str| -> Result<Vec<String>> {
+        let mut names = vec![];
+        for id in as_array!(ids_json, json_path, field) {
+            names.push(users.name_of(get_field_str!(id, json_path, field)));
+        }
+        Ok(names)
+    };
+
+    let typed = match event_type {
+        "REGULAR_CHAT_MESSAGE" => {
+            let content = get_field_object!(get_field_object!(event, json_path, "chat_message"), json_path, "message_content");
+            let mut builder = MessageBuilder::regular(*timestamp, from_id)
+                .source_id(source_id);
+            if let Some(segments) = content.get("segment") {
+                builder = builder.rich_texts(parse_hangouts_segments(as_array!(segments, json_path, "segment"), &json_path)?);
+            }
+            if let Some(attachments) = content.get("attachment") {
+                for attachment in as_array!(attachments, json_path, "attachment") {
+                    let Some(photo) = attachment.get("embed_item").and_then(|e| e.get("plus_photo")) else { continue; };
+                    builder = match photo.get("media_type").and_then(|v| v.as_str()) {
+                        Some("VIDEO") => builder.video(None, "video/mp4"),
+                        _ => builder.photo(None, 0, 0),
+                    };
+                }
+            }
+            return Ok(Some(builder.build()));
+        }
+        "ADD_USER" | "REMOVE_USER" => {
+            let change = get_field_object!(event, json_path, "membership_change");
+            let members = names_of(get_field!(change, json_path, "participant_id")?, "gaia_id")?;
+            if event_type == "ADD_USER" {
+                message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers { members }))
+            } else {
+                message_service!(GroupRemoveMembers(MessageServiceGroupRemoveMembers { members }))
+            }
+        }
+        "RENAME_CONVERSATION" => {
+            let rename = get_field_object!(event, json_path, "conversation_rename");
+            message_service!(GroupEditTitle(MessageServiceGroupEditTitle {
+                title: get_field_string!(rename, json_path, "new_name")
+            }))
+        }
+        "HANGOUT_EVENT" => {
+            let hangout = get_field_object!(event, json_path, "hangout_event");
+            // Call start is not worth a separate message, call end has all the details
+            if get_field_str!(hangout, json_path, "event_type") != "END_HANGOUT" {
+                return Ok(None);
+            }
+            let duration_sec_option = match hangout.get("hangout_duration_secs").and_then(|v| v.as_str()) {
+                Some(s) => Some(s.parse::<i32>().with_context(|| format!("{json_path}.hangout_duration_secs is not a number: {s}"))?),
+                None => None,
+            };
+            let members = match hangout.get("participant_id") {
+                Some(ids_json) => names_of(ids_json, "gaia_id")?,
+                None => vec![],
+            };
+            message_service!(PhoneCall(MessageServicePhoneCall { duration_sec_option, discard_reason_option: None, members }))
+        }
+        other => bail!("Unknown {json_path} event type: {other}"),
+    };
+
+    Ok(Some(Message::new(*NO_INTERNAL_ID, Some(source_id), *timestamp, from_id, vec![], typed)))
+}
+
+/// Message text consists of segments, plain ones (including line breaks) are merged together.
+fn parse_hangouts_segments(segments: &[BorrowedValue], json_path: &str) -> Result<Vec<RichTextElement>> {
+    let mut result = vec![];
+    let mut plain = String::new();
+    for segment in segments {
+        let text = segment.get("text").and_then(|v| v.as_str()).unwrap_or_default().to_owned();
+        let is_set = |flag: &str| segment.get("formatting").and_then(|f| f.get(flag)).and_then(|v| v.as_bool()).unwrap_or(false);
+        let rte = match get_field_str!(segment, json_path, "type") {
+            "LINE_BREAK" => {
+                plain.push('\n');
+                continue;
+            }
+            "LINK" => {
+                let href = segment.get("link_data").and_then(|l| l.get("link_target")).and_then(|v| v.as_str())
+                    .map(|s| s.to_owned()).unwrap_or_else(|| text.clone());
+                RichText::make_link(Some(text), href, false)
+            }
+            "TEXT" if is_set("bold") => RichText::make_bold(text),
+            "TEXT" if is_set("italics") => RichText::make_italic(text),
+            "TEXT" if is_set("strikethrough") => RichText::make_strikethrough(text),
+            "TEXT" if is_set("underline") => RichText::make_underline(text),
+            "TEXT" => {
+                plain.push_str(&text);
+                continue;
+            }
+            other => bail!("Unknown {json_path} segment type: {other}"),
+        };
+        if !plain.is_empty() {
+            result.push(RichText::make_plain(std::mem::take(&mut plain)));
+        }
+        result.push(rte);
+    }
+    if !plain.is_empty() {
+        result.push(RichText::make_plain(plain));
+    }
+    Ok(result)
+}
+
+//
+// Google Chat
+//
+
+/// Own email and name from `Users/User <id>/user_info.json`, if present.
+fn parse_user_info(google_chat_dir: &Path) -> Result<Option<(String, String)>> {
+    let users_dir = google_chat_dir.join(USERS_DIR);
+    if !users_dir.is_dir() {
+        return Ok(None);
+    }
+    let Some(user_info_path) = sorted_subdirs(&users_dir)?.into_iter()
+        .map(|dir| dir.join(USER_INFO_JSON))
+        .find(|p| p.is_file()) else { return Ok(None); };
+    let mut content = fs::read(&user_info_path).with_context(|| format!("Cannot read {}", user_info_path.display()))?;
+    let parsed = simd_json::to_borrowed_value(&mut content)?;
+    let user = get_field_object!(parsed, USER_INFO_JSON, "user");
+    let json_path = format!("{USER_INFO_JSON}.user");
+    Ok(Some((get_field_string!(user, json_path, "email"), get_field_string!(user, json_path, "name"))))
+}
+
+fn sorted_subdirs(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut result = vec![];
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            result.push(path);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Users are keyed by email, bots and deleted users might not have one.
+fn add_google_chat_user(user_json: &BorrowedValue, json_path: &str, users: &mut Users) -> Result<UserId> {
+    let name_option = user_json.get("name").and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(|s| s.to_owned());
+    let email_option = user_json.get("email").and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(|s| s.to_owned());
+    let key = email_option.clone().or_else(|| name_option.clone())
+        .with_context(|| format!("{json_path} has neither name nor email"))?;
+    Ok(users.add(&key, name_option, email_option))
+}
+
+fn parse_google_chat(root: &Path, rel_prefix: &str, ds_uuid: &PbUuid, users: &mut Users) -> Result<Vec<ChatWithMessages>> {
+    let mut result = vec![];
+    for group_dir in sorted_subdirs(&root.join(rel_prefix).join(GROUPS_DIR))? {
+        let group_dir_name = path_file_name(&group_dir)?;
+        let messages_path = group_dir.join(MESSAGES_JSON);
+        if !messages_path.is_file() { continue; }
+        let rel_dir = format!("{rel_prefix}{GROUPS_DIR}/{group_dir_name}");
+
+        let group_info_path = group_dir.join(GROUP_INFO_JSON);
+        let mut group_name_option = None;
+        let mut member_ids = vec![];
+        if group_info_path.is_file() {
+            let mut content = fs::read(&group_info_path).with_context(|| format!("Cannot read {}", group_info_path.display()))?;
+            let parsed = simd_json::to_borrowed_value(&mut content)?;
+            group_name_option = parsed.get("name").and_then(|v| v.as_str()).map(|s| s.to_owned());
+            if let Some(members) = parsed.get("members") {
+                for member in as_array!(members, rel_dir, "members") {
+                    member_ids.push(add_google_chat_user(member, &rel_dir, users)?);
+                }
+            }
+        }
+
+        let mut content = fs::read(&messages_path).with_context(|| format!("Cannot read {}", messages_path.display()))?;
+        let parsed = simd_json::to_borrowed_value(&mut content)?;
+        let mut messages = vec![];
+        for message_json in as_array!(get_field!(parsed, rel_dir, "messages")?, rel_dir, "messages") {
+            let msg = parse_google_chat_message(message_json, root, &rel_dir, users)?;
+            if !member_ids.contains(&UserId(msg.from_id)) {
+                member_ids.push(UserId(msg.from_id));
+            }
+            messages.push(msg);
+        }
+        finish_messages(&mut messages);
+
+        // Group DMs are not named, so they are treated as (unnamed) groups
+        let (tpe, name_option) = if group_dir_name.starts_with(DM_DIR_PREFIX) && member_ids.len() <= 2 {
+            (ChatType::Personal, None)
+        } else {
+            (ChatType::PrivateGroup, group_name_option)
+        };
+
+        result.push(ChatWithMessages {
+            chat: make_chat(ds_uuid, super::hash_to_id(group_dir_name), name_option, tpe, member_ids, messages.len()),
+            messages,
+        });
+    }
+    Ok(result)
+}
+
+/// Dates look like `Monday, February 5, 2024 at 9:15:00 AM UTC`, newer exports use a narrow no-break space
+/// before AM/PM.
+fn parse_google_chat_datetime(s: &str) -> Result<Timestamp> {
+    let normalized = s.replace('\u{202f}', " ");
+    let without_weekday = normalized.split_once(", ").map(|(_, rest)| rest).unwrap_or(&normalized);
+    let without_tz = without_weekday.strip_suffix(" UTC").unwrap_or(without_weekday);
+    let dt = NaiveDateTime::parse_from_str(without_tz, "%B %d, %Y at %I:%M:%S %p")
+        .with_context(|| format!("Cannot parse date: {s}"))?;
+    Ok(Timestamp(Utc.from_utc_datetime(&dt).timestamp()))
+}
+
+fn parse_google_chat_message(message_json: &BorrowedValue, root: &Path, rel_dir: &str, users: &mut Users) -> Result<Message> {
+    let message_id = get_field_str!(message_json, rel_dir, "message_id");
+    let json_path = format!("{rel_dir}.message[{message_id}]");
+    let from_id = add_google_chat_user(get_field!(message_json, json_path, "creator")?, &json_path, users)?;
+    let timestamp = parse_google_chat_datetime(get_field_str!(message_json, json_path, "created_date"))?;
+
+    let mut builder = MessageBuilder::regular(*timestamp, from_id)
+        .source_id(super::hash_to_id(message_id));
+    if let Some(updated) = message_json.get("updated_date") {
+        builder = builder.edited_at(*parse_google_chat_datetime(as_str!(updated, json_path, "updated_date"))?);
+    }
+    if message_json.get("deletion_metadata").is_some() {
+        return Ok(builder.deleted().build());
+    }
+
+    let text = message_json.get("text").and_then(|v| v.as_str()).unwrap_or_default();
+    builder = builder.text(text);
+
+    // Attached Drive files are not exported, only referenced
+    if let Some(annotations) = message_json.get("annotations") {
+        for annotation in as_array!(annotations, json_path, "annotations") {
+            let Some(drive) = annotation.get("drive_metadata") else { continue; };
+            let id = get_field_str!(drive, json_path, "id");
+            if text.contains(id) { continue; }
+            let title_option = drive.get("title").and_then(|v| v.as_str()).map(|s| s.to_owned());
+            builder = builder.rich_text(RichText::make_link(title_option, format!("{DRIVE_LINK_PREFIX}{id}"), false));
+        }
+    }
+
+    if let Some(attached_files) = message_json.get("attached_files") {
+        for file in as_array!(attached_files, json_path, "attached_files") {
+            let original_name = get_field_string!(file, json_path, "original_name");
+            let rel_path = format!("{rel_dir}/{}", get_field_str!(file, json_path, "export_name"));
+            let path_option = if root.join(&rel_path).is_file() { Some(rel_path) } else { None };
+            let ext = original_name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+            builder = match ext.as_str() {
+                "jpg" | "jpeg" | "png" | "gif" | "webp" | "heic" => builder.photo(path_option, 0, 0),
+                "mp4" => builder.video(path_option, "video/mp4"),
+                "mov" => builder.video(path_option, "video/quicktime"),
+                _ => builder.file(path_option, Some(original_name)),
+            };
+        }
+    }
+
+    Ok(builder.build())
+}
@@ -0,0 +1,233 @@
+#![allow(unused_imports)]
+
+use chrono::prelude::*;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::ChatHistoryReader;
+use crate::entity_utils::*;
+use crate::loader::hash_to_id;
+use crate::protobuf::history::message::*;
+
+use super::*;
+
+const LOADER: GoogleChatDataLoader = GoogleChatDataLoader;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2024_02() -> EmptyRes {
+    let res = resource("google-takeout_2024-02");
+    LOADER.looks_about_right(&res)?;
+    LOADER.looks_about_right(&res.join(HANGOUTS_DIR))?;
+    LOADER.looks_about_right(&res.join(HANGOUTS_DIR).join(HANGOUTS_JSON))?;
+    LOADER.looks_about_right(&res.join(GOOGLE_CHAT_DIR))?;
+
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let user = |key: &str, name_option: Option<&str>, email_option: Option<&str>| User {
+        ds_uuid: ds_uuid.clone(),
+        id: hash_to_id(key),
+        first_name_option: name_option.map(|s| s.to_owned()),
+        last_name_option: None,
+        username_option: email_option.map(|s| s.to_owned()),
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+
+    // Own Hangouts GAIA ID is merged with own Google Chat email
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself, user("me@gmail.com", Some("Me Myself"), Some("me@gmail.com")));
+
+    let alice_chat = user("alice@gmail.com", Some("Alice Smith"), Some("alice@gmail.com"));
+    let carol = user("carol@example.com", Some("Carol Jones"), Some("carol@example.com"));
+    let alice_hangouts = user("111", Some("Alice Smith"), None);
+    let nameless = user("222", None, None);
+    let mut users = dao.users_single_ds();
+    users.sort_by_key(|u| u.id);
+    let mut expected_users = vec![myself.clone(), alice_chat.clone(), carol.clone(), alice_hangouts.clone(), nameless.clone()];
+    expected_users.sort_by_key(|u| u.id);
+    assert_eq!(users, expected_users);
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 4);
+
+    let utc_ts = |s: &str| dt(s, Some(&Utc.fix())).timestamp();
+    let service = |internal_id: i64, event_id: &str, ts: &str, from_id: UserId, typed: message::Typed| Message::new(
+        internal_id,
+        Some(hash_to_id(event_id)),
+        utc_ts(ts),
+        from_id,
+        vec![],
+        typed,
+    );
+
+    // Hangouts personal conversation
+    {
+        let cwm = &cwms[0];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: hash_to_id("UgwPersonal"),
+            name_option: Some("Alice Smith".to_owned()),
+            source_type: SourceType::GoogleChat as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, alice_hangouts.id],
+            msg_count: 3,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs, vec![
+            MessageBuilder::regular(utc_ts("2020-03-01 10:00:00"), alice_hangouts.id())
+                .internal_id(0)
+                .source_id(hash_to_id("7-H0Z7-aaa"))
+                .text("Hi, ")
+                .rich_text(RichText::make_bold("look".to_owned()))
+                .text(" at this:\n")
+                .rich_text(RichText::make_link(Some("example.com".to_owned()), "https://example.com/".to_owned(), false))
+                .build(),
+            MessageBuilder::regular(utc_ts("2020-03-01 10:01:00"), myself.id())
+                .internal_id(1)
+                .source_id(hash_to_id("7-H0Z7-bbb"))
+                .photo(None, 0, 0)
+                .build(),
+            service(2, "7-H0Z7-ddd", "2020-03-01 10:03:05", myself.id(),
+                    message_service!(PhoneCall(MessageServicePhoneCall {
+                        duration_sec_option: Some(65),
+                        discard_reason_option: None,
+                        members: vec!["Me Myself".to_owned(), "Alice Smith".to_owned()],
+                    }))),
+        ]);
+    }
+
+    // Hangouts group conversation
+    {
+        let cwm = &cwms[1];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: hash_to_id("UgwGroup"),
+            name_option: Some("Book club".to_owned()),
+            source_type: SourceType::GoogleChat as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, alice_hangouts.id, nameless.id],
+            msg_count: 4,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs, vec![
+            service(0, "7-H0Z7-eee", "2020-03-02 10:00:00", myself.id(),
+                    message_service!(GroupEditTitle(MessageServiceGroupEditTitle { title: "Book club".to_owned() }))),
+            service(1, "7-H0Z7-fff", "2020-03-02 10:01:00", myself.id(),
+                    message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers { members: vec!["222".to_owned()] }))),
+            MessageBuilder::regular(utc_ts("2020-03-02 10:02:00"), nameless.id())
+                .internal_id(2)
+                .source_id(hash_to_id("7-H0Z7-ggg"))
+                .text("Thanks for adding me!")
+                .build(),
+            service(3, "7-H0Z7-hhh", "2020-03-02 10:03:00", alice_hangouts.id(),
+                    message_service!(GroupRemoveMembers(MessageServiceGroupRemoveMembers { members: vec!["Alice Smith".to_owned()] }))),
+        ]);
+    }
+
+    // Google Chat DM
+    {
+        let cwm = &cwms[2];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: hash_to_id("DM abc123"),
+            name_option: Some("Alice Smith".to_owned()),
+            source_type: SourceType::GoogleChat as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, alice_chat.id],
+            msg_count: 2,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs, vec![
+            MessageBuilder::regular(utc_ts("2024-02-05 09:15:00"), alice_chat.id())
+                .internal_id(0)
+                .source_id(hash_to_id("abc123/t1/m1"))
+                .text("Here's the photo")
+                .photo(Some("Google Chat/Groups/DM abc123/File-photo.jpg".to_owned()), 0, 0)
+                .build(),
+            MessageBuilder::regular(utc_ts("2024-02-05 13:20:30"), myself.id())
+                .internal_id(1)
+                .source_id(hash_to_id("abc123/t2/m2"))
+                .edited_at(utc_ts("2024-02-05 13:25:00"))
+                .text("Notes are in the doc")
+                .rich_text(RichText::make_link(Some("Trip notes".to_owned()), "https://drive.google.com/open?id=1AbCdEf".to_owned(), false))
+                .build(),
+        ]);
+    }
+
+    // Google Chat space
+    {
+        let cwm = &cwms[3];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: hash_to_id("Space xyz789"),
+            name_option: Some("Team".to_owned()),
+            source_type: SourceType::GoogleChat as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, alice_chat.id, carol.id],
+            msg_count: 2,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs, vec![
+            MessageBuilder::regular(utc_ts("2024-02-06 10:00:00"), carol.id())
+                .internal_id(0)
+                .source_id(hash_to_id("xyz789/t3/m3"))
+                .text("Welcome to the team space")
+                .build(),
+            MessageBuilder::regular(utc_ts("2024-02-06 10:05:00"), alice_chat.id())
+                .internal_id(1)
+                .source_id(hash_to_id("xyz789/t3/m4"))
+                .deleted()
+                .build(),
+        ]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn loading_hangouts_only() -> EmptyRes {
+    let res = resource("google-takeout_2024-02").join(HANGOUTS_DIR);
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    // Without Google Chat data, own user is identified by GAIA ID
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.id, hash_to_id("999"));
+    assert_eq!(myself.first_name_option, Some("Me Myself".to_owned()));
+    assert_eq!(dao.cwms_single_ds().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn parsing_datetimes() -> EmptyRes {
+    let expected = dt("2024-02-05 21:15:07", Some(&Utc.fix())).timestamp();
+    assert_eq!(*parse_google_chat_datetime("Monday, February 5, 2024 at 9:15:07 PM UTC")?, expected);
+    assert_eq!(*parse_google_chat_datetime("Monday, February 5, 2024 at 9:15:07\u{202f}PM UTC")?, expected);
+    assert_eq!(*parse_google_chat_datetime("Monday, February 5, 2024 at 12:00:00 AM UTC")?,
+               dt("2024-02-05 00:00:00", Some(&Utc.fix())).timestamp());
+    assert!(parse_google_chat_datetime("yesterday").is_err());
+    Ok(())
+}
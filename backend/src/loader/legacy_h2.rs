@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use csv::StringRecord;
+use itertools::Itertools;
+use uuid::Uuid;
+
+use crate::dao::in_memory_dao::{DatasetEntry, InMemoryDao};
+use crate::dao::sqlite_dao::mapping::*;
+use crate::dao::sqlite_dao::utils::{self, EnumSerialization};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "legacy_h2_tests.rs"]
+mod tests;
+
+/// Loading this file triggers the legacy import.
+pub const DATASET_CSV: &str = "dataset.csv";
+
+const USER_CSV: &str = "user.csv";
+const CHAT_CSV: &str = "chat.csv";
+const CHAT_MEMBER_CSV: &str = "chat_member.csv";
+const MESSAGE_CSV: &str = "message.csv";
+const MESSAGE_CONTENT_CSV: &str = "message_content.csv";
+const MESSAGE_TEXT_ELEMENT_CSV: &str = "message_text_element.csv";
+
+/// Loads a storage of the older Scala version of chat-history-manager, dumped from H2 into CSV files.
+///
+/// H2 database files cannot be read directly, so every table has to be dumped first (e.g. using H2 console)
+/// right into the storage folder, next to dataset directories:
+/// ```sql
+/// CALL CSVWRITE('<storage>/dataset.csv', 'SELECT * FROM dataset');
+/// CALL CSVWRITE('<storage>/user.csv', 'SELECT * FROM user');
+/// -- Same for chat, chat_member, message, message_content and message_text_element
+/// ```
+/// Layout of these tables was carried over as the initial schema of [crate::dao::sqlite_dao::SqliteDao],
+/// so rows are converted the same way. Column names are case-insensitive, columns added later are optional.
+///
+/// Dataset UUIDs, message internal and source IDs are preserved, so previously merged data can be merged again.
+/// Note that internal IDs will be reassigned if the result is saved to a new database.
+pub fn load_legacy_dump(path: &Path) -> Result<Box<InMemoryDao>> {
+    let storage_path = path.parent().context("Dump has no parent directory")?.to_path_buf();
+    measure(|| {
+        let read = |file_name: &str| CsvTable::read(&storage_path.join(file_name));
+
+        let datasets: Vec<Dataset> = read(DATASET_CSV)?.rows().map(|r| ok(Dataset {
+            uuid: r.uuid("uuid")?,
+            alias: r.req("alias")?.to_owned(),
+        })).try_collect()?;
+
+        let mut users: HashMap<PbUuid, Vec<(User, bool)>> = HashMap::new();
+        for r in read(USER_CSV)?.rows() {
+            let raw = RawUser {
+                ds_uuid: raw_uuid(&r.uuid("ds_uuid")?),
+                id: r.req_i64("id")?,
+                first_name: r.opt("first_name"),
+                last_name: r.opt("last_name"),
+                username: r.opt("username"),
+                phone_numbers: r.opt("phone_numbers").or_else(|| r.opt("phone_number")),
+                is_myself: utils::serialize_bool(r.req_bool("is_myself")?),
+            };
+            let (user, is_myself) = utils::user::deserialize(raw, vec![])?;
+            users.entry(user.ds_uuid.clone()).or_default().push((user, is_myself));
+        }
+
+        let mut members: HashMap<(PbUuid, i64), Vec<(i64, i64)>> = HashMap::new();
+        for (idx, r) in read(CHAT_MEMBER_CSV)?.rows().enumerate() {
+            // Member order was not recorded initially
+            let order = r.opt_i64("order")?.unwrap_or(idx as i64);
+            members.entry((r.uuid("ds_uuid")?, r.req_i64("chat_id")?)).or_default().push((order, r.req_i64("user_id")?));
+        }
+
+        let mut msgs = read_messages(&read(MESSAGE_CSV)?, &read(MESSAGE_CONTENT_CSV)?, &read(MESSAGE_TEXT_ELEMENT_CSV)?)?;
+
+        let mut cwms: HashMap<PbUuid, Vec<ChatWithMessages>> = HashMap::new();
+        for r in read(CHAT_CSV)?.rows() {
+            let ds_uuid = r.uuid("ds_uuid")?;
+            let id = r.req_i64("id")?;
+            let messages = msgs.remove(&(ds_uuid.clone(), id)).unwrap_or_default();
+            let member_ids = members.remove(&(ds_uuid.clone(), id)).unwrap_or_default()
+                .into_iter().sorted_by_key(|(order, _)| *order).map(|(_, user_id)| user_id).collect_vec();
+            let chat = Chat {
+                ds_uuid: ds_uuid.clone(),
+                id,
+                name_option: r.opt("name"),
+                source_type: SourceType::deserialize(r.req("source_type")?)?,
+                tpe: ChatType::deserialize(r.req("type")?)?,
+                img_path_option: r.opt("img_path"),
+                member_ids,
+                msg_count: messages.len() as i32,
+                main_chat_id: r.opt_i64("main_chat_id")?,
+                archived: None,
+                hidden: None,
+            };
+            cwms.entry(ds_uuid).or_default().push(ChatWithMessages { chat, messages });
+        }
+
+        let data: Vec<DatasetEntry> = datasets.into_iter().map(|ds| {
+            let users = users.remove(&ds.uuid).unwrap_or_default();
+            let myself_ids = users.iter().filter(|(_, is_myself)| *is_myself).map(|(u, _)| u.id()).collect_vec();
+            ensure!(myself_ids.len() == 1, "Expected exactly one myself in dataset {}, found {}",
+                    ds.uuid.value, myself_ids.len());
+            let users = users.into_iter().map(|(u, _)| u)
+                .sorted_by_key(|u| if u.id() == myself_ids[0] { i64::MIN } else { u.id })
+                .collect_vec();
+            // If dataset has no files, its root doesn't really matter
+            let ds_root = Some(storage_path.join(&ds.uuid.value)).filter(|p| p.exists())
+                .unwrap_or_else(|| storage_path.clone());
+            Ok(DatasetEntry {
+                ds_root,
+                myself_id: myself_ids[0],
+                users,
+                cwms: cwms.remove(&ds.uuid).unwrap_or_default(),
+                ds,
+            })
+        }).try_collect()?;
+        ensure!(cwms.is_empty(), "Chats of unknown datasets found: {}", cwms.keys().map(|k| &k.value).join(", "));
+
+        Ok(Box::new(InMemoryDao::new(
+            format!("Legacy storage ({})", path_file_name(&storage_path)?),
+            storage_path.clone(),
+            data,
+        )))
+    }, |_, t| log::info!("Legacy storage {} loaded in {t} ms", storage_path.display()))
+}
+
+/// Messages by dataset and chat ID, in order of their internal IDs.
+fn read_messages(messages: &CsvTable,
+                 contents: &CsvTable,
+                 rtes: &CsvTable) -> Result<HashMap<(PbUuid, i64), Vec<Message>>> {
+    let mut contents_by_id: HashMap<i64, Vec<(i64, RawMessageContent)>> = HashMap::new();
+    for r in contents.rows() {
+        let internal_id = r.req_i64("message_internal_id")?;
+        contents_by_id.entry(internal_id).or_default().push((r.req_i64("id")?, RawMessageContent {
+            id: None,
+            message_internal_id: Some(internal_id),
+            element_type: r.req("element_type")?.to_owned(),
+            path: r.opt("path"),
+            thumbnail_path: r.opt("thumbnail_path"),
+            file_name: r.opt("file_name"),
+            emoji: r.opt("emoji"),
+            width: r.opt_i32("width")?,
+            height: r.opt_i32("height")?,
+            mime_type: r.opt("mime_type"),
+            title: r.opt("title"),
+            performer: r.opt("performer"),
+            duration_sec: r.opt_i32("duration_sec")?,
+            // Not tracked initially, but required for photos and videos
+            is_one_time: Some(utils::serialize_bool(r.opt_bool("is_one_time")?.unwrap_or(false))),
+            lat: r.opt("lat"),
+            lon: r.opt("lon"),
+            address: r.opt("address"),
+            poll_question: r.opt("poll_question"),
+            first_name: r.opt("first_name"),
+            last_name: r.opt("last_name"),
+            phone_number: r.opt("phone_number"),
+            members: r.opt("members"),
+            discard_reason: r.opt("discard_reason"),
+            pinned_message_id: r.opt_i64("pinned_message_id")?,
+            is_blocked: r.opt_bool("is_blocked")?.map(utils::serialize_bool),
+            ..Default::default()
+        }));
+    }
+
+    let mut rtes_by_id: HashMap<i64, Vec<(i64, RawRichTextElement)>> = HashMap::new();
+    for r in rtes.rows() {
+        let internal_id = r.req_i64("message_internal_id")?;
+        rtes_by_id.entry(internal_id).or_default().push((r.req_i64("id")?, RawRichTextElement {
+            id: None,
+            message_internal_id: Some(internal_id),
+            element_type: r.req("element_type")?.to_owned(),
+            text: r.opt("text"),
+            href: r.opt("href"),
+            hidden: r.opt_bool("hidden")?.map(utils::serialize_bool),
+            language: r.opt("language"),
+            // Legacy format has no mentions, custom emojis, quote references or text history
+            user_id: None,
+            handle: None,
+            emoji_id: None,
+            path: None,
+            quoted_source_id: None,
+            text_version: None,
+            text_version_time: None,
+        }));
+    }
+
+    let mut result: HashMap<(PbUuid, i64), Vec<Message>> = HashMap::new();
+    for r in messages.rows() {
+        let internal_id = r.req_i64("internal_id")?;
+        let ds_uuid = r.uuid("ds_uuid")?;
+        let chat_id = r.req_i64("chat_id")?;
+        let raw = FullRawMessage {
+            m: RawMessage {
+                internal_id: Some(internal_id),
+                ds_uuid: raw_uuid(&ds_uuid),
+                chat_id,
+                source_id: r.opt_i64("source_id")?,
+                tpe: r.req("type")?.to_owned(),
+                subtype: r.opt("subtype"),
+                time_sent: r.req_timestamp("time_sent")?,
+                time_edited: r.opt_timestamp("time_edited")?,
+                is_deleted: utils::serialize_bool(r.opt_bool("is_deleted")?.unwrap_or(false)),
+                from_id: r.req_i64("from_id")?,
+                forward_from_name: r.opt("forward_from_name"),
+                forward_from_id: None,
+                reply_to_message_id: r.opt_i64("reply_to_message_id")?,
+                searchable_string: "".to_owned(),
+                topic_id: None,
+                topic_title: None,
+                import_batch_uuid: None,
+                ephemeral_duration_sec: None,
+            },
+            mc: sorted(contents_by_id.remove(&internal_id)),
+            rtes: sorted(rtes_by_id.remove(&internal_id)),
+        };
+        let msg = utils::message::deserialize(raw)
+            .with_context(|| format!("Cannot convert message with internal ID {internal_id}"))?;
+        result.entry((ds_uuid, chat_id)).or_default().push(msg);
+    }
+    for msgs in result.values_mut() {
+        msgs.sort_by_key(|m| m.internal_id);
+    }
+    Ok(result)
+}
+
+/// Values ordered by their IDs.
+fn sorted<T>(v: Option<Vec<(i64, T)>>) -> Vec<T> {
+    v.unwrap_or_default().into_iter().sorted_by_key(|(id, _)| *id).map(|(_, v)| v).collect_vec()
+}
+
+fn raw_uuid(uuid: &PbUuid) -> Vec<u8> {
+    Vec::from(Uuid::parse_str(&uuid.value).expect("Invalid UUID!").as_bytes().as_slice())
+}
+
+/// CSV file written by H2 `CSVWRITE`: header names are upper-cased, NULLs are written as empty values,
+/// booleans as `TRUE`/`FALSE`.
+struct CsvTable {
+    name: String,
+    columns: HashMap<String, usize>,
+    records: Vec<StringRecord>,
+}
+
+impl CsvTable {
+    fn read(path: &Path) -> Result<Self> {
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Cannot read {}", path.display()))?;
+        let columns = reader.headers()?.iter().enumerate().map(|(i, h)| (h.to_lowercase(), i)).collect();
+        let records = reader.records().try_collect()?;
+        Ok(CsvTable { name: path_file_name(path)?.to_owned(), columns, records })
+    }
+
+    fn rows(&self) -> impl Iterator<Item=CsvRow<'_>> {
+        self.records.iter().map(move |record| CsvRow { table: self, record })
+    }
+}
+
+struct CsvRow<'a> {
+    table: &'a CsvTable,
+    record: &'a StringRecord,
+}
+
+impl CsvRow<'_> {
+    fn opt(&self, column: &str) -> Option<String> {
+        self.table.columns.get(column)
+            .and_then(|&i| self.record.get(i))
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_owned())
+    }
+
+    fn req(&self, column: &str) -> Result<&str> {
+        let idx = *self.table.columns.get(column)
+            .with_context(|| format!("Column {column} not found in {}", self.table.name))?;
+        self.record.get(idx).filter(|v| !v.is_empty())
+            .with_context(|| format!("Column {column} is empty in {}", self.table.name))
+    }
+
+    fn opt_parsed<T: std::str::FromStr>(&self, column: &str) -> Result<Option<T>> {
+        self.opt(column).map(|v| v.parse::<T>()
+            .map_err(|_| anyhow!("Cannot parse {column} value '{v}' in {}", self.table.name))).transpose()
+    }
+
+    fn opt_i32(&self, column: &str) -> Result<Option<i32>> { self.opt_parsed(column) }
+
+    fn opt_i64(&self, column: &str) -> Result<Option<i64>> { self.opt_parsed(column) }
+
+    fn req_i64(&self, column: &str) -> Result<i64> {
+        self.req(column)?;
+        Ok(self.opt_i64(column)?.unwrap())
+    }
+
+    fn opt_bool(&self, column: &str) -> Result<Option<bool>> {
+        self.opt(column).map(|v| match v.to_lowercase().as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => err!("Cannot parse {column} value '{v}' in {} as boolean", self.table.name),
+        }).transpose()
+    }
+
+    fn req_bool(&self, column: &str) -> Result<bool> {
+        self.req(column)?;
+        Ok(self.opt_bool(column)?.unwrap())
+    }
+
+    /// Either epoch seconds or local H2 timestamp, e.g. `2020-01-02 12:34:56.789`.
+    fn opt_timestamp(&self, column: &str) -> Result<Option<i64>> {
+        self.opt(column).map(|v| {
+            if let Ok(epoch_seconds) = v.parse::<i64>() {
+                return Ok(epoch_seconds);
+            }
+            let naive_dt = NaiveDateTime::parse_from_str(&v, "%Y-%m-%d %H:%M:%S%.f")
+                .with_context(|| format!("Cannot parse {column} value '{v}' in {}", self.table.name))?;
+            Local.from_local_datetime(&naive_dt).earliest()
+                .map(|dt| dt.timestamp())
+                .with_context(|| format!("Time {v} does not exist in local timezone"))
+        }).transpose()
+    }
+
+    fn req_timestamp(&self, column: &str) -> Result<i64> {
+        self.req(column)?;
+        Ok(self.opt_timestamp(column)?.unwrap())
+    }
+
+    fn uuid(&self, column: &str) -> Result<PbUuid> {
+        let v = self.req(column)?;
+        let uuid = Uuid::parse_str(v).with_context(|| format!("Invalid UUID {v} in {}", self.table.name))?;
+        Ok(PbUuid { value: uuid.to_string() })
+    }
+}
@@ -0,0 +1,152 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::ChatHistoryReader;
+use crate::entity_utils::*;
+use crate::protobuf::history::content::SealedValueOptional;
+use crate::protobuf::history::message_service::SealedValueOptional as ServiceSvo;
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2023_01() -> EmptyRes {
+    let res = resource("legacy-h2_2023-01");
+    let dao = load_legacy_dump(&res.join(DATASET_CSV))?;
+
+    let ds_uuid = PbUuid { value: "5a2c1f3e-8b7d-4c6a-9e21-0f4d3b2a1c9e".to_owned() };
+    assert_eq!(dao.datasets()?, vec![Dataset {
+        uuid: ds_uuid.clone(),
+        alias: "Telegram data, loaded @ 2019-06-02".to_owned(),
+    }]);
+    assert_eq!(dao.dataset_root(&ds_uuid)?.0, res.join(&ds_uuid.value).canonicalize()?);
+
+    let myself = User {
+        ds_uuid: ds_uuid.clone(),
+        id: 1111,
+        first_name_option: Some("Myself".to_owned()),
+        last_name_option: None,
+        username_option: Some("myself".to_owned()),
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+    let friend = User {
+        ds_uuid: ds_uuid.clone(),
+        id: 2222,
+        first_name_option: Some("Aaaaa".to_owned()),
+        last_name_option: Some("Bbbbb".to_owned()),
+        username_option: None,
+        phone_number_option: Some("+7 123 456 78 90".to_owned()),
+        profile_pictures: vec![],
+    };
+    assert_eq!(dao.myself(&ds_uuid)?, myself);
+    assert_eq!(dao.users(&ds_uuid)?, vec![myself.clone(), friend.clone()]);
+
+    let cwds = dao.chats(&ds_uuid)?;
+    assert_eq!(cwds.len(), 2);
+    let group = cwds.iter().find(|cwd| cwd.chat.id == 6666).unwrap();
+    assert_eq!(group.chat.tpe, ChatType::PrivateGroup as i32);
+    assert_eq!(group.chat.img_path_option.as_deref(), Some("chats/6666/photo.jpg"));
+    assert_eq!(group.chat.member_ids, vec![myself.id]);
+    assert_eq!(group.chat.msg_count, 0);
+
+    let chat = cwds.iter().find(|cwd| cwd.chat.id == 5555).unwrap().chat.clone();
+    assert_eq!(chat, Chat {
+        ds_uuid: ds_uuid.clone(),
+        id: 5555,
+        name_option: Some("Aaaaa Bbbbb".to_owned()),
+        source_type: SourceType::Telegram as i32,
+        tpe: ChatType::Personal as i32,
+        img_path_option: None,
+        member_ids: vec![myself.id, friend.id],
+        msg_count: 3,
+        main_chat_id: None,
+        archived: None,
+        hidden: None,
+    });
+
+    // Internal and source IDs are preserved, messages are ordered by internal IDs
+    let msgs = dao.first_messages(&chat, 99999)?;
+    assert_eq!(msgs, vec![
+        Message::new(
+            10,
+            Some(100),
+            1559469540,
+            myself.id(),
+            vec![
+                RichText::make_plain("Look ".to_owned()),
+                RichText::make_link(Some("here".to_owned()), "https://example.com".to_owned(), false),
+            ],
+            message_regular! {
+                edit_timestamp_option: Some(1559469541),
+                is_deleted: false,
+                forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![],
+            },
+        ),
+        Message::new(
+            11,
+            Some(101),
+            1559469570,
+            friend.id(),
+            vec![RichText::make_bold(r#"Nice, "quoted""#.to_owned())],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: Some("Someone".to_owned()),
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
+                reply_to_message_id_option: Some(100),
+                contents: vec![Content {
+                    sealed_value_optional: Some(SealedValueOptional::Photo(ContentPhoto {
+                        path_option: Some("chats/5555/photo_1.jpg".to_owned()),
+                        width: 800,
+                        height: 600,
+                        mime_type_option: None,
+                        is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
+                    }))
+                }],
+            },
+        ),
+        Message::new(
+            12,
+            Some(102),
+            1559469600,
+            friend.id(),
+            vec![],
+            message_service!(ServiceSvo::PinMessage(MessageServicePinMessage { message_source_id: 100 })),
+        ),
+    ]);
+    Ok(())
+}
+
+#[test]
+fn timestamps() -> EmptyRes {
+    let table = CsvTable {
+        name: "test.csv".to_owned(),
+        columns: HashMap::from([("time".to_owned(), 0)]),
+        records: vec![
+            StringRecord::from(vec!["1559469540"]),
+            StringRecord::from(vec!["2019-06-02 12:59:00.123"]),
+            StringRecord::from(vec![""]),
+            StringRecord::from(vec!["yesterday"]),
+        ],
+    };
+    let rows = table.rows().collect_vec();
+    assert_eq!(rows[0].opt_timestamp("time")?, Some(1559469540));
+    assert_eq!(rows[1].opt_timestamp("time")?, Some(dt("2019-06-02 12:59:00", None).timestamp()));
+    assert_eq!(rows[2].opt_timestamp("time")?, None);
+    assert!(rows[2].req_timestamp("time").is_err());
+    assert!(rows[3].opt_timestamp("time").is_err());
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::fs;
+
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "media_policy_tests.rs"]
+mod tests;
+
+/// Replace media not allowed by the policy in a freshly loaded dataset with placeholders, so that their files
+/// won't be copied when a dataset is saved. Returns the number of replaced contents.
+///
+/// Placeholder is a file content without a file, named after the original absolute path.
+/// Media whose files are missing are left as-is.
+pub fn apply_media_policy(dao: &mut InMemoryDao, policy: &MediaPolicy) -> Result<usize> {
+    let allowed_kinds: Vec<MediaKind> = policy.allowed_kinds.iter().map(|k| MediaKind::resolve(*k)).try_collect()?;
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Media policy can only be applied to a single dataset"))?.uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+
+    let mut num_skipped = 0;
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        for msg in cwm.messages.iter_mut() {
+            let mut changed = false;
+            let Some(message::Typed::Regular(mr)) = msg.typed.as_mut() else { continue };
+            for content in mr.contents.iter_mut() {
+                let Some((kind, path, _)) = content_media_file(content) else { continue };
+                let path = ds_root.to_absolute(path);
+                let Ok(meta) = fs::metadata(&path) else { continue };
+                if is_allowed(policy, &allowed_kinds, kind, meta.len()) { continue; }
+                *content = content!(File {
+                    path_option: None,
+                    file_name_option: Some(path.display().to_string()),
+                    mime_type_option: mime_type_option(content),
+                    thumbnail_path_option: None,
+                });
+                changed = true;
+                num_skipped += 1;
+            }
+            if changed {
+                msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+            }
+        }
+    }
+    log::info!("Skipped {num_skipped} media files not allowed by media policy");
+    Ok(num_skipped)
+}
+
+fn is_allowed(policy: &MediaPolicy, allowed_kinds: &[MediaKind], kind: MediaKind, file_size: u64) -> bool {
+    let is_video = matches!(kind, MediaKind::Video | MediaKind::VideoMsg);
+    !(is_video && policy.skip_videos == Some(true))
+        && (allowed_kinds.is_empty() || allowed_kinds.contains(&kind))
+        && policy.max_file_size_bytes.is_none_or(|max| file_size <= max as u64)
+}
+
+fn mime_type_option(content: &Content) -> Option<String> {
+    use content::SealedValueOptional::*;
+    match content.sealed_value_optional.as_ref()? {
+        Photo(c) => c.mime_type_option.clone(),
+        Video(c) => Some(c.mime_type.clone()),
+        VideoMsg(c) => Some(c.mime_type.clone()),
+        VoiceMsg(c) => Some(c.mime_type.clone()),
+        Audio(c) => Some(c.mime_type.clone()),
+        Sticker(c) => c.mime_type_option.clone(),
+        File(c) => c.mime_type_option.clone(),
+        Location(_) | Poll(_) | SharedContact(_) => None,
+    }
+}
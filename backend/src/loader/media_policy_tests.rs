@@ -0,0 +1,152 @@
+#![allow(unused_imports)]
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn applying_media_policy() -> EmptyRes {
+    let mut dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=4).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            let content = match msg.source_id_option {
+                Some(1) => content!(Photo {
+                    path_option: Some(ds_root.to_relative(&create_random_file(&ds_root.0)).unwrap()),
+                    width: 0,
+                    height: 0,
+                    mime_type_option: Some("image/jpeg".to_owned()),
+                    is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
+                }),
+                Some(2) => content!(Video {
+                    path_option: Some(ds_root.to_relative(&create_random_file(&ds_root.0)).unwrap()),
+                    file_name_option: None,
+                    title_option: None,
+                    performer_option: None,
+                    width: 0,
+                    height: 0,
+                    mime_type: "video/mp4".to_owned(),
+                    duration_sec_option: None,
+                    thumbnail_path_option: None,
+                    is_one_time: false,
+                }),
+                Some(3) => {
+                    let path = ds_root.0.join("big.bin");
+                    create_named_file(&path, &[0; 1024]);
+                    content!(File {
+                        path_option: Some(ds_root.to_relative(&path).unwrap()),
+                        file_name_option: Some("big.bin".to_owned()),
+                        mime_type_option: None,
+                        thumbnail_path_option: None,
+                    })
+                }
+                // File is missing
+                Some(4) => content!(File {
+                    path_option: Some("missing.pdf".to_owned()),
+                    file_name_option: None,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                }),
+                _ => unreachable!(),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![content];
+        });
+    let ds_uuid = dao_holder.dao.ds_uuid();
+    let ds_root = dao_holder.dao.dataset_root(&ds_uuid)?;
+    let original_msgs = dao_holder.dao.cwms[&ds_uuid][0].messages.clone();
+    let contents = |dao: &InMemoryDao| dao.cwms[&ds_uuid][0].messages.iter().map(|m| match m.typed() {
+        message_regular_pat! { contents, .. } => contents[0].clone(),
+        _ => unreachable!(),
+    }).collect_vec();
+    let placeholder = |idx: usize, mime_type_option: Option<&str>| {
+        let content = contents(&dao_holder.dao).remove(idx);
+        let (_, path, _) = content_media_file(&content).unwrap();
+        content!(File {
+            path_option: None,
+            file_name_option: Some(path_to_str(&ds_root.to_absolute(path)).unwrap().to_owned()),
+            mime_type_option: mime_type_option.map(|s| s.to_owned()),
+            thumbnail_path_option: None,
+        })
+    };
+    let expected = vec![
+        contents(&dao_holder.dao)[0].clone(),
+        placeholder(1, Some("video/mp4")),
+        placeholder(2, None),
+        contents(&dao_holder.dao)[3].clone(),
+    ];
+
+    // Empty policy allows everything
+    let skipped = apply_media_policy(&mut dao_holder.dao, &MediaPolicy::default())?;
+    assert_eq!(skipped, 0);
+    assert_eq!(dao_holder.dao.cwms[&ds_uuid][0].messages, original_msgs);
+
+    let policy = MediaPolicy {
+        max_file_size_bytes: Some(512),
+        allowed_kinds: vec![],
+        skip_videos: Some(true),
+    };
+    let skipped = apply_media_policy(&mut dao_holder.dao, &policy)?;
+    assert_eq!(skipped, 2);
+    assert_eq!(contents(&dao_holder.dao), expected);
+    let msg = &dao_holder.dao.cwms[&ds_uuid][0].messages[1];
+    assert_eq!(msg.searchable_string, make_searchable_string(&msg.text, msg.typed()));
+    Ok(())
+}
+
+#[test]
+fn media_policy_allowed_kinds() -> EmptyRes {
+    let mut dao_holder = create_simple_dao(
+        false,
+        "test",
+        (1..=2).map(|idx| create_regular_message(idx, 1)).collect_vec(),
+        2,
+        &|_, ds_root, msg| {
+            let path_option = Some(ds_root.to_relative(&create_random_file(&ds_root.0)).unwrap());
+            let content = match msg.source_id_option {
+                Some(1) => content!(Sticker {
+                    path_option,
+                    file_name_option: None,
+                    width: 0,
+                    height: 0,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                    emoji_option: None,
+                    pack_id_option: None,
+                    pack_name_option: None,
+                }),
+                Some(2) => content!(File {
+                    path_option,
+                    file_name_option: None,
+                    mime_type_option: None,
+                    thumbnail_path_option: None,
+                }),
+                _ => unreachable!(),
+            };
+            let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+            mr.contents = vec![content];
+        });
+    let ds_uuid = dao_holder.dao.ds_uuid();
+
+    let policy = MediaPolicy {
+        max_file_size_bytes: None,
+        allowed_kinds: vec![MediaKind::File as i32],
+        skip_videos: None,
+    };
+    let skipped = apply_media_policy(&mut dao_holder.dao, &policy)?;
+    assert_eq!(skipped, 1);
+    let kinds = dao_holder.dao.cwms[&ds_uuid][0].messages.iter().map(|m| match m.typed() {
+        message_regular_pat! { contents, .. } => content_media_file(&contents[0]).map(|(kind, _, _)| kind),
+        _ => unreachable!(),
+    }).collect_vec();
+    assert_eq!(kinds, vec![None, Some(MediaKind::File)]);
+    Ok(())
+}
@@ -279,6 +279,8 @@ fn convert_cartoon(src: &str) -> Result<TextAndTyped> {
                 mime_type_option: None,
                 thumbnail_path_option: None,
                 emoji_option,
+                pack_id_option: None,
+                pack_name_option: None,
             })
         ],
         ..Default::default()
@@ -586,7 +588,9 @@ fn next_u32_size(bytes: &[u8]) -> (usize, &[u8]) {
 
 /// Assumes the next 4 payload bytes to specify the size of the chunk. Read and return it, and the rest of the payload.
 fn next_sized_chunk(payload: &[u8]) -> Result<(&[u8], &[u8])> {
+    ensure!(payload.len() >= 4, "Payload ended before chunk size");
     let (len, rest) = next_u32_size(payload);
+    ensure!(len <= rest.len(), "Chunk size {len} exceeds remaining {} bytes", rest.len());
     Ok(rest.split_at(len))
 }
 
@@ -139,7 +139,7 @@ fn load_conversation_messages(conv_username: &str, db_bytes: &[u8]) -> Result<Ve
         let mut mra_msg = DbMessage { offset: offset as u32, header, payload: payload.to_vec(), sections: vec![] };
 
         require_format_clue(bytes.is_empty(), &mra_msg, conv_username, "incorrect remainder")?;
-        require_format_clue(payload[0] == 0x01, &mra_msg, conv_username, "incorrect payload magic")?;
+        require_format_clue(payload.first() == Some(&0x01), &mra_msg, conv_username, "incorrect payload magic")?;
 
         let (payload_inner_length, payload) = next_u32_size(&payload[1..]);
         require_format_clue(payload_inner_length == payload.len(), &mra_msg, conv_username,
@@ -629,6 +629,7 @@ fn convert_message(
                             lat_str,
                             lon_str,
                             duration_sec_option: None,
+                            updates: vec![],
                         });
                     }
                     MraMessageType::Sticker => {
@@ -709,6 +710,8 @@ fn convert_message(
                         mime_type_option: None,
                         thumbnail_path_option: None,
                         emoji_option: None,
+                        pack_id_option: None,
+                        pack_name_option: None,
                     })
                 ],
                 ..Default::default()
@@ -786,6 +789,8 @@ pub(super) fn merge_conversations(
                         member_ids: vec![], // Will be changed later
                         msg_count: -1, // Will be changed later
                         main_chat_id: None,
+                        archived: None,
+                        hidden: None,
                     },
                     messages: vec![],
                 }
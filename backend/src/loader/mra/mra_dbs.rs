@@ -318,6 +318,8 @@ pub(super) fn convert_messages(
                 member_ids,
                 msg_count: msgs.len() as i32,
                 main_chat_id: None,
+                archived: None,
+                hidden: None,
             },
             messages: msgs,
         });
@@ -486,6 +488,7 @@ fn convert_message(
                 lat_str,
                 lon_str,
                 duration_sec_option: None,
+                updates: vec![],
             });
             (vec![RichText::make_plain("(Location changed)".to_owned())],
              message_regular! {
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+use crate::utils::http_client::{post_bytes_blocking, shared_http_client};
+
+#[cfg(test)]
+#[path = "ocr_tests.rs"]
+mod tests;
+
+const TESSERACT: &str = "tesseract";
+
+/// Recognized text shorter than this is most likely noise (e.g. a photo without any actual text).
+const MIN_TEXT_LEN: usize = 3;
+
+/// Backend extracting text from images.
+pub trait OcrEngine: Send + Sync {
+    fn name(&self) -> String;
+
+    /// Whether the engine can be used at all, e.g. whether external tool is installed.
+    fn check_available(&self) -> EmptyRes;
+
+    /// Recognized text, as-is.
+    fn recognize(&self, path: &Path) -> Result<String>;
+}
+
+/// Runs `tesseract` executable (should be available on `PATH`) with the given languages, e.g. `eng+rus`.
+pub struct TesseractOcr {
+    pub languages: Option<String>,
+}
+
+impl OcrEngine for TesseractOcr {
+    fn name(&self) -> String {
+        TESSERACT.to_owned()
+    }
+
+    fn check_available(&self) -> EmptyRes {
+        Command::new(TESSERACT).arg("--version").output()?;
+        Ok(())
+    }
+
+    fn recognize(&self, path: &Path) -> Result<String> {
+        let mut cmd = Command::new(TESSERACT);
+        cmd.arg(path).arg("stdout");
+        if let Some(ref languages) = self.languages {
+            cmd.arg("-l").arg(languages);
+        }
+        let output = cmd.output()?;
+        ensure!(output.status.success(), "{TESSERACT} failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Sends raw image bytes as a POST request body to the given URL, expects recognized text in response body.
+/// Requests are made through a shared HTTP client, so this is unavailable in offline mode.
+pub struct HttpOcr {
+    pub url: String,
+}
+
+impl OcrEngine for HttpOcr {
+    fn name(&self) -> String {
+        format!("OCR service at {}", self.url)
+    }
+
+    fn check_available(&self) -> EmptyRes {
+        ensure!(!shared_http_client().is_offline(), "Offline mode is on");
+        Ok(())
+    }
+
+    fn recognize(&self, path: &Path) -> Result<String> {
+        let bytes = fs::read(path)?;
+        match post_bytes_blocking(shared_http_client(), &self.url, bytes)? {
+            HttpResponse::Ok(body) => Ok(String::from_utf8_lossy(&body).into_owned()),
+            HttpResponse::Failure { status, body, .. } =>
+                bail!("OCR service responded with {status}: {}", String::from_utf8_lossy(&body)),
+        }
+    }
+}
+
+/// Parses OCR engine specification: either `tesseract` (optionally followed by `:<languages>`,
+/// e.g. `tesseract:eng+rus`) or an HTTP(S) URL of an OCR service.
+pub fn parse_ocr_engine(spec: &str) -> Result<Box<dyn OcrEngine>> {
+    if spec.starts_with("http://") || spec.starts_with("https://") {
+        return Ok(Box::new(HttpOcr { url: spec.to_owned() }));
+    }
+    match spec.split_once(':') {
+        None if spec == TESSERACT =>
+            Ok(Box::new(TesseractOcr { languages: None })),
+        Some((TESSERACT, languages)) if !languages.is_empty() =>
+            Ok(Box::new(TesseractOcr { languages: Some(languages.to_owned()) })),
+        _ => bail!("Unknown OCR engine '{spec}', expected '{TESSERACT}[:<languages>]' or a URL"),
+    }
+}
+
+/// For every photo whose file is present in a freshly loaded dataset, recognize text on it,
+/// so that it could be found by search.
+pub fn recognize_photos(dao: &mut InMemoryDao, engine: &dyn OcrEngine) -> EmptyRes {
+    if let Err(e) = engine.check_available() {
+        log::warn!("{} is not available, skipping OCR: {e}", engine.name());
+        return Ok(());
+    }
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("OCR can only be performed for a single dataset"))?.uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+
+    let mut num_recognized = 0;
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        for msg in cwm.messages.iter_mut() {
+            let mut changed = false;
+            let Some(message::Typed::Regular(mr)) = msg.typed.as_mut() else { continue };
+            for content in mr.contents.iter_mut() {
+                let Some(content::SealedValueOptional::Photo(photo)) = content.sealed_value_optional.as_mut() else {
+                    continue;
+                };
+                if photo.ocr_text_option.is_some() { continue; }
+                let Some(path) = photo.path_option.as_ref().map(|p| ds_root.to_absolute(p)) else { continue };
+                if !path.exists() { continue; }
+                match engine.recognize(&path) {
+                    Ok(text) => {
+                        let text = text.split_whitespace().join(" ");
+                        if text.chars().count() >= MIN_TEXT_LEN {
+                            photo.ocr_text_option = Some(text);
+                            changed = true;
+                        }
+                    }
+                    Err(e) => log::warn!("Couldn't recognize text on {}: {e}", path.display()),
+                }
+            }
+            if changed {
+                msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+                num_recognized += 1;
+            }
+        }
+    }
+    log::info!("Recognized text on photos of {num_recognized} messages");
+    Ok(())
+}
@@ -0,0 +1,89 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn recognizing() -> EmptyRes {
+    let msgs = (1..=3).map(|idx| create_regular_message(idx, 1)).collect_vec();
+    let mut dao_holder = create_simple_dao(false, "Photos", msgs, 2, &|_, ds_root, msg| {
+        let idx = msg.source_id_option.unwrap();
+        let path_option = if idx == 3 {
+            None
+        } else {
+            let rel_path = format!("photo_{idx}.jpg");
+            create_named_file(&ds_root.to_absolute(&rel_path), &[]);
+            Some(rel_path)
+        };
+        msg.text = vec![RichText::make_plain(format!("Look {idx}"))];
+        let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+        mr.contents = vec![content!(Photo {
+            path_option,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        })];
+        msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+    });
+    let dao = dao_holder.dao.as_mut();
+    recognize_photos(dao, &MockOcr)?;
+
+    let ds_uuid = dao.ds_uuid();
+    let msgs = &dao.cwms[&ds_uuid][0].messages;
+    let ocr_texts = msgs.iter().map(|m| {
+        let message_regular_pat! { contents, .. } = m.typed() else { unreachable!() };
+        let Some(content::SealedValueOptional::Photo(photo)) = contents[0].sealed_value_optional.as_ref() else {
+            unreachable!()
+        };
+        photo.ocr_text_option.clone()
+    }).collect_vec();
+    assert_eq!(ocr_texts, vec![Some("Total: 42 EUR".to_owned()), None, None]);
+    assert_eq!(msgs.iter().map(|m| m.searchable_string.as_str()).collect_vec(),
+               vec!["Look 1 Total: 42 EUR", "Look 2", "Look 3"]);
+    Ok(())
+}
+
+#[test]
+fn parsing_engine_spec() -> EmptyRes {
+    assert_eq!(parse_ocr_engine("tesseract")?.name(), "tesseract");
+    assert_eq!(parse_ocr_engine("tesseract:eng+rus")?.name(), "tesseract");
+    assert_eq!(parse_ocr_engine("http://localhost:8884/ocr")?.name(), "OCR service at http://localhost:8884/ocr");
+    assert!(parse_ocr_engine("tesseract:").is_err());
+    assert!(parse_ocr_engine("easyocr").is_err());
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+/// Recognizes a receipt on the first photo and a short noise on the second one.
+struct MockOcr;
+
+impl OcrEngine for MockOcr {
+    fn name(&self) -> String {
+        "mock".to_owned()
+    }
+
+    fn check_available(&self) -> EmptyRes {
+        Ok(())
+    }
+
+    fn recognize(&self, path: &Path) -> Result<String> {
+        Ok(match path_file_name(path)? {
+            "photo_1.jpg" => "  Total:\n42   EUR\n\n".to_owned(),
+            _ => " ~\n".to_owned(),
+        })
+    }
+}
@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use csv::StringRecord;
+use indexmap::IndexMap;
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::loader::DataLoader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "reddit_tests.rs"]
+mod tests;
+
+const MESSAGES_CSV: &str = "messages.csv";
+const CHAT_HISTORY_CSV: &str = "chat_history.csv";
+const STATISTICS_CSV: &str = "statistics.csv";
+
+const MESSAGES_HEADER_START: &str = "id,permalink,thread_id,date,";
+const CHAT_HISTORY_HEADER_START: &str = "message_id,created_at,updated_at,username,message,";
+
+/// Loader for Reddit GDPR data export, takes either `messages.csv`/`chat_history.csv` or a folder containing them.
+///
+/// Private messages (`messages.csv`) and Reddit Chat direct messages (`chat_history.csv`) with the same user
+/// are combined into a single personal chat.
+pub struct RedditDataLoader;
+
+impl DataLoader for RedditDataLoader {
+    fn name(&self) -> String { "Reddit".to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        let root = get_root_path(path)?;
+        let messages_path = root.join(MESSAGES_CSV);
+        let chat_history_path = root.join(CHAT_HISTORY_CSV);
+        if !messages_path.exists() && !chat_history_path.exists() {
+            bail!("Neither {MESSAGES_CSV} nor {CHAT_HISTORY_CSV} found");
+        }
+        if messages_path.exists() && !super::first_line(&messages_path)?.starts_with(MESSAGES_HEADER_START) {
+            bail!("{MESSAGES_CSV} has unexpected header");
+        }
+        if chat_history_path.exists() && !super::first_line(&chat_history_path)?.starts_with(CHAT_HISTORY_HEADER_START) {
+            bail!("{CHAT_HISTORY_CSV} has unexpected header");
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, path: &Path, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+        parse_reddit_export(&get_root_path(path)?, ds, user_input_requester)
+    }
+}
+
+fn get_root_path(path: &Path) -> Result<PathBuf> {
+    if path.is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    let filename = path_file_name(path)?;
+    if filename != MESSAGES_CSV && filename != CHAT_HISTORY_CSV {
+        bail!("File is not {MESSAGES_CSV} or {CHAT_HISTORY_CSV}");
+    }
+    Ok(path.parent().unwrap().to_path_buf())
+}
+
+/// Private message from `messages.csv`.
+struct RawPrivateMessage {
+    id: String,
+    thread_id: String,
+    timestamp: Timestamp,
+    from: String,
+    to: String,
+    subject: String,
+    body: String,
+}
+
+/// Reddit Chat message from `chat_history.csv`.
+struct RawChatMessage {
+    id: String,
+    timestamp: Timestamp,
+    edit_timestamp: Timestamp,
+    username: String,
+    text: String,
+    parent_id: String,
+    channel_url: String,
+    channel_name: String,
+    is_direct: bool,
+}
+
+struct ChatAccumulator {
+    name_option: Option<String>,
+    tpe: ChatType,
+    member_ids: Vec<UserId>,
+    messages: Vec<Message>,
+}
+
+fn parse_reddit_export(root: &Path, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+    let messages_path = root.join(MESSAGES_CSV);
+    let pms = if messages_path.exists() { parse_private_messages(&messages_path)? } else { vec![] };
+
+    let chat_history_path = root.join(CHAT_HISTORY_CSV);
+    let chat_msgs = if chat_history_path.exists() { parse_chat_messages(&chat_history_path)? } else { vec![] };
+
+    // Reddit only identifies users by their usernames.
+    let mut users: IndexMap<String, User> = IndexMap::new();
+    let usernames = pms.iter().flat_map(|pm| [&pm.from, &pm.to])
+        .chain(chat_msgs.iter().map(|cm| &cm.username));
+    for username in usernames {
+        if !users.contains_key(username) {
+            users.insert(username.clone(), User {
+                ds_uuid: ds.uuid.clone(),
+                id: super::hash_to_id(username),
+                first_name_option: None,
+                last_name_option: None,
+                username_option: Some(username.clone()),
+                phone_number_option: None,
+                profile_pictures: vec![],
+            });
+        }
+    }
+
+    let myself_username = match parse_account_name(&root.join(STATISTICS_CSV))? {
+        Some(name) => name,
+        None => {
+            let users_vec = users.values().cloned().collect_vec();
+            let myself_idx = user_input_requester.choose_myself(&users_vec)?;
+            users_vec[myself_idx].username_option.clone().unwrap()
+        }
+    };
+    let myself_id = UserId(super::hash_to_id(&myself_username));
+    if !users.contains_key(&myself_username) {
+        users.insert(myself_username.clone(), User {
+            ds_uuid: ds.uuid.clone(),
+            id: *myself_id,
+            first_name_option: None,
+            last_name_option: None,
+            username_option: Some(myself_username.clone()),
+            phone_number_option: None,
+            profile_pictures: vec![],
+        });
+    }
+    let user_id = |username: &str| UserId(users[username].id);
+
+    let mut chats: IndexMap<i64, ChatAccumulator> = IndexMap::new();
+
+    for pm in pms.into_iter() {
+        let other_username = if pm.from == myself_username { &pm.to } else { &pm.from };
+        let from_id = user_id(&pm.from);
+        let source_id = parse_base36_id(&pm.id)?;
+        let reply_to_message_id_option = match pm.thread_id.strip_prefix("t4_") {
+            Some(thread_id) => Some(parse_base36_id(thread_id)?),
+            None => None,
+        };
+
+        // Subject is only meaningful for the first message in a thread, the rest are "re: <subject>".
+        let mut text = vec![];
+        if reply_to_message_id_option.is_none() && !pm.subject.is_empty() {
+            text.push(RichText::make_bold(pm.subject));
+            text.extend(RichText::parse_markdown(&format!("\n{}", pm.body)));
+        } else {
+            text.extend(RichText::parse_markdown(&pm.body));
+        }
+
+        personal_chat(&mut chats, myself_id, &users[other_username]).messages.push(
+            MessageBuilder::regular(*pm.timestamp, from_id)
+                .source_id(source_id)
+                .rich_texts(text)
+                .reply_to(reply_to_message_id_option)
+                .build()
+        );
+    }
+
+    let chat_msgs_by_channel = chat_msgs.into_iter().into_group_map_by(|cm| cm.channel_url.clone());
+    for (channel_url, channel_msgs) in chat_msgs_by_channel.into_iter().sorted_by_key(|(_, msgs)| msgs[0].timestamp) {
+        let member_usernames = channel_msgs.iter().map(|cm| &cm.username).unique().collect_vec();
+        let others = member_usernames.iter().filter(|u| ***u != myself_username).collect_vec();
+        let is_direct = channel_msgs[0].is_direct;
+
+        let messages = channel_msgs.iter().map(|cm| {
+            MessageBuilder::regular(*cm.timestamp, user_id(&cm.username))
+                .source_id(super::hash_to_id(&cm.id))
+                .rich_texts(RichText::parse_markdown(&cm.text))
+                .edited_at(if cm.edit_timestamp != cm.timestamp { Some(*cm.edit_timestamp) } else { None })
+                .reply_to(match cm.parent_id.as_str() {
+                    "" => None,
+                    parent_id => Some(super::hash_to_id(parent_id)),
+                })
+                .build()
+        }).collect_vec();
+
+        if is_direct && others.len() == 1 {
+            personal_chat(&mut chats, myself_id, &users[others[0].as_str()]).messages.extend(messages);
+        } else {
+            let mut member_ids = vec![myself_id];
+            member_ids.extend(others.iter().map(|u| user_id(u)));
+            let name_option = match channel_msgs[0].channel_name.as_str() {
+                "" => None,
+                name => Some(name.to_owned()),
+            };
+            chats.insert(super::hash_to_id(&channel_url), ChatAccumulator {
+                name_option,
+                tpe: if is_direct { ChatType::Personal } else { ChatType::PrivateGroup },
+                member_ids,
+                messages,
+            });
+        }
+    }
+
+    let cwms = chats.into_iter().map(|(id, mut acc)| {
+        acc.messages.sort_by_key(|m| (m.timestamp, m.source_id_option));
+        for (idx, m) in acc.messages.iter_mut().enumerate() {
+            m.internal_id = idx as i64;
+        }
+        ChatWithMessages {
+            chat: Chat {
+                ds_uuid: ds.uuid.clone(),
+                id,
+                name_option: acc.name_option,
+                source_type: SourceType::Reddit as i32,
+                tpe: acc.tpe as i32,
+                img_path_option: None,
+                member_ids: acc.member_ids.iter().map(|id| **id).collect_vec(),
+                msg_count: acc.messages.len() as i32,
+                main_chat_id: None,
+                archived: None,
+                hidden: None,
+            },
+            messages: acc.messages,
+        }
+    }).collect_vec();
+
+    let myself = users.shift_remove(&myself_username).unwrap();
+    let users = [myself].into_iter().chain(users.into_values()).collect_vec();
+
+    Ok(Box::new(InMemoryDao::new_single(
+        format!("Reddit ({})", path_file_name(root)?),
+        ds,
+        root.to_path_buf(),
+        myself_id,
+        users,
+        cwms,
+    )))
+}
+
+/// Personal chats are keyed by the other user ID.
+fn personal_chat<'a>(chats: &'a mut IndexMap<i64, ChatAccumulator>, myself_id: UserId, other: &User) -> &'a mut ChatAccumulator {
+    chats.entry(other.id).or_insert_with(|| ChatAccumulator {
+        name_option: other.pretty_name_option(),
+        tpe: ChatType::Personal,
+        member_ids: vec![myself_id, other.id()],
+        messages: vec![],
+    })
+}
+
+fn read_csv(path: &Path) -> Result<(StringRecord, Vec<StringRecord>)> {
+    let mut reader = csv::Reader::from_path(path)
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    let headers = reader.headers()?.clone();
+    let records = reader.records().try_collect()?;
+    Ok((headers, records))
+}
+
+fn column(headers: &StringRecord, name: &str) -> Result<usize> {
+    headers.iter().position(|h| h == name).with_context(|| format!("Column '{name}' not found"))
+}
+
+fn parse_account_name(path: &Path) -> Result<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let (headers, records) = read_csv(path)?;
+    let (statistic_idx, value_idx) = (column(&headers, "statistic")?, column(&headers, "value")?);
+    Ok(records.iter()
+        .find(|r| &r[statistic_idx] == "account name")
+        .map(|r| r[value_idx].to_owned()))
+}
+
+fn parse_private_messages(path: &Path) -> Result<Vec<RawPrivateMessage>> {
+    let (headers, records) = read_csv(path)?;
+    let [id, thread_id, date, from, to, subject, body] =
+        ["id", "thread_id", "date", "from", "to", "subject", "body"].map(|c| column(&headers, c));
+    let (id, thread_id, date, from, to, subject, body) = (id?, thread_id?, date?, from?, to?, subject?, body?);
+    records.iter().map(|r| Ok(RawPrivateMessage {
+        id: r[id].to_owned(),
+        thread_id: r[thread_id].to_owned(),
+        timestamp: parse_datetime(&r[date])?,
+        from: r[from].to_owned(),
+        to: r[to].to_owned(),
+        subject: r[subject].to_owned(),
+        body: r[body].to_owned(),
+    })).try_collect()
+}
+
+fn parse_chat_messages(path: &Path) -> Result<Vec<RawChatMessage>> {
+    let (headers, records) = read_csv(path)?;
+    let [id, created_at, updated_at, username, message, parent, channel_url, channel_name, conversation_type] =
+        ["message_id", "created_at", "updated_at", "username", "message", "thread_parent_message_id",
+            "channel_url", "channel_name", "conversation_type"].map(|c| column(&headers, c));
+    let (id, created_at, updated_at, username, message, parent, channel_url, channel_name, conversation_type) =
+        (id?, created_at?, updated_at?, username?, message?, parent?, channel_url?, channel_name?, conversation_type?);
+    records.iter().map(|r| {
+        let timestamp = parse_datetime(&r[created_at])?;
+        Ok(RawChatMessage {
+            id: r[id].to_owned(),
+            timestamp,
+            edit_timestamp: if r[updated_at].is_empty() { timestamp } else { parse_datetime(&r[updated_at])? },
+            username: r[username].to_owned(),
+            text: r[message].to_owned(),
+            parent_id: r[parent].to_owned(),
+            channel_url: r[channel_url].to_owned(),
+            channel_name: r[channel_name].to_owned(),
+            is_direct: &r[conversation_type] == "direct",
+        })
+    }).try_collect()
+}
+
+/// Reddit message IDs are base-36 numbers.
+fn parse_base36_id(s: &str) -> Result<i64> {
+    i64::from_str_radix(s, 36).with_context(|| format!("Not a valid Reddit ID: {s}"))
+}
+
+/// Datetime format used by Reddit export: `2024-05-01 10:00:00 UTC`
+fn parse_datetime(s: &str) -> Result<Timestamp> {
+    const DATE_TIME_FMT: &str = "%Y-%m-%d %H:%M:%S UTC";
+    let naive_dt = NaiveDateTime::parse_from_str(s, DATE_TIME_FMT)
+        .with_context(|| format!("Cannot parse datetime: {s}"))?;
+    Ok(Timestamp(Utc.from_utc_datetime(&naive_dt).timestamp()))
+}
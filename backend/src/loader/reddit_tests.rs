@@ -0,0 +1,152 @@
+#![allow(unused_imports)]
+
+use chrono::prelude::*;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::ChatHistoryReader;
+use crate::entity_utils::*;
+use crate::loader::hash_to_id;
+use crate::protobuf::history::message::*;
+
+use super::*;
+
+const LOADER: RedditDataLoader = RedditDataLoader;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2024_05() -> EmptyRes {
+    let res = resource("reddit_2024-05");
+    LOADER.looks_about_right(&res)?;
+    LOADER.looks_about_right(&res.join(MESSAGES_CSV))?;
+    LOADER.looks_about_right(&res.join(CHAT_HISTORY_CSV))?;
+
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let user = |username: &str| User {
+        ds_uuid: ds_uuid.clone(),
+        id: hash_to_id(username),
+        first_name_option: None,
+        last_name_option: None,
+        username_option: Some(username.to_owned()),
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself, user("my_username"));
+
+    let friend = user("friend_user");
+    let other = user("other_user");
+    let third = user("third_user");
+    let mut users = dao.users_single_ds();
+    users.sort_by_key(|u| u.id);
+    let mut expected_users = vec![myself.clone(), friend.clone(), other.clone(), third.clone()];
+    expected_users.sort_by_key(|u| u.id);
+    assert_eq!(users, expected_users);
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 3);
+
+    let regular = |edit_timestamp_option: Option<i64>, reply_to_message_id_option: Option<i64>| message_regular! {
+        edit_timestamp_option,
+        is_deleted: false,
+        forward_from_name_option: None,
+        forward_from_id_option: None,
+        ephemeral_duration_sec_option: None,
+        reply_to_message_id_option,
+        contents: vec![],
+    };
+
+    // Private messages and chat messages with the same user are combined
+    {
+        let cwm = &cwms[0];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: friend.id,
+            name_option: Some("friend_user".to_owned()),
+            source_type: SourceType::Reddit as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, friend.id],
+            msg_count: 4,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs, vec![
+            Message::new(
+                0,
+                Some(i64::from_str_radix("abc1", 36)?),
+                dt("2024-05-01 10:00:00", Some(&Utc.fix())).timestamp(),
+                myself.id(),
+                vec![
+                    RichText::make_bold("Hello".to_owned()),
+                    RichText::make_plain("\nHi there!\nLong time no see.".to_owned()),
+                ],
+                regular(None, None),
+            ),
+            Message::new(
+                1,
+                Some(i64::from_str_radix("abc2", 36)?),
+                dt("2024-05-01 11:00:00", Some(&Utc.fix())).timestamp(),
+                friend.id(),
+                vec![RichText::make_plain("Indeed it's been a while".to_owned())],
+                regular(None, Some(i64::from_str_radix("abc1", 36)?)),
+            ),
+            Message::new(
+                2,
+                Some(hash_to_id("m1")),
+                dt("2024-05-03 12:00:00", Some(&Utc.fix())).timestamp(),
+                friend.id(),
+                vec![RichText::make_plain("Moving to chat then".to_owned())],
+                regular(None, None),
+            ),
+            Message::new(
+                3,
+                Some(hash_to_id("m2")),
+                dt("2024-05-03 12:01:00", Some(&Utc.fix())).timestamp(),
+                myself.id(),
+                vec![RichText::make_plain("Sure (edited)".to_owned())],
+                regular(Some(dt("2024-05-03 12:05:00", Some(&Utc.fix())).timestamp()), Some(hash_to_id("m1"))),
+            ),
+        ]);
+    }
+
+    // Incoming private message only
+    {
+        let cwm = &cwms[1];
+        assert_eq!(cwm.chat.id, other.id);
+        assert_eq!(cwm.chat.tpe, ChatType::Personal as i32);
+        assert_eq!(cwm.chat.member_ids, vec![myself.id, other.id]);
+        assert_eq!(cwm.chat.msg_count, 1);
+    }
+
+    // Group chat
+    {
+        let cwm = &cwms[2];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: hash_to_id("https://chat.reddit.com/room/!bbb:reddit.com"),
+            name_option: Some("Our group".to_owned()),
+            source_type: SourceType::Reddit as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, friend.id, third.id],
+            msg_count: 3,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs.iter().map(|m| m.from_id).collect_vec(), vec![friend.id, third.id, myself.id]);
+    }
+
+    Ok(())
+}
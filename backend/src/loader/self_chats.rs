@@ -0,0 +1,24 @@
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "self_chats_tests.rs"]
+mod tests;
+
+/// Bring chats with oneself in a freshly loaded dataset to a common convention regardless of their source
+/// (see [ChatType::SelfChat]): myself is the only member, and chat is named [SELF_CHAT_NAME].
+pub fn normalize_self_chats(dao: &mut InMemoryDao) -> EmptyRes {
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Self chats can only be normalized for a single dataset"))?.uuid;
+    let myself_id = dao.myself(&ds_uuid)?.id;
+
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        if cwm.chat.tpe() != ChatType::SelfChat { continue; }
+        cwm.chat.member_ids = vec![myself_id];
+        cwm.chat.name_option = Some(SELF_CHAT_NAME.to_owned());
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn normalizing() -> EmptyRes {
+    let users = (1..=2).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let self_chat = Chat {
+        tpe: ChatType::SelfChat as i32,
+        name_option: None,
+        ..create_group_chat(&ZERO_PB_UUID, 1, "Self", vec![1, 2], 0)
+    };
+    let group_chat = create_group_chat(&ZERO_PB_UUID, 2, "Group", vec![1, 2], 0);
+    let mut dao_holder = create_dao("One", users, vec![
+        ChatWithMessages { chat: self_chat, messages: vec![] },
+        ChatWithMessages { chat: group_chat.clone(), messages: vec![] },
+    ], |_, _| {});
+    let dao = dao_holder.dao.as_mut();
+
+    normalize_self_chats(dao)?;
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms[0].chat.tpe(), ChatType::SelfChat);
+    assert_eq!(cwms[0].chat.member_ids, vec![1]);
+    assert_eq!(cwms[0].chat.name_option, Some(SELF_CHAT_NAME.to_owned()));
+    assert_eq!(cwms[1].chat.member_ids, group_chat.member_ids);
+    assert_eq!(cwms[1].chat.name_option, group_chat.name_option);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "senders_tests.rs"]
+mod tests;
+
+/// Phone-looking names with fewer digits are more likely to be something else.
+const MIN_PHONE_DIGITS: usize = 7;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SenderResolutionReport {
+    /// Name-only users and users they were merged into
+    pub merged: Vec<(User, User)>,
+    /// Placeholders created for senders and members that had no user at all
+    pub created: Vec<User>,
+    /// Name-only users that matched no known user, or more than one
+    pub unresolved: Vec<User>,
+}
+
+/// Some sources (e.g. WhatsApp text exports) only know a sender by the name string, so such senders end up as
+/// name-only users (no username or phone number, ID derived from the name) in a freshly loaded dataset.
+/// Every such user is matched against other users:
+/// * a phone-looking name is matched against phone numbers,
+/// * otherwise, a name is matched against full names and usernames, case-insensitively.
+///
+/// If exactly one user matches, the name-only user is merged into them, otherwise it stays as-is.
+/// Additionally, explicit placeholder users are created for sender and member IDs that have no user.
+pub fn resolve_senders(dao: &mut InMemoryDao) -> Result<SenderResolutionReport> {
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Senders can only be resolved for a single dataset"))?.uuid;
+    let myself_id = dao.myself(&ds_uuid)?.id();
+    let mut report = SenderResolutionReport::default();
+
+    let users = dao.users(&ds_uuid)?;
+    let (name_only, known): (Vec<_>, Vec<_>) = users.into_iter()
+        .partition(|u| u.id() != myself_id && is_name_only(u));
+
+    let mut replacements: HashMap<i64, i64> = HashMap::new();
+    for user in name_only {
+        let name = user.pretty_name();
+        let matches = known.iter().filter(|k| matches_name(k, &name)).collect_vec();
+        match matches.as_slice() {
+            [target] => {
+                replacements.insert(user.id, target.id);
+                report.merged.push((user, (*target).clone()));
+            }
+            _ => report.unresolved.push(user),
+        }
+    }
+
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        let replace = |id: &mut i64| if let Some(new_id) = replacements.get(id) { *id = *new_id };
+        cwm.chat.member_ids.iter_mut().for_each(replace);
+        cwm.chat.member_ids = cwm.chat.member_ids.iter().cloned().unique().collect_vec();
+        for msg in cwm.messages.iter_mut() {
+            replace(&mut msg.from_id);
+            if let Some(message::Typed::Regular(mr)) = msg.typed.as_mut() {
+                mr.forward_from_id_option.iter_mut().for_each(replace);
+            }
+        }
+    }
+    for (user, _) in report.merged.iter() {
+        dao.remove_user(&ds_uuid, user.id())?;
+    }
+
+    let user_ids: HashSet<i64> = dao.users(&ds_uuid)?.iter().map(|u| u.id).collect();
+    let missing_ids = dao.cwms[&ds_uuid].iter()
+        .flat_map(|cwm| cwm.chat.member_ids.iter().cloned().chain(cwm.messages.iter().map(|m| m.from_id)))
+        .filter(|id| !user_ids.contains(id))
+        .unique()
+        .sorted()
+        .collect_vec();
+    for id in missing_ids {
+        let user = User {
+            ds_uuid: ds_uuid.clone(),
+            id,
+            first_name_option: None,
+            last_name_option: None,
+            username_option: None,
+            phone_number_option: None,
+            profile_pictures: vec![],
+        };
+        dao.put_user(user.clone())?;
+        report.created.push(user);
+    }
+
+    log::info!("Resolved senders: {} merged, {} placeholders created",
+        report.merged.len(), report.created.len());
+    if !report.unresolved.is_empty() {
+        log::warn!("Unresolved senders: {}", report.unresolved.iter().map(|u| u.pretty_name()).join(", "));
+    }
+    Ok(report)
+}
+
+/// User has nothing but a name, and their ID was derived from it.
+fn is_name_only(user: &User) -> bool {
+    user.username_option.is_none() && user.phone_number_option.is_none() &&
+        user.pretty_name_option().is_some_and(|name| super::hash_to_id(&name) == user.id)
+}
+
+fn matches_name(user: &User, name: &str) -> bool {
+    let digits = |s: &str| s.chars().filter(|c| c.is_ascii_digit()).collect::<String>();
+    if looks_like_phone(name) {
+        let name_digits = digits(name);
+        user.phone_number_option.iter()
+            .flat_map(|s| s.split(','))
+            .any(|p| digits(p) == name_digits)
+    } else {
+        let normalize = |s: &str| s.split_whitespace().join(" ").to_lowercase();
+        let name = normalize(name);
+        user.pretty_name_option().is_some_and(|n| normalize(&n) == name) ||
+            user.username_option.as_ref().is_some_and(|u| normalize(u) == name.trim_start_matches('@'))
+    }
+}
+
+fn looks_like_phone(name: &str) -> bool {
+    name.chars().all(|c| c.is_ascii_digit() || "+-() ".contains(c)) &&
+        name.chars().filter(|c| c.is_ascii_digit()).count() >= MIN_PHONE_DIGITS
+}
@@ -0,0 +1,82 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::loader::hash_to_id;
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn resolving() -> EmptyRes {
+    let name_only = |name: &str| User {
+        ds_uuid: ZERO_PB_UUID.clone(),
+        id: hash_to_id(name),
+        first_name_option: Some(name.to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+    let myself = create_user(&ZERO_PB_UUID, 1);
+    let known = create_user(&ZERO_PB_UUID, 2);
+    // Only have names, but IDs are real
+    let namesake1 = User { id: 3, ..name_only("Namesake") };
+    let namesake2 = User { id: 4, ..name_only("Namesake") };
+    let by_name = name_only("user  2");
+    let by_username = name_only("@User2");
+    let by_phone = name_only("+222-22-22");
+    let ambiguous = name_only("Namesake");
+    let stranger = name_only("Stranger");
+    let users = vec![myself, known, namesake1, namesake2,
+                     by_name.clone(), by_username.clone(), by_phone.clone(), ambiguous.clone(), stranger.clone()];
+
+    let msgs = users.iter().enumerate().map(|(idx, u)| Message {
+        from_id: u.id,
+        ..create_regular_message(idx, 1)
+    }).collect_vec();
+    let mut member_ids = users.iter().map(|u| u.id).collect_vec();
+    member_ids.push(999);
+    let chat = create_group_chat(&ZERO_PB_UUID, 1, "One", member_ids, msgs.len());
+    let mut dao_holder = create_dao("One", users, vec![ChatWithMessages { chat, messages: msgs }], |_, _| {});
+    let dao = dao_holder.dao.as_mut();
+    let ds_uuid = dao.ds_uuid();
+
+    let report = resolve_senders(dao)?;
+    assert_eq!(report.merged.iter().map(|(u, t)| (u.id, t.id)).sorted().collect_vec(),
+               vec![(by_name.id, 2), (by_username.id, 2), (by_phone.id, 2)].into_iter().sorted().collect_vec());
+    assert_eq!(report.unresolved.iter().map(|u| u.id).sorted().collect_vec(),
+               vec![ambiguous.id, stranger.id].into_iter().sorted().collect_vec());
+    assert_eq!(report.created, vec![User {
+        ds_uuid: ds_uuid.clone(),
+        id: 999,
+        first_name_option: None,
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    }]);
+
+    assert_eq!(dao.users(&ds_uuid)?.iter().map(|u| u.id).sorted().collect_vec(),
+               vec![1, 2, 3, 4, ambiguous.id, stranger.id, 999].into_iter().sorted().collect_vec());
+
+    let cwm = &dao.cwms[&ds_uuid][0];
+    assert_eq!(cwm.chat.member_ids, vec![1, 2, 3, 4, ambiguous.id, stranger.id, 999]);
+    assert_eq!(cwm.messages.iter().map(|m| m.from_id).collect_vec(),
+               vec![1, 2, 3, 4, 2, 2, 2, ambiguous.id, stranger.id]);
+    Ok(())
+}
+
+#[test]
+fn phone_heuristic() {
+    let user = create_user(&ZERO_PB_UUID, 5);
+    assert!(matches_name(&user, "555 55 55"));
+    assert!(matches_name(&user, "+555-55-55"));
+    assert!(!matches_name(&user, "555 55 56"));
+    // Too short to be a phone
+    assert!(!looks_like_phone("555"));
+    assert!(!looks_like_phone("Agent 5555555"));
+}
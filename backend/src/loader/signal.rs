@@ -36,6 +36,7 @@ const PLAINTEXT_DB_FILENAME: &str = "plaintext.sqlite";
 const CONFIG_FILENAME: &str = "config.json";
 
 const ATTACHMENTS_DIR_NAME: &str = "attachments.noindex";
+const STICKERS_DIR_NAME: &str = "stickers.noindex";
 const DECRYPTED_ATTACHMENTS_DIR_NAME: &str = "_decrypted";
 
 impl DataLoader for SignalDataLoader {
@@ -193,6 +194,9 @@ fn parse_cwms(conn: &Connection,
     // Call details were embedded in JSON in Signal v6, but in v7 they're in separate table
     let mut calls_stmt = conn.prepare(r"SELECT * FROM callsHistory WHERE callId = ?").ok();
 
+    let sticker_pack_titles = parse_sticker_pack_titles(conn)?;
+    let stickers_path = attachments_path.map(|p| p.with_file_name(STICKERS_DIR_NAME));
+
     let mut conv_rows = conv_stmt.query([])?;
     while let Some(row) = conv_rows.next()? {
         let chat_uuid_string = row.get::<_, String>("id")?;
@@ -206,10 +210,10 @@ fn parse_cwms(conn: &Connection,
                 .map_err(|_| anyhow!(r##"Neither "serviceId" nor "uuid" column found in table "conversations""##))?;
         let user_uuid = Uuid::parse_str(&user_uuid)?;
         let user = users.get(&user_uuid).ok_or_else(|| anyhow!("Unknown user"))?;
-        let member_ids = if user.id == *myself_id {
-            vec![*myself_id]
+        let (member_ids, tpe) = if user.id == *myself_id {
+            (vec![*myself_id], ChatType::SelfChat)
         } else {
-            vec![*myself_id, user.id]
+            (vec![*myself_id, user.id], ChatType::Personal)
         };
 
         let mut messages: Vec<Message> = vec![];
@@ -344,6 +348,13 @@ fn parse_cwms(conn: &Connection,
                         reply_to.and_then(|m| m.source_id_option)
                     } else { None };
 
+                // Present for messages sent while disappearing messages were on
+                const EXPIRE_TIMER_KEY: &str = "expireTimer";
+                let ephemeral_duration_sec_option =
+                    if let Some(expire_timer) = json.get(EXPIRE_TIMER_KEY) {
+                        Some(as_i64!(expire_timer, EXPIRE_TIMER_KEY) as i32).filter(|&d| d > 0)
+                    } else { None };
+
                 const ATTACHMENTS_KEY: &str = "attachments";
                 let attachments =
                     if attachments_path.is_none() {
@@ -360,10 +371,18 @@ fn parse_cwms(conn: &Connection,
                     contents.push(c);
                 }
 
+                if let (Some(sticker), Some(stickers_path)) = (json.get(STICKER_KEY), stickers_path.as_deref()) {
+                    let sticker = parse_sticker(as_object!(sticker, STICKER_KEY))?;
+                    let c = decrypt_sticker(sticker, &sticker_pack_titles, stickers_path, attachments_decrypt_path.unwrap())?;
+                    contents.push(c);
+                }
+
                 message_regular! {
                     edit_timestamp_option,
                     is_deleted,
                     forward_from_name_option: None,
+                    forward_from_id_option: None,
+                    ephemeral_duration_sec_option,
                     reply_to_message_id_option,
                     contents,
                 }
@@ -391,11 +410,13 @@ fn parse_cwms(conn: &Connection,
                     id: *chat_id,
                     name_option: user.first_name_option.clone(),
                     source_type: SourceType::Signal as i32,
-                    tpe: ChatType::Personal as i32,
+                    tpe: tpe as i32,
                     img_path_option: None,
                     member_ids,
                     msg_count: messages.len() as i32,
                     main_chat_id: None,
+                    archived: None,
+                    hidden: None,
                 },
                 messages,
             });
@@ -416,6 +437,9 @@ fn decrypt_attachment(a: LinkedAttachment, src_path: &Path, dst_path: &Path) ->
             height: a.file_info.height.unwrap_or(0),
             mime_type_option: Some(mime_type),
             is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
         })
     } else if mime_type.starts_with("video/") {
         let thumbnail_path_option =
@@ -443,6 +467,7 @@ fn decrypt_attachment(a: LinkedAttachment, src_path: &Path, dst_path: &Path) ->
             file_name_option,
             mime_type,
             duration_sec_option: None,
+            waveform_option: None,
         })
     } else {
         bail!("Unsupported attachment MIME type: {mime_type}")
@@ -450,6 +475,28 @@ fn decrypt_attachment(a: LinkedAttachment, src_path: &Path, dst_path: &Path) ->
     Ok(result)
 }
 
+fn decrypt_sticker(s: LinkedSticker,
+                   pack_titles: &HashMap<String, String>,
+                   src_path: &Path,
+                   dst_path: &Path) -> Result<Content> {
+    let path_option = match s.file_info {
+        Some(ref file_info) => decrypt_linked_file(Some(STICKER_KEY), file_info, src_path, dst_path)?,
+        None => None,
+    };
+    let pack_name_option = pack_titles.get(&s.pack_id).cloned();
+    Ok(content!(Sticker {
+        path_option,
+        file_name_option: None,
+        width: s.file_info.as_ref().and_then(|fi| fi.width).unwrap_or(0),
+        height: s.file_info.as_ref().and_then(|fi| fi.height).unwrap_or(0),
+        mime_type_option: s.file_info.map(|fi| fi.mime_type),
+        thumbnail_path_option: None,
+        emoji_option: s.emoji,
+        pack_id_option: Some(s.pack_id),
+        pack_name_option,
+    }))
+}
+
 /// Returns relative path to decrypted file
 fn decrypt_linked_file(name: Option<&str>,
                        file_info: &LinkedFileInfo,
@@ -559,6 +606,31 @@ fn parse_linked_file_info(json: &Object, key: &str) -> Result<LinkedFileInfo> {
     Ok(LinkedFileInfo { _version: version, mime_type, path, local_key, width, height })
 }
 
+const STICKER_KEY: &str = "sticker";
+
+fn parse_sticker(json: &Object) -> Result<LinkedSticker> {
+    let pack_id = get_field_string!(json, STICKER_KEY, "packId");
+    let emoji = json.get("emoji").and_then(|v| v.as_str()).map(|v| v.to_owned());
+    // Sticker data is absent if it wasn't downloaded
+    let file_info = if let Some(data) = json.get("data") {
+        Some(parse_linked_file_info(as_object!(data, STICKER_KEY), &format!("{STICKER_KEY}.data"))?)
+    } else { None };
+    Ok(LinkedSticker { pack_id, emoji, file_info })
+}
+
+/// Sticker pack ID -> title, table is absent in older versions.
+fn parse_sticker_pack_titles(conn: &Connection) -> Result<HashMap<String, String>> {
+    let Ok(mut stmt) = conn.prepare(r"SELECT id, title FROM sticker_packs") else { return Ok(HashMap::new()) };
+    let mut rows = stmt.query([])?;
+    let mut res = HashMap::new();
+    while let Some(row) = rows.next()? {
+        if let Some(title) = row.get::<_, Option<String>>("title")?.filter(|t| !t.is_empty()) {
+            res.insert(row.get::<_, String>("id")?, title);
+        }
+    }
+    Ok(res)
+}
+
 fn get_myself(conn: &Connection) -> Result<UserId> {
     let mut stmt = conn.prepare(r"SELECT * FROM items WHERE id = 'uuid_id'")?;
     let mut rows = stmt.query([])?;
@@ -575,13 +647,13 @@ fn get_myself(conn: &Connection) -> Result<UserId> {
     let json = json_vec.first().unwrap().as_str();
     let idx = json.find(PATTERN).ok_or(anyhow!("Malformed uuid_id JSON!"))?;
     let idx = idx + PATTERN.len() + 1;
-    let uuid = &json[idx..idx + 36];
+    let uuid = json.get(idx..idx + 36).ok_or(anyhow!("Malformed uuid_id JSON!"))?;
     let uuid = Uuid::parse_str(uuid).map_err(|_| anyhow!("Malformed uuid_id JSON!"))?;
     let id = UserId(uuid_to_i64_pos(uuid)?);
     Ok(id)
 }
 
-fn uuid_to_i64_pos(uuid: Uuid) -> Result<i64> {
+pub(super) fn uuid_to_i64_pos(uuid: Uuid) -> Result<i64> {
     let uuid_bytes = uuid.as_bytes();
     let uuid_parts: Vec<[u8; 8]> = vec![
         uuid_bytes[0..8].try_into()?,
@@ -601,6 +673,13 @@ struct LinkedAttachment {
     screenshot: Option<LinkedFileInfo>,
 }
 
+struct LinkedSticker {
+    pack_id: String,
+    emoji: Option<String>,
+
+    file_info: Option<LinkedFileInfo>,
+}
+
 struct LinkedFileInfo {
     _version: Option<i32>,
     mime_type: String,
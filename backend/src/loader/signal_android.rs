@@ -0,0 +1,780 @@
+use super::{DataLoader, hash_to_id};
+use crate::prelude::*;
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use itertools::Itertools;
+use rusqlite::Connection;
+use uuid::Uuid;
+
+use message_service::SealedValueOptional as ServiceSvo;
+
+use super::signal::uuid_to_i64_pos;
+
+#[cfg(test)]
+#[path = "signal_android_tests.rs"]
+mod tests;
+
+/// Loader for an encrypted backup file produced by Signal for Android (Settings > Chats > Chat backups),
+/// based on backups of Signal v7.x.
+///
+/// Backup is a stream of encrypted protobuf frames, mostly SQL statements recreating the app database
+/// interleaved with attachment files. Statements are replayed into an in-memory database, attachments are written
+/// next to the backup file, and then the database is parsed.
+///
+/// Format is documented by https://github.com/bepaald/signalbackup-tools and Signal's own `FullBackupImporter`.
+pub struct SignalAndroidDataLoader;
+
+const NAME: &str = "Signal (Android backup)";
+
+const FILENAME_PREFIX: &str = "signal-";
+const FILENAME_SUFFIX: &str = ".backup";
+
+const DECRYPTED_DIR_SUFFIX: &str = "_decrypted";
+const ATTACHMENTS_DIR_NAME: &str = "attachments";
+
+const ACI_KEY: &str = "account.aci";
+
+impl DataLoader for SignalAndroidDataLoader {
+    fn name(&self) -> String { NAME.to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        let file_name = path_file_name(path)?;
+        if !file_name.starts_with(FILENAME_PREFIX) || !file_name.ends_with(FILENAME_SUFFIX) {
+            bail!("File is not {FILENAME_PREFIX}*{FILENAME_SUFFIX}")
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, path: &Path, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+        let passphrase = user_input_requester.ask_for_text("\
+            Input 30-digit passphrase of Signal backup.\n\
+            It was shown when backups were turned on in Signal app.\n\
+        ".trim())?;
+        let passphrase = passphrase.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+        ensure!(passphrase.len() == 30 && passphrase.chars().all(|c| c.is_ascii_digit()),
+                "Passphrase should consist of 30 digits");
+
+        let file_name = path_file_name(path)?;
+        let ds_root = path.with_file_name(format!("{}{DECRYPTED_DIR_SUFFIX}", file_name.trim_end_matches(FILENAME_SUFFIX)));
+        let mut conn = Connection::open_in_memory()?;
+        let restored = restore_backup(path, &passphrase, &mut conn, &ds_root)?;
+
+        let (users, myself_id) = parse_users(&conn, &ds.uuid, restored.aci_option.as_deref())?;
+        let cwms = parse_cwms(&conn, &ds.uuid, &users, myself_id, &ds_root)?;
+
+        // Only keep users that took part in chats
+        let participating_ids: HashSet<i64> =
+            cwms.iter().flat_map(|cwm| cwm.chat.member_ids.iter().cloned()).collect();
+        let mut users = users.into_values()
+            .filter(|u| u.id == *myself_id || participating_ids.contains(&u.id))
+            .unique_by(|u| u.id)
+            .collect_vec();
+        users.sort_by_key(|u| if u.id == *myself_id { *UserId::MIN } else { u.id });
+
+        Ok(Box::new(InMemoryDao::new_single(
+            format!("{NAME} ({file_name})"),
+            ds,
+            ds_root,
+            myself_id,
+            users,
+            cwms,
+        )))
+    }
+}
+
+//
+// Restoring
+//
+
+struct RestoredBackup {
+    aci_option: Option<String>,
+}
+
+/// Replay SQL statements from the backup into the given connection, writing attachment files into
+/// attachments directory of `ds_root` named after their row IDs.
+fn restore_backup(path: &Path, passphrase: &str, conn: &mut Connection, ds_root: &Path) -> Result<RestoredBackup> {
+    let attachments_dir = ds_root.join(ATTACHMENTS_DIR_NAME);
+    fs::create_dir_all(&attachments_dir)?;
+
+    let input = io::BufReader::new(fs::File::open(path)?);
+    let mut reader = frames::BackupReader::new(input, passphrase)?;
+    let mut aci_option = None;
+    let mut num_statements = 0;
+    let mut num_attachments = 0;
+
+    let tx = conn.transaction()?;
+    loop {
+        let frame = reader.next_frame()?;
+        if frame.end() {
+            break;
+        } else if let Some(statement) = frame.statement {
+            let sql = statement.statement.unwrap_or_default();
+            if is_ignored_statement(&sql) { continue; }
+            let params = statement.parameters.into_iter().map(|p| p.into_sql_value()).collect_vec();
+            tx.execute(&sql, rusqlite::params_from_iter(params))
+                .with_context(|| format!("Failed to execute backup statement {sql}"))?;
+            num_statements += 1;
+        } else if let Some(attachment) = frame.attachment {
+            let file = fs::File::create(attachments_dir.join(attachment.row_id().to_string()))?;
+            let mut file = BufWriter::new(file);
+            reader.read_file(attachment.length(), &mut file)?;
+            file.flush()?;
+            num_attachments += 1;
+        } else if let Some(avatar) = frame.avatar {
+            reader.read_file(avatar.length(), &mut io::sink())?;
+        } else if let Some(sticker) = frame.sticker {
+            reader.read_file(sticker.length(), &mut io::sink())?;
+        } else if let Some(key_value) = frame.key_value {
+            if key_value.key() == ACI_KEY {
+                aci_option = key_value.string_value;
+            }
+        }
+        // Header, database version and preferences are of no interest
+    }
+    tx.commit()?;
+
+    log::info!("Restored {num_statements} statements and {num_attachments} attachments from backup");
+    Ok(RestoredBackup { aci_option })
+}
+
+/// SQLite internal tables can't be created explicitly, and full-text search tables are just indexes.
+fn is_ignored_statement(sql: &str) -> bool {
+    let sql = sql.to_lowercase();
+    sql.starts_with("create table sqlite_")
+        || sql.starts_with("insert into sqlite_")
+        || sql.contains("_fts")
+}
+
+//
+// Parsing
+//
+
+/// Recipient row ID -> user, only for individual recipients.
+type Users = HashMap<i64, User>;
+
+const RECIPIENT_TYPE_INDIVIDUAL: i64 = 0;
+
+fn parse_users(conn: &Connection, ds_uuid: &PbUuid, aci_option: Option<&str>) -> Result<(Users, UserId)> {
+    let mut users = Users::new();
+    let mut aci_to_id = HashMap::new();
+
+    let mut stmt = conn.prepare(r"SELECT * FROM recipient WHERE type = ?")?;
+    let mut rows = stmt.query([RECIPIENT_TYPE_INDIVIDUAL])?;
+    while let Some(row) = rows.next()? {
+        let recipient_id = row.get::<_, i64>("_id")?;
+        let aci_option = row.get::<_, Option<String>>("aci")?;
+        let phone_number_option = row.get::<_, Option<String>>("e164")?;
+
+        // Matching Signal Desktop user IDs where possible
+        let id = match (aci_option.as_deref().map(Uuid::parse_str), phone_number_option.as_deref()) {
+            (Some(Ok(uuid)), _) => uuid_to_i64_pos(uuid)?,
+            (_, Some(phone)) => hash_to_id(phone),
+            _ => hash_to_id(&format!("recipient#{recipient_id}")),
+        };
+
+        let non_empty = |s: Option<String>| s.filter(|s| !s.is_empty());
+        let (first_name_option, last_name_option) =
+            match non_empty(row.get::<_, Option<String>>("system_joined_name")?) {
+                Some(name) => (Some(name), None),
+                None => (non_empty(row.get::<_, Option<String>>("profile_given_name")?),
+                         non_empty(row.get::<_, Option<String>>("profile_family_name")?)),
+            };
+
+        if let Some(aci) = aci_option {
+            aci_to_id.insert(aci.to_lowercase(), id);
+        }
+        users.insert(recipient_id, User {
+            ds_uuid: ds_uuid.clone(),
+            id,
+            first_name_option,
+            last_name_option,
+            username_option: non_empty(row.get::<_, Option<String>>("username")?),
+            phone_number_option,
+            profile_pictures: vec![],
+        });
+    }
+
+    let myself_id = match aci_option {
+        Some(aci) => *aci_to_id.get(&aci.to_lowercase()).with_context(|| format!("Own account {aci} not found"))?,
+        None => {
+            // Fall back to sender of any outgoing message
+            let recipient_id: i64 = conn.query_row(
+                &format!("SELECT from_recipient_id FROM message WHERE {OUTGOING_CONDITION} LIMIT 1"),
+                [], |row| row.get(0),
+            ).context("Couldn't determine own account")?;
+            users.get(&recipient_id).context("Own account not found")?.id
+        }
+    };
+
+    Ok((users, UserId(myself_id)))
+}
+
+// Message types are bit masks, lower 5 bits being the base type
+
+const BASE_TYPE_MASK: i64 = 0x1F;
+
+const INCOMING_AUDIO_CALL_TYPE: i64 = 1;
+const OUTGOING_AUDIO_CALL_TYPE: i64 = 2;
+const MISSED_AUDIO_CALL_TYPE: i64 = 3;
+const MISSED_VIDEO_CALL_TYPE: i64 = 8;
+const INCOMING_VIDEO_CALL_TYPE: i64 = 10;
+const OUTGOING_VIDEO_CALL_TYPE: i64 = 11;
+const BASE_INBOX_TYPE: i64 = 20;
+const BASE_OUTGOING_TYPES: std::ops::RangeInclusive<i64> = 21..=26;
+
+const OUTGOING_CONDITION: &str = "(type & 31) BETWEEN 21 AND 26";
+
+fn parse_cwms(conn: &Connection,
+              ds_uuid: &PbUuid,
+              users: &Users,
+              myself_id: UserId,
+              ds_root: &Path) -> Result<Vec<ChatWithMessages>> {
+    let group_titles = parse_group_titles(conn)?;
+    let mut attachments = parse_attachments(conn)?;
+
+    let mut cwms = vec![];
+
+    let mut thread_stmt = conn.prepare(r"SELECT _id, recipient_id FROM thread ORDER BY _id")?;
+    // Earlier revisions of edited messages reference the latest one
+    let mut msg_stmt = conn.prepare(r"
+        SELECT * FROM message
+        WHERE thread_id = ? AND latest_revision_id IS NULL
+        ORDER BY date_sent ASC, _id ASC
+    ")?;
+
+    let mut thread_rows = thread_stmt.query([])?;
+    while let Some(row) = thread_rows.next()? {
+        let thread_id = row.get::<_, i64>("_id")?;
+        let recipient_id = row.get::<_, i64>("recipient_id")?;
+
+        let (chat_id, name_option, tpe) = match (users.get(&recipient_id), group_titles.get(&recipient_id)) {
+            (Some(user), _) if user.id == *myself_id => (user.id, None, ChatType::SelfChat),
+            (Some(user), _) => (user.id, user.pretty_name_option(), ChatType::Personal),
+            (None, Some((group_id, title))) => (hash_to_id(group_id), title.clone(), ChatType::PrivateGroup),
+            (None, None) => {
+                log::warn!("Skipping thread {thread_id} with unsupported recipient {recipient_id}");
+                continue;
+            }
+        };
+
+        let mut messages: Vec<Message> = vec![];
+        let mut msg_rows = msg_stmt.query([thread_id])?;
+        while let Some(row) = msg_rows.next()? {
+            let source_id = row.get::<_, i64>("_id")?;
+            let base_type = row.get::<_, i64>("type")? & BASE_TYPE_MASK;
+
+            let from_id = if BASE_OUTGOING_TYPES.contains(&base_type)
+                || base_type == OUTGOING_AUDIO_CALL_TYPE || base_type == OUTGOING_VIDEO_CALL_TYPE {
+                myself_id
+            } else {
+                let from_recipient_id = row.get::<_, i64>("from_recipient_id")?;
+                users.get(&from_recipient_id)
+                    .with_context(|| format!("Unknown sender {from_recipient_id} of message {source_id}"))?
+                    .id()
+            };
+
+            // Note: This is timestamp in millis, not in seconds! This is needed to resolve replies, and is
+            // divided by 1000 further down.
+            let timestamp_ms = row.get::<_, i64>("date_sent")?;
+
+            let call_discard_reason_option = match base_type {
+                INCOMING_AUDIO_CALL_TYPE | OUTGOING_AUDIO_CALL_TYPE |
+                INCOMING_VIDEO_CALL_TYPE | OUTGOING_VIDEO_CALL_TYPE => Some("hangup"),
+                MISSED_AUDIO_CALL_TYPE | MISSED_VIDEO_CALL_TYPE => Some("missed"),
+                _ => None,
+            };
+
+            let (text, typed) = if let Some(discard_reason) = call_discard_reason_option {
+                (vec![], message_service!(ServiceSvo::PhoneCall(MessageServicePhoneCall {
+                    duration_sec_option: None, // Duration is not recorded
+                    discard_reason_option: Some(discard_reason.to_owned()),
+                    members: vec![],
+                })))
+            } else if base_type == BASE_INBOX_TYPE || BASE_OUTGOING_TYPES.contains(&base_type) {
+                let text = match row.get::<_, Option<String>>("body")? {
+                    Some(body) if !body.is_empty() => vec![RichText::make_plain(body)],
+                    _ => vec![],
+                };
+
+                let reply_to_message_id_option = match row.get::<_, Option<i64>>("quote_id")? {
+                    Some(reply_to_timestamp) if reply_to_timestamp > 0 => {
+                        messages.iter().rev()
+                            .take_while(|m| m.timestamp >= reply_to_timestamp)
+                            .find(|m| m.timestamp == reply_to_timestamp)
+                            .and_then(|m| m.source_id_option)
+                    }
+                    _ => None,
+                };
+
+                let ephemeral_duration_sec_option =
+                    row.get::<_, Option<i64>>("expires_in")?.map(|ms| (ms / 1000) as i32).filter(|&d| d > 0);
+
+                let contents = attachments.remove(&source_id).unwrap_or_default().into_iter()
+                    .map(|a| a.into_content(ds_root))
+                    .collect_vec();
+
+                (text, message_regular! {
+                    edit_timestamp_option: None,
+                    is_deleted: row.get::<_, i64>("remote_deleted")? != 0,
+                    forward_from_name_option: None,
+                    forward_from_id_option: None,
+                    ephemeral_duration_sec_option,
+                    reply_to_message_id_option,
+                    contents,
+                })
+            } else {
+                // Group updates, safety number changes, etc.
+                continue;
+            };
+
+            messages.push(Message::new(
+                *NO_INTERNAL_ID, // Will be set later
+                Some(source_id),
+                timestamp_ms, // Will be corrected later
+                from_id,
+                text,
+                typed,
+            ));
+        }
+
+        if messages.is_empty() { continue; }
+
+        messages.iter_mut().enumerate().for_each(|(i, m)| {
+            m.internal_id = i as i64;
+            m.timestamp /= 1000;
+        });
+
+        let member_ids = match tpe {
+            ChatType::SelfChat => vec![*myself_id],
+            ChatType::Personal => vec![*myself_id, chat_id],
+            ChatType::PrivateGroup => {
+                // Group membership isn't tracked, so only those who wrote something are known
+                let others = messages.iter().map(|m| m.from_id).filter(|id| *id != *myself_id).unique().sorted();
+                std::iter::once(*myself_id).chain(others).collect_vec()
+            }
+        };
+
+        cwms.push(ChatWithMessages {
+            chat: Chat {
+                ds_uuid: ds_uuid.clone(),
+                id: chat_id,
+                name_option,
+                source_type: SourceType::Signal as i32,
+                tpe: tpe as i32,
+                img_path_option: None,
+                member_ids,
+                msg_count: messages.len() as i32,
+                main_chat_id: None,
+                archived: None,
+                hidden: None,
+            },
+            messages,
+        });
+    }
+
+    Ok(cwms)
+}
+
+/// Group recipient row ID -> (group ID, title)
+fn parse_group_titles(conn: &Connection) -> Result<HashMap<i64, (String, Option<String>)>> {
+    let mut stmt = conn.prepare(r"SELECT recipient_id, group_id, title FROM groups")?;
+    let mut rows = stmt.query([])?;
+    let mut res = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let title_option = row.get::<_, Option<String>>("title")?.filter(|t| !t.is_empty());
+        res.insert(row.get::<_, i64>("recipient_id")?, (row.get::<_, String>("group_id")?, title_option));
+    }
+    Ok(res)
+}
+
+struct AttachmentRow {
+    row_id: i64,
+    mime_type: String,
+    file_name_option: Option<String>,
+    width: i32,
+    height: i32,
+    is_voice_note: bool,
+    is_sticker: bool,
+}
+
+impl AttachmentRow {
+    fn into_content(self, ds_root: &Path) -> Content {
+        let rel_path = format!("{ATTACHMENTS_DIR_NAME}/{}", self.row_id);
+        // Attachments that weren't downloaded are not in the backup
+        let path_option = Some(rel_path).filter(|p| ds_root.join(p).exists());
+        let mime_type = self.mime_type;
+        if self.is_sticker {
+            content!(Sticker {
+                path_option,
+                file_name_option: None,
+                width: self.width,
+                height: self.height,
+                mime_type_option: Some(mime_type),
+                thumbnail_path_option: None,
+                emoji_option: None,
+                pack_id_option: None,
+                pack_name_option: None,
+            })
+        } else if mime_type.starts_with("image/") {
+            content!(Photo {
+                path_option,
+                width: self.width,
+                height: self.height,
+                mime_type_option: Some(mime_type),
+                is_one_time: false,
+                lat_str_option: None,
+                lon_str_option: None,
+                ocr_text_option: None,
+            })
+        } else if mime_type.starts_with("video/") {
+            content!(Video {
+                path_option,
+                file_name_option: self.file_name_option,
+                title_option: None,
+                performer_option: None,
+                width: self.width,
+                height: self.height,
+                mime_type,
+                duration_sec_option: None,
+                thumbnail_path_option: None,
+                is_one_time: false,
+            })
+        } else if mime_type.starts_with("audio/") && self.is_voice_note {
+            content!(VoiceMsg {
+                path_option,
+                file_name_option: self.file_name_option,
+                mime_type,
+                duration_sec_option: None,
+                waveform_option: None,
+            })
+        } else if mime_type.starts_with("audio/") {
+            content!(Audio {
+                path_option,
+                file_name_option: self.file_name_option,
+                title_option: None,
+                performer_option: None,
+                mime_type,
+                duration_sec_option: None,
+                thumbnail_path_option: None,
+                waveform_option: None,
+            })
+        } else {
+            content!(File {
+                path_option,
+                file_name_option: self.file_name_option,
+                mime_type_option: Some(mime_type),
+                thumbnail_path_option: None,
+            })
+        }
+    }
+}
+
+/// Message row ID -> attachments, in order.
+/// Table was called "part" and had shorter column names in older versions.
+fn parse_attachments(conn: &Connection) -> Result<HashMap<i64, Vec<AttachmentRow>>> {
+    let query = if table_exists(conn, "attachment")? {
+        r"SELECT _id, message_id, content_type, file_name, width, height, voice_note, sticker_pack_id
+          FROM attachment ORDER BY _id"
+    } else {
+        r"SELECT _id, mid AS message_id, ct AS content_type, file_name, width, height, voice_note, sticker_pack_id
+          FROM part ORDER BY _id"
+    };
+    let mut stmt = conn.prepare(query)?;
+    let mut rows = stmt.query([])?;
+    let mut res: HashMap<i64, Vec<AttachmentRow>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        res.entry(row.get("message_id")?).or_default().push(AttachmentRow {
+            row_id: row.get("_id")?,
+            mime_type: row.get::<_, Option<String>>("content_type")?.unwrap_or_else(|| "application/octet-stream".to_owned()),
+            file_name_option: row.get("file_name")?,
+            width: row.get::<_, Option<i32>>("width")?.unwrap_or(0),
+            height: row.get::<_, Option<i32>>("height")?.unwrap_or(0),
+            is_voice_note: row.get::<_, Option<i64>>("voice_note")?.unwrap_or(0) != 0,
+            is_sticker: row.get::<_, Option<String>>("sticker_pack_id")?.is_some(),
+        });
+    }
+    Ok(res)
+}
+
+fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                      [name], |row| row.get::<_, i64>(0))? > 0)
+}
+
+/// Backup frames and their decryption.
+mod frames {
+    use std::io::{Read, Write};
+
+    use aes::Aes256;
+    use aes::cipher::{BlockEncrypt, KeyInit};
+    use aes::cipher::generic_array::GenericArray;
+    use hmac::{Hmac, Mac};
+    use prost::Message;
+    use rusqlite::types::Value as SqlValue;
+    use sha2::{Digest, Sha256, Sha512};
+
+    use crate::prelude::*;
+
+    type HmacSha256 = Hmac<Sha256>;
+
+    const NUM_KEY_ITERATIONS: usize = 250_000;
+    const HKDF_INFO: &[u8] = b"Backup Export";
+    const MAC_SIZE: usize = 10;
+    const BLOCK_SIZE: usize = 16;
+
+    /// Frames are small, anything bigger is a sign of a wrong passphrase
+    const MAX_FRAME_SIZE: u32 = 64 * 1024 * 1024;
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct BackupFrame {
+        #[prost(message, optional, tag = "1")]
+        pub header: Option<Header>,
+        #[prost(message, optional, tag = "2")]
+        pub statement: Option<SqlStatement>,
+        #[prost(message, optional, tag = "4")]
+        pub attachment: Option<Attachment>,
+        #[prost(bool, optional, tag = "6")]
+        pub end: Option<bool>,
+        #[prost(message, optional, tag = "7")]
+        pub avatar: Option<Avatar>,
+        #[prost(message, optional, tag = "8")]
+        pub sticker: Option<Sticker>,
+        #[prost(message, optional, tag = "9")]
+        pub key_value: Option<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Header {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub iv: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub salt: Option<Vec<u8>>,
+        #[prost(uint32, optional, tag = "3")]
+        pub version: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct SqlStatement {
+        #[prost(string, optional, tag = "1")]
+        pub statement: Option<String>,
+        #[prost(message, repeated, tag = "2")]
+        pub parameters: Vec<SqlParameter>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct SqlParameter {
+        #[prost(string, optional, tag = "1")]
+        pub string_parameter: Option<String>,
+        #[prost(uint64, optional, tag = "2")]
+        pub integer_parameter: Option<u64>,
+        #[prost(double, optional, tag = "3")]
+        pub double_parameter: Option<f64>,
+        #[prost(bytes = "vec", optional, tag = "4")]
+        pub blob_parameter: Option<Vec<u8>>,
+        #[prost(bool, optional, tag = "5")]
+        pub null_parameter: Option<bool>,
+    }
+
+    impl SqlParameter {
+        pub fn into_sql_value(self) -> SqlValue {
+            match self {
+                SqlParameter { string_parameter: Some(v), .. } => SqlValue::Text(v),
+                // Signed integers are stored as unsigned
+                SqlParameter { integer_parameter: Some(v), .. } => SqlValue::Integer(v as i64),
+                SqlParameter { double_parameter: Some(v), .. } => SqlValue::Real(v),
+                SqlParameter { blob_parameter: Some(v), .. } => SqlValue::Blob(v),
+                _ => SqlValue::Null,
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Attachment {
+        #[prost(uint64, optional, tag = "1")]
+        pub row_id: Option<u64>,
+        #[prost(uint64, optional, tag = "2")]
+        pub attachment_id: Option<u64>,
+        #[prost(uint32, optional, tag = "3")]
+        pub length: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Avatar {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+        #[prost(uint32, optional, tag = "2")]
+        pub length: Option<u32>,
+        #[prost(string, optional, tag = "3")]
+        pub recipient_id: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Sticker {
+        #[prost(uint64, optional, tag = "1")]
+        pub row_id: Option<u64>,
+        #[prost(uint32, optional, tag = "2")]
+        pub length: Option<u32>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct KeyValue {
+        #[prost(string, optional, tag = "1")]
+        pub key: Option<String>,
+        #[prost(string, optional, tag = "7")]
+        pub string_value: Option<String>,
+    }
+
+    /// Derive cipher and MAC keys from a passphrase.
+    pub fn derive_keys(passphrase: &str, salt: &[u8]) -> ([u8; 32], [u8; 32]) {
+        let input = passphrase.as_bytes();
+        let mut digest = Sha512::new();
+        digest.update(salt);
+        let mut hash = input.to_vec();
+        for _ in 0..NUM_KEY_ITERATIONS {
+            digest.update(&hash);
+            digest.update(input);
+            hash = digest.finalize_reset().to_vec();
+        }
+
+        // HKDF-SHA256 with zero salt
+        let hmac = |key: &[u8], parts: &[&[u8]]| {
+            let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+            parts.iter().for_each(|p| mac.update(p));
+            mac.finalize().into_bytes()
+        };
+        let prk = hmac(&[0u8; 32], &[&hash[..32]]);
+        let cipher_key = hmac(&prk, &[HKDF_INFO, &[1u8]]);
+        let mac_key = hmac(&prk, &[cipher_key.as_slice(), HKDF_INFO, &[2u8]]);
+        (cipher_key.as_slice().try_into().unwrap(), mac_key.as_slice().try_into().unwrap())
+    }
+
+    /// AES-256 in CTR mode, counter being the whole big-endian block.
+    pub struct CtrCipher<'a> {
+        aes: &'a Aes256,
+        counter: u128,
+        keystream: [u8; BLOCK_SIZE],
+        pos: usize,
+    }
+
+    impl<'a> CtrCipher<'a> {
+        pub fn new(aes: &'a Aes256, iv: [u8; BLOCK_SIZE]) -> Self {
+            CtrCipher { aes, counter: u128::from_be_bytes(iv), keystream: [0; BLOCK_SIZE], pos: BLOCK_SIZE }
+        }
+
+        pub fn apply(&mut self, data: &mut [u8]) {
+            for b in data {
+                if self.pos == BLOCK_SIZE {
+                    let mut block = GenericArray::from(self.counter.to_be_bytes());
+                    self.aes.encrypt_block(&mut block);
+                    self.keystream.copy_from_slice(&block);
+                    self.counter = self.counter.wrapping_add(1);
+                    self.pos = 0;
+                }
+                *b ^= self.keystream[self.pos];
+                self.pos += 1;
+            }
+        }
+    }
+
+    pub struct BackupReader<R: Read> {
+        input: R,
+        /// Starting with version 1, frame lengths are encrypted too
+        version: u32,
+        aes: Aes256,
+        mac_key: [u8; 32],
+        iv: [u8; BLOCK_SIZE],
+        counter: u32,
+    }
+
+    impl<R: Read> BackupReader<R> {
+        /// Reads a plaintext header frame
+        pub fn new(mut input: R, passphrase: &str) -> Result<Self> {
+            let mut len = [0u8; 4];
+            input.read_exact(&mut len).context("Backup file is empty")?;
+            let len = u32::from_be_bytes(len);
+            ensure!(len <= MAX_FRAME_SIZE, "Not a Signal backup");
+            let mut header = vec![0u8; len as usize];
+            input.read_exact(&mut header)?;
+            let header = BackupFrame::decode(header.as_slice())
+                .ok()
+                .and_then(|f| f.header)
+                .context("Not a Signal backup")?;
+
+            let iv: [u8; BLOCK_SIZE] = header.iv().try_into().map_err(|_| anyhow!("Invalid backup IV"))?;
+            let (cipher_key, mac_key) = derive_keys(passphrase, header.salt());
+            Ok(BackupReader {
+                input,
+                version: header.version(),
+                aes: Aes256::new(GenericArray::from_slice(&cipher_key)),
+                mac_key,
+                counter: u32::from_be_bytes(iv[..4].try_into().unwrap()),
+                iv,
+            })
+        }
+
+        pub fn next_frame(&mut self) -> Result<BackupFrame> {
+            let iv = self.next_iv();
+            let mut mac = self.mac();
+            let mut cipher = CtrCipher::new(&self.aes, iv);
+
+            let mut len = [0u8; 4];
+            self.input.read_exact(&mut len).context("Backup ended unexpectedly")?;
+            if self.version >= 1 {
+                mac.update(&len);
+                cipher.apply(&mut len);
+            }
+            let len = u32::from_be_bytes(len);
+            ensure!((MAC_SIZE as u32..=MAX_FRAME_SIZE).contains(&len),
+                    "Invalid frame length, the passphrase is probably incorrect");
+
+            let mut frame = vec![0u8; len as usize];
+            self.input.read_exact(&mut frame).context("Backup ended unexpectedly")?;
+            let (data, their_mac) = frame.split_at_mut(len as usize - MAC_SIZE);
+            mac.update(data);
+            ensure!(mac.finalize().into_bytes()[..MAC_SIZE] == *their_mac,
+                    "Frame MAC mismatch, the passphrase is probably incorrect");
+
+            cipher.apply(data);
+            Ok(BackupFrame::decode(&*data)?)
+        }
+
+        /// Decrypt file data following a frame into the given output.
+        pub fn read_file(&mut self, len: u32, output: &mut impl Write) -> EmptyRes {
+            let iv = self.next_iv();
+            let mut mac = self.mac();
+            mac.update(&iv);
+            let mut cipher = CtrCipher::new(&self.aes, iv);
+
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut remaining = len as usize;
+            while remaining > 0 {
+                let chunk = &mut buf[..remaining.min(64 * 1024)];
+                self.input.read_exact(chunk).context("Backup ended unexpectedly")?;
+                mac.update(chunk);
+                cipher.apply(chunk);
+                output.write_all(chunk)?;
+                remaining -= chunk.len();
+            }
+
+            let mut their_mac = [0u8; MAC_SIZE];
+            self.input.read_exact(&mut their_mac).context("Backup ended unexpectedly")?;
+            ensure!(mac.finalize().into_bytes()[..MAC_SIZE] == their_mac, "File MAC mismatch");
+            Ok(())
+        }
+
+        fn mac(&self) -> HmacSha256 {
+            <HmacSha256 as Mac>::new_from_slice(&self.mac_key).expect("HMAC can take key of any size")
+        }
+
+        /// IV with its first 4 bytes replaced with an incrementing counter
+        fn next_iv(&mut self) -> [u8; BLOCK_SIZE] {
+            let mut iv = self.iv;
+            iv[..4].copy_from_slice(&self.counter.to_be_bytes());
+            self.counter = self.counter.wrapping_add(1);
+            iv
+        }
+    }
+}
@@ -0,0 +1,311 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use aes::Aes256;
+use aes::cipher::KeyInit;
+use aes::cipher::generic_array::GenericArray;
+use hmac::{Hmac, Mac};
+use prost::Message as ProstMessage;
+use pretty_assertions::{assert_eq, assert_ne};
+use sha2::Sha256;
+
+use crate::dao::ChatHistoryReader;
+use crate::protobuf::history::message_service::SealedValueOptional::*;
+
+use super::*;
+use super::frames::*;
+
+const PASSPHRASE: &str = "123451234512345123451234512345";
+
+const MY_ACI: &str = "11111111-1111-4111-8111-111111111111";
+const ALICE_ACI: &str = "22222222-2222-4222-8222-222222222222";
+const UNRELATED_ACI: &str = "44444444-4444-4444-8444-444444444444";
+
+const PHOTO_BYTES: &[u8] = b"Not really a JPEG";
+
+//
+// Tests
+//
+
+#[test]
+fn loading_backup() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("signal-2024-05-01-12-00-00.backup");
+    write_backup(&path, PASSPHRASE, &sample_frames())?;
+
+    let loader = SignalAndroidDataLoader;
+    loader.looks_about_right(&path)?;
+    let input = client::PredefinedInput { myself_id: None, text: Some("12345 12345 12345 12345 12345 12345".to_owned()) };
+    let dao = loader.load(&path, &input)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let ds_root = dao.dataset_root(ds_uuid)?;
+    let myself = dao.myself_single_ds();
+    let alice_id = uuid_to_i64_pos(Uuid::parse_str(ALICE_ACI)?)?;
+    let bob_id = hash_to_id("+10000000003");
+
+    assert_eq!(myself, User {
+        ds_uuid: ds_uuid.clone(),
+        id: uuid_to_i64_pos(Uuid::parse_str(MY_ACI)?)?,
+        first_name_option: Some("Me".to_owned()),
+        last_name_option: Some("Myself".to_owned()),
+        username_option: None,
+        phone_number_option: Some("+10000000001".to_owned()),
+        profile_pictures: vec![],
+    });
+    let users = dao.users_single_ds();
+    assert_eq!(users.len(), 3);
+    assert_eq!(users[0].id, myself.id);
+    let alice = users.iter().find(|u| u.id == alice_id).unwrap();
+    assert_eq!(alice.first_name_option.as_deref(), Some("Alice Contact"));
+    assert_eq!(alice.last_name_option, None);
+    assert_eq!(alice.username_option.as_deref(), Some("alice.01"));
+    let bob = users.iter().find(|u| u.id == bob_id).unwrap();
+    assert_eq!(bob.first_name_option.as_deref(), Some("Bob"));
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 3);
+
+    {
+        let cwm = &cwms[0];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: alice_id,
+            name_option: Some("Alice Contact".to_owned()),
+            source_type: SourceType::Signal as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, alice_id],
+            msg_count: 5,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = &cwm.messages;
+        assert_eq!(msgs.iter().map(|m| m.source_id_option.unwrap()).collect_vec(), vec![1, 2, 3, 4, 6]);
+        assert_eq!(msgs[0].timestamp, 1700000000);
+        assert_eq!(msgs[0].from_id, alice_id);
+        assert_eq!(msgs[0].text, vec![RichText::make_plain("Hi there".to_owned())]);
+
+        assert_eq!(msgs[1].from_id, myself.id);
+        let message_regular_pat! { reply_to_message_id_option, ephemeral_duration_sec_option, .. } = msgs[1].typed() else {
+            unreachable!()
+        };
+        assert_eq!(*reply_to_message_id_option, Some(1));
+        assert_eq!(*ephemeral_duration_sec_option, Some(3600));
+
+        let message_regular_pat! { contents, .. } = msgs[2].typed() else { unreachable!() };
+        assert_eq!(contents, &vec![content!(Photo {
+            path_option: Some("attachments/1".to_owned()),
+            width: 640,
+            height: 480,
+            mime_type_option: Some("image/jpeg".to_owned()),
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        })]);
+        assert_eq!(fs::read(ds_root.to_absolute("attachments/1"))?, PHOTO_BYTES);
+
+        assert_eq!(msgs[3].typed(), &message_service!(PhoneCall(MessageServicePhoneCall {
+            duration_sec_option: None,
+            discard_reason_option: Some("hangup".to_owned()),
+            members: vec![],
+        })));
+
+        // Only the latest revision of an edited message is kept, attachment that wasn't downloaded has no file
+        assert_eq!(msgs[4].text, vec![RichText::make_plain("New".to_owned())]);
+        let message_regular_pat! { contents, .. } = msgs[4].typed() else { unreachable!() };
+        assert_eq!(contents, &vec![content!(File {
+            path_option: None,
+            file_name_option: Some("doc.pdf".to_owned()),
+            mime_type_option: Some("application/pdf".to_owned()),
+            thumbnail_path_option: None,
+        })]);
+    }
+
+    {
+        let cwm = &cwms[1];
+        assert_eq!(cwm.chat.name_option.as_deref(), Some("Friends"));
+        assert_eq!(cwm.chat.tpe, ChatType::PrivateGroup as i32);
+        assert_eq!(cwm.chat.id, hash_to_id("__signal_group__v2__!friends"));
+        assert_eq!(cwm.chat.member_ids, vec![myself.id, bob_id]);
+        assert_eq!(cwm.messages.iter().map(|m| m.from_id).collect_vec(), vec![bob_id, myself.id]);
+    }
+
+    {
+        let cwm = &cwms[2];
+        assert_eq!(cwm.chat.tpe, ChatType::SelfChat as i32);
+        assert_eq!(cwm.chat.member_ids, vec![myself.id]);
+        assert_eq!(cwm.messages.len(), 1);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn wrong_passphrase() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("signal-2024-05-01-12-00-00.backup");
+    write_backup(&path, PASSPHRASE, &sample_frames())?;
+
+    let input = client::PredefinedInput { myself_id: None, text: Some("0".repeat(30)) };
+    let res = SignalAndroidDataLoader.load(&path, &input);
+    assert!(res.is_err());
+    let err = res.err().unwrap();
+    assert!(format!("{err:?}").contains("passphrase is probably incorrect"), "{err:?}");
+
+    let input = client::PredefinedInput { myself_id: None, text: Some("12345".to_owned()) };
+    let res = SignalAndroidDataLoader.load(&path, &input);
+    assert!(res.is_err());
+    let err = res.err().unwrap();
+    assert!(format!("{err:?}").contains("30 digits"), "{err:?}");
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+fn sample_frames() -> Vec<(BackupFrame, Option<Vec<u8>>)> {
+    let statement = |sql: &str, params: Vec<SqlParameter>| (BackupFrame {
+        statement: Some(SqlStatement { statement: Some(sql.to_owned()), parameters: params }),
+        ..Default::default()
+    }, None);
+    let str_p = |s: &str| SqlParameter { string_parameter: Some(s.to_owned()), ..Default::default() };
+    let int_p = |i: i64| SqlParameter { integer_parameter: Some(i as u64), ..Default::default() };
+    let null_p = || SqlParameter { null_parameter: Some(true), ..Default::default() };
+    let opt_str_p = |s: Option<&str>| s.map(str_p).unwrap_or_else(null_p);
+    let opt_int_p = |i: Option<i64>| i.map(int_p).unwrap_or_else(null_p);
+
+    let mut frames = vec![
+        statement("CREATE TABLE sqlite_sequence(name,seq)", vec![]),
+        statement("CREATE VIRTUAL TABLE message_fts USING fts5(body)", vec![]),
+        statement("CREATE TABLE recipient (_id INTEGER PRIMARY KEY, type INTEGER, e164 TEXT, aci TEXT, \
+                   group_id TEXT, system_joined_name TEXT, profile_given_name TEXT, profile_family_name TEXT, \
+                   username TEXT)", vec![]),
+        statement("CREATE TABLE groups (_id INTEGER PRIMARY KEY, group_id TEXT, recipient_id INTEGER, title TEXT)", vec![]),
+        statement("CREATE TABLE thread (_id INTEGER PRIMARY KEY, recipient_id INTEGER)", vec![]),
+        statement("CREATE TABLE message (_id INTEGER PRIMARY KEY, date_sent INTEGER, thread_id INTEGER, \
+                   from_recipient_id INTEGER, type INTEGER, body TEXT, quote_id INTEGER, expires_in INTEGER, \
+                   remote_deleted INTEGER, latest_revision_id INTEGER)", vec![]),
+        statement("CREATE TABLE attachment (_id INTEGER PRIMARY KEY, message_id INTEGER, content_type TEXT, \
+                   file_name TEXT, width INTEGER, height INTEGER, voice_note INTEGER, sticker_pack_id TEXT)", vec![]),
+        statement("INSERT INTO sqlite_sequence VALUES ('message', 10)", vec![]),
+    ];
+
+    // ID, type, phone, ACI, group ID, system name, given name, family name, username
+    let recipients = [
+        (1, 0, Some("+10000000001"), Some(MY_ACI), None, None, Some("Me"), Some("Myself"), None),
+        (2, 0, Some("+10000000002"), Some(ALICE_ACI), None, Some("Alice Contact"), Some("Alice"), None, Some("alice.01")),
+        (3, 0, Some("+10000000003"), None, None, None, Some("Bob"), None, None),
+        (4, 0, None, Some(UNRELATED_ACI), None, None, Some("Unrelated"), None, None),
+        (5, 3, None, None, Some("__signal_group__v2__!friends"), None, None, None, None),
+    ];
+    for (id, tpe, e164, aci, group_id, system_name, given_name, family_name, username) in recipients {
+        frames.push(statement("INSERT INTO recipient VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)", vec![
+            int_p(id), int_p(tpe), opt_str_p(e164), opt_str_p(aci), opt_str_p(group_id),
+            opt_str_p(system_name), opt_str_p(given_name), opt_str_p(family_name), opt_str_p(username),
+        ]));
+    }
+    frames.push(statement("INSERT INTO groups VALUES (?, ?, ?, ?)", vec![
+        int_p(1), str_p("__signal_group__v2__!friends"), int_p(5), str_p("Friends"),
+    ]));
+    for (id, recipient_id) in [(1, 2), (2, 5), (3, 1)] {
+        frames.push(statement("INSERT INTO thread VALUES (?, ?)", vec![int_p(id), int_p(recipient_id)]));
+    }
+
+    const SENT: i64 = 0xA00000 | 23;
+    const RECEIVED: i64 = 0xA00000 | 20;
+    // ID, date sent, thread, from, type, body, quote ID, expires in, latest revision ID
+    let messages = [
+        (1, 1700000000000, 1, 2, RECEIVED, Some("Hi there"), None, None, None),
+        (2, 1700000001000, 1, 1, SENT, Some("Hello!"), Some(1700000000000), Some(3600_000), None),
+        (3, 1700000002000, 1, 2, RECEIVED, None, None, None, None),
+        (4, 1700000003000, 1, 2, 1, None, None, None, None), // Incoming audio call
+        (5, 1700000004000, 1, 1, SENT, Some("Old"), None, None, Some(6)),
+        (6, 1700000005000, 1, 1, SENT, Some("New"), None, None, None),
+        (7, 1700000006000, 1, 2, 4, None, None, None, None), // Joined Signal
+        (8, 1700000007000, 2, 3, RECEIVED, Some("Hey all"), None, None, None),
+        (9, 1700000008000, 2, 1, SENT, Some("Hi Bob"), None, None, None),
+        (10, 1700000009000, 3, 1, SENT, Some("Note"), None, None, None),
+    ];
+    for (id, date_sent, thread_id, from_id, tpe, body, quote_id, expires_in, latest_revision_id) in messages {
+        frames.push(statement("INSERT INTO message VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)", vec![
+            int_p(id), int_p(date_sent), int_p(thread_id), int_p(from_id), int_p(tpe), opt_str_p(body),
+            opt_int_p(quote_id), int_p(expires_in.unwrap_or(0)), int_p(0), opt_int_p(latest_revision_id),
+        ]));
+    }
+    frames.push(statement("INSERT INTO attachment VALUES (?, ?, ?, ?, ?, ?, ?, ?)", vec![
+        int_p(1), int_p(3), str_p("image/jpeg"), null_p(), int_p(640), int_p(480), int_p(0), null_p(),
+    ]));
+    frames.push(statement("INSERT INTO attachment VALUES (?, ?, ?, ?, ?, ?, ?, ?)", vec![
+        int_p(2), int_p(6), str_p("application/pdf"), str_p("doc.pdf"), int_p(0), int_p(0), int_p(0), null_p(),
+    ]));
+
+    frames.push((BackupFrame {
+        attachment: Some(Attachment { row_id: Some(1), attachment_id: Some(1), length: Some(PHOTO_BYTES.len() as u32) }),
+        ..Default::default()
+    }, Some(PHOTO_BYTES.to_vec())));
+    frames.push((BackupFrame {
+        key_value: Some(KeyValue { key: Some(ACI_KEY.to_owned()), string_value: Some(MY_ACI.to_owned()) }),
+        ..Default::default()
+    }, None));
+    frames.push((BackupFrame { end: Some(true), ..Default::default() }, None));
+    frames
+}
+
+/// Inverse of [BackupReader], producing version 1 backup
+fn write_backup(path: &Path, passphrase: &str, frames: &[(BackupFrame, Option<Vec<u8>>)]) -> EmptyRes {
+    let iv = [7u8; 16];
+    let salt = [42u8; 32];
+    let (cipher_key, mac_key) = derive_keys(passphrase, &salt);
+    let aes = Aes256::new(GenericArray::from_slice(&cipher_key));
+    let new_mac = || <Hmac<Sha256> as Mac>::new_from_slice(&mac_key).unwrap();
+
+    let mut out = vec![];
+    let header = BackupFrame {
+        header: Some(Header { iv: Some(iv.to_vec()), salt: Some(salt.to_vec()), version: Some(1) }),
+        ..Default::default()
+    }.encode_to_vec();
+    out.extend_from_slice(&(header.len() as u32).to_be_bytes());
+    out.extend_from_slice(&header);
+
+    let mut counter = u32::from_be_bytes(iv[..4].try_into()?);
+    let mut next_iv = || {
+        let mut res = iv;
+        res[..4].copy_from_slice(&counter.to_be_bytes());
+        counter += 1;
+        res
+    };
+
+    for (frame, data_option) in frames {
+        let mut cipher = CtrCipher::new(&aes, next_iv());
+        let mut mac = new_mac();
+        let mut frame = frame.encode_to_vec();
+        let mut len = ((frame.len() + 10) as u32).to_be_bytes();
+        cipher.apply(&mut len);
+        mac.update(&len);
+        cipher.apply(&mut frame);
+        mac.update(&frame);
+        out.extend_from_slice(&len);
+        out.extend_from_slice(&frame);
+        out.extend_from_slice(&mac.finalize().into_bytes()[..10]);
+
+        if let Some(data) = data_option {
+            let iv = next_iv();
+            let mut cipher = CtrCipher::new(&aes, iv);
+            let mut mac = new_mac();
+            mac.update(&iv);
+            let mut data = data.clone();
+            cipher.apply(&mut data);
+            mac.update(&data);
+            out.extend_from_slice(&data);
+            out.extend_from_slice(&mac.finalize().into_bytes()[..10]);
+        }
+    }
+    fs::write(path, out)?;
+    Ok(())
+}
@@ -6,7 +6,7 @@ use cbc::cipher::BlockEncryptMut;
 use cbc::Encryptor;
 use pretty_assertions::{assert_eq, assert_ne};
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::protobuf::history::message_service::SealedValueOptional::*;
 use crate::protobuf::history::User;
 
@@ -59,6 +59,8 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
             member_ids: vec![myself.id, member.id],
             msg_count: 5,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -71,10 +73,14 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
             from_id: myself.id,
             text: vec![RichText::make_plain("Photo caption".to_owned())],
             searchable_string: "Photo caption".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Photo {
@@ -83,6 +89,9 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
                         height: 100,
                         mime_type_option: Some("image/jpeg".to_owned()),
                         is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
                     })
                 ],
             }),
@@ -95,6 +104,8 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
             from_id: myself.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(PhoneCall(MessageServicePhoneCall {
                 duration_sec_option: None,
                 discard_reason_option: Some("hangup".to_owned()),
@@ -109,10 +120,14 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(VoiceMsg {
@@ -120,6 +135,7 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
                         file_name_option: None,
                         mime_type: "audio/aac".to_owned(),
                         duration_sec_option: None,
+                        waveform_option: None,
                     })
                 ],
             }),
@@ -132,10 +148,14 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Video {
@@ -173,6 +193,8 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
             from_id: myself.id,
             text: vec![RichText::make_plain("Edited message, final version".to_owned())],
             searchable_string: "Edited message, final version".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: Some(1696178321),
                 ..Default::default()
@@ -183,6 +205,32 @@ fn loading_v7_27_macos_plaintext() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn parsing_sticker() -> EmptyRes {
+    let mut json = r#"{
+        "packId": "9acc9e8aba563d26a4994e69263e3b25",
+        "stickerId": 3,
+        "emoji": "🤔",
+        "data": { "contentType": "image/webp", "path": "ab/abcdef", "width": 512, "height": 512 }
+    }"#.as_bytes().to_vec();
+    let json = simd_json::to_borrowed_value(&mut json)?;
+    let sticker = parse_sticker(as_object!(json, "json"))?;
+    assert_eq!(sticker.pack_id, "9acc9e8aba563d26a4994e69263e3b25");
+    assert_eq!(sticker.emoji.as_deref(), Some("🤔"));
+    let file_info = sticker.file_info.unwrap();
+    assert_eq!(file_info.mime_type, "image/webp");
+    assert_eq!(file_info.path.as_deref(), Some("ab/abcdef"));
+    assert_eq!((file_info.width, file_info.height), (Some(512), Some(512)));
+
+    // Not downloaded
+    let mut json = br#"{ "packId": "9acc9e8aba563d26a4994e69263e3b25", "stickerId": 3 }"#.to_vec();
+    let json = simd_json::to_borrowed_value(&mut json)?;
+    let sticker = parse_sticker(as_object!(json, "json"))?;
+    assert_eq!(sticker.emoji, None);
+    assert!(sticker.file_info.is_none());
+    Ok(())
+}
+
 //
 // Helpers
 //
@@ -0,0 +1,223 @@
+use std::env;
+
+use chrono::{TimeZone, Utc};
+use itertools::Itertools;
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::prelude::*;
+
+use message_service::SealedValueOptional as ServiceSvo;
+
+#[cfg(test)]
+#[path = "synthetic_tests.rs"]
+mod tests;
+
+/// Upper limit on a total number of generated messages, to keep a generated dataset in memory
+pub const MAX_MESSAGES: usize = 10_000_000;
+
+const MYSELF_ID: i64 = 1;
+
+const WORDS: &[&str] = &[
+    "hello", "there", "how", "are", "you", "doing", "today", "meeting", "tomorrow", "coffee", "weekend", "project",
+    "deadline", "photo", "trip", "home", "call", "later", "sure", "thanks", "sorry", "maybe", "great", "idea",
+    "привет", "как", "дела", "завтра", "встреча", "спасибо", "😀", "👍",
+];
+
+const FIRST_NAMES: &[&str] = &["Alice", "Bob", "Carol", "Dave", "Eve", "Frank", "Grace", "Heidi", "Ivan", "Judy"];
+const LAST_NAMES: &[&str] = &["Smith", "Jones", "Brown", "Taylor", "Wilson", "Davies", "Evans", "Ivanov"];
+
+/// Generates a dataset of made-up users and chats, for exercising clients without real personal data at hand.
+/// Generation is deterministic for a given seed.
+///
+/// Every chat gets its own user to talk to, every third chat is a group chat also including a couple of other users.
+/// Messages are mostly text (some formatted, some replies), mixed with media, locations, polls and service messages.
+/// Media has no files behind it.
+pub fn generate_synthetic_dao(name: String,
+                              chats_count: usize,
+                              messages_per_chat: usize,
+                              seed: u64) -> Result<Box<InMemoryDao>> {
+    ensure!(chats_count > 0, "At least one chat should be requested");
+    ensure!(chats_count.saturating_mul(messages_per_chat) <= MAX_MESSAGES,
+            "Too many messages requested, at most {MAX_MESSAGES} are supported");
+
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let ds = Dataset {
+        uuid: PbUuid::random(),
+        alias: format!("Synthetic {chats_count}x{messages_per_chat}"),
+    };
+
+    let users = (MYSELF_ID..=(chats_count as i64 + 1)).map(|id| User {
+        ds_uuid: ds.uuid.clone(),
+        id,
+        first_name_option: Some(FIRST_NAMES[id as usize % FIRST_NAMES.len()].to_owned()),
+        last_name_option: Some(format!("{} {id}", LAST_NAMES[id as usize % LAST_NAMES.len()])),
+        username_option: Some(format!("user{id}")),
+        phone_number_option: Some(format!("+1 555 {id:07}")),
+        profile_pictures: vec![],
+    }).collect_vec();
+
+    let cwms = (1..=chats_count as i64).map(|chat_id| {
+        let interlocutor = &users[chat_id as usize];
+        let is_group = chat_id % 3 == 0 && users.len() > 3;
+        let mut member_ids = vec![MYSELF_ID, interlocutor.id];
+        if is_group {
+            while member_ids.len() < 4 {
+                let id = rng.random_range(2..=users.len() as i64);
+                if !member_ids.contains(&id) { member_ids.push(id); }
+            }
+        }
+        let chat = Chat {
+            ds_uuid: ds.uuid.clone(),
+            id: chat_id,
+            name_option: Some(if is_group {
+                format!("Group {chat_id}")
+            } else {
+                interlocutor.pretty_name_option().unwrap_or_default()
+            }),
+            source_type: SourceType::TextImport as i32,
+            tpe: (if is_group { ChatType::PrivateGroup } else { ChatType::Personal }) as i32,
+            img_path_option: None,
+            member_ids: member_ids.clone(),
+            msg_count: messages_per_chat as i32,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        };
+        let messages = generate_messages(&mut rng, &chat, &users, messages_per_chat);
+        ChatWithMessages { chat, messages }
+    }).collect_vec();
+
+    Ok(Box::new(InMemoryDao::new_single(
+        name,
+        ds,
+        env::temp_dir(),
+        UserId(MYSELF_ID),
+        users,
+        cwms,
+    )))
+}
+
+fn generate_messages(rng: &mut SmallRng, chat: &Chat, users: &[User], count: usize) -> Vec<Message> {
+    let mut timestamp = Utc.with_ymd_and_hms(2020, 1, 1, 9, 0, 0).unwrap().timestamp();
+    (0..count).map(|idx| {
+        // From a minute to a day between messages
+        timestamp += rng.random_range(60..=24 * 60 * 60);
+        let from_id = chat.member_ids[rng.random_range(0..chat.member_ids.len())];
+        let (text, typed) = if idx == 0 && chat.tpe() == ChatType::PrivateGroup {
+            let members = chat.member_ids.iter()
+                .map(|id| users[*id as usize - 1].pretty_name())
+                .collect_vec();
+            (vec![], message_service!(ServiceSvo::GroupCreate(MessageServiceGroupCreate {
+                title: name_or_unnamed(&chat.name_option),
+                members,
+            })))
+        } else {
+            generate_message_content(rng, idx, timestamp)
+        };
+        let searchable_string = make_searchable_string(&text, &typed);
+        Message {
+            internal_id: idx as i64 + 1,
+            source_id_option: Some(idx as i64 + 1),
+            timestamp,
+            from_id,
+            text,
+            searchable_string,
+            topic_option: None,
+            text_history: vec![],
+            typed: Some(typed),
+        }
+    }).collect_vec()
+}
+
+fn generate_message_content(rng: &mut SmallRng, idx: usize, timestamp: i64) -> (Vec<RichTextElement>, message::Typed) {
+    let sentence = |rng: &mut SmallRng| -> String {
+        let len = rng.random_range(1..=20);
+        (0..len).map(|_| WORDS[rng.random_range(0..WORDS.len())]).join(" ")
+    };
+    let regular = |text: Vec<RichTextElement>, reply_to: Option<i64>, edited: bool, contents: Vec<Content>| {
+        (text, message_regular! {
+            edit_timestamp_option: if edited { Some(timestamp + 60) } else { None },
+            is_deleted: false,
+            forward_from_name_option: None,
+            forward_from_id_option: None,
+            reply_to_message_id_option: reply_to,
+            ephemeral_duration_sec_option: None,
+            contents,
+        })
+    };
+    let roll = rng.random_range(0..100);
+    let edited = rng.random_range(0..20) == 0;
+    match roll {
+        0..55 => regular(vec![RichText::make_plain(sentence(rng))], None, edited, vec![]),
+        55..65 if idx > 0 => {
+            // Reply to any previous message, source IDs are 1-based
+            let reply_to = rng.random_range(1..=idx as i64);
+            regular(vec![RichText::make_plain(sentence(rng))], Some(reply_to), edited, vec![])
+        }
+        55..72 => regular(vec![
+            RichText::make_plain(sentence(rng)),
+            RichText::make_bold(sentence(rng)),
+            RichText::make_link(Some("link".to_owned()), format!("https://example.com/{idx}"), false),
+        ], None, edited, vec![]),
+        72..80 => regular(vec![], None, false, vec![content!(Photo {
+            path_option: None,
+            width: rng.random_range(320..=1920),
+            height: rng.random_range(320..=1920),
+            mime_type_option: Some("image/jpeg".to_owned()),
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        })]),
+        80..84 => regular(vec![], None, false, vec![content!(Sticker {
+            path_option: None,
+            file_name_option: None,
+            width: 512,
+            height: 512,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+            emoji_option: Some("👍".to_owned()),
+            pack_id_option: None,
+            pack_name_option: None,
+        })]),
+        84..88 => regular(vec![], None, false, vec![content!(VoiceMsg {
+            path_option: None,
+            file_name_option: None,
+            mime_type: "audio/ogg".to_owned(),
+            duration_sec_option: Some(rng.random_range(1..=120)),
+            waveform_option: None,
+        })]),
+        88..91 => regular(vec![RichText::make_plain(sentence(rng))], None, false, vec![content!(File {
+            path_option: None,
+            file_name_option: Some(format!("document-{idx}.pdf")),
+            mime_type_option: Some("application/pdf".to_owned()),
+            thumbnail_path_option: None,
+        })]),
+        91..93 => regular(vec![], None, false, vec![content!(Location {
+            title_option: None,
+            address_option: None,
+            lat_str: format!("{:.6}", rng.random_range(-60.0..60.0)),
+            lon_str: format!("{:.6}", rng.random_range(-180.0..180.0)),
+            duration_sec_option: None,
+            updates: vec![],
+        })]),
+        93..95 => regular(vec![], None, false, vec![content!(Poll {
+            question: sentence(rng),
+            options: (1..=3).map(|i| ContentPollOption {
+                text: format!("Option {i}"),
+                voters_option: None,
+                is_chosen: false,
+                is_correct: false,
+            }).collect_vec(),
+            is_quiz: None,
+            total_voters_option: None,
+        })]),
+        _ => (vec![], message_service!(ServiceSvo::PhoneCall(MessageServicePhoneCall {
+            duration_sec_option: Some(rng.random_range(0..=3600)),
+            discard_reason_option: None,
+            members: vec![],
+        }))),
+    }
+}
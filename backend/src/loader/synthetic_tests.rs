@@ -0,0 +1,66 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::ChatHistoryReader;
+
+use super::*;
+
+#[test]
+fn generating() -> EmptyRes {
+    let dao = generate_synthetic_dao("Synthetic".to_owned(), 7, 300, 42)?;
+    let ds_uuid = dao.ds_uuid();
+
+    assert_eq!(dao.users(&ds_uuid)?.len(), 8);
+    assert_eq!(dao.myself(&ds_uuid)?.id, MYSELF_ID);
+
+    let cwds = dao.chats(&ds_uuid)?;
+    assert_eq!(cwds.len(), 7);
+    assert_eq!(cwds.iter().filter(|cwd| cwd.chat.tpe() == ChatType::PrivateGroup).count(), 2);
+
+    for cwd in cwds.iter() {
+        let msgs = dao.first_messages(&cwd.chat, usize::MAX)?;
+        assert_eq!(msgs.len(), 300);
+        assert_eq!(cwd.chat.msg_count, 300);
+        assert!(msgs.iter().tuple_windows().all(|(m1, m2)| m1.timestamp < m2.timestamp));
+        assert!(msgs.iter().all(|m| cwd.chat.member_ids.contains(&m.from_id)));
+
+        let source_ids = msgs.iter().filter_map(|m| m.source_id_option).collect::<HashSet<_>>();
+        for m in msgs.iter() {
+            if let Some(message::Typed::Regular(mr)) = &m.typed {
+                assert!(!m.text.is_empty() || !mr.contents.is_empty());
+                if let Some(reply_to) = mr.reply_to_message_id_option {
+                    assert!(source_ids.contains(&reply_to) && reply_to < m.source_id_option.unwrap());
+                }
+            }
+        }
+
+        // Mixed content
+        let content_types = msgs.iter()
+            .filter_map(|m| match m.typed() {
+                message_regular_pat! { contents, .. } => contents.first(),
+                _ => None
+            })
+            .filter_map(|c| c.sealed_value_optional.as_ref().map(std::mem::discriminant))
+            .unique()
+            .count();
+        assert!(content_types >= 5);
+        assert!(msgs.iter().any(|m| matches!(m.typed(), message_service_pat!(_))));
+    }
+
+    // Same seed, same content
+    let dao2 = generate_synthetic_dao("Synthetic".to_owned(), 7, 300, 42)?;
+    let ds_uuid2 = dao2.ds_uuid();
+    let chat = |dao: &InMemoryDao, ds_uuid: &PbUuid| dao.chats(ds_uuid).unwrap().remove(2).chat;
+    let (chat1, chat2) = (chat(&dao, &ds_uuid), chat(&dao2, &ds_uuid2));
+    assert_eq!(chat1.member_ids, chat2.member_ids);
+    assert_eq!(dao.first_messages(&chat1, usize::MAX)?, dao2.first_messages(&chat2, usize::MAX)?);
+    Ok(())
+}
+
+#[test]
+fn limits() {
+    assert!(generate_synthetic_dao("Synthetic".to_owned(), 0, 10, 0).is_err());
+    assert!(generate_synthetic_dao("Synthetic".to_owned(), 10, MAX_MESSAGES, 0).is_err());
+    assert!(generate_synthetic_dao("Synthetic".to_owned(), 1, 0, 0).is_ok());
+}
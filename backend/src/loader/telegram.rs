@@ -4,15 +4,18 @@ use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-use chrono::NaiveDate;
+use chrono::{NaiveDate, TimeZone};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 use simd_json::borrowed::Object;
 use simd_json::BorrowedValue;
 use simd_json::prelude::*;
+use chat_history_manager_core::parsing::telegram::{actual_path_option, full_name, is_single_chat_export, parse_timestamp};
+use crate::dao::ChatHistoryReader;
 use crate::dao::in_memory_dao::InMemoryDao;
 use crate::loader::DataLoader;
+use crate::loader::avatars::FoundAvatars;
 use crate::prelude::*;
 // Reexporting JSON utils for simplicity.
 pub use crate::utils::json_utils::*;
@@ -34,6 +37,17 @@ const GROUP_CHAT_ID_SHIFT: i64 = PERSONAL_CHAT_ID_SHIFT * 2;
 
 const RESULT_JSON: &str = "result.json";
 
+/// Directory with profile pictures of myself, only present in full exports
+const PROFILE_PICTURES_DIR: &str = "profile_pictures";
+
+lazy_static! {
+    static ref CHUNK_FILENAME_REGEX: Regex = Regex::new(r"^(?:messages(\d+)|result[ _-]?\(?(\d+)\)?)\.json$").unwrap();
+
+    // E.g. photo_2@14-05-2019_21-29-03.jpg
+    static ref PROFILE_PICTURE_FILENAME_REGEX: Regex =
+        Regex::new(r"@(\d{2})-(\d{2})-(\d{4})_(\d{2})-(\d{2})-(\d{2})\.\w+$").unwrap();
+}
+
 pub struct TelegramDataLoader;
 
 impl DataLoader for TelegramDataLoader {
@@ -53,6 +67,19 @@ impl DataLoader for TelegramDataLoader {
     fn load_inner(&self, path: &Path, ds: Dataset, user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
         parse_telegram_file(path, ds, user_input_requester)
     }
+
+    fn find_avatars(&self, dao: &InMemoryDao) -> Result<FoundAvatars> {
+        let ds_uuid = dao.datasets()?.remove(0).uuid;
+        let dir = dao.dataset_root(&ds_uuid)?.0.join(PROFILE_PICTURES_DIR);
+        let mut found = FoundAvatars::default();
+        if !dir.is_dir() { return Ok(found); }
+
+        let paths = find_profile_pictures(&dir)?;
+        if !paths.is_empty() {
+            found.users.insert(dao.myself(&ds_uuid)?.id(), paths);
+        }
+        Ok(found)
+    }
 }
 
 type CB<'a> = ParseCallback<'a>;
@@ -65,11 +92,7 @@ struct Users {
 
 impl Users {
     fn pretty_name(u: &User) -> String {
-        String::from(format!(
-            "{} {}",
-            u.first_name_option.as_deref().unwrap_or(""),
-            u.last_name_option.as_deref().unwrap_or(""),
-        ).trim())
+        full_name(u.first_name_option.as_deref(), u.last_name_option.as_deref())
     }
 
     /// Consumes both users, creating a mega-user!
@@ -88,7 +111,21 @@ impl Users {
             last_name_option,
             phone_number_option: original.phone_number_option.or(new.phone_number_option),
             username_option: original.username_option.or(new.username_option),
-            profile_pictures: original.profile_pictures, // TG doesn't export profile pictures
+            profile_pictures: original.profile_pictures, // Only resolved for myself, after loading
+        }
+    }
+
+    /// Adds users from another (partial) parse result, merging the ones already known.
+    fn absorb(&mut self, other: Users) {
+        for (id, user) in other.id_to_user {
+            let user = match self.id_to_user.remove(&id) {
+                Some(existing) => Self::merge(existing, user),
+                None => user,
+            };
+            self.id_to_user.insert(id, user);
+        }
+        for (_pretty_name, user) in other.pretty_name_to_idless_users {
+            self.insert(user);
         }
     }
 
@@ -140,15 +177,14 @@ impl Users {
 }
 
 enum ShouldProceed {
-    ProceedMessage { text_prefix: Option<String> },
+    /// `topic_title` is only set for messages starting a forum topic.
+    ProceedMessage { text_prefix: Option<String>, topic_title: Option<String> },
     SkipMessage,
-    SkipChat,
 }
 
 enum ParsedMessage {
     Ok(Box<Message>),
     SkipMessage,
-    SkipChat,
 }
 
 #[derive(Clone)]
@@ -169,33 +205,18 @@ fn parse_telegram_file(path: &Path, ds: Dataset, user_input_requester: &dyn User
     let path = get_real_path(path);
     assert!(path.exists()); // Should be checked by looks_about_right already.
 
-    log::info!("Parsing '{}'", path.display());
-
-    let start_time = Instant::now();
-
-    let mut file_content = fs::read(&path)?;
-    let parsed = simd_json::to_borrowed_value(&mut file_content)?;
-
-    log::info!("Parsed in {} ms", start_time.elapsed().as_millis());
-
-    let start_time = Instant::now();
-    let root_obj = as_object!(parsed, "root");
-
     let mut myself = User {
         ds_uuid: ds.uuid.clone(),
         ..Default::default()
     };
 
-    let single_chat_keys = HashSet::from(["name", "type", "id", "messages"]);
-    let keys = root_obj.keys().map(|s| s.deref()).collect::<HashSet<_>>();
-    let (users, chats_with_messages) =
-        if single_chat_keys.is_superset(&keys) {
-            parser_single::parse(root_obj, &ds.uuid, &mut myself, user_input_requester)?
-        } else {
-            parser_full::parse(root_obj, &ds.uuid, &mut myself)?
-        };
+    let (mut users, mut chats_with_messages) = parse_json_file(&path, &ds.uuid, &mut myself, user_input_requester)?;
 
-    log::info!("Processed in {} ms", start_time.elapsed().as_millis());
+    for chunk_path in find_chunks(&path)? {
+        let (chunk_users, chunk_cwms) = parse_json_file(&chunk_path, &ds.uuid, &mut myself, user_input_requester)?;
+        users.absorb(chunk_users);
+        stitch_chats(&mut chats_with_messages, chunk_cwms);
+    }
 
     if !users.pretty_name_to_idless_users.is_empty() {
         log::warn!("Discarding users with no IDs:");
@@ -219,6 +240,11 @@ fn parse_telegram_file(path: &Path, ds: Dataset, user_input_requester: &dyn User
     // Set myself to be a first member (not required by convention but to match existing behaviour).
     users.sort_by_key(|u| if u.id == myself.id { *UserId::MIN } else { u.id });
 
+    // Mentions by username can only be resolved once all users are known
+    for msg in chats_with_messages.iter_mut().flat_map(|cwm| cwm.messages.iter_mut()) {
+        resolve_mentions(&mut msg.text, &users);
+    }
+
     let parent_name = path_file_name(path.parent().unwrap())?;
     let mut result = Box::new(InMemoryDao::new_single(
         format!("Telegram ({})", parent_name),
@@ -233,6 +259,110 @@ fn parse_telegram_file(path: &Path, ds: Dataset, user_input_requester: &dyn User
     Ok(result)
 }
 
+fn parse_json_file(path: &Path,
+                   ds_uuid: &PbUuid,
+                   myself: &mut User,
+                   user_input_requester: &dyn UserInputBlockingRequester) -> Result<(Users, Vec<ChatWithMessages>)> {
+    log::info!("Parsing '{}'", path.display());
+
+    let start_time = Instant::now();
+
+    let mut file_content = fs::read(path)?;
+    let parsed = simd_json::to_borrowed_value(&mut file_content)?;
+
+    log::info!("Parsed in {} ms", start_time.elapsed().as_millis());
+
+    let start_time = Instant::now();
+    let root_obj = as_object!(parsed, "root");
+
+    let res =
+        if is_single_chat_export(root_obj.keys().map(|s| s.deref())) {
+            parser_single::parse(root_obj, ds_uuid, myself, user_input_requester)?
+        } else {
+            parser_full::parse(root_obj, ds_uuid, myself)?
+        };
+
+    log::info!("Processed in {} ms", start_time.elapsed().as_millis());
+    Ok(res)
+}
+
+/// Large or repeated exports might be saved as several files alongside `result.json` -
+/// either `messages2.json`-style chunks, or other exports named like `result (2).json`.
+/// Returns them ordered by the number in their name.
+fn find_chunks(result_json_path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = result_json_path.parent().unwrap();
+    let mut chunks = list_all_files(dir, false)?.into_iter()
+        .filter(|p| path_file_name(p).is_ok_and(|name| CHUNK_FILENAME_REGEX.is_match(name)))
+        .collect_vec();
+    chunks.sort_by_cached_key(|p| {
+        let name = path_file_name(p).unwrap().to_owned();
+        let num = CHUNK_FILENAME_REGEX.captures(&name)
+            .and_then(|c| c.get(1).or(c.get(2)))
+            .and_then(|m| m.as_str().parse::<u64>().ok());
+        (num, name)
+    });
+    Ok(chunks)
+}
+
+/// Merges chats from a newly parsed chunk into the already known ones.
+/// Messages are de-duplicated by their source IDs, later chunks taking precedence.
+/// Profile pictures ordered from most recent, files with unrecognized names go last.
+fn find_profile_pictures(dir: &Path) -> Result<Vec<PathBuf>> {
+    let paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .filter_ok(|p| p.is_file())
+        .collect::<std::io::Result<Vec<_>>>()?;
+    Ok(paths.into_iter()
+        .map(|p| {
+            let key = path_file_name(&p).ok()
+                .and_then(|name| PROFILE_PICTURE_FILENAME_REGEX.captures(name))
+                .map(|c| [3, 2, 1, 4, 5, 6].map(|i| c[i].to_owned()).concat());
+            (key, p)
+        })
+        .sorted_by(|(k1, p1), (k2, p2)| k2.cmp(k1).then_with(|| p1.cmp(p2)))
+        .map(|(_, p)| p)
+        .collect_vec())
+}
+
+fn stitch_chats(cwms: &mut Vec<ChatWithMessages>, chunk_cwms: Vec<ChatWithMessages>) {
+    for chunk_cwm in chunk_cwms {
+        let Some(cwm) = cwms.iter_mut().find(|cwm| cwm.chat.id == chunk_cwm.chat.id) else {
+            cwms.push(chunk_cwm);
+            continue;
+        };
+
+        if chunk_cwm.chat.name_option.is_some() {
+            cwm.chat.name_option = chunk_cwm.chat.name_option;
+        }
+        for member_id in chunk_cwm.chat.member_ids {
+            if !cwm.chat.member_ids.contains(&member_id) {
+                cwm.chat.member_ids.push(member_id);
+            }
+        }
+
+        let mut messages: Vec<Message> = Vec::with_capacity(cwm.messages.len() + chunk_cwm.messages.len());
+        let mut source_id_to_idx: HashMap<i64, usize, Hasher> = HashMap::with_hasher(hasher());
+        for m in std::mem::take(&mut cwm.messages).into_iter().chain(chunk_cwm.messages) {
+            match m.source_id_option.and_then(|id| source_id_to_idx.get(&id)) {
+                Some(&idx) => messages[idx] = m,
+                None => {
+                    if let Some(source_id) = m.source_id_option {
+                        source_id_to_idx.insert(source_id, messages.len());
+                    }
+                    messages.push(m);
+                }
+            }
+        }
+
+        messages.sort_by_key(|m| (m.timestamp, m.source_id_option));
+        for (idx, m) in messages.iter_mut().enumerate() {
+            m.internal_id = idx as i64;
+        }
+        cwm.chat.msg_count = messages.len() as i32;
+        cwm.messages = messages;
+    }
+}
+
 /** Returns a partially filled user. */
 fn parse_contact(json_path: &str, bw: &BorrowedValue) -> Result<User> {
     let mut user: User = Default::default();
@@ -272,7 +402,7 @@ fn parse_contact(json_path: &str, bw: &BorrowedValue) -> Result<User> {
     Ok(user)
 }
 
-/// Returns None if the chat is skipped (e.g. is saved_messages).
+/// Returns None if the chat is skipped (e.g. is a channel).
 fn parse_chat(json_path: &str,
               chat_json: &Object,
               ds_uuid: &PbUuid,
@@ -309,7 +439,8 @@ fn parse_chat(json_path: &str,
                 "personal_chat" => Ok(ChatType::Personal),
                 "private_group" => Ok(ChatType::PrivateGroup),
                 "private_supergroup" => Ok(ChatType::PrivateGroup),
-                "saved_messages" | "private_channel" => {
+                "saved_messages" => Ok(ChatType::SelfChat),
+                "private_channel" => {
                     skip_processing = true;
                     Ok(ChatType::Personal) // Doesn't matter
                 }
@@ -333,12 +464,6 @@ fn parse_chat(json_path: &str,
                         messages.push(*msg),
                     ParsedMessage::SkipMessage =>
                         { /* NOOP */ }
-                    ParsedMessage::SkipChat => {
-                        log::warn!("Skipping chat '{}' because it contains topics!", name_or_unnamed(&chat_name));
-                        // Note: the parsing itself might've modified users, so we want to remove orphan users later.
-                        skip_processing = true;
-                        break;
-                    }
                 }
             }
             Ok(())
@@ -354,6 +479,8 @@ fn parse_chat(json_path: &str,
 
     messages.sort_by_key(|m| (m.timestamp, m.internal_id));
 
+    assign_topics(&mut messages);
+
     for (idx, m) in messages.iter_mut().enumerate() {
         m.internal_id = idx as i64;
     }
@@ -362,7 +489,7 @@ fn parse_chat(json_path: &str,
 
     // Undo the shifts introduced by Telegram 2021-05.
     match ChatType::resolve(chat.tpe)? {
-        ChatType::Personal if chat.id < PERSONAL_CHAT_ID_SHIFT =>
+        ChatType::Personal | ChatType::SelfChat if chat.id < PERSONAL_CHAT_ID_SHIFT =>
             chat.id += PERSONAL_CHAT_ID_SHIFT,
         ChatType::PrivateGroup if chat.id < GROUP_CHAT_ID_SHIFT =>
             chat.id += GROUP_CHAT_ID_SHIFT,
@@ -458,17 +585,7 @@ impl<'lt> MessageJson<'lt> {
     /// Retrieve a RELATIVE path!
     fn field_opt_path(&mut self, name: &'lt str) -> Result<Option<String>> {
         let field_opt = self.field_opt_str(name)?;
-
-        Ok(field_opt.and_then(|s| match s.as_str() {
-            "" => None,
-            "(File not included. Change data exporting settings to download.)" => None,
-            "(File exceeds maximum size. Change data exporting settings to download.)" => None,
-            "(File unavailable, please try again later)" => {
-                // So far looks like it may mean timed photo, or file manually skipped during export.
-                None
-            }
-            _ => Some(s)
-        }))
+        Ok(field_opt.and_then(actual_path_option))
     }
 }
 
@@ -488,10 +605,12 @@ fn parse_message(json_path: &str,
         static ref REGULAR_MSG_FIELDS: ExpectedMessageField<'static> = ExpectedMessageField {
             required_fields: hash_set(["id", "type", "date", "text", "from", "from_id"]),
             // forwarded_from: the original source message
+            // forwarded_from_id: ID of the original source, present in newer exports
             // saved_from:     where the message was last forwarded from, could match forwarded_from (ignored)
-            optional_fields: hash_set(["date_unixtime", "text_entities", "forwarded_from", "saved_from", "via_bot",
+            optional_fields: hash_set(["date_unixtime", "text_entities", "forwarded_from", "forwarded_from_id",
+                                       "saved_from", "via_bot",
                                        "reply_to_peer_id", "reply_to_message_id", "inline_bot_buttons",
-                                       "author", "reactions"]),
+                                       "author", "reactions", "invoice_information"]),
         };
 
         static ref SERVICE_MSG_FIELDS: ExpectedMessageField<'static> = ExpectedMessageField {
@@ -510,6 +629,7 @@ fn parse_message(json_path: &str,
     let mut short_user: ShortUser = ShortUser::default();
     let mut text: Vec<RichTextElement> = vec![];
     let tpe = message_json.field_str("type")?;
+    let mut topic_title_option: Option<String> = None;
     let typed: Typed;
     match tpe.as_str() {
         "message" => {
@@ -529,15 +649,14 @@ fn parse_message(json_path: &str,
             let mut service: MessageService = Default::default();
             let proceed = parse_service_message(&mut message_json, &mut service)?;
             match proceed {
-                ShouldProceed::ProceedMessage { text_prefix } => {
+                ShouldProceed::ProceedMessage { text_prefix, topic_title } => {
                     if let Some(text_prefix) = text_prefix {
                         text.push(RichText::make_plain(format!("{text_prefix}\n")));
                     }
+                    topic_title_option = topic_title;
                 }
                 ShouldProceed::SkipMessage =>
                     return Ok(ParsedMessage::SkipMessage),
-                ShouldProceed::SkipChat =>
-                    return Ok(ParsedMessage::SkipChat)
             };
             typed = Typed::Service(service);
 
@@ -547,10 +666,7 @@ fn parse_message(json_path: &str,
         etc => bail!("Unknown message type: {}", etc),
     }
 
-    // Normalize user ID.
-    if *short_user.id >= USER_ID_SHIFT {
-        short_user.id = UserId(*short_user.id - USER_ID_SHIFT);
-    }
+    short_user.id = UserId(normalize_user_id(short_user.id));
 
     let from_id = short_user.id;
 
@@ -588,6 +704,9 @@ fn parse_message(json_path: &str,
                 // No way to get the actual messages being answered to
                 text.push(RichText::make_italic("(Replying to a channel post)\n".to_owned()));
             }
+            "invoice_information" => {
+                text.push(parse_invoice(&format!("{}.invoice_information", message_json.json_path), v)?);
+            }
             "text_entities" => {
                 text.extend(parse_rich_text(&format!("{}.text_entities", message_json.json_path), v)?);
             }
@@ -617,14 +736,42 @@ fn parse_message(json_path: &str,
         }
     }
 
-    Ok(ParsedMessage::Ok(Box::new(Message::new(
+    let mut msg = Message::new(
         *NO_INTERNAL_ID,
         source_id_option,
         timestamp.with_context(|| format!("{}: timestamp not set", message_json.json_path))?,
         from_id,
         text,
         typed,
-    ))))
+    );
+    if let Some(title) = topic_title_option {
+        let id = source_id_option.with_context(|| format!("{}: topic has no ID", message_json.json_path))?;
+        msg.topic_option = Some(MessageTopic { id, title });
+    }
+    Ok(ParsedMessage::Ok(Box::new(msg)))
+}
+
+/// Forum topic messages are replies either to a message that started the topic, or to other messages
+/// within the same topic. Messages in a "General" topic don't belong to any.
+fn assign_topics(messages: &mut [Message]) {
+    let mut source_id_to_topic: HashMap<i64, MessageTopic> = HashMap::new();
+    for m in messages.iter_mut() {
+        if let (Some(topic), Some(source_id)) = (&m.topic_option, m.source_id_option) {
+            source_id_to_topic.insert(source_id, topic.clone());
+            continue;
+        }
+        if source_id_to_topic.is_empty() { continue; }
+        let reply_to_option = match m.typed() {
+            message::Typed::Regular(mr) => mr.reply_to_message_id_option,
+            _ => None,
+        };
+        if let Some(topic) = reply_to_option.and_then(|id| source_id_to_topic.get(&id)).cloned() {
+            if let Some(source_id) = m.source_id_option {
+                source_id_to_topic.insert(source_id, topic.clone());
+            }
+            m.topic_option = Some(topic);
+        }
+    }
 }
 
 fn parse_regular_message(message_json: &mut MessageJson,
@@ -649,6 +796,11 @@ fn parse_regular_message(message_json: &mut MessageJson,
         Some(forwarded_from) if forwarded_from.is_null() => Some(UNKNOWN.to_owned()),
         Some(forwarded_from) => Some(as_string!(forwarded_from, json_path, "forwarded_from")),
     };
+    regular_msg.forward_from_id_option = match message_json.field_opt("forwarded_from_id")? {
+        None => None,
+        Some(forwarded_from_id) if forwarded_from_id.is_null() => None,
+        Some(forwarded_from_id) => Some(normalize_user_id(parse_user_id(forwarded_from_id)?)),
+    };
     if message_json.field_opt("reply_to_peer_id")?.is_none() {
         // Otherwise reply_to_message_id is pointless
         regular_msg.reply_to_message_id_option = message_json.field_opt_i64("reply_to_message_id")?;
@@ -664,6 +816,7 @@ fn parse_regular_message(message_json: &mut MessageJson,
         Some(poll) => as_object!(poll, json_path, "poll").get("question").is_some(),
     };
     let contact_info_present = message_json.field_opt("contact_information")?.is_some();
+    let mime_type = || mime_type_option.clone().with_context(|| format!("{json_path}: mime_type is missing"));
 
     // Helpers to reduce boilerplate, since we can't have match guards for separate pattern arms.
     let make_content_audio = |message_json: &mut MessageJson| -> Result<Option<_>> {
@@ -674,8 +827,9 @@ fn parse_regular_message(message_json: &mut MessageJson,
             file_name_option: message_json.field_opt_str("file_name")?,
             title_option: message_json.field_opt_str("title")?,
             performer_option: message_json.field_opt_str("performer")?,
-            mime_type: mime_type_option.clone().unwrap(),
+            mime_type: mime_type()?,
             duration_sec_option: message_json.field_opt_i32("duration_seconds")?,
+            waveform_option: None,
             thumbnail_path_option: message_json.field_opt_path("thumbnail")?,
         })))
     };
@@ -690,7 +844,7 @@ fn parse_regular_message(message_json: &mut MessageJson,
             performer_option: message_json.field_opt_str("performer")?,
             width: message_json.field_opt_i32("width")?.unwrap_or(0),
             height: message_json.field_opt_i32("height")?.unwrap_or(0),
-            mime_type: mime_type_option.clone().unwrap(),
+            mime_type: mime_type()?,
             duration_sec_option: message_json.field_opt_i32("duration_seconds")?,
             thumbnail_path_option: message_json.field_opt_path("thumbnail")?,
             is_one_time: false,
@@ -717,6 +871,8 @@ fn parse_regular_message(message_json: &mut MessageJson,
                 mime_type_option: None,
                 thumbnail_path_option: message_json.field_opt_path("thumbnail")?,
                 emoji_option: message_json.field_opt_str("sticker_emoji")?,
+                pack_id_option: None,
+                pack_name_option: None,
             }))
         }
         (Some("voice_message"), None, true, false, false, false) => {
@@ -724,8 +880,9 @@ fn parse_regular_message(message_json: &mut MessageJson,
             Some(content!(VoiceMsg {
                 path_option: message_json.field_opt_path("file")?,
                 file_name_option: message_json.field_opt_str("file_name")?,
-                mime_type: mime_type_option.unwrap(),
+                mime_type: mime_type()?,
                 duration_sec_option: message_json.field_opt_i32("duration_seconds")?,
+                waveform_option: None,
             }))
         }
         (Some("audio_file"), None, true, false, false, false) =>
@@ -740,7 +897,7 @@ fn parse_regular_message(message_json: &mut MessageJson,
                 file_name_option: message_json.field_opt_str("file_name")?,
                 width: message_json.field_i32("width")?,
                 height: message_json.field_i32("height")?,
-                mime_type: mime_type_option.unwrap(),
+                mime_type: mime_type()?,
                 duration_sec_option: message_json.field_opt_i32("duration_seconds")?,
                 thumbnail_path_option: message_json.field_opt_path("thumbnail")?,
                 is_one_time: false,
@@ -757,7 +914,7 @@ fn parse_regular_message(message_json: &mut MessageJson,
                 performer_option: None,
                 width: message_json.field_i32("width")?,
                 height: message_json.field_i32("height")?,
-                mime_type: mime_type_option.unwrap(),
+                mime_type: mime_type()?,
                 duration_sec_option: message_json.field_opt_i32("duration_seconds")?,
                 thumbnail_path_option: message_json.field_opt_path("thumbnail")?,
                 is_one_time: false,
@@ -792,6 +949,9 @@ fn parse_regular_message(message_json: &mut MessageJson,
                         height: message_json.field_i32("height")?,
                         mime_type_option: None,
                         is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
                     }))
                 }
                 Some(_) => {
@@ -804,6 +964,9 @@ fn parse_regular_message(message_json: &mut MessageJson,
                         height: 0,
                         mime_type_option: None,
                         is_one_time: true,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
                     }))
                 }
             }
@@ -821,14 +984,13 @@ fn parse_regular_message(message_json: &mut MessageJson,
                 lat_str,
                 lon_str,
                 duration_sec_option: message_json.field_opt_i32("live_location_period_seconds")?,
+                // Export only has a single (initial) point
+                updates: vec![],
             }))
         }
         (None, None, false, false, true, false) => {
-            let question = {
-                let poll_info = as_object!(message_json.field("poll")?, json_path, "poll");
-                get_field_string!(poll_info, json_path, "question")
-            };
-            Some(content!(Poll { question }))
+            let poll = parse_poll(&json_path, message_json.field("poll")?)?;
+            Some(content!(Poll { ..poll }))
         }
         (None, None, false, false, false, true) => {
             message_json.add_optional("contact_vcard_file_size");
@@ -856,6 +1018,8 @@ fn parse_regular_message(message_json: &mut MessageJson,
                 last_name_option,
                 phone_number_option,
                 vcard_path_option,
+                // Filled from vCard later, if it's present
+                emails: vec![],
             }))
         }
         _ => bail!("Couldn't determine content type for '{:?}'", message_json.val)
@@ -885,6 +1049,7 @@ fn parse_service_message(message_json: &mut MessageJson,
             .collect::<Result<Vec<String>>>()
     }
 
+    let mut topic_title: Option<String> = None;
     let (val, text_prefix): (SealedValueOptional, Option<String>) = match message_json.field_str("action")?.as_str() {
         "phone_call" =>
             (SealedValueOptional::PhoneCall(MessageServicePhoneCall {
@@ -892,12 +1057,23 @@ fn parse_service_message(message_json: &mut MessageJson,
                 discard_reason_option: message_json.field_opt_str("discard_reason")?,
                 members: vec![],
             }), None),
-        "group_call" => // Treated the same as phone_call
+        "group_call" => // Treated the same as phone_call, video chat that has just started has no duration
             (SealedValueOptional::PhoneCall(MessageServicePhoneCall {
                 duration_sec_option: message_json.field_opt_i32("duration")?,
                 discard_reason_option: None,
                 members: vec![],
             }), None),
+        "group_call_scheduled" => {
+            let json_path = format!("{}.schedule_date", message_json.json_path);
+            let schedule_date = message_json.field("schedule_date")?;
+            let ts = match schedule_date.as_str() {
+                Some(s) if s.contains('T') => *parse_datetime(s)?,
+                Some(s) => parse_timestamp(s)?,
+                None => as_i64!(schedule_date, json_path),
+            };
+            (SealedValueOptional::Notice(MessageServiceNotice {}),
+             Some(format!("Video chat scheduled for {}", format_local_datetime(ts)?)))
+        }
         "pin_message" =>
             (SealedValueOptional::PinMessage(MessageServicePinMessage {
                 message_source_id: message_json.field_i64("message_id")?
@@ -910,6 +1086,9 @@ fn parse_service_message(message_json: &mut MessageJson,
                     width: message_json.field_i32("width")?,
                     mime_type_option: None,
                     is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
                 }
             }), None),
         "clear_history" =>
@@ -932,6 +1111,9 @@ fn parse_service_message(message_json: &mut MessageJson,
                     width: message_json.field_i32("width")?,
                     mime_type_option: None,
                     is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
                 }
             }), None),
         "delete_group_photo" =>
@@ -1006,16 +1188,106 @@ fn parse_service_message(message_json: &mut MessageJson,
             // Not really interesting to track.
             return Ok(ShouldProceed::SkipMessage);
         }
-        "topic_created" | "topic_edit" => {
-            // Topic-level division is implemented via repies to "topic_created" messages.
-            // This is a questionable approach that I don't want to track at the moment.
-            return Ok(ShouldProceed::SkipChat);
+        "topic_created" => {
+            // Topic-level division is implemented via replies to "topic_created" messages, see assign_topics.
+            message_json.add_optional("icon_emoji_id");
+            let title = message_json.field_str("title")?;
+            let text_prefix = format!("Topic created: {title}");
+            topic_title = Some(title);
+            (SealedValueOptional::Notice(MessageServiceNotice {}), Some(text_prefix))
+        }
+        "topic_edit" => {
+            message_json.add_optional("new_icon_emoji_id");
+            let text = match (message_json.field_opt_str("new_title")?,
+                              message_json.field_opt("closed")?.and_then(|v| v.as_bool()),
+                              message_json.field_opt("hidden")?.and_then(|v| v.as_bool())) {
+                (Some(title), _, _) => format!("Topic renamed to {title}"),
+                (None, Some(true), _) => "Topic closed".to_owned(),
+                (None, Some(false), _) => "Topic reopened".to_owned(),
+                (None, None, Some(true)) => "Topic hidden".to_owned(),
+                (None, None, Some(false)) => "Topic unhidden".to_owned(),
+                (None, None, None) => "Topic icon changed".to_owned(),
+            };
+            (SealedValueOptional::Notice(MessageServiceNotice {}), Some(text))
+        }
+        "send_payment" => {
+            message_json.add_optional("invoice_message_id");
+            message_json.add_optional("recurring_init");
+            message_json.add_optional("recurring_used");
+            let amount = message_json.field_i64("amount")?;
+            let currency = message_json.field_str("currency")?;
+            (SealedValueOptional::Notice(MessageServiceNotice {}),
+             Some(format!("Payment sent: {}", format_amount(amount, &currency))))
+        }
+        "proximity_reached" => {
+            message_json.add_optional("from");
+            message_json.add_optional("from_id");
+            message_json.add_optional("to_id");
+            let to = name_or_unnamed(&message_json.field_opt_str("to")?);
+            let distance = message_json.field_i32("distance")?;
+            (SealedValueOptional::Notice(MessageServiceNotice {}),
+             Some(format!("Now within {distance} m from {to}")))
         }
         etc =>
             bail!("Don't know how to parse service message for action '{etc}'"),
     };
     service_msg.sealed_value_optional = Some(val);
-    Ok(ShouldProceed::ProceedMessage { text_prefix })
+    Ok(ShouldProceed::ProceedMessage { text_prefix, topic_title })
+}
+
+/// Amount is given in the smallest currency units, most currencies have two decimal digits.
+fn format_amount(amount: i64, currency: &str) -> String {
+    format!("{}.{:02} {currency}", amount / 100, (amount % 100).abs())
+}
+
+fn format_local_datetime(ts: i64) -> Result<String> {
+    let dt = LOCAL_TZ.timestamp_opt(ts, 0).single().with_context(|| format!("Invalid timestamp {ts}"))?;
+    Ok(dt.format("%Y-%m-%d %H:%M").to_string())
+}
+
+/// Invoices are regular messages with no dedicated content type, so they're rendered as text
+fn parse_poll(json_path: &str, poll_json: &BorrowedValue) -> Result<ContentPoll> {
+    let json_path = format!("{json_path}.poll");
+    let poll_json = as_object!(poll_json, json_path);
+    let question = get_field_string!(poll_json, json_path, "question");
+    let total_voters_option = match poll_json.get("total_voters") {
+        Some(v) => Some(as_i32!(v, json_path, "total_voters")),
+        None => None,
+    };
+    let mut options = vec![];
+    if let Some(answers) = poll_json.get("answers") {
+        let answer_path = format!("{json_path}.answers[]");
+        for answer in as_array!(answers, json_path, "answers") {
+            let answer = as_object!(answer, answer_path);
+            options.push(ContentPollOption {
+                text: get_field_string!(answer, answer_path, "text"),
+                voters_option: match answer.get("voters") {
+                    Some(v) => Some(as_i32!(v, answer_path, "voters")),
+                    None => None,
+                },
+                is_chosen: match answer.get("chosen") {
+                    Some(v) => as_bool!(v, answer_path, "chosen"),
+                    None => false,
+                },
+                // Telegram export does not specify whether the poll is a quiz
+                is_correct: false,
+            });
+        }
+    }
+    Ok(ContentPoll { question, options, is_quiz: None, total_voters_option })
+}
+
+fn parse_invoice(json_path: &str, invoice_json: &BorrowedValue) -> Result<RichTextElement> {
+    let invoice_json = as_object!(invoice_json, json_path);
+    let title = get_field_string!(invoice_json, json_path, "title");
+    let description = get_field_string_option!(invoice_json, json_path, "description");
+    let amount = get_field_i64!(invoice_json, json_path, "amount");
+    let currency = get_field_string!(invoice_json, json_path, "currency");
+
+    let mut lines = vec![format!("Invoice: {title}")];
+    lines.extend(description);
+    lines.push(format!("Amount: {}", format_amount(amount, &currency)));
+    Ok(RichText::make_plain(format!("{}\n", lines.join("\n"))))
 }
 
 //
@@ -1156,19 +1428,40 @@ fn parse_rich_text_object(json_path: &str,
             ))
         }
         "mention_name" => {
-            // No special treatment for mention_name, but prepent @
+            // Mentioned user has no username, prepend @ to its name
             check_keys!(["type", "text", "user_id"]);
-            Some(RichText::make_plain(format!("@{}", get_field_str!(rte_json, json_path, "text"))))
+            Some(RichText::make_mention(
+                format!("@{}", get_field_str!(rte_json, json_path, "text")),
+                Some(get_field_i64!(rte_json, json_path, "user_id")),
+                None,
+            ))
+        }
+        "mention" => {
+            // User ID is resolved by username later on, if the user is known
+            check_keys!(["type", "text"]);
+            let text = get_field_string!(rte_json, json_path, "text");
+            let handle = text.trim_start_matches('@').to_owned();
+            Some(RichText::make_mention(text, None, Some(handle)))
+        }
+        "hashtag" | "cashtag" => {
+            check_keys!(["type", "text"]);
+            Some(RichText::make_hashtag(get_field_string!(rte_json, json_path, "text"), tpe == "cashtag"))
         }
-        "email" | "mention" | "phone" | "hashtag" | "bot_command" | "bank_card" | "cashtag" => {
+        "email" | "phone" | "bot_command" | "bank_card" => {
             // No special treatment for any of these
             check_keys!(["type", "text"]);
             Some(RichText::make_plain(get_field_string!(rte_json, json_path, "text")))
         }
         "custom_emoji" => {
-            // Just taken as a regular emoji
+            // Older exports have document ID here, newer ones have a path to the emoji image instead
             check_keys!(["type", "text", "document_id"]);
-            Some(RichText::make_plain(get_field_string!(rte_json, json_path, "text")))
+            let document_id = get_field_string!(rte_json, json_path, "document_id");
+            let (emoji_id_option, path_option) = if document_id.parse::<i64>().is_ok() {
+                (Some(document_id), None)
+            } else {
+                (None, actual_path_option(document_id))
+            };
+            Some(RichText::make_custom_emoji(get_field_string!(rte_json, json_path, "text"), emoji_id_option, path_option))
         }
         etc =>
             bail!("Don't know how to parse RichText element of type '{etc}' for {:?}", rte_json)
@@ -1201,7 +1494,8 @@ fn simplify_rich_text(mut rtes: Vec<RichTextElement>) -> Vec<RichTextElement> {
             Val::Blockquote(_) | Val::Spoiler(_) => {
                 rte.get_text().unwrap().chars().all(|c| c.is_whitespace())
             }
-            Val::Link(_) | Val::PrefmtInline(_) | Val::PrefmtBlock(_) => {
+            Val::Link(_) | Val::PrefmtInline(_) | Val::PrefmtBlock(_) | Val::Mention(_) | Val::Hashtag(_) |
+            Val::CustomEmoji(_) => {
                 false
             }
         }
@@ -1302,8 +1596,9 @@ fn parse_user_id(bw: &BorrowedValue) -> Result<UserId> {
     }
 }
 
-fn parse_timestamp(s: &str) -> Result<i64> {
-    s.parse::<i64>().with_context(|| format!("Failed to parse unit timestamp {s}"))
+/// Un-shifts user ID, see [USER_ID_SHIFT].
+fn normalize_user_id(id: UserId) -> i64 {
+    if *id >= USER_ID_SHIFT { *id - USER_ID_SHIFT } else { *id }
 }
 
 fn parse_datetime(s: &str) -> Result<Timestamp> {
@@ -1314,9 +1609,11 @@ fn parse_datetime(s: &str) -> Result<Timestamp> {
             .map(|s| s.parse::<u32>())
             .collect::<StdResult<Vec<u32>, ParseIntError>>()
             .with_context(|| format!("Failed to parse date {s}"))?;
+    let [y, mo, d, h, mi, sec] = split[..] else { bail!("Failed to parse date {s}: unexpected format") };
     let date =
-        NaiveDate::from_ymd_opt(split[0] as i32, split[1], split[2]).unwrap()
-            .and_hms_opt(split[3], split[4], split[5]).unwrap()
+        NaiveDate::from_ymd_opt(y as i32, mo, d)
+            .and_then(|date| date.and_hms_opt(h, mi, sec))
+            .with_context(|| format!("Failed to parse date {s}: out of range"))?
             .and_local_timezone(*LOCAL_TZ)
             .single()
             .with_context(|| format!("Failed to parse date {}: ambiguous?", s))?;
@@ -20,10 +20,13 @@ pub(super) fn parse(root_obj: &Object,
         }
     }
 
-    // In single chat, self section is not present. As such, myself must be populated from users.
-    let mut users_vec = users.id_to_user.values().cloned().collect_vec();
-    let myself_idx = user_input_requester.choose_myself(&users_vec)?;
-    *myself = users_vec.swap_remove(myself_idx);
+    // In single chat, self section is not present. As such, myself must be populated from users,
+    // unless it was already chosen for another chunk of the same export.
+    if myself.id == 0 {
+        let mut users_vec = users.id_to_user.values().cloned().collect_vec();
+        let myself_idx = user_input_requester.choose_myself(&users_vec)?;
+        *myself = users_vec.swap_remove(myself_idx);
+    }
 
     Ok((users, chats_with_messages))
 }
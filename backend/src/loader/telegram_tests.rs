@@ -3,7 +3,7 @@
 use chrono::prelude::*;
 use pretty_assertions::assert_eq;
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::protobuf::history::message::*;
 use crate::protobuf::history::message_service::SealedValueOptional::*;
 use crate::protobuf::history::User;
@@ -78,6 +78,47 @@ fn loading_2020_01() -> EmptyRes {
 
     assert_eq!(dao.cwms_single_ds().len(), 4);
 
+    // Poll
+    {
+        let msg = dao.cwms_single_ds().into_iter()
+            .flat_map(|cwm| cwm.messages)
+            .find(|m| m.source_id_option == Some(132894))
+            .unwrap();
+        let option = |text: &str, voters: i32, is_chosen: bool| ContentPollOption {
+            text: text.to_owned(),
+            voters_option: Some(voters),
+            is_chosen,
+            is_correct: false,
+        };
+        assert_eq!(coerce_enum!(msg.typed(), Typed::Regular(r) => &r.contents), &vec![content!(Poll {
+            question: "Вечерний Мудозвон – это...".to_owned(),
+            options: vec![
+                option("Соловьёв", 18714, false),
+                option("Киселёв", 3046, false),
+                option("Ургант", 1422, false),
+                option("Собирательный образ тележурналиста", 11202, true),
+                option("Просто лирический герой", 1700, false),
+            ],
+            is_quiz: None,
+            total_voters_option: Some(36084),
+        })]);
+    }
+
+    // Shared contact, completed from vCard
+    {
+        let msg = dao.cwms_single_ds().into_iter()
+            .flat_map(|cwm| cwm.messages)
+            .find(|m| m.source_id_option == Some(129125))
+            .unwrap();
+        assert_eq!(coerce_enum!(msg.typed(), Typed::Regular(r) => &r.contents), &vec![content!(SharedContact {
+            first_name_option: None,
+            last_name_option: None,
+            phone_number_option: Some("+998 90 9222229, +998909990099".to_owned()),
+            vcard_path_option: Some("chats/chat_08/contacts/contact_1.vcard".to_owned()),
+            emails: vec![],
+        })]);
+    }
+
     // "Ordered" chat
     {
         let cwm = dao.cwms_single_ds().into_iter()
@@ -94,6 +135,8 @@ fn loading_2020_01() -> EmptyRes {
             member_ids: vec![myself.id, *member.id],
             msg_count: 5,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -177,6 +220,8 @@ fn loading_2021_05() -> EmptyRes {
             member_ids: vec![myself.id, service_member.id, member1.id, member2.id],
             msg_count: 3,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -239,6 +284,8 @@ fn loading_2021_06_supergroup() -> EmptyRes {
             member_ids: vec![myself.id, u222222222.id, u333333333.id, u444444444.id],
             msg_count: 4,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -251,6 +298,8 @@ fn loading_2021_06_supergroup() -> EmptyRes {
             from_id: u222222222.id,
             text: vec![],
             searchable_string: "Vvvvvvvv Bbbbbbb".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
                 members: vec![u444444444.first_name_option.unwrap()]
             }))),
@@ -268,10 +317,14 @@ fn loading_2021_06_supergroup() -> EmptyRes {
                 })),
             }],
             searchable_string: "Message text with emoji 🙂".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
             }),
@@ -289,10 +342,14 @@ fn loading_2021_06_supergroup() -> EmptyRes {
                 })),
             }],
             searchable_string: "Message from an added user".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
             }),
@@ -304,10 +361,14 @@ fn loading_2021_06_supergroup() -> EmptyRes {
             from_id: myself.id,
             text: vec![],
             searchable_string: format!("{} {}", myself.first_name_option.unwrap_ref(), &myself.phone_number_option.as_ref().unwrap()),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(SharedContact {
@@ -315,6 +376,7 @@ fn loading_2021_06_supergroup() -> EmptyRes {
                         last_name_option: None,
                         phone_number_option: Some(myself.phone_number_option.to_owned().unwrap()),
                         vcard_path_option: None,
+                        emails: vec![],
                     })
                 ],
             }),
@@ -366,6 +428,8 @@ fn loading_2021_07() -> EmptyRes {
             member_ids: vec![myself.id, member.id],
             msg_count: 2,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -378,6 +442,8 @@ fn loading_2021_07() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "Www Wwwwww".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(PhoneCall(MessageServicePhoneCall {
                 duration_sec_option: None,
                 discard_reason_option: None,
@@ -391,6 +457,8 @@ fn loading_2021_07() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "Myself".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(PhoneCall(MessageServicePhoneCall {
                 duration_sec_option: None,
                 discard_reason_option: None,
@@ -456,6 +524,8 @@ fn loading_2023_01() -> EmptyRes {
             member_ids: vec![myself.id, member.id, channel_user.id],
             msg_count: 6,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -469,6 +539,8 @@ fn loading_2023_01() -> EmptyRes {
             from_id: channel_user.id,
             text: vec![],
             searchable_string: "My Group".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(GroupMigrateFrom(MessageServiceGroupMigrateFrom {
                 title: "My Group".to_owned()
             }))),
@@ -480,6 +552,8 @@ fn loading_2023_01() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(GroupMigrateTo(MessageServiceGroupMigrateTo {}))),
         });
         assert_eq!(msgs[2], Message {
@@ -488,11 +562,18 @@ fn loading_2023_01() -> EmptyRes {
             timestamp: dt("2016-11-17 17:57:40", Some(&offset)).timestamp(),
             from_id: member.id,
             text: vec![
-                // Two plaintext elements are concatenated
                 RichTextElement {
-                    searchable_string: "this contains a lot of stuff: 😁".to_owned(),
+                    searchable_string: "this contains a lot of stuff:".to_owned(),
                     val: Some(rich_text_element::Val::Plain(RtePlain {
-                        text: "this contains a lot of stuff: 😁".to_owned(),
+                        text: "this contains a lot of stuff: ".to_owned(),
+                    })),
+                },
+                RichTextElement {
+                    searchable_string: "😁".to_owned(),
+                    val: Some(rich_text_element::Val::CustomEmoji(RteCustomEmoji {
+                        text: "😁".to_owned(),
+                        emoji_id_option: None,
+                        path_option: Some("chats/chat_004/stickers/sticker (62).webp".to_owned()),
                     })),
                 },
                 RichTextElement {
@@ -511,10 +592,14 @@ fn loading_2023_01() -> EmptyRes {
                 },
             ],
             searchable_string: "this contains a lot of stuff: 😁 http://mylink.org/ HIDE ME".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
             }),
@@ -526,6 +611,8 @@ fn loading_2023_01() -> EmptyRes {
             from_id: myself.id,
             text: vec![],
             searchable_string: UNKNOWN.to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
                 members: vec![UNKNOWN.to_owned()]
             }))),
@@ -537,6 +624,8 @@ fn loading_2023_01() -> EmptyRes {
             from_id: myself.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(GroupDeletePhoto(MessageServiceGroupDeletePhoto {}))),
         });
         assert_eq!(msgs[5], Message {
@@ -546,6 +635,8 @@ fn loading_2023_01() -> EmptyRes {
             from_id: myself.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(SuggestProfilePhoto(MessageServiceSuggestProfilePhoto {
                 photo: ContentPhoto {
                     path_option: None,
@@ -553,6 +644,9 @@ fn loading_2023_01() -> EmptyRes {
                     height: 640,
                     mime_type_option: None,
                     is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
                 }
             }))),
         });
@@ -603,6 +697,8 @@ fn loading_2023_08() -> EmptyRes {
             member_ids: vec![myself.id, unnamed_user.id],
             msg_count: 2,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs: &Vec<Message> = &cwm.messages;
@@ -616,6 +712,8 @@ fn loading_2023_08() -> EmptyRes {
             from_id: unnamed_user.id,
             text: vec![],
             searchable_string: UNNAMED.to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
                 members: vec![UNNAMED.to_owned()]
             }))),
@@ -634,10 +732,14 @@ fn loading_2023_08() -> EmptyRes {
                 },
             ],
             searchable_string: "My message!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
             }),
@@ -689,6 +791,8 @@ fn loading_2023_10_audio_video() -> EmptyRes {
             member_ids: vec![myself.id, unnamed_user.id],
             msg_count: 4,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs: &Vec<Message> = &cwm.messages;
@@ -701,10 +805,14 @@ fn loading_2023_10_audio_video() -> EmptyRes {
             from_id: unnamed_user.id,
             text: vec![RichText::make_plain("Audio file (incomplete) message".to_owned())],
             searchable_string: "Audio file (incomplete) message".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Audio {
@@ -714,6 +822,7 @@ fn loading_2023_10_audio_video() -> EmptyRes {
                         performer_option: None,
                         mime_type: "audio/mpeg".to_owned(),
                         duration_sec_option: None,
+                        waveform_option: None,
                         thumbnail_path_option: None,
                     })
                 ],
@@ -726,10 +835,14 @@ fn loading_2023_10_audio_video() -> EmptyRes {
             from_id: unnamed_user.id,
             text: vec![RichText::make_plain("Audio file (full) message".to_owned())],
             searchable_string: "Audio file (full) message Song Name Audio Performer".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Audio {
@@ -739,6 +852,7 @@ fn loading_2023_10_audio_video() -> EmptyRes {
                         performer_option: Some("Audio Performer".to_owned()),
                         mime_type: "audio/mpeg".to_owned(),
                         duration_sec_option: Some(123),
+                        waveform_option: None,
                         thumbnail_path_option: Some("audio_file.mp3_thumb.jpg".to_owned()),
                     })
                 ],
@@ -751,10 +865,14 @@ fn loading_2023_10_audio_video() -> EmptyRes {
             from_id: unnamed_user.id,
             text: vec![RichText::make_plain("Video file (incomplete) message".to_owned())],
             searchable_string: "Video file (incomplete) message".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Video {
@@ -779,10 +897,14 @@ fn loading_2023_10_audio_video() -> EmptyRes {
             from_id: unnamed_user.id,
             text: vec![RichText::make_plain("Video file (full) message".to_owned())],
             searchable_string: "Video file (full) message Clip Name Video Performer".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Video {
@@ -874,10 +996,14 @@ fn loading_2024_02() -> EmptyRes {
         from_id: 11111111,
         text: vec![RichText::make_plain("Forward of a forward of a message".to_owned())],
         searchable_string: "Forward of a forward of a message".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: None,
             is_deleted: false,
             forward_from_name_option: Some("Forwarded From Name".to_owned()),
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![],
         }),
@@ -906,10 +1032,14 @@ fn loading_2024_05() -> EmptyRes {
         from_id: 11111111,
         text: vec![],
         searchable_string: "my-file.jpg".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: None,
             is_deleted: false,
             forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![
                 content!(File {
@@ -929,10 +1059,14 @@ fn loading_2024_05() -> EmptyRes {
         from_id: 11111111,
         text: vec![],
         searchable_string: "😱".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: None,
             is_deleted: false,
             forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![
                 content!(Sticker {
@@ -943,6 +1077,8 @@ fn loading_2024_05() -> EmptyRes {
                     mime_type_option: None,
                     thumbnail_path_option: Some("chats/chat_001/stickers/sticker.webm_thumb.jpg".to_owned()),
                     emoji_option: Some("😱".to_owned()),
+                    pack_id_option: None,
+                    pack_name_option: None,
                 })
             ],
         }),
@@ -955,6 +1091,8 @@ fn loading_2024_05() -> EmptyRes {
         from_id: 11111111,
         text: vec![RichText::make_plain("Group boosted by 123".to_owned())],
         searchable_string: "Group boosted by 123".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_service!(Notice(MessageServiceNotice {}))),
     });
 
@@ -980,10 +1118,14 @@ fn loading_2024_06() -> EmptyRes {
         from_id: 11111111,
         text: vec![RichText::make_blockquote("Blockquote with collapsed property".to_owned())],
         searchable_string: "Blockquote with collapsed property".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: Some(1665499755),
             is_deleted: false,
             forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![],
         }),
@@ -1011,10 +1153,14 @@ fn loading_2024_08() -> EmptyRes {
         from_id: 123123123,
         text: vec![RichText::make_plain("Admin msg!".to_owned())],
         searchable_string: "Admin msg!".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: Some(1665499755),
             is_deleted: false,
             forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![],
         }),
@@ -1027,10 +1173,14 @@ fn loading_2024_08() -> EmptyRes {
         from_id: 123123123,
         text: vec![RichText::make_plain("Bot msg!".to_owned())],
         searchable_string: "Bot msg!".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: None,
             is_deleted: false,
             forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![],
         }),
@@ -1043,6 +1193,8 @@ fn loading_2024_08() -> EmptyRes {
         from_id: 11111111,
         text: vec![],
         searchable_string: "Aaaaa Aaaaaaaaaaa".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
             members: vec!["Aaaaa Aaaaaaaaaaa".to_owned()]
         }))),
@@ -1055,10 +1207,14 @@ fn loading_2024_08() -> EmptyRes {
         from_id: 11111111,
         text: vec![],
         searchable_string: "".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: None,
             is_deleted: false,
             forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![
                 content!(Photo {
@@ -1067,6 +1223,9 @@ fn loading_2024_08() -> EmptyRes {
                     height: 0,
                     mime_type_option: None,
                     is_one_time: true,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
                 })
             ],
         }),
@@ -1099,10 +1258,14 @@ fn loading_2025_02() -> EmptyRes {
         from_id: 11111111,
         text: vec![],
         searchable_string: "".to_owned(),
+        topic_option: None,
+        text_history: vec![],
         typed: Some(message_regular! {
             edit_timestamp_option: None,
             is_deleted: false,
             forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
             reply_to_message_id_option: None,
             contents: vec![
                 content!(Video {
@@ -1124,6 +1287,57 @@ fn loading_2025_02() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_2025_06_service_messages() -> EmptyRes {
+    let res = resource("telegram_2025-06_service-messages");
+    LOADER.looks_about_right(&res)?;
+
+    let dao =
+        LOADER.load(&res, &client::NoChooser)?;
+
+    let cwm = &dao.cwms_single_ds()[0];
+    let msgs = &cwm.messages;
+    assert_eq!(msgs.len() as i32, 10);
+
+    let assert_notice = |msg: &Message, text: &str| {
+        assert_matches!(&msg.typed, Some(message_service_pat!(Notice(_))));
+        assert_eq!(msg.text, vec![RichText::make_plain(text.to_owned())]);
+    };
+
+    let topic = MessageTopic { id: 11201, title: "Cats".to_owned() };
+    assert_notice(&msgs[0], "Topic created: Cats");
+    assert_eq!(msgs[0].topic_option, Some(topic.clone()));
+    assert_eq!(msgs[1].text, vec![RichText::make_plain("Meow".to_owned())]);
+    assert_eq!(msgs[1].topic_option, Some(topic));
+    assert!(msgs[2..].iter().all(|m| m.topic_option.is_none()));
+    assert_matches!(&msgs[1].typed, Some(message::Typed::Regular(MessageRegular {
+        reply_to_message_id_option: Some(11201),
+        ..
+    })));
+    assert_notice(&msgs[2], "Topic renamed to Dogs");
+    assert_notice(&msgs[3], "Topic closed");
+
+    let scheduled = Local.timestamp_opt(1665600000, 0).unwrap().format("%Y-%m-%d %H:%M");
+    assert_notice(&msgs[4], &format!("Video chat scheduled for {scheduled}"));
+    assert_eq!(msgs[5].typed, Some(message_service!(PhoneCall(MessageServicePhoneCall {
+        duration_sec_option: None,
+        discard_reason_option: None,
+        members: vec![],
+    }))));
+    assert_eq!(msgs[6].typed, Some(message_service!(PhoneCall(MessageServicePhoneCall {
+        duration_sec_option: Some(3600),
+        discard_reason_option: None,
+        members: vec![],
+    }))));
+
+    assert_eq!(msgs[7].text, vec![RichText::make_plain("Invoice: Premium\nOne month\nAmount: 19.99 USD".to_owned())]);
+    assert_matches!(&msgs[7].typed, Some(message::Typed::Regular(_)));
+    assert_notice(&msgs[8], "Payment sent: 19.99 USD");
+    assert_notice(&msgs[9], "Now within 50 m from Bbbbb Bbbbbbbbbbb");
+
+    Ok(())
+}
+
 #[test]
 fn inline_bot_buttons() -> EmptyRes {
     let res = resource("telegram_2024-01_inline-bot-buttons");
@@ -1167,6 +1381,132 @@ fn inline_bot_buttons() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_2024_09_chunks() -> EmptyRes {
+    let res = resource("telegram_2024-09_chunks");
+    LOADER.looks_about_right(&res)?;
+
+    let dao =
+        LOADER.load(&res, &client::NoChooser)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    assert_eq!(dao.myself_single_ds().id, 11111111);
+    assert_eq!(dao.users_single_ds().iter().map(|u| u.id).collect_vec(), vec![11111111, 22222222, 33333333]);
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 2);
+
+    // Chat present in both chunks is stitched together, overlapping message is taken from the later chunk.
+    let cwm = &cwms[0];
+    assert_eq!(cwm.chat.ds_uuid, *ds_uuid);
+    assert_eq!(cwm.chat.name_option.as_deref(), Some("Bbbbb Bbbbbbbbbbb"));
+    assert_eq!(cwm.chat.member_ids, vec![11111111, 22222222]);
+    assert_eq!(cwm.chat.msg_count, 3);
+    assert_eq!(cwm.messages.iter().map(|m| (m.internal_id, m.source_id_option, m.searchable_string.as_str())).collect_vec(),
+               vec![(0, Some(1), "First"), (1, Some(2), "Second (edited)"), (2, Some(3), "Third")]);
+
+    // Chat present only in a later chunk is added as-is.
+    let cwm = &cwms[1];
+    assert_eq!(cwm.chat.name_option.as_deref(), Some("Ccccc Ccccccccccc"));
+    assert_eq!(cwm.chat.msg_count, 1);
+    assert_eq!(cwm.messages[0].source_id_option, Some(10));
+
+    Ok(())
+}
+
+#[test]
+fn loading_2024_10_forwarded_from_id() -> EmptyRes {
+    let res = resource("telegram_2024-10_forwarded-from-id");
+    LOADER.looks_about_right(&res)?;
+
+    let dao =
+        LOADER.load(&res, &client::NoChooser)?;
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 1);
+    let regulars = cwms[0].messages.iter()
+        .map(|m| coerce_enum!(m.typed(), Typed::Regular(mr) => mr))
+        .collect_vec();
+    let channel_id = 4444444444 - USER_ID_SHIFT;
+    assert_eq!(regulars.iter().map(|mr| mr.forward_from_id_option).collect_vec(),
+               vec![Some(33333333), Some(channel_id), None]);
+    assert_eq!(regulars.iter().map(|mr| mr.forward_origin_option()).collect_vec(), vec![
+        Some(ForwardOrigin::User(UserId(33333333))),
+        Some(ForwardOrigin::User(UserId(channel_id))),
+        Some(ForwardOrigin::Unknown),
+    ]);
+
+    Ok(())
+}
+
+#[test]
+fn loading_2024_10_profile_pictures() -> EmptyRes {
+    let res = resource("telegram_2024-10_profile-pictures");
+    LOADER.looks_about_right(&res)?;
+
+    let dao =
+        LOADER.load(&res, &client::NoChooser)?;
+
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.id, 11111111);
+    assert_eq!(myself.profile_pictures, vec![
+        ProfilePicture { path: "profile_pictures/photo_2@01-02-2021_08-15-00.jpg".to_owned(), frame_option: None },
+        ProfilePicture { path: "profile_pictures/photo_1@14-05-2019_21-29-03.jpg".to_owned(), frame_option: None },
+    ]);
+
+    // Interlocutor has no known avatar
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 1);
+    assert_eq!(cwms[0].chat.img_path_option, None);
+
+    Ok(())
+}
+
+#[test]
+fn chunks_ordering() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    for name in [RESULT_JSON, "messages10.json", "messages2.json", "result (3).json", "unrelated.json", "messages.json"] {
+        fs::write(tmp_dir.path.join(name), "{}")?;
+    }
+    let chunks = find_chunks(&tmp_dir.path.join(RESULT_JSON))?;
+    assert_eq!(chunks.iter().map(|p| path_file_name(p).unwrap()).collect_vec(),
+               vec!["messages2.json", "result (3).json", "messages10.json"]);
+    Ok(())
+}
+
+#[test]
+fn previewing_2020_01() -> EmptyRes {
+    use chat_history_manager_core::parsing::telegram::*;
+
+    let res = resource("telegram_2020-01");
+    let preview = preview_telegram_export(&fs::read_to_string(res.join("result.json"))?)?;
+    assert_eq!(preview.myself_name_option.as_deref(), Some("Aaaaa Aaaaaaaaaaa"));
+    assert_eq!(preview.chats.iter().map(|c| (c.id, c.tpe.as_str(), c.messages_count)).collect_vec(), vec![
+        (8123123123, "private_group", 31),
+        (9777777777, "personal_chat", 12),
+        (4321012345, "personal_chat", 5),
+        (9333333333, "personal_chat", 2),
+    ]);
+
+    // Chats are the same as the ones a full load yields
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+    let loaded_chats = dao.chats(&dao.ds_uuid())?;
+    assert_eq!(preview.chats.iter().map(|c| (c.id, c.name_option.clone())).sorted().collect_vec(),
+               loaded_chats.iter().map(|cwd| (cwd.chat.id, cwd.chat.name_option.clone())).sorted().collect_vec());
+
+    let chat = &preview.chats[1];
+    assert_eq!(chat.participants, vec!["Aaaaa Aaaaaaaaaaa", "Vvvvv Vvvvvvvvv"]);
+    assert_eq!(chat.media_paths, vec![
+        "chats/chat_01/stickers/sticker (100).webp",
+        "chats/chat_01/stickers/sticker (100).webp_thumb.jpg",
+        "chats/chat_02/stickers/sticker (8).webp",
+        "chats/chat_02/stickers/sticker (8).webp_thumb.jpg",
+    ]);
+    // This export predates unix timestamps
+    assert_eq!(chat.first_timestamp_option, None);
+    Ok(())
+}
+
 //
 // Helpers
 //
@@ -2,6 +2,8 @@ use std::fs;
 
 use rusqlite::Connection;
 
+use crate::utils::http_client::get_bytes_blocking;
+
 use super::*;
 use super::android::*;
 
@@ -71,7 +73,9 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
             for photo_url in photo_urls {
                 let (_, file_name) = photo_url.rsplit_once("/").unwrap();
                 // TODO: This can be downloaded in parallel, but slow running time isn't a big deal.
-                download_if_missing(file_name, &downloaded_media_path, &photo_url, self.http_client)?;
+                if !download_if_missing(file_name, &downloaded_media_path, &photo_url, self.http_client)? {
+                    continue;
+                }
                 profile_pictures.push(ProfilePicture {
                     path: format!("{RELATIVE_MEDIA_DIR}/{file_name}"),
                     frame_option: None,
@@ -121,30 +125,39 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
                 let from_id = if &row.get::<_, String>("from_id")? == key { user.id() } else { MYSELF_ID };
 
                 let text = row.get::<_, String>("text")?;
-                let (text, contents) = if text.starts_with("https://media.tenor.com/") {
-                    // This is a GIF, let's download it and include it as a sticker.
-                    // Example: https://media.tenor.com/mYFQztB4EHoAAAAM/house-hugh-laurie.gif?width=220&height=226
-                    let hash = hash_to_id(&text);
-                    let file_name = format!("{}.gif", hash);
-                    download_if_missing(&file_name, &downloaded_media_path, &text, self.http_client)?;
-                    let (width, height) = {
-                        let split = text.split(['?', '&']).skip(1).collect_vec();
-                        (split.iter().find(|s| s.starts_with("width=")).map(|s| s[6..].parse()).unwrap_or(Ok(0))?,
-                         split.iter().find(|s| s.starts_with("height=")).map(|s| s[7..].parse()).unwrap_or(Ok(0))?)
-                    };
-                    (vec![], vec![
-                        content!(Sticker {
-                            path_option: Some(format!("{RELATIVE_MEDIA_DIR}/{file_name}")),
-                            file_name_option: Some(file_name),
-                            width: width * 2,
-                            height: height * 2,
-                            mime_type_option: None,
-                            thumbnail_path_option: None,
-                            emoji_option: None,
-                        })
-                    ])
+                let is_gif = text.starts_with("https://media.tenor.com/");
+                // This is a GIF, let's download it and include it as a sticker.
+                // Example: https://media.tenor.com/mYFQztB4EHoAAAAM/house-hugh-laurie.gif?width=220&height=226
+                let gif_file_name_option = if is_gif {
+                    let file_name = format!("{}.gif", hash_to_id(&text));
+                    download_if_missing(&file_name, &downloaded_media_path, &text, self.http_client)?.then_some(file_name)
                 } else {
-                    (vec![RichText::make_plain(text)], vec![])
+                    None
+                };
+                let (text, contents) = match gif_file_name_option {
+                    Some(file_name) => {
+                        let (width, height) = {
+                            let split = text.split(['?', '&']).skip(1).collect_vec();
+                            (split.iter().find(|s| s.starts_with("width=")).map(|s| s[6..].parse()).unwrap_or(Ok(0))?,
+                             split.iter().find(|s| s.starts_with("height=")).map(|s| s[7..].parse()).unwrap_or(Ok(0))?)
+                        };
+                        (vec![], vec![
+                            content!(Sticker {
+                                path_option: Some(format!("{RELATIVE_MEDIA_DIR}/{file_name}")),
+                                file_name_option: Some(file_name),
+                                width: width * 2,
+                                height: height * 2,
+                                mime_type_option: None,
+                                thumbnail_path_option: None,
+                                emoji_option: None,
+                                pack_id_option: None,
+                                pack_name_option: None,
+                            })
+                        ])
+                    }
+                    // GIF couldn't be downloaded (e.g. in offline mode), keeping a link to it instead.
+                    None if is_gif => (vec![RichText::make_link(Some(text.clone()), text, false)], vec![]),
+                    None => (vec![RichText::make_plain(text)], vec![]),
                 };
 
                 messages.push(Message::new(
@@ -157,6 +170,8 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
                         edit_timestamp_option: None,
                         is_deleted: false,
                         forward_from_name_option: None,
+                        forward_from_id_option: None,
+                        ephemeral_duration_sec_option: None,
                         reply_to_message_id_option: None,
                         contents,
                     },
@@ -175,6 +190,8 @@ impl<H: HttpClient> AndroidDataLoader for TinderAndroidDataLoader<'_, H> {
                     member_ids: vec![*MYSELF_ID, user.id],
                     msg_count: messages.len() as i32,
                     main_chat_id: None,
+                    archived: None,
+                    hidden: None,
                 },
                 messages,
             });
@@ -296,11 +313,14 @@ fn analyze_photos_blob(user_key: &UserKey, bytes: Vec<u8>) -> Result<Vec<String>
     Ok(photos)
 }
 
-fn download_if_missing(file_name: &str, storage_path: &Path, url: &str, http_client: &impl HttpClient) -> EmptyRes {
+/// Returns whether the file is present afterwards. Nothing is downloaded in offline mode.
+fn download_if_missing(file_name: &str, storage_path: &Path, url: &str, http_client: &impl HttpClient) -> Result<bool> {
     let file_path = storage_path.join(file_name);
-    if !file_path.exists() {
+    if !file_path.exists() && http_client.is_offline() {
+        log::info!("Offline mode is on, not downloading {}", url);
+    } else if !file_path.exists() {
         log::info!("Downloading {}", url);
-        match http_client.get_bytes(url) {
+        match get_bytes_blocking(http_client, url) {
             Ok(HttpResponse::Ok(body)) => {
                 fs::write(&file_path, body)?
             }
@@ -310,5 +330,5 @@ fn download_if_missing(file_name: &str, storage_path: &Path, url: &str, http_cli
                 log::warn!("Failed to download {file_name}: {}", e),
         }
     }
-    Ok(())
+    Ok(file_path.exists())
 }
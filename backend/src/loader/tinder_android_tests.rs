@@ -7,7 +7,7 @@ use chrono::prelude::*;
 use lazy_static::lazy_static;
 use pretty_assertions::{assert_eq, assert_ne};
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::entity_utils::*;
 use crate::protobuf::history::content::SealedValueOptional::*;
 use crate::protobuf::history::message::*;
@@ -63,6 +63,8 @@ fn loading_2023_11() -> EmptyRes {
             member_ids: vec![myself.id, member.id],
             msg_count: 2,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -75,6 +77,8 @@ fn loading_2023_11() -> EmptyRes {
             from_id: myself.id,
             text: vec![RichText::make_plain("Sending you a text!".to_owned())],
             searchable_string: "Sending you a text!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
         assert_eq!(msgs[1], Message {
@@ -84,10 +88,14 @@ fn loading_2023_11() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Sticker {
@@ -98,6 +106,8 @@ fn loading_2023_11() -> EmptyRes {
                         mime_type_option: None,
                         thumbnail_path_option: None,
                         emoji_option: None,
+                        pack_id_option: None,
+                        pack_name_option: None,
                     })
                 ],
             }),
@@ -157,10 +167,13 @@ fn loading_2024_07_photos() -> EmptyRes {
             name_option: Some("Abcde".to_owned()),
             source_type: SourceType::TinderDb as i32,
             tpe: ChatType::Personal as i32,
-            img_path_option: None,
+            // Falls back to member avatar
+            img_path_option: Some(member.profile_pictures[0].path.clone()),
             member_ids: vec![myself.id, member.id],
             msg_count: 1,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -173,6 +186,8 @@ fn loading_2024_07_photos() -> EmptyRes {
             from_id: myself.id,
             text: vec![RichText::make_plain("Sending you a text!".to_owned())],
             searchable_string: "Sending you a text!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
     }
@@ -185,6 +200,29 @@ fn loading_2024_07_photos() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_offline() -> EmptyRes {
+    let http_client = MockHttpClient::new_offline();
+    let loader = TinderAndroidDataLoader { http_client: &http_client };
+
+    let (res, db_dir) = test_android::create_databases(RESOURCE_DIR, "2023-11", ".db", DB_FILENAME);
+    let _media_dir = TmpDir::new_at(db_dir.path.parent().unwrap().join(MEDIA_DIR));
+    let dao = loader.load(&res, &client::NoChooser)?;
+    let chat = dao.cwms_single_ds().remove(0).chat;
+    let msgs = dao.first_messages(&chat, 99999)?;
+    let gif_url = "https://media.tenor.com/mYFQztB4EHoAAAAC/house-hugh-laurie.gif?width=271&height=279";
+    assert_eq!(msgs[1].text, vec![RichText::make_link(Some(gif_url.to_owned()), gif_url.to_owned(), false)]);
+    assert_eq!(msgs[1].typed(), &*MESSAGE_REGULAR_NO_CONTENT);
+
+    let (res, db_dir) = test_android::create_databases(RESOURCE_DIR, "2024-07_photos", ".db", DB_FILENAME);
+    let _media_dir = TmpDir::new_at(db_dir.path.parent().unwrap().join(MEDIA_DIR));
+    let dao = loader.load(&res, &client::NoChooser)?;
+    assert!(dao.users_single_ds().iter().all(|u| u.profile_pictures.is_empty()));
+
+    assert!(http_client.calls_copy().is_empty());
+    Ok(())
+}
+
 //
 // Helpers
 //
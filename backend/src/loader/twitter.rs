@@ -0,0 +1,442 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::DateTime;
+use itertools::Itertools;
+use simd_json::borrowed::Object;
+use simd_json::BorrowedValue;
+use simd_json::prelude::*;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::loader::DataLoader;
+use crate::prelude::*;
+use crate::protobuf::history::message_service::SealedValueOptional::*;
+
+#[cfg(test)]
+#[path = "twitter_tests.rs"]
+mod tests;
+
+const DATA_DIR: &str = "data";
+
+const DIRECT_MESSAGES_JS: &str = "direct-messages.js";
+const DIRECT_MESSAGES_GROUP_JS: &str = "direct-messages-group.js";
+const ACCOUNT_JS: &str = "account.js";
+const TWEETS_JS: &[&str] = &["tweets.js", "tweet.js"];
+
+const DIRECT_MESSAGES_MEDIA_DIR: &str = "direct_messages_media";
+const DIRECT_MESSAGES_GROUP_MEDIA_DIR: &str = "direct_messages_group_media";
+
+const DIRECT_MESSAGES_PREFIX: &str = "window.YTD.direct_messages.";
+
+/// Loader for Twitter/X archive (unpacked), takes `data/direct-messages.js` or archive root folder.
+///
+/// Archive only has numeric IDs for DM participants, names are resolved from tweet mentions where possible.
+pub struct TwitterDataLoader;
+
+impl DataLoader for TwitterDataLoader {
+    fn name(&self) -> String { "Twitter".to_owned() }
+
+    fn looks_about_right_inner(&self, src_path: &Path) -> EmptyRes {
+        let path = get_real_path(src_path);
+        if !path.exists() {
+            bail!("File is not {DIRECT_MESSAGES_JS} or archive folder");
+        }
+        if !super::first_line(&path)?.starts_with(DIRECT_MESSAGES_PREFIX) {
+            bail!("{DIRECT_MESSAGES_JS} does not start with a DM header");
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, path: &Path, ds: Dataset, _user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+        parse_twitter_archive(path, ds)
+    }
+}
+
+fn get_real_path(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(DATA_DIR).join(DIRECT_MESSAGES_JS)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn parse_twitter_archive(path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+    let path = get_real_path(path);
+    let data_path = path.parent().unwrap();
+
+    let mut users = Users::default();
+    let myself_id = parse_account(&data_path.join(ACCOUNT_JS), &ds.uuid, &mut users)?;
+    for tweets_filename in TWEETS_JS {
+        let tweets_path = data_path.join(tweets_filename);
+        if tweets_path.exists() {
+            parse_tweet_mentions(&tweets_path, &ds.uuid, &mut users)?;
+        }
+    }
+
+    let mut cwms = vec![];
+    cwms.extend(parse_dm_file(&path, data_path, DIRECT_MESSAGES_MEDIA_DIR, &ds.uuid, myself_id, &mut users)?);
+
+    let group_path = data_path.join(DIRECT_MESSAGES_GROUP_JS);
+    if group_path.exists() {
+        cwms.extend(parse_dm_file(&group_path, data_path, DIRECT_MESSAGES_GROUP_MEDIA_DIR, &ds.uuid, myself_id, &mut users)?);
+    }
+
+    let mut users = users.id_to_user.into_values().collect_vec();
+
+    // Set myself to be a first member.
+    users.sort_by_key(|u| if u.id == *myself_id { *UserId::MIN } else { u.id });
+
+    let archive_name = data_path.parent().map(path_file_name).transpose()?.unwrap_or(DATA_DIR);
+    let mut result = Box::new(InMemoryDao::new_single(
+        format!("Twitter ({})", archive_name),
+        ds,
+        data_path.to_path_buf(),
+        myself_id,
+        users,
+        cwms,
+    ));
+    // Users mentioned in tweets might not participate in any DM
+    result.remove_orphan_users();
+    Ok(result)
+}
+
+#[derive(Default)]
+struct Users {
+    id_to_user: HashMap<UserId, User, Hasher>,
+}
+
+impl Users {
+    /// Registers a user with only ID known, unless already present.
+    fn ensure_present(&mut self, ds_uuid: &PbUuid, id: UserId) {
+        self.id_to_user.entry(id).or_insert_with(|| User {
+            ds_uuid: ds_uuid.clone(),
+            id: *id,
+            ..Default::default()
+        });
+    }
+}
+
+/// Twitter archive files are JS files of a form `window.YTD.<name>.part0 = [ ... ]`,
+/// this strips the assignment and leaves a JSON content.
+fn read_js_as_json(path: &Path) -> Result<Vec<u8>> {
+    let mut content = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let eq_pos = content.iter().position(|&b| b == b'=')
+        .with_context(|| format!("{} is not a Twitter archive JS file", path.display()))?;
+    content.drain(..=eq_pos);
+    Ok(content)
+}
+
+fn parse_id(s: &str, json_path: &str) -> Result<i64> {
+    s.parse::<i64>().with_context(|| format!("{json_path} is not a numeric ID: {s}"))
+}
+
+fn parse_timestamp(s: &str, json_path: &str) -> Result<Timestamp> {
+    let dt = DateTime::parse_from_rfc3339(s).with_context(|| format!("{json_path} is not a valid timestamp: {s}"))?;
+    Ok(Timestamp(dt.timestamp()))
+}
+
+fn parse_account(path: &Path, ds_uuid: &PbUuid, users: &mut Users) -> Result<UserId> {
+    let mut content = read_js_as_json(path)?;
+    let parsed = simd_json::to_borrowed_value(&mut content)?;
+    let entries = as_array!(parsed, "account");
+    let entry = entries.first().context("account.js is empty")?;
+    let account = get_field_object!(entry, "account[0]", "account");
+    let json_path = "account[0].account";
+
+    let id = UserId(parse_id(get_field_str!(account, json_path, "accountId"), json_path)?);
+    users.id_to_user.insert(id, User {
+        ds_uuid: ds_uuid.clone(),
+        id: *id,
+        first_name_option: as_string_option!(get_field!(account, json_path, "accountDisplayName")?, json_path, "accountDisplayName"),
+        last_name_option: None,
+        username_option: as_string_option!(get_field!(account, json_path, "username")?, json_path, "username"),
+        phone_number_option: None,
+        profile_pictures: vec![],
+    });
+    Ok(id)
+}
+
+/// Tweets mentions are the only place where archive specifies names of other users.
+fn parse_tweet_mentions(path: &Path, ds_uuid: &PbUuid, users: &mut Users) -> EmptyRes {
+    let mut content = read_js_as_json(path)?;
+    let parsed = simd_json::to_borrowed_value(&mut content)?;
+    for entry in as_array!(parsed, "tweets") {
+        let Some(mentions) = entry.get("tweet")
+            .and_then(|t| t.get("entities"))
+            .and_then(|e| e.get("user_mentions"))
+            .and_then(|m| m.as_array()) else { continue; };
+        for mention in mentions {
+            let json_path = "tweet.entities.user_mentions";
+            let id = UserId(parse_id(get_field_str!(mention, json_path, "id_str"), json_path)?);
+            if users.id_to_user.contains_key(&id) { continue; }
+            users.id_to_user.insert(id, User {
+                ds_uuid: ds_uuid.clone(),
+                id: *id,
+                first_name_option: get_field_string_option!(mention, json_path, "name"),
+                last_name_option: None,
+                username_option: get_field_string_option!(mention, json_path, "screen_name"),
+                phone_number_option: None,
+                profile_pictures: vec![],
+            });
+        }
+    }
+    Ok(())
+}
+
+fn parse_dm_file(path: &Path,
+                 data_path: &Path,
+                 media_dir: &str,
+                 ds_uuid: &PbUuid,
+                 myself_id: UserId,
+                 users: &mut Users) -> Result<Vec<ChatWithMessages>> {
+    let mut content = read_js_as_json(path)?;
+    let parsed = simd_json::to_borrowed_value(&mut content)?;
+    let mut result = vec![];
+    for entry in as_array!(parsed, "dms") {
+        let conversation = get_field_object!(entry, "dms", "dmConversation");
+        let cwm = parse_conversation(conversation, data_path, media_dir, ds_uuid, myself_id, users)?;
+        result.push(cwm);
+    }
+    Ok(result)
+}
+
+fn parse_conversation(conversation: &Object,
+                      data_path: &Path,
+                      media_dir: &str,
+                      ds_uuid: &PbUuid,
+                      myself_id: UserId,
+                      users: &mut Users) -> Result<ChatWithMessages> {
+    let conversation_id = get_field_str!(conversation, "dmConversation", "conversationId");
+    let json_path = format!("dmConversation[{conversation_id}]");
+
+    // Personal conversation IDs are "<user1>-<user2>", group ones are plain numbers.
+    let (chat_id, tpe) = match conversation_id.split_once('-') {
+        Some((id1, id2)) => {
+            let id1 = UserId(parse_id(id1, &json_path)?);
+            let id2 = UserId(parse_id(id2, &json_path)?);
+            let other_id = if id1 == myself_id { id2 } else { id1 };
+            // Using user ID as a chat ID
+            (*other_id, if other_id == myself_id { ChatType::SelfChat } else { ChatType::Personal })
+        }
+        None => (parse_id(conversation_id, &json_path)?, ChatType::PrivateGroup),
+    };
+
+    let mut member_ids: Vec<UserId> = vec![myself_id];
+    if tpe == ChatType::Personal {
+        member_ids.push(UserId(chat_id));
+    }
+
+    let mut title_option: Option<String> = None;
+    let mut messages = vec![];
+    for message_json in as_array!(get_field!(conversation, json_path, "messages")?, json_path, "messages") {
+        let (msg, msg_member_ids) = parse_message(message_json, &json_path, data_path, media_dir, users)?;
+        if let message_service_pat!(GroupEditTitle(MessageServiceGroupEditTitle { title })) = msg.typed() {
+            title_option = Some(title.clone());
+        }
+        for id in msg_member_ids {
+            if !member_ids.contains(&id) {
+                member_ids.push(id);
+            }
+        }
+        messages.push(msg);
+    }
+
+    // Archive lists messages newest first.
+    messages.sort_by_key(|m| (m.timestamp, m.source_id_option));
+    for (idx, m) in messages.iter_mut().enumerate() {
+        m.internal_id = idx as i64;
+    }
+
+    member_ids[1..].sort_by_key(|id| id.0);
+    for id in member_ids.iter() {
+        users.ensure_present(ds_uuid, *id);
+    }
+
+    let name_option = match tpe {
+        ChatType::Personal => users.id_to_user[&UserId(chat_id)].pretty_name_option(),
+        ChatType::PrivateGroup => title_option,
+        ChatType::SelfChat => None,
+    };
+
+    Ok(ChatWithMessages {
+        chat: Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: chat_id,
+            name_option,
+            source_type: SourceType::Twitter as i32,
+            tpe: tpe as i32,
+            img_path_option: None,
+            member_ids: member_ids.iter().map(|id| **id).collect_vec(),
+            msg_count: messages.len() as i32,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        },
+        messages,
+    })
+}
+
+/// Returns a message (with no internal ID set) and a list of IDs of users involved.
+fn parse_message(bw: &BorrowedValue,
+                 json_path: &str,
+                 data_path: &Path,
+                 media_dir: &str,
+                 users: &Users) -> Result<(Message, Vec<UserId>)> {
+    let obj = as_object!(bw, json_path, "messages");
+    let (key, value) = obj.iter().exactly_one().map_err(|_| anyhow!("{json_path} message has multiple keys"))?;
+    let json_path = format!("{json_path}.{key}");
+
+    let timestamp = parse_timestamp(get_field_str!(value, json_path, "createdAt"), &json_path)?;
+    let parse_user_ids = |field: &str| -> Result<Vec<UserId>> {
+        let mut ids = vec![];
+        for id in as_array!(get_field!(value, json_path, field)?, json_path, field) {
+            ids.push(UserId(parse_id(as_str!(id, json_path, field), &json_path)?));
+        }
+        Ok(ids)
+    };
+    let names_of = |ids: &[UserId]| -> Vec<String> {
+        ids.iter().map(|id| users.id_to_user.get(id).map(|u| u.pretty_name()).unwrap_or_else(|| id.0.to_string())).collect_vec()
+    };
+
+    let (from_id, source_id_option, text, typed, member_ids) = match key.as_ref() {
+        "messageCreate" => {
+            let from_id = UserId(parse_id(get_field_str!(value, json_path, "senderId"), &json_path)?);
+            let source_id = parse_id(get_field_str!(value, json_path, "id"), &json_path)?;
+            let (text, contents) = parse_message_create(value, &json_path, source_id, data_path, media_dir)?;
+            let typed = message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
+                reply_to_message_id_option: None,
+                contents,
+            };
+            (from_id, Some(source_id), text, typed, vec![from_id])
+        }
+        "joinConversation" => {
+            let from_id = UserId(parse_id(get_field_str!(value, json_path, "initiatingUserId"), &json_path)?);
+            let ids = parse_user_ids("participantsSnapshot")?;
+            let typed = message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
+                members: names_of(&ids)
+            }));
+            (from_id, None, vec![], typed, [vec![from_id], ids].concat())
+        }
+        "participantsJoin" => {
+            let from_id = UserId(parse_id(get_field_str!(value, json_path, "initiatingUserId"), &json_path)?);
+            let ids = parse_user_ids("userIds")?;
+            let typed = message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
+                members: names_of(&ids)
+            }));
+            (from_id, None, vec![], typed, [vec![from_id], ids].concat())
+        }
+        "participantsLeave" => {
+            let ids = parse_user_ids("userIds")?;
+            let from_id = *ids.first().with_context(|| format!("{json_path}.userIds is empty"))?;
+            let typed = message_service!(GroupRemoveMembers(MessageServiceGroupRemoveMembers {
+                members: names_of(&ids)
+            }));
+            (from_id, None, vec![], typed, ids)
+        }
+        "conversationNameUpdate" => {
+            let from_id = UserId(parse_id(get_field_str!(value, json_path, "initiatingUserId"), &json_path)?);
+            let typed = message_service!(GroupEditTitle(MessageServiceGroupEditTitle {
+                title: get_field_string!(value, json_path, "name")
+            }));
+            (from_id, None, vec![], typed, vec![from_id])
+        }
+        other => bail!("Unknown message type: {json_path}.{other}")
+    };
+
+    let msg = Message::new(
+        *NO_INTERNAL_ID,
+        source_id_option,
+        *timestamp,
+        from_id,
+        text,
+        typed,
+    );
+    Ok((msg, member_ids))
+}
+
+fn parse_message_create(value: &BorrowedValue,
+                        json_path: &str,
+                        source_id: i64,
+                        data_path: &Path,
+                        media_dir: &str) -> Result<(Vec<RichTextElement>, Vec<Content>)> {
+    let text = unescape_html(get_field_str!(value, json_path, "text"));
+
+    let mut contents = vec![];
+    for media_url in as_array!(get_field!(value, json_path, "mediaUrls")?, json_path, "mediaUrls") {
+        let media_url = as_str!(media_url, json_path, "mediaUrls");
+        contents.push(parse_media(media_url, source_id, data_path, media_dir));
+    }
+
+    // Shortened URLs within a text are either links or references to attached media.
+    let mut rtes = vec![];
+    let mut remaining: &str = &text;
+    for url_json in as_array!(get_field!(value, json_path, "urls")?, json_path, "urls") {
+        let url = get_field_str!(url_json, json_path, "url");
+        let Some(pos) = remaining.find(url) else { continue; };
+        let expanded = get_field_str!(url_json, json_path, "expanded");
+        let is_media = expanded.contains("/messages/media/");
+        let before = if is_media { remaining[..pos].trim_end() } else { &remaining[..pos] };
+        if !before.is_empty() {
+            rtes.push(RichText::make_plain(before.to_owned()));
+        }
+        if !is_media {
+            let display = get_field_string_option!(url_json, json_path, "display");
+            rtes.push(RichText::make_link(display, expanded.to_owned(), false));
+        }
+        remaining = &remaining[(pos + url.len())..];
+    }
+    if !remaining.trim().is_empty() {
+        rtes.push(RichText::make_plain(remaining.to_owned()));
+    }
+
+    Ok((rtes, contents))
+}
+
+/// Media files are stored as `<message_id>-<filename>`, filename being the last URL path segment.
+fn parse_media(media_url: &str, source_id: i64, data_path: &Path, media_dir: &str) -> Content {
+    let url_path = media_url.split(['?', '#']).next().unwrap();
+    let filename = url_path.rsplit('/').next().unwrap();
+    let rel_path = format!("{media_dir}/{source_id}-{filename}");
+    let path_option = if data_path.join(&rel_path).exists() { Some(rel_path) } else { None };
+    let ext = filename.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+
+    match ext.as_str() {
+        "jpg" | "jpeg" | "png" | "webp" => content!(Photo {
+            path_option,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        }),
+        "mp4" => content!(Video {
+            path_option,
+            file_name_option: Some(filename.to_owned()),
+            title_option: None,
+            performer_option: None,
+            width: 0,
+            height: 0,
+            mime_type: "video/mp4".to_owned(),
+            duration_sec_option: None,
+            thumbnail_path_option: None,
+            is_one_time: false,
+        }),
+        _ => content!(File {
+            path_option,
+            file_name_option: Some(filename.to_owned()),
+            mime_type_option: None,
+            thumbnail_path_option: None,
+        }),
+    }
+}
+
+fn unescape_html(s: &str) -> String {
+    s.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
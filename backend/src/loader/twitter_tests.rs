@@ -0,0 +1,224 @@
+#![allow(unused_imports)]
+
+use chrono::prelude::*;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::ChatHistoryReader;
+use crate::entity_utils::*;
+use crate::protobuf::history::content::SealedValueOptional::*;
+use crate::protobuf::history::message::*;
+use crate::protobuf::history::message_service::SealedValueOptional::*;
+
+use super::*;
+
+const LOADER: TwitterDataLoader = TwitterDataLoader;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2024_03() -> EmptyRes {
+    let res = resource("twitter_2024-03");
+    LOADER.looks_about_right(&res)?;
+    LOADER.looks_about_right(&res.join(DATA_DIR).join(DIRECT_MESSAGES_JS))?;
+
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself, User {
+        ds_uuid: ds_uuid.clone(),
+        id: 1000,
+        first_name_option: Some("Me Myself".to_owned()),
+        last_name_option: None,
+        username_option: Some("my_username".to_owned()),
+        phone_number_option: None,
+        profile_pictures: vec![],
+    });
+
+    let friend = User {
+        ds_uuid: ds_uuid.clone(),
+        id: 2000,
+        first_name_option: Some("Friend Name".to_owned()),
+        last_name_option: None,
+        username_option: Some("friend_username".to_owned()),
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+    let other = User {
+        ds_uuid: ds_uuid.clone(),
+        id: 3000,
+        first_name_option: Some("Other Name".to_owned()),
+        last_name_option: None,
+        username_option: Some("other_username".to_owned()),
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+    // Not mentioned in tweets, so name is unknown
+    let stranger = User {
+        ds_uuid: ds_uuid.clone(),
+        id: 4000,
+        first_name_option: None,
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+
+    let mut users = dao.users_single_ds();
+    users.sort_by_key(|u| u.id);
+    assert_eq!(users, vec![myself.clone(), friend.clone(), other.clone(), stranger.clone()]);
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 3);
+
+    // Personal chat with a friend
+    {
+        let cwm = cwms.iter().find(|cwm| cwm.chat.id == friend.id).unwrap();
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: friend.id,
+            name_option: Some("Friend Name".to_owned()),
+            source_type: SourceType::Twitter as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, friend.id],
+            msg_count: 3,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs.len(), 3);
+
+        assert_eq!(msgs[0], Message::new(
+            0,
+            Some(1001),
+            dt("2024-03-01 12:00:00", Some(&Utc.fix())).timestamp(),
+            myself.id(),
+            vec![RichText::make_plain("Hi there!\nHow are you?".to_owned())],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![],
+            },
+        ));
+
+        assert_eq!(msgs[1], Message::new(
+            1,
+            Some(1002),
+            dt("2024-03-01 12:01:00", Some(&Utc.fix())).timestamp(),
+            myself.id(),
+            vec![RichText::make_plain("Look at this photo".to_owned())],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(Photo {
+                    path_option: Some("direct_messages_media/1002-abcd.jpg".to_owned()),
+                    width: 0,
+                    height: 0,
+                    mime_type_option: None,
+                    is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
+                })],
+            },
+        ));
+
+        assert_eq!(msgs[2], Message::new(
+            2,
+            Some(1003),
+            dt("2024-03-01 12:02:00", Some(&Utc.fix())).timestamp(),
+            friend.id(),
+            vec![
+                RichText::make_plain("Check this out ".to_owned()),
+                RichText::make_link(Some("example.com/page".to_owned()), "https://example.com/page".to_owned(), false),
+            ],
+            message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![],
+            },
+        ));
+    }
+
+    // Personal chat with a stranger, media file is missing
+    {
+        let cwm = cwms.iter().find(|cwm| cwm.chat.id == stranger.id).unwrap();
+        assert_eq!(cwm.chat.name_option, None);
+        assert_eq!(cwm.chat.member_ids, vec![myself.id, stranger.id]);
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs.len(), 1);
+        let typed = coerce_enum!(msgs[0].typed(), Typed::Regular(r) => r);
+        assert_eq!(typed.contents, vec![content!(Video {
+            path_option: None,
+            file_name_option: Some("efgh.mp4".to_owned()),
+            title_option: None,
+            performer_option: None,
+            width: 0,
+            height: 0,
+            mime_type: "video/mp4".to_owned(),
+            duration_sec_option: None,
+            thumbnail_path_option: None,
+            is_one_time: false,
+        })]);
+    }
+
+    // Group chat
+    {
+        let cwm = cwms.iter().find(|cwm| cwm.chat.id == 9000).unwrap();
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: 9000,
+            name_option: Some("Best group".to_owned()),
+            source_type: SourceType::Twitter as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, friend.id, other.id],
+            msg_count: 4,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs.len(), 4);
+
+        assert_eq!(msgs[0].from_id, friend.id);
+        assert_eq!(msgs[0].source_id_option, None);
+        assert_eq!(msgs[0].typed(), &message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
+            members: vec!["Me Myself".to_owned(), "Friend Name".to_owned(), "Other Name".to_owned()]
+        })));
+
+        assert_eq!(msgs[1].from_id, other.id);
+        assert_eq!(msgs[1].source_id_option, Some(2001));
+
+        assert_eq!(msgs[2].from_id, myself.id);
+        assert_eq!(msgs[2].typed(), &message_service!(GroupEditTitle(MessageServiceGroupEditTitle {
+            title: "Best group".to_owned()
+        })));
+
+        assert_eq!(msgs[3].from_id, other.id);
+        assert_eq!(msgs[3].typed(), &message_service!(GroupRemoveMembers(MessageServiceGroupRemoveMembers {
+            members: vec!["Other Name".to_owned()]
+        })));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use std::fs;
+
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "vcard_tests.rs"]
+mod tests;
+
+pub use chat_history_manager_core::parsing::vcard::parse_vcard;
+
+/// For every shared contact whose vCard file is present in a freshly loaded dataset,
+/// fill in the missing name, extra phone numbers and e-mails from it.
+pub fn complete_shared_contacts(dao: &mut InMemoryDao) -> EmptyRes {
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Shared contacts can only be completed for a single dataset"))?.uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+
+    let mut num_contacts = 0;
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        for msg in cwm.messages.iter_mut() {
+            let mut changed = false;
+            let Some(message::Typed::Regular(mr)) = msg.typed.as_mut() else { continue };
+            for content in mr.contents.iter_mut() {
+                use content::SealedValueOptional::SharedContact;
+                let Some(SharedContact(contact)) = content.sealed_value_optional.as_mut() else { continue };
+                let Some(path) = contact.vcard_path_option.as_ref().map(|p| ds_root.to_absolute(p)) else { continue };
+                if !path.exists() { continue; }
+                let parsed = match fs::read_to_string(&path).map_err(|e| e.into()).and_then(|s| parse_vcard(&s)) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        log::warn!("Couldn't parse vCard {}: {e}", path.display());
+                        continue;
+                    }
+                };
+                changed |= merge_contact(contact, parsed);
+            }
+            if changed {
+                msg.searchable_string = make_searchable_string(&msg.text, msg.typed());
+                num_contacts += 1;
+            }
+        }
+    }
+    log::info!("Completed {num_contacts} shared contacts from vCards");
+    Ok(())
+}
+
+/// Returns whether anything has changed.
+fn merge_contact(contact: &mut ContentSharedContact, parsed: ContentSharedContact) -> bool {
+    let old = contact.clone();
+    if contact.first_name_option.is_none() && contact.last_name_option.is_none() {
+        contact.first_name_option = parsed.first_name_option;
+        contact.last_name_option = parsed.last_name_option;
+    }
+    if let Some(parsed_phones) = parsed.phone_number_option {
+        let digits = |s: &str| s.chars().filter(|c| c.is_ascii_digit()).collect::<String>();
+        let mut phones = contact.phone_number_option.iter()
+            .flat_map(|s| s.split(", "))
+            .map(|s| s.to_owned())
+            .collect_vec();
+        for phone in parsed_phones.split(", ") {
+            if !phones.iter().any(|p| digits(p) == digits(phone)) {
+                phones.push(phone.to_owned());
+            }
+        }
+        contact.phone_number_option = Some(phones.join(", "));
+    }
+    for email in parsed.emails {
+        if !contact.emails.contains(&email) {
+            contact.emails.push(email);
+        }
+    }
+    *contact != old
+}
@@ -0,0 +1,123 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn vcards() -> EmptyRes {
+    fn parse(vcard_string: &str) -> Result<ContentSharedContact> {
+        parse_vcard(&trim_vcard_string(vcard_string))
+    }
+    fn vc(first_name: &str, phone: &str) -> ContentSharedContact {
+        ContentSharedContact {
+            first_name_option: Some(first_name.to_owned()),
+            last_name_option: None,
+            phone_number_option: Some(phone.to_owned()),
+            vcard_path_option: None,
+            emails: vec![],
+        }
+    }
+
+    assert_eq!(parse(r"
+        BEGIN:VCARD
+        VERSION:3.0
+        N:;Name (comment);;;
+        FN:Name (comment)
+        TEL;type=Mobile;waid=112223456543:+11 222-3456-543
+        END:VCARD
+    ")?, vc("Name (comment)", "+11 222-3456-543"));
+
+    assert_eq!(parse(r"
+        BEGIN:VCARD
+        VERSION:3.0
+        N:Name3;Name1;Name2;;
+        FN:Name1 Name2 Name3
+        TEL;type=Home:+12 345-6789-8765
+        TEL;type=Mobile;waid=9876543212345:+98 765-4321-2345
+        END:VCARD
+    ")?, vc("Name1 Name2 Name3", "+98 765-4321-2345, +12 345-6789-8765"));
+
+    assert_eq!(parse(r"
+        BEGIN:VCARD
+        VERSION:3.0
+        N:;+11 222-3333-4444;;;
+        FN:+11 222-3333-4444
+        TEL;type=CELL;waid=1122233334444:+11 222-3333-4444
+        X-WA-BIZ-NAME:+11 222-3333-4444
+        X-WA-BIZ-DESCRIPTION:My Fancy Description!
+        END:VCARD
+    ")?, vc("+11 222-3333-4444", "+11 222-3333-4444"));
+
+    assert_eq!(parse(r"
+        BEGIN:VCARD
+        VERSION:3.0
+        N:Name;Full;;;
+        FN:Full Name
+        item1.TEL;waid=1122233334444:+11 222-3333-4444
+        item1.X-ABLabel:Ponsel
+        X-WA-BIZ-DESCRIPTION:My Fancy Description!
+        X-WA-BIZ-NAME:Full Name
+        END:VCARD
+    ")?, vc("Full Name", "+11 222-3333-4444"));
+
+    Ok(())
+}
+
+#[test]
+fn vcards_without_full_name() -> EmptyRes {
+    assert_eq!(parse_vcard(&trim_vcard_string(r"
+        BEGIN:VCARD
+        VERSION:3.0
+        N:Doe;John;Q;;
+        FN:
+        TEL;TYPE=CELL:+1 555 0100
+        TEL;TYPE=HOME:+1 555 0199
+        EMAIL;TYPE=INTERNET:john@example.com
+        item2.EMAIL:jdoe@example.org
+        END:VCARD
+    "))?, ContentSharedContact {
+        first_name_option: Some("John Q".to_owned()),
+        last_name_option: Some("Doe".to_owned()),
+        phone_number_option: Some("+1 555 0100, +1 555 0199".to_owned()),
+        vcard_path_option: None,
+        emails: vec!["john@example.com".to_owned(), "jdoe@example.org".to_owned()],
+    });
+    Ok(())
+}
+
+#[test]
+fn merging() {
+    let mut contact = ContentSharedContact {
+        first_name_option: None,
+        last_name_option: None,
+        phone_number_option: Some("+998 90 999-00-99".to_owned()),
+        vcard_path_option: Some("contact.vcf".to_owned()),
+        emails: vec![],
+    };
+    assert!(merge_contact(&mut contact, ContentSharedContact {
+        first_name_option: Some("John".to_owned()),
+        last_name_option: None,
+        phone_number_option: Some("+998909990099, +998 90 1112233".to_owned()),
+        vcard_path_option: None,
+        emails: vec!["john@example.com".to_owned()],
+    }));
+    assert_eq!(contact, ContentSharedContact {
+        first_name_option: Some("John".to_owned()),
+        last_name_option: None,
+        phone_number_option: Some("+998 90 999-00-99, +998 90 1112233".to_owned()),
+        vcard_path_option: Some("contact.vcf".to_owned()),
+        emails: vec!["john@example.com".to_owned()],
+    });
+    assert!(!merge_contact(&mut contact.clone(), contact));
+}
+
+//
+// Helpers
+//
+
+fn trim_vcard_string(s: &str) -> String {
+    s.trim().lines().map(|s| s.trim()).join("\n")
+}
@@ -0,0 +1,308 @@
+use std::path::Path;
+
+use itertools::Itertools;
+use num_traits::FromPrimitive;
+use rusqlite::Connection;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::loader::{hash_to_id, DataLoader};
+use crate::prelude::*;
+use crate::protobuf::history::message_service::SealedValueOptional::*;
+
+#[cfg(test)]
+#[path = "viber_desktop_tests.rs"]
+mod tests;
+
+/// Loader for Viber Desktop database `viber.db`, which is stored in `ViberPC/<phone number>` folder
+/// of the application data.
+///
+/// Media files are not stored alongside the database, they're looked up by their file names in
+/// `ViberDownloads` folder next to `viber.db`, while stickers are looked up as `Stickers/<sticker ID>.png`.
+pub struct ViberDesktopDataLoader;
+
+const NAME: &str = "Viber";
+
+pub const DB_FILENAME: &str = "viber.db";
+
+const DOWNLOADS_DIR: &str = "ViberDownloads";
+const STICKERS_DIR: &str = "Stickers";
+
+/// `Events.Direction` value of messages and calls initiated by myself.
+const DIRECTION_OUTGOING: i32 = 1;
+
+/// Known values of `Messages.Type`.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+enum MessageType {
+    Text = 1,
+    Picture = 2,
+    Video = 3,
+    Sticker = 4,
+    File = 10,
+}
+
+impl DataLoader for ViberDesktopDataLoader {
+    fn name(&self) -> String { NAME.to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        if path_file_name(path)? != DB_FILENAME {
+            bail!("File is not {DB_FILENAME}")
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, path: &Path, ds: Dataset, _user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+        let root = path.parent().unwrap();
+        let conn = Connection::open(path)?;
+
+        // Account folder is named after own phone number
+        let account_name = path_file_name(root)?;
+        let users = parse_users(&conn, &ds.uuid, account_name)?;
+        let cwms = parse_cwms(&conn, &ds.uuid, root, &users)?;
+
+        let myself_id = users.myself_id;
+        let mut users = users.by_id.into_values().collect_vec();
+        users.sort_by_key(|u| if u.id == *myself_id { *UserId::MIN } else { u.id });
+
+        Ok(Box::new(InMemoryDao::new_single(
+            format!("{NAME} ({account_name})"),
+            ds,
+            root.to_path_buf(),
+            myself_id,
+            users,
+            cwms,
+        )))
+    }
+}
+
+struct Users {
+    myself_id: UserId,
+    by_id: HashMap<UserId, User>,
+}
+
+impl Users {
+    fn get(&self, id: UserId) -> Result<&User> {
+        self.by_id.get(&id).with_context(|| format!("Unknown contact {}", *id))
+    }
+}
+
+/// Own contact is the one with account phone number, if there's none - it's made up.
+fn parse_users(conn: &Connection, ds_uuid: &PbUuid, account_name: &str) -> Result<Users> {
+    let digits = |s: &str| s.chars().filter(|c| c.is_ascii_digit()).collect::<String>();
+    let account_digits = digits(account_name);
+
+    let mut by_id = HashMap::new();
+    let mut myself_id_option = None;
+
+    let mut stmt = conn.prepare(r"SELECT * FROM Contact")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let id = UserId(row.get("ContactID")?);
+        // Name from the address book takes precedence over the name from Viber profile
+        let first_name_option = row.get::<_, Option<String>>("Name")?
+            .or(row.get::<_, Option<String>>("ClientName")?)
+            .filter(|s| !s.is_empty());
+        let phone_number_option = row.get::<_, Option<String>>("Number")?.filter(|s| !s.is_empty());
+
+        if !account_digits.is_empty() && phone_number_option.as_deref().is_some_and(|n| digits(n) == account_digits) {
+            myself_id_option = Some(id);
+        }
+
+        ensure!(by_id.insert(id, User {
+            ds_uuid: ds_uuid.clone(),
+            id: *id,
+            first_name_option,
+            last_name_option: None,
+            username_option: None,
+            phone_number_option,
+            profile_pictures: vec![],
+        }).is_none(), "Duplicate contact ID {}", *id);
+    }
+
+    let myself_id = match myself_id_option {
+        Some(id) => id,
+        None => {
+            let id = UserId(hash_to_id(account_name));
+            by_id.insert(id, User {
+                ds_uuid: ds_uuid.clone(),
+                id: *id,
+                first_name_option: Some("Me".to_owned()),
+                last_name_option: None,
+                username_option: None,
+                phone_number_option: Some(format!("+{account_digits}")).filter(|_| !account_digits.is_empty()),
+                profile_pictures: vec![],
+            });
+            id
+        }
+    };
+
+    Ok(Users { myself_id, by_id })
+}
+
+fn parse_cwms(conn: &Connection, ds_uuid: &PbUuid, root: &Path, users: &Users) -> Result<Vec<ChatWithMessages>> {
+    let mut cwms = vec![];
+
+    let mut chat_stmt = conn.prepare(r"SELECT * FROM ChatInfo ORDER BY ChatID ASC")?;
+    let mut members_stmt = conn.prepare(r"SELECT ContactID FROM ChatRelation WHERE ChatID = ? ORDER BY ContactID ASC")?;
+    let mut events_stmt = conn.prepare(r"
+        SELECT
+            e.EventID, e.TimeStamp, e.Direction, e.ContactID,
+            m.Type AS MessageType, m.Body, m.PayloadPath, m.StickerID, m.Duration,
+            c.EventID AS CallEventID, c.Duration AS CallDuration
+        FROM Events e
+        LEFT JOIN Messages m ON m.EventID = e.EventID
+        LEFT JOIN Calls c ON c.EventID = e.EventID
+        WHERE e.ChatID = ?
+        ORDER BY e.TimeStamp ASC, e.EventID ASC
+    ")?;
+
+    let mut chat_rows = chat_stmt.query([])?;
+    while let Some(row) = chat_rows.next()? {
+        let chat_id: i64 = row.get("ChatID")?;
+        let name_option = row.get::<_, Option<String>>("Name")?.filter(|s| !s.is_empty());
+
+        let member_ids: Vec<UserId> = members_stmt.query_map([chat_id], |row| row.get(0).map(UserId))?
+            .try_collect()?;
+        let member_ids = member_ids.into_iter().filter(|id| *id != users.myself_id).collect_vec();
+        for id in member_ids.iter() {
+            users.get(*id)?;
+        }
+
+        // 1-on-1 chats have no name of their own
+        let (name_option, tpe) = match (name_option, member_ids.as_slice()) {
+            (None, [member_id]) => (users.get(*member_id)?.pretty_name_option(), ChatType::Personal),
+            (name_option, _) => (name_option, ChatType::PrivateGroup),
+        };
+
+        let mut messages = vec![];
+        let mut event_rows = events_stmt.query([chat_id])?;
+        while let Some(row) = event_rows.next()? {
+            let source_id: i64 = row.get("EventID")?;
+            let timestamp = row.get::<_, i64>("TimeStamp")? / 1000;
+            let from_id = if row.get::<_, i32>("Direction")? == DIRECTION_OUTGOING {
+                users.myself_id
+            } else {
+                let id = UserId(row.get("ContactID")?);
+                users.get(id)?;
+                id
+            };
+
+            let (text, typed) = if row.get::<_, Option<i64>>("CallEventID")?.is_some() {
+                // Zero duration means nobody picked up
+                let duration_sec = row.get::<_, Option<i32>>("CallDuration")?.unwrap_or(0);
+                let discard_reason = if duration_sec > 0 { "hangup" } else { "missed" };
+                (vec![], message_service!(PhoneCall(MessageServicePhoneCall {
+                    duration_sec_option: Some(duration_sec).filter(|d| *d > 0),
+                    discard_reason_option: Some(discard_reason.to_owned()),
+                    members: vec![],
+                })))
+            } else if let Some(msg_tpe) = row.get::<_, Option<i32>>("MessageType")? {
+                let msg_tpe = FromPrimitive::from_i32(msg_tpe).with_context(|| format!("Unknown message type ID: {msg_tpe}"))?;
+                let text = match row.get::<_, Option<String>>("Body")? {
+                    Some(body) if !body.is_empty() => vec![RichText::make_plain(body)],
+                    _ => vec![],
+                };
+
+                let payload_file_name_option = row.get::<_, Option<String>>("PayloadPath")?
+                    .and_then(|p| p.rsplit(['/', '\\']).next().map(|s| s.to_owned()))
+                    .filter(|s| !s.is_empty());
+                let payload_path_option = payload_file_name_option.as_deref().and_then(|name| {
+                    let rel_path = format!("{DOWNLOADS_DIR}/{name}");
+                    root.join(&rel_path).exists().then_some(rel_path)
+                });
+
+                let contents = match msg_tpe {
+                    MessageType::Text => vec![],
+                    MessageType::Picture => vec![content!(Photo {
+                        path_option: payload_path_option,
+                        width: 0,
+                        height: 0,
+                        mime_type_option: None,
+                        is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
+                    })],
+                    MessageType::Video => vec![content!(Video {
+                        path_option: payload_path_option,
+                        file_name_option: payload_file_name_option,
+                        title_option: None,
+                        performer_option: None,
+                        width: 0,
+                        height: 0,
+                        mime_type: "".to_owned(),
+                        duration_sec_option: row.get("Duration")?,
+                        thumbnail_path_option: None,
+                        is_one_time: false,
+                    })],
+                    MessageType::Sticker => {
+                        let sticker_id: i64 = row.get::<_, Option<i64>>("StickerID")?
+                            .with_context(|| format!("Sticker ID is not set for event {source_id}"))?;
+                        let rel_path = format!("{STICKERS_DIR}/{sticker_id}.png");
+                        let path_option = root.join(&rel_path).exists().then_some(rel_path);
+                        vec![content!(Sticker {
+                            mime_type_option: path_option.as_ref().map(|_| "image/png".to_owned()),
+                            path_option,
+                            file_name_option: None,
+                            width: 0,
+                            height: 0,
+                            thumbnail_path_option: None,
+                            emoji_option: None,
+                            pack_id_option: None,
+                            pack_name_option: None,
+                        })]
+                    }
+                    MessageType::File => vec![content!(File {
+                        path_option: payload_path_option,
+                        file_name_option: payload_file_name_option,
+                        mime_type_option: None,
+                        thumbnail_path_option: None,
+                    })],
+                };
+
+                (text, message_regular! {
+                    edit_timestamp_option: None,
+                    is_deleted: false,
+                    forward_from_name_option: None,
+                    forward_from_id_option: None,
+                    ephemeral_duration_sec_option: None,
+                    reply_to_message_id_option: None,
+                    contents,
+                })
+            } else {
+                bail!("Event {source_id} is neither a message nor a call")
+            };
+
+            messages.push(Message::new(
+                *NO_INTERNAL_ID, // Will be set later
+                Some(source_id),
+                timestamp,
+                from_id,
+                text,
+                typed,
+            ));
+        }
+
+        if messages.is_empty() { continue; }
+        messages.iter_mut().enumerate().for_each(|(i, m)| m.internal_id = i as i64);
+
+        cwms.push(ChatWithMessages {
+            chat: Chat {
+                ds_uuid: ds_uuid.clone(),
+                id: chat_id,
+                name_option,
+                source_type: SourceType::Viber as i32,
+                tpe: tpe as i32,
+                img_path_option: None,
+                member_ids: std::iter::once(*users.myself_id).chain(member_ids.iter().map(|id| **id)).collect_vec(),
+                msg_count: messages.len() as i32,
+                main_chat_id: None,
+                archived: None,
+                hidden: None,
+            },
+            messages,
+        });
+    }
+
+    Ok(cwms)
+}
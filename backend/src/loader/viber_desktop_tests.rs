@@ -0,0 +1,183 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::ChatHistoryReader;
+use crate::protobuf::history::message_service::SealedValueOptional::*;
+
+use super::*;
+
+const RESOURCE_DIR: &str = "viber-desktop";
+const LOADER: ViberDesktopDataLoader = ViberDesktopDataLoader;
+
+/// Account folder name, matching own phone number in the test database.
+const ACCOUNT_DIR: &str = "79991112233";
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2024_03() -> EmptyRes {
+    let (res, db_dir) = create_databases(RESOURCE_DIR, "2024-03", ACCOUNT_DIR, ".db", DB_FILENAME);
+    fs::create_dir_all(db_dir.path.join(DOWNLOADS_DIR))?;
+    fs::write(db_dir.path.join(DOWNLOADS_DIR).join("0-1-photo.jpg"), b"photo")?;
+    fs::create_dir_all(db_dir.path.join(STICKERS_DIR))?;
+    fs::write(db_dir.path.join(STICKERS_DIR).join("123.png"), b"sticker")?;
+
+    LOADER.looks_about_right(&res)?;
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let user = |id: i64, name: &str, phone: &str| User {
+        ds_uuid: ds_uuid.clone(),
+        id,
+        first_name_option: Some(name.to_owned()),
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: Some(phone.to_owned()),
+        profile_pictures: vec![],
+    };
+    let myself = user(3, "Me Myself", "+79991112233");
+    let alice = user(1, "Alice Smith", "+79990001111");
+    let bob = user(2, "Bob", "+79990002222");
+
+    assert_eq!(dao.myself_single_ds(), myself);
+    assert_eq!(dao.users_single_ds(), vec![myself.clone(), alice.clone(), bob.clone()]);
+
+    // Chat without events is skipped
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 2);
+
+    // Personal chat
+    {
+        let chat = &cwms[0].chat;
+        assert_eq!(chat, &Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: 10,
+            name_option: Some("Alice Smith".to_owned()),
+            source_type: SourceType::Viber as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, alice.id],
+            msg_count: 5,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let call = |internal_id: i64, source_id: i64, timestamp: i64, from_id: UserId,
+                    duration_sec_option: Option<i32>, discard_reason: &str| Message::new(
+            internal_id,
+            Some(source_id),
+            timestamp,
+            from_id,
+            vec![],
+            message_service!(PhoneCall(MessageServicePhoneCall {
+                duration_sec_option,
+                discard_reason_option: Some(discard_reason.to_owned()),
+                members: vec![],
+            })),
+        );
+
+        let msgs = dao.first_messages(chat, 99999)?;
+        assert_eq!(msgs, vec![
+            MessageBuilder::regular(1709280000, alice.id())
+                .internal_id(0)
+                .source_id(100)
+                .text("Hi there!")
+                .build(),
+            MessageBuilder::regular(1709280060, myself.id())
+                .internal_id(1)
+                .source_id(101)
+                .text("Hello!")
+                .build(),
+            MessageBuilder::regular(1709280120, alice.id())
+                .internal_id(2)
+                .source_id(102)
+                .content(content!(Sticker {
+                    path_option: Some("Stickers/123.png".to_owned()),
+                    file_name_option: None,
+                    width: 0,
+                    height: 0,
+                    mime_type_option: Some("image/png".to_owned()),
+                    thumbnail_path_option: None,
+                    emoji_option: None,
+                    pack_id_option: None,
+                    pack_name_option: None,
+                }))
+                .build(),
+            call(3, 103, 1709280180, alice.id(), Some(65), "hangup"),
+            call(4, 104, 1709280240, myself.id(), None, "missed"),
+        ]);
+    }
+
+    // Group chat
+    {
+        let chat = &cwms[1].chat;
+        assert_eq!(chat, &Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: 11,
+            name_option: Some("Weekend plans".to_owned()),
+            source_type: SourceType::Viber as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, alice.id, bob.id],
+            msg_count: 3,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(chat, 99999)?;
+        assert_eq!(msgs, vec![
+            MessageBuilder::regular(1709370000, myself.id())
+                .internal_id(0)
+                .source_id(200)
+                .text("Look at this")
+                .photo(Some("ViberDownloads/0-1-photo.jpg".to_owned()), 0, 0)
+                .build(),
+            // File wasn't copied to downloads folder
+            MessageBuilder::regular(1709370060, bob.id())
+                .internal_id(1)
+                .source_id(201)
+                .file(None, Some("notes.pdf".to_owned()))
+                .build(),
+            MessageBuilder::regular(1709370120, alice.id())
+                .internal_id(2)
+                .source_id(202)
+                .content(content!(Video {
+                    path_option: None,
+                    file_name_option: None,
+                    title_option: None,
+                    performer_option: None,
+                    width: 0,
+                    height: 0,
+                    mime_type: "".to_owned(),
+                    duration_sec_option: Some(12),
+                    thumbnail_path_option: None,
+                    is_one_time: false,
+                }))
+                .build(),
+        ]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn own_contact_made_up_if_missing() -> EmptyRes {
+    let (res, _db_dir) = create_databases(RESOURCE_DIR, "2024-03", "79990009999", ".db", DB_FILENAME);
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.id, hash_to_id("79990009999"));
+    assert_eq!(myself.first_name_option, Some("Me".to_owned()));
+    assert_eq!(myself.phone_number_option, Some("+79990009999".to_owned()));
+
+    // Former own contact is now just another member
+    assert_eq!(dao.cwms_single_ds()[1].chat.member_ids, vec![myself.id, 1, 2, 3]);
+    Ok(())
+}
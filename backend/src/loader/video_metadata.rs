@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "video_metadata_tests.rs"]
+mod tests;
+
+const FFPROBE: &str = "ffprobe";
+const FFMPEG: &str = "ffmpeg";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct VideoMetadata {
+    width: i32,
+    height: i32,
+    duration_sec_option: Option<i32>,
+}
+
+/// For every video and video message whose file is present in a freshly loaded dataset,
+/// fill in missing duration and dimensions and extract the first frame as a thumbnail (stored next to the video).
+///
+/// Requires `ffprobe` and `ffmpeg` to be available on `PATH`, does nothing otherwise.
+pub fn enrich_videos(dao: &mut InMemoryDao) -> EmptyRes {
+    if let Err(e) = Command::new(FFPROBE).arg("-version").output() {
+        log::warn!("{FFPROBE} is not available, skipping video metadata enrichment: {e}");
+        return Ok(());
+    }
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Videos can only be enriched for a single dataset"))?.uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+
+    let mut num_videos = 0;
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        for msg in cwm.messages.iter_mut() {
+            let Some(message::Typed::Regular(mr)) = msg.typed.as_mut() else { continue };
+            for content in mr.contents.iter_mut() {
+                use content::SealedValueOptional::*;
+                let (path_option, width, height, duration_sec_option, thumbnail_path_option) =
+                    match content.sealed_value_optional.as_mut() {
+                        Some(VideoMsg(v)) =>
+                            (&v.path_option, &mut v.width, &mut v.height,
+                             &mut v.duration_sec_option, &mut v.thumbnail_path_option),
+                        Some(Video(v)) =>
+                            (&v.path_option, &mut v.width, &mut v.height,
+                             &mut v.duration_sec_option, &mut v.thumbnail_path_option),
+                        _ => continue,
+                    };
+                let Some(path) = path_option.as_ref().map(|p| ds_root.to_absolute(p)) else { continue };
+                if !path.exists() { continue; }
+                let mut changed = false;
+
+                if duration_sec_option.is_none() || *width <= 0 || *height <= 0 {
+                    match probe(&path) {
+                        Ok(meta) => {
+                            if *width <= 0 || *height <= 0 {
+                                (*width, *height) = (meta.width, meta.height);
+                            }
+                            *duration_sec_option = duration_sec_option.or(meta.duration_sec_option);
+                            changed = true;
+                        }
+                        Err(e) => log::warn!("Couldn't probe video {}: {e}", path.display()),
+                    }
+                }
+
+                if thumbnail_path_option.is_none() {
+                    let thumbnail_path = thumbnail_path_for(&path);
+                    let res = if thumbnail_path.exists() { Ok(()) } else { extract_first_frame(&path, &thumbnail_path) };
+                    match res.and_then(|_| ds_root.to_relative(&thumbnail_path)) {
+                        Ok(thumbnail_path) => {
+                            *thumbnail_path_option = Some(thumbnail_path);
+                            changed = true;
+                        }
+                        Err(e) => log::warn!("Couldn't extract first frame of video {}: {e}", path.display()),
+                    }
+                }
+
+                if changed { num_videos += 1; }
+            }
+        }
+    }
+    log::info!("Enriched {num_videos} videos");
+    Ok(())
+}
+
+/// Same convention as Telegram uses, e.g. `video.mp4` -> `video.mp4_thumb.jpg`
+fn thumbnail_path_for(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().expect("Video path has no file name").to_os_string();
+    file_name.push("_thumb.jpg");
+    path.with_file_name(file_name)
+}
+
+fn probe(path: &Path) -> Result<VideoMetadata> {
+    let output = Command::new(FFPROBE)
+        .args(["-v", "error", "-select_streams", "v:0",
+            "-show_entries", "stream=width,height,duration:format=duration", "-of", "json"])
+        .arg(path)
+        .output()?;
+    ensure!(output.status.success(), "{FFPROBE} failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    parse_ffprobe_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_ffprobe_output(json: &str) -> Result<VideoMetadata> {
+    let json: serde_json::Value = serde_json::from_str(json)?;
+    let stream = json["streams"].as_array().and_then(|s| s.first()).context("No video stream found")?;
+    let dimension = |key: &str| stream[key].as_i64().and_then(|v| i32::try_from(v).ok()).unwrap_or(0);
+    // Duration is a decimal string, stream might not have it (e.g. for WebM)
+    let duration = [&stream["duration"], &json["format"]["duration"]].into_iter()
+        .filter_map(|d| d.as_str())
+        .filter_map(|d| d.parse::<f64>().ok())
+        .next();
+    Ok(VideoMetadata {
+        width: dimension("width"),
+        height: dimension("height"),
+        duration_sec_option: duration.map(|d| d.round() as i32),
+    })
+}
+
+fn extract_first_frame(path: &Path, thumbnail_path: &Path) -> EmptyRes {
+    let output = Command::new(FFMPEG)
+        .args(["-v", "error", "-n", "-i"])
+        .arg(path)
+        .args(["-frames:v", "1", "-q:v", "3"])
+        .arg(thumbnail_path)
+        .output()?;
+    ensure!(output.status.success(), "{FFMPEG} failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    ensure!(thumbnail_path.exists(), "{FFMPEG} produced no output");
+    Ok(())
+}
@@ -0,0 +1,50 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+#[test]
+fn parsing_ffprobe_output() -> EmptyRes {
+    let json = r#"{
+        "programs": [],
+        "streams": [{ "width": 640, "height": 640, "duration": "12.533000" }],
+        "format": { "duration": "12.560000" }
+    }"#;
+    assert_eq!(parse_ffprobe_output(json)?, VideoMetadata {
+        width: 640,
+        height: 640,
+        duration_sec_option: Some(13),
+    });
+
+    // WebM streams have no duration
+    let json = r#"{
+        "programs": [],
+        "streams": [{ "width": 1280, "height": 720 }],
+        "format": { "duration": "3.400000" }
+    }"#;
+    assert_eq!(parse_ffprobe_output(json)?, VideoMetadata {
+        width: 1280,
+        height: 720,
+        duration_sec_option: Some(3),
+    });
+
+    let json = r#"{
+        "programs": [],
+        "streams": [{ "width": 1280, "height": 720, "duration": "N/A" }],
+        "format": {}
+    }"#;
+    assert_eq!(parse_ffprobe_output(json)?.duration_sec_option, None);
+
+    let json = r#"{ "programs": [], "streams": [], "format": { "duration": "3.400000" } }"#;
+    assert!(parse_ffprobe_output(json).is_err());
+    Ok(())
+}
+
+#[test]
+fn thumbnail_path() {
+    assert_eq!(thumbnail_path_for(Path::new("/data/videos/video.mp4")),
+               PathBuf::from("/data/videos/video.mp4_thumb.jpg"));
+}
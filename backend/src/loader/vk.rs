@@ -0,0 +1,419 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDateTime, TimeZone};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::loader::DataLoader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "vk_tests.rs"]
+mod tests;
+
+const MESSAGES_DIR: &str = "messages";
+const INDEX_MESSAGES_HTML: &str = "index-messages.html";
+
+/// Peer IDs of group chats (as opposed to users and communities) are offset by this much.
+const GROUP_CHAT_PEER_ID_OFFSET: i64 = 2_000_000_000;
+
+/// Archive never mentions own VK ID, messages are just signed as "You".
+const MYSELF_KEY: &str = "vk:myself";
+
+const EDITED_MARKERS: &[&str] = &[" (ред.)", " (edited)"];
+
+/// Russian month names as used in message headers, both in nominative and genitive.
+const RU_MONTHS: &[(&str, &str)] = &[
+    ("янв", "Jan"), ("фев", "Feb"), ("мар", "Mar"), ("апр", "Apr"), ("мая", "May"), ("май", "May"),
+    ("июн", "Jun"), ("июл", "Jul"), ("авг", "Aug"), ("сен", "Sep"), ("окт", "Oct"), ("ноя", "Nov"), ("дек", "Dec"),
+];
+
+lazy_static! {
+    static ref MESSAGE_SELECTOR: Selector = Selector::parse("div.message").unwrap();
+    static ref HEADER_SELECTOR: Selector = Selector::parse("div.message__header").unwrap();
+    static ref LINK_SELECTOR: Selector = Selector::parse("a").unwrap();
+    static ref ATTACHMENT_SELECTOR: Selector = Selector::parse("div.attachment").unwrap();
+    static ref ATTACHMENT_DESCRIPTION_SELECTOR: Selector = Selector::parse("div.attachment__description").unwrap();
+    static ref ATTACHMENT_LINK_SELECTOR: Selector = Selector::parse("a.attachment__link").unwrap();
+    static ref CRUMB_SELECTOR: Selector = Selector::parse("div.ui_crumb").unwrap();
+}
+
+/// Loader for VK (VKontakte) archive of personal data, takes either the archive folder, its `messages` folder,
+/// or `messages/index-messages.html`.
+///
+/// Every chat is a `messages/<peer_id>` folder with one or more `messages<offset>.html` pages,
+/// attachments are links to VK servers rather than files, so media content has no paths.
+pub struct VkDataLoader;
+
+impl DataLoader for VkDataLoader {
+    fn name(&self) -> String { "VK".to_owned() }
+
+    fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
+        let messages_dir = get_messages_dir(path)?;
+        if !peer_dirs(&messages_dir)?.iter().any(|(_, dir)| !message_pages(dir).unwrap_or_default().is_empty()) {
+            bail!("No chat folders with message pages found in {}", messages_dir.display());
+        }
+        Ok(())
+    }
+
+    fn load_inner(&self, path: &Path, ds: Dataset, _user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
+        parse_vk_archive(&get_messages_dir(path)?, ds)
+    }
+}
+
+fn get_messages_dir(path: &Path) -> Result<PathBuf> {
+    if path.is_dir() {
+        if path.join(INDEX_MESSAGES_HTML).exists() || path_file_name(path)? == MESSAGES_DIR {
+            return Ok(path.to_path_buf());
+        }
+        let messages_dir = path.join(MESSAGES_DIR);
+        ensure!(messages_dir.is_dir(), "No {MESSAGES_DIR} folder found");
+        return Ok(messages_dir);
+    }
+    ensure!(path_file_name(path)? == INDEX_MESSAGES_HTML, "File is not {INDEX_MESSAGES_HTML}");
+    Ok(path.parent().unwrap().to_path_buf())
+}
+
+/// Chat folders named by peer IDs, in ID order.
+fn peer_dirs(messages_dir: &Path) -> Result<Vec<(i64, PathBuf)>> {
+    let mut result = vec![];
+    for entry in fs::read_dir(messages_dir)? {
+        let path = entry?.path();
+        if let Ok(peer_id) = path_file_name(&path)?.parse::<i64>() {
+            if path.is_dir() {
+                result.push((peer_id, path));
+            }
+        }
+    }
+    result.sort_by_key(|(peer_id, _)| *peer_id);
+    Ok(result)
+}
+
+/// Message pages `messages<offset>.html` of a chat, in offset order.
+fn message_pages(peer_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut result = vec![];
+    for entry in fs::read_dir(peer_dir)? {
+        let path = entry?.path();
+        let offset_option = path_file_name(&path)?
+            .strip_prefix("messages")
+            .and_then(|s| s.strip_suffix(".html"))
+            .and_then(|s| s.parse::<u64>().ok());
+        if let Some(offset) = offset_option {
+            result.push((offset, path));
+        }
+    }
+    result.sort_by_key(|(offset, _)| *offset);
+    Ok(result.into_iter().map(|(_, path)| path).collect_vec())
+}
+
+/// Archive pages are usually encoded in Windows-1251, as declared in their `<meta>`.
+fn read_html(path: &Path) -> Result<Html> {
+    let bytes = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_lowercase();
+    let content = if head.contains("windows-1251") {
+        encoding_rs::WINDOWS_1251.decode(&bytes).0.into_owned()
+    } else {
+        String::from_utf8(bytes).with_context(|| format!("{} is neither UTF-8 nor Windows-1251", path.display()))?
+    };
+    Ok(Html::parse_document(&content))
+}
+
+struct RawMessage {
+    id: i64,
+    /// `None` for own messages
+    from_option: Option<RawUser>,
+    timestamp: Timestamp,
+    is_edited: bool,
+    text: Vec<RichTextElement>,
+    contents: Vec<Content>,
+}
+
+struct RawUser {
+    id: i64,
+    name: String,
+}
+
+fn parse_vk_archive(messages_dir: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+    let myself_id = UserId(super::hash_to_id(MYSELF_KEY));
+    let mut users: IndexMap<i64, User> = IndexMap::new();
+    users.insert(*myself_id, User {
+        ds_uuid: ds.uuid.clone(),
+        id: *myself_id,
+        first_name_option: None,
+        last_name_option: None,
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    });
+    let mut add_user = |id: i64, name: &str| {
+        users.entry(id).or_insert_with(|| {
+            let (first_name_option, last_name_option) = split_name(id, name);
+            User {
+                ds_uuid: ds.uuid.clone(),
+                id,
+                first_name_option,
+                last_name_option,
+                username_option: None,
+                phone_number_option: None,
+                profile_pictures: vec![],
+            }
+        });
+    };
+
+    let mut cwms = vec![];
+    for (peer_id, peer_dir) in peer_dirs(messages_dir)? {
+        let pages = message_pages(&peer_dir)?;
+        if pages.is_empty() { continue; }
+
+        let mut chat_name_option = None;
+        let mut raw_messages = vec![];
+        for page_path in pages.iter() {
+            let html = read_html(page_path)?;
+            if chat_name_option.is_none() {
+                chat_name_option = html.select(&CRUMB_SELECTOR).last()
+                    .map(|e| normalize_spaces(&e.text().collect::<String>()))
+                    .filter(|s| !s.is_empty());
+            }
+            for message_el in html.select(&MESSAGE_SELECTOR) {
+                let msg = parse_message(message_el)
+                    .with_context(|| format!("Failed to parse a message in {}", page_path.display()))?;
+                raw_messages.push(msg);
+            }
+        }
+        // Pages go from newest to oldest, as do messages within them
+        raw_messages.sort_by_key(|m| (m.timestamp, m.id));
+        raw_messages.dedup_by_key(|m| m.id);
+
+        let tpe = if peer_id >= GROUP_CHAT_PEER_ID_OFFSET { ChatType::PrivateGroup } else { ChatType::Personal };
+        let mut member_ids = vec![myself_id];
+        if tpe == ChatType::Personal {
+            add_user(peer_id, chat_name_option.as_deref().unwrap_or_default());
+            member_ids.push(UserId(peer_id));
+        }
+
+        let messages = raw_messages.into_iter().enumerate().map(|(idx, rm)| {
+            let from_id = match rm.from_option {
+                Some(from) => {
+                    add_user(from.id, &from.name);
+                    UserId(from.id)
+                }
+                None => myself_id,
+            };
+            if !member_ids.contains(&from_id) {
+                member_ids.push(from_id);
+            }
+            MessageBuilder::regular(*rm.timestamp, from_id)
+                .internal_id(idx as i64)
+                .source_id(rm.id)
+                .rich_texts(rm.text)
+                // Edit time is not exported
+                .edited_at(if rm.is_edited { Some(*rm.timestamp) } else { None })
+                .contents(rm.contents)
+                .build()
+        }).collect_vec();
+
+        cwms.push(ChatWithMessages {
+            chat: Chat {
+                ds_uuid: ds.uuid.clone(),
+                id: peer_id,
+                name_option: chat_name_option,
+                source_type: SourceType::Vk as i32,
+                tpe: tpe as i32,
+                img_path_option: None,
+                member_ids: member_ids.iter().map(|id| **id).collect_vec(),
+                msg_count: messages.len() as i32,
+                main_chat_id: None,
+                archived: None,
+                hidden: None,
+            },
+            messages,
+        });
+    }
+
+    Ok(Box::new(InMemoryDao::new_single(
+        format!("VK ({})", path_file_name(messages_dir.parent().unwrap_or(messages_dir))?),
+        ds,
+        messages_dir.to_path_buf(),
+        myself_id,
+        users.into_values().collect_vec(),
+        cwms,
+    )))
+}
+
+/// Message looks like this:
+/// ```html
+/// <div class="message" data-id="1234">
+///   <div class="message__header"><a href="https://vk.com/id1">Pavel Durov</a>, 1 янв 2020 в 12:34:56 (ред.)</div>
+///   <div>Text<br>Second line<div class="kludges"><div class="attachment">...</div></div></div>
+/// </div>
+/// ```
+/// Own messages have just "Вы" or "You" instead of a link in a header.
+fn parse_message(message_el: ElementRef) -> Result<RawMessage> {
+    let id = message_el.value().attr("data-id")
+        .context("Message has no data-id")?
+        .parse::<i64>()?;
+
+    let header = message_el.select(&HEADER_SELECTOR).next().context("Message has no header")?;
+    let header_text = normalize_spaces(&header.text().collect::<String>());
+    let (from_option, date_str) = match header.select(&LINK_SELECTOR).next() {
+        Some(link) => {
+            let name = normalize_spaces(&link.text().collect::<String>());
+            let href = link.value().attr("href").unwrap_or_default();
+            let date_str = header_text.strip_prefix(name.as_str()).unwrap_or(&header_text);
+            (Some(RawUser { id: parse_user_id(href), name }), date_str)
+        }
+        None => (None, header_text.split_once(',').map(|(_, rest)| rest).unwrap_or(&header_text)),
+    };
+    let date_str = date_str.trim_start_matches(',').trim();
+    let (date_str, is_edited) = match EDITED_MARKERS.iter().find_map(|m| date_str.strip_suffix(m)) {
+        Some(date_str) => (date_str, true),
+        None => (date_str, false),
+    };
+    let timestamp = parse_datetime(date_str)?;
+
+    let mut text = String::new();
+    let mut contents = vec![];
+    let mut text_links = vec![];
+    for body in message_el.children().filter_map(ElementRef::wrap).filter(|e| e.id() != header.id()) {
+        collect_text(body, &mut text);
+        for attachment in body.select(&ATTACHMENT_SELECTOR) {
+            let description = attachment.select(&ATTACHMENT_DESCRIPTION_SELECTOR).next()
+                .map(|e| normalize_spaces(&e.text().collect::<String>()))
+                .unwrap_or_default();
+            let href_option = attachment.select(&ATTACHMENT_LINK_SELECTOR).next()
+                .and_then(|e| e.value().attr("href"))
+                .map(|s| s.to_owned());
+            match parse_attachment(&description) {
+                Some(content) => contents.push(content),
+                None => text_links.push(match href_option {
+                    Some(href) => RichText::make_link(Some(description), href, false),
+                    None => RichText::make_plain(format!("[{description}]")),
+                }),
+            }
+        }
+    }
+
+    let text = text.trim();
+    let mut rtes = if text.is_empty() { vec![] } else { vec![RichText::make_plain(text.to_owned())] };
+    rtes.extend(text_links);
+    Ok(RawMessage { id, from_option, timestamp, is_edited, text: rtes, contents })
+}
+
+/// Text of an element with line breaks, attachments (kludges) are skipped.
+fn collect_text(el: ElementRef, text: &mut String) {
+    for child in el.children() {
+        match child.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(e) if e.name() == "br" => text.push('\n'),
+            Node::Element(e) if e.attr("class").is_some_and(|c| c.split_whitespace().any(|c| c == "kludges")) => {}
+            Node::Element(_) => collect_text(ElementRef::wrap(child).unwrap(), text),
+            _ => {}
+        }
+    }
+}
+
+/// Media attachment by its description, others (wall posts, links, gifts, forwarded messages, etc.)
+/// are added to message text instead.
+fn parse_attachment(description: &str) -> Option<Content> {
+    match description.to_lowercase().as_str() {
+        "фотография" | "photo" => Some(content!(Photo {
+            path_option: None,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        })),
+        "видеозапись" | "video" => Some(content!(Video {
+            path_option: None,
+            file_name_option: None,
+            title_option: None,
+            performer_option: None,
+            width: 0,
+            height: 0,
+            mime_type: "video/mp4".to_owned(),
+            duration_sec_option: None,
+            thumbnail_path_option: None,
+            is_one_time: false,
+        })),
+        "аудиозапись" | "audio" => Some(content!(Audio {
+            path_option: None,
+            file_name_option: None,
+            title_option: None,
+            performer_option: None,
+            mime_type: "audio/mpeg".to_owned(),
+            duration_sec_option: None,
+            waveform_option: None,
+            thumbnail_path_option: None,
+        })),
+        "голосовое сообщение" | "аудиосообщение" | "voice message" => Some(content!(VoiceMsg {
+            path_option: None,
+            file_name_option: None,
+            mime_type: "audio/ogg".to_owned(),
+            duration_sec_option: None,
+            waveform_option: None,
+        })),
+        "документ" | "file" | "document" => Some(content!(File {
+            path_option: None,
+            file_name_option: None,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+        })),
+        "стикер" | "sticker" => Some(content!(Sticker {
+            path_option: None,
+            file_name_option: None,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+            emoji_option: None,
+            pack_id_option: None,
+            pack_name_option: None,
+        })),
+        _ => None,
+    }
+}
+
+/// Users are linked as `https://vk.com/id<ID>`, communities as `https://vk.com/club<ID>` or `public<ID>`
+/// and are given negative IDs, same as VK does. Anything else (e.g. a custom short name) is hashed.
+fn parse_user_id(href: &str) -> i64 {
+    let path = href.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    let parse_suffix = |prefix: &str| path.strip_prefix(prefix).and_then(|s| s.parse::<i64>().ok());
+    parse_suffix("id")
+        .or_else(|| parse_suffix("club").or_else(|| parse_suffix("public")).map(|id| -id))
+        .unwrap_or_else(|| super::hash_to_id(href))
+}
+
+/// User name is split into first and last names, community name is kept whole.
+fn split_name(id: i64, name: &str) -> (Option<String>, Option<String>) {
+    match name.split_once(' ') {
+        _ if name.is_empty() => (None, None),
+        Some((first, last)) if id > 0 => (Some(first.to_owned()), Some(last.to_owned())),
+        _ => (Some(name.to_owned()), None),
+    }
+}
+
+fn normalize_spaces(s: &str) -> String {
+    s.split_whitespace().join(" ")
+}
+
+/// Datetime in a local timezone, e.g. `1 янв 2020 в 12:34:56` or `1 Jan 2020 at 12:34:56`.
+fn parse_datetime(s: &str) -> Result<Timestamp> {
+    const DATE_TIME_FMT: &str = "%d %b %Y at %H:%M:%S";
+    let mut normalized = s.to_lowercase().replace(" в ", " at ");
+    for (ru, en) in RU_MONTHS {
+        normalized = normalized.replace(ru, en);
+    }
+    let naive_dt = NaiveDateTime::parse_from_str(&normalized, DATE_TIME_FMT)
+        .with_context(|| format!("Cannot parse datetime: {s}"))?;
+    // Wall clock time might be skipped over by a DST transition
+    let local_dt = LOCAL_TZ.from_local_datetime(&naive_dt).earliest()
+        .with_context(|| format!("Timestamp does not exist in local timezone: {s}"))?;
+    Ok(Timestamp(local_dt.timestamp()))
+}
@@ -0,0 +1,172 @@
+#![allow(unused_imports)]
+
+use chrono::prelude::*;
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::ChatHistoryReader;
+use crate::entity_utils::*;
+use crate::loader::hash_to_id;
+use crate::protobuf::history::message::*;
+
+use super::*;
+
+const LOADER: VkDataLoader = VkDataLoader;
+
+//
+// Tests
+//
+
+#[test]
+fn loading_2024_03() -> EmptyRes {
+    let res = resource("vk_2024-03");
+    LOADER.looks_about_right(&res)?;
+    LOADER.looks_about_right(&res.join(MESSAGES_DIR))?;
+    LOADER.looks_about_right(&res.join(MESSAGES_DIR).join(INDEX_MESSAGES_HTML))?;
+
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    let ds_uuid = &dao.ds_uuid();
+    let user = |id: i64, first_name: &str, last_name_option: Option<&str>| User {
+        ds_uuid: ds_uuid.clone(),
+        id,
+        first_name_option: Some(first_name.to_owned()),
+        last_name_option: last_name_option.map(|s| s.to_owned()),
+        username_option: None,
+        phone_number_option: None,
+        profile_pictures: vec![],
+    };
+
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.id, hash_to_id(MYSELF_KEY));
+    assert_eq!(myself.first_name_option, None);
+
+    let durov = user(123, "Павел", Some("Дуров"));
+    let jane = user(789, "Jane", Some("Doe"));
+    let cat_lovers = user(-456, "Cat Lovers", None);
+    assert_eq!(dao.users_single_ds(), vec![myself.clone(), cat_lovers.clone(), durov.clone(), jane.clone()]);
+
+    let cwms = dao.cwms_single_ds();
+    assert_eq!(cwms.len(), 2);
+
+    let regular = |edit_timestamp_option: Option<i64>, contents: Vec<Content>| message_regular! {
+        edit_timestamp_option,
+        is_deleted: false,
+        forward_from_name_option: None,
+        forward_from_id_option: None,
+        ephemeral_duration_sec_option: None,
+        reply_to_message_id_option: None,
+        contents,
+    };
+
+    // Personal chat, Windows-1251 encoded and split into pages
+    {
+        let cwm = &cwms[0];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: 123,
+            name_option: Some("Павел Дуров".to_owned()),
+            source_type: SourceType::Vk as i32,
+            tpe: ChatType::Personal as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, durov.id],
+            msg_count: 4,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        let edited_ts = dt("2024-03-02 10:05:00", None).timestamp();
+        assert_eq!(msgs, vec![
+            Message::new(
+                0,
+                Some(1001),
+                dt("2023-05-01 09:00:00", None).timestamp(),
+                durov.id(),
+                vec![RichText::make_plain("Здравствуйте".to_owned())],
+                regular(None, vec![]),
+            ),
+            Message::new(
+                1,
+                Some(1002),
+                dt("2023-05-01 09:30:00", None).timestamp(),
+                myself.id(),
+                vec![RichText::make_plain("Добрый день & всё такое".to_owned())],
+                regular(None, vec![]),
+            ),
+            Message::new(
+                2,
+                Some(1003),
+                dt("2024-03-02 10:00:00", None).timestamp(),
+                durov.id(),
+                vec![RichText::make_plain("Привет!\nКак дела?".to_owned())],
+                regular(None, vec![content!(Photo {
+                    path_option: None,
+                    width: 0,
+                    height: 0,
+                    mime_type_option: None,
+                    is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
+                })]),
+            ),
+            Message::new(
+                3,
+                Some(1004),
+                edited_ts,
+                myself.id(),
+                vec![RichText::make_plain("Смотри, что нашёл".to_owned())],
+                regular(Some(edited_ts), vec![]),
+            ),
+        ]);
+    }
+
+    // Group chat in English
+    {
+        let cwm = &cwms[1];
+        assert_eq!(cwm.chat, Chat {
+            ds_uuid: ds_uuid.clone(),
+            id: 2000000001,
+            name_option: Some("Our chat".to_owned()),
+            source_type: SourceType::Vk as i32,
+            tpe: ChatType::PrivateGroup as i32,
+            img_path_option: None,
+            member_ids: vec![myself.id, jane.id, cat_lovers.id],
+            msg_count: 3,
+            main_chat_id: None,
+            archived: None,
+            hidden: None,
+        });
+
+        let msgs = dao.first_messages(&cwm.chat, 99999)?;
+        assert_eq!(msgs.iter().map(|m| m.from_id).collect_vec(), vec![jane.id, cat_lovers.id, myself.id]);
+        assert_eq!(msgs[1].text, vec![
+            RichText::make_plain("Look at this".to_owned()),
+            RichText::make_link(Some("Wall post".to_owned()), "https://vk.com/wall-456_1".to_owned(), false),
+        ]);
+        assert_eq!(msgs[2].text, vec![]);
+        let message_regular_pat! { contents, .. } = msgs[2].typed() else { unreachable!() };
+        assert!(matches!(contents[..], [Content { sealed_value_optional: Some(content::SealedValueOptional::Sticker(_)) }]));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn parsing_user_ids() {
+    assert_eq!(parse_user_id("https://vk.com/id123"), 123);
+    assert_eq!(parse_user_id("https://vk.com/club456"), -456);
+    assert_eq!(parse_user_id("https://vk.com/public456/"), -456);
+    assert_eq!(parse_user_id("https://vk.com/durov"), hash_to_id("https://vk.com/durov"));
+}
+
+#[test]
+fn parsing_datetimes() -> EmptyRes {
+    let expected = dt("2020-01-01 12:34:56", None).timestamp();
+    assert_eq!(*parse_datetime("1 янв 2020 в 12:34:56")?, expected);
+    assert_eq!(*parse_datetime("1 Jan 2020 at 12:34:56")?, expected);
+    assert_eq!(*parse_datetime("15 мая 2021 в 00:00:01")?, dt("2021-05-15 00:00:01", None).timestamp());
+    assert!(parse_datetime("yesterday").is_err());
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "waveform_tests.rs"]
+mod tests;
+
+/// Number of amplitude buckets in a computed waveform (fewer for very short recordings).
+pub const WAVEFORM_BUCKETS: usize = 100;
+
+/// For every voice message and audio whose file is present in a freshly loaded dataset,
+/// precompute a waveform envelope, so that it doesn't need to be decoded at view time.
+pub fn compute_waveforms(dao: &mut InMemoryDao) -> EmptyRes {
+    let ds_uuid = dao.datasets()?.into_iter().exactly_one()
+        .map_err(|_| anyhow!("Waveforms can only be computed for a single dataset"))?.uuid;
+    let ds_root = dao.dataset_root(&ds_uuid)?;
+
+    let mut num_waveforms = 0;
+    for cwm in dao.cwms.get_mut(&ds_uuid).into_iter().flatten() {
+        for msg in cwm.messages.iter_mut() {
+            let Some(message::Typed::Regular(mr)) = msg.typed.as_mut() else { continue };
+            for content in mr.contents.iter_mut() {
+                use content::SealedValueOptional::*;
+                let (path_option, waveform_option) = match content.sealed_value_optional.as_mut() {
+                    Some(VoiceMsg(v)) => (&v.path_option, &mut v.waveform_option),
+                    Some(Audio(v)) => (&v.path_option, &mut v.waveform_option),
+                    _ => continue,
+                };
+                if waveform_option.is_some() { continue; }
+                let Some(path) = path_option.as_ref().map(|p| ds_root.to_absolute(p)) else { continue };
+                if !path.exists() { continue; }
+                match compute_waveform(&path) {
+                    Ok(Some(waveform)) => {
+                        *waveform_option = Some(waveform);
+                        num_waveforms += 1;
+                    }
+                    Ok(None) => { /* Unsupported format */ }
+                    Err(e) => log::warn!("Couldn't compute waveform for {}: {e}", path.display()),
+                }
+            }
+        }
+    }
+    log::info!("Computed {num_waveforms} waveforms");
+    Ok(())
+}
+
+/// Computes an amplitude envelope of an audio file, one byte (0-255) per bucket, normalized to the loudest bucket.
+/// Returns `None` if the file format is not supported.
+///
+/// Supported formats are PCM/float WAV (precise peak amplitudes) and Ogg Opus/Vorbis.
+/// The latter is not decoded, instead sizes of VBR-encoded packets are used as a loudness approximation -
+/// this is good enough for a visual hint, silence takes much less space than speech.
+pub fn compute_waveform(path: &Path) -> Result<Option<Vec<u8>>> {
+    let bytes = fs::read(path)?;
+    let envelope = if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WAVE") {
+        wav_envelope(&bytes)?
+    } else if bytes.starts_with(b"OggS") {
+        ogg_envelope(&bytes)?
+    } else {
+        None
+    };
+    Ok(envelope.filter(|e| !e.is_empty()).map(|e| normalize(&e)))
+}
+
+fn wav_envelope(bytes: &[u8]) -> Result<Option<Vec<f64>>> {
+    // (format tag, bits per sample)
+    let mut format: Option<(u16, u16)> = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into()?) as usize;
+        let data = &bytes[offset + 8..(offset + 8).saturating_add(size).min(bytes.len())];
+        match id {
+            b"fmt " => {
+                ensure!(data.len() >= 16, "WAV format chunk is too short");
+                let tag = u16::from_le_bytes([data[0], data[1]]);
+                let bits = u16::from_le_bytes([data[14], data[15]]);
+                // WAVE_FORMAT_EXTENSIBLE stores the actual format tag in a sub-format GUID
+                let tag =
+                    if tag == 0xFFFE && data.len() >= 26 { u16::from_le_bytes([data[24], data[25]]) } else { tag };
+                format = Some((tag, bits));
+            }
+            b"data" => {
+                let (tag, bits) = format.context("WAV data chunk precedes format chunk")?;
+                let amplitude: fn(&[u8]) -> f64 = match (tag, bits) {
+                    (1, 8) => |s| (s[0] as f64 - 128.0).abs() / 128.0,
+                    (1, 16) => |s| (i16::from_le_bytes([s[0], s[1]]) as f64).abs() / 32768.0,
+                    (1, 24) => |s| (i32::from_le_bytes([0, s[0], s[1], s[2]]) as f64).abs() / 2147483648.0,
+                    (1, 32) => |s| (i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f64).abs() / 2147483648.0,
+                    (3, 32) => |s| (f32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f64).abs(),
+                    _ => {
+                        log::debug!("Unsupported WAV format {tag} with {bits} bits per sample");
+                        return Ok(None);
+                    }
+                };
+                // Channels are interleaved uniformly, so there's no need to separate them
+                let samples = data.chunks_exact(bits as usize / 8).map(amplitude);
+                return Ok(Some(bucketize(samples, true)));
+            }
+            _ => { /* Irrelevant chunk */ }
+        }
+        // Chunks are word-aligned
+        offset = offset.saturating_add(8 + size + size % 2);
+    }
+    bail!("No data chunk found in WAV")
+}
+
+fn ogg_envelope(bytes: &[u8]) -> Result<Option<Vec<f64>>> {
+    let mut serial_option: Option<u32> = None;
+    let mut first_packet_start: Option<usize> = None;
+    let mut packet_sizes: Vec<usize> = vec![];
+    let mut current_packet_size = 0;
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let page = &bytes[offset..];
+        ensure!(page.starts_with(b"OggS") && page.len() >= 27, "Malformed Ogg page at offset {offset}");
+        let serial = u32::from_le_bytes(page[14..18].try_into()?);
+        let num_segments = page[26] as usize;
+        let segments = page.get(27..27 + num_segments).context("Truncated Ogg segment table")?;
+        let data_offset = offset + 27 + num_segments;
+        // Only the first logical stream is considered
+        if *serial_option.get_or_insert(serial) == serial {
+            first_packet_start.get_or_insert(data_offset);
+            for &segment in segments {
+                current_packet_size += segment as usize;
+                // Segment shorter than 255 bytes terminates a packet
+                if segment < 255 {
+                    packet_sizes.push(current_packet_size);
+                    current_packet_size = 0;
+                }
+            }
+        }
+        offset = data_offset + segments.iter().map(|&s| s as usize).sum::<usize>();
+    }
+
+    let first_packet = &bytes[first_packet_start.context("No pages found in Ogg")?..];
+    let num_header_packets = if first_packet.starts_with(b"OpusHead") {
+        2
+    } else if first_packet.starts_with(b"\x01vorbis") {
+        3
+    } else {
+        log::debug!("Unsupported Ogg codec");
+        return Ok(None);
+    };
+    ensure!(packet_sizes.len() > num_header_packets, "No audio packets found in Ogg");
+    let sizes = packet_sizes.into_iter().skip(num_header_packets).map(|s| s as f64);
+    Ok(Some(bucketize(sizes, false)))
+}
+
+/// Splits values into (at most) [WAVEFORM_BUCKETS] evenly sized buckets, taking either peak or mean of each.
+fn bucketize(values: impl ExactSizeIterator<Item=f64>, peak: bool) -> Vec<f64> {
+    let len = values.len();
+    let num_buckets = len.min(WAVEFORM_BUCKETS);
+    // (sum, max, count)
+    let mut buckets = vec![(0.0, 0.0_f64, 0); num_buckets];
+    for (idx, v) in values.enumerate() {
+        let bucket = &mut buckets[idx * num_buckets / len];
+        bucket.0 += v;
+        bucket.1 = bucket.1.max(v);
+        bucket.2 += 1;
+    }
+    buckets.into_iter()
+        .map(|(sum, max, count)| if peak { max } else { sum / count as f64 })
+        .collect_vec()
+}
+
+fn normalize(envelope: &[f64]) -> Vec<u8> {
+    let max = envelope.iter().cloned().fold(0.0, f64::max);
+    envelope.iter()
+        .map(|v| if max > 0.0 { (v / max * 255.0).round() as u8 } else { 0 })
+        .collect_vec()
+}
@@ -0,0 +1,167 @@
+#![allow(unused_imports)]
+
+use std::fs;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::prelude::*;
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn wav() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("voice.wav");
+
+    // One second of silence followed by a second of a full-scale square wave, then a half-scale one
+    let samples = std::iter::repeat_n(0_i16, 8000)
+        .chain((0..8000).map(|i| if i % 40 < 20 { i16::MAX } else { i16::MIN + 1 }))
+        .chain((0..8000).map(|i| if i % 40 < 20 { i16::MAX / 2 } else { i16::MIN / 2 }))
+        .collect_vec();
+    create_named_file(&path, &make_wav(&samples));
+
+    let waveform = compute_waveform(&path)?.unwrap();
+    assert_eq!(waveform.len(), WAVEFORM_BUCKETS);
+    assert!(waveform[..33].iter().all(|&v| v == 0));
+    assert!(waveform[34..66].iter().all(|&v| v == 255));
+    assert!(waveform[67..].iter().all(|&v| v == 128));
+    Ok(())
+}
+
+#[test]
+fn short_wav() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("voice.wav");
+    create_named_file(&path, &make_wav(&[0, 100, -200, 50]));
+    assert_eq!(compute_waveform(&path)?, Some(vec![0, 128, 255, 64]));
+    Ok(())
+}
+
+#[test]
+fn ogg_opus() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let path = tmp_dir.path.join("voice.ogg");
+
+    // Quiet packets followed by loud ones, one of them spanning several segments
+    let packet_sizes = std::iter::repeat_n(20, 150).chain(std::iter::repeat_n(300, 50)).collect_vec();
+    create_named_file(&path, &make_ogg(b"OpusHead", &packet_sizes));
+
+    let waveform = compute_waveform(&path)?.unwrap();
+    assert_eq!(waveform.len(), WAVEFORM_BUCKETS);
+    assert!(waveform[..75].iter().all(|&v| v == 17));
+    assert!(waveform[75..].iter().all(|&v| v == 255));
+    Ok(())
+}
+
+#[test]
+fn unsupported() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+
+    let path = tmp_dir.path.join("audio.mp3");
+    create_named_file(&path, b"ID3\x04\x00\x00\x00\x00\x00\x00");
+    assert_eq!(compute_waveform(&path)?, None);
+
+    let path = tmp_dir.path.join("voice.ogg");
+    create_named_file(&path, &make_ogg(b"Speex   ", &[10, 20, 30]));
+    assert_eq!(compute_waveform(&path)?, None);
+
+    let path = tmp_dir.path.join("broken.ogg");
+    create_named_file(&path, b"OggS\x00\x02");
+    assert!(compute_waveform(&path).is_err());
+    Ok(())
+}
+
+#[test]
+fn computing_for_dataset() -> EmptyRes {
+    let users = (1..=2).map(|i| create_user(&ZERO_PB_UUID, i)).collect_vec();
+    let messages = (1..=3).map(|i| create_regular_message(i, 1)).collect_vec();
+    let cwms = vec![
+        ChatWithMessages { chat: create_personal_chat(&ZERO_PB_UUID, 2, &users[1], vec![1, 2], 3), messages },
+    ];
+    let mut holder = create_dao("Waveforms", users, cwms, |ds_root, msg| {
+        let path = format!("voice_{}.wav", msg.internal_id);
+        let waveform_option = if msg.internal_id == 200 { Some(vec![1, 2, 3]) } else { None };
+        if msg.internal_id != 300 {
+            create_named_file(&ds_root.to_absolute(&path), &make_wav(&[0, 100, -200, 50]));
+        }
+        let message::Typed::Regular(mr) = msg.typed_mut() else { unreachable!() };
+        mr.contents = vec![content!(VoiceMsg {
+            path_option: Some(path),
+            file_name_option: None,
+            mime_type: "audio/wav".to_owned(),
+            duration_sec_option: None,
+            waveform_option,
+        })];
+    });
+    let dao = holder.dao.as_mut();
+    let ds_uuid = dao.ds_uuid();
+
+    compute_waveforms(dao)?;
+
+    let waveforms = dao.cwms[&ds_uuid][0].messages.iter().map(|msg| {
+        let contents = coerce_enum!(msg.typed(), message::Typed::Regular(mr) => &mr.contents);
+        let voice_msg = coerce_enum!(contents[0].sealed_value_optional.as_ref(),
+                                     Some(content::SealedValueOptional::VoiceMsg(v)) => v);
+        voice_msg.waveform_option.clone()
+    }).collect_vec();
+    assert_eq!(waveforms, vec![
+        Some(vec![0, 128, 255, 64]),
+        // Already present, not recomputed
+        Some(vec![1, 2, 3]),
+        // File is missing
+        None,
+    ]);
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+/// Mono 16-bit PCM at 8 kHz
+fn make_wav(samples: &[i16]) -> Vec<u8> {
+    let data = samples.iter().flat_map(|s| s.to_le_bytes()).collect_vec();
+    let mut res = vec![];
+    res.extend_from_slice(b"RIFF");
+    res.extend_from_slice(&(4 + 8 + 16 + 8 + data.len() as u32).to_le_bytes());
+    res.extend_from_slice(b"WAVE");
+    res.extend_from_slice(b"fmt ");
+    res.extend_from_slice(&16_u32.to_le_bytes());
+    res.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+    res.extend_from_slice(&1_u16.to_le_bytes()); // Channels
+    res.extend_from_slice(&8000_u32.to_le_bytes()); // Sample rate
+    res.extend_from_slice(&16000_u32.to_le_bytes()); // Byte rate
+    res.extend_from_slice(&2_u16.to_le_bytes()); // Block align
+    res.extend_from_slice(&16_u16.to_le_bytes()); // Bits per sample
+    res.extend_from_slice(b"data");
+    res.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    res.extend_from_slice(&data);
+    res
+}
+
+/// Ogg stream with two header packets (first one starting with a given codec magic) followed by given audio packets,
+/// one packet per page. CRC is not filled in as it's not validated.
+fn make_ogg(codec_magic: &[u8], packet_sizes: &[usize]) -> Vec<u8> {
+    let mut packets = vec![[codec_magic, &[0; 11]].concat(), b"OpusTags".to_vec()];
+    packets.extend(packet_sizes.iter().map(|&size| vec![0xAA; size]));
+    let mut res = vec![];
+    for (seq_no, packet) in packets.iter().enumerate() {
+        let mut segments = vec![255_u8; packet.len() / 255];
+        segments.push((packet.len() % 255) as u8);
+        res.extend_from_slice(b"OggS");
+        res.push(0); // Version
+        res.push(if seq_no == 0 { 0x02 } else { 0x00 }); // Header type
+        res.extend_from_slice(&0_u64.to_le_bytes()); // Granule position
+        res.extend_from_slice(&0x1234_u32.to_le_bytes()); // Serial
+        res.extend_from_slice(&(seq_no as u32).to_le_bytes());
+        res.extend_from_slice(&0_u32.to_le_bytes()); // CRC
+        res.push(segments.len() as u8);
+        res.extend_from_slice(&segments);
+        res.extend_from_slice(packet);
+    }
+    res
+}
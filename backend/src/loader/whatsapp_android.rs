@@ -1,12 +1,14 @@
 use std::collections::hash_map::Entry;
+use std::fs;
 
-use ical::VcardParser;
 use lazy_static::lazy_static;
 use num_traits::FromPrimitive;
 use regex::Regex;
 use rusqlite::{Connection, OptionalExtension, Row, Statement};
 use super::*;
 use super::android::AndroidDataLoader;
+use super::avatars::FoundAvatars;
+use super::vcard::parse_vcard;
 
 #[cfg(test)]
 #[path = "whatsapp_android_tests.rs"]
@@ -20,10 +22,23 @@ lazy_static! {
 /// 1. msgstore.db and wa.db file should lie in either in the data root folder, or in ./databases subfolder
 /// 2. Media is resolved using <data_root>/Media
 /// 3. User avatars are looked up in <data_root>/files/Avatars
+/// 4. Historical group photos embedded in the database are extracted to <data_root>/_group_photos
+/// 5. Shared contacts vCards are extracted to <data_root>/_vcards
+/// 6. Encrypted msgstore.db.crypt14/crypt15 backups are decrypted into a temporary database first,
+///    see [crypt] for where the key comes from
 pub struct WhatsAppAndroidDataLoader;
 
 const NAME: &str = "WhatsApp";
+const AVATARS_DIR: &str = "files/Avatars";
+/// Own avatar, not present in the avatars folder
+const MY_AVATAR_FILE: &str = "files/me.jpg";
+/// Group photos stored as blobs in `message_system_photo_change` are extracted here
+const GROUP_PHOTOS_DIR: &str = "_group_photos";
+/// Contact cards stored in `message_vcard` are extracted here
+const VCARDS_DIR: &str = "_vcards";
 pub const DB_FILENAME: &str = "msgstore.db";
+const CRYPT14: &str = "crypt14";
+const CRYPT15: &str = "crypt15";
 
 type Jid = String;
 type MessageKey = String;
@@ -54,9 +69,14 @@ impl Users {
 impl AndroidDataLoader for WhatsAppAndroidDataLoader {
     const NAME: &'static str = NAME;
     const DB_FILENAME: &'static str = DB_FILENAME;
+    const ENCRYPTED_DB_EXTENSIONS: &'static [&'static str] = &[CRYPT14, CRYPT15];
 
     type Users = Users;
 
+    fn decrypt_db(&self, path: &Path, dst: &Path, user_input_requester: &dyn UserInputBlockingRequester) -> EmptyRes {
+        crypt::decrypt_db(path, dst, user_input_requester)
+    }
+
     fn tweak_conn(&self, path: &Path, conn: &Connection) -> EmptyRes {
         conn.execute(r#"ATTACH DATABASE ?1 AS wa_db"#, [path_to_str(&path.join("wa.db"))?])?;
         Ok(())
@@ -78,6 +98,30 @@ impl AndroidDataLoader for WhatsAppAndroidDataLoader {
         Ok(users)
     }
 
+    fn find_avatars(&self, dao: &InMemoryDao) -> Result<FoundAvatars> {
+        let ds_uuid = dao.datasets()?.remove(0).uuid;
+        let ds_root = dao.dataset_root(&ds_uuid)?;
+        let mut found = FoundAvatars::default();
+
+        let my_avatar = ds_root.to_absolute(MY_AVATAR_FILE);
+        if my_avatar.is_file() {
+            found.users.insert(dao.myself(&ds_uuid)?.id(), vec![my_avatar]);
+        }
+
+        let dir = ds_root.to_absolute(AVATARS_DIR);
+        if !dir.is_dir() { return Ok(found); }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() { continue; }
+            // Avatar file is named after JID, which is what both user and chat IDs are derived from
+            let Some(jid) = path.file_stem().and_then(|s| s.to_str()) else { continue; };
+            let id = hash_to_id(jid);
+            found.users.insert(UserId(id), vec![path.clone()]);
+            found.chats.insert(ChatId(id), path);
+        }
+        Ok(found)
+    }
+
     fn parse_users(&self, conn: &Connection, ds_uuid: &PbUuid, _path: &Path) -> Result<Users> {
         let mut users: Users = Default::default();
 
@@ -132,9 +176,9 @@ impl AndroidDataLoader for WhatsAppAndroidDataLoader {
     fn parse_chats(&self,
                    conn: &Connection,
                    ds_uuid: &PbUuid,
-                   _path: &Path,
+                   path: &Path,
                    users: &mut Users) -> Result<Vec<ChatWithMessages>> {
-        parse_chats(conn, ds_uuid, users)
+        parse_chats(conn, ds_uuid, path, users)
     }
 }
 
@@ -207,13 +251,17 @@ enum MessageType {
     DisappearTimerSet = 36,
     OneTimePhoto = 42,
     OneTimeVideo = 43,
+    /// Question is in `text_data`, options are in `message_poll_option`.
+    Poll = 46,
     VideoCall = 90,
 }
 
 #[repr(i32)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 enum SystemActionType {
-    /// Details are in `message_system_photo_change`, but it's not very useful
+    /// New subject is a message text, old one is in `message_system_value_change`
+    GroupSubjectChange = 1,
+    /// Details are in `message_system_photo_change`, new photo (if any) is stored as a blob
     GroupPhotoChange = 6,
     /// Details are in `message_system_group`
     GroupCreate = 11,
@@ -271,6 +319,9 @@ mod columns {
         pub const NAME: &str = "place_name";
         pub const ADDR: &str = "place_address";
         pub const DURATION: &str = "live_location_share_duration";
+        pub const FINAL_LAT: &str = "live_location_final_latitude";
+        pub const FINAL_LON: &str = "live_location_final_longitude";
+        pub const FINAL_TIMESTAMP: &str = "live_location_final_timestamp";
     }
 
     pub mod message_revoked {
@@ -278,6 +329,15 @@ mod columns {
         pub const REVOKE_TIMESTAMP: &str = "revoke_timestamp";
     }
 
+    pub mod message_poll_option {
+        pub const NAME: &str = "option_name";
+        pub const VOTE_TOTAL: &str = "vote_total";
+    }
+
+    pub mod message_system_photo_change {
+        pub const NEW_PHOTO: &str = "new_photo";
+    }
+
     pub mod call_logs {
         pub const TIMESTAMP: &str = "timestamp";
         pub const FROM_ME: &str = "from_me";
@@ -285,13 +345,16 @@ mod columns {
         pub const DURATION: &str = "duration";
     }
 
+    pub const MESSAGE_ROW_ID: &str = "message_row_id";
+    pub const IS_CHOSEN: &str = "is_chosen";
     pub const SENDER_JID: &str = "sender_jid";
     pub const GROUP_USER_JID: &str = "group_user_jid";
     pub const MIGRATE_USER_JID: &str = "migrate_user_jid";
     pub const PARENT_KEY_ID: &str = "parent_key_id";
+    pub const EPHEMERAL_DURATION: &str = "ephemeral_duration";
 }
 
-fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result<Vec<ChatWithMessages>> {
+fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, path: &Path, users: &mut Users) -> Result<Vec<ChatWithMessages>> {
     let mut cwms_map: HashMap<Jid, ChatWithMessages> = Default::default();
     let myself_id = users.myself_id.unwrap();
 
@@ -324,6 +387,10 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
                 // Subject is only set for group chats
                 (subject, ChatType::PrivateGroup)
             }
+            None if UserId(id) == myself_id => {
+                // "Message yourself" chat
+                (None, ChatType::SelfChat)
+            }
             None => {
                 let user = users.id_to_user.get(&UserId(id)).unwrap();
                 (user.pretty_name_option(), ChatType::Personal)
@@ -337,10 +404,12 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
                 name_option,
                 source_type: SourceType::WhatsappDb as i32,
                 tpe: tpe as i32,
-                img_path_option: Some(format!("files/Avatars/{jid}.j")),
+                img_path_option: Some(format!("{AVATARS_DIR}/{jid}.j")),
                 member_ids: vec![],
                 msg_count: 0, // Some messages might be filtered out later, so at this point we're leaving it unset
                 main_chat_id: None,
+                archived: None,
+                hidden: None,
             },
             messages: Vec::with_capacity(row.get::<_, usize>("msgs_count")?),
         });
@@ -356,10 +425,16 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
      * - For source_id, we're using hash of `message.key_id` and `call_log.call_id`.
      */
     let mut msgs_stmt = {
-        use columns::{*, chat::*, message::*, message_revoked::*};
+        use columns::{*, chat::*, message::*, message_revoked::*, message_system_photo_change::*};
         fn join_by_message_id(table_name: &str) -> String {
             format!("LEFT JOIN {table_name} ON {table_name}.message_row_id = message._id")
         }
+        // Older databases do not track disappearing messages
+        let (ephemeral_select, ephemeral_join) = if table_exists(conn, "message_ephemeral")? {
+            ("message_ephemeral.duration", join_by_message_id("message_ephemeral"))
+        } else {
+            ("NULL", "".to_owned())
+        };
         conn.prepare(&format!(
             r"SELECT
                   CASE
@@ -380,7 +455,9 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
                   message_system_group.is_me_joined,
                   group_user_jid.raw_string AS {GROUP_USER_JID},
                   migrate_user_jid.raw_string AS {MIGRATE_USER_JID},
-                  message_system_block_contact.is_blocked
+                  message_system_block_contact.is_blocked,
+                  message_system_photo_change.{NEW_PHOTO},
+                  {ephemeral_select} AS {EPHEMERAL_DURATION}
               FROM message
               INNER JOIN chat                  ON chat._id             = message.chat_row_id
               INNER JOIN jid  chat_jid         ON chat_jid._id         = chat.jid_row_id
@@ -397,6 +474,8 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
               {}
               {}
               {}
+              {}
+              {ephemeral_join}
               LEFT  JOIN jid  group_user_jid   ON group_user_jid._id   = message_system_chat_participant.user_jid_row_id
               LEFT  JOIN jid  migrate_user_jid ON migrate_user_jid._id = message_system_number_change.old_jid_row_id
               WHERE chat_jid.raw_string = ?1
@@ -408,9 +487,11 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
             },
             {
                 use columns::message_location::*;
-                let rest = [NAME, ADDR, DURATION].iter()
+                let rest = [NAME, ADDR, DURATION, FINAL_TIMESTAMP].iter()
                     .map(|c| format!("message_location.{c}")).join(", ");
-                format!("CAST(message_location.{LAT} AS text) AS {LAT}, CAST(message_location.{LON} AS text) AS {LON}, {rest}")
+                let as_text = [LAT, LON, FINAL_LAT, FINAL_LON].iter()
+                    .map(|c| format!("CAST(message_location.{c} AS text) AS {c}")).join(", ");
+                format!("{as_text}, {rest}")
             },
             join_by_message_id("message_edit_info"),
             join_by_message_id("message_quoted"),
@@ -424,8 +505,10 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
             join_by_message_id("message_system_chat_participant"),
             join_by_message_id("message_system_number_change"),
             join_by_message_id("message_system_block_contact"),
+            join_by_message_id("message_system_photo_change"),
         ))?
     };
+    let poll_options = parse_poll_options(conn)?;
     let mut calls_stmt = {
         use columns::*;
         conn.prepare(&format!(
@@ -463,7 +546,7 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
             // system messages even though sender JID points to the real actor.
             // If this is a personal chat, non-myself sender ID matches chat ID.
             let from_id: UserId = match chat_tpe {
-                ChatType::Personal =>
+                ChatType::Personal | ChatType::SelfChat =>
                     if from_me { myself_id } else { UserId(chat.id) },
                 ChatType::PrivateGroup => match sender_jid {
                     None => myself_id,
@@ -480,11 +563,11 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
             let (typed, text_column) = {
                 let result_option = match msg_tpe {
                     MessageType::System | MessageType::MissedCall =>
-                        parse_system_message(row, msg_tpe, users, &mut member_ids)?,
+                        parse_system_message(row, msg_tpe, path, users, &mut member_ids)?,
                     MessageType::VideoCall =>
                         None, // Will be processed when parsing call_rows
                     _ =>
-                        parse_regular_message(row, msg_tpe, &msg_key_to_source_id)?
+                        parse_regular_message(row, msg_tpe, path, &msg_key_to_source_id, &poll_options)?
                 };
                 match result_option {
                     Some(v) => v,
@@ -573,10 +656,55 @@ fn parse_chats(conn: &Connection, ds_uuid: &PbUuid, users: &mut Users) -> Result
         .collect_vec())
 }
 
+fn table_exists(conn: &Connection, name: &str) -> Result<bool> {
+    Ok(conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                      [name], |row| row.get::<_, i64>(0))? > 0)
+}
+
+/// Poll options by message row ID, in their original order.
+/// Older databases have no polls support, so missing tables yield no options.
+fn parse_poll_options(conn: &Connection) -> Result<HashMap<i64, Vec<ContentPollOption>, Hasher>> {
+    let mut result: HashMap<i64, Vec<ContentPollOption>, Hasher> = Default::default();
+    if !table_exists(conn, "message_poll_option")? {
+        return Ok(result);
+    }
+    // Own vote is stored as a poll vote add-on with selected options
+    let is_chosen_expr = if table_exists(conn, "message_add_on")? &&
+        table_exists(conn, "message_add_on_poll_vote_selected_option")? {
+        r"EXISTS(
+              SELECT 1 FROM message_add_on
+              INNER JOIN message_add_on_poll_vote_selected_option selected
+                ON selected.message_add_on_row_id = message_add_on._id
+              WHERE message_add_on.parent_message_row_id = message_poll_option.message_row_id
+                AND message_add_on.from_me = 1
+                AND selected.message_poll_option_id = message_poll_option._id
+          )"
+    } else {
+        "0"
+    };
+    use columns::{*, message_poll_option::*};
+    let mut stmt = conn.prepare(&format!(
+        r"SELECT {MESSAGE_ROW_ID}, {NAME}, {VOTE_TOTAL}, {is_chosen_expr} AS {IS_CHOSEN}
+          FROM message_poll_option
+          ORDER BY {MESSAGE_ROW_ID}, _id"
+    ))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        result.entry(row.get(MESSAGE_ROW_ID)?).or_default().push(ContentPollOption {
+            text: row.get(NAME)?,
+            voters_option: row.get(VOTE_TOTAL)?,
+            is_chosen: row.get(IS_CHOSEN)?,
+            is_correct: false,
+        });
+    }
+    Ok(result)
+}
+
 /// Returns `None` for rows that should be skipped.
 fn parse_system_message<'a>(
     row: &Row,
     msg_tpe: MessageType,
+    path: &Path,
     users: &'a mut Users,
     chat_member_ids: &mut HashSet<UserId, Hasher>,
 ) -> Result<Option<(message::Typed, Option<&'static str>)>> {
@@ -596,7 +724,8 @@ fn parse_system_message<'a>(
                     // Found a second reference to myself! Time to update
                     // Also, workaround for mut-immut borrowing from id_to_user
                     let user = users.id_to_user.get(&user_id)
-                        .unwrap_or_else(|| panic!("{}", row.get::<_, String>(column).unwrap())).clone();
+                        .with_context(|| format!("Unknown user {}", row.get::<_, String>(column).unwrap_or_default()))?
+                        .clone();
                     let myself_id = users.myself_id.unwrap();
                     chat_member_ids.insert(myself_id);
                     let myself: &mut User = users.id_to_user.get_mut(&myself_id).unwrap();
@@ -612,16 +741,33 @@ fn parse_system_message<'a>(
             };
 
             match action_type {
+                SystemActionType::GroupSubjectChange => {
+                    text_column = None; // Text is a new title
+                    GroupEditTitle(MessageServiceGroupEditTitle {
+                        title: row.get::<_, Option<String>>(columns::message::TEXT)?.unwrap_or_default(),
+                    })
+                }
                 SystemActionType::GroupPhotoChange => {
-                    // We only know some weird "new_photo_id" that leads nowhere
                     text_column = None; // Text is a new_photo_id
+                    let new_photo = row.get::<_, Option<Vec<u8>>>(columns::message_system_photo_change::NEW_PHOTO)?;
+                    let path_option = match new_photo {
+                        Some(data) if !data.is_empty() => {
+                            let key: MessageKey = row.get(columns::message::KEY)?;
+                            Some(extract_group_photo(path, &key, &data)?)
+                        }
+                        _ => None, // Photo was removed, or only the photo ID is known
+                    };
+                    let mime_type_option = path_option.as_ref().map(|_| "image/jpeg".to_owned());
                     GroupEditPhoto(MessageServiceGroupEditPhoto {
                         photo: ContentPhoto {
-                            path_option: None,
+                            path_option,
                             width: 0,
                             height: 0,
-                            mime_type_option: None,
+                            mime_type_option,
                             is_one_time: false,
+                            lat_str_option: None,
+                            lon_str_option: None,
+                            ocr_text_option: None,
                         }
                     })
                 }
@@ -675,11 +821,34 @@ fn parse_system_message<'a>(
     Ok(Some((message_service!(val), text_column)))
 }
 
+/// Writes group photo blob to a file (unless it's already there), returns its path relative to data root.
+fn extract_group_photo(path: &Path, key: &MessageKey, data: &[u8]) -> Result<String> {
+    let rel_path = format!("{GROUP_PHOTOS_DIR}/{key}.jpg");
+    let full_path = path.join(&rel_path);
+    if !full_path.exists() {
+        fs::create_dir_all(full_path.parent().unwrap())?;
+        fs::write(&full_path, data)?;
+    }
+    Ok(rel_path)
+}
+
+fn extract_vcard(path: &Path, key: &MessageKey, vcard: &str) -> Result<String> {
+    let rel_path = format!("{VCARDS_DIR}/{key}.vcf");
+    let full_path = path.join(&rel_path);
+    if !full_path.exists() {
+        fs::create_dir_all(full_path.parent().unwrap())?;
+        fs::write(&full_path, vcard)?;
+    }
+    Ok(rel_path)
+}
+
 /// Returns `None` for rows that should be skipped.
 fn parse_regular_message(
     row: &Row,
     msg_tpe: MessageType,
+    path: &Path,
     msg_key_to_source_id: &HashMap<MessageKey, i64, Hasher>,
+    poll_options: &HashMap<i64, Vec<ContentPollOption>, Hasher>,
 ) -> Result<Option<(message::Typed, Option<&'static str>)>> {
     let mut text_column = Some(columns::message::TEXT);
 
@@ -710,6 +879,9 @@ fn parse_regular_message(
                 height: get_mandatory_height!(),
                 mime_type_option,
                 is_one_time: false,
+                lat_str_option: None,
+                lon_str_option: None,
+                ocr_text_option: None,
             })],
         MessageType::OneTimePhoto => {
             text_column = None;
@@ -719,6 +891,9 @@ fn parse_regular_message(
                 height: get_mandatory_height!(),
                 mime_type_option,
                 is_one_time: true,
+                lat_str_option: None,
+                lon_str_option: None,
+                ocr_text_option: None,
             })]
         }
         MessageType::Audio => {
@@ -728,6 +903,7 @@ fn parse_regular_message(
                 file_name_option,
                 mime_type: mime_type_option.expect("MIME type missing"),
                 duration_sec_option: get_zero_as_null(row, columns::message_media::DURATION)?,
+                waveform_option: None,
             })]
         }
         MessageType::Video | MessageType::AnimatedGif => {
@@ -786,12 +962,17 @@ fn parse_regular_message(
                 mime_type_option,
                 thumbnail_path_option: None,
                 emoji_option: None,
+                pack_id_option: None,
+                pack_name_option: None,
             })]
         }
         MessageType::ContactVcard => {
             text_column = None; // Text is a contact name, we have it already
-            let vcard = parse_vcard(&row.get::<_, String>("vcard")?)?;
-            vec![content!(SharedContact { ..vcard })]
+            let vcard_string = row.get::<_, String>("vcard")?;
+            let vcard = parse_vcard(&vcard_string)?;
+            let key: MessageKey = row.get(columns::message::KEY)?;
+            let vcard_path_option = Some(extract_vcard(path, &key, &vcard_string)?);
+            vec![content!(SharedContact { vcard_path_option, ..vcard })]
         }
         MessageType::StaticLocation | MessageType::LiveLocation => {
             // Since there's no point in having more than 8 precision digits, we're only storing 8.
@@ -802,12 +983,35 @@ fn parse_regular_message(
                     _ => str
                 }
             }
+            // Only the last known position of a live location is stored, besides the initial one.
+            let final_lat: Option<String> = row.get(columns::message_location::FINAL_LAT)?;
+            let final_lon: Option<String> = row.get(columns::message_location::FINAL_LON)?;
+            let final_ts = get_zero_as_null_i64(row, columns::message_location::FINAL_TIMESTAMP)?;
+            let updates = match (final_lat, final_lon, final_ts) {
+                (Some(lat), Some(lon), Some(ts)) => vec![ContentLocationPoint {
+                    lat_str: reduce_precision(lat),
+                    lon_str: reduce_precision(lon),
+                    timestamp: ts / 1000,
+                }],
+                _ => vec![],
+            };
             vec![content!(Location {
                 title_option: row.get(columns::message_location::NAME)?,
                 address_option: row.get(columns::message_location::ADDR)?,
                 lat_str: reduce_precision(row.get(columns::message_location::LAT)?),
                 lon_str: reduce_precision(row.get(columns::message_location::LON)?),
                 duration_sec_option: row.get(columns::message_location::DURATION)?,
+                updates,
+            })]
+        }
+        MessageType::Poll => {
+            text_column = None; // Text is a poll question
+            let options = poll_options.get(&row.get::<_, i64>("_id")?).cloned().unwrap_or_default();
+            vec![content!(Poll {
+                question: row.get::<_, Option<String>>(columns::message::TEXT)?.unwrap_or_default(),
+                options,
+                is_quiz: None,
+                total_voters_option: None,
             })]
         }
         MessageType::Deleted => {
@@ -843,6 +1047,8 @@ fn parse_regular_message(
         edit_timestamp_option: row.get::<_, Option<i64>>(edit_timestamp_col)?.map(|ts| ts / 1000),
         is_deleted,
         forward_from_name_option,
+        forward_from_id_option: None,
+        ephemeral_duration_sec_option: get_zero_as_null(row, columns::EPHEMERAL_DURATION)?,
         reply_to_message_id_option,
         contents,
     }, text_column)))
@@ -852,25 +1058,156 @@ fn get_zero_as_null(row: &Row, col_name: &str) -> Result<Option<i32>> {
     Ok(row.get::<_, Option<i32>>(col_name)?.filter(|&i| i != 0))
 }
 
-fn parse_vcard(vcard: &str) -> Result<ContentSharedContact> {
-    let mut vcard = VcardParser::new(BufReader::new(vcard.as_bytes()));
-    let vcard = vcard.next().unwrap()?;
-
-    let full_name = vcard.properties.iter()
-        .find(|p| p.name == "FN")
-        .and_then(|p| p.value.clone())
-        .expect("Name not found for vcard!");
-
-    let phone_number = vcard.properties.iter()
-        .filter(|p| p.name.split('.').contains(&"TEL"))
-        .find(|p| p.params.as_ref().is_some_and(|params| params.iter().any(|(k, _)| k == "WAID")))
-        .and_then(|p| p.value.clone())
-        .expect("Phone number not found for vcard!");
-
-    Ok(ContentSharedContact {
-        first_name_option: Some(full_name),
-        last_name_option: None,
-        phone_number_option: Some(phone_number),
-        vcard_path_option: None,
-    })
+fn get_zero_as_null_i64(row: &Row, col_name: &str) -> Result<Option<i64>> {
+    Ok(row.get::<_, Option<i64>>(col_name)?.filter(|&i| i != 0))
+}
+
+/// Decryption of msgstore.db.crypt14 and msgstore.db.crypt15 backups, as done by
+/// https://github.com/ElDavoo/wa-crypt-tools.
+///
+/// Backup is a protobuf header (holding the IV) followed by zlib-compressed database encrypted by AES-256-GCM,
+/// optionally followed by an MD5 checksum.
+///
+/// Key is looked up in `key` or `encrypted_backup.key` files in the databases folder, data root or
+/// `<data_root>/files`, otherwise user is asked for it:
+/// - crypt14 key is the last 32 bytes of `/data/data/com.whatsapp/files/key`,
+/// - crypt15 is encrypted by a key derived from a root key, which is either a 64-digit hex key shown when
+///   end-to-end encrypted backups were turned on, or the last 32 bytes of `encrypted_backup.key`.
+mod crypt {
+    use std::io::Read;
+
+    use aes::Aes256;
+    use aes_gcm::AesGcm;
+    use aes_gcm::aead::{Aead, KeyInit};
+    use aes_gcm::aead::consts::U16;
+    use aes_gcm::aead::generic_array::GenericArray;
+    use flate2::read::ZlibDecoder;
+    use hmac::{Hmac, Mac};
+    use prost::Message;
+    use sha2::Sha256;
+
+    use super::*;
+
+    pub type Aes256Gcm16 = AesGcm<Aes256, U16>;
+
+    const KEY_FILENAMES: &[&str] = &["key", "encrypted_backup.key"];
+    const KEY_LENGTH: usize = 32;
+    const IV_LENGTH: usize = 16;
+    const CHECKSUM_LENGTH: usize = 16;
+    /// Precedes the protobuf header if backup has a feature table
+    const FEATURES_FLAG: u8 = 0x01;
+    const HKDF_INFO: &[u8] = b"backup encryption";
+    const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct BackupPrefix {
+        #[prost(message, optional, tag = "2")]
+        pub c14_cipher: Option<C14Cipher>,
+        #[prost(message, optional, tag = "3")]
+        pub c15_iv: Option<C15Iv>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct C14Cipher {
+        #[prost(bytes = "vec", tag = "5")]
+        pub iv: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct C15Iv {
+        #[prost(bytes = "vec", tag = "1")]
+        pub iv: Vec<u8>,
+    }
+
+    pub fn decrypt_db(path: &Path, dst: &Path, user_input_requester: &dyn UserInputBlockingRequester) -> EmptyRes {
+        let is_crypt15 = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(CRYPT14) => false,
+            Some(CRYPT15) => true,
+            _ => bail!("Unknown encrypted database format: {}", path.display()),
+        };
+
+        let key = read_key(path, user_input_requester)?;
+        let key = if is_crypt15 { derive_crypt15_key(&key) } else { key };
+
+        let data = fs::read(path)?;
+        let (iv, ciphertext) = parse_header(&data, is_crypt15)?;
+
+        let cipher = Aes256Gcm16::new(GenericArray::from_slice(&key));
+        let nonce = GenericArray::from_slice(&iv);
+        // Ciphertext (with GCM tag at the end) might be followed by a checksum
+        let compressed = ciphertext.len().checked_sub(CHECKSUM_LENGTH)
+            .and_then(|len| cipher.decrypt(nonce, &ciphertext[..len]).ok())
+            .or_else(|| cipher.decrypt(nonce, ciphertext).ok())
+            .ok_or_else(|| anyhow!("Failed to decrypt {}, is the key correct?", path.display()))?;
+
+        let mut decompressed = Vec::with_capacity(compressed.len() * 2);
+        ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed)
+            .context("Failed to decompress decrypted database")?;
+        ensure!(decompressed.starts_with(SQLITE_HEADER), "Decrypted file is not an SQLite database");
+
+        fs::write(dst, decompressed)?;
+        Ok(())
+    }
+
+    fn read_key(path: &Path, user_input_requester: &dyn UserInputBlockingRequester) -> Result<[u8; KEY_LENGTH]> {
+        let dir = path.parent().unwrap();
+        let root_dirs = [Some(dir), dir.parent()];
+        let key_file = root_dirs.iter().flatten()
+            .flat_map(|root| [root.to_path_buf(), root.join("files")])
+            .flat_map(|root| KEY_FILENAMES.iter().map(move |name| root.join(name)))
+            .find(|p| p.is_file());
+        if let Some(key_file) = key_file {
+            log::info!("Using key file {}", key_file.display());
+            return read_key_file(&key_file);
+        }
+
+        let input = user_input_requester.ask_for_text("\
+            Input 64-digit hex key of WhatsApp end-to-end encrypted backup,\n\
+            or a path to the key file (/data/data/com.whatsapp/files/key).\n\
+        ".trim())?;
+        let input = input.trim();
+        if input.len() == KEY_LENGTH * 2 && input.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(hex::decode(input)?.try_into().unwrap())
+        } else if Path::new(input).is_file() {
+            read_key_file(Path::new(input))
+        } else {
+            bail!("Expected a 64-digit hex key or a path to the key file")
+        }
+    }
+
+    /// Key is stored in the last bytes of a serialized Java object
+    fn read_key_file(path: &Path) -> Result<[u8; KEY_LENGTH]> {
+        let bytes = fs::read(path)?;
+        ensure!(bytes.len() >= KEY_LENGTH, "Key file {} is too short", path.display());
+        Ok(bytes[(bytes.len() - KEY_LENGTH)..].try_into().unwrap())
+    }
+
+    /// HKDF-SHA256 with zero salt
+    pub fn derive_crypt15_key(root_key: &[u8; KEY_LENGTH]) -> [u8; KEY_LENGTH] {
+        let hmac = |key: &[u8], parts: &[&[u8]]| {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+            parts.iter().for_each(|p| mac.update(p));
+            mac.finalize().into_bytes()
+        };
+        let prk = hmac(&[0u8; 32], &[root_key]);
+        hmac(&prk, &[HKDF_INFO, &[1u8]]).as_slice().try_into().unwrap()
+    }
+
+    fn parse_header(data: &[u8], is_crypt15: bool) -> Result<(Vec<u8>, &[u8])> {
+        ensure!(data.len() > 2, "Encrypted database is too short");
+        let header_len = data[0] as usize;
+        let offset = if data[1] == FEATURES_FLAG { 2 } else { 1 };
+        let data_offset = offset + header_len;
+        ensure!(data.len() > data_offset, "Encrypted database is too short");
+
+        let prefix = BackupPrefix::decode(&data[offset..data_offset]).context("Malformed backup header")?;
+        let iv = if is_crypt15 {
+            prefix.c15_iv.map(|c| c.iv)
+        } else {
+            prefix.c14_cipher.map(|c| c.iv)
+        };
+        let iv = iv.ok_or_else(|| anyhow!("Backup header has no IV"))?;
+        ensure!(iv.len() == IV_LENGTH, "Backup header has IV of unexpected length {}", iv.len());
+        Ok((iv, &data[data_offset..]))
+    }
 }
@@ -1,12 +1,18 @@
 #![allow(unused_imports)]
 
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::aead::generic_array::GenericArray;
 use chrono::prelude::*;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
 use lazy_static::lazy_static;
 use pretty_assertions::{assert_eq, assert_ne};
+use prost::Message as ProstMessage;
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::entity_utils::*;
 use crate::protobuf::history::content::SealedValueOptional::*;
 use crate::protobuf::history::message::*;
@@ -14,6 +20,7 @@ use crate::protobuf::history::message_service::SealedValueOptional::*;
 use crate::protobuf::history::User;
 
 use super::*;
+use super::crypt::*;
 
 const RESOURCE_DIR: &str = "whatsapp-android";
 const LOADER: WhatsAppAndroidDataLoader = WhatsAppAndroidDataLoader;
@@ -22,68 +29,13 @@ const LOADER: WhatsAppAndroidDataLoader = WhatsAppAndroidDataLoader;
 // Tests
 //
 
-#[test]
-fn vcards() -> EmptyRes {
-    fn parse(vcard_string: &str) -> Result<ContentSharedContact> {
-        parse_vcard(&trim_vcard_string(vcard_string))
-    }
-    fn vc(first_name: &str, phone: &str) -> ContentSharedContact {
-        ContentSharedContact {
-            first_name_option: Some(first_name.to_owned()),
-            last_name_option: None,
-            phone_number_option: Some(phone.to_owned()),
-            vcard_path_option: None,
-        }
-    }
-
-    assert_eq!(parse(r"
-        BEGIN:VCARD
-        VERSION:3.0
-        N:;Name (comment);;;
-        FN:Name (comment)
-        TEL;type=Mobile;waid=112223456543:+11 222-3456-543
-        END:VCARD
-    ")?, vc("Name (comment)", "+11 222-3456-543"));
-
-    assert_eq!(parse(r"
-        BEGIN:VCARD
-        VERSION:3.0
-        N:Name3;Name1;Name2;;
-        FN:Name1 Name2 Name3
-        TEL;type=Home:+12 345-6789-8765
-        TEL;type=Mobile;waid=9876543212345:+98 765-4321-2345
-        END:VCARD
-    ")?, vc("Name1 Name2 Name3", "+98 765-4321-2345"));
-
-    assert_eq!(parse(r"
-        BEGIN:VCARD
-        VERSION:3.0
-        N:;+11 222-3333-4444;;;
-        FN:+11 222-3333-4444
-        TEL;type=CELL;waid=1122233334444:+11 222-3333-4444
-        X-WA-BIZ-NAME:+11 222-3333-4444
-        X-WA-BIZ-DESCRIPTION:My Fancy Description!
-        END:VCARD
-    ")?, vc("+11 222-3333-4444", "+11 222-3333-4444"));
-
-    assert_eq!(parse(r"
-        BEGIN:VCARD
-        VERSION:3.0
-        N:Name;Full;;;
-        FN:Full Name
-        item1.TEL;waid=1122233334444:+11 222-3333-4444
-        item1.X-ABLabel:Ponsel
-        X-WA-BIZ-DESCRIPTION:My Fancy Description!
-        X-WA-BIZ-NAME:Full Name
-        END:VCARD
-    ")?, vc("Full Name", "+11 222-3333-4444"));
-
-    Ok(())
-}
-
 #[test]
 fn loading_2023_10() -> EmptyRes {
-    let (res, _db_dir) = test_android::create_databases(RESOURCE_DIR, "2023-10", ".db", DB_FILENAME);
+    let (res, db_dir) = test_android::create_databases(RESOURCE_DIR, "2023-10", ".db", DB_FILENAME);
+    // Group photos are extracted next to the databases folder, this will clean them up
+    let group_photos_dir = db_dir.path.parent().unwrap().join(GROUP_PHOTOS_DIR);
+    if group_photos_dir.exists() { fs::remove_dir_all(&group_photos_dir)?; }
+    let group_photos_dir = TmpDir::new_at(group_photos_dir);
     LOADER.looks_about_right(&res)?;
 
     let dao = LOADER.load(&res, &client::NoChooser)?;
@@ -117,8 +69,10 @@ fn loading_2023_10() -> EmptyRes {
             tpe: ChatType::PrivateGroup as i32,
             img_path_option: Some("files/Avatars/100000000000000001@g.us.j".to_owned()),
             member_ids: vec![myself.id, member.id],
-            msg_count: 2,
+            msg_count: 5,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -131,12 +85,78 @@ fn loading_2023_10() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: myself.pretty_name(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_service!(GroupInviteMembers(MessageServiceGroupInviteMembers {
                 members: vec![myself.pretty_name()],
             }))),
         });
         assert_eq!(msgs[1], Message {
             internal_id: 1,
+            source_id_option: Some(hash_to_id("GROUPMSG00400")),
+            timestamp: 1650000000,
+            from_id: member.id,
+            text: vec![],
+            searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
+            typed: Some(message_service!(GroupEditTitle(MessageServiceGroupEditTitle {
+                title: "My Group".to_owned(),
+            }))),
+        });
+        let photo_path = format!("{GROUP_PHOTOS_DIR}/GROUPMSG00500.jpg");
+        assert_eq!(msgs[2], Message {
+            internal_id: 2,
+            source_id_option: Some(hash_to_id("GROUPMSG00500")),
+            timestamp: 1655000000,
+            from_id: member.id,
+            text: vec![],
+            searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
+            typed: Some(message_service!(GroupEditPhoto(MessageServiceGroupEditPhoto {
+                photo: ContentPhoto {
+                    path_option: Some(photo_path),
+                    width: 0,
+                    height: 0,
+                    mime_type_option: Some("image/jpeg".to_owned()),
+                    is_one_time: false,
+                    lat_str_option: None,
+                    lon_str_option: None,
+                    ocr_text_option: None,
+                }
+            }))),
+        });
+        assert!(group_photos_dir.path.join("GROUPMSG00500.jpg").exists());
+        assert_eq!(msgs[3], Message {
+            internal_id: 3,
+            source_id_option: Some(hash_to_id("GROUPMSG00600")),
+            timestamp: 1658000000,
+            from_id: member.id,
+            text: vec![],
+            searchable_string: "Lunch? Pizza Sushi".to_owned(),
+            topic_option: None,
+            text_history: vec![],
+            typed: Some(message_regular! {
+                edit_timestamp_option: None,
+                is_deleted: false,
+                forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
+                reply_to_message_id_option: None,
+                contents: vec![content!(Poll {
+                    question: "Lunch?".to_owned(),
+                    options: vec![
+                        ContentPollOption { text: "Pizza".to_owned(), voters_option: Some(1), is_chosen: false, is_correct: false },
+                        ContentPollOption { text: "Sushi".to_owned(), voters_option: Some(2), is_chosen: true, is_correct: false },
+                    ],
+                    is_quiz: None,
+                    total_voters_option: None,
+                })],
+            }),
+        });
+        assert_eq!(msgs[4], Message {
+            internal_id: 4,
             source_id_option: Some(4824408779253713719),
             timestamp: 1661417508,
             from_id: myself.id,
@@ -149,10 +169,14 @@ fn loading_2023_10() -> EmptyRes {
                 },
             ],
             searchable_string: "Last group message".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: Some(1661417955),
                 is_deleted: false,
                 forward_from_name_option: Some(SOMEONE.to_owned()),
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: msgs[0].source_id_option,
                 contents: vec![],
             }),
@@ -172,6 +196,8 @@ fn loading_2023_10() -> EmptyRes {
             member_ids: vec![myself.id, member.id],
             msg_count: 2,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -184,10 +210,14 @@ fn loading_2023_10() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "Jl. Gurita No.21x, Denpasar, Bali New Bahari -8.70385650 115.21673666".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Location {
@@ -196,6 +226,11 @@ fn loading_2023_10() -> EmptyRes {
                         lat_str: "-8.70385650".to_string(),
                         lon_str: "115.21673666".to_string(),
                         duration_sec_option: Some(123),
+                        updates: vec![ContentLocationPoint {
+                            lat_str: "-8.70412345".to_string(),
+                            lon_str: "115.21701234".to_string(),
+                            timestamp: 1687757290,
+                        }],
                     })
                 ],
             }),
@@ -208,10 +243,14 @@ fn loading_2023_10() -> EmptyRes {
             from_id: myself.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: Some(1693993963),
                 is_deleted: true,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![],
             }),
@@ -220,10 +259,80 @@ fn loading_2023_10() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_2023_10_encrypted() -> EmptyRes {
+    let (res, db_dir) = test_android::create_databases(RESOURCE_DIR, "2023-10", ".db", DB_FILENAME);
+    let group_photos_dir = db_dir.path.parent().unwrap().join(GROUP_PHOTOS_DIR);
+    if group_photos_dir.exists() { fs::remove_dir_all(&group_photos_dir)?; }
+    let _group_photos_dir = TmpDir::new_at(group_photos_dir);
+
+    let plain_dao = *LOADER.load(&res, &client::NoChooser)?;
+    let db_bytes = fs::read(&res)?;
+
+    // crypt15, root key is asked for
+    let root_key = [7u8; 32];
+    let crypt15_path = db_dir.path.join(format!("{DB_FILENAME}.{CRYPT15}"));
+    write_encrypted_db(&crypt15_path, &db_bytes, &derive_crypt15_key(&root_key), true)?;
+    LOADER.looks_about_right(&crypt15_path)?;
+
+    let input = client::PredefinedInput { myself_id: None, text: Some(hex::encode(root_key)) };
+    assert_same_content(&plain_dao, &*LOADER.load(&crypt15_path, &input)?);
+
+    let input = client::PredefinedInput { myself_id: None, text: Some("00".repeat(32)) };
+    let err = LOADER.load(&crypt15_path, &input).err().expect("wrong key must be rejected");
+    assert!(format!("{err:?}").contains("is the key correct"), "{err:?}");
+
+    let input = client::PredefinedInput { myself_id: None, text: Some("12345".to_owned()) };
+    let err = LOADER.load(&crypt15_path, &input).err().expect("malformed key must be rejected");
+    assert!(format!("{err:?}").contains("64-digit hex key"), "{err:?}");
+
+    // crypt14, key file lies next to it
+    let key = [9u8; 32];
+    let crypt14_path = db_dir.path.join(format!("{DB_FILENAME}.{CRYPT14}"));
+    write_encrypted_db(&crypt14_path, &db_bytes, &key, false)?;
+    fs::write(db_dir.path.join("key"), [vec![0u8; 126], key.to_vec()].concat())?;
+    LOADER.looks_about_right(&crypt14_path)?;
+    assert_same_content(&plain_dao, &*LOADER.load(&crypt14_path, &client::NoChooser)?);
+    Ok(())
+}
+
 //
 // Helpers
 //
 
+/// crypt15 is written with a feature table flag and a checksum, crypt14 - without them.
+fn write_encrypted_db(path: &Path, db_bytes: &[u8], key: &[u8; 32], is_crypt15: bool) -> EmptyRes {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(db_bytes)?;
+    let compressed = encoder.finish()?;
+
+    let iv = vec![3u8; 16];
+    let cipher = Aes256Gcm16::new(GenericArray::from_slice(key));
+    let encrypted = cipher.encrypt(GenericArray::from_slice(&iv), compressed.as_slice()).unwrap();
+
+    let prefix = if is_crypt15 {
+        BackupPrefix { c14_cipher: None, c15_iv: Some(C15Iv { iv }) }
+    } else {
+        BackupPrefix { c14_cipher: Some(C14Cipher { iv }), c15_iv: None }
+    }.encode_to_vec();
+
+    let mut bytes = vec![prefix.len() as u8];
+    if is_crypt15 { bytes.push(0x01); }
+    bytes.extend(prefix);
+    bytes.extend(encrypted);
+    if is_crypt15 { bytes.extend([0u8; 16]); }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+fn assert_same_content(expected: &InMemoryDao, actual: &InMemoryDao) {
+    let ds_uuid = actual.ds_uuid();
+    assert_eq!(actual.users_single_ds(),
+               expected.users_single_ds().into_iter().map(|u| User { ds_uuid: ds_uuid.clone(), ..u }).collect_vec());
+    assert_eq!(actual.cwms_single_ds().into_iter().map(|cwm| cwm.messages).collect_vec(),
+               expected.cwms_single_ds().into_iter().map(|cwm| cwm.messages).collect_vec());
+}
+
 fn expected_myself(ds_uuid: &PbUuid) -> User {
     User {
         ds_uuid: ds_uuid.clone(),
@@ -236,6 +345,3 @@ fn expected_myself(ds_uuid: &PbUuid) -> User {
     }
 }
 
-fn trim_vcard_string(s: &str) -> String {
-    s.trim().lines().map(|s| s.trim()).join("\n")
-}
@@ -1,6 +1,8 @@
 use std::fs;
+use std::fs::File;
+use std::path::PathBuf;
 
-use chrono::{NaiveDateTime, TimeZone};
+use chrono::{Datelike, NaiveDateTime, TimeZone};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -13,39 +15,134 @@ use crate::prelude::*;
 #[path = "whatsapp_text_tests.rs"]
 mod tests;
 
-const TIMESTAMP_REGEX_STR: &str = r"^(\d{1,2}/\d{1,2}/\d{1,4}, \d{2}:\d{2})";
+/// Covers Android (`6/30/20, 16:14 - `) and iOS (`[30.06.20, 4:14:05 PM] `) layouts with any date separator.
+const TIMESTAMP_REGEX_STR: &str =
+    r"^\[?(\d{1,4}[./-]\d{1,2}[./-]\d{1,4},? \d{1,2}[:.]\d{2}(?:[:.]\d{2})?(?:[ \u{202f}\u{a0}]?[AaPp]\.? ?[Mm]\.?)?)\]?";
+
+/// Name of the chat file inside iOS export archive, chat name is then taken from the archive name.
+const IOS_CHAT_FILENAME: &str = "_chat.txt";
+
+/// Left-to-right mark, iOS prefixes system lines and attachments with it.
+const LRM: char = '\u{200e}';
+
+/// Placeholders used instead of media when chat is exported without media, in various languages.
+const MEDIA_OMITTED_LINES: &[&str] = &[
+    "null",
+    "<Media omitted>",
+    "<Medien ausgeschlossen>",
+    "<Media weggelaten>",
+    "<Multimedia omitido>",
+    "<Médias omis>",
+    "<Без медиафайлов>",
+    "<Мультимедиа отсутствует>",
+];
+
+/// Whole text of a deleted message, whether it was deleted by myself or by the other party.
+const DELETED_LINES: &[&str] = &[
+    "This message was deleted",
+    "This message was deleted.",
+    "You deleted this message",
+    "You deleted this message.",
+];
+
+/// Appended to the last line of an edited message.
+const EDITED_MARKER: &str = "<This message was edited>";
 
 lazy_static! {
-    static ref FILENAME_REGEX: Regex = Regex::new(r"^WhatsApp Chat with ([^.]+)\.txt$").unwrap();
+    static ref FILENAME_REGEX: Regex = Regex::new(r"^WhatsApp Chat (?:with|-) (.+)\.(?:txt|zip)$").unwrap();
     static ref TIMESTAMP_REGEX: Regex = Regex::new(TIMESTAMP_REGEX_STR).unwrap();
-    static ref MESSAGE_PREFIX_REGEX: Regex = Regex::new(&format!("{}{}", TIMESTAMP_REGEX_STR, " - ([^:]+): (.+)$")).unwrap();
-    static ref ATTACHED_FILE_REGEX: Regex = Regex::new(r"^(([^-]+)-[^ ]+) \(file attached\)$").unwrap();
+    static ref MESSAGE_PREFIX_REGEX: Regex = Regex::new(&format!("{}{}", TIMESTAMP_REGEX_STR, " (?:- )?([^:]+): (.+)$")).unwrap();
+    static ref ATTACHED_FILE_REGEX: Regex = Regex::new(r"^(.+) \(file attached\)$").unwrap();
+    static ref IOS_ATTACHED_FILE_REGEX: Regex = Regex::new(r"^<attached: (.+)>$").unwrap();
+    /// Poll option with its vote count, e.g. `OPTION: Pizza (2 votes)`
+    static ref POLL_OPTION_REGEX: Regex = Regex::new(r"^OPTION: (.*) \((\d+) votes?\)$").unwrap();
+    static ref IOS_MEDIA_OMITTED_REGEX: Regex = Regex::new(r"^(image|video|audio|sticker|GIF|document|Contact card) omitted$").unwrap();
+    /// Android attachment name, e.g. `IMG-20230630-WA0000.jpg`
+    static ref ANDROID_MEDIA_NAME_REGEX: Regex = Regex::new(r"^([A-Z]+)-\d{8}-WA\d+").unwrap();
+    /// iOS attachment name, e.g. `00000012-PHOTO-2020-06-30-16-14-05.jpg`
+    static ref IOS_MEDIA_NAME_REGEX: Regex = Regex::new(r"^\d+-([A-Z]+)-").unwrap();
 }
 
+/// Loads a chat exported through WhatsApp "Export chat" feature.
+/// Either a text file (`WhatsApp Chat with X.txt`, or `_chat.txt` in `WhatsApp Chat - X` folder) or
+/// a ZIP archive with it and the media is accepted, archive is then unpacked alongside.
 pub struct WhatsAppTextDataLoader;
 
 impl DataLoader for WhatsAppTextDataLoader {
     fn name(&self) -> String { "WhatsApp (text)".to_owned() }
 
     fn looks_about_right_inner(&self, path: &Path) -> EmptyRes {
-        let filename = path_file_name(path)?;
-        if !FILENAME_REGEX.is_match(filename) {
-            bail!("File is not \"WhatsApp Chat with X.txt\"");
+        if is_zip(path) {
+            let filename = path_file_name(path)?;
+            if !FILENAME_REGEX.is_match(filename) {
+                bail!("File is not \"WhatsApp Chat with X.zip\"");
+            }
+            let archive = zip::ZipArchive::new(File::open(path)?)?;
+            if !archive.file_names().any(|n| n.ends_with(".txt")) {
+                bail!("Archive does not contain a chat text file");
+            }
+            return Ok(());
         }
-        if !TIMESTAMP_REGEX.is_match(super::first_line(path)?.as_str()) {
+        other_name_from_path(path)?;
+        let first_line = super::first_line(path)?;
+        if !TIMESTAMP_REGEX.is_match(first_line.trim_start_matches(['\u{feff}', LRM])) {
             bail!("File does not start with a timestamp as expected");
         }
         Ok(())
     }
 
     fn load_inner(&self, path: &Path, ds: Dataset, _user_input_requester: &dyn UserInputBlockingRequester) -> Result<Box<InMemoryDao>> {
-        parse_whatsapp_text_file(path, ds)
+        if is_zip(path) {
+            let txt_path = unpack_zip(path)?;
+            parse_whatsapp_text_file(&txt_path, &other_name_from_path(path)?, ds)
+        } else {
+            parse_whatsapp_text_file(path, &other_name_from_path(path)?, ds)
+        }
+    }
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Name of the other party is taken from file name, or from parent folder name for iOS `_chat.txt`.
+fn other_name_from_path(path: &Path) -> Result<String> {
+    let filename = path_file_name(path)?;
+    let filename = if filename == IOS_CHAT_FILENAME {
+        let parent = path.parent().context("No parent directory")?;
+        format!("{}.txt", path_file_name(parent)?)
+    } else {
+        filename.to_owned()
+    };
+    match FILENAME_REGEX.captures(&filename) {
+        Some(captures) => Ok(captures.get(1).unwrap().as_str().to_owned()),
+        None => err!("File is not \"WhatsApp Chat with X.txt\""),
+    }
+}
+
+/// Unpacks archive into a sibling directory named after it (unless already done),
+/// returns the path to unpacked chat text file.
+fn unpack_zip(zip_path: &Path) -> Result<PathBuf> {
+    let target_dir = zip_path.with_extension("");
+    if !target_dir.exists() {
+        log::info!("Unpacking {} into {}", zip_path.display(), target_dir.display());
+        let mut archive = zip::ZipArchive::new(File::open(zip_path)?)?;
+        archive.extract(&target_dir)
+            .with_context(|| format!("Cannot unpack {}", zip_path.display()))?;
+    }
+    let txt_paths = list_all_files(&target_dir, false)?.into_iter()
+        .filter(|p| p.extension().is_some_and(|ext| ext == "txt"))
+        .collect_vec();
+    match txt_paths.as_slice() {
+        [txt_path] => Ok(txt_path.clone()),
+        _ => err!("Expected exactly one chat text file in {}, found {}", target_dir.display(), txt_paths.len()),
     }
 }
 
-fn parse_whatsapp_text_file(path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>> {
+fn parse_whatsapp_text_file(path: &Path, other_name: &str, ds: Dataset) -> Result<Box<InMemoryDao>> {
     let file_content = fs::read_to_string(path)?;
-    let (myself, other) = parse_users(&ds.uuid, path_file_name(path)?, &file_content)?;
+    let file_content = file_content.trim_start_matches('\u{feff}').replace(LRM, "");
+    let (myself, other) = parse_users(&ds.uuid, other_name, &file_content)?;
 
     let messages = parse_messages(&file_content, &myself, &other)?;
 
@@ -60,6 +157,8 @@ fn parse_whatsapp_text_file(path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>
             member_ids: vec![myself.id, other.id],
             msg_count: messages.len() as i32,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         },
         messages
     }];
@@ -75,10 +174,9 @@ fn parse_whatsapp_text_file(path: &Path, ds: Dataset) -> Result<Box<InMemoryDao>
     )))
 }
 
-fn parse_users(ds_uuid: &PbUuid, filename: &str, content: &str) -> Result<(User, User)> {
-    let other_name = FILENAME_REGEX.captures(filename).unwrap().get(1).unwrap().as_str();
-
+fn parse_users(ds_uuid: &PbUuid, other_name: &str, content: &str) -> Result<(User, User)> {
     let mut user_names = content.lines()
+        .filter(|line| !is_skipped_line(line, other_name))
         .filter_map(|line| MESSAGE_PREFIX_REGEX.captures(line).map(|capt| capt.get(2).unwrap().as_str()))
         .unique()
         .collect_vec();
@@ -114,11 +212,19 @@ fn parse_users(ds_uuid: &PbUuid, filename: &str, content: &str) -> Result<(User,
     }))
 }
 
-fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Message>> {
+fn is_skipped_line(line: &str, other_name: &str) -> bool {
     const NOTICE_LINE: &str = "Messages and calls are end-to-end encrypted.";
     const TIMER_LINE: &str = "updated the message timer. New messages will disappear from this chat";
 
-    let is_a_contact_line: String = format!("- {} is a contact", other.pretty_name());
+    line.contains(NOTICE_LINE) || line.contains(TIMER_LINE) || line.ends_with(&format!("{other_name} is a contact"))
+}
+
+fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Message>> {
+    let other_name = other.pretty_name();
+
+    let datetime_parser = DateTimeParser::new(
+        content.lines().filter_map(|line| MESSAGE_PREFIX_REGEX.captures(line).map(|c| c.get(1).unwrap().as_str()))
+    );
 
     let mut result = vec![];
 
@@ -129,13 +235,13 @@ fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Mess
 
     let mut iter = content.lines().peekable();
     while let Some(line) = iter.next() {
-        if line.contains(NOTICE_LINE) || line.contains(TIMER_LINE) || line.ends_with(&is_a_contact_line) {
+        if is_skipped_line(line, &other_name) {
             continue;
         }
         match MESSAGE_PREFIX_REGEX.captures(line) {
             Some(capture) => {
                 // First message line
-                let timestamp2: Timestamp = parse_datetime(capture.get(1).unwrap().as_str())?;
+                let timestamp2: Timestamp = datetime_parser.parse(capture.get(1).unwrap().as_str())?;
                 if *timestamp2 > *timestamp {
                     timestamp = timestamp2;
                 } else {
@@ -168,7 +274,12 @@ fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Mess
 
                 last_internal_id = MessageInternalId(*last_internal_id + 1);
 
-                let (text, contents) = parse_message_text(&lines)?;
+                let is_deleted = lines.len() == 1 && DELETED_LINES.contains(&lines[0].trim());
+                if is_deleted { lines.clear(); }
+                // Edit time is not exported, the best we can do is to mark message as edited at the time of sending
+                let is_edited = strip_edited_marker(&mut lines);
+
+                let (text, contents) = if lines.is_empty() { (vec![], vec![]) } else { parse_message_text(&lines)? };
                 result.push(Message::new(
                     *last_internal_id,
                     None /* source_id_option */,
@@ -176,9 +287,11 @@ fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Mess
                     from_id,
                     text,
                     message_regular! {
-                        edit_timestamp_option: None,
-                        is_deleted: false,
+                        edit_timestamp_option: if is_edited { Some(timestamp) } else { None },
+                        is_deleted,
                         forward_from_name_option: None,
+                        forward_from_id_option: None,
+                        ephemeral_duration_sec_option: None,
                         reply_to_message_id_option: None,
                         contents,
                     },
@@ -192,67 +305,33 @@ fn parse_messages(content: &str, myself: &User, other: &User) -> Result<Vec<Mess
     Ok(result)
 }
 
-fn parse_message_text(lines: &[&str]) -> Result<(Vec<RichTextElement>, Vec<Content>)> {
-    let (lines, content) = if let Some(attachment_captures) = ATTACHED_FILE_REGEX.captures(lines[0]) {
-        // First line describes attached file, determine the type
-        let tpe = attachment_captures.get(2).unwrap().as_str();
-        let filename = attachment_captures.get(1).unwrap().as_str();
+/// Returns whether the marker was found.
+fn strip_edited_marker(lines: &mut Vec<&str>) -> bool {
+    let Some(last) = lines.last_mut() else { return false };
+    let Some(stripped) = last.trim_end().strip_suffix(EDITED_MARKER) else { return false };
+    *last = stripped.trim_end();
+    if last.is_empty() { lines.pop(); }
+    true
+}
 
-        let content_value = match tpe {
-            "IMG" => content!(Photo {
-                path_option: Some(filename.to_owned()),
-                width: 0,
-                height: 0,
-                mime_type_option: None,
-                is_one_time: false,
-            }),
-            "STK" => content!(Sticker {
-                path_option: Some(filename.to_owned()),
-                file_name_option: Some(filename.to_owned()),
-                width: 0,
-                height: 0,
-                mime_type_option: None,
-                thumbnail_path_option: None,
-                emoji_option: None,
-            }),
-            "VID" => {
-                ensure!(filename.ends_with(".mp4"), "Unexpected video file extension: {}", filename);
-                content!(Video {
-                    path_option: Some(filename.to_owned()),
-                    file_name_option: Some(filename.to_owned()),
-                    title_option: None,
-                    performer_option: None,
-                    width: 0,
-                    height: 0,
-                    mime_type: "video/mp4".to_owned(),
-                    duration_sec_option: None,
-                    thumbnail_path_option: None,
-                    is_one_time: false,
-                })
-            }
-            "AUD" => {
-                ensure!(filename.ends_with(".opus"), "Unexpected audio file extension: {}", filename);
-                content!(VoiceMsg {
-                    path_option: Some(filename.to_owned()),
-                    file_name_option: Some(filename.to_owned()),
-                    mime_type: "audio/ogg".to_owned(),
-                    duration_sec_option: None,
-                })
-            }
-            _ => bail!("Unknown file type: {}", filename)
-        };
+fn parse_message_text(lines: &[&str]) -> Result<(Vec<RichTextElement>, Vec<Content>)> {
+    let first_line = lines[0].trim();
+    let attached_filename = ATTACHED_FILE_REGEX.captures(first_line)
+        .or_else(|| IOS_ATTACHED_FILE_REGEX.captures(first_line))
+        .map(|c| c.get(1).unwrap().as_str());
 
-        (&lines[1..], Some(content_value))
-    } else if lines[0] == "null" || lines[0] == "<Media omitted>" {
-        // File wasn't present - e.g. one-time photo/video.
+    let (lines, content) = if let Some(filename) = attached_filename {
+        // First line describes attached file, determine the type
+        (&lines[1..], Some(attachment_content(filename)?))
+    } else if let Some(omitted_captures) = IOS_MEDIA_OMITTED_REGEX.captures(first_line) {
+        let tpe = omitted_captures.get(1).unwrap().as_str();
+        (&lines[1..], Some(media_omitted_content(tpe)))
+    } else if first_line == "POLL:" && lines.len() > 1 {
+        (&[] as &[&str], Some(poll_content(&lines[1..])?))
+    } else if MEDIA_OMITTED_LINES.contains(&first_line) {
+        // File wasn't present - e.g. one-time photo/video, or chat was exported without media.
         // Since we don't know the type, represent it as a missing file.
-        let content_value = content!(File {
-            path_option: None,
-            file_name_option: None,
-            mime_type_option: None,
-            thumbnail_path_option: None,
-        });
-        (&lines[1..], Some(content_value))
+        (&lines[1..], Some(media_omitted_content("")))
     } else {
         (lines, None)
     };
@@ -267,18 +346,224 @@ fn parse_message_text(lines: &[&str]) -> Result<(Vec<RichTextElement>, Vec<Conte
     Ok((rtes, content.into_iter().collect_vec()))
 }
 
-/// Datetime formats used by WhatsApp:
+/// Poll is exported as a question followed by options with vote counts, e.g.
+/// ```text
+/// POLL:
+/// Lunch?
+/// OPTION: Pizza (2 votes)
+/// OPTION: Sushi (1 vote)
+/// ```
+/// Own vote is not exported.
+fn poll_content(lines: &[&str]) -> Result<Content> {
+    let question = lines[0].trim().to_owned();
+    let options: Vec<_> = lines[1..].iter().map(|line| {
+        let captures = POLL_OPTION_REGEX.captures(line.trim())
+            .with_context(|| format!("Unexpected poll option line: {line}"))?;
+        Ok(ContentPollOption {
+            text: captures.get(1).unwrap().as_str().to_owned(),
+            voters_option: Some(captures.get(2).unwrap().as_str().parse()?),
+            is_chosen: false,
+            is_correct: false,
+        })
+    }).collect::<Result<_>>()?;
+    Ok(content!(Poll {
+        question,
+        options,
+        is_quiz: None,
+        total_voters_option: None,
+    }))
+}
+
+/// Attached files are stored alongside the chat file, so file name is also a relative path.
+fn attachment_content(filename: &str) -> Result<Content> {
+    if filename.to_lowercase().ends_with(".vcf") {
+        // Contact details are filled in from the vCard file itself after loading
+        return Ok(content!(SharedContact {
+            first_name_option: None,
+            last_name_option: None,
+            phone_number_option: None,
+            vcard_path_option: Some(filename.to_owned()),
+            emails: vec![],
+        }));
+    }
+    let tpe = ANDROID_MEDIA_NAME_REGEX.captures(filename)
+        .or_else(|| IOS_MEDIA_NAME_REGEX.captures(filename))
+        .map(|c| c.get(1).unwrap().as_str())
+        .unwrap_or_default();
+    Ok(match tpe {
+        "IMG" | "PHOTO" => content!(Photo {
+            path_option: Some(filename.to_owned()),
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        }),
+        "STK" | "STICKER" => content!(Sticker {
+            path_option: Some(filename.to_owned()),
+            file_name_option: Some(filename.to_owned()),
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+            emoji_option: None,
+            pack_id_option: None,
+            pack_name_option: None,
+        }),
+        "VID" | "VIDEO" | "GIF" => {
+            ensure!(filename.ends_with(".mp4"), "Unexpected video file extension: {}", filename);
+            content!(Video {
+                path_option: Some(filename.to_owned()),
+                file_name_option: Some(filename.to_owned()),
+                title_option: None,
+                performer_option: None,
+                width: 0,
+                height: 0,
+                mime_type: "video/mp4".to_owned(),
+                duration_sec_option: None,
+                thumbnail_path_option: None,
+                is_one_time: false,
+            })
+        }
+        "AUD" | "PTT" | "AUDIO" => {
+            ensure!(filename.ends_with(".opus"), "Unexpected audio file extension: {}", filename);
+            content!(VoiceMsg {
+                path_option: Some(filename.to_owned()),
+                file_name_option: Some(filename.to_owned()),
+                mime_type: "audio/ogg".to_owned(),
+                duration_sec_option: None,
+                waveform_option: None,
+            })
+        }
+        // Documents keep their original names
+        _ => content!(File {
+            path_option: Some(filename.to_owned()),
+            file_name_option: Some(filename.to_owned()),
+            mime_type_option: None,
+            thumbnail_path_option: None,
+        }),
+    })
+}
+
+fn media_omitted_content(tpe: &str) -> Content {
+    match tpe {
+        "image" => content!(Photo {
+            path_option: None,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            is_one_time: false,
+            lat_str_option: None,
+            lon_str_option: None,
+            ocr_text_option: None,
+        }),
+        "sticker" => content!(Sticker {
+            path_option: None,
+            file_name_option: None,
+            width: 0,
+            height: 0,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+            emoji_option: None,
+            pack_id_option: None,
+            pack_name_option: None,
+        }),
+        "video" | "GIF" => content!(Video {
+            path_option: None,
+            file_name_option: None,
+            title_option: None,
+            performer_option: None,
+            width: 0,
+            height: 0,
+            mime_type: "video/mp4".to_owned(),
+            duration_sec_option: None,
+            thumbnail_path_option: None,
+            is_one_time: false,
+        }),
+        "audio" => content!(VoiceMsg {
+            path_option: None,
+            file_name_option: None,
+            mime_type: "audio/ogg".to_owned(),
+            duration_sec_option: None,
+            waveform_option: None,
+        }),
+        _ => content!(File {
+            path_option: None,
+            file_name_option: None,
+            mime_type_option: None,
+            thumbnail_path_option: None,
+        }),
+    }
+}
+
+/// Datetime format used by WhatsApp depends on a phone locale, e.g.:
 /// ```text
 /// 6/30/20, 16:14
 /// 30/6/2020, 16:14
+/// 30.06.20, 16:14:05
+/// 2020-06-30, 4:14 PM
 /// ```
-fn parse_datetime(s: &str) -> Result<Timestamp> {
-    // NaiveDateTime::parse_from_str is slow, but we don't usually have a lot of mesages in this format,
-    // so we're fine with it.
-    const DATE_TIME_SHORT_FMT: &str = "%m/%d/%y, %H:%M"; // 2-digit year
-    const DATE_TIME_LONG_FMT: &str = "%d/%m/%Y, %H:%M";
-    let parse = NaiveDateTime::parse_from_str;
-    let naive_dt = parse(s, DATE_TIME_SHORT_FMT).or(parse(s, DATE_TIME_LONG_FMT))?;
-    let local_dt = LOCAL_TZ.from_local_datetime(&naive_dt).unwrap();
-    Ok(Timestamp(local_dt.timestamp()))
+/// Day-month order is ambiguous for some timestamps, so formats that fit the most timestamps in a file
+/// are tried first.
+struct DateTimeParser {
+    formats: Vec<String>,
+}
+
+impl DateTimeParser {
+    const DATE_FORMATS: &'static [&'static str] = &[
+        "%m/%d/%y", "%d/%m/%Y", "%d/%m/%y", "%m/%d/%Y",
+        "%d.%m.%y", "%d.%m.%Y", "%Y-%m-%d", "%d-%m-%Y", "%Y/%m/%d",
+    ];
+    const TIME_FORMATS: &'static [&'static str] = &[
+        "%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M:%S %p", "%H.%M", "%H.%M.%S",
+    ];
+
+    /// Amount of timestamps to consider when ranking formats.
+    const SAMPLE_SIZE: usize = 1000;
+
+    fn new<'a>(timestamps: impl Iterator<Item=&'a str>) -> Self {
+        let sample = timestamps.take(Self::SAMPLE_SIZE).map(Self::normalize).collect_vec();
+        let formats = Self::DATE_FORMATS.iter()
+            .cartesian_product(Self::TIME_FORMATS.iter())
+            .map(|(date_fmt, time_fmt)| format!("{date_fmt} {time_fmt}"))
+            .map(|fmt| {
+                let fits = sample.iter().filter(|s| Self::parse_with(s, &fmt).is_some()).count();
+                (fmt, fits)
+            })
+            .sorted_by_key(|(_, fits)| std::cmp::Reverse(*fits)) // Stable sort preserves formats order
+            .map(|(fmt, _)| fmt)
+            .collect_vec();
+        DateTimeParser { formats }
+    }
+
+    fn parse(&self, s: &str) -> Result<Timestamp> {
+        let normalized = Self::normalize(s);
+        // NaiveDateTime::parse_from_str is slow, but we don't usually have a lot of mesages in this format,
+        // so we're fine with it.
+        let naive_dt = self.formats.iter()
+            .find_map(|fmt| Self::parse_with(&normalized, fmt))
+            .with_context(|| format!("Unknown timestamp format: {s}"))?;
+        // Wall clock time might be skipped over by a DST transition
+        let local_dt = LOCAL_TZ.from_local_datetime(&naive_dt).earliest()
+            .with_context(|| format!("Timestamp does not exist in local timezone: {s}"))?;
+        Ok(Timestamp(local_dt.timestamp()))
+    }
+
+    fn parse_with(s: &str, fmt: &str) -> Option<NaiveDateTime> {
+        // 4-digit year format happily accepts 2-digit years, WhatsApp is certainly younger than that.
+        NaiveDateTime::parse_from_str(s, fmt).ok().filter(|dt| dt.year() >= 2000)
+    }
+
+    /// Unifies separators and AM/PM markers, e.g. `6/30/20, 4:14\u{202f}p.m.` becomes `6/30/20 4:14 PM`
+    fn normalize(s: &str) -> String {
+        let s = s.replace(',', "").replace(['\u{202f}', '\u{a0}'], " ").to_uppercase();
+        let s = s.replace("A.M.", "AM").replace("P.M.", "PM").replace("A. M.", "AM").replace("P. M.", "PM");
+        match s.strip_suffix("AM").or_else(|| s.strip_suffix("PM")) {
+            Some(without_marker) if !without_marker.ends_with(' ') =>
+                format!("{without_marker} {}", &s[without_marker.len()..]),
+            _ => s,
+        }
+    }
 }
@@ -4,7 +4,7 @@ use chrono::prelude::*;
 use lazy_static::lazy_static;
 use pretty_assertions::{assert_eq, assert_ne};
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::entity_utils::*;
 use crate::protobuf::history::content::SealedValueOptional::*;
 use crate::protobuf::history::message::*;
@@ -57,6 +57,8 @@ fn loading_2023_10() -> EmptyRes {
             member_ids: vec![myself.id, member.id],
             msg_count: 10,
             main_chat_id: None,
+            archived: None,
+            hidden: None,
         });
 
         let msgs = dao.first_messages(&chat, 99999)?;
@@ -79,6 +81,8 @@ fn loading_2023_10() -> EmptyRes {
                 },
             ],
             searchable_string: "hello there! this is a multi-line message!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
         assert_eq!(msgs[1], Message {
@@ -95,6 +99,8 @@ fn loading_2023_10() -> EmptyRes {
                 },
             ],
             searchable_string: "and these messages".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
         assert_eq!(msgs[2], Message {
@@ -111,6 +117,8 @@ fn loading_2023_10() -> EmptyRes {
                 },
             ],
             searchable_string: "should not be reordered!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
         assert_eq!(msgs[3], Message {
@@ -127,6 +135,8 @@ fn loading_2023_10() -> EmptyRes {
                 },
             ],
             searchable_string: "should not be reordered indeed!".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(MESSAGE_REGULAR_NO_CONTENT.clone()),
         });
         assert_eq!(msgs[4], Message {
@@ -143,10 +153,14 @@ fn loading_2023_10() -> EmptyRes {
                 }
             ],
             searchable_string: "image comment".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Photo {
@@ -155,6 +169,9 @@ fn loading_2023_10() -> EmptyRes {
                         height: 0,
                         mime_type_option: None,
                         is_one_time: false,
+                        lat_str_option: None,
+                        lon_str_option: None,
+                        ocr_text_option: None,
                     })
                 ],
             }),
@@ -166,10 +183,14 @@ fn loading_2023_10() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Video {
@@ -194,10 +215,14 @@ fn loading_2023_10() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(VoiceMsg {
@@ -205,6 +230,7 @@ fn loading_2023_10() -> EmptyRes {
                         file_name_option: Some("AUD-20230630-WA0002.opus".to_owned()),
                         mime_type: "audio/ogg".to_owned(),
                         duration_sec_option: None,
+                        waveform_option: None,
                     })
                 ],
             }),
@@ -216,10 +242,14 @@ fn loading_2023_10() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![
                     content!(Sticker {
@@ -230,6 +260,8 @@ fn loading_2023_10() -> EmptyRes {
                         mime_type_option: None,
                         thumbnail_path_option: None,
                         emoji_option: None,
+                        pack_id_option: None,
+                        pack_name_option: None,
                     })
                 ],
             }),
@@ -241,10 +273,14 @@ fn loading_2023_10() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![FILE_UNAVAILABLE.clone()],
             }),
@@ -256,10 +292,14 @@ fn loading_2023_10() -> EmptyRes {
             from_id: member.id,
             text: vec![],
             searchable_string: "".to_owned(),
+            topic_option: None,
+            text_history: vec![],
             typed: Some(message_regular! {
                 edit_timestamp_option: None,
                 is_deleted: false,
                 forward_from_name_option: None,
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 reply_to_message_id_option: None,
                 contents: vec![FILE_UNAVAILABLE.clone()],
             }),
@@ -268,6 +308,152 @@ fn loading_2023_10() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn loading_2024_06_12h() -> EmptyRes {
+    let res = resource("whatsapp-text_2024-06_12h/WhatsApp Chat with Jane.txt");
+    LOADER.looks_about_right(&res)?;
+
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.first_name_option.as_deref(), Some("Me"));
+
+    let cwm = dao.cwms_single_ds().remove(0);
+    assert_eq!(cwm.chat.name_option.as_deref(), Some("Jane"));
+
+    let msgs = dao.first_messages(&cwm.chat, 99999)?;
+    assert_eq!(msgs.len(), 7);
+
+    // Month goes first since 1/13/24 can't be parsed otherwise
+    assert_eq!(msgs[0].timestamp, dt("2024-01-02 16:14:00", None).timestamp());
+    assert_eq!(msgs[1].timestamp, dt("2024-01-02 16:15:00", None).timestamp());
+    assert_eq!(msgs[2].timestamp, dt("2024-01-13 09:05:00", None).timestamp());
+
+    assert_eq!(msgs[1].from_id, myself.id);
+    assert_eq!(msgs[1].text, vec![RichText::make_plain("Here's the report".to_owned())]);
+    assert_eq!(coerce_enum!(msgs[1].typed(), Typed::Regular(r) => &r.contents), &vec![content!(File {
+        path_option: Some("report.pdf".to_owned()),
+        file_name_option: Some("report.pdf".to_owned()),
+        mime_type_option: None,
+        thumbnail_path_option: None,
+    })]);
+
+    assert_eq!(coerce_enum!(msgs[2].typed(), Typed::Regular(r) => &r.contents), &vec![FILE_UNAVAILABLE.clone()]);
+
+    let option = |text: &str, voters: i32| ContentPollOption {
+        text: text.to_owned(),
+        voters_option: Some(voters),
+        is_chosen: false,
+        is_correct: false,
+    };
+    assert_eq!(msgs[3].text, vec![]);
+    assert_eq!(coerce_enum!(msgs[3].typed(), Typed::Regular(r) => &r.contents), &vec![content!(Poll {
+        question: "Lunch?".to_owned(),
+        options: vec![option("Pizza", 2), option("Sushi", 1), option("Salad", 0)],
+        is_quiz: None,
+        total_voters_option: None,
+    })]);
+
+    // Contact details are taken from the vCard
+    assert_eq!(coerce_enum!(msgs[4].typed(), Typed::Regular(r) => &r.contents), &vec![content!(SharedContact {
+        first_name_option: Some("Jane Doe".to_owned()),
+        last_name_option: None,
+        phone_number_option: Some("+1 555 0100, +1 555 0199".to_owned()),
+        vcard_path_option: Some("Jane Doe.vcf".to_owned()),
+        emails: vec!["jane@example.com".to_owned()],
+    })]);
+    assert_eq!(msgs[4].searchable_string, "Jane Doe +1 555 0100, +1 555 0199 jane@example.com");
+
+    assert_eq!(msgs[5].text, vec![]);
+    assert_eq!(msgs[5].typed(), &message_regular! {
+        edit_timestamp_option: None,
+        is_deleted: true,
+        forward_from_name_option: None,
+        forward_from_id_option: None,
+        ephemeral_duration_sec_option: None,
+        reply_to_message_id_option: None,
+        contents: vec![],
+    });
+
+    // Edit time is unknown
+    assert_eq!(msgs[6].text, vec![RichText::make_plain("Fixed typo".to_owned())]);
+    assert_eq!(coerce_enum!(msgs[6].typed(), Typed::Regular(r) => r.edit_timestamp_option), Some(msgs[6].timestamp));
+    Ok(())
+}
+
+#[test]
+fn loading_2024_06_ios_zip() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let zip_name = "WhatsApp Chat - John Doe.zip";
+    let res = tmp_dir.path.join(zip_name);
+    fs::copy(resource("whatsapp-text_2024-06_ios").join(zip_name), &res)?;
+    LOADER.looks_about_right(&res)?;
+
+    let dao = LOADER.load(&res, &client::NoChooser)?;
+
+    // Archive is unpacked alongside, and the unpacked chat is recognized too
+    let unpacked_dir = tmp_dir.path.join("WhatsApp Chat - John Doe");
+    assert_eq!(dao.dataset_root(&dao.ds_uuid())?.0, unpacked_dir);
+    LOADER.looks_about_right(&unpacked_dir.join(IOS_CHAT_FILENAME))?;
+
+    let myself = dao.myself_single_ds();
+    assert_eq!(myself.first_name_option.as_deref(), Some("Me Myself"));
+
+    let cwm = dao.cwms_single_ds().remove(0);
+    assert_eq!(cwm.chat.name_option.as_deref(), Some("John Doe"));
+
+    let msgs = dao.first_messages(&cwm.chat, 99999)?;
+    assert_eq!(msgs.len(), 5);
+
+    assert_eq!(msgs[0].timestamp, dt("2024-06-01 16:14:00", None).timestamp());
+    assert_eq!(msgs[0].text, vec![RichText::make_plain("Hey there".to_owned())]);
+
+    let photo_path = "00000003-PHOTO-2024-06-01-16-14-05.jpg";
+    assert_eq!(msgs[1].from_id, myself.id);
+    assert_eq!(coerce_enum!(msgs[1].typed(), Typed::Regular(r) => &r.contents), &vec![content!(Photo {
+        path_option: Some(photo_path.to_owned()),
+        width: 0,
+        height: 0,
+        mime_type_option: None,
+        is_one_time: false,
+        lat_str_option: None,
+        lon_str_option: None,
+        ocr_text_option: None,
+    })]);
+    assert!(unpacked_dir.join(photo_path).exists());
+
+    assert_eq!(coerce_enum!(msgs[2].typed(), Typed::Regular(r) => &r.contents), &vec![content!(Photo {
+        path_option: None,
+        width: 0,
+        height: 0,
+        mime_type_option: None,
+        is_one_time: false,
+        lat_str_option: None,
+        lon_str_option: None,
+        ocr_text_option: None,
+    })]);
+
+    assert_eq!(msgs[3].text, vec![RichText::make_plain("Multi\nline".to_owned())]);
+
+    assert_eq!(msgs[4].timestamp, dt("2024-06-13 09:00:00", None).timestamp());
+    Ok(())
+}
+
+#[test]
+fn parse_datetime_formats() -> EmptyRes {
+    let parse = |all: &[&str], s: &str| DateTimeParser::new(all.iter().cloned()).parse(s).map(|ts| *ts);
+    let expected = dt("2020-06-03 16:14:00", None).timestamp();
+
+    assert_eq!(parse(&["6/3/20, 16:14", "6/30/20, 16:14"], "6/3/20, 16:14")?, expected);
+    assert_eq!(parse(&["3/6/2020, 16:14", "30/6/2020, 16:14"], "3/6/2020, 16:14")?, expected);
+    assert_eq!(parse(&["03.06.20, 16:14"], "03.06.20, 16:14")?, expected);
+    assert_eq!(parse(&["2020-06-03, 4:14 PM"], "2020-06-03, 4:14 PM")?, expected);
+    assert_eq!(parse(&["6/3/20, 4:14\u{202f}p.m."], "6/3/20, 4:14\u{202f}p.m.")?, expected);
+    assert_eq!(parse(&["03.06.20, 16:14:05"], "03.06.20, 16:14:05")?, expected + 5);
+    assert!(parse(&["not a date"], "not a date").is_err());
+    Ok(())
+}
+
 //
 // Helpers
 //
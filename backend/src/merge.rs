@@ -1,2 +1,5 @@
 pub mod analyzer;
+pub mod comparison;
 pub mod merger;
+pub mod templates;
+pub mod sync;
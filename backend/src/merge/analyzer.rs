@@ -2,7 +2,7 @@ use std::cmp::Ordering;
 
 use itertools::Itertools;
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::prelude::*;
 
 #[cfg(test)]
@@ -10,18 +10,18 @@ use crate::prelude::*;
 mod tests;
 
 pub struct DatasetDiffAnalyzer<'a> {
-    m_dao: &'a dyn ChatHistoryDao,
+    m_dao: &'a dyn ChatHistoryReader,
     m_root: DatasetRoot,
 
-    s_dao: &'a dyn ChatHistoryDao,
+    s_dao: &'a dyn ChatHistoryReader,
     s_root: DatasetRoot,
 }
 
 impl<'a> DatasetDiffAnalyzer<'a> {
     pub fn create(
-        m_dao: &'a dyn ChatHistoryDao,
+        m_dao: &'a dyn ChatHistoryReader,
         m_ds: &'a Dataset,
-        s_dao: &'a dyn ChatHistoryDao,
+        s_dao: &'a dyn ChatHistoryReader,
         s_ds: &'a Dataset,
     ) -> Result<Self> {
         let m_root = m_dao.dataset_root(&m_ds.uuid)?;
@@ -39,6 +39,8 @@ impl<'a> DatasetDiffAnalyzer<'a> {
         title: &str,
         force_conflicts: bool,
     ) -> Result<Vec<MergeAnalysisSection>> {
+        ensure!(are_comparable_types(master_cwd.chat.tpe(), slave_cwd.chat.tpe()),
+                "Chat {title} is {:?} in master but {:?} in slave", master_cwd.chat.tpe(), slave_cwd.chat.tpe());
         measure(|| {
             let mut analysis = self.analyze_inner(
                 AnalysisContext {
@@ -213,6 +215,12 @@ impl<'a> DatasetDiffAnalyzer<'a> {
     }
 }
 
+/// Self chats used to be loaded as personal ones, so these are treated as the same type of chat.
+fn are_comparable_types(master_tpe: ChatType, slave_tpe: ChatType) -> bool {
+    use ChatType::*;
+    master_tpe == slave_tpe || matches!((master_tpe, slave_tpe), (Personal, SelfChat) | (SelfChat, Personal))
+}
+
 /// Everything starting at first mismatch and ending just before trailing match (if any) will be merged into
 /// a single conflict if possible
 fn enforce_conflicts(analysis: Vec<MergeAnalysisSection>) -> Result<Vec<MergeAnalysisSection>> {
@@ -420,7 +428,7 @@ impl AnalysisContext<'_> {
 const BATCH_SIZE: usize = 1000;
 
 fn messages_stream<'a, T: WithTypedId>(
-    dao: &'a dyn ChatHistoryDao,
+    dao: &'a dyn ChatHistoryReader,
     chat: &'a Chat,
     wrap: fn(Message) -> T,
     unwrap_id: fn(&T) -> MessageInternalId,
@@ -439,7 +447,7 @@ fn messages_stream<'a, T: WithTypedId>(
 }
 
 struct BatchedMessageIterator<'a, T: WithTypedId> {
-    dao: &'a dyn ChatHistoryDao,
+    dao: &'a dyn ChatHistoryReader,
     chat: &'a Chat,
     wrap: fn(Message) -> T,
     unwrap_id: fn(&T) -> MessageInternalId,
@@ -513,6 +521,7 @@ fn equals_with_no_mismatching_content(mm_eq: PracticalEqTuple<MasterMessage>,
                 ..mr.clone()
             }),
             text: m.text.iter().map(text_to_comparable).collect_vec(),
+            text_history: vec![],
             ..m.clone()
         }
     }
@@ -9,7 +9,7 @@ use pretty_assertions::{assert_eq, assert_ne};
 use super::MergeAnalysisSection::*;
 
 use crate::prelude::*;
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 
 use super::*;
 
@@ -677,6 +677,52 @@ fn file_name_diff() -> EmptyRes {
     Ok(())
 }
 
+/// Forward author names should not cause conflicts, known author IDs should
+#[test]
+fn forward_origin_diff() -> EmptyRes {
+    let msgs = create_messages(src_id(4));
+    let helper = MergerHelper::new(
+        MAX_USER_ID, msgs.clone(), msgs,
+        &|is_master: bool, _ds_root: &DatasetRoot, msg: &mut Message| {
+            let (name, id): (Option<&str>, Option<i64>) = match (msg.source_id_option.unwrap(), is_master) {
+                (0, true) => (Some("Old name"), None),
+                (0, false) => (Some("New name"), None),
+                (1, true) => (Some("Name"), None),
+                (1, false) => (Some("Name"), Some(12345)),
+                (2, true) => (Some(SOMEONE), None),
+                (2, false) => (Some("Name"), Some(12345)),
+                (3, true) => (Some("Name"), Some(12345)),
+                (3, false) => (Some("Name"), Some(54321)),
+                (4, true) => (Some("Name"), None),
+                (4, false) => (None, None),
+                _ => unreachable!(),
+            };
+            let mr = coerce_enum!(msg.typed.as_mut(), Some(message::Typed::Regular(mr)) => mr);
+            mr.forward_from_name_option = name.map(|s| s.to_owned());
+            mr.forward_from_id_option = id;
+        },
+    );
+    let analysis = analyzer(&helper).analyze(helper.m.cwd(), helper.s.cwd(), "", false)?;
+
+    assert_eq!(
+        analysis, vec![
+            Match(MergeAnalysisSectionMatch {
+                first_master_msg_id: helper.m.msgs[&src_id(0)].typed_id(),
+                last_master_msg_id: helper.m.msgs[&src_id(2)].typed_id(),
+                first_slave_msg_id: helper.s.msgs[&src_id(0)].typed_id(),
+                last_slave_msg_id: helper.s.msgs[&src_id(2)].typed_id(),
+            }),
+            Conflict(MergeAnalysisSectionConflict {
+                first_master_msg_id: helper.m.msgs[&src_id(3)].typed_id(),
+                last_master_msg_id: helper.m.msgs[&src_id(4)].typed_id(),
+                first_slave_msg_id: helper.s.msgs[&src_id(3)].typed_id(),
+                last_slave_msg_id: helper.s.msgs[&src_id(4)].typed_id(),
+            }),
+        ]
+    );
+    Ok(())
+}
+
 /// "not found" should NOT conflict with "not downloaded" and vice versa
 #[test]
 fn present_absent_not_downloaded() -> EmptyRes {
@@ -688,6 +734,9 @@ fn present_absent_not_downloaded() -> EmptyRes {
         height: 100600,
         mime_type_option: None,
         is_one_time: false,
+        lat_str_option: None,
+        lon_str_option: None,
+        ocr_text_option: None,
     };
 
     let not_downloaded = ContentPhoto { path_option: None, ..not_found.clone() };
@@ -698,6 +747,9 @@ fn present_absent_not_downloaded() -> EmptyRes {
         height: -1,
         mime_type_option: Some("image/lol".to_owned()),
         is_one_time: false,
+        lat_str_option: None,
+        lon_str_option: None,
+        ocr_text_option: None,
     };
 
     let placeholder2 = ContentPhoto {
@@ -715,6 +767,8 @@ fn present_absent_not_downloaded() -> EmptyRes {
                 is_deleted: false,
                 reply_to_message_id_option: None,
                 forward_from_name_option: Some("some user".to_owned()),
+                forward_from_id_option: None,
+                ephemeral_duration_sec_option: None,
                 contents: vec![
                     content!(Photo { ..photo.clone() })
                 ],
@@ -732,6 +786,8 @@ fn present_absent_not_downloaded() -> EmptyRes {
             from_id: user_id as i64,
             searchable_string: make_searchable_string(&text, &typed),
             text,
+            topic_option: None,
+            text_history: vec![],
             typed: Some(typed),
         }
     };
@@ -904,6 +960,27 @@ fn telegram_2023_11_amending_double_style_export() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn self_chat_vs_personal_chat() -> EmptyRes {
+    let msgs = vec![create_regular_message(0, 1)];
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs.clone(), msgs);
+    let with_type = |cwd: &ChatWithDetails, tpe: ChatType| {
+        let mut cwd = cwd.clone();
+        cwd.chat.tpe = tpe as i32;
+        cwd
+    };
+    let analyze = |m_tpe: ChatType, s_tpe: ChatType| {
+        analyzer(&helper).analyze(&with_type(helper.m.cwd(), m_tpe), &with_type(helper.s.cwd(), s_tpe), "", false)
+    };
+
+    // Self chats used to be loaded as personal ones
+    assert_eq!(analyze(ChatType::Personal, ChatType::SelfChat)?.len(), 1);
+    assert_eq!(analyze(ChatType::SelfChat, ChatType::SelfChat)?.len(), 1);
+    assert!(analyze(ChatType::PrivateGroup, ChatType::SelfChat).is_err());
+    assert!(analyze(ChatType::PrivateGroup, ChatType::Personal).is_err());
+    Ok(())
+}
+
 //
 // Helpers
 //
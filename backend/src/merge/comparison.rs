@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use itertools::{EitherOrBoth, Itertools};
+
+use crate::dao::{BATCH_SIZE, ChatHistoryReader};
+use crate::merge::analyzer::*;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "comparison_tests.rs"]
+mod tests;
+
+/// Message-level difference between two versions of the same chat - master being the older one (e.g. a backup)
+/// and slave being the newer one (e.g. a fresh export).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChatDiff {
+    pub matching_count: usize,
+    /// Master messages missing in slave
+    pub removed: Vec<MasterInternalId>,
+    /// Slave messages missing in master
+    pub added: Vec<SlaveInternalId>,
+    /// Messages present in both but having different content
+    pub edited: Vec<(MasterInternalId, SlaveInternalId)>,
+}
+
+impl ChatDiff {
+    /// Whether slave contains everything master does, edited messages being considered newer versions.
+    pub fn supersedes(&self) -> bool {
+        self.removed.is_empty()
+    }
+}
+
+/// Compare two versions of a chat without merging anything, using the same logic as merge analysis.
+pub fn diff_chats(m_dao: &dyn ChatHistoryReader, m_ds: &Dataset, m_cwd: &ChatWithDetails,
+                  s_dao: &dyn ChatHistoryReader, s_ds: &Dataset, s_cwd: &ChatWithDetails) -> Result<ChatDiff> {
+    let analyzer = DatasetDiffAnalyzer::create(m_dao, m_ds, s_dao, s_ds)?;
+    let analysis = analyzer.analyze(m_cwd, s_cwd, &s_cwd.chat.qualified_name(), false)?;
+
+    let m_slice = |first: MasterInternalId, last: MasterInternalId|
+        m_dao.messages_slice(&m_cwd.chat, first.generalize(), last.generalize());
+    let s_slice = |first: SlaveInternalId, last: SlaveInternalId|
+        s_dao.messages_slice(&s_cwd.chat, first.generalize(), last.generalize());
+
+    let mut diff = ChatDiff::default();
+    for section in analysis {
+        match section {
+            MergeAnalysisSection::Match(v) => {
+                diff.matching_count +=
+                    m_dao.messages_slice_len(&m_cwd.chat, v.first_master_msg_id.generalize(), v.last_master_msg_id.generalize())?;
+            }
+            MergeAnalysisSection::Retention(v) => {
+                let msgs = m_slice(v.first_master_msg_id, v.last_master_msg_id)?;
+                diff.removed.extend(msgs.iter().map(|m| MasterInternalId(m.internal_id)));
+            }
+            MergeAnalysisSection::Addition(v) => {
+                let msgs = s_slice(v.first_slave_msg_id, v.last_slave_msg_id)?;
+                diff.added.extend(msgs.iter().map(|m| SlaveInternalId(m.internal_id)));
+            }
+            MergeAnalysisSection::Conflict(v) => {
+                let m_msgs = m_slice(v.first_master_msg_id, v.last_master_msg_id)?;
+                let s_msgs = s_slice(v.first_slave_msg_id, v.last_slave_msg_id)?;
+                // Conflicting ranges are normally of the same length, but let's not rely on that
+                for pair in m_msgs.iter().zip_longest(s_msgs.iter()) {
+                    match pair {
+                        EitherOrBoth::Both(mm, sm) =>
+                            diff.edited.push((MasterInternalId(mm.internal_id), SlaveInternalId(sm.internal_id))),
+                        EitherOrBoth::Left(mm) => diff.removed.push(MasterInternalId(mm.internal_id)),
+                        EitherOrBoth::Right(sm) => diff.added.push(SlaveInternalId(sm.internal_id)),
+                    }
+                }
+            }
+        }
+    }
+    Ok(diff)
+}
+
+/// Datasets of a DAO that also exist (by UUID) in other DAOs but differ from their copies there,
+/// i.e. copies of the same database that were changed independently.
+/// Other DAOs are given along with their keys, ones stored in the same file as `dao` are skipped.
+pub fn find_divergences(dao: &dyn ChatHistoryReader,
+                        other_daos: &[(&str, &dyn ChatHistoryReader)]) -> Result<Vec<DatasetDivergence>> {
+    let mut res = vec![];
+    for ds in dao.datasets()? {
+        for (other_key, other_dao) in other_daos {
+            if other_dao.storage_path() == dao.storage_path() { continue; }
+            let Some(other_ds) = other_dao.datasets()?.into_iter().find(|other_ds| other_ds.uuid == ds.uuid) else { continue };
+            if let Some(mut divergence) = dataset_divergence(dao, &ds, *other_dao, &other_ds)? {
+                divergence.other_dao_key = other_key.to_string();
+                res.push(divergence);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// How a dataset differs from its copy in another DAO, `None` if they're the same.
+/// Other copy is treated as master when comparing chats, see [diff_chats].
+///
+/// Chats having the same message count and the same last message are assumed to be the same,
+/// so that unchanged copies can be compared without analyzing every chat.
+pub fn dataset_divergence(dao: &dyn ChatHistoryReader, ds: &Dataset,
+                          other_dao: &dyn ChatHistoryReader, other_ds: &Dataset) -> Result<Option<DatasetDivergence>> {
+    let cwds: BTreeMap<i64, ChatWithDetails> = dao.chats(&ds.uuid)?.into_iter().map(|cwd| (cwd.chat.id, cwd)).collect();
+    let other_cwds: BTreeMap<i64, ChatWithDetails> = other_dao.chats(&other_ds.uuid)?.into_iter().map(|cwd| (cwd.chat.id, cwd)).collect();
+    let last_msg = |cwd: &ChatWithDetails| cwd.last_msg_option.clone().map(|m| Message { internal_id: 0, ..m });
+
+    let mut divergence = DatasetDivergence {
+        dataset: ds.clone(),
+        other_dao_name: other_dao.name().to_owned(),
+        chat_ids_only_in_other: other_cwds.keys().filter(|id| !cwds.contains_key(id)).copied().collect_vec(),
+        ..Default::default()
+    };
+    for (id, cwd) in cwds.iter() {
+        let Some(other_cwd) = other_cwds.get(id) else {
+            divergence.chat_ids_only_here.push(*id);
+            continue;
+        };
+        if cwd.chat.msg_count == other_cwd.chat.msg_count && last_msg(cwd) == last_msg(other_cwd) { continue; }
+
+        let diff = diff_chats(other_dao, other_ds, other_cwd, dao, ds, cwd)?;
+        if diff.added.is_empty() && diff.removed.is_empty() && diff.edited.is_empty() { continue; }
+        divergence.diverged_chat_ids.push(*id);
+        divergence.messages_only_here += diff.added.len() as i32;
+        divergence.messages_only_in_other += diff.removed.len() as i32;
+        divergence.edited_messages += diff.edited.len() as i32;
+    }
+
+    let same = divergence.chat_ids_only_here.is_empty()
+        && divergence.chat_ids_only_in_other.is_empty()
+        && divergence.diverged_chat_ids.is_empty();
+    Ok((!same).then_some(divergence))
+}
+
+/// Source chat content that is missing in a dataset, see [verify_against_source].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatLoss {
+    /// As found in source
+    pub src_cwd: ChatWithDetails,
+    /// Whether dataset has no such chat at all
+    pub chat_missing: bool,
+    /// Source messages missing in dataset
+    pub missing: Vec<SlaveInternalId>,
+    /// How many of missing messages were sent in disappearing mode.
+    /// These might have expired before reaching the dataset, so such gaps are expected.
+    pub missing_ephemeral_count: usize,
+}
+
+/// Compare a freshly parsed original source against a dataset it was imported into (and possibly merged with
+/// other sources since), chats are matched by ID.
+///
+/// Everything present in source should normally be present in dataset, so any source messages missing there
+/// indicate that content was silently lost along the way, e.g. by a loader regression.
+/// Messages that are only present in dataset, or differ in content, are expected and are not reported.
+/// Missing disappearing messages are reported, but are counted separately.
+/// Chats without losses are omitted.
+pub fn verify_against_source(dao: &dyn ChatHistoryReader, ds: &Dataset,
+                             src_dao: &dyn ChatHistoryReader, src_ds: &Dataset) -> Result<Vec<ChatLoss>> {
+    let mut res = vec![];
+    for src_cwd in src_dao.chats(&src_ds.uuid)? {
+        let loss = match dao.chat_option(&ds.uuid, src_cwd.chat.id)? {
+            None => {
+                let mut missing = vec![];
+                let mut missing_ephemeral_count = 0;
+                let mut offset = 0;
+                loop {
+                    let msgs = src_dao.scroll_messages(&src_cwd.chat, offset, BATCH_SIZE)?;
+                    missing.extend(msgs.iter().map(|m| SlaveInternalId(m.internal_id)));
+                    missing_ephemeral_count += msgs.iter().filter(|m| is_ephemeral(m)).count();
+                    if msgs.len() < BATCH_SIZE { break; }
+                    offset += BATCH_SIZE;
+                }
+                ChatLoss { src_cwd, chat_missing: true, missing, missing_ephemeral_count }
+            }
+            Some(cwd) => {
+                let diff = diff_chats(dao, ds, &cwd, src_dao, src_ds, &src_cwd)?;
+                if diff.added.is_empty() { continue; }
+                let mut missing_ephemeral_count = 0;
+                for id in diff.added.iter() {
+                    let msg = src_dao.message_option_by_internal_id(&src_cwd.chat, id.generalize())?
+                        .with_context(|| format!("Message {} not found in source", **id))?;
+                    if is_ephemeral(&msg) { missing_ephemeral_count += 1; }
+                }
+                ChatLoss { src_cwd, chat_missing: false, missing: diff.added, missing_ephemeral_count }
+            }
+        };
+        res.push(loss);
+    }
+    Ok(res)
+}
+
+fn is_ephemeral(msg: &Message) -> bool {
+    matches!(msg.typed(), message_regular_pat! { ephemeral_duration_sec_option: Some(_), .. })
+}
@@ -0,0 +1,148 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::in_memory_dao::InMemoryDao;
+use crate::prelude::*;
+
+use super::*;
+
+const MAX_USER_ID: usize = 3;
+
+/**
+ * ```text
+ * Master messages - 0 1 2 3  4 5
+ * Slave messages  -   1 2 3* 4 5 6
+ * ```
+ */
+#[test]
+fn removed_added_and_edited() -> EmptyRes {
+    let msgs_a = (0..=5).map(|i| create_regular_message(i, MergerHelper::random_user_id(MAX_USER_ID))).collect_vec();
+    let mut msgs_b = msgs_a.cloned([1, 2, 3, 4, 5].map(src_id)).changed(|id| *id == 3);
+    msgs_b.push(create_regular_message(6, 1));
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs_a, msgs_b);
+
+    let diff = diff_chats(helper.m.dao_holder.dao.as_ref(), &helper.m.ds, helper.m.cwd(),
+                          helper.s.dao_holder.dao.as_ref(), &helper.s.ds, helper.s.cwd())?;
+    assert_eq!(diff, ChatDiff {
+        matching_count: 4,
+        removed: vec![helper.m.msgs[&src_id(0)].typed_id()],
+        added: vec![helper.s.msgs[&src_id(6)].typed_id()],
+        edited: vec![(helper.m.msgs[&src_id(3)].typed_id(), helper.s.msgs[&src_id(3)].typed_id())],
+    });
+    assert!(!diff.supersedes());
+    Ok(())
+}
+
+#[test]
+fn superseding() -> EmptyRes {
+    let msgs_a = (0..=3).map(|i| create_regular_message(i, MergerHelper::random_user_id(MAX_USER_ID))).collect_vec();
+    let mut msgs_b = msgs_a.clone();
+    msgs_b.push(create_regular_message(4, 1));
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs_a, msgs_b);
+
+    let diff = diff_chats(helper.m.dao_holder.dao.as_ref(), &helper.m.ds, helper.m.cwd(),
+                          helper.s.dao_holder.dao.as_ref(), &helper.s.ds, helper.s.cwd())?;
+    assert_eq!(diff, ChatDiff {
+        matching_count: 4,
+        removed: vec![],
+        added: vec![helper.s.msgs[&src_id(4)].typed_id()],
+        edited: vec![],
+    });
+    assert!(diff.supersedes());
+    Ok(())
+}
+
+/**
+ * ```text
+ * Dataset messages - 0 1 2 3  5 6
+ * Source messages  -   1 2 3* 4 5
+ * ```
+ * Source messages 4 and 5 are ephemeral.
+ */
+#[test]
+fn verifying_against_source() -> EmptyRes {
+    let msgs_a = (0..=6).map(|i| create_regular_message(i, MergerHelper::random_user_id(MAX_USER_ID))).collect_vec();
+    let mut msgs_b = msgs_a.cloned([1, 2, 3, 4, 5].map(src_id)).changed(|id| *id == 3);
+    for m in msgs_b.iter_mut().filter(|m| *m.source_id() >= 4) {
+        let message::Typed::Regular(mr) = m.typed_mut() else { unreachable!() };
+        mr.ephemeral_duration_sec_option = Some(60);
+    }
+    let msgs_a = msgs_a.cloned([0, 1, 2, 3, 5, 6].map(src_id));
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs_a, msgs_b);
+    let (dao, ds) = (helper.m.dao_holder.dao.as_ref(), &helper.m.ds);
+    let (src_dao, src_ds) = (helper.s.dao_holder.dao.as_ref(), &helper.s.ds);
+
+    let losses = verify_against_source(dao, ds, src_dao, src_ds)?;
+    assert_eq!(losses, vec![ChatLoss {
+        src_cwd: helper.s.cwd().clone(),
+        chat_missing: false,
+        missing: vec![helper.s.msgs[&src_id(4)].typed_id()],
+        missing_ephemeral_count: 1,
+    }]);
+
+    // Nothing is lost
+    assert_eq!(verify_against_source(src_dao, src_ds, src_dao, src_ds)?, vec![]);
+
+    // Whole chat is lost
+    let users = (1..=MAX_USER_ID).map(|i| create_user(&ZERO_PB_UUID, i as i64)).collect_vec();
+    let empty_dao_holder = create_dao("Empty", users, vec![], |_, _| {});
+    let empty_dao = empty_dao_holder.dao.as_ref();
+    let empty_ds = empty_dao.datasets()?.remove(0);
+    let losses = verify_against_source(empty_dao, &empty_ds, src_dao, src_ds)?;
+    assert_eq!(losses, vec![ChatLoss {
+        src_cwd: helper.s.cwd().clone(),
+        chat_missing: true,
+        missing: helper.s.msgs.values().map(|m| m.typed_id()).collect_vec(),
+        missing_ephemeral_count: 2,
+    }]);
+    Ok(())
+}
+
+/**
+ * ```text
+ * Other copy messages - 0 1 2 3  4 5
+ * This copy messages  -   1 2 3* 4 5 6
+ * ```
+ */
+#[test]
+fn finding_divergences() -> EmptyRes {
+    let msgs_a = (0..=5).map(|i| create_regular_message(i, MergerHelper::random_user_id(MAX_USER_ID))).collect_vec();
+    let mut msgs_b = msgs_a.cloned([1, 2, 3, 4, 5].map(src_id)).changed(|id| *id == 3);
+    msgs_b.push(create_regular_message(6, 1));
+    let helper = MergerHelper::new_as_is(MAX_USER_ID, msgs_a, msgs_b);
+    let m_dao: &dyn ChatHistoryReader = helper.m.dao_holder.dao.as_ref();
+    let s_dao: &dyn ChatHistoryReader = helper.s.dao_holder.dao.as_ref();
+
+    assert_eq!(dataset_divergence(m_dao, &helper.m.ds, m_dao, &helper.m.ds)?, None);
+
+    // Slave dataset under master UUID, as if both were copies of the same dataset
+    let ds = Dataset { uuid: helper.m.ds.uuid.clone(), ..helper.s.ds.clone() };
+    let users = s_dao.users(&helper.s.ds.uuid)?.into_iter()
+        .map(|u| User { ds_uuid: ds.uuid.clone(), ..u })
+        .collect_vec();
+    let chat = Chat { ds_uuid: ds.uuid.clone(), ..helper.s.cwd().chat.clone() };
+    let messages = s_dao.first_messages(&helper.s.cwd().chat, usize::MAX)?;
+    let copy = InMemoryDao::new_single("Copy".to_owned(), ds.clone(), helper.s.ds_root.0.clone(),
+                                       s_dao.myself(&helper.s.ds.uuid)?.id(), users,
+                                       vec![ChatWithMessages { chat: chat.clone(), messages }]);
+
+    // DAO stored in the same place is not a copy
+    let other_daos: [(&str, &dyn ChatHistoryReader); 2] = [("master", m_dao), ("copy", &copy)];
+    let divergences = find_divergences(&copy, &other_daos)?;
+    assert_eq!(divergences, vec![DatasetDivergence {
+        dataset: ds,
+        other_dao_key: "master".to_owned(),
+        other_dao_name: m_dao.name().to_owned(),
+        chat_ids_only_here: vec![],
+        chat_ids_only_in_other: vec![],
+        diverged_chat_ids: vec![chat.id],
+        messages_only_here: 1,
+        messages_only_in_other: 1,
+        edited_messages: 1,
+    }]);
+
+    // Unrelated datasets are not compared
+    assert_eq!(find_divergences(m_dao, &[("slave", s_dao)])?, vec![]);
+    Ok(())
+}
@@ -1,10 +1,13 @@
 use std::io;
+use chrono::Local;
 use itertools::Itertools;
 
-use crate::dao::ChatHistoryDao;
-use crate::dao::MutableChatHistoryDao;
+use crate::dao::ChatHistoryReader;
+use crate::dao::ChatHistoryWriter;
+use crate::dao::notes;
 use crate::dao::sqlite_dao::SqliteDao;
 use crate::merge::analyzer::*;
+use crate::merge::templates;
 use crate::prelude::*;
 
 #[cfg(test)]
@@ -18,15 +21,15 @@ const BATCH_SIZE: usize = 1000;
 /// user_merges and chat_merges should contain decisions for ALL users and chats.
 pub fn merge_datasets(
     sqlite_dao_dir: &Path,
-    master_dao: &dyn ChatHistoryDao,
+    master_dao: &dyn ChatHistoryReader,
     master_ds: &Dataset,
-    slave_dao: &dyn ChatHistoryDao,
+    slave_dao: &dyn ChatHistoryReader,
     slave_ds: &Dataset,
     user_merges: Vec<UserMergeDecision>,
     chat_merges: Vec<ChatMergeDecision>,
 ) -> Result<(SqliteDao, Dataset)> {
     measure(|| {
-        fn get_users_and_cwds(dao: &dyn ChatHistoryDao, ds_uuid: &PbUuid)
+        fn get_users_and_cwds(dao: &dyn ChatHistoryReader, ds_uuid: &PbUuid)
                               -> Result<(HashMap<UserId, User>, HashMap<ChatId, ChatWithDetails>)> {
             Ok((dao.users(ds_uuid)?.into_iter().map(|u| (u.id(), u)).collect(),
                 dao.chats(ds_uuid)?.into_iter().map(|cwd| (cwd.id(), cwd)).collect()))
@@ -79,7 +82,7 @@ pub fn merge_datasets(
 }
 
 struct DaoMergeEntities<'a> {
-    dao: &'a dyn ChatHistoryDao,
+    dao: &'a dyn ChatHistoryReader,
     ds: &'a Dataset,
     users: HashMap<UserId, User>,
     cwds: HashMap<ChatId, ChatWithDetails>,
@@ -97,6 +100,12 @@ fn merge_inner(
         alias: format!("{} (merged)", master.ds.alias),
     };
     let new_ds = new_dao.insert_dataset(new_ds)?;
+    let template_option = templates::common_source_type(slave.cwds.values()).map(|source_type| {
+        templates::make_template(&new_ds.uuid, source_type, Timestamp(Local::now().timestamp()),
+                                 &user_merges, &chat_merges)
+    });
+    new_dao.copy_import_batches_from(master.dao, &master.ds.uuid, &new_ds.uuid)?;
+    new_dao.copy_import_batches_from(slave.dao, &slave.ds.uuid, &new_ds.uuid)?;
 
     let master_ds_root = master.dao.dataset_root(&master.ds.uuid)?;
     let slave_ds_root = slave.dao.dataset_root(&slave.ds.uuid)?;
@@ -114,12 +123,16 @@ fn merge_inner(
             ChatMergeDecision::Merge { chat_id, .. } => {
                 let mut chat_to_insert = slave.cwds[chat_id].clone();
 
+                // User-assigned flags are preserved from master
+                let master_chat = &master.cwds[chat_id].chat;
+                chat_to_insert.chat.archived = master_chat.archived;
+                chat_to_insert.chat.hidden = master_chat.hidden;
+
                 // If slave chat has no image, preserve master image
                 let ds_root = if chat_to_insert.chat.get_img_path_option(&slave_ds_root).is_some_and(|p| p.exists()) {
                     &slave_ds_root
                 } else {
-                    let master_chat = &master.cwds[chat_id];
-                    chat_to_insert.chat.img_path_option = master_chat.chat.img_path_option.clone();
+                    chat_to_insert.chat.img_path_option = master_chat.img_path_option.clone();
                     &master_ds_root
                 };
 
@@ -170,6 +183,15 @@ fn merge_inner(
     }
     let final_users = new_dao.users(&new_ds.uuid)?;
 
+    // Master identity registry takes precedence, identities of users that weren't kept are dropped
+    let final_user_ids: HashSet<i64> = final_users.iter().map(|u| u.id).collect();
+    for entities in [&master, &slave] {
+        let identities = entities.dao.user_identities(&entities.ds.uuid)?.into_iter()
+            .filter(|identity| final_user_ids.contains(&identity.user_id))
+            .collect_vec();
+        new_dao.insert_user_identities(&new_ds.uuid, &identities)?;
+    }
+
     // Chats
     for (mut cwd, chat_ds_root, cm) in chat_inserts {
         cwd.chat.ds_uuid = new_ds.uuid.clone();
@@ -195,6 +217,24 @@ fn merge_inner(
         macro_rules! master_cwd { () => { &master.cwds[&cwd.id()] }; }
         macro_rules! slave_cwd { () =>  { &slave.cwds[&cwd.id()] }; }
 
+        let sources: Vec<(&dyn ChatHistoryReader, &ChatWithDetails)> = match cm {
+            ChatMergeDecision::Retain { .. } | ChatMergeDecision::DontMerge { .. } => vec![(master.dao, master_cwd!())],
+            ChatMergeDecision::Add { .. } => vec![(slave.dao, slave_cwd!())],
+            ChatMergeDecision::DontAdd { .. } => unreachable!(),
+            ChatMergeDecision::Merge { .. } => vec![(master.dao, master_cwd!()), (slave.dao, slave_cwd!())],
+        };
+
+        // Names chat had in sources (including ones they remember) are kept as former names
+        let mut former_names = vec![];
+        for (src_dao, src_cwd) in sources.iter() {
+            former_names.extend(src_dao.chat_name_history(&src_cwd.chat)?.into_iter()
+                .filter(|entry| entry.timestamp_option.is_none())
+                .map(|entry| entry.name));
+            former_names.extend(src_cwd.chat.name_option.clone());
+        }
+        former_names.retain(|name| new_chat.name_option.as_ref() != Some(name));
+        new_dao.insert_chat_former_names(&new_chat, &former_names)?;
+
         // Messages
         let mut msg_count = 0;
         match cm {
@@ -247,6 +287,8 @@ fn merge_inner(
                                         update_with_slave_data(&mut mm, &sm);
                                         (mm, Source::Master)
                                     } else {
+                                        let mut sm = sm;
+                                        sm.text_history = combine_text_histories(&mm.text_history, &sm.text_history);
                                         (sm, Source::Slave)
                                     }
                                 })
@@ -275,11 +317,22 @@ fn merge_inner(
                             vec![]
                         }
                         MessagesMergeDecision::Replace(v) => {
-                            // Treat exactly as Add
-                            // TODO: Should we analyze content and make sure nothing is lost?
-                            let msgs = slave.dao.messages_slice(&slave_cwd.chat,
-                                                                v.first_slave_msg_id.generalize(),
-                                                                v.last_slave_msg_id.generalize())?;
+                            // Treat as Add, but keep texts of master messages that were edited since
+                            // TODO: Should we analyze content and make sure nothing else is lost?
+                            let master_msgs = master.dao.messages_slice(&master_cwd.chat,
+                                                                        v.first_master_msg_id.generalize(),
+                                                                        v.last_master_msg_id.generalize())?;
+                            let master_msgs_by_source_id: HashMap<i64, Message> = master_msgs.into_iter()
+                                .filter_map(|mm| mm.source_id_option.map(|id| (id, mm)))
+                                .collect();
+                            let mut msgs = slave.dao.messages_slice(&slave_cwd.chat,
+                                                                    v.first_slave_msg_id.generalize(),
+                                                                    v.last_slave_msg_id.generalize())?;
+                            for sm in msgs.iter_mut() {
+                                if let Some(mm) = sm.source_id_option.and_then(|id| master_msgs_by_source_id.get(&id)) {
+                                    preserve_text_history(mm, sm);
+                                }
+                            }
                             vec![(Source::Slave, msgs)]
                         }
                         MessagesMergeDecision::DontReplace(v) => {
@@ -296,9 +349,9 @@ fn merge_inner(
                             Source::Master => &master_ds_root,
                             Source::Slave => &slave_ds_root,
                         };
-                        let cwd = match source {
-                            Source::Master => master_cwd,
-                            Source::Slave => slave_cwd
+                        let (src_dao, cwd) = match source {
+                            Source::Master => (master.dao, master_cwd),
+                            Source::Slave => (slave.dao, slave_cwd)
                         };
 
                         msg_count += msgs.len();
@@ -307,21 +360,41 @@ fn merge_inner(
                             for m in batch.iter_mut() {
                                 fixup_members(m, &final_users, cwd)?;
                             }
-                            new_dao.insert_messages(batch, &new_chat, ds_root)?;
+                            new_dao.insert_messages_from(src_dao, &cwd.chat, batch, &new_chat, ds_root)?;
                         }
                     }
                 }
             }
         }
         new_chat.msg_count = msg_count as i32;
-        new_dao.update_chat(new_chat.id(), new_chat)?;
+        let new_chat = new_dao.update_chat(new_chat.id(), new_chat)?;
+
+        // User notes are carried over from sources, as long as messages they're attached to survived
+        for (src_dao, src_cwd) in sources.iter() {
+            let src_notes = src_dao.notes(&src_cwd.chat.ds_uuid, Some(src_cwd.chat.id))?;
+            for note in notes::retarget_notes(*src_dao, &src_cwd.chat, &*new_dao, &new_chat, src_notes)? {
+                new_dao.insert_note(note)?;
+            }
+        }
+    }
+
+    // Master policy applies to the merged dataset, including messages that just came from slave
+    new_dao.update_blocked_users_policy(&new_ds.uuid, &master.dao.blocked_users_policy(&master.ds.uuid)?)?;
+    new_dao.apply_blocked_users_policy(&new_ds.uuid)?;
+
+    // Merged dataset becomes a master of the next merge, so it inherits master templates for other source types
+    for template in master.dao.merge_templates(&master.ds.uuid)? {
+        new_dao.store_merge_template(&MergeTemplate { ds_uuid: new_ds.uuid.clone(), ..template })?;
+    }
+    if let Some(template) = template_option {
+        new_dao.store_merge_template(&template)?;
     }
 
     Ok(new_ds)
 }
 
 fn copy_all_messages(
-    src_dao: &dyn ChatHistoryDao,
+    src_dao: &dyn ChatHistoryReader,
     src_cwd: &ChatWithDetails,
     src_ds_root: &DatasetRoot,
     dst_dao: &mut SqliteDao,
@@ -337,7 +410,7 @@ fn copy_all_messages(
         for m in batch.iter_mut() {
             fixup_members(m, final_users, src_cwd)?;
         }
-        dst_dao.insert_messages(batch, dst_chat, src_ds_root)?;
+        dst_dao.insert_messages_from(src_dao, &src_cwd.chat, batch, dst_chat, src_ds_root)?;
         offset += BATCH_SIZE;
     }
     Ok(msg_count)
@@ -389,11 +462,13 @@ fn fixup_members(msg: &mut Message, final_users: &[User], cwd: &ChatWithDetails)
 /// * Source message ID
 /// * File name (if present)
 ///
+/// Text histories of both messages are combined.
 ///
 /// Messages are assumed to be matching.
 /// Rationale for file name is that newer version may reveal more accurate info.
 fn update_with_slave_data(mm: &mut Message, sm: &Message) {
     mm.source_id_option = sm.source_id_option;
+    mm.text_history = combine_text_histories(&mm.text_history, &sm.text_history);
     match (mm.typed_mut(), sm.typed()) {
         (message::Typed::Regular(mmr), message::Typed::Regular(smr)) => {
             mmr.reply_to_message_id_option = smr.reply_to_message_id_option;
@@ -415,6 +490,32 @@ fn update_with_slave_data(mm: &mut Message, sm: &Message) {
     }
 }
 
+/// If master message text differs from that of the slave message replacing it, record master text
+/// as a former version of slave message text, so that edits are not lost.
+fn preserve_text_history(mm: &Message, sm: &mut Message) {
+    let mut former_versions = mm.text_history.clone();
+    if !mm.text.is_empty() && mm.text != sm.text {
+        let timestamp = match mm.typed() {
+            message::Typed::Regular(mmr) => mmr.edit_timestamp_option.unwrap_or(mm.timestamp),
+            message::Typed::Service(_) => mm.timestamp,
+        };
+        former_versions.push(MessageTextVersion { timestamp, text: mm.text.clone() });
+    }
+    sm.text_history = combine_text_histories(&former_versions, &sm.text_history);
+}
+
+/// Union of two text histories, ordered by timestamp.
+fn combine_text_histories(h1: &[MessageTextVersion], h2: &[MessageTextVersion]) -> Vec<MessageTextVersion> {
+    let mut res = h1.to_vec();
+    for v in h2 {
+        if !res.contains(v) {
+            res.push(v.clone());
+        }
+    }
+    res.sort_by_key(|v| v.timestamp);
+    res
+}
+
 /// Deduplicate profile pictures vec by content. Skips subsequent elements, ignoring framing.
 fn dedup_profile_pics(profile_pics: Vec<AbsoluteProfilePicture>) -> Result<Vec<AbsoluteProfilePicture>> {
     let mut seen = HashSet::new();
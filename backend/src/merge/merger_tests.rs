@@ -5,7 +5,7 @@ use pretty_assertions::{assert_eq, assert_ne};
 use uuid::Uuid;
 
 use crate::prelude::*;
-use crate::dao::{ChatHistoryDao, UserCacheForDataset, WithCache};
+use crate::dao::{ChatHistoryReader, UserCacheForDataset, WithCache};
 
 use super::*;
 
@@ -122,6 +122,38 @@ fn merge_users_updating_chat_name() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn merge_chats_preserves_flags() -> EmptyRes {
+    let users = (1..=2).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let cwm = |archived: bool| ChatWithMessages {
+        chat: Chat { archived: Some(archived), hidden: Some(!archived), ..create_group_chat(&ZERO_PB_UUID, 1, "Group", vec![1, 2], 0) },
+        messages: vec![],
+    };
+
+    let helper = MergerHelper::new_from_daos(
+        create_dao("One", users.clone(), vec![cwm(true)], |_, _| {}),
+        create_dao("Two", users.clone(), vec![cwm(false)], |_, _| {}),
+    );
+
+    let (new_dao, new_ds, _tmpdir) = merge(
+        &helper,
+        vec![
+            UserMergeDecision::MatchOrDontReplace(UserId(1)),
+            UserMergeDecision::MatchOrDontReplace(UserId(2)),
+        ],
+        vec![ChatMergeDecision::Merge {
+            chat_id: ChatId(1),
+            message_merges: vec![],
+        }],
+    );
+
+    let new_chat = new_dao.chats(&new_ds.uuid)?.remove(0).chat;
+    assert!(new_chat.archived());
+    assert!(!new_chat.hidden());
+
+    Ok(())
+}
+
 #[test]
 fn merge_multiple_datasets() -> EmptyRes {
     let msgs = vec![create_regular_message(1, 1)];
@@ -472,7 +504,10 @@ fn merge_chats_replace_single_message() -> EmptyRes {
     let new_messages = new_dao.first_messages(new_chat, usize::MAX)?;
     assert_eq!(new_messages.len(), 1);
 
-    assert_practically_equals(&helper.s.msgs[&src_id(1)].0, &helper.s.ds_root, helper.s.cwd(),
+    // Former text is kept in history
+    let expected = with_former_text(&helper.s.msgs[&src_id(1)].0, &helper.m.msgs[&src_id(1)].0);
+    assert_eq!(new_messages[0].text_history.len(), 1);
+    assert_practically_equals(&expected, &helper.s.ds_root, helper.s.cwd(),
                               &new_messages[0], &new_ds_root, &new_chats[0]);
 
     Ok(())
@@ -548,7 +583,10 @@ fn merge_chats_replace_two_messages() -> EmptyRes {
     assert_eq!(new_messages.len(), helper.s.msgs.len());
     assert_eq!(new_chat.msg_count, helper.s.msgs.len() as i32);
 
-    for (SlaveMessage(s_msg), new_msg) in helper.s.msgs.values().zip(new_messages.iter()) {
+    let expected = helper.s.msgs.values().zip(helper.m.msgs.values())
+        .map(|(SlaveMessage(s_msg), MasterMessage(m_msg))| with_former_text(s_msg, m_msg))
+        .collect_vec();
+    for (s_msg, new_msg) in expected.iter().zip(new_messages.iter()) {
         assert_practically_equals(s_msg, &helper.s.ds_root, helper.s.cwd(),
                                   new_msg, &new_ds_root, &new_chats[0]);
     }
@@ -613,11 +651,13 @@ fn merge_chats_match_replace_keep() -> EmptyRes {
     assert_eq!(new_messages.len(), 6);
     assert_eq!(new_chat.msg_count, 6);
 
+    let replaced_3 = with_former_text(&helper.s.msgs[&src_id(3)].0, &helper.m.msgs[&src_id(3)].0);
+    let replaced_4 = with_former_text(&helper.s.msgs[&src_id(4)].0, &helper.m.msgs[&src_id(4)].0);
     let expected = vec![
         PracticalEqTuple::new(&helper.m.msgs[&src_id(1)].0, &helper.m.ds_root, helper.m.cwd()),
         PracticalEqTuple::new(&helper.m.msgs[&src_id(2)].0, &helper.m.ds_root, helper.m.cwd()),
-        PracticalEqTuple::new(&helper.s.msgs[&src_id(3)].0, &helper.s.ds_root, helper.s.cwd()),
-        PracticalEqTuple::new(&helper.s.msgs[&src_id(4)].0, &helper.s.ds_root, helper.s.cwd()),
+        PracticalEqTuple::new(&replaced_3, &helper.s.ds_root, helper.s.cwd()),
+        PracticalEqTuple::new(&replaced_4, &helper.s.ds_root, helper.s.cwd()),
         PracticalEqTuple::new(&helper.m.msgs[&src_id(5)].0, &helper.m.ds_root, helper.m.cwd()),
         PracticalEqTuple::new(&helper.m.msgs[&src_id(6)].0, &helper.m.ds_root, helper.m.cwd()),
     ];
@@ -703,12 +743,13 @@ fn merge_chats_merge_all_modes() -> EmptyRes {
     assert_eq!(new_messages.len(), 5);
     assert_eq!(new_chat.msg_count, 5);
 
+    let replaced_6 = with_former_text(&helper.s.msgs[&src_id(6)].0, &helper.m.msgs[&src_id(6)].0);
     let expected = vec![
         PracticalEqTuple::new(&helper.m.msgs[&src_id(1)].0, &helper.m.ds_root, helper.m.cwd()),
         PracticalEqTuple::new(&helper.s.msgs[&src_id(2)].0, &helper.s.ds_root, helper.s.cwd()),
         PracticalEqTuple::new(&helper.m.msgs[&src_id(4)].0, &helper.m.ds_root, helper.m.cwd()),
         PracticalEqTuple::new(&helper.m.msgs[&src_id(5)].0, &helper.m.ds_root, helper.m.cwd()),
-        PracticalEqTuple::new(&helper.s.msgs[&src_id(6)].0, &helper.s.ds_root, helper.s.cwd()),
+        PracticalEqTuple::new(&replaced_6, &helper.s.ds_root, helper.s.cwd()),
     ];
 
     for (old_pet, new_msg) in expected.into_iter().zip(new_messages.iter()) {
@@ -769,9 +810,12 @@ fn merge_chats_merge_a_lot_of_messages() -> EmptyRes {
     assert_eq!(new_messages.len(), MAX_MSG_ID as usize);
     assert_eq!(new_chat.msg_count, MAX_MSG_ID as i32);
 
+    let replaced = (1..half)
+        .map(|i| with_former_text(&helper.s.msgs[&src_id(i)].0, &helper.m.msgs[&src_id(i)].0))
+        .collect_vec();
     let expected = vec![
-        (1..half)
-            .map(|i| PracticalEqTuple::new(&helper.s.msgs[&src_id(i)].0, &helper.s.ds_root, helper.s.cwd()))
+        replaced.iter()
+            .map(|msg| PracticalEqTuple::new(msg, &helper.s.ds_root, helper.s.cwd()))
             .collect_vec(),
         (half..=MAX_MSG_ID)
             .map(|i| PracticalEqTuple::new(&helper.m.msgs[&src_id(i)].0, &helper.m.ds_root, helper.m.cwd()))
@@ -1222,6 +1266,58 @@ fn merge_chats_content_adopt_new_filename() -> EmptyRes {
     Ok(())
 }
 
+#[test]
+fn merge_stores_template() -> EmptyRes {
+    let users = (1..=3).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let cwm = |id: i64, member_ids: Vec<i64>| ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, id, "Chat", member_ids, 0),
+        messages: vec![],
+    };
+    let helper = MergerHelper::new_from_daos(
+        create_dao("One", users[..2].to_vec(), vec![cwm(1, vec![1, 2])], |_, _| {}),
+        create_dao("Two", users.clone(), vec![cwm(1, vec![1, 2]), cwm(2, vec![1, 3])], |_, _| {}),
+    );
+
+    let (new_dao, new_ds, _tmpdir) = merge(
+        &helper,
+        vec![
+            UserMergeDecision::MatchOrDontReplace(UserId(1)),
+            UserMergeDecision::Replace(UserId(2)),
+            UserMergeDecision::Add(UserId(3)),
+        ],
+        vec![
+            ChatMergeDecision::Merge { chat_id: ChatId(1), message_merges: vec![] },
+            ChatMergeDecision::Add { slave_chat_id: ChatId(2) },
+        ],
+    );
+
+    let stored = new_dao.merge_templates(&new_ds.uuid)?;
+    assert_eq!(stored.len(), 1);
+    let stored = &stored[0];
+    assert_eq!(stored.ds_uuid, new_ds.uuid);
+    assert_eq!(stored.source_type(), SourceType::Telegram);
+    assert_eq!(stored.user_merges, vec![
+        UserMerge { tpe: UserMergeType::MatchOrDontReplace as i32, user_id: 1 },
+        UserMerge { tpe: UserMergeType::Replace as i32, user_id: 2 },
+        UserMerge { tpe: UserMergeType::Add as i32, user_id: 3 },
+    ]);
+    assert_eq!(stored.chat_merges, vec![
+        ChatMerge { tpe: ChatMergeType::Merge as i32, chat_id: 1, message_merges: vec![] },
+        ChatMerge { tpe: ChatMergeType::Add as i32, chat_id: 2, message_merges: vec![] },
+    ]);
+
+    // Merging the same slave again, added entities are now present in both
+    let template = templates::applicable_template(&new_dao, &new_ds, helper.s.dao_holder.dao.as_ref(), &helper.s.ds)?
+        .expect("Template should be found");
+    assert_eq!(template.user_merges.iter().map(|um| um.user_id).collect_vec(), vec![1, 2]);
+    assert_eq!(template.chat_merges.iter().map(|cm| cm.chat_id).collect_vec(), vec![1]);
+
+    // Master had no templates to offer
+    assert_eq!(templates::applicable_template(helper.m.dao_holder.dao.as_ref(), &helper.m.ds,
+                                              helper.s.dao_holder.dao.as_ref(), &helper.s.ds)?, None);
+    Ok(())
+}
+
 //
 // Helpers
 //
@@ -1309,6 +1405,18 @@ enum ContentMode {
     NonePaths,
 }
 
+/// Slave message as it should look after replacing a master message with a different text.
+fn with_former_text(s_msg: &Message, m_msg: &Message) -> Message {
+    let timestamp = match m_msg.typed() {
+        message::Typed::Regular(mr) => mr.edit_timestamp_option.unwrap_or(m_msg.timestamp),
+        message::Typed::Service(_) => m_msg.timestamp,
+    };
+    Message {
+        text_history: vec![MessageTextVersion { timestamp, text: m_msg.text.clone() }],
+        ..s_msg.clone()
+    }
+}
+
 fn assert_practically_equals(src: &Message, src_ds_root: &DatasetRoot, src_cwd: &ChatWithDetails,
                              dst: &Message, dst_ds_root: &DatasetRoot, dst_cwd: &ChatWithDetails) {
     let src_pet = PracticalEqTuple::new(src, src_ds_root, src_cwd);
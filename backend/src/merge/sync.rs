@@ -0,0 +1,148 @@
+use itertools::Itertools;
+
+use crate::dao::ChatHistoryReader;
+use crate::dao::ChatHistoryWriter;
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "sync_tests.rs"]
+mod tests;
+
+const BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub new_users: usize,
+    pub new_chats: usize,
+    pub new_messages: usize,
+}
+
+/// Append everything from source dataset that is newer than what destination dataset already has.
+/// Intended for re-imports of a source that was previously imported (and possibly merged) into destination,
+/// so unlike full merge this does not reconcile anything - existing users, chats (including their members)
+/// and messages are left as-is, only users/chats that are missing are added,
+/// and only messages past the last known one are appended.
+///
+/// Source users are resolved through the identity registry of destination, so that users whose IDs were
+/// changed after the previous import (e.g. when merging) are still matched. New identities are registered.
+pub fn append_sync(
+    src: &dyn ChatHistoryReader,
+    src_ds_uuid: &PbUuid,
+    dst: &mut dyn ChatHistoryWriter,
+    dst_ds_uuid: &PbUuid,
+) -> Result<SyncReport> {
+    measure(|| {
+        let src_identities = src.user_identities(src_ds_uuid)?;
+        let id_map = canonical_user_ids(&src_identities, &dst.user_identities(dst_ds_uuid)?);
+        if !id_map.is_empty() {
+            log::info!("{} user(s) resolved to different IDs through identity registry", id_map.len());
+        }
+        let canonical = |id: i64| id_map.get(&id).cloned().unwrap_or(id);
+
+        let src_myself = src.myself(src_ds_uuid)?;
+        let dst_myself = dst.myself(dst_ds_uuid)?;
+        ensure!(canonical(src_myself.id) == dst_myself.id,
+                "Cannot sync, self differs ({} in source vs {} in destination)",
+                src_myself.pretty_name(), dst_myself.pretty_name());
+
+        let src_ds_root = src.dataset_root(src_ds_uuid)?;
+        let mut report = SyncReport::default();
+
+        dst.copy_import_batches_from(src, src_ds_uuid, dst_ds_uuid)?;
+        dst.insert_user_identities(dst_ds_uuid, &src_identities.into_iter()
+            .map(|identity| UserIdentity { user_id: canonical(identity.user_id), ..identity })
+            .collect_vec())?;
+
+        // Users
+        let dst_user_ids: HashSet<i64> = dst.users(dst_ds_uuid)?.iter().map(|u| u.id).collect();
+        for user in src.users(src_ds_uuid)? {
+            let user = User { id: canonical(user.id), ..user };
+            if dst_user_ids.contains(&user.id) { continue; }
+            let src_profile_pics = user.profile_pictures.clone();
+            let profile_pics = src_profile_pics.iter().map(|pp| pp.to_absolute(&src_ds_root)).collect_vec();
+            let user = dst.insert_user(User { ds_uuid: dst_ds_uuid.clone(), ..user }, false)?;
+            dst.update_user_profile_pics(user, profile_pics)?;
+            report.new_users += 1;
+        }
+
+        // Chats and messages
+        let dst_cwds: HashMap<i64, ChatWithDetails> =
+            dst.chats(dst_ds_uuid)?.into_iter().map(|cwd| (cwd.chat.id, cwd)).collect();
+        for src_cwd in src.chats(src_ds_uuid)? {
+            let (mut dst_chat, last_known) = match dst_cwds.get(&src_cwd.chat.id) {
+                None => {
+                    let chat = Chat {
+                        ds_uuid: dst_ds_uuid.clone(),
+                        msg_count: 0,
+                        member_ids: src_cwd.chat.member_ids.iter().map(|id| canonical(*id)).collect_vec(),
+                        ..src_cwd.chat.clone()
+                    };
+                    report.new_chats += 1;
+                    (dst.insert_chat(chat, &src_ds_root)?, None)
+                }
+                Some(dst_cwd) =>
+                    (dst_cwd.chat.clone(), last_known_messages(dst, dst_cwd)?),
+            };
+
+            let mut appended = 0_usize;
+            let mut offset = 0_usize;
+            loop {
+                let batch = src.scroll_messages(&src_cwd.chat, offset, BATCH_SIZE)?;
+                if batch.is_empty() { break; }
+                offset += batch.len();
+                let batch = batch.into_iter().filter(|m| match last_known {
+                    None => true,
+                    Some((ts, ref source_ids)) =>
+                        m.timestamp > ts || (m.timestamp == ts && !source_ids.contains(&m.source_id_option)),
+                }).map(|mut m| {
+                    m.from_id = canonical(m.from_id);
+                    if let Some(message::Typed::Regular(mr)) = m.typed.as_mut() {
+                        mr.forward_from_id_option = mr.forward_from_id_option.map(canonical);
+                    }
+                    m
+                }).collect_vec();
+                if batch.is_empty() { continue; }
+                appended += batch.len();
+                dst.insert_messages_from(src, &src_cwd.chat, batch, &dst_chat, &src_ds_root)?;
+            }
+
+            if appended > 0 {
+                dst_chat.msg_count += appended as i32;
+                dst.update_chat(dst_chat.id(), dst_chat)?;
+            }
+            report.new_messages += appended;
+        }
+
+        Ok(report)
+    }, |report, t| match report {
+        Ok(report) => log::info!("Dataset synced in {t} ms: {report:?}"),
+        Err(_) => log::info!("Dataset sync failed after {t} ms"),
+    })
+}
+
+/// Source users whose keys are registered in destination under a different ID, mapped to that ID.
+fn canonical_user_ids(src_identities: &[UserIdentity], dst_identities: &[UserIdentity]) -> HashMap<i64, i64> {
+    let dst_ids: HashMap<(&str, &str), i64> = dst_identities.iter()
+        .map(|i| ((i.source.as_str(), i.source_key.as_str()), i.user_id))
+        .collect();
+    src_identities.iter()
+        .filter_map(|i| match dst_ids.get(&(i.source.as_str(), i.source_key.as_str())) {
+            Some(dst_id) if *dst_id != i.user_id => Some((i.user_id, *dst_id)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Timestamp of the last message in a chat, along with source IDs of all messages sharing that timestamp.
+fn last_known_messages(
+    dao: &dyn ChatHistoryWriter,
+    cwd: &ChatWithDetails,
+) -> Result<Option<(i64, HashSet<Option<i64>>)>> {
+    let Some(last) = cwd.last_msg_option.as_ref() else { return Ok(None) };
+    let ts = last.timestamp;
+    let source_ids = dao.last_messages(&cwd.chat, BATCH_SIZE)?.iter()
+        .filter(|m| m.timestamp == ts)
+        .map(|m| m.source_id_option)
+        .collect();
+    Ok(Some((ts, source_ids)))
+}
@@ -0,0 +1,96 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::dao::sqlite_dao::SqliteDao;
+
+use super::*;
+
+//
+// Tests
+//
+
+#[test]
+fn append_sync_new_messages_and_chats() -> EmptyRes {
+    let users = (1..=3).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let old_msgs = (0..5).map(|idx| create_regular_message(idx, 1 + idx % 2)).collect_vec();
+    let new_msgs = (0..8).map(|idx| create_regular_message(idx, 1 + idx % 2)).collect_vec();
+
+    let old_dao_holder = create_dao("Old", users[..2].to_vec(), vec![ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 1, "One", vec![1, 2], old_msgs.len()),
+        messages: old_msgs,
+    }], |_, _| {});
+    let new_dao_holder = create_dao("New", users.clone(), vec![ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 1, "One", vec![1, 2], new_msgs.len()),
+        messages: new_msgs.clone(),
+    }, ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 2, "Two", vec![1, 3], 1),
+        messages: vec![Message { from_id: 3, ..create_regular_message(0, 3) }],
+    }], |_, _| {});
+    let old_ds_uuid = old_dao_holder.dao.ds_uuid();
+    let new_ds_uuid = new_dao_holder.dao.ds_uuid();
+
+    let tmp_dir = TmpDir::new();
+    let mut dst = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME))?;
+    dst.copy_datasets_from(old_dao_holder.dao.as_ref(), &[old_ds_uuid.clone()])?;
+
+    let report = append_sync(new_dao_holder.dao.as_ref(), &new_ds_uuid, &mut dst, &old_ds_uuid)?;
+    assert_eq!(report, SyncReport { new_users: 1, new_chats: 1, new_messages: 4 });
+
+    assert_eq!(dst.users(&old_ds_uuid)?.len(), 3);
+    let cwds = dst.chats(&old_ds_uuid)?;
+    assert_eq!(cwds.len(), 2);
+    let chat1 = &cwds.iter().find(|cwd| cwd.chat.id == 1).unwrap().chat;
+    assert_eq!(chat1.msg_count, 8);
+    assert_eq!(dst.first_messages(chat1, usize::MAX)?.iter().map(|m| m.source_id_option).collect_vec(),
+               new_msgs.iter().map(|m| m.source_id_option).collect_vec());
+    assert_eq!(cwds.iter().find(|cwd| cwd.chat.id == 2).unwrap().chat.msg_count, 1);
+
+    // Syncing again is a no-op
+    let report = append_sync(new_dao_holder.dao.as_ref(), &new_ds_uuid, &mut dst, &old_ds_uuid)?;
+    assert_eq!(report, SyncReport::default());
+    Ok(())
+}
+
+#[test]
+fn append_sync_resolves_users_through_identity_registry() -> EmptyRes {
+    let users = (1..=3).map(|id| create_user(&ZERO_PB_UUID, id)).collect_vec();
+    let old_msgs = (0..5).map(|idx| create_regular_message(idx, 1 + idx % 2)).collect_vec();
+    let new_msgs = (0..8).map(|idx| create_regular_message(idx, 1 + idx % 2)).collect_vec();
+    let identities = |ids: &[i64]| ids.iter().map(|id| UserIdentity {
+        source: "Test".to_owned(),
+        source_key: format!("key{id}"),
+        user_id: *id,
+    }).collect_vec();
+
+    let mut old_dao_holder = create_dao("Old", users[..2].to_vec(), vec![ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 1, "One", vec![1, 2], old_msgs.len()),
+        messages: old_msgs,
+    }], |_, _| {});
+    let mut new_dao_holder = create_dao("New", users.clone(), vec![ChatWithMessages {
+        chat: create_group_chat(&ZERO_PB_UUID, 1, "One", vec![1, 2, 3], new_msgs.len()),
+        messages: new_msgs,
+    }], |_, _| {});
+    let old_ds_uuid = old_dao_holder.dao.ds_uuid();
+    let new_ds_uuid = new_dao_holder.dao.ds_uuid();
+    old_dao_holder.dao.user_identities.insert(old_ds_uuid.clone(), identities(&[1, 2]));
+    new_dao_holder.dao.user_identities.insert(new_ds_uuid.clone(), identities(&[1, 2, 3]));
+
+    let tmp_dir = TmpDir::new();
+    let mut dst = SqliteDao::create(&tmp_dir.path.join(SqliteDao::FILENAME))?;
+    dst.copy_datasets_from(old_dao_holder.dao.as_ref(), &[old_ds_uuid.clone()])?;
+
+    // User ID changes after import, registry follows
+    let user2 = dst.users(&old_ds_uuid)?.into_iter().find(|u| u.id == 2).unwrap();
+    dst.update_user(UserId(2), User { id: 20, ..user2 })?;
+    assert_eq!(dst.user_identities(&old_ds_uuid)?.iter().map(|i| i.user_id).collect_vec(), vec![1, 20]);
+
+    let report = append_sync(new_dao_holder.dao.as_ref(), &new_ds_uuid, &mut dst, &old_ds_uuid)?;
+    assert_eq!(report, SyncReport { new_users: 1, new_chats: 0, new_messages: 3 });
+
+    assert_eq!(dst.users(&old_ds_uuid)?.iter().map(|u| u.id).sorted().collect_vec(), vec![1, 3, 20]);
+    let chat1 = dst.chats(&old_ds_uuid)?.remove(0).chat;
+    assert_eq!(dst.last_messages(&chat1, 3)?.iter().map(|m| m.from_id).collect_vec(), vec![20, 1, 20]);
+    assert_eq!(dst.user_identities(&old_ds_uuid)?.iter().map(|i| i.user_id).collect_vec(), vec![1, 20, 3]);
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use itertools::Itertools;
+
+use crate::dao::ChatHistoryReader;
+use crate::merge::merger::{ChatMergeDecision, UserMergeDecision};
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "templates_tests.rs"]
+mod tests;
+
+/// Source type shared by all given chats, `None` if there are none or they came from different sources.
+pub fn common_source_type<'a>(cwds: impl IntoIterator<Item=&'a ChatWithDetails>) -> Option<SourceType> {
+    cwds.into_iter()
+        .map(|cwd| cwd.chat.source_type())
+        .unique()
+        .exactly_one()
+        .ok()
+}
+
+/// Template of a merge that resulted in a given dataset. Message merge decisions are dropped.
+pub fn make_template(ds_uuid: &PbUuid,
+                     source_type: SourceType,
+                     timestamp: Timestamp,
+                     user_merges: &[UserMergeDecision],
+                     chat_merges: &[ChatMergeDecision]) -> MergeTemplate {
+    let user_merges = user_merges.iter().map(|um| {
+        let (tpe, id) = match um {
+            UserMergeDecision::Retain(id) => (UserMergeType::Retain, id),
+            UserMergeDecision::Add(id) => (UserMergeType::Add, id),
+            UserMergeDecision::DontAdd(id) => (UserMergeType::DontAdd, id),
+            UserMergeDecision::Replace(id) => (UserMergeType::Replace, id),
+            UserMergeDecision::MatchOrDontReplace(id) => (UserMergeType::MatchOrDontReplace, id),
+        };
+        UserMerge { tpe: tpe as i32, user_id: id.0 }
+    }).collect_vec();
+    let chat_merges = chat_merges.iter().map(|cm| {
+        let (tpe, id) = match cm {
+            ChatMergeDecision::Retain { master_chat_id } => (ChatMergeType::Retain, master_chat_id),
+            ChatMergeDecision::Add { slave_chat_id } => (ChatMergeType::Add, slave_chat_id),
+            ChatMergeDecision::DontAdd { slave_chat_id } => (ChatMergeType::DontAdd, slave_chat_id),
+            ChatMergeDecision::Merge { chat_id, .. } => (ChatMergeType::Merge, chat_id),
+            ChatMergeDecision::DontMerge { chat_id } => (ChatMergeType::DontMerge, chat_id),
+        };
+        ChatMerge { tpe: tpe as i32, chat_id: id.0, message_merges: vec![] }
+    }).collect_vec();
+    MergeTemplate {
+        ds_uuid: ds_uuid.clone(),
+        source_type: source_type as i32,
+        timestamp: timestamp.0,
+        user_merges,
+        chat_merges,
+    }
+}
+
+/// Template stored in master dataset for the source type of slave, narrowed down to decisions applicable to this pair,
+/// see [narrow_template].
+pub fn applicable_template(master_dao: &dyn ChatHistoryReader,
+                           master_ds: &Dataset,
+                           slave_dao: &dyn ChatHistoryReader,
+                           slave_ds: &Dataset) -> Result<Option<MergeTemplate>> {
+    let slave_cwds = slave_dao.chats(&slave_ds.uuid)?;
+    let Some(source_type) = common_source_type(slave_cwds.iter()) else { return Ok(None) };
+    let Some(template) = master_dao.merge_templates(&master_ds.uuid)?.into_iter()
+        .find(|t| t.source_type == source_type as i32) else { return Ok(None) };
+
+    let user_ids = |dao: &dyn ChatHistoryReader, ds: &Dataset| -> Result<HashSet<i64>> {
+        Ok(dao.users(&ds.uuid)?.into_iter().map(|u| u.id).collect())
+    };
+    let master_chat_ids = master_dao.chats(&master_ds.uuid)?.into_iter().map(|cwd| cwd.chat.id).collect();
+    let slave_chat_ids = slave_cwds.into_iter().map(|cwd| cwd.chat.id).collect();
+    Ok(Some(narrow_template(template,
+                            &user_ids(master_dao, master_ds)?, &user_ids(slave_dao, slave_ds)?,
+                            &master_chat_ids, &slave_chat_ids)))
+}
+
+/// Drops decisions for users and chats absent from this pair, as well as ones that no longer fit
+/// (e.g. `Add` for a chat that now exists in master).
+pub fn narrow_template(template: MergeTemplate,
+                       master_user_ids: &HashSet<i64>,
+                       slave_user_ids: &HashSet<i64>,
+                       master_chat_ids: &HashSet<i64>,
+                       slave_chat_ids: &HashSet<i64>) -> MergeTemplate {
+    let user_merges = template.user_merges.into_iter().filter(|um| {
+        let presence = (master_user_ids.contains(&um.user_id), slave_user_ids.contains(&um.user_id));
+        match um.tpe() {
+            UserMergeType::Retain => presence == (true, false),
+            UserMergeType::Add | UserMergeType::DontAdd => presence == (false, true),
+            UserMergeType::Replace | UserMergeType::MatchOrDontReplace => presence == (true, true),
+        }
+    }).collect_vec();
+    let chat_merges = template.chat_merges.into_iter().filter(|cm| {
+        let presence = (master_chat_ids.contains(&cm.chat_id), slave_chat_ids.contains(&cm.chat_id));
+        match cm.tpe() {
+            ChatMergeType::Retain => presence == (true, false),
+            ChatMergeType::Add | ChatMergeType::DontAdd => presence == (false, true),
+            ChatMergeType::Merge | ChatMergeType::DontMerge => presence == (true, true),
+        }
+    }).collect_vec();
+    MergeTemplate { user_merges, chat_merges, ..template }
+}
@@ -0,0 +1,54 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+#[test]
+fn finding_common_source_type() {
+    let cwd = |id: i64, source_type: SourceType| {
+        let mut chat = create_group_chat(&ZERO_PB_UUID, id, "Chat", vec![1, 2], 0);
+        chat.source_type = source_type as i32;
+        ChatWithDetails { chat, last_msg_option: None, members: vec![] }
+    };
+    let telegram = [cwd(1, SourceType::Telegram), cwd(2, SourceType::Telegram)];
+    let mixed = [cwd(1, SourceType::Telegram), cwd(2, SourceType::WhatsappDb)];
+
+    assert_eq!(common_source_type(telegram.iter()), Some(SourceType::Telegram));
+    assert_eq!(common_source_type(mixed.iter()), None);
+    assert_eq!(common_source_type([].iter()), None);
+}
+
+#[test]
+fn narrowing() {
+    let template = make_template(&ZERO_PB_UUID, SourceType::Telegram, Timestamp(123), &[
+        UserMergeDecision::Retain(UserId(1)),
+        UserMergeDecision::Add(UserId(2)),
+        UserMergeDecision::DontAdd(UserId(3)),
+        UserMergeDecision::MatchOrDontReplace(UserId(4)),
+        UserMergeDecision::Replace(UserId(5)),
+    ], &[
+        ChatMergeDecision::Retain { master_chat_id: ChatId(1) },
+        ChatMergeDecision::Add { slave_chat_id: ChatId(2) },
+        ChatMergeDecision::DontAdd { slave_chat_id: ChatId(3) },
+        ChatMergeDecision::Merge { chat_id: ChatId(4), message_merges: vec![] },
+        ChatMergeDecision::DontMerge { chat_id: ChatId(5) },
+    ]);
+    assert_eq!(template.timestamp, 123);
+    assert_eq!(template.source_type(), SourceType::Telegram);
+
+    let ids = |ids: &[i64]| ids.iter().cloned().collect::<HashSet<_>>();
+
+    // Same layout as before, everything applies
+    let narrowed = narrow_template(template.clone(),
+                                   &ids(&[1, 4, 5]), &ids(&[2, 3, 4, 5]),
+                                   &ids(&[1, 4, 5]), &ids(&[2, 3, 4, 5]));
+    assert_eq!(narrowed, template);
+
+    // Previously added entities are now in master, entity 3 is gone altogether, and 5 is no longer in slave
+    let narrowed = narrow_template(template.clone(),
+                                   &ids(&[1, 2, 4, 5]), &ids(&[2, 4]),
+                                   &ids(&[1, 2, 4, 5]), &ids(&[2, 4]));
+    assert_eq!(narrowed.user_merges.iter().map(|um| um.user_id).collect_vec(), vec![1, 4]);
+    assert_eq!(narrowed.chat_merges.iter().map(|cm| cm.chat_id).collect_vec(), vec![1, 4]);
+}
@@ -1 +1,18 @@
+//! Protobuf entities and services.
+//!
+//! Databases, exported files and the frontend all depend on protobuf wire format staying stable,
+//! so schema evolution must follow these rules:
+//! * Field numbers, labels and types never change, and field numbers are never reused.
+//!   Removed fields are mentioned in a comment and marked as `removed` in `protobuf/schema.lock`.
+//! * Fields added to existing messages are `optional` or `repeated`, never `required`,
+//!   so that entities serialized before can still be read.
+//! * Enum values are never renumbered or removed.
+//!
+//! Rules are enforced by tests comparing schema against `protobuf/schema.lock`, which should be refreshed on release
+//! (by running ignored `update_schema_lock` test), and by decoding golden entities serialized by previous releases.
+
 pub mod history;
+
+#[cfg(test)]
+#[path = "protobuf_tests.rs"]
+mod tests;
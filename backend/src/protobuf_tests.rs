@@ -0,0 +1,412 @@
+#![allow(unused_imports)]
+
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use pretty_assertions::{assert_eq, assert_ne};
+use prost::Message as ProstMessage;
+use regex::Regex;
+
+use crate::prelude::*;
+
+const PROTO_FILES: [&str; 2] = ["../core/protobuf/entities.proto", "protobuf/services.proto"];
+const SCHEMA_LOCK_FILE: &str = "protobuf/schema.lock";
+const GOLDEN_DIR: &str = "protobuf-golden_0.9.0";
+
+const REMOVED: &str = "removed";
+
+//
+// Tests
+//
+
+#[test]
+fn schema_evolution_policy() -> EmptyRes {
+    let violations = policy_violations(&load_schema_lock()?, &parse_current_schema()?);
+    assert!(violations.is_empty(), "Protobuf schema evolution policy is violated:\n{}", violations.join("\n"));
+    Ok(())
+}
+
+#[test]
+fn detecting_policy_violations() {
+    let locked = parse_schema(r#"
+        message Foo {
+          required int64 id = 1;
+          optional string name_option = 2;
+          // Removed: old_field = 3
+          oneof typed {
+            Bar bar = 4;
+          }
+        }
+        message Bar {}
+        enum Baz {
+          BAZ_ONE = 0;
+        }
+    "#);
+    let locked = locked.into_iter().chain([entry("Foo.old_field", Some(3), REMOVED)]).collect_vec();
+
+    assert_eq!(policy_violations(&locked, &parse_schema(r#"
+        message Foo {
+          required int64 id = 1;
+          optional string name_option = 2;
+          oneof typed {
+            Bar bar = 4;
+            Bar bar2 = 5;
+          }
+          repeated int32 values = 6;
+        }
+        message Bar {
+          optional bool flag_option = 1;
+        }
+        message Qux {
+          required bool flag = 1;
+        }
+        enum Baz {
+          BAZ_ONE = 0;
+          BAZ_TWO = 1;
+        }
+    "#)), Vec::<String>::new());
+
+    assert_eq!(policy_violations(&locked, &parse_schema(r#"
+        message Foo {
+          required int32 id = 1;
+          optional string old_field = 7;
+          oneof typed {
+            Bar bar = 3;
+          }
+        }
+        message Bar {
+          required bool flag = 1;
+        }
+        enum Baz {
+          BAZ_ONE = 1;
+        }
+    "#)), vec![
+        "Foo.id was changed from `Foo.id 1 required int64` to `Foo.id 1 required int32`",
+        "Foo.name_option was removed, it should be marked as removed instead",
+        "Foo.bar was changed from `Foo.bar 4 oneof:typed Bar` to `Foo.bar 3 oneof:typed Bar`",
+        "Baz.BAZ_ONE was changed from `Baz.BAZ_ONE 0 value` to `Baz.BAZ_ONE 1 value`",
+        "Foo.old_field reuses removed field `Foo.old_field 3 removed`",
+        "Foo.bar reuses removed field `Foo.old_field 3 removed`",
+        "Bar.flag is a new required field of an existing message",
+    ]);
+}
+
+#[test]
+fn decoding_golden_entities() -> EmptyRes {
+    let uuid = PbUuid { value: "00000000-0000-0000-0000-000000000000".to_owned() };
+    assert_golden("user.bin", User {
+        ds_uuid: uuid.clone(),
+        id: 777,
+        first_name_option: Some("Alice".to_owned()),
+        last_name_option: None,
+        username_option: Some("alice".to_owned()),
+        phone_number_option: None,
+        profile_pictures: vec![ProfilePicture {
+            path: "pics/alice.jpg".to_owned(),
+            frame_option: Some(PictureFrame { x: 10, y: 20, w: 100, h: 200 }),
+        }],
+    })?;
+
+    assert_golden("chat.bin", Chat {
+        ds_uuid: uuid.clone(),
+        id: 123,
+        name_option: Some("Alice & Bob".to_owned()),
+        source_type: SourceType::Telegram as i32,
+        tpe: ChatType::Personal as i32,
+        img_path_option: Some("chat.jpg".to_owned()),
+        member_ids: vec![777, 778],
+        msg_count: 2,
+        main_chat_id: None,
+        archived: None,
+        hidden: None,
+    })?;
+
+    assert_golden("message_regular.bin", Message {
+        internal_id: 5,
+        source_id_option: Some(15),
+        timestamp: 1546427721,
+        from_id: 777,
+        text: vec![
+            RichTextElement {
+                val: Some(rich_text_element::Val::Plain(RtePlain { text: "Hello ".to_owned() })),
+                searchable_string: "Hello".to_owned(),
+            },
+            RichTextElement {
+                val: Some(rich_text_element::Val::Link(RteLink {
+                    text_option: Some("there".to_owned()),
+                    href: "https://example.org".to_owned(),
+                    hidden: false,
+                })),
+                searchable_string: "there https://example.org".to_owned(),
+            },
+        ],
+        searchable_string: "Hello there https://example.org".to_owned(),
+        topic_option: None,
+        text_history: vec![],
+        typed: Some(message_regular! {
+            edit_timestamp_option: Some(1546427800),
+            is_deleted: false,
+            forward_from_name_option: None,
+            forward_from_id_option: None,
+            ephemeral_duration_sec_option: None,
+            reply_to_message_id_option: Some(14),
+            contents: vec![content!(Photo {
+                path_option: Some("photos/1.jpg".to_owned()),
+                width: 640,
+                height: 480,
+                mime_type_option: Some("image/jpeg".to_owned()),
+                is_one_time: false,
+                lat_str_option: None,
+                lon_str_option: None,
+                ocr_text_option: None,
+            })],
+        }),
+    })?;
+
+    assert_golden("message_service.bin", Message {
+        internal_id: 6,
+        source_id_option: Some(16),
+        timestamp: 1546427821,
+        from_id: 778,
+        text: vec![],
+        searchable_string: "".to_owned(),
+        topic_option: None,
+        text_history: vec![],
+        typed: Some(message_service!(message_service::SealedValueOptional::PinMessage(
+            MessageServicePinMessage { message_source_id: 15 }
+        ))),
+    })?;
+    Ok(())
+}
+
+/// Refreshes schema lock file, to be run on release.
+#[test]
+#[ignore]
+fn update_schema_lock() -> EmptyRes {
+    let locked = load_schema_lock()?;
+    let current = parse_current_schema()?;
+    let violations = policy_violations(&locked, &current);
+    assert!(violations.is_empty(), "Protobuf schema evolution policy is violated:\n{}", violations.join("\n"));
+
+    let header = fs::read_to_string(crate_path(SCHEMA_LOCK_FILE))?.lines()
+        .take_while(|l| l.starts_with('#'))
+        .map(|l| format!("{l}\n"))
+        .join("");
+    let entries = current.into_iter()
+        .chain(locked.into_iter().filter(|e| e.kind == REMOVED))
+        .sorted_by(|a, b| a.path.cmp(&b.path))
+        .map(|e| format!("{e}\n"))
+        .join("");
+    fs::write(crate_path(SCHEMA_LOCK_FILE), format!("{header}{entries}"))?;
+    Ok(())
+}
+
+//
+// Helpers
+//
+
+/// Message/enum declaration, message field or enum value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SchemaEntry {
+    /// `<Type>` or `<Type>.<name>`
+    path: String,
+    /// Absent for type declarations
+    number: Option<i32>,
+    /// Field label and type, `value` for enum values, `message`/`enum` for type declarations, or [REMOVED].
+    kind: String,
+}
+
+impl SchemaEntry {
+    fn parent(&self) -> Option<&str> {
+        self.number.and(self.path.rsplit_once('.').map(|(parent, _)| parent))
+    }
+}
+
+impl Display for SchemaEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.number {
+            Some(number) => write!(f, "{} {} {}", self.path, number, self.kind),
+            None => write!(f, "{} {}", self.path, self.kind),
+        }
+    }
+}
+
+fn entry(path: &str, number: Option<i32>, kind: &str) -> SchemaEntry {
+    SchemaEntry { path: path.to_owned(), number, kind: kind.to_owned() }
+}
+
+fn policy_violations(locked: &[SchemaEntry], current: &[SchemaEntry]) -> Vec<String> {
+    let current_by_path: HashMap<&str, &SchemaEntry> = current.iter().map(|e| (e.path.as_str(), e)).collect();
+    let locked_paths: HashSet<&str> = locked.iter().map(|e| e.path.as_str()).collect();
+    let mut res = vec![];
+    for locked_entry in locked {
+        if locked_entry.kind == REMOVED {
+            let reused = current.iter().filter(|e|
+                e.path == locked_entry.path ||
+                    (e.number.is_some() && e.number == locked_entry.number && e.parent() == locked_entry.parent()));
+            for e in reused {
+                res.push(format!("{} reuses removed field `{locked_entry}`", e.path));
+            }
+            continue;
+        }
+        match current_by_path.get(locked_entry.path.as_str()) {
+            None =>
+                res.push(format!("{} was removed, it should be marked as removed instead", locked_entry.path)),
+            Some(e) if *e != locked_entry =>
+                res.push(format!("{} was changed from `{locked_entry}` to `{e}`", locked_entry.path)),
+            Some(_) => { /* Unchanged */ }
+        }
+    }
+    for e in current {
+        if locked_paths.contains(e.path.as_str()) { continue; }
+        if e.kind.starts_with("required ") && e.parent().is_some_and(|parent| locked_paths.contains(parent)) {
+            res.push(format!("{} is a new required field of an existing message", e.path));
+        }
+    }
+    res
+}
+
+fn crate_path(relative_path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative_path)
+}
+
+fn load_schema_lock() -> Result<Vec<SchemaEntry>> {
+    fs::read_to_string(crate_path(SCHEMA_LOCK_FILE))?.lines()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let parts = l.split_whitespace().collect_vec();
+            ensure!(parts.len() >= 2, "Malformed schema lock line: {l}");
+            Ok(match parts[1].parse::<i32>() {
+                Ok(number) => entry(parts[0], Some(number), &parts[2..].join(" ")),
+                Err(_) => entry(parts[0], None, &parts[1..].join(" ")),
+            })
+        })
+        .try_collect()
+}
+
+fn parse_current_schema() -> Result<Vec<SchemaEntry>> {
+    let mut res = vec![];
+    for file in PROTO_FILES {
+        res.extend(parse_schema(&fs::read_to_string(crate_path(file))?));
+    }
+    Ok(res)
+}
+
+/// Simplistic parser of proto2 files, only supporting constructs used in this project.
+fn parse_schema(proto: &str) -> Vec<SchemaEntry> {
+    let proto = Regex::new(r"(?s)//[^\n]*|/\*.*?\*/").unwrap().replace_all(proto, "");
+    let tokens = Regex::new(r#""[^"]*"|[\w.]+|\S"#).unwrap().find_iter(&proto).map(|m| m.as_str()).collect_vec();
+    let mut res = vec![];
+    let mut idx = 0;
+    parse_block(&tokens, &mut idx, None, None, &mut res);
+    res
+}
+
+/// Parses file, message or oneof body, stopping after closing brace.
+fn parse_block(tokens: &[&str], idx: &mut usize, scope: Option<&str>, oneof: Option<&str>, res: &mut Vec<SchemaEntry>) {
+    while *idx < tokens.len() {
+        match tokens[*idx] {
+            "}" => {
+                *idx += 1;
+                return;
+            }
+            kind @ ("message" | "enum") => {
+                let name = tokens[*idx + 1];
+                let path = scope.map(|s| format!("{s}.{name}")).unwrap_or(name.to_owned());
+                res.push(entry(&path, None, kind));
+                *idx += 3;
+                if kind == "message" {
+                    parse_block(tokens, idx, Some(&path), None, res);
+                } else {
+                    parse_enum(tokens, idx, &path, res);
+                }
+            }
+            "oneof" => {
+                let name = tokens[*idx + 1];
+                *idx += 3;
+                parse_block(tokens, idx, scope, Some(name), res);
+            }
+            "service" => {
+                *idx += 2;
+                skip_block(tokens, idx);
+            }
+            "option" | "syntax" | "package" | "import" | "reserved" | "extensions" => {
+                skip_statement(tokens, idx);
+            }
+            label => {
+                let label = match oneof {
+                    Some(oneof) => format!("oneof:{oneof}"),
+                    None => {
+                        *idx += 1;
+                        label.to_owned()
+                    }
+                };
+                let (tpe, name, number) = (tokens[*idx], tokens[*idx + 1], tokens[*idx + 3]);
+                assert_eq!(tokens[*idx + 2], "=", "Unexpected token near {name}");
+                let path = format!("{}.{name}", scope.expect("Field outside of a message"));
+                res.push(entry(&path, Some(number.parse().unwrap()), &format!("{label} {tpe}")));
+                skip_statement(tokens, idx);
+            }
+        }
+    }
+}
+
+fn parse_enum(tokens: &[&str], idx: &mut usize, path: &str, res: &mut Vec<SchemaEntry>) {
+    loop {
+        match tokens[*idx] {
+            "}" => {
+                *idx += 1;
+                return;
+            }
+            "option" | "reserved" => skip_statement(tokens, idx),
+            name => {
+                assert_eq!(tokens[*idx + 1], "=", "Unexpected token near {name}");
+                res.push(entry(&format!("{path}.{name}"), Some(tokens[*idx + 2].parse().unwrap()), "value"));
+                skip_statement(tokens, idx);
+            }
+        }
+    }
+}
+
+/// Skips until semicolon outside of braces.
+fn skip_statement(tokens: &[&str], idx: &mut usize) {
+    let mut depth = 0;
+    loop {
+        let token = tokens[*idx];
+        *idx += 1;
+        match token {
+            "{" => depth += 1,
+            "}" => depth -= 1,
+            ";" if depth == 0 => return,
+            _ => { /* NOOP */ }
+        }
+    }
+}
+
+/// Skips a block in braces, including nested ones.
+fn skip_block(tokens: &[&str], idx: &mut usize) {
+    let mut depth = 0;
+    loop {
+        let token = tokens[*idx];
+        *idx += 1;
+        match token {
+            "{" => depth += 1,
+            "}" => {
+                depth -= 1;
+                if depth == 0 { return; }
+            }
+            _ => { /* NOOP */ }
+        }
+    }
+}
+
+/// Golden entity should be decoded as expected, and encoded back into the very same bytes.
+fn assert_golden<T: ProstMessage + Default + PartialEq + std::fmt::Debug>(file_name: &str, expected: T) -> EmptyRes {
+    let bytes = fs::read(resource(GOLDEN_DIR).join(file_name))?;
+    let decoded = T::decode(bytes.as_slice())?;
+    assert_eq!(decoded, expected, "{file_name} decoded incorrectly");
+    assert_eq!(decoded.encode_to_vec(), bytes, "{file_name} encoded differently");
+    Ok(())
+}
@@ -10,7 +10,7 @@ use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 pub use anyhow::{anyhow, bail, Context, ensure};
-use chrono::Local;
+use chrono::{DateTime, Local};
 use hashers::fx_hash::FxHasher;
 use itertools::Itertools;
 use lazy_static::lazy_static;
@@ -18,6 +18,8 @@ use unicode_segmentation::UnicodeSegmentation;
 
 pub mod blob_utils;
 pub mod entity_utils;
+pub mod file_clone;
+pub mod http_client;
 pub mod json_utils;
 
 #[cfg(test)]
@@ -183,6 +185,13 @@ pub fn error_message(e: &anyhow::Error) -> String {
     format!("{:#}", e)
 }
 
+/// Extracts a message from a payload of a caught panic.
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "no details".to_owned())
+}
+
 pub trait ToResult<T> {
     fn normalize_error(self) -> Result<T>;
 }
@@ -274,3 +283,14 @@ pub fn without_indices<T>(vec: Vec<T>, indices_to_remove: impl IntoIterator<Item
         .map(|(_, m)| m)
         .collect_vec()
 }
+
+/// Date of the given epoch seconds timestamp in local timezone, formatted as `YYYY-MM-DD`.
+pub fn local_date_string(timestamp: i64) -> Result<String> {
+    local_timestamp_string(timestamp, "%Y-%m-%d")
+}
+
+/// Given epoch seconds timestamp in local timezone, formatted using `strftime`-like format.
+pub fn local_timestamp_string(timestamp: i64, format: &str) -> Result<String> {
+    let dt = DateTime::from_timestamp(timestamp, 0).with_context(|| format!("Invalid timestamp {timestamp}"))?;
+    Ok(dt.with_timezone(&*LOCAL_TZ).format(format).to_string())
+}
@@ -1,9 +1,15 @@
 use std::fmt::{Display, Formatter};
 
+use itertools::Itertools;
+
 use crate::prelude::*;
 
 pub mod entity_equality;
 
+#[cfg(test)]
+#[path = "entity_utils_tests.rs"]
+mod tests;
+
 //
 // Helper entities
 //
@@ -29,11 +35,87 @@ impl From<ChatWithDetailsPb> for ChatWithDetails {
 }
 
 impl From<ChatWithDetails> for ChatWithDetailsPb {
+    /// Former names are not populated.
     fn from(value: ChatWithDetails) -> Self {
         Self {
             chat: value.chat,
             last_msg_option: value.last_msg_option,
             members: value.members,
+            former_names: vec![],
+        }
+    }
+}
+
+impl MessageFilter {
+    pub fn matches(&self, msg: &Message) -> Result<bool> {
+        let content_type_option = self.content_type.map(ContentType::resolve).transpose()?;
+        if self.from_id.is_some_and(|from_id| msg.from_id != from_id) {
+            return Ok(false);
+        }
+        if self.topic_id.is_some_and(|topic_id| msg.topic_option.as_ref().is_none_or(|t| t.id != topic_id)) {
+            return Ok(false);
+        }
+        if self.mentioned_user_id.is_some_and(|user_id| !mentioned_user_ids(&msg.text).contains(&user_id)) {
+            return Ok(false);
         }
+        Ok(match msg.typed() {
+            message::Typed::Regular(mr) => {
+                self.service != Some(true)
+                    && self.deleted.is_none_or(|deleted| mr.is_deleted == deleted)
+                    && content_type_option.is_none_or(|ct| mr.contents.iter().any(|c| c.content_type() == ct))
+            }
+            message_service_pat!(_) => {
+                self.service != Some(false)
+                    && self.deleted != Some(true)
+                    && content_type_option.is_none()
+            }
+            message_service_pat_unreachable!() => unreachable!()
+        })
     }
 }
+
+/// Mentioned user, found either by ID or by username (case-insensitive).
+pub fn resolve_mention<'a>(mention: &RteMention, users: &'a [User]) -> Option<&'a User> {
+    match (mention.user_id_option, mention.handle_option.as_deref()) {
+        (Some(user_id), _) => users.iter().find(|u| u.id == user_id),
+        (None, Some(handle)) => users.iter().find(|u| {
+            u.username_option.as_deref().is_some_and(|un| un.trim_start_matches('@').eq_ignore_ascii_case(handle))
+        }),
+        (None, None) => None,
+    }
+}
+
+/// Fills in IDs of users mentioned by username only, where such users are known.
+pub fn resolve_mentions(rtes: &mut [RichTextElement], users: &[User]) {
+    for rte in rtes.iter_mut() {
+        if let Some(rich_text_element::Val::Mention(ref mut mention)) = rte.val {
+            if mention.user_id_option.is_none() {
+                mention.user_id_option = resolve_mention(mention, users).map(|u| u.id);
+            }
+        }
+    }
+}
+
+/// IDs of users mentioned in a text, as far as they're known.
+pub fn mentioned_user_ids(rtes: &[RichTextElement]) -> Vec<i64> {
+    rtes.iter().filter_map(|rte| match rte.val {
+        Some(rich_text_element::Val::Mention(ref mention)) => mention.user_id_option,
+        _ => None,
+    }).unique().collect_vec()
+}
+
+/// Kind, relative path and original file name of a content that has a file.
+pub fn content_media_file(content: &Content) -> Option<(MediaKind, &String, Option<&String>)> {
+    use content::SealedValueOptional::*;
+    let (kind, path_option, file_name_option) = match content.sealed_value_optional.as_ref()? {
+        Photo(c) => (MediaKind::Photo, c.path_option.as_ref(), None),
+        Video(c) => (MediaKind::Video, c.path_option.as_ref(), c.file_name_option.as_ref()),
+        VideoMsg(c) => (MediaKind::VideoMsg, c.path_option.as_ref(), c.file_name_option.as_ref()),
+        VoiceMsg(c) => (MediaKind::VoiceMsg, c.path_option.as_ref(), c.file_name_option.as_ref()),
+        Audio(c) => (MediaKind::Audio, c.path_option.as_ref(), c.file_name_option.as_ref()),
+        Sticker(c) => (MediaKind::Sticker, c.path_option.as_ref(), c.file_name_option.as_ref()),
+        File(c) => (MediaKind::File, c.path_option.as_ref(), c.file_name_option.as_ref()),
+        Location(_) | Poll(_) | SharedContact(_) => return None,
+    };
+    Some((kind, path_option?, file_name_option))
+}
@@ -119,7 +119,8 @@ impl PracticalEq for Tup<'_, User> {
 
 impl PracticalEq for Tup<'_, Chat> {
     fn practically_equals(&self, other: &Self) -> Result<bool> {
-        Ok(cloned_equals_without!(self.v, other.v, Chat, img_path_option: None, member_ids: vec![]) &&
+        Ok(cloned_equals_without!(self.v, other.v, Chat,
+                                  img_path_option: None, member_ids: vec![], archived: None, hidden: None) &&
             self.v.member_ids.len() == other.v.member_ids.len() &&
             self.v.member_ids.iter().all(|e| other.v.member_ids.contains(e)) &&
             self.apply(|v| &v.img_path_option).practically_equals(&other.apply(|v| &v.img_path_option))?)
@@ -136,11 +137,34 @@ impl PracticalEq for Tup<'_, Message> {
                                   internal_id: 0,
                                   source_id_option: None,
                                   searchable_string: "".to_owned(),
+                                  text: vec![],
+                                  text_history: vec![],
                                   typed: None) &&
+            self.apply(|v| &v.text).practically_equals(&other.apply(|v| &v.text))? &&
+            self.apply(|v| &v.text_history).practically_equals(&other.apply(|v| &v.text_history))? &&
             self.apply(|v| v.typed()).practically_equals(&other.apply(|v| v.typed()))?)
     }
 }
 
+impl PracticalEq for Tup<'_, MessageTextVersion> {
+    fn practically_equals(&self, other: &Self) -> Result<bool> {
+        Ok(self.v.timestamp == other.v.timestamp &&
+            self.apply(|v| &v.text).practically_equals(&other.apply(|v| &v.text))?)
+    }
+}
+
+impl PracticalEq for Tup<'_, RichTextElement> {
+    fn practically_equals(&self, other: &Self) -> Result<bool> {
+        use rich_text_element::Val;
+        match (self.v.val.as_ref(), other.v.val.as_ref()) {
+            (Some(Val::CustomEmoji(v1)), Some(Val::CustomEmoji(v2))) =>
+                Ok(self.v.searchable_string == other.v.searchable_string &&
+                    self.with(v1).practically_equals(&other.with(v2))?),
+            _ => Ok(self.v == other.v)
+        }
+    }
+}
+
 impl PracticalEq for Tup<'_, message::Typed> {
     fn practically_equals(&self, other: &Self) -> Result<bool> {
         use message::Typed::*;
@@ -154,11 +178,27 @@ impl PracticalEq for Tup<'_, message::Typed> {
 
 impl PracticalEq for Tup<'_, MessageRegular> {
     fn practically_equals(&self, other: &Self) -> Result<bool> {
-        Ok(cloned_equals_without!(self.v, other.v, MessageRegular, forward_from_name_option: None, contents: vec![]) &&
+        Ok(cloned_equals_without!(self.v, other.v, MessageRegular,
+                                  forward_from_name_option: None, forward_from_id_option: None,
+                                  ephemeral_duration_sec_option: None, contents: vec![]) &&
+            forward_origins_match(self.v.forward_origin_option(), other.v.forward_origin_option()) &&
+            // Not every source marks disappearing messages
+            self.v.ephemeral_duration_sec_option.zip(other.v.ephemeral_duration_sec_option).is_none_or(|(d1, d2)| d1 == d2) &&
             self.apply(|v| &v.contents).practically_equals(&other.apply(|v| &v.contents))?)
     }
 }
 
+/// Forward author names are unreliable (they change over time, some sources don't preserve them at all),
+/// so only author IDs are compared, and only if they're known on both sides.
+fn forward_origins_match(o1: Option<ForwardOrigin>, o2: Option<ForwardOrigin>) -> bool {
+    match (o1, o2) {
+        (None, None) => true,
+        (Some(ForwardOrigin::User(id1)), Some(ForwardOrigin::User(id2))) => id1 == id2,
+        (Some(_), Some(_)) => true,
+        _ => false,
+    }
+}
+
 impl PracticalEq for Tup<'_, MessageService> {
     fn practically_equals(&self, other: &Self) -> Result<bool> {
         use message_service::SealedValueOptional::*;
@@ -252,10 +292,11 @@ macro_rules! practical_eq_with_path {
     };
 }
 
-practical_eq_with_path!(ContentSticker, [path_option, thumbnail_path_option], [file_name_option]);
-practical_eq_with_path!(ContentPhoto, [path_option], []);
-practical_eq_with_path!(ContentVoiceMsg, [path_option], [file_name_option]);
-practical_eq_with_path!(ContentAudio, [path_option], [file_name_option]);
+practical_eq_with_path!(RteCustomEmoji, [path_option], []);
+practical_eq_with_path!(ContentSticker, [path_option, thumbnail_path_option], [file_name_option, pack_id_option, pack_name_option]);
+practical_eq_with_path!(ContentPhoto, [path_option], [lat_str_option, lon_str_option, ocr_text_option]);
+practical_eq_with_path!(ContentVoiceMsg, [path_option], [file_name_option, waveform_option]);
+practical_eq_with_path!(ContentAudio, [path_option], [file_name_option, waveform_option]);
 practical_eq_with_path!(ContentVideoMsg, [path_option, thumbnail_path_option], [file_name_option]);
 practical_eq_with_path!(ContentVideo, [path_option, thumbnail_path_option], [file_name_option]);
 practical_eq_with_path!(ContentFile, [path_option, thumbnail_path_option], [file_name_option]);
@@ -263,8 +304,10 @@ practical_eq_with_path!(ContentSharedContact, [vcard_path_option], []);
 
 impl PracticalEq for Tup<'_, ContentPoll> {
     fn practically_equals(&self, other: &Self) -> Result<bool> {
-        // We don't really care about poll result
-        Ok(self.v == other.v)
+        // We don't really care about poll result, only about the poll itself
+        let options = |v: &ContentPoll| v.options.iter().map(|o| o.text.clone()).collect_vec();
+        Ok(self.v.question == other.v.question && self.v.is_quiz() == other.v.is_quiz() &&
+            options(self.v) == options(other.v))
     }
 }
 
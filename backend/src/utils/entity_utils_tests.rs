@@ -0,0 +1,257 @@
+#![allow(unused_imports)]
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+#[test]
+fn dataset_root_exotic_file_names() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let ds_root = DatasetRoot(tmp_dir.path.clone());
+
+    let check = |rel_path: &str| -> EmptyRes {
+        let path = ds_root.to_absolute(rel_path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        create_named_file(&path, rel_path.as_bytes());
+        assert_eq!(ds_root.to_relative(&path)?, rel_path);
+        assert_eq!(fs::read(ds_root.to_absolute(rel_path))?, rel_path.as_bytes());
+        Ok(())
+    };
+
+    check("plain.jpg")?;
+    check("chats/Фото 😀 (1).jpg")?;
+    check("chats/テスト/IMG 2024.jpg")?;
+    // Percent sign in a valid Unicode name is taken literally
+    check("chats/50%.jpg")?;
+    check("chats/100%41.jpg")?;
+    // Total length exceeds MAX_PATH on Windows
+    check(&format!("{}/{}.jpg", "a".repeat(100), "b".repeat(200)))?;
+
+    assert!(ds_root.to_relative(&tmp_dir.path.parent().unwrap().join("outside.txt")).is_err());
+    Ok(())
+}
+
+#[test]
+fn storage_string_encoding() {
+    assert_eq!(os_str_to_storage_string(OsStr::new("Фото 😀 50%.jpg")), "Фото 😀 50%.jpg");
+    assert_eq!(storage_string_to_path("Фото 😀 50%.jpg"), PathBuf::from("Фото 😀 50%.jpg"));
+    assert_eq!(storage_string_to_path("100%41.jpg"), PathBuf::from("100%41.jpg"));
+    assert_eq!(storage_string_to_path("broken%ZZ.jpg"), PathBuf::from("broken%ZZ.jpg"));
+
+    // Literal name that looks like an encoded one
+    assert_eq!(os_str_to_storage_string(OsStr::new("r%E9sum%E9.pdf")), "r%E9sum%E9.pdf");
+    assert_eq!(storage_string_to_path("docs/r%E9sum%E9.pdf"), PathBuf::from("docs/r%E9sum%E9.pdf"));
+
+    // Literal name that starts with the prefix
+    let encoded = os_str_to_storage_string(OsStr::new("%%notes.txt"));
+    assert_eq!(encoded, "%%%25%25notes.txt");
+    assert_eq!(storage_string_to_path(&format!("docs/{encoded}")), PathBuf::from("docs/%%notes.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn storage_string_encoding_non_utf8() -> EmptyRes {
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = OsStr::from_bytes(b"caf\xE9 50%.jpg");
+    let encoded = os_str_to_storage_string(name);
+    assert_eq!(encoded, "%%caf%E9 50%25.jpg");
+    assert_eq!(storage_string_to_path(&encoded).as_os_str(), name);
+
+    // Filesystems on macOS don't allow such names
+    let tmp_dir = TmpDir::new();
+    let ds_root = DatasetRoot(tmp_dir.path.clone());
+    let path = tmp_dir.path.join(name);
+    if fs::write(&path, b"content").is_ok() {
+        assert_eq!(ds_root.to_relative(&path)?, encoded);
+        assert_eq!(ds_root.to_absolute(&encoded), path);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn dataset_root_symlinks() -> EmptyRes {
+    use std::os::unix::fs::symlink;
+
+    let tmp_dir = TmpDir::new();
+    let ds_root_path = tmp_dir.path.join("db");
+    let other_disk_path = tmp_dir.path.join("other_disk");
+    fs::create_dir_all(ds_root_path.join("chats"))?;
+    fs::create_dir_all(&other_disk_path)?;
+    create_named_file(&other_disk_path.join("photo.jpg"), b"photo");
+
+    // Media directory lies elsewhere
+    symlink(&other_disk_path, ds_root_path.join("chats").join("media"))?;
+    let ds_root = DatasetRoot(ds_root_path.clone());
+    assert_eq!(ds_root.to_relative(&ds_root_path.join("chats/media/photo.jpg"))?, "chats/media/photo.jpg");
+    assert_eq!(ds_root.to_relative(&ds_root_path.join("chats/../chats/media/./photo.jpg"))?, "chats/media/photo.jpg");
+    assert_eq!(fs::read(ds_root.to_absolute("chats/media/photo.jpg"))?, b"photo");
+
+    // Dataset root itself is a symlink
+    let linked_root_path = tmp_dir.path.join("linked_db");
+    symlink(&ds_root_path, &linked_root_path)?;
+    let linked_ds_root = DatasetRoot(linked_root_path.clone());
+    assert_eq!(linked_ds_root.to_relative(&ds_root_path.join("chats/media/photo.jpg"))?, "chats/media/photo.jpg");
+    assert_eq!(linked_ds_root.to_relative(&linked_root_path.join("chats/media/photo.jpg"))?, "chats/media/photo.jpg");
+
+    // Escaping dataset root lexically is not allowed
+    assert!(ds_root.to_relative(&ds_root_path.join("chats/../../other_disk/photo.jpg")).is_err());
+    Ok(())
+}
+
+#[test]
+fn resolving_mentions() -> EmptyRes {
+    let ds_uuid = PbUuid::random();
+    let user = |id: i64, username_option: Option<&str>| User {
+        ds_uuid: ds_uuid.clone(),
+        id,
+        username_option: username_option.map(|s| s.to_owned()),
+        ..Default::default()
+    };
+    let users = vec![user(1, None), user(2, Some("Alice")), user(3, Some("@bob"))];
+    let mention = |handle: &str| RichText::make_mention(format!("@{handle}"), None, Some(handle.to_owned()));
+
+    let mut rtes = vec![
+        mention("alice"),
+        RichText::make_plain(" and ".to_owned()),
+        mention("bob"),
+        mention("carol"),
+        RichText::make_mention("@Someone".to_owned(), Some(1), None),
+        mention("alice"),
+    ];
+    resolve_mentions(&mut rtes, &users);
+    assert_eq!(mentioned_user_ids(&rtes), vec![2, 3, 1]);
+    let Some(rich_text_element::Val::Mention(ref carol)) = rtes[3].val else { unreachable!() };
+    assert_eq!(resolve_mention(carol, &users), None);
+
+    let msg = MessageBuilder::regular(0, UserId(1)).rich_texts(rtes).build();
+    assert!(MessageFilter { mentioned_user_id: Some(3), ..Default::default() }.matches(&msg)?);
+    assert!(!MessageFilter { mentioned_user_id: Some(4), ..Default::default() }.matches(&msg)?);
+    Ok(())
+}
+
+#[test]
+fn rich_text_markdown_parsing() {
+    let plain = |s: &str| RichText::make_plain(s.to_owned());
+    assert_eq!(RichText::parse_markdown("Hi **there**, *you* and __me__!"), vec![
+        plain("Hi "),
+        RichText::make_bold("there".to_owned()),
+        plain(", "),
+        RichText::make_italic("you".to_owned()),
+        plain(" and "),
+        RichText::make_underline("me".to_owned()),
+        plain("!"),
+    ]);
+    assert_eq!(RichText::parse_markdown(r"snake_case_name, 2 * 3 * 4 and \*escaped\*"), vec![
+        plain("snake_case_name, 2 * 3 * 4 and *escaped*"),
+    ]);
+    assert_eq!(RichText::parse_markdown("See [docs](https://example.com/a_(b)) or <https://example.com>[](https://hidden.com)"), vec![
+        plain("See "),
+        RichText::make_link(Some("docs".to_owned()), "https://example.com/a_(b)".to_owned(), false),
+        plain(" or "),
+        RichText::make_link(Some("https://example.com".to_owned()), "https://example.com".to_owned(), false),
+        RichText::make_link(None, "https://hidden.com".to_owned(), true),
+    ]);
+    assert_eq!(RichText::parse_markdown("Run `ls -la` then\n```bash\necho 1\n```\n``a`b``"), vec![
+        plain("Run "),
+        RichText::make_prefmt_inline("ls -la".to_owned()),
+        plain(" then\n"),
+        RichText::make_prefmt_block("echo 1".to_owned(), Some("bash".to_owned())),
+        plain("\n"),
+        RichText::make_prefmt_inline("a`b".to_owned()),
+    ]);
+    assert_eq!(RichText::parse_markdown("> quoted\n>more\nnot >!spoiler!<"), vec![
+        RichText::make_blockquote("quoted\nmore".to_owned()),
+        plain("\nnot "),
+        RichText::make_spoiler("spoiler".to_owned()),
+    ]);
+    assert_eq!(RichText::parse_markdown("**unclosed and `unclosed"), vec![plain("**unclosed and `unclosed")]);
+    assert_eq!(RichText::parse_markdown(""), vec![]);
+}
+
+#[test]
+fn rich_text_html_parsing() {
+    let src = concat!(
+        r#"<b>Bold <i>nested</i></b> &amp; <a href="https://example.com?a=1&amp;b=2">link</a><br>"#,
+        r#"<tg-spoiler>secret</tg-spoiler> <pre><code class="language-rust">let x = 1 &lt; 2;</code></pre>"#,
+        r#"<!-- comment --><unknown>text</unknown> 1 < 2"#,
+    );
+    assert_eq!(RichText::parse_html(src), vec![
+        RichText::make_bold("Bold nested".to_owned()),
+        RichText::make_plain(" & ".to_owned()),
+        RichText::make_link(Some("link".to_owned()), "https://example.com?a=1&b=2".to_owned(), false),
+        RichText::make_plain("\n".to_owned()),
+        RichText::make_spoiler("secret".to_owned()),
+        RichText::make_plain(" ".to_owned()),
+        RichText::make_prefmt_block("let x = 1 < 2;".to_owned(), Some("rust".to_owned())),
+        RichText::make_plain("text 1 < 2".to_owned()),
+    ]);
+    assert_eq!(RichText::to_html(&[RichText::make_link(None, "https://example.org".to_owned(), false)]),
+               r#"<a href="https://example.org">https://example.org</a>"#);
+}
+
+#[test]
+fn rich_text_round_trip() {
+    let rtes = vec![
+        RichText::make_plain("Plain *text* with <special> chars_\n".to_owned()),
+        RichText::make_bold("bold".to_owned()),
+        RichText::make_plain(" ".to_owned()),
+        RichText::make_italic("italic".to_owned()),
+        RichText::make_plain(" ".to_owned()),
+        RichText::make_underline("under".to_owned()),
+        RichText::make_strikethrough("strike".to_owned()),
+        RichText::make_spoiler("spoiler".to_owned()),
+        RichText::make_prefmt_inline("code with ` tick".to_owned()),
+        RichText::make_plain("\n".to_owned()),
+        RichText::make_prefmt_block("fn main() {}".to_owned(), Some("rust".to_owned())),
+        RichText::make_plain("\n".to_owned()),
+        RichText::make_prefmt_block("no language".to_owned(), None),
+        RichText::make_plain("\n".to_owned()),
+        RichText::make_blockquote("quote\nsecond line".to_owned()),
+        RichText::make_plain("\n".to_owned()),
+        RichText::make_link(Some("text".to_owned()), "https://example.com/a_(b)".to_owned(), false),
+        RichText::make_plain(" ".to_owned()),
+        RichText::make_link(Some("https://example.com".to_owned()), "https://example.com".to_owned(), false),
+        RichText::make_link(None, "https://hidden.com".to_owned(), true),
+    ];
+
+    let markdown = RichText::to_markdown(&rtes);
+    assert!(markdown.starts_with(r"Plain \*text\* with \<special\> chars\_"));
+    assert_eq!(RichText::parse_markdown(&markdown), rtes);
+
+    let html = RichText::to_html(&rtes);
+    assert!(html.starts_with("Plain *text* with &lt;special&gt; chars_"));
+    assert_eq!(RichText::parse_html(&html), rtes);
+}
+
+#[test]
+fn rich_text_plain() {
+    let rtes = vec![
+        RichText::make_plain("Hey ".to_owned()),
+        RichText::make_bold("you".to_owned()),
+        RichText::make_plain(", ".to_owned()),
+        RichText::make_underline("see".to_owned()),
+        RichText::make_plain(" ".to_owned()),
+        RichText::make_link(Some("this".to_owned()), "https://example.com".to_owned(), false),
+        RichText::make_plain(" and ".to_owned()),
+        RichText::make_link(None, "https://example.org".to_owned(), false),
+        RichText::make_link(None, "https://hidden.com".to_owned(), true),
+        RichText::make_plain(": ".to_owned()),
+        RichText::make_spoiler("secret".to_owned()),
+        RichText::make_prefmt_inline("code".to_owned()),
+        RichText::make_blockquote("quote\nsecond line".to_owned()),
+    ];
+
+    assert_eq!(RichText::to_plain(&rtes, PlainTextStyle::Stripped),
+               "Hey you, see this (https://example.com) and https://example.org: secretcodequote\nsecond line");
+    assert_eq!(RichText::to_plain(&rtes, PlainTextStyle::Spoilers),
+               "Hey you, see this (https://example.com) and https://example.org: ‖secret‖codequote\nsecond line");
+    assert_eq!(RichText::to_plain(&rtes, PlainTextStyle::Markdownish),
+               "Hey *you*, see this (https://example.com) and https://example.org: ‖secret‖`code`\n> quote\n> second line");
+    assert_eq!(RichText::to_plain(&[], PlainTextStyle::Markdownish), "");
+}
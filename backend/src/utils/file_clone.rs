@@ -0,0 +1,75 @@
+use std::fs;
+use std::io;
+
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "file_clone_tests.rs"]
+mod tests;
+
+/// How a file ended up being duplicated by [clone_file].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloneKind {
+    /// Copy-on-write clone sharing data blocks with the source (btrfs, XFS, APFS, etc.)
+    Reflink,
+    /// Another name for the same file
+    HardLink,
+    /// Plain byte copy
+    Copy,
+}
+
+/// Duplicate `src` into a (not yet existing) `dst`, sharing the data with it if possible - first by trying a reflink,
+/// then, if `allow_hard_link` is set, a hard link, falling back to a plain copy.
+///
+/// Hard linked files share their content, so they should only be allowed for files that are never modified in place.
+pub fn clone_file(src: &Path, dst: &Path, allow_hard_link: bool) -> Result<CloneKind> {
+    match reflink(src, dst) {
+        Ok(()) => return Ok(CloneKind::Reflink),
+        Err(e) => log::trace!("Can't reflink {} to {}: {e}", src.display(), dst.display()),
+    }
+    if allow_hard_link {
+        match fs::hard_link(src, dst) {
+            Ok(()) => return Ok(CloneKind::HardLink),
+            Err(e) => log::trace!("Can't hard link {} to {}: {e}", src.display(), dst.display()),
+        }
+    }
+    fs::copy(src, dst).with_context(|| format!("Couldn't copy {} to {}", src.display(), dst.display()))?;
+    Ok(CloneKind::Copy)
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    /// `_IOW(0x94, 9, int)` from linux/fs.h
+    const FICLONE: libc::c_ulong = 0x40049409;
+
+    let src_file = fs::File::open(src)?;
+    let dst_file = fs::File::options().write(true).create_new(true).open(dst)?;
+    // SAFETY: Both descriptors are valid open files for the duration of the call
+    let res = unsafe { libc::ioctl(dst_file.as_raw_fd(), FICLONE as _, src_file.as_raw_fd()) };
+    if res == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    drop(dst_file);
+    fs::remove_file(dst)?;
+    Err(err)
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let src = CString::new(src.as_os_str().as_bytes())?;
+    let dst = CString::new(dst.as_os_str().as_bytes())?;
+    // SAFETY: Both paths are valid NUL-terminated strings for the duration of the call
+    let res = unsafe { libc::clonefile(src.as_ptr(), dst.as_ptr(), 0) };
+    if res == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
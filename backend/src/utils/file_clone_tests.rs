@@ -0,0 +1,25 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use crate::utils::test_utils::*;
+
+use super::*;
+
+#[test]
+fn cloning_files() -> EmptyRes {
+    let tmp_dir = TmpDir::new();
+    let src = create_random_file(&tmp_dir.path);
+
+    // Whatever filesystem we're on, data should be shared
+    let dst = tmp_dir.path.join("linked.bin");
+    let kind = clone_file(&src, &dst, true)?;
+    assert_ne!(kind, CloneKind::Copy);
+    assert!(files_are_equal(&src, &dst)?);
+
+    let dst = tmp_dir.path.join("not_linked.bin");
+    let kind = clone_file(&src, &dst, false)?;
+    assert_ne!(kind, CloneKind::HardLink);
+    assert!(files_are_equal(&src, &dst)?);
+    Ok(())
+}
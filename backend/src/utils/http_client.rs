@@ -0,0 +1,230 @@
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use indexmap::IndexMap;
+use reqwest::header::{CONTENT_RANGE, ETAG, IF_NONE_MATCH, RANGE};
+use reqwest::StatusCode;
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+
+use crate::prelude::*;
+
+#[cfg(test)]
+#[path = "http_client_tests.rs"]
+mod tests;
+
+/// Responses are cached in memory, up to this total body size.
+const MAX_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+static SHARED_HTTP_CLIENT: OnceLock<SharedHttpClient> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    /// At most this many requests are in flight at once, across all users of a client.
+    pub max_concurrent_requests: usize,
+    /// Proxy for all requests, e.g. `http://127.0.0.1:3128` or `socks5://127.0.0.1:9050`.
+    pub proxy_option: Option<String>,
+    /// Any request fails right away without touching the network, see [HttpClient::is_offline].
+    pub offline: bool,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        HttpClientConfig { max_concurrent_requests: 4, proxy_option: None, offline: false }
+    }
+}
+
+/// Client backed by [reqwest], shared by loaders and downloaders (see [shared_http_client]).
+///
+/// Responses having an `ETag` are cached, and are only re-downloaded if a server says they were modified.
+pub struct SharedHttpClient {
+    config: HttpClientConfig,
+    client: reqwest::Client,
+    permits: Semaphore,
+    cache: Mutex<ResponseCache>,
+}
+
+impl SharedHttpClient {
+    pub fn new(config: HttpClientConfig) -> Result<Self> {
+        ensure!(config.max_concurrent_requests > 0, "At least one concurrent request should be allowed");
+        let mut builder = reqwest::Client::builder();
+        if let Some(ref proxy) = config.proxy_option {
+            builder = builder.proxy(reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy {proxy}"))?);
+        }
+        Ok(SharedHttpClient {
+            client: builder.build()?,
+            permits: Semaphore::new(config.max_concurrent_requests),
+            cache: Mutex::new(ResponseCache::default()),
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &HttpClientConfig {
+        &self.config
+    }
+
+    /// POST request with the given body, not cached.
+    pub async fn post_bytes(&self, url: &str, body: Vec<u8>) -> Result<HttpResponse> {
+        ensure!(!self.config.offline, "Offline mode is on, not requesting {url}");
+        let _permit = self.permits.acquire().await?;
+        let res = self.client.post(url).body(body).send().await?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.bytes().await?.to_vec();
+        if status.is_success() {
+            Ok(HttpResponse::Ok(body))
+        } else {
+            Ok(HttpResponse::Failure { status, headers, body })
+        }
+    }
+
+    fn cache(&self) -> Result<MutexGuard<'_, ResponseCache>> {
+        self.cache.lock().map_err(|_| anyhow!("HTTP response cache lock is poisoned"))
+    }
+}
+
+impl HttpClient for SharedHttpClient {
+    async fn get_bytes(&self, url: &str) -> Result<HttpResponse> {
+        ensure!(!self.config.offline, "Offline mode is on, not requesting {url}");
+        let _permit = self.permits.acquire().await?;
+
+        let cached_etag_option = self.cache()?.get(url).map(|cached| cached.etag.clone());
+        let mut req = self.client.get(url);
+        if let Some(ref etag) = cached_etag_option {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        let res = req.send().await?;
+        let status = res.status();
+        if status == StatusCode::NOT_MODIFIED {
+            let cached_body_option = self.cache()?.get(url).map(|cached| cached.body.clone());
+            if let Some(body) = cached_body_option {
+                log::debug!("{url} is not modified, using cached response");
+                return Ok(HttpResponse::Ok(body));
+            }
+        }
+
+        let headers = res.headers().clone();
+        let body = res.bytes().await?.to_vec();
+        if status.is_success() {
+            if let Some(etag) = headers.get(ETAG).and_then(|v| v.to_str().ok()) {
+                self.cache()?.insert(url.to_owned(), etag.to_owned(), body.clone());
+            }
+            Ok(HttpResponse::Ok(body))
+        } else {
+            Ok(HttpResponse::Failure { status, headers, body })
+        }
+    }
+
+    async fn get_range(&self, url: &str, offset: u64, length: u64) -> Result<(Vec<u8>, u64)> {
+        ensure!(!self.config.offline, "Offline mode is on, not requesting {url}");
+        ensure!(length > 0, "Empty range requested from {url}");
+        let _permit = self.permits.acquire().await?;
+
+        let range = format!("bytes={}-{}", offset, offset.saturating_add(length - 1));
+        let res = self.client.get(url).header(RANGE, range).send().await?;
+        let status = res.status();
+        let total_size_option = res.headers().get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(content_range_total_size);
+        match status {
+            StatusCode::PARTIAL_CONTENT => {
+                let total_size = total_size_option.with_context(|| format!("No total size in Content-Range of {url}"))?;
+                Ok((res.bytes().await?.to_vec(), total_size))
+            }
+            // Range starts past the end of a resource
+            StatusCode::RANGE_NOT_SATISFIABLE if total_size_option.is_some() => {
+                Ok((vec![], total_size_option.unwrap()))
+            }
+            // Server doesn't support ranges and returned the whole resource
+            status if status.is_success() => Ok(slice_range(res.bytes().await?.to_vec(), offset, length)),
+            status => err!("Request to {url} failed: {status}"),
+        }
+    }
+
+    fn is_offline(&self) -> bool {
+        self.config.offline
+    }
+}
+
+/// Range of a whole resource body, along with its total size.
+pub(crate) fn slice_range(body: Vec<u8>, offset: u64, length: u64) -> (Vec<u8>, u64) {
+    let total_size = body.len() as u64;
+    let start = offset.min(total_size) as usize;
+    let end = offset.saturating_add(length).min(total_size) as usize;
+    (body[start..end].to_vec(), total_size)
+}
+
+/// Total size from a `Content-Range` header value like `bytes 0-99/1234` or `bytes */1234`,
+/// absent if it's unknown.
+fn content_range_total_size(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// Client shared by everything within a process, configured by [configure_shared_http_client]
+/// or with default settings on first use.
+pub fn shared_http_client() -> &'static SharedHttpClient {
+    SHARED_HTTP_CLIENT.get_or_init(|| {
+        SharedHttpClient::new(HttpClientConfig::default()).expect("Failed to create a default HTTP client")
+    })
+}
+
+/// Has to be called before a shared client is first used.
+pub fn configure_shared_http_client(config: HttpClientConfig) -> EmptyRes {
+    SHARED_HTTP_CLIENT.set(SharedHttpClient::new(config)?)
+        .map_err(|_| anyhow!("Shared HTTP client is already in use, it can no longer be configured"))
+}
+
+/// Blocking request for use within synchronous code like loaders.
+/// Has to be called from outside of async context, e.g. within a blocking task.
+pub fn get_bytes_blocking(http_client: &impl HttpClient, url: &str) -> Result<HttpResponse> {
+    block_on(http_client.get_bytes(url))
+}
+
+/// Same as [get_bytes_blocking], but for [HttpClient::get_range].
+pub fn get_range_blocking(http_client: &impl HttpClient, url: &str, offset: u64, length: u64) -> Result<(Vec<u8>, u64)> {
+    block_on(http_client.get_range(url, offset, length))
+}
+
+/// Same as [get_bytes_blocking], but for [SharedHttpClient::post_bytes].
+pub fn post_bytes_blocking(http_client: &SharedHttpClient, url: &str, body: Vec<u8>) -> Result<HttpResponse> {
+    block_on(http_client.post_bytes(url, body))
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    match Handle::try_current() {
+        Ok(handle) => handle.block_on(future),
+        // No runtime, e.g. in tests with a mock client
+        Err(_) => futures::executor::block_on(future),
+    }
+}
+
+/// Response bodies by URL, oldest evicted first once total size exceeds [MAX_CACHE_SIZE].
+#[derive(Default)]
+struct ResponseCache {
+    entries: IndexMap<String, CachedResponse>,
+    total_size: usize,
+}
+
+struct CachedResponse {
+    etag: String,
+    body: Vec<u8>,
+}
+
+impl ResponseCache {
+    fn get(&self, url: &str) -> Option<&CachedResponse> {
+        self.entries.get(url)
+    }
+
+    fn insert(&mut self, url: String, etag: String, body: Vec<u8>) {
+        if let Some(old) = self.entries.shift_remove(&url) {
+            self.total_size -= old.body.len();
+        }
+        if body.len() > MAX_CACHE_SIZE { return; }
+        while self.total_size + body.len() > MAX_CACHE_SIZE {
+            let (_, evicted) = self.entries.shift_remove_index(0).expect("Cache size is off");
+            self.total_size -= evicted.body.len();
+        }
+        self.total_size += body.len();
+        self.entries.insert(url, CachedResponse { etag, body });
+    }
+}
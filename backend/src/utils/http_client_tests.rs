@@ -0,0 +1,52 @@
+#![allow(unused_imports)]
+
+use pretty_assertions::{assert_eq, assert_ne};
+
+use super::*;
+
+#[test]
+fn caching_responses() {
+    let mut cache = ResponseCache::default();
+    let third = MAX_CACHE_SIZE / 3;
+    cache.insert("a".to_owned(), "etag-a".to_owned(), vec![1; third]);
+    cache.insert("b".to_owned(), "etag-b".to_owned(), vec![2; third]);
+    assert_eq!(cache.get("a").map(|c| c.etag.as_str()), Some("etag-a"));
+
+    // Replacing an entry doesn't count its old body
+    cache.insert("a".to_owned(), "etag-a2".to_owned(), vec![3; third]);
+    assert_eq!(cache.total_size, third * 2);
+    assert_eq!(cache.get("a").map(|c| (c.etag.as_str(), c.body[0])), Some(("etag-a2", 3)));
+
+    // Oldest entry is evicted
+    cache.insert("c".to_owned(), "etag-c".to_owned(), vec![4; third + 1]);
+    assert!(cache.get("b").is_none());
+    assert!(cache.get("a").is_some());
+    assert!(cache.get("c").is_some());
+    assert_eq!(cache.total_size, third * 2 + 1);
+
+    // Too large to be cached at all
+    cache.insert("d".to_owned(), "etag-d".to_owned(), vec![5; MAX_CACHE_SIZE + 1]);
+    assert!(cache.get("d").is_none());
+    assert_eq!(cache.entries.len(), 2);
+}
+
+#[test]
+fn offline() {
+    let client = SharedHttpClient::new(HttpClientConfig { offline: true, ..Default::default() }).unwrap();
+    let res = get_bytes_blocking(&client, "https://example.com/");
+    assert!(res.is_err());
+    assert!(SharedHttpClient::new(HttpClientConfig { max_concurrent_requests: 0, ..Default::default() }).is_err());
+}
+
+#[test]
+fn slicing_ranges() {
+    let body: Vec<u8> = (0..10).collect();
+    assert_eq!(slice_range(body.clone(), 2, 3), (vec![2, 3, 4], 10));
+    assert_eq!(slice_range(body.clone(), 8, 5), (vec![8, 9], 10));
+    assert_eq!(slice_range(body.clone(), 20, 5), (vec![], 10));
+    assert_eq!(slice_range(body.clone(), 0, u64::MAX), (body, 10));
+
+    assert_eq!(content_range_total_size("bytes 0-99/1234"), Some(1234));
+    assert_eq!(content_range_total_size("bytes */1234"), Some(1234));
+    assert_eq!(content_range_total_size("bytes 0-99/*"), None);
+}
@@ -16,7 +16,7 @@ use uuid::Uuid;
 
 pub use chat_history_manager_core::utils::test_utils::*;
 
-use crate::dao::ChatHistoryDao;
+use crate::dao::ChatHistoryReader;
 use crate::prelude::*;
 
 lazy_static! {
@@ -33,6 +33,8 @@ lazy_static! {
         edit_timestamp_option: None,
         is_deleted: false,
         forward_from_name_option: None,
+        forward_from_id_option: None,
+        ephemeral_duration_sec_option: None,
         reply_to_message_id_option: None,
         contents: vec![],
     };
@@ -174,7 +176,7 @@ pub fn create_databases(resource_name: &str,
 
 /// Returns paths to all files referenced by entities of this dataset. Some might not exist.
 /// Files order matches the chats and messages order returned by DAO.
-pub fn dataset_files(dao: &impl ChatHistoryDao, ds_uuid: &PbUuid) -> Vec<PathBuf> {
+pub fn dataset_files(dao: &impl ChatHistoryReader, ds_uuid: &PbUuid) -> Vec<PathBuf> {
     let ds_root = dao.dataset_root(ds_uuid).unwrap();
     let cwds = dao.chats(ds_uuid).unwrap();
     let mut files: Vec<PathBuf> = cwds.iter()
@@ -194,12 +196,12 @@ pub fn dataset_files(dao: &impl ChatHistoryDao, ds_uuid: &PbUuid) -> Vec<PathBuf
 pub fn assert_files(src_files: &[PathBuf], dst_files: &[PathBuf]) {
     assert_eq!(src_files.len(), dst_files.len());
     for (src, dst) in src_files.iter().zip(dst_files.iter()) {
-        assert!(src.exists(), "File {} not found! Bug in test?", src.to_str().unwrap());
-        assert!(dst.exists(), "File {} wasn't copied from source", dst.to_str().unwrap());
+        assert!(src.exists(), "File {} not found! Bug in test?", src.display());
+        assert!(dst.exists(), "File {} wasn't copied from source", dst.display());
         let src_content = fs::read(src).unwrap();
         let dst_content = fs::read(dst).unwrap();
         let content_eq = src_content == dst_content;
-        assert!(content_eq, "Content of {} didn't match its source {}", dst.to_str().unwrap(), src.to_str().unwrap());
+        assert!(content_eq, "Content of {} didn't match its source {}", dst.display(), src.display());
     }
 }
 
@@ -359,6 +361,8 @@ pub fn create_personal_chat(ds_uuid: &PbUuid, idx: i32, user: &User, member_ids:
         member_ids,
         msg_count: msg_count as i32,
         main_chat_id: None,
+        archived: None,
+        hidden: None,
     }
 }
 
@@ -374,6 +378,8 @@ pub fn create_group_chat(ds_uuid: &PbUuid, id: i64, name_suffix: &str, member_id
         member_ids,
         msg_count: msg_count as i32,
         main_chat_id: None,
+        archived: None,
+        hidden: None,
     }
 }
 
@@ -390,8 +396,10 @@ pub fn create_regular_message(idx: usize, user_id: usize) -> Message {
         is_deleted: false,
         reply_to_message_id_option: reply_to_message_id_option,
         forward_from_name_option: Some(format!("u{user_id}")),
+        forward_from_id_option: None,
+        ephemeral_duration_sec_option: None,
         contents: vec![
-            content!(Poll { question: format!("Hey, {idx}!") })
+            content!(Poll { question: format!("Hey, {idx}!"), options: vec![], is_quiz: None, total_voters_option: None })
         ],
     };
 
@@ -404,6 +412,8 @@ pub fn create_regular_message(idx: usize, user_id: usize) -> Message {
         from_id: user_id as i64,
         text,
         searchable_string,
+        topic_option: None,
+        text_history: vec![],
         typed: Some(typed),
     }
 }
@@ -517,7 +527,7 @@ impl Drop for TmpDir {
 pub struct NoopHttpClient;
 
 impl HttpClient for NoopHttpClient {
-    fn get_bytes(&self, url: &str) -> Result<HttpResponse> {
+    async fn get_bytes(&self, url: &str) -> Result<HttpResponse> {
         log::info!("Mocking request to {}", url);
         Ok(HttpResponse::Ok(Vec::from(url.as_bytes())))
     }
@@ -525,6 +535,7 @@ impl HttpClient for NoopHttpClient {
 
 pub struct MockHttpClient {
     pub calls: Arc<Mutex<RefCell<Vec<String>>>>,
+    pub offline: bool,
 }
 
 impl Default for MockHttpClient {
@@ -535,7 +546,11 @@ impl Default for MockHttpClient {
 
 impl MockHttpClient {
     pub fn new() -> Self {
-        MockHttpClient { calls: Arc::new(Mutex::new(RefCell::new(vec![]))) }
+        MockHttpClient { calls: Arc::new(Mutex::new(RefCell::new(vec![]))), offline: false }
+    }
+
+    pub fn new_offline() -> Self {
+        MockHttpClient { offline: true, ..Self::new() }
     }
 
     pub fn calls_copy(&self) -> Vec<String> {
@@ -547,11 +562,16 @@ impl MockHttpClient {
 }
 
 impl HttpClient for MockHttpClient {
-    fn get_bytes(&self, url: &str) -> Result<HttpResponse> {
+    async fn get_bytes(&self, url: &str) -> Result<HttpResponse> {
+        ensure!(!self.offline, "Offline mode is on, not requesting {url}");
         log::info!("Mocking request to {}", url);
         let lock = self.calls.lock().unwrap();
         let cell = &*lock;
         cell.borrow_mut().push(url.to_owned());
         Ok(HttpResponse::Ok(Vec::from(url.as_bytes())))
     }
+
+    fn is_offline(&self) -> bool {
+        self.offline
+    }
 }
@@ -1,2 +1,3 @@
+pub mod parsing;
 pub mod protobuf;
 pub mod utils;
@@ -0,0 +1,5 @@
+//! Parsing that only needs file contents, with no filesystem access, so that it can be done client-side
+//! (including `wasm32` builds) before anything is uploaded to the backend.
+
+pub mod telegram;
+pub mod vcard;
@@ -0,0 +1,136 @@
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use serde_json::{Map, Value};
+
+/// Root of a single chat export has only these keys, full export has others.
+const SINGLE_CHAT_EXPORT_KEYS: [&str; 4] = ["name", "type", "id", "messages"];
+
+/// Fields holding paths of a message media, relative to the export root.
+const MEDIA_PATH_FIELDS: [&str; 3] = ["photo", "file", "thumbnail"];
+
+/// Summary of a Telegram export, enough to show what's inside before uploading it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TelegramExportPreview {
+    pub myself_name_option: Option<String>,
+    pub chats: Vec<TelegramChatPreview>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TelegramChatPreview {
+    pub id: i64,
+    /// Not present for saved messages
+    pub name_option: Option<String>,
+    /// As-is, e.g. `personal_chat` or `private_group`
+    pub tpe: String,
+    pub messages_count: usize,
+    /// Unix timestamps, only present for exports made by newer Telegram versions
+    pub first_timestamp_option: Option<i64>,
+    pub last_timestamp_option: Option<i64>,
+    /// Names of message authors and service message actors, in order of appearance
+    pub participants: Vec<String>,
+    /// Paths to media files referenced by messages, relative to the export root.
+    /// Media that wasn't included in the export is not listed.
+    pub media_paths: Vec<String>,
+}
+
+/// Lightweight counterpart of a backend Telegram loader, working on a `result.json` content.
+/// Supports both full exports and single chat exports.
+/// Messages content is not parsed, and the export is not validated beyond what's needed for a preview.
+pub fn preview_telegram_export(json: &str) -> Result<TelegramExportPreview> {
+    let root: Value = serde_json::from_str(json).context("Export is not a valid JSON")?;
+    let root = as_object(&root, "root")?;
+
+    if is_single_chat_export(root.keys().map(|k| k.as_str())) {
+        return Ok(TelegramExportPreview { myself_name_option: None, chats: vec![preview_chat(root)?] });
+    }
+    let Some(chats_json) = root.get("chats") else {
+        bail!("Neither a full nor a single chat Telegram export")
+    };
+    let myself_name_option = match root.get("personal_information") {
+        Some(info) => {
+            let info = as_object(info, "personal_information")?;
+            let name_field = |k: &str| info.get(k).and_then(|v| v.as_str());
+            Some(full_name(name_field("first_name"), name_field("last_name"))).filter(|s| !s.is_empty())
+        }
+        None => None
+    };
+    let chats_json = as_object(chats_json, "chats")?;
+    let list = chats_json.get("list").and_then(|v| v.as_array()).context("chats.list is not an array")?;
+    let chats = list.iter().enumerate()
+        .map(|(idx, chat_json)| preview_chat(as_object(chat_json, &format!("chats.list[{idx}]"))?))
+        .try_collect()?;
+    Ok(TelegramExportPreview { myself_name_option, chats })
+}
+
+//
+// Field parsing shared with the backend loader
+//
+
+/// Whether an export root with the given keys is a single chat export, as opposed to a full one.
+pub fn is_single_chat_export<'a>(mut root_keys: impl Iterator<Item = &'a str>) -> bool {
+    root_keys.all(|k| SINGLE_CHAT_EXPORT_KEYS.contains(&k))
+}
+
+/// Filters out placeholders Telegram uses in place of files that weren't exported.
+pub fn actual_path_option(s: String) -> Option<String> {
+    match s.as_str() {
+        "" => None,
+        "(File not included. Change data exporting settings to download.)" => None,
+        "(File exceeds maximum size. Change data exporting settings to download.)" => None,
+        "(File unavailable, please try again later)" => {
+            // So far looks like it may mean timed photo, or file manually skipped during export.
+            None
+        }
+        _ => Some(s)
+    }
+}
+
+/// Parses a timestamp given as a string, e.g. `date_unixtime`.
+pub fn parse_timestamp(s: &str) -> Result<i64> {
+    s.parse::<i64>().with_context(|| format!("Failed to parse unit timestamp {s}"))
+}
+
+/// Name of a user as Telegram shows it, either part might be missing.
+pub fn full_name(first_name_option: Option<&str>, last_name_option: Option<&str>) -> String {
+    format!("{} {}", first_name_option.unwrap_or(""), last_name_option.unwrap_or("")).trim().to_owned()
+}
+
+fn preview_chat(chat_json: &Map<String, Value>) -> Result<TelegramChatPreview> {
+    let id = chat_json.get("id").and_then(|v| v.as_i64()).context("Chat has no ID")?;
+    let json_path = format!("chat[#{id}]");
+    let name_option = chat_json.get("name").and_then(|v| v.as_str()).map(|s| s.to_owned());
+    let tpe = chat_json.get("type").and_then(|v| v.as_str())
+        .with_context(|| format!("{json_path}.type is not a string"))?.to_owned();
+    let messages = chat_json.get("messages").and_then(|v| v.as_array())
+        .with_context(|| format!("{json_path}.messages is not an array"))?;
+
+    let timestamps = messages.iter()
+        .filter_map(|m| m.get("date_unixtime").and_then(|v| v.as_str()))
+        .filter_map(|s| parse_timestamp(s).ok())
+        .collect_vec();
+    let participants = messages.iter()
+        .filter_map(|m| m.get("from").or_else(|| m.get("actor")).and_then(|v| v.as_str()))
+        .unique()
+        .map(|s| s.to_owned())
+        .collect_vec();
+    let media_paths = messages.iter()
+        .flat_map(|m| MEDIA_PATH_FIELDS.iter().filter_map(|k| m.get(*k).and_then(|v| v.as_str())))
+        .filter_map(|s| actual_path_option(s.to_owned()))
+        .unique()
+        .collect_vec();
+
+    Ok(TelegramChatPreview {
+        id,
+        name_option,
+        tpe,
+        messages_count: messages.len(),
+        first_timestamp_option: timestamps.iter().min().cloned(),
+        last_timestamp_option: timestamps.iter().max().cloned(),
+        participants,
+        media_paths,
+    })
+}
+
+fn as_object<'a>(value: &'a Value, json_path: &str) -> Result<&'a Map<String, Value>> {
+    value.as_object().with_context(|| format!("{json_path} is not an object"))
+}
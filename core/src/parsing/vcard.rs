@@ -0,0 +1,63 @@
+use std::io::BufReader;
+
+use anyhow::{Context, Result};
+use ical::parser::vcard::component::VcardContact;
+use ical::property::Property;
+use ical::VcardParser;
+use itertools::Itertools;
+
+use crate::protobuf::history::*;
+
+/// Parses the first contact of a vCard (`.vcf`) content.
+/// All phone numbers are kept (comma-separated), the one having WhatsApp ID (if any) goes first.
+pub fn parse_vcard(vcard: &str) -> Result<ContentSharedContact> {
+    let mut parser = VcardParser::new(BufReader::new(vcard.as_bytes()));
+    let vcard = parser.next().context("No contact found in vCard")??;
+
+    let full_name_option = property_values(&vcard, "FN").next();
+    // Structured name is "Last;First;Middle;Prefix;Suffix"
+    let (first_name_option, last_name_option) = match full_name_option {
+        Some(full_name) => (Some(full_name), None),
+        None => match property_values(&vcard, "N").next() {
+            Some(name) => {
+                let parts = name.split(';').map(|s| s.trim()).collect_vec();
+                let first_name = parts.iter().skip(1).take(2).filter(|s| !s.is_empty()).join(" ");
+                (Some(first_name).filter(|s| !s.is_empty()),
+                 parts.first().map(|s| s.to_string()).filter(|s| !s.is_empty()))
+            }
+            None => (None, None),
+        }
+    };
+
+    let has_waid = |p: &&Property|
+        p.params.as_ref().is_some_and(|params| params.iter().any(|(k, _)| k.eq_ignore_ascii_case("WAID")));
+    let phones = vcard.properties.iter()
+        .filter(|p| has_name(&p.name, "TEL"))
+        .sorted_by_key(|p| !has_waid(p))
+        .filter_map(|p| non_blank(p.value.as_ref()))
+        .unique()
+        .collect_vec();
+
+    Ok(ContentSharedContact {
+        first_name_option,
+        last_name_option,
+        phone_number_option: Some(phones.join(", ")).filter(|s| !s.is_empty()),
+        vcard_path_option: None,
+        emails: property_values(&vcard, "EMAIL").unique().collect_vec(),
+    })
+}
+
+/// Property name might be grouped, e.g. `item1.TEL`
+fn has_name(property_name: &str, name: &str) -> bool {
+    property_name.split('.').any(|n| n.eq_ignore_ascii_case(name))
+}
+
+fn property_values<'a>(vcard: &'a VcardContact, name: &'a str) -> impl Iterator<Item=String> + 'a {
+    vcard.properties.iter()
+        .filter(move |p| has_name(&p.name, name))
+        .filter_map(|p| non_blank(p.value.as_ref()))
+}
+
+fn non_blank(s: Option<&String>) -> Option<String> {
+    s.map(|s| s.trim()).filter(|s| !s.is_empty()).map(|s| s.to_owned())
+}
@@ -1,4 +1,6 @@
 pub mod entity_utils;
+pub mod message_builder;
+pub mod rich_text;
 
 // Unfortunately, #[cfg(test)] is not exported outside the crate, so we're using feature as a workaround
 #[cfg(feature = "test-utils")]
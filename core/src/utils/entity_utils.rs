@@ -1,12 +1,14 @@
 use std::error::Error as StdError;
-use std::fmt::{Display, Formatter};
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter, Write};
 use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use deepsize::DeepSizeOf;
 use derive_deref::Deref;
 use itertools::Itertools;
 use lazy_static::lazy_static;
+use path_dedot::ParseDot;
 use regex::Regex;
 use uuid::Uuid;
 
@@ -15,6 +17,8 @@ use crate::protobuf::history::*;
 pub const UNNAMED: &str = "[unnamed]";
 pub const UNKNOWN: &str = "[unknown]";
 pub const SOMEONE: &str = "[someone]";
+/// Name of a chat with oneself, no matter what source calls it.
+pub const SELF_CHAT_NAME: &str = "Notes to Self";
 
 pub const NO_INTERNAL_ID: MessageInternalId = MessageInternalId(-1);
 
@@ -40,25 +44,127 @@ impl Eq for PbUuid {}
 pub struct DatasetRoot(pub PathBuf);
 
 impl DatasetRoot {
+    /// Resolves a relative path stored in a database, see [DatasetRoot::to_relative].
     pub fn to_absolute(&self, path_str: &str) -> PathBuf {
-        let path = Path::new(path_str);
+        let path = storage_string_to_path(path_str);
         assert!(!path.is_absolute(), "Path {} needs to be relative!", path_str);
-        self.0.join(path)
+        long_path(self.0.join(path))
     }
 
+    /// Path relative to dataset root as it's stored in a database: components are separated by `/`,
+    /// names that aren't valid Unicode are encoded by [os_str_to_storage_string].
+    ///
+    /// Directories within dataset root might be symlinks (or junctions) pointing elsewhere, e.g. to media on
+    /// another disk, so path is matched both as-is (with `.` and `..` resolved lexically) and canonicalized.
+    /// Canonical path might also have a different form, e.g. verbatim `\\?\C:\...` on Windows.
     pub fn to_relative(&self, path: &Path) -> Result<String> {
         let ds_root = &self.0;
         assert!(ds_root.is_absolute(), "Path {} needs to be absolute!", path.display());
-        let path = path.canonicalize()?;
-        let path = path.to_str().with_context(|| "Path is not a valid string!")?;
-        let ds_root = ds_root.to_str().with_context(|| "Dataset root is not a valid string!")?;
-        if !path.starts_with(ds_root) {
-            bail!("Path {} is not under dataset root {}", path, ds_root);
+        let canonical_path = path.canonicalize()?;
+        let lexical_path = std::path::absolute(path)?.parse_dot()?.into_owned();
+        let ds_roots = [Some(ds_root.clone()), ds_root.canonicalize().ok()];
+        let rel_path = [&lexical_path, &canonical_path].into_iter()
+            .cartesian_product(ds_roots.iter().flatten())
+            .find_map(|(path, ds_root)| path.strip_prefix(ds_root).ok())
+            .with_context(|| format!("Path {} is not under dataset root {}", path.display(), ds_root.display()))?;
+        Ok(rel_path.components().map(|c| os_str_to_storage_string(c.as_os_str())).join("/"))
+    }
+}
+
+/// Marks a path component encoded by [os_str_to_storage_string], all other components are stored verbatim.
+pub const ENCODED_NAME_PREFIX: &str = "%%";
+
+/// Valid Unicode is kept as-is. Otherwise, as well as for names that happen to start with [ENCODED_NAME_PREFIX],
+/// `%` and bytes that are not valid UTF-8 are percent-encoded and the result is marked with [ENCODED_NAME_PREFIX],
+/// e.g. `caf\xE9.jpg` (Latin-1 name from an old phone) becomes `%%caf%E9.jpg`.
+pub fn os_str_to_storage_string(s: &OsStr) -> String {
+    match s.to_str() {
+        Some(s) if !s.starts_with(ENCODED_NAME_PREFIX) => return s.to_owned(),
+        _ => {}
+    }
+    let bytes = s.as_encoded_bytes();
+    let mut result = String::with_capacity(ENCODED_NAME_PREFIX.len() + bytes.len() * 3);
+    result.push_str(ENCODED_NAME_PREFIX);
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '%' { result.push_str("%25"); } else { result.push(c); }
         }
-        Ok(path[(ds_root.len() + 1)..].to_owned())
+        for b in chunk.invalid() {
+            write!(result, "%{b:02X}").unwrap();
+        }
+    }
+    result
+}
+
+/// Reverse of [os_str_to_storage_string], applied to each `/`-separated component.
+/// Only components marked with [ENCODED_NAME_PREFIX] are decoded, so a plain name like `r%E9sum%E9.pdf` stays as-is.
+pub fn storage_string_to_path(s: &str) -> PathBuf {
+    if !s.split('/').any(|c| c.starts_with(ENCODED_NAME_PREFIX)) {
+        return PathBuf::from(s);
+    }
+    let mut result = OsString::with_capacity(s.len());
+    for (idx, component) in s.split('/').enumerate() {
+        if idx > 0 { result.push("/"); }
+        match component.strip_prefix(ENCODED_NAME_PREFIX) {
+            Some(encoded) => result.push(bytes_to_os_string(percent_decode(encoded))),
+            None => result.push(component),
+        }
+    }
+    PathBuf::from(result)
+}
+
+/// Malformed escapes are kept as-is.
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex_byte = (bytes[i] == b'%')
+            .then(|| s.get((i + 1)..(i + 3)))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex_byte {
+            Some(b) => { decoded.push(b); i += 3; }
+            None => { decoded.push(bytes[i]); i += 1; }
+        }
+    }
+    decoded
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes)
+}
+
+/// Non-Unicode names can only be decoded on Unix, other platforms get the closest approximation.
+#[cfg(not(unix))]
+fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Paths longer than `MAX_PATH` only work on Windows in a verbatim form (`\\?\C:\...`),
+/// which in turn doesn't accept `/` separators.
+#[cfg(windows)]
+fn long_path(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+    const MAX_PATH: usize = 260;
+
+    if path.as_os_str().len() < MAX_PATH { return path; }
+    let mut components = path.components();
+    match components.next() {
+        Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::Disk(_)) => {
+            let mut result = PathBuf::from(format!(r"\\?\{}", prefix.as_os_str().to_string_lossy()));
+            result.extend(components);
+            result
+        }
+        _ => path,
     }
 }
 
+#[cfg(not(windows))]
+fn long_path(path: PathBuf) -> PathBuf { path }
+
 #[repr(transparent)]
 #[derive(Deref, Clone, Copy, Debug, PartialEq, Eq, Hash, DeepSizeOf)]
 pub struct UserId(pub i64);
@@ -292,6 +398,8 @@ impl Message {
             from_id: *from_id,
             text,
             searchable_string,
+            topic_option: None,
+            text_history: vec![],
             typed: Some(typed),
         }
     }
@@ -310,8 +418,9 @@ impl Message {
         self.typed.as_mut().expect("Invalid typed message")
     }
 
+    /// Includes images of custom emojis in text (current as well as former versions), after the content files.
     pub fn files_relative(&self) -> Vec<&str> {
-        let possibilities: Vec<Option<&str>> = match self.typed() {
+        let mut possibilities: Vec<Option<&str>> = match self.typed() {
             message::Typed::Regular(mr) => {
                 mr.contents.iter()
                     .flat_map(|content| {
@@ -353,6 +462,11 @@ impl Message {
             }
             message_service_pat_unreachable!() => { unreachable!() }
         };
+        let texts = std::iter::once(&self.text).chain(self.text_history.iter().map(|v| &v.text));
+        possibilities.extend(texts.flatten().map(|rte| match rte.val.as_ref().unwrap() {
+            rich_text_element::Val::CustomEmoji(v) => v.path_option.as_deref(),
+            _ => None,
+        }));
         possibilities.into_iter().flatten().collect()
     }
 
@@ -362,6 +476,28 @@ impl Message {
     }
 }
 
+/// Structured attribution of a forwarded message, derived from `forward_from_*` fields.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForwardOrigin<'a> {
+    /// Author ID is known, it's not necessarily one of the dataset users though
+    User(UserId),
+    /// Only the author name is known
+    Named(&'a str),
+    Unknown,
+}
+
+impl MessageRegular {
+    /// `None` if message is not a forward.
+    pub fn forward_origin_option(&self) -> Option<ForwardOrigin<'_>> {
+        match (self.forward_from_id_option, self.forward_from_name_option.as_deref()) {
+            (Some(id), _) => Some(ForwardOrigin::User(UserId(id))),
+            (None, None) => None,
+            (None, Some(name)) if name.is_empty() || name == UNKNOWN || name == SOMEONE => Some(ForwardOrigin::Unknown),
+            (None, Some(name)) => Some(ForwardOrigin::Named(name)),
+        }
+    }
+}
+
 impl RichTextElement {
     pub fn get_text(&self) -> Option<&str> {
         use rich_text_element::Val;
@@ -373,8 +509,11 @@ impl RichTextElement {
             Val::Strikethrough(RteStrikethrough { text }) |
             Val::PrefmtInline(RtePrefmtInline { text }) |
             Val::PrefmtBlock(RtePrefmtBlock { text, .. }) |
-            Val::Blockquote(RteBlockquote { text }) |
-            Val::Spoiler(RteSpoiler { text }) => {
+            Val::Blockquote(RteBlockquote { text, .. }) |
+            Val::Spoiler(RteSpoiler { text }) |
+            Val::Mention(RteMention { text, .. }) |
+            Val::Hashtag(RteHashtag { text, .. }) |
+            Val::CustomEmoji(RteCustomEmoji { text, .. }) => {
                 Some(text)
             }
             Val::Link(RteLink { text_option, .. }) => {
@@ -393,8 +532,11 @@ impl RichTextElement {
             Val::Strikethrough(RteStrikethrough { text }) |
             Val::PrefmtInline(RtePrefmtInline { text }) |
             Val::PrefmtBlock(RtePrefmtBlock { text, .. }) |
-            Val::Blockquote(RteBlockquote { text }) |
-            Val::Spoiler(RteSpoiler { text }) => {
+            Val::Blockquote(RteBlockquote { text, .. }) |
+            Val::Spoiler(RteSpoiler { text }) |
+            Val::Mention(RteMention { text, .. }) |
+            Val::Hashtag(RteHashtag { text, .. }) |
+            Val::CustomEmoji(RteCustomEmoji { text, .. }) => {
                 Some(text)
             }
             Val::Link(RteLink { text_option, .. }) => {
@@ -443,9 +585,14 @@ impl RichText {
     }
 
     pub fn make_blockquote(text: String) -> RichTextElement {
+        Self::make_quote(text, None)
+    }
+
+    /// Blockquote of another message of the same chat, referenced by its source ID (if known).
+    pub fn make_quote(text: String, quoted_source_id_option: Option<i64>) -> RichTextElement {
         RichTextElement {
             searchable_string: normalize_seachable_string(text.as_str()),
-            val: Some(rich_text_element::Val::Blockquote(RteBlockquote { text })),
+            val: Some(rich_text_element::Val::Blockquote(RteBlockquote { text, quoted_source_id_option })),
         }
     }
 
@@ -476,6 +623,32 @@ impl RichText {
         }
     }
 
+    /// Handle is searchable too, even if not shown.
+    pub fn make_mention(text: String, user_id_option: Option<i64>, handle_option: Option<String>) -> RichTextElement {
+        let searchable_string = match handle_option {
+            Some(ref handle) if !text.contains(handle.as_str()) => format!("{text} {handle}"),
+            _ => text.clone(),
+        };
+        RichTextElement {
+            searchable_string: normalize_seachable_string(searchable_string.as_str()),
+            val: Some(rich_text_element::Val::Mention(RteMention { text, user_id_option, handle_option })),
+        }
+    }
+
+    pub fn make_hashtag(text: String, is_cashtag: bool) -> RichTextElement {
+        RichTextElement {
+            searchable_string: normalize_seachable_string(text.as_str()),
+            val: Some(rich_text_element::Val::Hashtag(RteHashtag { text, is_cashtag })),
+        }
+    }
+
+    pub fn make_custom_emoji(text: String, emoji_id_option: Option<String>, path_option: Option<String>) -> RichTextElement {
+        RichTextElement {
+            searchable_string: normalize_seachable_string(text.as_str()),
+            val: Some(rich_text_element::Val::CustomEmoji(RteCustomEmoji { text, emoji_id_option, path_option })),
+        }
+    }
+
     pub fn make_prefmt_inline(text: String) -> RichTextElement {
         RichTextElement {
             searchable_string: normalize_seachable_string(text.as_str()),
@@ -503,6 +676,22 @@ macro_rules! get_file_name_helper {
 }
 
 impl Content {
+    pub fn content_type(&self) -> ContentType {
+        use content::SealedValueOptional::*;
+        match self.sealed_value_optional.as_ref().unwrap() { // @formatter:off
+            Sticker(_)       => ContentType::Sticker,
+            Photo(_)         => ContentType::Photo,
+            VoiceMsg(_)      => ContentType::VoiceMsg,
+            Audio(_)         => ContentType::Audio,
+            VideoMsg(_)      => ContentType::VideoMsg,
+            Video(_)         => ContentType::Video,
+            File(_)          => ContentType::File,
+            Location(_)      => ContentType::Location,
+            Poll(_)          => ContentType::Poll,
+            SharedContact(_) => ContentType::SharedContact,
+        } // @formatter:on
+    }
+
     pub fn path_file_option(&self, ds_root: &DatasetRoot) -> Option<PathBuf> {
         use content::SealedValueOptional::*;
         match self.sealed_value_optional.as_ref() { // @formatter:off
@@ -626,7 +815,18 @@ fn normalize_seachable_string(s: &str) -> String {
     NORMALIZE_REGEX.replace_all(s, " ").trim().to_owned()
 }
 
+/// Searchable string made according to default [SearchableStringSettings].
 pub fn make_searchable_string(components: &[RichTextElement], typed: &message::Typed) -> String {
+    make_searchable_string_with(components, typed, &SearchableStringSettings::default())
+}
+
+pub fn make_searchable_string_with(components: &[RichTextElement],
+                                   typed: &message::Typed,
+                                   settings: &SearchableStringSettings) -> String {
+    let file_names = |v: Vec<&Option<String>>| -> Vec<String> {
+        if settings.include_file_names() { v.into_iter().flatten().cloned().collect_vec() } else { vec![] }
+    };
+
     let joined_text: String =
         components.iter()
             .map(|rte| &rte.searchable_string)
@@ -643,23 +843,34 @@ pub fn make_searchable_string(components: &[RichTextElement], typed: &message::T
                         Sticker(sticker) =>
                             vec![&sticker.emoji_option].into_iter().flatten().cloned().collect_vec(),
                         Audio(file) =>
-                            vec![&file.title_option, &file.performer_option].into_iter().flatten().cloned().collect_vec(),
+                            file_names(vec![&file.title_option, &file.performer_option]),
                         Video(file) =>
-                            vec![&file.title_option, &file.performer_option].into_iter().flatten().cloned().collect_vec(),
+                            file_names(vec![&file.title_option, &file.performer_option]),
                         File(file) =>
-                            vec![&file.file_name_option].into_iter().flatten().cloned().collect_vec(),
+                            file_names(vec![&file.file_name_option]),
                         Location(loc) => {
                             let mut vec1 = vec![&loc.address_option, &loc.title_option].into_iter().flatten().collect_vec();
-                            let mut vec2 = vec![&loc.lat_str, &loc.lon_str];
-                            vec1.append(&mut vec2);
+                            if settings.include_coordinates() {
+                                let mut vec2 = vec![&loc.lat_str, &loc.lon_str];
+                                vec1.append(&mut vec2);
+                            }
                             vec1.into_iter().cloned().collect_vec()
                         }
                         Poll(poll) =>
-                            vec![poll.question.clone()],
-                        SharedContact(contact) =>
-                            vec![&contact.first_name_option, &contact.last_name_option, &contact.phone_number_option]
-                                .into_iter().flatten().cloned().collect_vec(),
-                        Photo(_) | VoiceMsg(_) | VideoMsg(_) => {
+                            std::iter::once(&poll.question).chain(poll.options.iter().map(|o| &o.text))
+                                .cloned().collect_vec(),
+                        SharedContact(contact) => {
+                            let phone_numbers = contact.phone_number_option.iter()
+                                .flat_map(|phone| searchable_phone_numbers(phone, settings.phone_number_format()));
+                            vec![&contact.first_name_option, &contact.last_name_option]
+                                .into_iter().flatten().cloned()
+                                .chain(phone_numbers)
+                                .chain(contact.emails.iter().cloned())
+                                .collect_vec()
+                        }
+                        Photo(photo) =>
+                            photo.ocr_text_option.iter().cloned().collect_vec(),
+                        VoiceMsg(_) | VideoMsg(_) => {
                             // Text is enough.
                             vec![]
                             // TODO: Add this and reform the database
@@ -686,12 +897,64 @@ pub fn make_searchable_string(components: &[RichTextElement], typed: &message::T
         _ => unreachable!()
     };
 
-    [joined_text, typed_component_text.join(" ")].iter()
+    let searchable_string = [joined_text, typed_component_text.join(" ")].iter()
         .map(|s| s.trim())
         .filter(|s| !s.is_empty())
         .join(" ")
         .trim()
-        .to_owned()
+        .to_owned();
+    match settings.transliterate().then(|| transliterate(&searchable_string)).flatten() {
+        Some(transliterated) => format!("{searchable_string} {transliterated}"),
+        None => searchable_string,
+    }
+}
+
+/// Lowercase Latin transliteration of Cyrillic (Russian, Ukrainian and Belarusian) letters in the string,
+/// other characters are kept as-is. Absent if there's nothing to transliterate.
+///
+/// Only one romanization is used, e.g. `х` becomes `kh` and `й` becomes `y`, so alternative spellings
+/// like `h` or `j` won't match.
+pub fn transliterate(s: &str) -> Option<String> {
+    fn latin(c: char) -> Option<&'static str> {
+        Some(match c {
+            'а' => "a", 'б' => "b", 'в' => "v", 'г' => "g", 'ґ' => "g", 'д' => "d", 'е' => "e", 'ё' => "e",
+            'є' => "ye", 'ж' => "zh", 'з' => "z", 'и' => "i", 'і' => "i", 'ї' => "yi", 'й' => "y", 'к' => "k",
+            'л' => "l", 'м' => "m", 'н' => "n", 'о' => "o", 'п' => "p", 'р' => "r", 'с' => "s", 'т' => "t",
+            'у' => "u", 'ў' => "u", 'ф' => "f", 'х' => "kh", 'ц' => "ts", 'ч' => "ch", 'ш' => "sh", 'щ' => "shch",
+            'ъ' => "", 'ы' => "y", 'ь' => "", 'э' => "e", 'ю' => "yu", 'я' => "ya",
+            _ => return None,
+        })
+    }
+    let mut found = false;
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        match latin(lower) {
+            Some(l) => {
+                found = true;
+                res.push_str(l);
+            }
+            None => res.push(c),
+        }
+    }
+    found.then_some(res)
+}
+
+/// Phone string could hold multiple comma-separated numbers.
+fn searchable_phone_numbers(phone: &str, format: PhoneNumberFormat) -> Vec<String> {
+    let digits_only = || phone.split(',')
+        .map(|phone| {
+            let plus = if phone.trim_start().starts_with('+') { "+" } else { "" };
+            format!("{plus}{}", phone.chars().filter(|c| c.is_ascii_digit()).collect::<String>())
+        })
+        .filter(|phone| !phone.is_empty())
+        .join(", ");
+    match format {
+        PhoneNumberFormat::AsIs => vec![phone.to_owned()],
+        PhoneNumberFormat::DigitsOnly => vec![digits_only()],
+        PhoneNumberFormat::Both => vec![phone.to_owned(), digits_only()].into_iter().unique().collect_vec(),
+        PhoneNumberFormat::Omit => vec![],
+    }
 }
 
 pub fn name_or_unnamed(name_option: &Option<String>) -> String {
@@ -0,0 +1,178 @@
+use crate::protobuf::history::*;
+use crate::utils::entity_utils::*;
+
+/// Builds a regular message without spelling out every optional field, e.g.
+/// ```ignore
+/// let msg = MessageBuilder::regular(timestamp, from_id)
+///     .source_id(123)
+///     .text("Look at this")
+///     .photo(Some("photos/1.jpg".to_owned()), 640, 480)
+///     .reply_to(122)
+///     .build();
+/// ```
+/// Fields not set explicitly are left empty, internal ID defaults to [NO_INTERNAL_ID].
+/// Searchable string is computed on [MessageBuilder::build], same as for [Message::new].
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    internal_id: i64,
+    source_id_option: Option<i64>,
+    timestamp: i64,
+    from_id: UserId,
+    text: Vec<RichTextElement>,
+    regular: MessageRegular,
+}
+
+impl MessageBuilder {
+    pub fn regular(timestamp: i64, from_id: UserId) -> Self {
+        MessageBuilder {
+            internal_id: *NO_INTERNAL_ID,
+            source_id_option: None,
+            timestamp,
+            from_id,
+            text: vec![],
+            regular: MessageRegular::default(),
+        }
+    }
+
+    pub fn internal_id(self, internal_id: i64) -> Self {
+        MessageBuilder { internal_id, ..self }
+    }
+
+    pub fn source_id(self, source_id: i64) -> Self {
+        MessageBuilder { source_id_option: Some(source_id), ..self }
+    }
+
+    /// Appends plain text, empty text is skipped.
+    pub fn text(self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        if text.is_empty() { return self; }
+        self.rich_text(RichText::make_plain(text))
+    }
+
+    /// Appends a formatted text element.
+    pub fn rich_text(mut self, rte: RichTextElement) -> Self {
+        self.text.push(rte);
+        self
+    }
+
+    pub fn rich_texts(mut self, rtes: impl IntoIterator<Item = RichTextElement>) -> Self {
+        self.text.extend(rtes);
+        self
+    }
+
+    /// Accepts either a timestamp or an `Option` of it.
+    pub fn edited_at(mut self, edit_timestamp_option: impl Into<Option<i64>>) -> Self {
+        self.regular.edit_timestamp_option = edit_timestamp_option.into();
+        self
+    }
+
+    /// Deletion time (if known) is expected to be set through [Self::edited_at].
+    pub fn deleted(mut self) -> Self {
+        self.regular.is_deleted = true;
+        self
+    }
+
+    pub fn forwarded_from(mut self, name: impl Into<String>, id_option: Option<i64>) -> Self {
+        self.regular.forward_from_name_option = Some(name.into());
+        self.regular.forward_from_id_option = id_option;
+        self
+    }
+
+    /// Replied message is referenced by its source ID, accepts either an ID or an `Option` of it.
+    pub fn reply_to(mut self, source_id_option: impl Into<Option<i64>>) -> Self {
+        self.regular.reply_to_message_id_option = source_id_option.into();
+        self
+    }
+
+    pub fn ephemeral(mut self, duration_sec: i32) -> Self {
+        self.regular.ephemeral_duration_sec_option = Some(duration_sec);
+        self
+    }
+
+    /// Appends arbitrary content, see [crate::content] macro.
+    pub fn content(mut self, content: Content) -> Self {
+        self.regular.contents.push(content);
+        self
+    }
+
+    pub fn contents(mut self, contents: impl IntoIterator<Item = Content>) -> Self {
+        self.regular.contents.extend(contents);
+        self
+    }
+
+    /// Path is relative to dataset root, dimensions are 0 if unknown.
+    pub fn photo(self, path_option: Option<String>, width: i32, height: i32) -> Self {
+        self.content_of(content::SealedValueOptional::Photo(ContentPhoto {
+            path_option,
+            width,
+            height,
+            ..Default::default()
+        }))
+    }
+
+    /// Path is relative to dataset root.
+    pub fn video(self, path_option: Option<String>, mime_type: impl Into<String>) -> Self {
+        self.content_of(content::SealedValueOptional::Video(ContentVideo {
+            path_option,
+            mime_type: mime_type.into(),
+            ..Default::default()
+        }))
+    }
+
+    /// Path is relative to dataset root.
+    pub fn audio(self, path_option: Option<String>, mime_type: impl Into<String>) -> Self {
+        self.content_of(content::SealedValueOptional::Audio(ContentAudio {
+            path_option,
+            mime_type: mime_type.into(),
+            ..Default::default()
+        }))
+    }
+
+    /// Path is relative to dataset root.
+    pub fn voice_msg(self, path_option: Option<String>, mime_type: impl Into<String>) -> Self {
+        self.content_of(content::SealedValueOptional::VoiceMsg(ContentVoiceMsg {
+            path_option,
+            mime_type: mime_type.into(),
+            ..Default::default()
+        }))
+    }
+
+    /// Path is relative to dataset root.
+    pub fn sticker(self, path_option: Option<String>, emoji_option: Option<String>) -> Self {
+        self.content_of(content::SealedValueOptional::Sticker(ContentSticker {
+            path_option,
+            emoji_option,
+            ..Default::default()
+        }))
+    }
+
+    /// Path is relative to dataset root.
+    pub fn file(self, path_option: Option<String>, file_name_option: Option<String>) -> Self {
+        self.content_of(content::SealedValueOptional::File(ContentFile {
+            path_option,
+            file_name_option,
+            ..Default::default()
+        }))
+    }
+
+    pub fn location(self, lat_str: impl Into<String>, lon_str: impl Into<String>) -> Self {
+        self.content_of(content::SealedValueOptional::Location(ContentLocation {
+            lat_str: lat_str.into(),
+            lon_str: lon_str.into(),
+            ..Default::default()
+        }))
+    }
+
+    fn content_of(self, val: content::SealedValueOptional) -> Self {
+        self.content(Content { sealed_value_optional: Some(val) })
+    }
+
+    pub fn build(self) -> Message {
+        Message::new(self.internal_id,
+                     self.source_id_option,
+                     self.timestamp,
+                     self.from_id,
+                     self.text,
+                     message::Typed::Regular(self.regular))
+    }
+}
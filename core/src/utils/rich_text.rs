@@ -0,0 +1,689 @@
+//! Conversion of rich text from and to Markdown and HTML, for loaders of sources storing formatted text
+//! and for HTML exporters, as well as rendering it as plain text for text exporters.
+//!
+//! Rich text elements can't be nested, so nested formatting is flattened to the outermost one.
+//! Rendering and parsing back yields the same elements, save for the following:
+//! * adjacent plain text elements are merged, empty elements are dropped;
+//! * whitespace at the edges of italic text in Markdown ends up outside of it;
+//! * link is considered hidden if and only if its text is blank;
+//! * visible link without a text is rendered in HTML with its href as a text;
+//! * mentions, hashtags and custom emojis have no markup of their own and are rendered as plain text;
+//! * quoted message reference of a blockquote is not rendered.
+
+use std::iter;
+use std::mem;
+
+use crate::protobuf::history::*;
+use crate::utils::entity_utils::*;
+
+use rich_text_element::Val;
+
+/// Characters with special meaning in Markdown, escaped with a backslash in a plain text.
+const MD_SPECIAL_CHARS: &[char] = &['\\', '*', '_', '~', '|', '`', '[', ']', '<', '>'];
+
+/// Marks spoiler text in plain text rendering.
+const SPOILER_MARKER: char = '‖';
+
+/// How [RichText::to_plain] represents formatting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlainTextStyle {
+    /// Formatting is dropped, spoilers are revealed.
+    #[default]
+    Stripped,
+    /// Formatting is dropped, spoilers are enclosed in `‖…‖`.
+    Spoilers,
+    /// Formatting is represented by lightweight markup readable as-is: `*bold*`, `_italic_`, `~strikethrough~`,
+    /// `` `code` ``, fenced code blocks, lines quoted with `> ` and spoilers enclosed in `‖…‖`.
+    /// Unlike [RichText::to_markdown], nothing is escaped and the result is not meant to be parsed back.
+    Markdownish,
+}
+
+impl RichText {
+    /// Renders text for places where formatting can't be shown, e.g. text exports or comparisons.
+    /// Link is rendered as its text followed by its href in parentheses (unless they're the same),
+    /// hidden links are omitted.
+    pub fn to_plain(rtes: &[RichTextElement], style: PlainTextStyle) -> String {
+        let markup = style == PlainTextStyle::Markdownish;
+        let mut res = String::new();
+        for rte in rtes {
+            match rte.val.as_ref().unwrap() {
+                Val::Link(v) if v.hidden => { /* Not visible */ }
+                Val::Link(v) => match v.text_option.as_deref() {
+                    Some(text) if !text.is_empty() && text != v.href => res.push_str(&format!("{text} ({})", v.href)),
+                    _ => res.push_str(&v.href),
+                },
+                _ if rte.get_text().is_none_or(|t| t.is_empty()) => { /* Nothing to render */ }
+                Val::Spoiler(v) if style != PlainTextStyle::Stripped =>
+                    res.push_str(&format!("{SPOILER_MARKER}{}{SPOILER_MARKER}", v.text)),
+                Val::Bold(v) if markup => res.push_str(&format!("*{}*", v.text)),
+                Val::Italic(v) if markup => res.push_str(&format!("_{}_", v.text)),
+                Val::Strikethrough(v) if markup => res.push_str(&format!("~{}~", v.text)),
+                Val::PrefmtInline(v) if markup => res.push_str(&format!("`{}`", v.text)),
+                Val::PrefmtBlock(v) if markup => {
+                    let language = v.language_option.as_deref().unwrap_or_default();
+                    res.push_str(&format!("```{language}\n{}\n```", v.text))
+                }
+                Val::Blockquote(v) if markup => {
+                    if !res.is_empty() && !res.ends_with('\n') {
+                        res.push('\n');
+                    }
+                    res.push_str(&v.text.split('\n').map(|line| format!("> {line}")).collect::<Vec<_>>().join("\n"))
+                }
+                _ => res.push_str(rte.get_text().unwrap()),
+            }
+        }
+        res
+    }
+
+    /// Parses a subset of Markdown common for messengers:
+    /// `**bold**`, `*italic*` or `_italic_`, `__underline__`, `~~strikethrough~~`, `||spoiler||` or `>!spoiler!<`,
+    /// `` `code` ``, fenced code blocks with optional language, `[text](href)` or `<href>` links,
+    /// and lines quoted with `>`.
+    /// Special characters can be escaped with a backslash, unmatched markers are kept as-is.
+    pub fn parse_markdown(src: &str) -> Vec<RichTextElement> {
+        MarkdownParser { chars: src.chars().collect(), pos: 0, plain: String::new(), result: vec![] }.parse()
+    }
+
+    /// Inverse of [RichText::parse_markdown].
+    pub fn to_markdown(rtes: &[RichTextElement]) -> String {
+        let mut res = String::new();
+        for rte in rtes {
+            match rte.val.as_ref().unwrap() {
+                Val::Link(v) => {
+                    match v.text_option {
+                        Some(ref text) if *text == v.href && !v.hidden && is_autolink_href(&v.href) => {
+                            res.push_str(&format!("<{}>", v.href))
+                        }
+                        ref text_option => {
+                            let text = text_option.as_deref().unwrap_or_default();
+                            res.push_str(&format!("[{}]({})", escape_markdown(text), v.href.replace(' ', "%20")))
+                        }
+                    }
+                }
+                _ if rte.get_text().is_none_or(|t| t.is_empty()) => { /* Nothing to render */ }
+                Val::Plain(RtePlain { text }) |
+                Val::Mention(RteMention { text, .. }) |
+                Val::Hashtag(RteHashtag { text, .. }) |
+                Val::CustomEmoji(RteCustomEmoji { text, .. }) => res.push_str(&escape_markdown(text)),
+                Val::Bold(v) => res.push_str(&format!("**{}**", escape_markdown(&v.text))),
+                Val::Italic(v) => {
+                    // Italic markers can't be adjacent to whitespace on the inner side
+                    let trimmed = v.text.trim();
+                    let leading = &v.text[..(v.text.len() - v.text.trim_start().len())];
+                    let trailing = &v.text[v.text.trim_end().len()..];
+                    if trimmed.is_empty() {
+                        res.push_str(&v.text)
+                    } else {
+                        res.push_str(&format!("{leading}*{}*{trailing}", escape_markdown(trimmed)))
+                    }
+                }
+                Val::Underline(v) => res.push_str(&format!("__{}__", escape_markdown(&v.text))),
+                Val::Strikethrough(v) => res.push_str(&format!("~~{}~~", escape_markdown(&v.text))),
+                Val::Spoiler(v) => res.push_str(&format!("||{}||", escape_markdown(&v.text))),
+                Val::PrefmtInline(v) => {
+                    let longest_run = v.text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+                    let fence = "`".repeat(longest_run + 1);
+                    let needs_padding = v.text.starts_with('`') || v.text.ends_with('`') ||
+                        (v.text.starts_with(' ') && v.text.ends_with(' ') && !v.text.trim().is_empty());
+                    let pad = if needs_padding { " " } else { "" };
+                    res.push_str(&format!("{fence}{pad}{}{pad}{fence}", v.text))
+                }
+                Val::PrefmtBlock(v) => {
+                    let language = v.language_option.as_deref().unwrap_or_default();
+                    res.push_str(&format!("```{language}\n{}\n```", v.text))
+                }
+                Val::Blockquote(v) => {
+                    if !res.is_empty() && !res.ends_with('\n') {
+                        res.push('\n');
+                    }
+                    res.push_str(&v.text.split('\n').map(|line| format!("> {}", escape_markdown(line))).collect::<Vec<_>>().join("\n"))
+                }
+            }
+        }
+        res
+    }
+
+    /// Parses HTML as used by messengers (e.g. Telegram Bot API):
+    /// `<b>`/`<strong>`, `<i>`/`<em>`, `<u>`/`<ins>`, `<s>`/`<strike>`/`<del>`, `<a href>`, `<code>`,
+    /// `<pre>` (with optional `<code class="language-xxx">` inside), `<blockquote>`,
+    /// `<tg-spoiler>` or `<span class="spoiler">`, and `<br>`.
+    /// Other tags are skipped leaving their text. Unlike browsers, whitespace is kept as-is.
+    pub fn parse_html(src: &str) -> Vec<RichTextElement> {
+        let mut parser = HtmlParser::default();
+        for token in tokenize_html(src) {
+            parser.accept(token);
+        }
+        parser.finish()
+    }
+
+    /// Inverse of [RichText::parse_html].
+    pub fn to_html(rtes: &[RichTextElement]) -> String {
+        let mut res = String::new();
+        for rte in rtes {
+            match rte.val.as_ref().unwrap() {
+                Val::Link(v) => {
+                    let text = match v.text_option {
+                        Some(ref text) => text.as_str(),
+                        None if v.hidden => "",
+                        None => v.href.as_str(),
+                    };
+                    res.push_str(&format!(r#"<a href="{}">{}</a>"#, escape_html(&v.href), escape_html(text)))
+                }
+                _ if rte.get_text().is_none_or(|t| t.is_empty()) => { /* Nothing to render */ }
+                Val::Plain(RtePlain { text }) |
+                Val::Mention(RteMention { text, .. }) |
+                Val::Hashtag(RteHashtag { text, .. }) |
+                Val::CustomEmoji(RteCustomEmoji { text, .. }) => res.push_str(&escape_html(text)),
+                Val::Bold(v) => res.push_str(&format!("<b>{}</b>", escape_html(&v.text))),
+                Val::Italic(v) => res.push_str(&format!("<i>{}</i>", escape_html(&v.text))),
+                Val::Underline(v) => res.push_str(&format!("<u>{}</u>", escape_html(&v.text))),
+                Val::Strikethrough(v) => res.push_str(&format!("<s>{}</s>", escape_html(&v.text))),
+                Val::Spoiler(v) => res.push_str(&format!(r#"<span class="spoiler">{}</span>"#, escape_html(&v.text))),
+                Val::PrefmtInline(v) => res.push_str(&format!("<code>{}</code>", escape_html(&v.text))),
+                Val::PrefmtBlock(v) => match v.language_option {
+                    Some(ref language) => res.push_str(&format!(r#"<pre><code class="language-{}">{}</code></pre>"#,
+                                                                escape_html(language), escape_html(&v.text))),
+                    None => res.push_str(&format!("<pre>{}</pre>", escape_html(&v.text))),
+                },
+                Val::Blockquote(v) => res.push_str(&format!("<blockquote>{}</blockquote>", escape_html(&v.text))),
+            }
+        }
+        res
+    }
+}
+
+/// Link with a blank text is considered hidden.
+fn make_link(text: String, href: String) -> RichTextElement {
+    let hidden = text.trim().is_empty();
+    RichText::make_link(Some(text).filter(|t| !t.is_empty()), href, hidden)
+}
+
+//
+// Markdown
+//
+
+struct MarkdownParser {
+    chars: Vec<char>,
+    pos: usize,
+    plain: String,
+    result: Vec<RichTextElement>,
+}
+
+impl MarkdownParser {
+    fn parse(mut self) -> Vec<RichTextElement> {
+        while self.pos < self.chars.len() {
+            if !self.try_parse_element() {
+                let (c, len) = self.char_at(self.pos);
+                self.plain.push(c);
+                self.pos += len;
+            }
+        }
+        self.flush_plain();
+        self.result
+    }
+
+    /// Character at the given position with escaping resolved, along with its length in source.
+    fn char_at(&self, pos: usize) -> (char, usize) {
+        match (self.chars[pos], self.chars.get(pos + 1)) {
+            ('\\', Some(c)) if MD_SPECIAL_CHARS.contains(c) => (*c, 2),
+            (c, _) => (c, 1),
+        }
+    }
+
+    fn starts_with(&self, pos: usize, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.chars.get(pos + i) == Some(&c))
+    }
+
+    fn is_at_line_start(&self) -> bool {
+        self.pos == 0 || self.chars[self.pos - 1] == '\n'
+    }
+
+    fn push(&mut self, rte: RichTextElement) {
+        self.flush_plain();
+        self.result.push(rte);
+    }
+
+    fn flush_plain(&mut self) {
+        if !self.plain.is_empty() {
+            self.result.push(RichText::make_plain(mem::take(&mut self.plain)));
+        }
+    }
+
+    /// Parses an element at the current position and advances past it, if there is one.
+    fn try_parse_element(&mut self) -> bool {
+        match self.chars[self.pos] {
+            '`' => self.parse_code(),
+            '>' if self.starts_with(self.pos, ">!") => self.try_parse_span(">!", "!<", RichText::make_spoiler),
+            '>' if self.is_at_line_start() => self.parse_blockquote(),
+            '[' => self.try_parse_link(),
+            '<' => self.try_parse_autolink(),
+            '*' if self.starts_with(self.pos, "**") => self.try_parse_span("**", "**", RichText::make_bold),
+            '*' => self.try_parse_span("*", "*", RichText::make_italic),
+            '_' if self.starts_with(self.pos, "__") => self.try_parse_span("__", "__", RichText::make_underline),
+            '_' => self.try_parse_span("_", "_", RichText::make_italic),
+            '~' if self.starts_with(self.pos, "~~") => self.try_parse_span("~~", "~~", RichText::make_strikethrough),
+            '|' if self.starts_with(self.pos, "||") => self.try_parse_span("||", "||", RichText::make_spoiler),
+            _ => false,
+        }
+    }
+
+    /// Single-character markers can't have whitespace on the inner side,
+    /// and underscores can't be used inside words, to not mangle `2 * 2 * 2` and `snake_case`.
+    fn try_parse_span(&mut self, open: &str, close: &str, make: fn(String) -> RichTextElement) -> bool {
+        let is_single = open.len() == 1;
+        let is_underscore = open == "_";
+        let start = self.pos + open.chars().count();
+        if is_underscore && self.pos > 0 && self.chars[self.pos - 1].is_alphanumeric() { return false; }
+        if is_single && self.chars.get(start).is_none_or(|c| c.is_whitespace()) { return false; }
+
+        let mut text = String::new();
+        let mut i = start;
+        while i < self.chars.len() {
+            let is_close = i > start && self.starts_with(i, close) &&
+                !(is_single && self.chars[i - 1].is_whitespace()) &&
+                !(is_underscore && self.chars.get(i + 1).is_some_and(|c| c.is_alphanumeric()));
+            if is_close {
+                self.push(make(text));
+                self.pos = i + close.chars().count();
+                return true;
+            }
+            let (c, len) = self.char_at(i);
+            text.push(c);
+            i += len;
+        }
+        false
+    }
+
+    /// Code block is fenced by triple backticks, optionally followed by language on the first line.
+    /// Code span is fenced by a run of backticks, which could be longer to allow backticks inside.
+    /// Unmatched backtick run is taken as a plain text.
+    fn parse_code(&mut self) -> bool {
+        let run_len = self.chars[self.pos..].iter().take_while(|c| **c == '`').count();
+        let start = self.pos + run_len;
+        if run_len == 3 {
+            if let Some(end) = (start..self.chars.len()).find(|i| self.starts_with(*i, "```")) {
+                let inner: String = self.chars[start..end].iter().collect();
+                let (language_option, text) = match inner.split_once('\n') {
+                    Some((first, text)) if first.chars().all(|c| c.is_alphanumeric() || "+-#_.".contains(c)) =>
+                        (Some(first.to_owned()).filter(|l| !l.is_empty()), text),
+                    _ => (None, inner.as_str()),
+                };
+                let text = text.strip_suffix('\n').unwrap_or(text);
+                self.push(RichText::make_prefmt_block(text.to_owned(), language_option));
+                self.pos = end + 3;
+                return true;
+            }
+        }
+
+        let mut i = start;
+        while i < self.chars.len() {
+            let closing_run_len = self.chars[i..].iter().take_while(|c| **c == '`').count();
+            if closing_run_len == run_len {
+                let mut text: String = self.chars[start..i].iter().collect();
+                if text.len() >= 2 && text.starts_with(' ') && text.ends_with(' ') && !text.trim().is_empty() {
+                    text = text[1..(text.len() - 1)].to_owned();
+                }
+                self.push(RichText::make_prefmt_inline(text));
+                self.pos = i + run_len;
+                return true;
+            }
+            i += closing_run_len.max(1);
+        }
+
+        self.plain.extend(iter::repeat_n('`', run_len));
+        self.pos = start;
+        true
+    }
+
+    /// Consecutive lines starting with `>`, the line break after the last one is left as a plain text.
+    fn parse_blockquote(&mut self) -> bool {
+        let mut lines = vec![];
+        let mut i = self.pos;
+        while self.chars.get(i) == Some(&'>') {
+            let line_end = self.chars[i..].iter().position(|c| *c == '\n').map(|p| i + p).unwrap_or(self.chars.len());
+            let mut j = if self.chars.get(i + 1) == Some(&' ') { i + 2 } else { i + 1 };
+            let mut line = String::new();
+            while j < line_end {
+                let (c, len) = self.char_at(j);
+                line.push(c);
+                j += len;
+            }
+            lines.push(line);
+            i = line_end;
+            if self.chars.get(i + 1) != Some(&'>') { break; }
+            i += 1;
+        }
+        self.push(RichText::make_blockquote(lines.join("\n")));
+        self.pos = i;
+        true
+    }
+
+    fn try_parse_link(&mut self) -> bool {
+        let mut text = String::new();
+        let mut i = self.pos + 1;
+        loop {
+            match self.chars.get(i) {
+                None => return false,
+                Some(']') => break,
+                Some(_) => {
+                    let (c, len) = self.char_at(i);
+                    text.push(c);
+                    i += len;
+                }
+            }
+        }
+        if self.chars.get(i + 1) != Some(&'(') { return false; }
+
+        // Href might contain balanced parentheses, e.g. in Wikipedia links
+        let href_start = i + 2;
+        let mut depth = 0;
+        let mut i = href_start;
+        loop {
+            match self.chars.get(i) {
+                None => return false,
+                Some(c) if c.is_whitespace() => return false,
+                Some(')') if depth == 0 => break,
+                Some(')') => depth -= 1,
+                Some('(') => depth += 1,
+                Some(_) => {}
+            }
+            i += 1;
+        }
+        let href: String = self.chars[href_start..i].iter().collect();
+        if href.is_empty() { return false; }
+        self.push(make_link(text, href));
+        self.pos = i + 1;
+        true
+    }
+
+    fn try_parse_autolink(&mut self) -> bool {
+        let start = self.pos + 1;
+        let Some(len) = self.chars[start..].iter().position(|c| *c == '>' || c.is_whitespace()) else { return false };
+        if self.chars[start + len] != '>' { return false; }
+        let href: String = self.chars[start..(start + len)].iter().collect();
+        if !is_autolink_href(&href) { return false; }
+        self.push(RichText::make_link(Some(href.clone()), href, false));
+        self.pos = start + len + 1;
+        true
+    }
+}
+
+fn is_autolink_href(href: &str) -> bool {
+    !href.is_empty() && !href.contains(char::is_whitespace) && (href.contains("://") || href.starts_with("mailto:"))
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        if MD_SPECIAL_CHARS.contains(&c) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+//
+// HTML
+//
+
+#[derive(Debug, Clone, PartialEq)]
+enum HtmlToken {
+    Text(String),
+    Open { name: String, attrs: Vec<(String, String)> },
+    Close { name: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum HtmlStyle {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
+    Code,
+    Pre,
+    Blockquote,
+    Link { href: String },
+}
+
+impl HtmlStyle {
+    fn of(name: &str, attrs: &[(String, String)]) -> Option<HtmlStyle> {
+        let attr = |key: &str| attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        match name {
+            "b" | "strong" => Some(HtmlStyle::Bold),
+            "i" | "em" => Some(HtmlStyle::Italic),
+            "u" | "ins" => Some(HtmlStyle::Underline),
+            "s" | "strike" | "del" => Some(HtmlStyle::Strikethrough),
+            "tg-spoiler" => Some(HtmlStyle::Spoiler),
+            "span" if attr("class").is_some_and(|c| c.split_whitespace().any(|c| c.ends_with("spoiler"))) =>
+                Some(HtmlStyle::Spoiler),
+            "code" => Some(HtmlStyle::Code),
+            "pre" => Some(HtmlStyle::Pre),
+            "blockquote" => Some(HtmlStyle::Blockquote),
+            "a" => attr("href").map(|href| HtmlStyle::Link { href: href.to_owned() }),
+            _ => None,
+        }
+    }
+}
+
+/// Formatting tag currently open, along with a nesting depth of same-named tags within it.
+#[derive(Debug)]
+struct OpenHtmlStyle {
+    style: HtmlStyle,
+    name: String,
+    depth: usize,
+    language_option: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct HtmlParser {
+    open_option: Option<OpenHtmlStyle>,
+    text: String,
+    result: Vec<RichTextElement>,
+}
+
+impl HtmlParser {
+    fn accept(&mut self, token: HtmlToken) {
+        match token {
+            HtmlToken::Text(text) => self.text.push_str(&text),
+            HtmlToken::Open { name, .. } if name == "br" => self.text.push('\n'),
+            HtmlToken::Open { name, attrs } => match self.open_option {
+                Some(ref mut open) => {
+                    if name == open.name {
+                        open.depth += 1;
+                    } else if open.style == HtmlStyle::Pre && name == "code" {
+                        open.language_option = attrs.iter()
+                            .find(|(k, _)| k == "class")
+                            .and_then(|(_, v)| v.split_whitespace().find_map(|c| c.strip_prefix("language-")))
+                            .map(|l| l.to_owned());
+                    }
+                }
+                None => {
+                    if let Some(style) = HtmlStyle::of(&name, &attrs) {
+                        self.flush();
+                        self.open_option = Some(OpenHtmlStyle { style, name, depth: 0, language_option: None });
+                    }
+                }
+            },
+            HtmlToken::Close { name } => {
+                if let Some(ref mut open) = self.open_option {
+                    if name == open.name {
+                        if open.depth == 0 {
+                            self.flush();
+                        } else {
+                            open.depth -= 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Unclosed tag is treated as if it was closed at the end.
+    fn finish(mut self) -> Vec<RichTextElement> {
+        self.flush();
+        self.result
+    }
+
+    /// Adds accumulated text as an element of a currently open style (if any) and closes it.
+    fn flush(&mut self) {
+        let text = mem::take(&mut self.text);
+        let rte_option = match self.open_option.take() {
+            Some(OpenHtmlStyle { style: HtmlStyle::Link { href }, .. }) => Some(make_link(text, href)),
+            _ if text.is_empty() => None,
+            None => Some(RichText::make_plain(text)),
+            Some(open) => Some(match open.style {
+                HtmlStyle::Bold => RichText::make_bold(text),
+                HtmlStyle::Italic => RichText::make_italic(text),
+                HtmlStyle::Underline => RichText::make_underline(text),
+                HtmlStyle::Strikethrough => RichText::make_strikethrough(text),
+                HtmlStyle::Spoiler => RichText::make_spoiler(text),
+                HtmlStyle::Code => RichText::make_prefmt_inline(text),
+                HtmlStyle::Pre => RichText::make_prefmt_block(text, open.language_option),
+                HtmlStyle::Blockquote => RichText::make_blockquote(text),
+                HtmlStyle::Link { .. } => unreachable!(),
+            }),
+        };
+        self.result.extend(rte_option);
+    }
+}
+
+fn tokenize_html(src: &str) -> Vec<HtmlToken> {
+    let mut tokens = vec![];
+    let mut text = String::new();
+    let mut rest = src;
+    while let Some(idx) = rest.find('<') {
+        text.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        match parse_html_tag(rest) {
+            Some((token_option, len)) => {
+                if !text.is_empty() {
+                    tokens.push(HtmlToken::Text(decode_html_entities(&mem::take(&mut text))));
+                }
+                tokens.extend(token_option);
+                rest = &rest[len..];
+            }
+            None => {
+                // Not a tag, just a stray less-than sign
+                text.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(HtmlToken::Text(decode_html_entities(&text)));
+    }
+    tokens
+}
+
+/// Parses a tag at the start of a string, returns a token (none for comments and doctypes)
+/// and the length of the tag source.
+fn parse_html_tag(src: &str) -> Option<(Option<HtmlToken>, usize)> {
+    if src.starts_with("<!--") {
+        let len = src.find("-->").map(|i| i + 3).unwrap_or(src.len());
+        return Some((None, len));
+    }
+
+    let mut quote_option = None;
+    let end = src.char_indices().skip(1).find(|&(_, c)| match quote_option {
+        Some(q) => {
+            if c == q { quote_option = None; }
+            false
+        }
+        None if c == '"' || c == '\'' => {
+            quote_option = Some(c);
+            false
+        }
+        None => c == '>',
+    }).map(|(i, _)| i)?;
+
+    let body = &src[1..end];
+    if body.starts_with('!') || body.starts_with('?') {
+        return Some((None, end + 1));
+    }
+    let (is_closing, body) = match body.strip_prefix('/') {
+        Some(body) => (true, body),
+        None => (false, body),
+    };
+    if !body.starts_with(|c: char| c.is_ascii_alphabetic()) { return None; }
+    let name_len = body.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-')).unwrap_or(body.len());
+    let name = body[..name_len].to_ascii_lowercase();
+    let token = if is_closing {
+        HtmlToken::Close { name }
+    } else {
+        HtmlToken::Open { name, attrs: parse_html_attrs(&body[name_len..]) }
+    };
+    Some((Some(token), end + 1))
+}
+
+fn parse_html_attrs(src: &str) -> Vec<(String, String)> {
+    let mut attrs = vec![];
+    let mut chars = src.trim_end_matches('/').chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == '=').is_some() {}
+        let key: String = iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != '=')).collect();
+        if key.is_empty() { break; }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let value = if chars.next_if_eq(&'=').is_some() {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next_if(|c| *c == '"' || *c == '\'') {
+                Some(q) => {
+                    let value: String = iter::from_fn(|| chars.next_if(|c| *c != q)).collect();
+                    chars.next();
+                    value
+                }
+                None => iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())).collect(),
+            }
+        } else {
+            String::new()
+        };
+        attrs.push((key.to_ascii_lowercase(), decode_html_entities(&value)));
+    }
+    attrs
+}
+
+fn decode_html_entities(src: &str) -> String {
+    let mut res = String::with_capacity(src.len());
+    let mut rest = src;
+    while let Some(idx) = rest.find('&') {
+        res.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let decoded_option = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                code if code.starts_with("#x") || code.starts_with("#X") =>
+                    u32::from_str_radix(&code[2..], 16).ok().and_then(char::from_u32),
+                code if code.starts_with('#') =>
+                    code[1..].parse::<u32>().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            c.map(|c| (c, end + 1))
+        });
+        match decoded_option {
+            Some((c, len)) => {
+                res.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,29 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chat_history_manager_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+/// Headers are prepended as-is, otherwise the loader rejects the file right away.
+#[derive(Arbitrary, Debug)]
+enum CsvSource {
+    RedditMessages,
+    RedditChatHistory,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    source: CsvSource,
+    rows: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let (file_name, header): (&str, &[u8]) = match input.source {
+        CsvSource::RedditMessages =>
+            ("messages.csv", b"id,permalink,thread_id,date,ip,from,to,subject,body\n"),
+        CsvSource::RedditChatHistory =>
+            ("chat_history.csv", b"message_id,created_at,updated_at,username,message,thread_parent_message_id,channel_url,subreddit,channel_name,conversation_type\n"),
+    };
+    let dir = ScratchDir::new();
+    parse(&dir.write(file_name, &[header, &input.rows].concat()));
+});
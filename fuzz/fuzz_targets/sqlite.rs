@@ -0,0 +1,29 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chat_history_manager_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+/// Tinder is left out since it downloads media over network.
+#[derive(Arbitrary, Debug)]
+enum SqliteSource {
+    WhatsAppAndroid,
+    Signal,
+    Badoo,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    source: SqliteSource,
+    db: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let file_name = match input.source {
+        SqliteSource::WhatsAppAndroid => "msgstore.db",
+        SqliteSource::Signal => "plaintext.sqlite",
+        SqliteSource::Badoo => "ChatComDatabase",
+    };
+    let dir = ScratchDir::new();
+    parse(&dir.write(file_name, &input.db));
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use chat_history_manager_core::parsing::telegram::preview_telegram_export;
+use chat_history_manager_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(json) = std::str::from_utf8(data) {
+        let _ = preview_telegram_export(json);
+    }
+
+    let dir = ScratchDir::new();
+    parse(&dir.write("result.json", data));
+});
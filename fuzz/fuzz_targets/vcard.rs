@@ -0,0 +1,8 @@
+#![no_main]
+
+use chat_history_manager_core::parsing::vcard::parse_vcard;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|vcard: &str| {
+    let _ = parse_vcard(vcard);
+});
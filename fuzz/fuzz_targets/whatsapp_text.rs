@@ -0,0 +1,9 @@
+#![no_main]
+
+use chat_history_manager_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let dir = ScratchDir::new();
+    parse(&dir.write("WhatsApp Chat with Fuzz.txt", data));
+});
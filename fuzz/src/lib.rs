@@ -0,0 +1,39 @@
+//! Helpers shared by fuzz targets. Loaders work on files, so inputs are written to a scratch directory first.
+//! Errors are expected on malformed input, panics are what the fuzzer is looking for.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chat_history_manager_backend::prelude::client::NoChooser;
+
+pub struct ScratchDir {
+    pub path: PathBuf,
+}
+
+impl ScratchDir {
+    /// Directory is unique per process, as libFuzzer might run several jobs in parallel.
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("chm-fuzz_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Can't create scratch directory");
+        ScratchDir { path }
+    }
+
+    pub fn write(&self, rel_path: &str, content: &[u8]) -> PathBuf {
+        let path = self.path.join(rel_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Run the whole loading pipeline on a given file, the same way the server does.
+pub fn parse(path: &Path) {
+    let _ = chat_history_manager_backend::parse_file(path.to_str().unwrap(), &NoChooser);
+}
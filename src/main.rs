@@ -1,4 +1,5 @@
 use std::future::Future;
+use std::path::Path;
 
 use clap::{Parser, Subcommand};
 use deepsize::DeepSizeOf;
@@ -18,6 +19,18 @@ struct Args {
     /// Next port will be used for the user info request server.
     port: Option<u16>,
 
+    /// Never make network requests, e.g. to download media referenced by parsed histories
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Compress gRPC messages sent to the other side, either `gzip` or `zstd`
+    #[arg(long, global = true)]
+    grpc_compression: Option<GrpcCompression>,
+
+    /// Max size of a single gRPC message, in megabytes
+    #[arg(long, global = true, default_value_t = GrpcConfig::default().max_message_size / 1024 / 1024)]
+    grpc_max_message_size_mb: usize,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -27,7 +40,38 @@ const DEFAULT_SERVER_PORT: u16 = 50051;
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Start a gRPC server on the given port
-    StartServer,
+    StartServer {
+        /// JSON file with access profiles (names, tokens and visible datasets), makes the server require
+        /// an access token with every request
+        #[arg(long)]
+        access_profiles: Option<String>,
+        /// How many requests per second a single client (profile or IP address) can make, unlimited by default
+        #[arg(long)]
+        rate_limit: Option<u32>,
+        /// How many requests a single client can make in a quick succession before the rate limit kicks in
+        #[arg(long, default_value_t = RequestLimits::default().burst)]
+        rate_limit_burst: u32,
+        /// Max size of a single incoming request, in megabytes, defaults to max gRPC message size
+        #[arg(long)]
+        max_request_size_mb: Option<usize>,
+        /// How long deleted datasets and chats are kept in trash before being removed permanently
+        #[arg(long, default_value_t = DEFAULT_TRASH_RETENTION_DAYS)]
+        trash_retention_days: u32,
+        /// Recognize text on photos of parsed histories to make it searchable, either `tesseract`
+        /// (optionally with languages, e.g. `tesseract:eng+rus`) or a URL of an OCR service accepting image bytes
+        #[arg(long)]
+        ocr: Option<String>,
+        /// Command tagging media files on request (e.g. a face recognizer), invoked with a file path appended
+        /// and expected to print one tag per line
+        #[arg(long)]
+        media_annotator: Option<String>,
+        /// Proxy for outgoing HTTP requests (e.g. media downloads), e.g. `socks5://127.0.0.1:9050`
+        #[arg(long)]
+        http_proxy: Option<String>,
+        /// How many outgoing HTTP requests can be made at once
+        #[arg(long, default_value_t = HttpClientConfig::default().max_concurrent_requests)]
+        http_max_concurrent_requests: usize,
+    },
     /// (For debugging purposes only) Parse and load a given file using whichever loader is appropriate,
     /// and print the result in-memory DB size to the log
     Parse {
@@ -44,12 +88,23 @@ async fn main() {
     init_logger();
 
     let args = Args::parse();
-    catch_fatal_error(execute_command(args.command, args.port).await)
+    let grpc_config = GrpcConfig {
+        compression_option: args.grpc_compression,
+        max_message_size: args.grpc_max_message_size_mb * 1024 * 1024,
+    };
+    catch_fatal_error(execute_command(args.command, args.port, args.offline, grpc_config).await)
 }
 
-async fn execute_command(command: Option<Command>, port: Option<u16>) -> EmptyRes {
+async fn execute_command(command: Option<Command>, port: Option<u16>, offline: bool, grpc_config: GrpcConfig) -> EmptyRes {
     let port = port.unwrap_or(DEFAULT_SERVER_PORT);
     let remote_port = port + 1;
+    let mut http_client_config = HttpClientConfig { offline, ..Default::default() };
+    if let Some(Command::StartServer { ref http_proxy, http_max_concurrent_requests, .. }) = command {
+        http_client_config.proxy_option = http_proxy.clone();
+        http_client_config.max_concurrent_requests = http_max_concurrent_requests;
+    }
+    configure_shared_http_client(http_client_config)?;
+    configure_grpc(grpc_config)?;
     match command {
         None => {
             if cfg!(not(feature = "ui-core")) {
@@ -63,7 +118,7 @@ async fn execute_command(command: Option<Command>, port: Option<u16>) -> EmptyRe
                 let handle = Handle::current();
                 // Start a server if not already running
                 spawn_server(&handle, "Server", port, async move {
-                    start_server(port, remote_port).await
+                    start_server(port, remote_port, None, RequestLimits::default(), DEFAULT_TRASH_RETENTION_DAYS, None, None).await
                 });
                 let clients = client::create_clients(port).await?;
                 let ui = chat_history_manager_ui::create_ui(clients, port);
@@ -75,8 +130,17 @@ async fn execute_command(command: Option<Command>, port: Option<u16>) -> EmptyRe
                 ui.start_and_block()
             }
         }
-        Some(Command::StartServer) => {
-            start_server(port, remote_port).await?;
+        Some(Command::StartServer {
+                 access_profiles, rate_limit, rate_limit_burst, max_request_size_mb,
+                 trash_retention_days, ocr, media_annotator, ..
+             }) => {
+            let request_limits = RequestLimits {
+                requests_per_sec_option: rate_limit,
+                burst: rate_limit_burst,
+                max_request_size_option: max_request_size_mb.map(|mb| mb * 1024 * 1024),
+            };
+            start_server(port, remote_port, access_profiles.as_deref().map(Path::new), request_limits,
+                         trash_retention_days, ocr.as_deref(), media_annotator.as_deref()).await?;
         }
         Some(Command::Parse { path, myself_id }) => {
             let handle = Handle::current();
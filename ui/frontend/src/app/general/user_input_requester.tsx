@@ -1,7 +1,8 @@
 import React from "react";
 
 import { User } from "@/protobuf/core/protobuf/entities";
-import { AssertUnreachable, PromiseCatchReportError } from "@/app/utils/utils";
+import { PreviousImport, ReimportAction } from "@/protobuf/backend/protobuf/services";
+import { AssertUnreachable, PromiseCatchReportError, TimestampToString } from "@/app/utils/utils";
 import { GetUserPrettyName } from "@/app/utils/entity_utils";
 
 import {
@@ -22,6 +23,10 @@ export type UserInputRequestState = {
 } | {
   $case: "ask_for_text"
   prompt: string
+} | {
+  $case: "choose_reimport_action"
+  sourcePath: string,
+  previousImports: Array<PreviousImport>
 }
 
 export default function UserInputRequsterComponent(args: {
@@ -38,6 +43,11 @@ export default function UserInputRequsterComponent(args: {
     args.setState(null)
   }, [args])
 
+  let onReimportActionChosen = React.useCallback((action: ReimportAction, previousImportIdx: number) => {
+    PromiseCatchReportError(emit("choose-reimport-action-response", [action, previousImportIdx]))
+    args.setState(null)
+  }, [args])
+
   let state = args.state
 
   return (
@@ -52,6 +62,10 @@ export default function UserInputRequsterComponent(args: {
               return <ChooseMyselfDialog users={state.users} onUserSelected={onUserSelected}/>
             case "ask_for_text":
               return <AskForTextDialog prompt={state.prompt} onSubmit={onTextSubmited}/>
+            case "choose_reimport_action":
+              return <ChooseReimportActionDialog sourcePath={state.sourcePath}
+                                                 previousImports={state.previousImports}
+                                                 onActionChosen={onReimportActionChosen}/>
             default:
               AssertUnreachable(state)
           }
@@ -112,3 +126,41 @@ function AskForTextDialog(args: {
   </>
 }
 
+
+function ChooseReimportActionDialog(args: {
+  sourcePath: string,
+  previousImports: Array<PreviousImport>,
+  onActionChosen: (action: ReimportAction, previousImportIdx: number) => void
+}) {
+  return <>
+    <DialogHeader>
+      <DialogTitle>Already imported</DialogTitle>
+      <DialogDescription>
+        {args.sourcePath} was already imported, what should be done with it?
+      </DialogDescription>
+    </DialogHeader>
+    {args.previousImports.map((prev, idx) =>
+      <div key={idx} className="flex flex-col gap-2">
+        <p>
+          Dataset &apos;{prev.dataset?.alias}&apos; of {prev.daoName}
+          {prev.batch ? `, imported ${TimestampToString(prev.batch.importTimestamp, false)}` : ""}
+        </p>
+        <DialogFooter>
+          <Button variant="secondary"
+                  onClick={() => args.onActionChosen(ReimportAction.SKIP, idx)}>
+            Open the database
+          </Button>
+          <Button variant="secondary"
+                  onClick={() => args.onActionChosen(ReimportAction.APPEND_SYNC, idx)}>
+            Append new messages
+          </Button>
+        </DialogFooter>
+      </div>)
+    }
+    <DialogFooter>
+      <Button onClick={() => args.onActionChosen(ReimportAction.NEW_DATASET, 0)}>
+        Load as a new dataset
+      </Button>
+    </DialogFooter>
+  </>
+}
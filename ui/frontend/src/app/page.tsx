@@ -37,6 +37,7 @@ import { cn } from "@/lib/utils";
 import { PbUuid, User } from "@/protobuf/core/protobuf/entities";
 import {
   ChatWithDetailsPB,
+  ChooseReimportActionRequest,
   EnsureSameRequest,
   EnsureSameResponse,
   MergeRequest
@@ -155,6 +156,14 @@ export default function Home() {
         let prompt = ev.payload
         setUserInputRequestState({ $case: "ask_for_text", prompt })
       }),
+      Listen<Uint8Array>(BackendEvents.ChooseReimportAction, (ev) => {
+        let request = ChooseReimportActionRequest.decode(ev.payload)
+        setUserInputRequestState({
+          $case: "choose_reimport_action",
+          sourcePath: request.sourcePath,
+          previousImports: request.previousImports
+        })
+      }),
       Listen<Uint8Array>(BackendEvents.CompareDatasetsFinished, (ev) => {
         let payload = ev.payload
         let response = EnsureSameResponse.decode(payload)
@@ -373,6 +382,7 @@ const BackendEvents = {
   MergeDatasetsClicked: "merge-datasets-clicked" as AppEvent,
   ChooseMyself: "choose-myself" as AppEvent,
   AskForText: "ask-for-text" as AppEvent,
+  ChooseReimportAction: "choose-reimport-action" as AppEvent,
 }
 
 async function LoadExistingData(
@@ -72,6 +72,9 @@ static EVENT_CHOOSE_MYSELF_RESPONSE: &str = "choose-myself-response";
 static EVENT_ASK_FOR_TEXT: &str = "ask-for-text";
 static EVENT_ASK_FOR_TEXT_RESPONSE: &str = "ask-for-text-response";
 
+static EVENT_CHOOSE_REIMPORT_ACTION: &str = "choose-reimport-action";
+static EVENT_CHOOSE_REIMPORT_ACTION_RESPONSE: &str = "choose-reimport-action-response";
+
 #[derive(Clone, Debug)]
 pub struct TauriUiWrapper {
     state: Arc<Mutex<TauriInnerState>>
@@ -243,6 +246,24 @@ impl UserInputRequester for TauriUserInputRequester {
         let result = selection_rx.await?;
         Ok(result)
     }
+
+    async fn choose_reimport_action(&self, source_path: &str, previous_imports: &[PreviousImport]) -> Result<(ReimportAction, usize)> {
+        use prost::Message;
+        let request = ChooseReimportActionRequest {
+            source_path: source_path.to_owned(),
+            previous_imports: previous_imports.to_vec(),
+        };
+        self.app_handle.emit(EVENT_CHOOSE_REIMPORT_ACTION, request.encode_to_vec())?;
+        let (selection_tx, selection_rx) = oneshot::channel::<(i32, usize)>();
+        self.app_handle.once(EVENT_CHOOSE_REIMPORT_ACTION_RESPONSE, |ev| {
+            let selection = serde_json::from_str(ev.payload()).expect("choose reimport action payload");
+            selection_tx.send(selection).expect("send selection");
+        });
+        let (action, idx) = selection_rx.await?;
+        let action = ReimportAction::try_from(action).map_err(|_| anyhow!("Unknown re-import action {action}"))?;
+        ensure!(idx < previous_imports.len(), "Previous import #{idx} does not exist");
+        Ok((action, idx))
+    }
 }
 
 //
@@ -334,7 +355,7 @@ async fn on_menu_event_open(
             let _wip = WorkInProgress::start(app_handle.clone(), busy_state.inner().clone(), Cow::Borrowed("Opening..."))?;
             let path = path_to_str(&picked)?.to_owned();
             let key = path.clone();
-            let _response = clients.grpc(|loader, _, _| loader.load(LoadRequest { key, path })).await?;
+            let _response = clients.grpc(|loader, _, _| loader.load(LoadRequest { key, path, media_policy: None })).await?;
             refresh_opened_files_list(app_handle, clients, true).await?;
         }
         _ => { /* No file picked */ }